@@ -12,28 +12,37 @@ LINKER_SCRIPT="$ROOT_DIR/targets/linker.ld"
 export RUSTFLAGS="-C link-arg=-T$LINKER_SCRIPT"
 BUILD_CMD="cargo build --release --target $TARGET_JSON -Z build-std=core,alloc -Z build-std-features=compiler-builtins-mem -Z json-target-spec"
 
-echo "[1/7] Building Init Orchestrator (init)..."
+echo "[1/11] Building Init Orchestrator (init)..."
 (cd apps/init && $BUILD_CMD)
 
-echo "[2/7] Building Window Server (compositor)..."
+echo "[2/11] Building Window Server (compositor)..."
 (cd apps/compositor && $BUILD_CMD)
 
-echo "[3/7] Building Terminal App (terminal)..."
+echo "[3/11] Building Terminal App (terminal)..."
 (cd apps/terminal && $BUILD_CMD)
 
-echo "[4/7] Building Settings App (settings)..."
+echo "[4/11] Building Settings App (settings)..."
 (cd apps/settings && $BUILD_CMD)
 
-echo "[5/7] Building Explorer Suite (explorer)..."
+echo "[5/11] Building Explorer Suite (explorer)..."
 (cd apps/explorer && $BUILD_CMD)
 
-echo "[6/7] Building Network Suite (network)..."
+echo "[6/11] Building Network Suite (network)..."
 (cd apps/network && $BUILD_CMD)
 
-echo "[7/7] Building System Monitor (sysmon)..."
+echo "[7/11] Building System Monitor (sysmon)..."
 (cd apps/sysmon && $BUILD_CMD)
 
-echo "[8/8] Generating App Tarball..."
+echo "[8/11] Building On-Screen Keyboard (osk)..."
+(cd apps/osk && $BUILD_CMD)
+
+echo "[9/11] Building Task Manager (taskmgr)..."
+(cd apps/taskmgr && $BUILD_CMD)
+
+echo "[10/11] Building Image Viewer (imageviewer)..."
+(cd apps/imageviewer && $BUILD_CMD)
+
+echo "[11/11] Generating App Tarball..."
 rm -rf build_initrd
 
 # 1. Create the App Folders
@@ -44,6 +53,9 @@ mkdir -p build_initrd/apps/Settings.nyx
 mkdir -p build_initrd/apps/Explorer.nyx
 mkdir -p build_initrd/apps/Network.nyx
 mkdir -p build_initrd/apps/SystemMonitor.nyx
+mkdir -p build_initrd/apps/Keyboard.nyx
+mkdir -p build_initrd/apps/TaskManager.nyx
+mkdir -p build_initrd/apps/ImageViewer.nyx
 
 # 2. Copy the compiled binaries into the folders as 'run.bin'
 # Notice the binary name for WindowServer is now 'compositor'
@@ -54,6 +66,9 @@ cp target/x86_64-nyx/release/nyx-settings build_initrd/apps/Settings.nyx/run.bin
 cp target/x86_64-nyx/release/nyx-explorer build_initrd/apps/Explorer.nyx/run.bin
 cp target/x86_64-nyx/release/nyx-network build_initrd/apps/Network.nyx/run.bin
 cp target/x86_64-nyx/release/nyx-sysmon build_initrd/apps/SystemMonitor.nyx/run.bin
+cp target/x86_64-nyx/release/nyx-osk build_initrd/apps/Keyboard.nyx/run.bin
+cp target/x86_64-nyx/release/nyx-taskmgr build_initrd/apps/TaskManager.nyx/run.bin
+cp target/x86_64-nyx/release/nyx-imageviewer build_initrd/apps/ImageViewer.nyx/run.bin
 
 # 3. Copy any JSON manifests from the source folders into the App Bundles
 cp apps/init/*.json build_initrd/apps/Init.nyx/ 2>/dev/null || true
@@ -63,6 +78,9 @@ cp apps/settings/*.json build_initrd/apps/Settings.nyx/ 2>/dev/null || true
 cp apps/explorer/*.json build_initrd/apps/Explorer.nyx/ 2>/dev/null || true
 cp apps/network/*.json build_initrd/apps/Network.nyx/ 2>/dev/null || true
 cp apps/sysmon/*.json build_initrd/apps/SystemMonitor.nyx/ 2>/dev/null || true
+cp apps/osk/*.json build_initrd/apps/Keyboard.nyx/ 2>/dev/null || true
+cp apps/taskmgr/*.json build_initrd/apps/TaskManager.nyx/ 2>/dev/null || true
+cp apps/imageviewer/*.json build_initrd/apps/ImageViewer.nyx/ 2>/dev/null || true
 
 # 4. Package it into a lightweight tape archive
 cd build_initrd
@@ -0,0 +1,114 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use nyx_api::*;
+use nyx_gui::canvas::Canvas;
+use nyx_gui::theme;
+use nyx_gui::icons::{draw_file_icon, FileKind, ICON_SIZE};
+use nyx_gui::ui::taskbar_h;
+
+// ─────────────────────────────────────────────────────────────────────────
+// DESKTOP ICONS
+// Entries of the desktop directory laid out column by column from the
+// top-left corner. Positions are recomputed from the index, so drawing and
+// hit-testing always agree.
+// ─────────────────────────────────────────────────────────────────────────
+pub const DESKTOP_DIR: &str = "/mnt/nvme";
+
+const ORIGIN_X: usize = 20;
+const ORIGIN_Y: usize = 20;
+const CELL_W: usize = 90;
+const CELL_H: usize = 76;
+
+pub struct DesktopIcon {
+    pub name: String,
+    pub kind: FileKind,
+}
+
+pub enum DesktopAction {
+    None,
+    Redraw,
+    Open(usize),
+}
+
+pub struct DesktopIcons {
+    pub icons: Vec<DesktopIcon>,
+    pub selected: Option<usize>,
+    screen_h: usize,
+}
+
+impl DesktopIcons {
+    pub fn new(screen_h: usize) -> Self {
+        let mut desktop = Self { icons: Vec::new(), selected: None, screen_h };
+        desktop.reload();
+        desktop
+    }
+
+    pub fn reload(&mut self) {
+        self.icons.clear();
+        self.selected = None;
+        for i in 0..sys_fs_count(DESKTOP_DIR).unwrap_or(0) {
+            let mut buf = [0u8; 256];
+            let len = match sys_fs_get_name(DESKTOP_DIR, i, &mut buf) { Ok(len) => len, Err(_) => break };
+            if let Ok(name) = core::str::from_utf8(&buf[..len]) {
+                if name.is_empty() { continue; }
+                // Directory names come back with a trailing '/'
+                self.icons.push(DesktopIcon { name: String::from(name.trim_end_matches('/')), kind: FileKind::classify(name) });
+            }
+        }
+    }
+
+    /// Lays the icons out again for a screen `screen_h` tall.
+    pub fn resize(&mut self, screen_h: usize) {
+        self.screen_h = screen_h;
+    }
+
+    pub fn path_of(&self, idx: usize) -> String {
+        alloc::format!("{}/{}", DESKTOP_DIR, self.icons[idx].name)
+    }
+
+    /// (x, y, w, h) of the cell for icon `idx`.
+    pub fn bounds(&self, idx: usize) -> (usize, usize, usize, usize) {
+        let rows = (self.screen_h.saturating_sub(taskbar_h() + ORIGIN_Y) / CELL_H).max(1);
+        (ORIGIN_X + (idx / rows) * CELL_W, ORIGIN_Y + (idx % rows) * CELL_H, CELL_W - 10, CELL_H - 6)
+    }
+
+    pub fn hit_test(&self, mx: usize, my: usize) -> Option<usize> {
+        (0..self.icons.len()).find(|&i| {
+            let (x, y, w, h) = self.bounds(i);
+            mx >= x && mx < x + w && my >= y && my < y + h
+        })
+    }
+
+    /// Single click selects (or clears on empty desktop); the second press of
+    /// a double-click on the selected icon opens it.
+    pub fn on_click(&mut self, mx: usize, my: usize, double: bool) -> DesktopAction {
+        let hit = self.hit_test(mx, my);
+        if let Some(idx) = hit {
+            if double && hit == self.selected { return DesktopAction::Open(idx); }
+        }
+        if hit == self.selected { return DesktopAction::None; }
+        self.selected = hit;
+        DesktopAction::Redraw
+    }
+
+    pub fn draw(&self, canvas: &mut Canvas) {
+        let theme = theme::current();
+        for (i, icon) in self.icons.iter().enumerate() {
+            let (x, y, w, h) = self.bounds(i);
+            if y + h > self.screen_h.saturating_sub(taskbar_h()) { continue; }
+
+            if self.selected == Some(i) { canvas.fill_rect(x, y, w, h, 0x60_000000 | (theme.accent & 0x00FF_FFFF)); }
+
+            let ix = x + (w - ICON_SIZE) / 2; let iy = y + 6;
+            draw_file_icon(canvas, ix, iy, icon.kind);
+
+            // Clip the label to the cell width
+            let max_chars = w / 8;
+            let end = icon.name.char_indices().nth(max_chars).map(|(i, _)| i).unwrap_or(icon.name.len());
+            let label = &icon.name[..end];
+            let lx = x + (w.saturating_sub(label.chars().count() * 8)) / 2;
+            canvas.print_str(lx, iy + ICON_SIZE + 8, label, theme.text_primary, 1);
+        }
+    }
+}
@@ -2,16 +2,32 @@
 #![no_main]
 extern crate alloc;
 
-use linked_list_allocator::LockedHeap;
+use alloc::string::String;
 use alloc::vec::Vec;
 use alloc::vec;
 
+mod desktop;
+use desktop::{DesktopAction, DesktopIcons};
+mod switcher;
+use switcher::WindowSwitcher;
+mod session;
+use session::{Geometry, Session};
+
 use nyx_api::*;
-use nyx_gui::canvas::{Canvas, Color};
-use nyx_gui::ui::{draw_taskbar, draw_window_rounded, draw_cursor, Window, CursorType};
+use nyx_gui::heap::BrkHeap;
+use nyx_core::config::{parse_resolution, resolution_value, CONFIG_PATH, RESOLUTION_KEY};
+use nyx_core::pointer::{self, MouseButton, PointerEvent, PointerTracker};
+use nyx_core::rect::{self, DirtyRegion};
+use nyx_gui::canvas::Canvas;
+use nyx_gui::draw::restore_wallpaper_rect;
+use nyx_gui::pixel::{present_rect, present_scaled, scaled_rect, Frame, Framebuffer};
+use nyx_gui::scale;
+use nyx_gui::theme::{self, ACCENTS};
+use nyx_gui::wallpaper::{Wallpaper, DEFAULT_WALLPAPER};
+use nyx_gui::ui::{draw_taskbar, draw_window_rounded, draw_window_shadow, draw_cursor, taskbar_button_x, window_bounds, StartMenu, StartMenuAction, Window, CursorType, taskbar_h, taskbar_btn_w, taskbar_btn_h, title_bar_h};
 
 #[global_allocator]
-static ALLOCATOR: LockedHeap = LockedHeap::empty();
+static ALLOCATOR: BrkHeap = BrkHeap::empty();
 
 pub struct WindowClient {
     pub win: Window,
@@ -21,8 +37,225 @@ pub struct WindowClient {
     pub buf_w: usize,
     pub buf_h: usize,
     pub gpu_gva: u32,
+    // The binary it was launched from, when the window server launched it
+    pub app: String,
+    // The size the app asked for, which "Reset Layout" goes back to
+    pub default_w: usize, pub default_h: usize,
+    // WIN_FLAG_NO_FOCUS: never focused, and kept above the other windows
+    pub no_focus: bool,
+}
+
+// Smallest size a window can be dragged or restored to
+fn min_win_w() -> usize { scale::px(200) }
+fn min_win_h() -> usize { scale::px(100) }
+const EXPLORER_PATH: &str = "/mnt/nvme/apps/Explorer.nyx/run.bin\0";
+const EXPLORER_TITLE: &str = "Nyx Explorer Suite";
+
+// Start menu entries whose path starts with this switch the resolution
+// ("display:1280x720", "display:native") instead of launching anything
+const DISPLAY_PREFIX: &str = "display:";
+// Start menu entry that forgets the saved layout
+const RESET_LAYOUT: &str = "session:reset";
+
+fn window_title(win: &Window) -> &str {
+    core::str::from_utf8(&win.title[..win.title_len]).unwrap_or("Window")
+}
+
+fn start_menu_entries() -> Vec<(&'static str, &'static str)> {
+    vec![
+        ("Terminal", "/mnt/nvme/apps/Terminal.nyx/run.bin\0"),
+        ("Settings", "/mnt/nvme/apps/Settings.nyx/run.bin\0"),
+        ("Explorer", "/mnt/nvme/apps/Explorer.nyx/run.bin\0"),
+        ("Network Suite", "/mnt/nvme/apps/Network.nyx/run.bin\0"),
+        ("System Monitor", "/mnt/nvme/apps/SystemMonitor.nyx/run.bin\0"),
+        ("Task Manager", "/mnt/nvme/apps/TaskManager.nyx/run.bin\0"),
+        ("Image Viewer", "/mnt/nvme/apps/ImageViewer.nyx/run.bin\0"),
+        ("Keyboard", "/mnt/nvme/apps/Keyboard.nyx/run.bin\0"),
+        ("Display 800x600", "display:800x600"),
+        ("Display 1024x768", "display:1024x768"),
+        ("Display 1280x720", "display:1280x720"),
+        ("Display Native", "display:native"),
+        ("Reset Layout", RESET_LAYOUT),
+    ]
+}
+
+/// The taskbar clock for the wall clock's `unix` seconds: "h:MM AM" in UTC,
+/// or dashes until something (the RTC, `netclock`) has set it.
+fn clock_text(unix: u64) -> String {
+    if unix == 0 { return String::from("--:--"); }
+    let time = nyx_core::date::DateTime::from_unix(unix);
+    let hour = match time.hour % 12 { 0 => 12, h => h };
+    alloc::format!("{}:{:02} {}", hour, time.minute, if time.hour < 12 { "AM" } else { "PM" })
+}
+
+/// Rewrites the boot config lines for `values`' keys, keeping every other
+/// line (comments included), so the next boot starts with these settings.
+fn save_config(values: &[(&str, &str)]) {
+    let mut text = String::new();
+    if let Ok(size) = sys_fs_size(CONFIG_PATH) {
+        let mut buf = vec![0u8; size];
+        if let Ok(n) = sys_fs_read(CONFIG_PATH, &mut buf, 0) {
+            for line in String::from_utf8_lossy(&buf[..n]).lines() {
+                let key = line.split_once('=').map(|(k, _)| k.trim());
+                if key.is_some_and(|k| values.iter().any(|(v, _)| *v == k)) { continue; }
+                text.push_str(line);
+                text.push('\n');
+            }
+        }
+    }
+    for (key, value) in values {
+        text.push_str(key);
+        text.push('=');
+        text.push_str(value);
+        text.push('\n');
+    }
+    if sys_fs_write(CONFIG_PATH, text.as_bytes(), 0, FS_WRITE_TRUNCATE).is_err() {
+        sys_print("[COMPOSITOR] Could not save the boot config\n");
+    }
+}
+
+/// Where each display sits in the composed frame. The frame covers the box
+/// around every display, at the resolution picked for the primary, and the
+/// pointer's coordinates are scaled into it the same way.
+pub struct DesktopLayout {
+    pub w: usize, pub h: usize,
+    // (x, y, w, h) of each display in frame pixels, the primary first
+    pub displays: Vec<(usize, usize, usize, usize)>,
+    // Size of the box the pointer moves in
+    pub pointer_w: usize, pub pointer_h: usize,
+}
+
+impl DesktopLayout {
+    fn query(native_w: usize, native_h: usize) -> Self {
+        let mut infos = [DisplayInfo::default(); MAX_DISPLAYS];
+        let n = sys_get_displays(&mut infos);
+        if n == 0 { infos[0] = DisplayInfo { x: 0, y: 0, w: native_w as u32, h: native_h as u32, is_virtual: 0 }; }
+        let infos = &infos[..n.max(1)];
+
+        let (mode_w, mode_h) = sys_get_resolution();
+        let (primary_w, primary_h) = (infos[0].w.max(1) as usize, infos[0].h.max(1) as usize);
+        let scale_x = |v: u32| v as usize * mode_w / primary_w;
+        let scale_y = |v: u32| v as usize * mode_h / primary_h;
+        let pointer_w = infos.iter().map(|d| (d.x + d.w) as usize).max().unwrap_or(primary_w);
+        let pointer_h = infos.iter().map(|d| (d.y + d.h) as usize).max().unwrap_or(primary_h);
+        Self {
+            w: scale_x(pointer_w as u32), h: scale_y(pointer_h as u32),
+            displays: infos.iter().map(|d| (scale_x(d.x), scale_y(d.y), scale_x(d.w), scale_y(d.h))).collect(),
+            pointer_w, pointer_h,
+        }
+    }
+}
+
+/// The framebuffer and the frame composed for it. Frames go straight into the
+/// framebuffer when it stores 0xAARRGGBB and the desktop runs at its size;
+/// otherwise they're drawn off-screen and converted, and scaled when the
+/// desktop's resolution differs, as they're presented.
+struct Screen {
+    fb_ptr: u64,
+    format: FbFormat,
+    native_w: usize, native_h: usize, native_stride: usize,
+    // The desktop's size and the frame's row length, in pixels
+    w: usize, h: usize, stride: usize,
+    // Empty while drawing straight into the framebuffer
+    shadow: Vec<u32>,
+}
+
+impl Screen {
+    fn new(fb_ptr: u64, format: FbFormat, native_w: usize, native_h: usize, native_stride: usize) -> Self {
+        Self { fb_ptr, format, native_w, native_h, native_stride, w: 0, h: 0, stride: 0, shadow: Vec::new() }
+    }
+
+    /// Sizes the frame for a w x h desktop.
+    fn resize(&mut self, w: usize, h: usize) {
+        // The old frame goes first so both never have to fit in the heap at once
+        self.shadow = Vec::new();
+        let native_size = (w, h) == (self.native_w, self.native_h);
+        self.stride = if native_size { self.native_stride } else { w };
+        if !native_size || !self.format.is_native_argb() { self.shadow = vec![0u32; self.stride * h]; }
+        self.w = w; self.h = h;
+    }
+
+    fn is_direct(&self) -> bool { self.shadow.is_empty() }
+
+    fn frame(&mut self) -> &mut [u32] {
+        if self.is_direct() {
+            unsafe { core::slice::from_raw_parts_mut(self.fb_ptr as *mut u32, self.stride * self.h) }
+        } else {
+            self.shadow.as_mut_slice()
+        }
+    }
+
+    /// Carries the dirty rectangle of an off-screen frame over to the
+    /// framebuffer, and returns the framebuffer rectangle it covers.
+    fn present(&mut self, x: usize, y: usize, w: usize, h: usize) -> (usize, usize, usize, usize) {
+        if self.is_direct() { return (x, y, w, h); }
+        let area = rect::from_rect(x, y, w, h);
+        let fb_len = self.native_stride * self.native_h * self.format.bytes_per_pixel;
+        let mut fb = Framebuffer {
+            bytes: unsafe { core::slice::from_raw_parts_mut(self.fb_ptr as *mut u8, fb_len) },
+            format: self.format, width: self.native_w, height: self.native_h, stride: self.native_stride,
+        };
+        let frame = Frame { pixels: &self.shadow, width: self.w, height: self.h, stride: self.stride };
+        if (self.w, self.h) != (self.native_w, self.native_h) {
+            present_scaled(&mut fb, &frame, area);
+            return scaled_rect(self.native_w, self.native_h, self.w, self.h, area);
+        }
+        present_rect(&mut fb, &frame, area);
+        (x, y, w, h)
+    }
+}
+
+const GHOST_W: usize = 120;
+const GHOST_H: usize = 24;
+
+#[derive(PartialEq)]
+pub enum DragPhase {
+    // Button held in a client area; released without moving this is a plain click
+    Pressed,
+    // MSG_DRAG_BEGIN sent, waiting for the app's payload
+    Querying,
+    // The app declined; the release still counts as a click
+    Declined,
+    // Payload received, ghost follows the cursor until release
+    Active,
+}
+
+/// One press-and-hold inside a window's client area. Generic over payloads so any
+/// app can act as a drag source or drop target through begin_drag/accept_drop.
+pub struct DragState {
+    pub phase: DragPhase,
+    pub source_pid: u64,
+    // Press position relative to the source's client area
+    pub rel_x: usize, pub rel_y: usize,
+    pub payload: String,
+}
+
+/// Text copied by any app, handed back to whichever app pastes.
+pub struct ClipBoard {
+    text: String,
+}
+
+impl ClipBoard {
+    fn set(&mut self, text: &str) {
+        self.text.clear();
+        self.text.push_str(text);
+    }
+
+    fn send_to(&self, pid: u64) {
+        if self.text.is_empty() { let _ = sys_ipc_send(pid, MSG_CLIPBOARD_DATA, 0, 0); }
+        else { let _ = sys_ipc_send_str(pid, MSG_CLIPBOARD_DATA, &self.text); }
+    }
+}
+
+fn ghost_bounds(mx: usize, my: usize) -> (usize, usize, usize, usize) {
+    (mx + 12, my + 12, GHOST_W, GHOST_H)
 }
 
+// Set-1 scancodes of the keys the window manager keeps for itself
+const SC_TAB: u8 = 0x0F;
+const SC_ALT: u8 = 0x38;
+const SC_F4: u8 = 0x3E;
+
 fn get_str_len(buf: &[u8; 64]) -> usize { buf.iter().position(|&c| c == 0).unwrap_or(64) }
 
 pub struct CompositorState {
@@ -31,10 +264,12 @@ pub struct CompositorState {
 
     pub mx: usize, pub my: usize,
     pub prev_mx: usize, pub prev_my: usize,
-    pub left_click: bool, pub prev_left: bool,
+    // The GPU draws the pointer, so moving it redraws nothing
+    pub hw_cursor: bool,
+    // Clicks, double-clicks and drags from the raw mouse state
+    pub pointer: PointerTracker,
 
-    pub dirty_min_x: usize, pub dirty_min_y: usize,
-    pub dirty_max_x: usize, pub dirty_max_y: usize,
+    pub dirty: DirtyRegion,
     pub needs_redraw: bool,
 
     pub dragging_win_idx: Option<usize>,
@@ -43,51 +278,442 @@ pub struct CompositorState {
     pub is_resizing: bool,
     pub resizing_win_idx: Option<usize>,
 
-    pub start_menu_open: bool,
+    pub start_menu: StartMenu,
     pub screen_w: usize, pub screen_h: usize, pub screen_stride: usize,
+    // From the DesktopLayout; the taskbar, start menu and icons live on displays[0]
+    pub displays: Vec<(usize, usize, usize, usize)>,
+    pub pointer_w: usize, pub pointer_h: usize,
+    // Resolution picked from the start menu, applied before the next frame
+    pub pending_mode: Option<(usize, usize)>,
+    // The display list or the resolution changed; the frame gets rebuilt
+    pub relayout: bool,
+
+    // Compose timing reported to SysMon
+    pub frame_time_avg_us: usize,
+    pub frames_composed: u64,
+
+    // Decoded desktop image; None falls back to a solid fill in the theme's window_bg
+    pub wallpaper: Option<Wallpaper>,
+    // Where it came from, to decode it again at another resolution
+    pub wallpaper_path: String,
+
+    pub desktop: DesktopIcons,
+    pub switcher: WindowSwitcher,
+    pub clipboard: ClipBoard,
+    // Paths to deliver with MSG_OPEN_PATH once the forked app's window exists
+    pub pending_opens: Vec<(u64, String)>,
+
+    pub drag: Option<DragState>,
+
+    // Owner of the focused window as last reported to the scheduler
+    pub foreground_pid: u64,
+
+    // Wall clock minute the taskbar clock last showed
+    pub clock_minute: u64,
+
+    // Filesystem generation the desktop icons were listed at, and when it was last checked
+    pub fs_generation: u64,
+    pub fs_checked_at: usize,
+
+    // Window layout and app state kept across reboots
+    pub session: Session,
 }
 
 impl CompositorState {
-    pub fn new(w: usize, h: usize, stride: usize) -> Self {
+    pub fn new(layout: DesktopLayout, stride: usize) -> Self {
+        let (w, h) = (layout.w, layout.h);
+        let (_, _, primary_w, primary_h) = layout.displays[0];
         Self {
             clients: Vec::new(), next_win_id: 0,
             mx: w / 2, my: h / 2, prev_mx: w / 2, prev_my: h / 2,
-            left_click: false, prev_left: false,
-            dirty_min_x: 0, dirty_min_y: 0, dirty_max_x: stride, dirty_max_y: h,
+            hw_cursor: sys_has_hw_cursor(),
+            pointer: PointerTracker::new(),
+            dirty: { let mut d = DirtyRegion::new(); d.mark_all(stride, h); d },
             needs_redraw: true,
             dragging_win_idx: None, drag_off_x: 0, drag_off_y: 0,
             is_resizing: false, resizing_win_idx: None,
-            start_menu_open: false,
+            start_menu: StartMenu::new(start_menu_entries(), primary_w, primary_h),
             screen_w: w, screen_h: h, screen_stride: stride,
+            displays: layout.displays,
+            pointer_w: layout.pointer_w, pointer_h: layout.pointer_h,
+            pending_mode: None,
+            relayout: false,
+            frame_time_avg_us: 0, frames_composed: 0,
+            wallpaper: None,
+            wallpaper_path: String::new(),
+            desktop: DesktopIcons::new(primary_h),
+            switcher: WindowSwitcher::new(primary_w, primary_h),
+            clipboard: ClipBoard { text: String::new() },
+            pending_opens: Vec::new(),
+            drag: None,
+            foreground_pid: 0,
+            clock_minute: sys_wall_clock() / 60,
+            fs_generation: sys_fs_generation(),
+            fs_checked_at: sys_get_time(),
+            session: Session::load(),
         }
     }
 
     pub fn mark_dirty(&mut self, x: usize, y: usize, w: usize, h: usize) {
-        self.dirty_min_x = self.dirty_min_x.min(x);
-        self.dirty_min_y = self.dirty_min_y.min(y);
-        self.dirty_max_x = self.dirty_max_x.max(x + w).min(self.screen_stride);
-        self.dirty_max_y = self.dirty_max_y.max(y + h).min(self.screen_h);
+        self.dirty.mark(x, y, w, h, self.screen_stride, self.screen_h);
         self.needs_redraw = true;
     }
 
+    /// Rolling average over roughly the last 16 frames.
+    pub fn record_frame_time(&mut self, us: usize) {
+        self.frame_time_avg_us = (self.frame_time_avg_us * 15 + us) / 16;
+        self.frames_composed += 1;
+    }
+
+    pub fn mark_window_dirty(&mut self, idx: usize) {
+        let (x, y, w, h) = window_bounds(&self.clients[idx].win);
+        self.mark_dirty(x, y, w, h);
+    }
+
+    pub fn mark_menu_dirty(&mut self) {
+        let (x, y, w, h) = self.start_menu.bounds();
+        self.mark_dirty(x, y, w, h);
+    }
+
     pub fn mark_full_redraw(&mut self) {
-        self.dirty_min_x = 0; self.dirty_min_y = 0;
-        self.dirty_max_x = self.screen_stride; self.dirty_max_y = self.screen_h;
+        self.dirty.mark_all(self.screen_stride, self.screen_h);
         self.needs_redraw = true;
     }
 
+    /// Client indices in taskbar order. Sorting by id keeps buttons from jumping around on focus changes.
+    pub fn taskbar_clients(&self) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.clients.len()).filter(|&i| self.clients[i].win.exists).collect();
+        order.sort_by_key(|&i| self.clients[i].win.id);
+        order
+    }
+
+    /// The focused window is the topmost one that is still on screen and can take focus.
+    pub fn active_client(&self) -> Option<usize> {
+        self.clients.iter().rposition(|c| c.win.exists && !c.win.is_minimized && !c.no_focus)
+    }
+
+    /// Moves window `idx` to the top of the z-order, below any no-focus windows.
+    fn raise(&mut self, idx: usize) {
+        let moved_client = self.clients.remove(idx);
+        let top = if moved_client.no_focus { self.clients.len() }
+            else { self.clients.iter().rposition(|c| !c.no_focus).map_or(0, |i| i + 1) };
+        self.clients.insert(top, moved_client);
+        if top != idx {
+            if self.dragging_win_idx == Some(idx) { self.dragging_win_idx = Some(top); }
+            if self.resizing_win_idx == Some(idx) { self.resizing_win_idx = Some(top); }
+            self.mark_full_redraw();
+        }
+    }
+
+    pub fn primary(&self) -> (usize, usize) {
+        let (_, _, w, h) = self.displays[0];
+        (w, h)
+    }
+
+    /// (x, y, w, h) of the part of display `idx` windows can use: all of it
+    /// but the primary's taskbar.
+    fn work_area(&self, idx: usize) -> (usize, usize, usize, usize) {
+        let (x, y, w, h) = self.displays[idx];
+        if idx == 0 { (x, y, w, h.saturating_sub(taskbar_h())) } else { (x, y, w, h) }
+    }
+
+    /// The display under (x, y); the primary for points off every display.
+    fn display_at(&self, x: usize, y: usize) -> usize {
+        self.displays.iter().position(|&(dx, dy, dw, dh)| x >= dx && x < dx + dw && y >= dy && y < dy + dh).unwrap_or(0)
+    }
+
+    /// Where the `slot`-th w x h window opens on display `idx`: cascading
+    /// down from near its top-left corner, kept on the display.
+    fn place_window(&self, idx: usize, slot: usize, w: usize, h: usize) -> (usize, usize) {
+        let (ax, ay, aw, ah) = self.work_area(idx.min(self.displays.len() - 1));
+        let offset = 100 + slot * 30;
+        (ax + offset.min(aw.saturating_sub(w)), ay + offset.min(ah.saturating_sub(h + title_bar_h())))
+    }
+
+    /// Toggles between the saved geometry and filling the window's display above the taskbar.
+    fn toggle_maximize(&mut self, idx: usize) {
+        let (cx, cy) = (self.clients[idx].win.x + self.clients[idx].win.w / 2, self.clients[idx].win.y + title_bar_h() / 2);
+        let (ax, ay, aw, ah) = self.work_area(self.display_at(cx, cy));
+        let win = &mut self.clients[idx].win;
+        if win.is_maximized {
+            win.x = win.saved_x; win.y = win.saved_y;
+            win.w = win.saved_w; win.h = win.saved_h;
+            win.is_maximized = false;
+        } else {
+            win.saved_x = win.x; win.saved_y = win.y;
+            win.saved_w = win.w; win.saved_h = win.h;
+            win.x = ax; win.y = ay;
+            win.w = aw; win.h = ah.saturating_sub(title_bar_h());
+            win.is_maximized = true;
+        }
+        let _ = sys_ipc_send(self.clients[idx].owner_pid, MSG_WINDOW_RESIZED, self.clients[idx].win.w as u64, self.clients[idx].win.h as u64);
+        self.session.touch();
+        self.mark_full_redraw();
+    }
+
+    /// Saved geometry moved onto the display it was on, or the primary if
+    /// that one is gone, and shrunk to fit it.
+    fn fit_geometry(&self, g: Geometry) -> Geometry {
+        let areas: Vec<_> = (0..self.displays.len()).map(|i| self.work_area(i)).collect();
+        let on = |&(ax, ay, aw, ah): &(usize, usize, usize, usize)| g.x >= ax && g.x < ax + aw && g.y >= ay && g.y < ay + ah;
+        let (ax, ay, aw, ah) = areas.iter().copied().find(|a| on(a)).unwrap_or(areas[0]);
+        let max_h = ah.saturating_sub(title_bar_h());
+        let w = g.w.max(min_win_w()).min(aw);
+        let h = g.h.max(min_win_h()).min(max_h);
+        Geometry { x: g.x.clamp(ax, ax + aw - w), y: g.y.clamp(ay, ay + max_h - h), w, h, maximized: g.maximized }
+    }
+
+    /// Forks `path` and remembers which binary the new pid runs, so its
+    /// window can be reopened next boot. Returns the pid, or None if the fork failed.
+    fn launch(&mut self, path: &str) -> Option<u64> {
+        let pid = sys_fork().ok()?;
+        if pid == 0 { sys_execve(path); sys_exit(1); }
+        self.session.launched(pid, path);
+        Some(pid)
+    }
+
+    /// Launches the apps whose windows were open when the state was last saved.
+    pub fn restore_session(&mut self) {
+        for app in self.session.apps_to_restore() {
+            let mut path = app;
+            path.push('\0');
+            self.launch(&path);
+        }
+    }
+
+    /// Updates the session record of window `idx`; a maximized window is
+    /// remembered at its restored size.
+    fn remember(&mut self, idx: usize) {
+        let client = &self.clients[idx];
+        let win = &client.win;
+        let geometry = if win.is_maximized {
+            Geometry { x: win.saved_x, y: win.saved_y, w: win.saved_w, h: win.saved_h, maximized: true }
+        } else {
+            Geometry { x: win.x, y: win.y, w: win.w, h: win.h, maximized: false }
+        };
+        let title = String::from(window_title(win));
+        let app = client.app.clone();
+        self.session.record(&title, &app, geometry, win.exists);
+    }
+
+    /// Writes the state file with every open window where it is now.
+    pub fn save_session(&mut self) {
+        for idx in 0..self.clients.len() {
+            if self.clients[idx].win.exists { self.remember(idx); }
+        }
+        self.session.save(sys_get_time());
+    }
+
+    /// "Reset Layout": deletes the state file and cascades the open windows
+    /// back to their default sizes, as if they had just been opened.
+    fn reset_layout(&mut self) {
+        self.session.reset();
+        for (slot, idx) in self.taskbar_clients().into_iter().enumerate() {
+            let (w, h) = (self.clients[idx].default_w, self.clients[idx].default_h);
+            let (x, y) = self.place_window(0, slot, w, h);
+            let client = &mut self.clients[idx];
+            let old_size = (client.win.w, client.win.h);
+            client.win.is_maximized = false;
+            client.win.is_minimized = false;
+            client.win.x = x; client.win.y = y;
+            client.win.w = w; client.win.h = h;
+            if (w, h) != old_size { let _ = sys_ipc_send(client.owner_pid, MSG_WINDOW_RESIZED, w as u64, h as u64); }
+        }
+        self.mark_full_redraw();
+    }
+
+    /// Shows `path` in the Explorer: an open Explorer window is focused and navigated,
+    /// otherwise a new one is launched and gets the path as soon as it has a window.
+    pub fn open_in_explorer(&mut self, path: String) {
+        let existing = self.clients.iter().position(|c| {
+            c.win.exists && &c.win.title[..c.win.title_len] == EXPLORER_TITLE.as_bytes()
+        });
+        if let Some(idx) = existing {
+            let _ = sys_ipc_send_str(self.clients[idx].owner_pid, MSG_OPEN_PATH, &path);
+            self.clients[idx].win.is_minimized = false;
+            self.raise(idx);
+            self.mark_full_redraw();
+            return;
+        }
+
+        self.open_with(EXPLORER_PATH, path);
+    }
+
+    /// Launches `app` and hands it `path` as soon as it has a window.
+    fn open_with(&mut self, app: &str, path: String) {
+        if let Some(pid) = self.launch(app) { self.pending_opens.push((pid, path)); }
+    }
+
+    fn finish_drag(&mut self, drag: DragState) {
+        if drag.phase != DragPhase::Active {
+            let _ = sys_ipc_send(drag.source_pid, MSG_MOUSE_EVENT, drag.rel_x as u64, drag.rel_y as u64);
+            return;
+        }
+
+        let (x, y, w, h) = ghost_bounds(self.mx, self.my);
+        self.mark_dirty(x, y, w, h);
+
+        // Drops land on the topmost window under the cursor. The desktop and the
+        // source window itself aren't targets yet, so those simply cancel.
+        let target = self.clients.iter().rev().find(|c| {
+            c.win.exists && !c.win.is_minimized &&
+            self.mx >= c.win.x && self.mx <= c.win.x + c.win.w && self.my >= c.win.y && self.my <= c.win.y + c.win.h + title_bar_h()
+        }).map(|c| c.owner_pid);
+
+        if let Some(pid) = target.filter(|&pid| pid != drag.source_pid) {
+            let _ = sys_ipc_send_str(pid, MSG_DROP, &drag.payload);
+        }
+    }
+
+    /// Sends wheel notches to the topmost window whose content is under the
+    /// cursor, focused or not.
+    fn deliver_wheel(&self, notches: i32) {
+        let target = self.clients.iter().rev().find(|c| {
+            c.win.exists && !c.win.is_minimized &&
+            self.mx >= c.win.x && self.mx <= c.win.x + c.win.w && self.my > c.win.y + title_bar_h() && self.my <= c.win.y + c.win.h + title_bar_h()
+        });
+        if let Some(c) = target {
+            let rel = ((self.mx - c.win.x) as u64) << 32 | (self.my - (c.win.y + title_bar_h())) as u64;
+            let _ = sys_ipc_send(c.owner_pid, MSG_MOUSE_WHEEL, notches as i64 as u64, rel);
+        }
+    }
+
+    pub fn load_wallpaper(&mut self, path: &str) -> bool {
+        // Drop the old image first so both never have to fit in the heap at once
+        self.wallpaper = None;
+        let (w, h) = self.primary();
+        self.wallpaper = Wallpaper::load(path, w, h, theme::current().window_bg);
+        if self.wallpaper.is_some() { self.wallpaper_path = String::from(path); }
+        self.mark_full_redraw();
+        self.wallpaper.is_some()
+    }
+
+    /// Switches every window to ACCENTS[accent] in light or dark and saves
+    /// the choice in the boot config.
+    fn apply_theme(&mut self, accent: usize, dark: bool) {
+        let accent = accent.min(ACCENTS.len() - 1);
+        theme::set(accent, dark);
+        save_config(&[("theme", if dark { "dark" } else { "light" }), ("accent", ACCENTS[accent].0)]);
+
+        let mut told: Vec<u64> = Vec::new();
+        for client in self.clients.iter().filter(|c| c.win.exists) {
+            if told.contains(&client.owner_pid) { continue; }
+            let _ = sys_ipc_send(client.owner_pid, MSG_THEME_CHANGED, accent as u64, dark as u64);
+            told.push(client.owner_pid);
+        }
+        // The wallpaper's letterbox bars are filled with the window background
+        if self.wallpaper.is_some() {
+            let path = self.wallpaper_path.clone();
+            self.load_wallpaper(&path);
+        }
+        self.mark_full_redraw();
+    }
+
+    /// Switches the UI scale, saves it and tells every app. The taskbar and
+    /// title bars change height with it, so the desktop icons are laid out
+    /// again and the windows refitted to the new work area and minimum size.
+    fn apply_ui_scale(&mut self, ui_scale: usize) {
+        scale::set(ui_scale);
+        let ui_scale = scale::current();
+        save_config(&[("ui_scale", &alloc::format!("{}", ui_scale))]);
+
+        let mut told: Vec<u64> = Vec::new();
+        for client in self.clients.iter().filter(|c| c.win.exists) {
+            if told.contains(&client.owner_pid) { continue; }
+            let _ = sys_ipc_send(client.owner_pid, MSG_UI_SCALE_CHANGED, ui_scale as u64, 0);
+            told.push(client.owner_pid);
+        }
+        let (_, primary_h) = self.primary();
+        self.desktop.resize(primary_h);
+        self.fit_windows(true);
+        self.mark_full_redraw();
+    }
+
+    /// Launches a start menu pick, queues the resolution it names or resets the layout.
+    fn run_menu_item(&mut self, path: &'static str) {
+        if path == RESET_LAYOUT { self.reset_layout(); return; }
+        match path.strip_prefix(DISPLAY_PREFIX) {
+            Some(mode) => self.pending_mode = parse_resolution(mode),
+            None => { self.launch(path); },
+        }
+    }
+
+    /// Lays the desktop out again after the resolution or the displays
+    /// changed: everything sized for the old layout is rebuilt, each window is
+    /// shrunk to fit the display it's on (the primary, if that display is
+    /// gone) and pulled back onto it, and the next frame redraws everything.
+    pub fn apply_layout(&mut self, layout: DesktopLayout, stride: usize) {
+        let (w, h) = (layout.w, layout.h);
+        self.screen_w = w; self.screen_h = h; self.screen_stride = stride;
+        self.displays = layout.displays;
+        self.pointer_w = layout.pointer_w; self.pointer_h = layout.pointer_h;
+
+        let (primary_w, primary_h) = self.primary();
+        self.start_menu.resize(primary_w, primary_h);
+        self.desktop.resize(primary_h);
+        self.switcher = WindowSwitcher::new(primary_w, primary_h);
+        if self.wallpaper.is_some() {
+            let path = self.wallpaper_path.clone();
+            self.load_wallpaper(&path);
+        }
+
+        self.fit_windows(false);
+
+        self.mx = self.mx.min(w - 1); self.my = self.my.min(h - 1);
+        self.prev_mx = self.mx; self.prev_my = self.my;
+        self.mark_full_redraw();
+    }
+
+    /// Shrinks each window to fit the work area of the display it's on (the
+    /// primary, if it's on none) and pulls it back onto that display; with
+    /// `grow`, windows under the minimum size are brought up to it too. Apps
+    /// whose window changed size are told.
+    fn fit_windows(&mut self, grow: bool) {
+        let (min_w, min_h) = if grow { (min_win_w(), min_win_h()) } else { (0, 0) };
+        let areas: Vec<_> = (0..self.displays.len()).map(|i| self.work_area(i)).collect();
+        for client in self.clients.iter_mut().filter(|c| c.win.exists) {
+            let win = &mut client.win;
+            let old_size = (win.w, win.h);
+            let on = |&(ax, ay, aw, ah): &(usize, usize, usize, usize)| win.x >= ax && win.x < ax + aw && win.y >= ay && win.y < ay + ah;
+            let (ax, ay, aw, ah) = areas.iter().copied().find(|a| on(a)).unwrap_or(areas[0]);
+            let max_h = ah.saturating_sub(title_bar_h());
+            if win.is_maximized {
+                win.x = ax; win.y = ay; win.w = aw; win.h = max_h;
+                win.saved_w = win.saved_w.max(min_w).min(aw); win.saved_h = win.saved_h.max(min_h).min(max_h);
+                win.saved_x = win.saved_x.clamp(ax, ax + aw - win.saved_w); win.saved_y = win.saved_y.clamp(ay, ay + max_h - win.saved_h);
+            } else {
+                win.w = win.w.max(min_w).min(aw); win.h = win.h.max(min_h).min(max_h);
+                win.x = win.x.clamp(ax, ax + aw - win.w); win.y = win.y.clamp(ay, ay + max_h - win.h);
+            }
+            if (win.w, win.h) != old_size {
+                let _ = sys_ipc_send(client.owner_pid, MSG_WINDOW_RESIZED, win.w as u64, win.h as u64);
+            }
+        }
+    }
+
     pub fn process_ipc(&mut self) {
         let mut msg = IpcMessage { sender_pid: 0, msg_type: 0, data1: 0, data2: 0 };
-        while sys_ipc_recv(&mut msg, false) {
+        while sys_ipc_recv(&mut msg, false).is_ok() {
             match msg.msg_type {
                 MSG_REQ_WINDOW => {
                     let shm_id = msg.data1;
                     let vaddr = sys_map_shm(shm_id) as *mut u8;
                     let header = unsafe { &*(vaddr as *const WindowHeader) };
                     if header.magic == WIN_MAGIC {
-                        let w = header.width as usize; let h = header.height as usize;
-                        let x = if header.requested_x == -1 { 100 + (self.next_win_id * 30) } else { header.requested_x as usize };
-                        let y = if header.requested_y == -1 { 100 + (self.next_win_id * 30) } else { header.requested_y as usize };
+                        let (default_w, default_h) = (header.width as usize, header.height as usize);
+                        let (place_x, place_y) = self.place_window(0, self.next_win_id, default_w, default_h);
+                        let title_len = get_str_len(&header.title);
+                        let title = core::str::from_utf8(&header.title[..title_len]).unwrap_or("Window");
+                        // Apps placing themselves win over where the window was last time
+                        let saved = if header.requested_x == -1 { self.session.placement(title).map(|g| self.fit_geometry(g)) } else { None };
+                        let (x, y, w, h) = match saved {
+                            Some(g) => (g.x, g.y, g.w, g.h),
+                            None => (
+                                if header.requested_x == -1 { place_x } else { header.requested_x as usize },
+                                if header.requested_y == -1 { place_y } else { header.requested_y as usize },
+                                default_w, default_h,
+                            ),
+                        };
                         
                         let gpu_gva = 0x2000_0000 + (self.next_win_id * 0x0100_0000) as u32;
                         sys_gpu_map_shm(shm_id, gpu_gva);
@@ -95,18 +721,37 @@ impl CompositorState {
                         self.clients.push(WindowClient {
                             win: Window { 
                                 id: self.next_win_id, x, y, w, h, 
-                                title: header.title, title_len: get_str_len(&header.title), 
+                                title: header.title, title_len, 
                                 active: true, exists: true, opacity: 0,
                                 is_minimized: false, is_maximized: false,
                                 saved_x: 0, saved_y: 0, saved_w: 0, saved_h: 0
                             },
                             owner_pid: msg.sender_pid, shm_id, buffer: unsafe { vaddr.add(core::mem::size_of::<WindowHeader>()) } as *const u32,
-                            buf_w: w, buf_h: h,
+                            buf_w: default_w, buf_h: default_h,
                             gpu_gva,
+                            app: self.session.take_launch(msg.sender_pid),
+                            default_w, default_h,
+                            no_focus: header.flags & WIN_FLAG_NO_FOCUS != 0,
                         });
                         self.next_win_id += 1;
+                        // New windows open on top, but under any no-focus ones
+                        let id = self.clients[self.clients.len() - 1].win.id;
+                        self.raise(self.clients.len() - 1);
+                        let idx = self.clients.iter().position(|c| c.win.id == id).unwrap_or(0);
                         self.mark_full_redraw();
-                        sys_ipc_send(msg.sender_pid, MSG_WINDOW_CREATED, shm_id, 0);
+                        let _ = sys_ipc_send(msg.sender_pid, MSG_WINDOW_CREATED, shm_id, 0);
+                        // The buffer is still the size the app asked for until it answers this
+                        if saved.is_some_and(|g| g.maximized) {
+                            self.toggle_maximize(idx);
+                        } else if (w, h) != (default_w, default_h) {
+                            let _ = sys_ipc_send(msg.sender_pid, MSG_WINDOW_RESIZED, w as u64, h as u64);
+                        }
+                        self.session.touch();
+
+                        if let Some(pos) = self.pending_opens.iter().position(|(pid, _)| *pid == msg.sender_pid) {
+                            let (pid, path) = self.pending_opens.remove(pos);
+                            let _ = sys_ipc_send_str(pid, MSG_OPEN_PATH, &path);
+                        }
                     }
                 },
                 MSG_WINDOW_UPDATE_SHM => {
@@ -126,174 +771,399 @@ impl CompositorState {
                 },
                 MSG_FLUSH_WINDOW => {
                     let dirty_rect = self.clients.iter()
-                        .find(|c| c.owner_pid == msg.sender_pid)
-                        .map(|c| (c.win.x, c.win.y, c.win.w + 15, c.win.h + 45));
+                        .find(|c| c.owner_pid == msg.sender_pid && !c.win.is_minimized)
+                        .map(|c| window_bounds(&c.win));
                     if let Some((x, y, w, h)) = dirty_rect { self.mark_dirty(x, y, w, h); }
                 },
+                MSG_DISPLAYS_CHANGED => self.relayout = true,
+                MSG_SET_WALLPAPER => {
+                    let ok = ipc_read_str(&msg, |path| self.load_wallpaper(path)).unwrap_or(false);
+                    let _ = sys_ipc_send(msg.sender_pid, MSG_WALLPAPER_STATUS, ok as u64, 0);
+                },
+                MSG_CLIPBOARD_SET => {
+                    ipc_read_str(&msg, |text| self.clipboard.set(text));
+                },
+                MSG_CLIPBOARD_GET => self.clipboard.send_to(msg.sender_pid),
+                MSG_DRAG_PAYLOAD => {
+                    let (mx, my) = (self.mx, self.my);
+                    if let Some(drag) = self.drag.as_mut().filter(|d| d.phase == DragPhase::Querying && d.source_pid == msg.sender_pid) {
+                        match ipc_read_str(&msg, |payload| String::from(payload)) {
+                            Some(payload) => {
+                                drag.payload = payload;
+                                drag.phase = DragPhase::Active;
+                                let (x, y, w, h) = ghost_bounds(mx, my);
+                                self.mark_dirty(x, y, w, h);
+                            },
+                            None => drag.phase = DragPhase::Declined,
+                        }
+                    }
+                },
+                MSG_SET_THEME => self.apply_theme(msg.data1 as usize, msg.data2 != 0),
+                MSG_SET_UI_SCALE => self.apply_ui_scale(msg.data1 as usize),
+                MSG_INJECT_KEY => {
+                    let allowed = self.clients.iter().any(|c| c.win.exists && c.no_focus && c.owner_pid == msg.sender_pid);
+                    if let Some(event) = KeyEvent::from_packed(msg.data2).filter(|_| allowed) { self.handle_key_event(event); }
+                },
+                MSG_SET_CLOCK => {
+                    // Only what the user is working in, not anything running in the background
+                    if msg.sender_pid == self.foreground_pid && sys_set_wall_clock(msg.data1).is_ok() {
+                        self.clock_minute = msg.data1 / 60;
+                        let (primary_w, primary_h) = self.primary();
+                        self.mark_dirty(0, primary_h - taskbar_h(), primary_w, taskbar_h());
+                    }
+                },
+                MSG_OPEN_WITH => {
+                    let request = ipc_read_str(&msg, |s| s.split_once('\n')
+                        .map(|(app, path)| (alloc::format!("{}\0", app), String::from(path)))).flatten();
+                    if let Some((app, path)) = request { self.open_with(&app, path); }
+                },
+                MSG_SAVE_STATE => {
+                    ipc_read_str(&msg, |pair| self.session.set(pair));
+                },
+                MSG_GET_FRAME_STATS => {
+                    let _ = sys_ipc_send(msg.sender_pid, MSG_FRAME_STATS, self.frame_time_avg_us as u64, self.frames_composed);
+                },
                 _ => {}
             }
         }
     }
 
-    pub fn process_input(&mut self) {
-        if let Some(key) = sys_read_key() {
-            if let Some(top_client) = self.clients.iter().rev().find(|c| c.win.exists && !c.win.is_minimized) {
-                sys_ipc_send(top_client.owner_pid, MSG_KEY_EVENT, key as u64, 0);
+    fn mark_switcher_dirty(&mut self) {
+        if !self.switcher.is_open() { return; }
+        let (x, y, w, h) = self.switcher.bounds();
+        self.mark_dirty(x, y, w, h);
+    }
+
+    /// Every open window, topmost (most recently focused) first.
+    fn switcher_entries(&self) -> Vec<(usize, String)> {
+        self.clients.iter().rev().filter(|c| c.win.exists && !c.no_focus).map(|c| (c.win.id, String::from(window_title(&c.win)))).collect()
+    }
+
+    fn focus_window(&mut self, id: usize) {
+        if let Some(idx) = self.clients.iter().position(|c| c.win.exists && c.win.id == id) {
+            self.clients[idx].win.is_minimized = false;
+            self.raise(idx);
+            self.mark_full_redraw();
+        }
+    }
+
+    fn close_window(&mut self, idx: usize) {
+        let client = &mut self.clients[idx];
+        client.win.exists = false;
+        let _ = sys_ipc_send(client.owner_pid, MSG_WINDOW_CLOSE, 0, 0);
+        let (bx, by, bw, bh) = window_bounds(&client.win);
+        self.mark_dirty(bx, by, bw, bh);
+        self.remember(idx);
+    }
+
+    /// Alt+Tab, Alt+F4 and the switcher itself. Returns true if the event was
+    /// used up here rather than meant for the focused app.
+    fn handle_wm_key(&mut self, event: &KeyEvent) -> bool {
+        if event.pressed && event.alt() && event.scancode == SC_TAB {
+            self.mark_switcher_dirty();
+            if self.switcher.is_open() {
+                self.switcher.step(event.shift());
+            } else {
+                self.start_menu.close();
+                let entries = self.switcher_entries();
+                if entries.is_empty() { return true; }
+                self.switcher.open(entries);
+            }
+            self.mark_switcher_dirty();
+            return true;
+        }
+        if self.switcher.is_open() {
+            // Everything typed while switching belongs to the switcher
+            if event.scancode == SC_ALT && !event.pressed {
+                self.mark_switcher_dirty();
+                if let Some(id) = self.switcher.close() { self.focus_window(id); }
+            } else if event.pressed && event.ch == '\x1b' {
+                self.mark_switcher_dirty();
+                self.switcher.cancel();
             }
+            return true;
+        }
+        if event.pressed && event.alt() && event.scancode == SC_F4 {
+            if let Some(idx) = self.active_client() { self.close_window(idx); }
+            return true;
         }
+        false
+    }
 
-        let (mx_raw, my_raw, left_click, _right) = sys_get_mouse();
-        self.mx = mx_raw.clamp(0, self.screen_w - 1); 
-        self.my = my_raw.clamp(0, self.screen_h - 1);
-        self.left_click = left_click;
+    /// One key from the keyboard or the on-screen keyboard: the window
+    /// manager's own keys first, then an open start menu, then the focused app.
+    fn handle_key_event(&mut self, event: KeyEvent) {
+        if self.handle_wm_key(&event) { return; }
+        // An open start menu takes the keyboard
+        if self.start_menu.is_open {
+            if !event.pressed { return; }
+            match self.start_menu.on_key(event.ch) {
+                StartMenuAction::Launch(path) => { self.run_menu_item(path); self.mark_menu_dirty(); },
+                StartMenuAction::Redraw => self.mark_menu_dirty(),
+                StartMenuAction::None => {},
+            }
+            return;
+        }
+        if let Some(top_client) = self.active_client().map(|i| &self.clients[i]) {
+            // data1 keeps the plain char for older clients, data2 carries the full event
+            let ch = if event.pressed { event.ch as u64 } else { 0 };
+            let _ = sys_ipc_send(top_client.owner_pid, MSG_KEY_EVENT, ch, event.to_packed());
+        }
+    }
+
+    pub fn process_input(&mut self) {
+        while let Some(event) = sys_read_key_event() { self.handle_key_event(event); }
+
+        let mouse = sys_get_mouse_state();
+        // Scaled from the pointer's box onto the frame
+        self.mx = (mouse.x * self.screen_w / self.pointer_w.max(1)).min(self.screen_w - 1);
+        self.my = (mouse.y * self.screen_h / self.pointer_h.max(1)).min(self.screen_h - 1);
 
         if self.mx != self.prev_mx || self.my != self.prev_my {
-            let pad = 20;
-            self.mark_dirty(self.prev_mx.saturating_sub(pad), self.prev_my.saturating_sub(pad), pad * 2, pad * 2);
-            self.mark_dirty(self.mx.saturating_sub(pad), self.my.saturating_sub(pad), pad * 2, pad * 2);
-        }
-
-        if self.left_click && !self.prev_left {
-            let mut clicked_idx: Option<usize> = None;
-
-            let btn_w = 70; let btn_x = (self.screen_stride / 2) - 35; let btn_y = self.screen_h - 36 + 6; 
-            let net_x = self.screen_stride - 50; let net_w = 30;
-            let menu_w = 180; let menu_h = 200;
-            let menu_x = (self.screen_stride / 2) - (menu_w / 2); let menu_y = self.screen_h - 36 - menu_h - 10;
-
-            if self.start_menu_open && self.mx >= menu_x && self.mx <= menu_x + menu_w && self.my >= menu_y && self.my <= menu_y + menu_h {
-                let rel_y = self.my - menu_y;
-                if rel_y < 40 { if sys_fork() == 0 { sys_execve("/mnt/nvme/apps/Terminal.nyx/run.bin\0"); sys_exit(1); } }
-                else if rel_y < 80 { if sys_fork() == 0 { sys_execve("/mnt/nvme/apps/Settings.nyx/run.bin\0"); sys_exit(1); } }
-                else if rel_y < 120 { if sys_fork() == 0 { sys_execve("/mnt/nvme/apps/Explorer.nyx/run.bin\0"); sys_exit(1); } }
-                else if rel_y < 160 { if sys_fork() == 0 { sys_execve("/mnt/nvme/apps/Network.nyx/run.bin\0"); sys_exit(1); } }
-                else { if sys_fork() == 0 { sys_execve("/mnt/nvme/apps/SystemMonitor.nyx/run.bin\0"); sys_exit(1); } }
-                
-                self.start_menu_open = false; 
-                self.mark_full_redraw();
-            } 
-            else if self.mx >= btn_x && self.mx <= btn_x + btn_w && self.my >= btn_y && self.my <= btn_y + 24 {
-                self.start_menu_open = !self.start_menu_open; 
-                self.mark_full_redraw();
+            if !self.hw_cursor {
+                let pad = 20;
+                self.mark_dirty(self.prev_mx.saturating_sub(pad), self.prev_my.saturating_sub(pad), pad * 2, pad * 2);
+                self.mark_dirty(self.mx.saturating_sub(pad), self.my.saturating_sub(pad), pad * 2, pad * 2);
             }
-            else if self.mx >= net_x && self.mx <= net_x + net_w && self.my >= btn_y && self.my <= btn_y + 24 {
-                if sys_fork() == 0 { sys_execve("/bin/nyx-network\0"); sys_exit(1); }
-                self.start_menu_open = false; 
-                self.mark_full_redraw();
-            } else {
-                if self.start_menu_open { self.start_menu_open = false; self.mark_full_redraw(); }
-
-                for (idx, client) in self.clients.iter_mut().enumerate().rev() {
-                    if !client.win.exists { continue; }
-                    let win_x = client.win.x; let win_y = client.win.y; let win_w = client.win.w; 
-                    let win_h = if client.win.is_minimized { 30 } else { client.win.h + 30 };
-
-                    if !client.win.is_minimized && !client.win.is_maximized && 
-                       self.mx >= win_x + win_w - 15 && self.mx <= win_x + win_w && 
-                       self.my >= win_y + win_h - 15 && self.my <= win_y + win_h {
-                        self.is_resizing = true;
-                        self.resizing_win_idx = Some(idx);
-                        clicked_idx = Some(idx); break;
-                    }
+            if self.start_menu.update_hover(self.mx, self.my) { self.mark_menu_dirty(); }
+            if self.drag.as_ref().is_some_and(|d| d.phase == DragPhase::Active) {
+                let (x, y, w, h) = ghost_bounds(self.prev_mx, self.prev_my);
+                self.mark_dirty(x, y, w, h);
+                let (x, y, w, h) = ghost_bounds(self.mx, self.my);
+                self.mark_dirty(x, y, w, h);
+            }
+        }
 
-                    if self.mx >= win_x + 12 && self.mx <= win_x + 24 && self.my >= win_y + 10 && self.my <= win_y + 22 {
-                        client.win.exists = false; 
-                        sys_ipc_send(client.owner_pid, MSG_WINDOW_CLOSE, 0, 0); 
-                        self.mark_dirty(win_x, win_y, win_w + 15, win_h + 15);
-                        clicked_idx = Some(idx); break;
+        let now = sys_get_time();
+        let sample = pointer::Sample { x: self.mx, y: self.my, left: mouse.left, right: mouse.right, middle: mouse.middle, wheel: mouse.wheel };
+        let events = self.pointer.update(sample, now);
+        for &event in &events {
+            match event {
+                PointerEvent::Press { x, y, button: MouseButton::Left } => {
+                    let double = events.contains(&PointerEvent::DoubleClick { x, y, button: MouseButton::Left });
+                    self.on_press(double);
+                },
+                PointerEvent::DragStart { button: MouseButton::Left, .. } => {
+                    if let Some(drag) = self.drag.as_mut().filter(|d| d.phase == DragPhase::Pressed) {
+                        drag.phase = DragPhase::Querying;
+                        let _ = sys_ipc_send(drag.source_pid, MSG_DRAG_BEGIN, drag.rel_x as u64, drag.rel_y as u64);
                     }
+                },
+                PointerEvent::DragMove { button: MouseButton::Left, .. } => self.on_drag_move(),
+                PointerEvent::Release { button: MouseButton::Left, .. } => self.on_release(),
+                PointerEvent::Wheel { notches, .. } if !self.start_menu.is_open => self.deliver_wheel(notches),
+                _ => {}
+            }
+        }
+    }
 
-                    if self.mx >= win_x + 28 && self.mx <= win_x + 40 && self.my >= win_y + 10 && self.my <= win_y + 22 {
-                        client.win.is_minimized = !client.win.is_minimized;
-                        let (w, h) = (client.win.w, client.win.h);
-                        self.mark_dirty(win_x, win_y, w + 15, h + 45); 
-                        clicked_idx = Some(idx); break;
-                    }
+    /// Left button went down: start menu, taskbar, window chrome, desktop icons,
+    /// or the start of a click / drag in a client area.
+    fn on_press(&mut self, double: bool) {
+        let mut clicked_idx: Option<usize> = None;
+        let mut maximize_idx: Option<usize> = None;
+        let mut close_idx: Option<usize> = None;
 
-                    if self.mx >= win_x + 44 && self.mx <= win_x + 56 && self.my >= win_y + 10 && self.my <= win_y + 22 {
-                        if client.win.is_maximized {
-                            client.win.x = client.win.saved_x; client.win.y = client.win.saved_y;
-                            client.win.w = client.win.saved_w; client.win.h = client.win.saved_h;
-                            client.win.is_maximized = false;
-                        } else {
-                            client.win.saved_x = client.win.x; client.win.saved_y = client.win.y;
-                            client.win.saved_w = client.win.w; client.win.saved_h = client.win.h;
-                            client.win.x = 0; client.win.y = 0;
-                            client.win.w = self.screen_w; client.win.h = self.screen_h - 36 - 30;
-                            client.win.is_maximized = true;
-                        }
-                        sys_ipc_send(client.owner_pid, MSG_WINDOW_RESIZED, client.win.w as u64, client.win.h as u64);
-                        self.mark_full_redraw();
-                        clicked_idx = Some(idx); break;
-                    }
+        // The taskbar runs along the bottom of the primary display
+        let (primary_w, primary_h) = self.primary();
+        let px = scale::px;
+        let btn_w = px(70); let btn_x = (primary_w / 2) - px(35); let btn_y = primary_h - taskbar_h() + px(6); 
+        let net_x = primary_w - px(50); let net_w = px(30);
+
+        let taskbar_hit = if self.my >= btn_y && self.my <= btn_y + taskbar_btn_h() {
+            self.taskbar_clients().into_iter().enumerate().find(|&(slot, _)| {
+                taskbar_button_x(primary_w, slot).is_some_and(|x| self.mx >= x && self.mx <= x + taskbar_btn_w())
+            }).map(|(_, idx)| idx)
+        } else { None };
+
+        if self.start_menu.contains(self.mx, self.my) {
+            if let Some(path) = self.start_menu.on_click(self.mx, self.my) { self.run_menu_item(path); }
+            self.mark_full_redraw();
+        } 
+        else if self.mx >= btn_x && self.mx <= btn_x + btn_w && self.my >= btn_y && self.my <= btn_y + taskbar_btn_h() {
+            self.start_menu.toggle(); 
+            self.mark_full_redraw();
+        }
+        else if let Some(idx) = taskbar_hit {
+            // Clicking the focused window's button minimizes it, otherwise restore and focus.
+            // No-focus windows are never focused, so theirs just shows and hides them.
+            let client = &self.clients[idx];
+            if self.active_client() == Some(idx) || (client.no_focus && !client.win.is_minimized) {
+                self.clients[idx].win.is_minimized = true;
+            } else {
+                self.clients[idx].win.is_minimized = false;
+                self.raise(idx);
+            }
+            self.start_menu.close();
+            self.mark_full_redraw();
+        }
+        else if self.mx >= net_x && self.mx <= net_x + net_w && self.my >= btn_y && self.my <= btn_y + taskbar_btn_h() {
+            self.launch("/bin/nyx-network\0");
+            self.start_menu.close(); 
+            self.mark_full_redraw();
+        } else {
+            if self.start_menu.is_open { self.start_menu.close(); self.mark_full_redraw(); }
 
-                    if self.mx >= win_x && self.mx <= win_x + win_w && self.my >= win_y && self.my <= win_y + 30 {
-                        if !client.win.is_maximized {
-                            self.dragging_win_idx = Some(idx); 
-                            self.drag_off_x = self.mx - win_x; 
-                            self.drag_off_y = self.my - win_y; 
+            let over_window = self.clients.iter().any(|c| {
+                c.win.exists && !c.win.is_minimized &&
+                self.mx >= c.win.x && self.mx <= c.win.x + c.win.w && self.my >= c.win.y && self.my <= c.win.y + c.win.h + title_bar_h()
+            });
+            let on_taskbar = self.mx < primary_w && self.my >= primary_h - taskbar_h() && self.my < primary_h;
+            if !over_window && !on_taskbar {
+                let old_sel = self.desktop.selected;
+                match self.desktop.on_click(self.mx, self.my, double) {
+                    DesktopAction::Open(idx) => {
+                        let path = self.desktop.path_of(idx);
+                        self.open_in_explorer(path);
+                    },
+                    DesktopAction::Redraw => {
+                        for idx in [old_sel, self.desktop.selected].into_iter().flatten() {
+                            let (x, y, w, h) = self.desktop.bounds(idx);
+                            self.mark_dirty(x, y, w, h);
                         }
-                        clicked_idx = Some(idx); break; 
-                    }
-                    
-                    if !client.win.is_minimized && self.mx >= win_x && self.mx <= win_x + win_w && self.my > win_y + 30 && self.my <= win_y + win_h {
-                        sys_ipc_send(client.owner_pid, MSG_MOUSE_EVENT, (self.mx - win_x) as u64, (self.my - (win_y + 30)) as u64);
-                        clicked_idx = Some(idx); break; 
-                    }
+                    },
+                    DesktopAction::None => {},
                 }
+            }
 
-                if let Some(idx) = clicked_idx {
-                    if idx != self.clients.len() - 1 {
-                        let moved_client = self.clients.remove(idx);
-                        self.clients.push(moved_client);
-                        if self.dragging_win_idx == Some(idx) { self.dragging_win_idx = Some(self.clients.len() - 1); }
-                        if self.resizing_win_idx == Some(idx) { self.resizing_win_idx = Some(self.clients.len() - 1); }
-                        self.mark_full_redraw();
+            for (idx, client) in self.clients.iter_mut().enumerate().rev() {
+                if !client.win.exists || client.win.is_minimized { continue; }
+                let win_x = client.win.x; let win_y = client.win.y; let win_w = client.win.w; 
+                let win_h = client.win.h + title_bar_h();
+
+                if !client.win.is_maximized && 
+                   self.mx >= win_x + win_w - px(15) && self.mx <= win_x + win_w && 
+                   self.my >= win_y + win_h - px(15) && self.my <= win_y + win_h {
+                    self.is_resizing = true;
+                    self.resizing_win_idx = Some(idx);
+                    clicked_idx = Some(idx); break;
+                }
+
+                if self.mx >= win_x + px(12) && self.mx <= win_x + px(24) && self.my >= win_y + px(10) && self.my <= win_y + px(22) {
+                    close_idx = Some(idx);
+                    clicked_idx = Some(idx); break;
+                }
+
+                if self.mx >= win_x + px(28) && self.mx <= win_x + px(40) && self.my >= win_y + px(10) && self.my <= win_y + px(22) {
+                    // Hidden until its taskbar button is clicked; it keeps its place in z-order
+                    client.win.is_minimized = true;
+                    self.mark_full_redraw();
+                    break;
+                }
+
+                if self.mx >= win_x + px(44) && self.mx <= win_x + px(56) && self.my >= win_y + px(10) && self.my <= win_y + px(22) {
+                    maximize_idx = Some(idx);
+                    clicked_idx = Some(idx); break;
+                }
+
+                if self.mx >= win_x && self.mx <= win_x + win_w && self.my >= win_y && self.my <= win_y + title_bar_h() {
+                    if double {
+                        maximize_idx = Some(idx);
+                    } else {
+                        // Maximized windows start dragging too; they restore once the mouse moves
+                        self.dragging_win_idx = Some(idx); 
+                        self.drag_off_x = self.mx - win_x; 
+                        self.drag_off_y = self.my - win_y; 
                     }
+                    clicked_idx = Some(idx); break; 
                 }
-            }
-        } else if self.left_click {
-            if let Some(idx) = self.resizing_win_idx {
-                let w = self.clients[idx].win.w + 15; let h = self.clients[idx].win.h + 45;
-                self.mark_dirty(self.clients[idx].win.x, self.clients[idx].win.y, w, h);
                 
-                let new_w = self.mx.saturating_sub(self.clients[idx].win.x).max(200); 
-                let new_h = self.my.saturating_sub(self.clients[idx].win.y + 30).max(100); 
-                
-                if new_w != self.clients[idx].win.w || new_h != self.clients[idx].win.h {
-                    self.clients[idx].win.w = new_w;
-                    self.clients[idx].win.h = new_h;
-                    sys_ipc_send(self.clients[idx].owner_pid, MSG_WINDOW_RESIZED, new_w as u64, new_h as u64);
+                if self.mx >= win_x && self.mx <= win_x + win_w && self.my > win_y + title_bar_h() && self.my <= win_y + win_h {
+                    let (rel_x, rel_y) = (self.mx - win_x, self.my - (win_y + title_bar_h()));
+                    if double {
+                        // Its release is not another click
+                        let _ = sys_ipc_send(client.owner_pid, MSG_MOUSE_DOUBLE_CLICK, rel_x as u64, rel_y as u64);
+                    } else {
+                        // Delivered as a click on release unless it turns into a drag
+                        self.drag = Some(DragState {
+                            phase: DragPhase::Pressed, source_pid: client.owner_pid,
+                            rel_x, rel_y, payload: String::new(),
+                        });
+                    }
+                    clicked_idx = Some(idx); break; 
                 }
-                
-                self.mark_dirty(self.clients[idx].win.x, self.clients[idx].win.y, new_w + 15, new_h + 45);
-            } else if let Some(idx) = self.dragging_win_idx {
-                let w = self.clients[idx].win.w + 15; let h = self.clients[idx].win.h + 45;
-                self.mark_dirty(self.clients[idx].win.x, self.clients[idx].win.y, w, h);
-                
-                self.clients[idx].win.x = self.mx.saturating_sub(self.drag_off_x); 
-                self.clients[idx].win.y = self.my.saturating_sub(self.drag_off_y);
-                
-                self.mark_dirty(self.clients[idx].win.x, self.clients[idx].win.y, w, h);
             }
-        } else if !self.left_click { 
-            self.dragging_win_idx = None; 
-            self.resizing_win_idx = None;
-            self.is_resizing = false;
+
+            if let Some(idx) = close_idx { self.close_window(idx); }
+            if let Some(idx) = maximize_idx { self.toggle_maximize(idx); }
+            if let Some(idx) = clicked_idx { self.raise(idx); }
         }
-        
-        self.prev_left = self.left_click;
+    }
+
+    /// Moves or resizes the grabbed window.
+    fn on_drag_move(&mut self) {
+        if let Some(idx) = self.resizing_win_idx {
+            self.mark_window_dirty(idx);
+            
+            let new_w = self.mx.saturating_sub(self.clients[idx].win.x).max(min_win_w()); 
+            let new_h = self.my.saturating_sub(self.clients[idx].win.y + title_bar_h()).max(min_win_h()); 
+            
+            if new_w != self.clients[idx].win.w || new_h != self.clients[idx].win.h {
+                self.clients[idx].win.w = new_w;
+                self.clients[idx].win.h = new_h;
+                let _ = sys_ipc_send(self.clients[idx].owner_pid, MSG_WINDOW_RESIZED, new_w as u64, new_h as u64);
+            }
+            
+            self.mark_window_dirty(idx);
+        } else if let Some(idx) = self.dragging_win_idx {
+            if self.clients[idx].win.is_maximized && (self.mx != self.prev_mx || self.my != self.prev_my) {
+                // Keep the grab point at the same relative spot on the restored title bar
+                let max_w = self.clients[idx].win.w.max(1);
+                self.toggle_maximize(idx);
+                self.drag_off_x = self.drag_off_x * self.clients[idx].win.w / max_w;
+                self.drag_off_y = self.drag_off_y.min(title_bar_h() - 1);
+            }
+
+            self.mark_window_dirty(idx);
+            
+            self.clients[idx].win.x = self.mx.saturating_sub(self.drag_off_x); 
+            self.clients[idx].win.y = self.my.saturating_sub(self.drag_off_y);
+            
+            self.mark_window_dirty(idx);
+        }
+    }
+
+    fn on_release(&mut self) {
+        if self.dragging_win_idx.is_some() || self.resizing_win_idx.is_some() { self.session.touch(); }
+        self.dragging_win_idx = None; 
+        self.resizing_win_idx = None;
+        self.is_resizing = false;
+        if let Some(drag) = self.drag.take() { self.finish_drag(drag); }
     }
 
     pub fn update(&mut self) {
         for i in 0..self.clients.len() {
             if self.clients[i].win.exists && self.clients[i].win.opacity < 255 {
                 self.clients[i].win.opacity = self.clients[i].win.opacity.saturating_add(15);
-                let (x, y, w, h) = (
-                    self.clients[i].win.x, self.clients[i].win.y, 
-                    self.clients[i].win.w + 15, self.clients[i].win.h + 45
-                );
-                self.mark_dirty(x, y, w, h);
+                self.mark_window_dirty(i);
+            }
+        }
+
+        // The app the user is looking at gets the scheduler's foreground boost
+        let focused = self.active_client().map_or(0, |i| self.clients[i].owner_pid);
+        if focused != self.foreground_pid {
+            self.foreground_pid = focused;
+            sys_set_foreground(focused);
+        }
+
+        // Also catches the clock being set, which can move it by hours
+        let minute = sys_wall_clock() / 60;
+        if minute != self.clock_minute {
+            self.clock_minute = minute;
+            let (primary_w, primary_h) = self.primary();
+            self.mark_dirty(0, primary_h - taskbar_h(), primary_w, taskbar_h());
+        }
+
+        // Once a second: files saved or deleted on the desktop show up without a restart
+        let now = sys_get_time();
+        if now.wrapping_sub(self.fs_checked_at) >= 1000 {
+            self.fs_checked_at = now;
+            let generation = sys_fs_generation();
+            if generation != self.fs_generation {
+                self.fs_generation = generation;
+                self.desktop.reload();
+                let (primary_w, primary_h) = self.primary();
+                self.mark_dirty(0, 0, primary_w, primary_h - taskbar_h());
             }
         }
     }
@@ -303,15 +1173,21 @@ impl CompositorState {
 #[link_section = ".text.entry"]
 pub extern "C" fn _start() -> ! {
     const HEAP_PAGES: usize = 4096; 
-    let heap_start = sys_alloc_pages(HEAP_PAGES);
-    if heap_start == 0 { sys_exit(1); }
-    unsafe { ALLOCATOR.lock().init(heap_start as *mut u8, HEAP_PAGES * 4096); }
+    if !ALLOCATOR.init(HEAP_PAGES * 4096) { sys_exit(1); }
 
-    let (screen_w, screen_h, screen_stride) = sys_get_screen_info();
+    // The boot mode; "native" in the config always means this one
+    let (native_w, native_h, native_stride) = sys_get_screen_info();
     let fb_ptr = sys_map_framebuffer();
-    let hardware_fb = unsafe { core::slice::from_raw_parts_mut(fb_ptr as *mut u32, screen_stride * screen_h) };
+    // Frames are drawn as 0xAARRGGBB, at the resolution from the boot config
+    let mut screen = Screen::new(fb_ptr, sys_get_fb_format(), native_w, native_h, native_stride);
+    let layout = DesktopLayout::query(native_w, native_h);
+    screen.resize(layout.w, layout.h);
     
-    let mut state = CompositorState::new(screen_w, screen_h, screen_stride);
+    theme::load();
+    scale::load();
+    let mut state = CompositorState::new(layout, screen.stride);
+    state.load_wallpaper(DEFAULT_WALLPAPER);
+    state.restore_session();
 
     let mut last_frame = sys_get_time();
     let ms_per_frame = 1000 / 60; 
@@ -323,82 +1199,121 @@ pub extern "C" fn _start() -> ! {
         state.process_input();
         state.update();
 
+        if let Some((w, h)) = state.pending_mode.take() {
+            match sys_set_resolution(w, h) {
+                Ok(()) => {
+                    // Under the Bochs adapter the framebuffer itself changed mode
+                    let (fb_w, fb_h, fb_stride) = sys_get_screen_info();
+                    if (fb_w, fb_h, fb_stride) != (screen.native_w, screen.native_h, screen.native_stride) {
+                        screen = Screen::new(sys_map_framebuffer(), sys_get_fb_format(), fb_w, fb_h, fb_stride);
+                    }
+                    let (w, h) = sys_get_resolution();
+                    save_config(&[(RESOLUTION_KEY, &resolution_value(w, h, (native_w, native_h)))]);
+                    state.relayout = true;
+                },
+                Err(_) => sys_print("[COMPOSITOR] The screen can't show that resolution\n"),
+            }
+        }
+        if core::mem::take(&mut state.relayout) {
+            let layout = DesktopLayout::query(screen.native_w, screen.native_h);
+            screen.resize(layout.w, layout.h);
+            state.apply_layout(layout, screen.stride);
+        }
+        if state.session.save_due(sys_get_time()) { state.save_session(); }
+
         let now = sys_get_time();
         if !state.needs_redraw && now.wrapping_sub(last_frame) < ms_per_frame { 
-            sys_sleep_ms(2); 
+            // Input wakes us early, so sleeping out the frame costs no latency
+            sys_sleep_ms((ms_per_frame - now.wrapping_sub(last_frame)) as u64); 
             continue; 
         }
         last_frame = now;
 
         if state.needs_redraw {
-            state.mark_dirty(state.mx.saturating_sub(15), state.my.saturating_sub(15), 35, 35);
+            if !state.hw_cursor { state.mark_dirty(state.mx.saturating_sub(15), state.my.saturating_sub(15), 35, 35); }
+            let frame_start = sys_uptime_us();
 
-            // 1. Submit GPU background fill for the ENTIRE screen (Asynchronous)
-            sys_gpu_fill_rect(0, 0, screen_stride, screen_h, Color::WARM_BG);
+            let (dirty_x, dirty_y, dirty_x1, dirty_y1) = state.dirty.get().unwrap_or((0, 0, 0, 0));
+            let (dirty_w, dirty_h) = (dirty_x1 - dirty_x, dirty_y1 - dirty_y);
 
-            // 2. Synchronize! Wait for GPU wallpaper clear to finish before CPU starts drawing
-            sys_gpu_sync();
+            let direct = screen.is_direct();
+            let (stride, screen_h) = (screen.stride, screen.h);
+            let hardware_fb = screen.frame();
+            let theme = theme::current();
+            if state.wallpaper.is_some() {
+                // 1. Copy the cached wallpaper back over the dirty region
+                restore_wallpaper_rect(hardware_fb, stride, screen_h, (dirty_x, dirty_y, dirty_x1, dirty_y1), state.wallpaper.as_ref());
+            } else if !direct {
+                // 1. The GPU fills the real framebuffer, not the off-screen frame
+                Canvas::new(hardware_fb, stride, screen_h).fill_rect(dirty_x, dirty_y, dirty_w, dirty_h, theme.window_bg);
+            } else {
+                // 1. Submit GPU background fill for the dirty region only (Asynchronous)
+                sys_gpu_fill_rect(dirty_x, dirty_y, dirty_w, dirty_h, theme.window_bg);
+
+                // 2. Synchronize! Wait for GPU wallpaper clear to finish before CPU starts drawing
+                sys_gpu_sync();
+            }
+
+            // 3. Perform CPU drawing (Text, Window Borders, Windows, Taskbar, Cursor), clipped to the dirty region
+            let mut canvas = Canvas::new(hardware_fb, stride, screen_h);
+            canvas.set_clip(dirty_x, dirty_y, dirty_w, dirty_h);
 
-            // 3. Perform CPU drawing (Text, Window Borders, Windows, Taskbar, Cursor)
-            let mut canvas = Canvas::new(hardware_fb, screen_stride, screen_h);
+            state.desktop.draw(&mut canvas);
+
+            // Blit each window's own buffer in Z-order; windows outside the dirty region are skipped entirely
+            let active_idx = state.active_client();
+            for (idx, client) in state.clients.iter().enumerate() {
+                if client.win.exists && !client.win.is_minimized {
+                    let (bx, by, bw, bh) = window_bounds(&client.win);
+                    if bx >= dirty_x + dirty_w || bx + bw <= dirty_x
+                        || by >= dirty_y + dirty_h || by + bh <= dirty_y { continue; }
+
+                    if active_idx == Some(idx) { draw_window_shadow(&mut canvas, &client.win); }
 
-            // Draw window decorations and CPU client compositing sequentially in Z-order
-            for client in state.clients.iter() {
-                if client.win.exists {
                     // Draw window border, white background, and title bar
-                    draw_window_rounded(canvas.buffer, screen_stride, screen_h, &client.win);
+                    draw_window_rounded(&mut canvas, &client.win, active_idx == Some(idx));
                     
-                    if !client.win.is_minimized {
-                        if client.buffer.is_null() || client.buffer as u64 == 0 { continue; }
-                        
-                        let expected_size = client.buf_w * client.buf_h;
-                        let client_pixels = unsafe { core::slice::from_raw_parts(client.buffer, expected_size) };
-                        canvas.composite_buffer(client.win.x, client.win.y + 30, client_pixels, client.buf_w, client.buf_h, client.win.opacity);
-                    }
+                    if client.buffer.is_null() || client.buffer as u64 == 0 { continue; }
+                    
+                    let expected_size = client.buf_w * client.buf_h;
+                    let client_pixels = unsafe { core::slice::from_raw_parts(client.buffer, expected_size) };
+                    canvas.composite_buffer(client.win.x, client.win.y + title_bar_h(), client_pixels, client.buf_w, client.buf_h, client.win.opacity);
                 }
             }
 
             // 5. Draw Taskbar on top of windows (CPU-based fills and text)
-            let bar_h = 36;
-            let start_y = screen_h - bar_h;
-            canvas.fill_rect(0, start_y, screen_stride, bar_h, 0xFF_FFFFFF); // Opaque white taskbar
-            canvas.fill_rect(0, start_y, screen_stride, 1, 0xFF_D1D1D1);     // Border
-            
-            // Draw Start Button
-            let btn_x = (screen_stride / 2) - 35;
-            canvas.fill_rect(btn_x, start_y + 6, 70, 24, Color::ACCENT_PRIMARY);
-
-            // Draw taskbar text
-            canvas.print_str(20, start_y + 14, "10:20 AM", Color::TEXT_DARK, 1);
-            canvas.print_str(btn_x + 15, start_y + 8, "NYX", Color::WHITE, 1);
-            
-            let net_x = screen_stride - 50; let btn_y = screen_h - 36 + 6;
-            canvas.print_str(net_x, btn_y + 4, "[WIFI]", Color::WHITE, 1);
+            let taskbar_wins: Vec<&Window> = state.taskbar_clients().into_iter().map(|i| &state.clients[i].win).collect();
+            let active_id = state.active_client().map(|i| state.clients[i].win.id);
+            let (primary_w, primary_h) = state.primary();
+            draw_taskbar(&mut canvas, primary_w, primary_h, &taskbar_wins, active_id, &clock_text(sys_wall_clock()));
 
             // Draw Start Menu on top of windows
-            if state.start_menu_open {
-                let menu_w = 180; let menu_h = 200;
-                let menu_x = (screen_stride / 2) - (menu_w / 2); let menu_y = screen_h - 36 - menu_h - 10;
-                
-                canvas.fill_rect(menu_x, menu_y, menu_w, menu_h, 0xFF_111111);
-                canvas.fill_rect(menu_x, menu_y, menu_w, 2, Color::NYX_ORANGE);
-                
-                canvas.print_str(menu_x + 20, menu_y + 12, "> Terminal", Color::WHITE, 1);
-                canvas.print_str(menu_x + 20, menu_y + 52, "> Settings", Color::WHITE, 1);
-                canvas.print_str(menu_x + 20, menu_y + 92, "> Explorer", Color::WHITE, 1);
-                canvas.print_str(menu_x + 20, menu_y + 132, "> Network Suite", Color::WHITE, 1);
-                canvas.print_str(menu_x + 20, menu_y + 172, "> System Monitor", Color::WHITE, 1);
+            state.start_menu.draw(&mut canvas);
+            state.switcher.draw(&mut canvas);
+
+            let dragging = match state.drag.as_ref() { Some(d) if d.phase == DragPhase::Active => Some(d), _ => None };
+            if let Some(drag) = dragging {
+                let (gx, gy, gw, gh) = ghost_bounds(state.mx, state.my);
+                canvas.fill_rect(gx, gy, gw, gh, 0xB0_000000 | (theme.surface & 0x00FF_FFFF));
+                canvas.fill_rect(gx, gy, 4, gh, theme.accent);
+                let name = drag.payload.rsplit('/').next().unwrap_or("");
+                let end = name.char_indices().nth((gw - 16) / 8).map(|(i, _)| i).unwrap_or(name.len());
+                canvas.print_str(gx + 10, gy + 8, &name[..end], theme.text_primary, 1);
             }
 
-            draw_cursor(canvas.buffer, screen_stride, screen_h, state.mx, state.my, CursorType::Arrow);
+            if !state.hw_cursor {
+                let cursor = if dragging.is_some() { CursorType::Hand } else { CursorType::Arrow };
+                draw_cursor(&mut canvas, state.mx, state.my, cursor);
+            }
 
-            sys_swap_buffers();
+            let (fx, fy, fw, fh) = screen.present(dirty_x, dirty_y, dirty_w, dirty_h);
+            sys_swap_buffers_rect(fx, fy, fw, fh);
             sys_gpu_sync();
+            state.record_frame_time(sys_uptime_us().wrapping_sub(frame_start) as usize);
 
             state.prev_mx = state.mx; 
             state.prev_my = state.my;
-            state.dirty_min_x = screen_stride; state.dirty_min_y = screen_h; 
-            state.dirty_max_x = 0; state.dirty_max_y = 0;
+            state.dirty.take();
             state.needs_redraw = false;
         }
     }
@@ -0,0 +1,156 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use nyx_api::*;
+use nyx_core::kv::KvFile;
+use nyx_gui::state::{self, STATE_PATH};
+
+// ==========================================
+// SESSION
+// ==========================================
+// The desktop state file, kept here because the window server is its only
+// writer. It remembers each window by title (so a relaunched app lands where
+// it was), whether it was open when things last changed, and the key=value
+// pairs apps send with MSG_SAVE_STATE. Changes go to disk at most every
+// SAVE_INTERVAL_MS. A missing or half-written file only means that less is
+// restored; geometry is clamped to the screen by the caller.
+
+const SAVE_INTERVAL_MS: usize = 3000;
+// Closed windows are remembered too, the oldest dropped past this many
+const MAX_WINDOWS: usize = 32;
+const WINDOW_PREFIX: &str = "window.";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Geometry {
+    pub x: usize, pub y: usize, pub w: usize, pub h: usize,
+    pub maximized: bool,
+}
+
+struct SavedWindow {
+    title: String,
+    // The binary the window server launched it from; empty when it didn't
+    app: String,
+    geometry: Geometry,
+    open: bool,
+}
+
+pub struct Session {
+    // Everything in the file but the window records
+    file: KvFile,
+    // Least recently changed first
+    windows: Vec<SavedWindow>,
+    // Apps launched from here that have no window yet, and their binaries
+    launches: Vec<(u64, String)>,
+    dirty: bool,
+    last_save: usize,
+}
+
+impl Session {
+    pub fn load() -> Self {
+        let mut file = state::load();
+        let mut windows = Vec::new();
+        for n in 0..MAX_WINDOWS {
+            let key = |field: &str| alloc::format!("{}{}.{}", WINDOW_PREFIX, n, field);
+            // A record short of any field is one the file was cut off in
+            let (Some(title), Some(x), Some(y), Some(w), Some(h)) = (
+                file.get(&key("title")), file.get_parsed(&key("x")), file.get_parsed(&key("y")),
+                file.get_parsed(&key("w")), file.get_parsed(&key("h")),
+            ) else { continue };
+            windows.push(SavedWindow {
+                title: String::from(title),
+                app: String::from(file.get(&key("app")).unwrap_or("")),
+                geometry: Geometry { x, y, w, h, maximized: file.get(&key("max")) == Some("1") },
+                open: file.get(&key("open")) == Some("1"),
+            });
+        }
+        file.remove_prefix(WINDOW_PREFIX);
+        Self { file, windows, launches: Vec::new(), dirty: false, last_save: 0 }
+    }
+
+    /// Binaries of the windows that were open, to launch again at startup.
+    pub fn apps_to_restore(&self) -> Vec<String> {
+        let mut apps: Vec<String> = Vec::new();
+        for win in self.windows.iter().filter(|w| w.open && !w.app.is_empty()) {
+            if !apps.contains(&win.app) { apps.push(win.app.clone()); }
+        }
+        apps
+    }
+
+    /// Where the window titled `title` was last seen.
+    pub fn placement(&self, title: &str) -> Option<Geometry> {
+        self.windows.iter().find(|w| w.title == title).map(|w| w.geometry)
+    }
+
+    pub fn launched(&mut self, pid: u64, app: &str) {
+        self.launches.push((pid, String::from(app.trim_end_matches('\0'))));
+    }
+
+    /// The binary `pid` was launched from, once its window shows up.
+    pub fn take_launch(&mut self, pid: u64) -> String {
+        match self.launches.iter().position(|(p, _)| *p == pid) {
+            Some(pos) => self.launches.remove(pos).1,
+            None => String::new(),
+        }
+    }
+
+    /// Updates the record for `title`; `app` only replaces a known binary
+    /// when it names one.
+    pub fn record(&mut self, title: &str, app: &str, geometry: Geometry, open: bool) {
+        let old_app = match self.windows.iter().position(|w| w.title == title) {
+            Some(pos) => self.windows.remove(pos).app,
+            None => String::new(),
+        };
+        let app = if app.is_empty() { old_app } else { String::from(app) };
+        self.windows.push(SavedWindow { title: String::from(title), app, geometry, open });
+        if self.windows.len() > MAX_WINDOWS { self.windows.remove(0); }
+        self.dirty = true;
+    }
+
+    /// Takes a "key=value" pair from an app. The window records are the
+    /// window server's own, so apps can't set those keys.
+    pub fn set(&mut self, pair: &str) {
+        let Some((key, value)) = pair.split_once('=') else { return };
+        let key = key.trim();
+        if key.is_empty() || key.starts_with(WINDOW_PREFIX) { return; }
+        self.file.set(key, value);
+        self.dirty = true;
+    }
+
+    /// Something changed that record() doesn't cover; it's saved with the next batch.
+    pub fn touch(&mut self) { self.dirty = true; }
+
+    pub fn save_due(&self, now: usize) -> bool {
+        self.dirty && now.wrapping_sub(self.last_save) >= SAVE_INTERVAL_MS
+    }
+
+    pub fn save(&mut self, now: usize) {
+        let mut file = self.file.clone();
+        for (n, win) in self.windows.iter().enumerate() {
+            let key = |field: &str| alloc::format!("{}{}.{}", WINDOW_PREFIX, n, field);
+            let g = win.geometry;
+            file.set(&key("title"), &win.title);
+            file.set(&key("app"), &win.app);
+            file.set(&key("x"), &alloc::format!("{}", g.x));
+            file.set(&key("y"), &alloc::format!("{}", g.y));
+            file.set(&key("w"), &alloc::format!("{}", g.w));
+            file.set(&key("h"), &alloc::format!("{}", g.h));
+            file.set(&key("max"), if g.maximized { "1" } else { "0" });
+            file.set(&key("open"), if win.open { "1" } else { "0" });
+        }
+        if sys_fs_write(STATE_PATH, file.to_text().as_bytes(), 0, FS_WRITE_TRUNCATE).is_err() {
+            sys_print("[COMPOSITOR] Could not save the desktop state\n");
+        }
+        self.dirty = false;
+        self.last_save = now;
+    }
+
+    /// Forgets everything and deletes the file.
+    pub fn reset(&mut self) {
+        self.file.clear();
+        self.windows.clear();
+        self.dirty = false;
+        match sys_fs_delete(STATE_PATH) {
+            Ok(()) | Err(NyxError::NotFound) => {},
+            Err(_) => sys_print("[COMPOSITOR] Could not delete the desktop state\n"),
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use nyx_gui::canvas::{Canvas, Color};
+use nyx_gui::theme;
+
+// ─────────────────────────────────────────────────────────────────────────
+// WINDOW SWITCHER
+// Alt+Tab overlay listing every open window, minimized ones included, most
+// recently focused first. It stays up while Alt is held; Tab / Shift+Tab
+// move the highlight and releasing Alt focuses the highlighted window.
+// Titles are copied when it opens, so the list can't shift under the user.
+// ─────────────────────────────────────────────────────────────────────────
+
+const SWITCHER_W: usize = 320;
+const ITEM_H: usize = 28;
+const PADDING: usize = 10;
+
+pub struct WindowSwitcher {
+    // (window id, title), empty while closed
+    entries: Vec<(usize, String)>,
+    selected: usize,
+    screen_w: usize, screen_h: usize,
+}
+
+impl WindowSwitcher {
+    pub fn new(screen_w: usize, screen_h: usize) -> Self {
+        Self { entries: Vec::new(), selected: 0, screen_w, screen_h }
+    }
+
+    pub fn is_open(&self) -> bool { !self.entries.is_empty() }
+
+    /// Opens on the window focused before the current one, like every desktop does.
+    pub fn open(&mut self, entries: Vec<(usize, String)>) {
+        self.selected = if entries.len() > 1 { 1 } else { 0 };
+        self.entries = entries;
+    }
+
+    pub fn step(&mut self, backwards: bool) {
+        let n = self.entries.len();
+        if n == 0 { return; }
+        self.selected = if backwards { (self.selected + n - 1) % n } else { (self.selected + 1) % n };
+    }
+
+    /// Closes the overlay and returns the id of the chosen window.
+    pub fn close(&mut self) -> Option<usize> {
+        let chosen = self.entries.get(self.selected).map(|(id, _)| *id);
+        self.entries.clear();
+        chosen
+    }
+
+    /// Drops the overlay without switching.
+    pub fn cancel(&mut self) {
+        self.entries.clear();
+    }
+
+    /// (x, y, w, h), centred on the screen. Only meaningful while open.
+    pub fn bounds(&self) -> (usize, usize, usize, usize) {
+        let h = self.entries.len() * ITEM_H + PADDING * 2;
+        (self.screen_w.saturating_sub(SWITCHER_W) / 2, self.screen_h.saturating_sub(h) / 2, SWITCHER_W, h)
+    }
+
+    pub fn draw(&self, canvas: &mut Canvas) {
+        if !self.is_open() { return; }
+        let (x, y, w, h) = self.bounds();
+        let accent = theme::current().accent;
+
+        canvas.fill_rect(x, y, w, h, 0xE6_111111);
+        canvas.fill_rect(x, y, w, 2, accent);
+        let max_chars = (w - PADDING * 2 - 16) / 8;
+        for (i, (_, title)) in self.entries.iter().enumerate() {
+            let item_y = y + PADDING + i * ITEM_H;
+            if i == self.selected {
+                canvas.fill_rect(x + PADDING, item_y, w - PADDING * 2, ITEM_H, 0xFF_2A2A2A);
+                canvas.fill_rect(x + PADDING, item_y, 3, ITEM_H, accent);
+            }
+            let end = title.char_indices().nth(max_chars).map(|(i, _)| i).unwrap_or(title.len());
+            canvas.print_str(x + PADDING + 12, item_y + 10, &title[..end], Color::WHITE, 1);
+        }
+    }
+}
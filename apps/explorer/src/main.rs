@@ -6,63 +6,105 @@ extern crate alloc;
 use alloc::string::String;
 use alloc::vec::Vec;
 use alloc::vec;
-use linked_list_allocator::LockedHeap;
 
 use nyx_api::*;
-use nyx_gui::app::NyxApp;
+use nyx_gui::heap::BrkHeap;
+use nyx_gui::app::{NyxApp, COMPOSITOR_PID};
+use nyx_gui::clipboard::{Clipboard, SystemClipboard};
+use nyx_gui::fileops::{self, FileCopy};
+use nyx_gui::state;
+use nyx_gui::font;
+use nyx_gui::scale::{self, px};
+use nyx_gui::theme;
 use nyx_gui::canvas::{Canvas, Color};
+use nyx_gui::icons::{draw_file_icon, FileKind, ICON_SIZE};
 use nyx_gui::ui::{Button, Widget};
+use nyx_core::date::DateTime;
+use nyx_core::gap_buffer::GapBuffer;
+use nyx_core::path;
+use nyx_core::wrap::WrappedRows;
 
 #[global_allocator]
-static ALLOCATOR: LockedHeap = LockedHeap::empty();
-
-// --- NATIVE SYSCALL WRAPPERS FOR VFS ---
-#[inline]
-unsafe fn syscall_2(id: u64, a: u64, b: u64) -> u64 {
-    let ret: u64;
-    core::arch::asm!("syscall", inlateout("rax") id => ret, in("rdi") a, in("rsi") b, out("rcx") _, out("r11") _, options(nostack, preserves_flags));
-    ret
-}
+static ALLOCATOR: BrkHeap = BrkHeap::empty();
 
-#[inline]
-unsafe fn syscall_3(id: u64, a: u64, b: u64, c: u64) -> u64 {
-    let ret: u64;
-    core::arch::asm!("syscall", inlateout("rax") id => ret, in("rdi") a, in("rsi") b, in("rdx") c, out("rcx") _, out("r11") _, options(nostack, preserves_flags));
-    ret
-}
+// Room for a few hundred typical names, so most folders list in one readdir call
+const DIRENT_BUF: usize = 8192;
+// Double-clicked images open here instead of in the editor
+const IMAGE_VIEWER_PATH: &str = "/mnt/nvme/apps/ImageViewer.nyx/run.bin";
+// Item count, free space and the selection's modified time along the bottom of the file view
+fn status_bar_h() -> usize { px(24) }
+// Buttons and the path or file name across the top of both views
+fn toolbar_h() -> usize { px(50) }
+// How often the folder is checked for changes made by other programs
+const WATCH_MS: usize = 1000;
+// The copy progress and overwrite dialogs, centered over the window
+fn modal_w() -> usize { px(360) }
+fn modal_h() -> usize { px(120) }
+// Copy / Cut / Paste sit at the right end of the status bar
+fn file_btn_w() -> usize { px(54) }
+fn file_btn_gap() -> usize { px(6) }
 
-#[inline]
-unsafe fn syscall_4(id: u64, a: u64, b: u64, c: u64, d: u64) -> u64 {
-    let ret: u64;
-    core::arch::asm!("syscall", inlateout("rax") id => ret, in("rdi") a, in("rsi") b, in("rdx") c, in("r10") d, out("rcx") _, out("r11") _, options(nostack, preserves_flags));
-    ret
+struct Listed {
+    /// Directories get a trailing '/'
+    name: String,
+    /// Negative for directories
+    size: i64,
+    mtime: u64,
+    hidden: bool,
+    read_only: bool,
 }
 
-fn get_directory_contents(path: &str) -> Vec<String> {
-    let mut files = Vec::new();
-    let count = unsafe { syscall_2(510, path.as_ptr() as u64, path.len() as u64) };
-    for i in 0..count {
-        let mut buf = [0u8; 256];
-        let len = unsafe { syscall_4(511, i, buf.as_mut_ptr() as u64, path.as_ptr() as u64, path.len() as u64) };
-        if len > 0 {
-            if let Ok(s) = core::str::from_utf8(&buf[..len as usize]) { files.push(String::from(s)); }
+/// Entries of `path` with their sizes, modified times and attributes, and the
+/// file system generation they were listed at.
+fn get_directory_listing(path: &str) -> (Vec<Listed>, u64) {
+    let mut entries = Vec::new();
+    let fd = match sys_fs_opendir(path) { Ok(fd) => fd, Err(_) => return (entries, sys_fs_generation()) };
+    let generation = sys_fs_dir_generation(fd).unwrap_or_else(|_| sys_fs_generation());
+
+    let mut buf = vec![0u8; DIRENT_BUF];
+    while let Ok(n) = sys_fs_readdir(fd, &mut buf) {
+        if n == 0 { break; }
+        for e in DirEntries::new(&buf, n) {
+            let (name, size) = if e.is_dir { (alloc::format!("{}/", e.name), -1) } else { (String::from(e.name), e.size as i64) };
+            entries.push(Listed { name, size, mtime: e.mtime, hidden: e.hidden || e.system, read_only: e.read_only });
         }
     }
-    files
+    let _ = sys_close(fd);
+    (entries, generation)
 }
 
-fn read_file(path: &str) -> String {
-    let fd = unsafe { syscall_2(2, path.as_ptr() as u64, path.len() as u64) } as i64;
-    if fd < 0 { return String::from("Error: Could not open file (Directory or Not Found)."); }
-    
-    let mut buf = vec![0u8; 8192];
-    let bytes_read = unsafe { syscall_3(0, fd as u64, buf.as_mut_ptr() as u64, buf.len() as u64) } as i64;
-    unsafe { syscall_2(3, fd as u64, 0); } 
-    
-    if bytes_read > 0 {
-        String::from_utf8_lossy(&buf[..bytes_read as usize]).into_owned()
-    } else {
-        String::from("[Empty File]")
+fn format_size(bytes: i64) -> String {
+    let b = bytes as u64;
+    if b < 1024 { alloc::format!("{} B", b) }
+    else if b < 1024 * 1024 { alloc::format!("{}.{} KB", b / 1024, (b % 1024) * 10 / 1024) }
+    else { alloc::format!("{}.{} MB", b / (1024 * 1024), (b % (1024 * 1024)) * 10 / (1024 * 1024)) }
+}
+
+// Files are streamed in fixed chunks so the editor isn't bounded by one syscall buffer.
+// Reads use bigger chunks since the kernel turns them into multi-block NVMe commands
+const IO_CHUNK: usize = 4096;
+const READ_CHUNK: usize = 64 * 1024;
+
+fn load_file(path: &str) -> NyxResult<GapBuffer> {
+    let fd = sys_open(path)?;
+
+    // The descriptor keeps the offset, so each read picks up where the last one stopped
+    let mut data: Vec<u8> = Vec::new();
+    let mut chunk = vec![0u8; READ_CHUNK];
+    loop {
+        let n = match sys_read(fd, &mut chunk) {
+            Ok(n) => n,
+            Err(e) => { let _ = sys_close(fd); return Err(e); }
+        };
+        if n == 0 { break; }
+        data.extend_from_slice(&chunk[..n]);
+    }
+    let _ = sys_close(fd);
+
+    // The editor indexes by char boundaries, so invalid UTF-8 is replaced up front
+    match String::from_utf8(data) {
+        Ok(s) => Ok(GapBuffer::from_bytes(s.into_bytes())),
+        Err(e) => Ok(GapBuffer::from_bytes(String::from_utf8_lossy(e.as_bytes()).into_owned().into_bytes())),
     }
 }
 
@@ -74,177 +116,977 @@ struct ExplorerApp {
     state: AppState,
     current_path: String,
     files: Vec<String>,
+    // Byte size per entry of `files`, negative for directories
+    sizes: Vec<i64>,
+    // Unix modified time per entry of `files`, 0 if it has none
+    mtimes: Vec<u64>,
+    read_only: Vec<bool>,
+    // sys_fs_generation() the listing was read at, and when it was last compared
+    listed_generation: u64,
+    last_watch: usize,
+    // Dot files and system entries are listed too; toggled from the toolbar
+    show_hidden: bool,
+    // Free space on the volume of current_path, if it has one that can tell
+    free_bytes: Option<u64>,
     current_page: usize,
     active_file: String,
-    editor_content: String,
+    editor: GapBuffer,
+    // Byte index of the caret inside the buffer (always on a char boundary)
+    cursor: usize,
+    // Other end of the selection, which runs between it and the caret
+    anchor: Option<usize>,
+    clipboard: SystemClipboard,
+    is_dirty: bool,
+    // Last save was rejected by the kernel; the editor header stays red until one succeeds
+    save_failed: bool,
+    // Layout of the last drawn frame, reused for cursor movement and click hit-testing
+    editor_rows: usize,
+    // The buffer wrapped at the width of the last frame; edited() keeps it current
+    editor_lines: WrappedRows,
+    editor_scroll: usize,
+    selected: Option<usize>,
+    renaming: bool,
+    rename_buffer: String,
+    status_msg: String,
+    // Breadcrumb hit boxes from the last frame: (x_start, x_end, path)
+    crumbs: Vec<(usize, usize, String)>,
+    // Folder and editor file last sent to the desktop state file
+    saved_path: String,
+    saved_file: String,
+    // Path picked with Copy or Cut, and whether Paste moves it
+    file_clip: Option<(String, bool)>,
+    // The Paste under way, a chunk per update()
+    transfer: Option<FileCopy>,
+    // A Paste waiting on "Replace?": (source, destination, move)
+    confirm_overwrite: Option<(String, String, bool)>,
+    // Top left of the dialog last drawn, for clicks on its buttons
+    modal_at: (usize, usize),
+    // Window width and status bar top in the last frame, for hit-testing clicks
+    status_bar: (usize, usize),
 }
 
 impl ExplorerApp {
     fn new() -> Self {
-        let initial_path = String::from("/mnt/nvme/apps");
-        Self {
+        let mut app = Self {
             state: AppState::Explorer,
-            files: get_directory_contents(&initial_path),
-            current_path: initial_path,
+            files: Vec::new(),
+            sizes: Vec::new(),
+            mtimes: Vec::new(),
+            read_only: Vec::new(),
+            show_hidden: false,
+            listed_generation: 0,
+            last_watch: 0,
+            free_bytes: None,
+            current_path: String::from("/mnt/nvme/apps"),
             current_page: 0,
             active_file: String::new(),
-            editor_content: String::new(),
+            editor: GapBuffer::new(),
+            cursor: 0,
+            anchor: None,
+            clipboard: SystemClipboard {},
+            is_dirty: false,
+            save_failed: false,
+            editor_rows: 1,
+            editor_lines: WrappedRows::new(&GapBuffer::new(), 1),
+            editor_scroll: 0,
+            selected: None,
+            renaming: false,
+            rename_buffer: String::new(),
+            status_msg: String::new(),
+            crumbs: Vec::new(),
+            saved_path: String::from("/mnt/nvme/apps"),
+            saved_file: String::new(),
+            file_clip: None,
+            transfer: None,
+            confirm_overwrite: None,
+            modal_at: (0, 0),
+            status_bar: (0, 0),
+        };
+        app.reload();
+        app
+    }
+
+    /// Index into `files` of the tile under (mx, my), using the same grid as draw().
+    fn tile_at(&self, mx: usize, my: usize) -> Option<usize> {
+        let width = self.status_bar.0;
+        let items_per_page = 24;
+        let start_idx = self.current_page * items_per_page;
+        let end_idx = core::cmp::min(start_idx + items_per_page, self.files.len());
+
+        let mut fx = px(20); let mut fy = toolbar_h() + px(20);
+        for idx in start_idx..end_idx {
+            if mx >= fx && mx <= fx + px(130) && my >= fy && my <= fy + px(40) { return Some(idx); }
+            fx += px(150); if fx > width.saturating_sub(px(150)) { fx = px(20); fy += px(60); }
+        }
+        None
+    }
+
+    /// Shows an absolute path: files open in the editor, directories are browsed.
+    fn open_path(&mut self, path: &str) -> bool {
+        let path = path.trim_end_matches('/');
+        if path.is_empty() { return false; }
+
+        // Anything with a size is a file
+        if sys_fs_size(path).is_ok() {
+            let split = path.rfind('/').unwrap_or(0);
+            self.current_path = if split == 0 { String::from("/") } else { String::from(&path[..split]) };
+            self.reload();
+            self.open_file(&path[split + 1..]);
+        } else {
+            self.current_path = String::from(path);
+            self.state = AppState::Explorer;
+            self.status_msg.clear();
+            self.reload();
+        }
+        true
+    }
+
+    /// Draws current_path as clickable components between x and max_x, dropping leading
+    /// components behind "..." when it doesn't fit.
+    fn draw_breadcrumbs(&mut self, canvas: &mut Canvas, x: usize, max_x: usize) {
+        let theme = theme::current();
+        let (s, text_y) = (scale::current(), px(17));
+        let parts: Vec<&str> = self.current_path.split('/').filter(|s| !s.is_empty()).collect();
+        let text_w = |s: &str| s.chars().count() * px(8);
+
+        // "/" for the root, then "name/" per component; the last one has no trailing slash
+        let widths: Vec<usize> = parts.iter().enumerate()
+            .map(|(i, p)| text_w(p) + if i + 1 < parts.len() { px(8) } else { 0 })
+            .collect();
+        let mut first = 0;
+        let ellipsis_w = if parts.is_empty() { 0 } else { px(24) };
+        while first + 1 < parts.len() && x + px(8) + ellipsis_w + widths[first..].iter().sum::<usize>() > max_x { first += 1; }
+
+        let mut cx = x;
+        canvas.print_str(cx, text_y, "/", theme.accent_hover, s);
+        self.crumbs.push((cx, cx + px(8), String::from("/")));
+        cx += px(8);
+        if first > 0 {
+            canvas.print_str(cx, text_y, "...", theme.text_secondary, s);
+            cx += px(24);
+        }
+
+        for i in first..parts.len() {
+            let is_last = i + 1 == parts.len();
+            let target = alloc::format!("/{}", parts[..=i].join("/"));
+            canvas.print_str(cx, text_y, parts[i], if is_last { theme.text_primary } else { theme.accent_hover }, s);
+            self.crumbs.push((cx, cx + text_w(parts[i]), target));
+            cx += text_w(parts[i]);
+            if !is_last {
+                canvas.print_str(cx, text_y, "/", theme.text_secondary, s);
+                cx += px(8);
+            }
+        }
+    }
+
+    fn join_path(&self, name: &str) -> String { path::join(&self.current_path, name) }
+
+    fn reload(&mut self) {
+        self.current_path = path::normalize(&self.current_path);
+        self.list();
+        self.current_page = 0;
+        self.selected = None;
+        self.renaming = false;
+        self.remember();
+    }
+
+    /// Reads the folder again after something else changed it, keeping the
+    /// page and the selected entry if it's still there.
+    fn refresh(&mut self) {
+        let selected = self.selected.and_then(|i| self.files.get(i)).cloned();
+        self.list();
+        self.selected = selected.and_then(|name| self.files.iter().position(|f| *f == name));
+        let items_per_page = 24;
+        let last_page = self.files.len().saturating_sub(1) / items_per_page;
+        self.current_page = self.current_page.min(last_page);
+    }
+
+    fn list(&mut self) {
+        let (mut listing, generation) = get_directory_listing(&self.current_path);
+        if !self.show_hidden { listing.retain(|e| !e.hidden); }
+        self.files = listing.iter().map(|e| e.name.clone()).collect();
+        self.sizes = listing.iter().map(|e| e.size).collect();
+        self.mtimes = listing.iter().map(|e| e.mtime).collect();
+        self.read_only = listing.iter().map(|e| e.read_only).collect();
+        self.listed_generation = generation;
+        self.free_bytes = sys_fs_volume_info(&self.current_path).ok().map(|v| v.free_bytes());
+    }
+
+    /// Tells the desktop state file where we are, so the next boot reopens it.
+    fn remember(&mut self) {
+        let file = if self.state == AppState::Editor { self.join_path(&self.active_file) } else { String::new() };
+        if self.current_path != self.saved_path {
+            self.saved_path = self.current_path.clone();
+            state::save("explorer.path", &self.saved_path);
+        }
+        if file != self.saved_file {
+            state::save("editor.file", &file);
+            self.saved_file = file;
+        }
+    }
+
+    fn save_file(&mut self) {
+        let path = self.join_path(&self.active_file);
+
+        // First write truncates, the rest append at the running offset
+        let (head, tail) = self.editor.as_slices();
+        let mut offset = 0;
+        let mut flags = FS_WRITE_TRUNCATE;
+        // None = ok, Some(Ok(n)) = short write of n bytes, Some(Err) = kernel error
+        let mut failure = None;
+        for chunk in head.chunks(IO_CHUNK).chain(tail.chunks(IO_CHUNK)) {
+            match sys_fs_write(&path, chunk, offset, flags) {
+                Ok(n) if n == chunk.len() => {}
+                other => { failure = Some(other); break; }
+            }
+            offset += chunk.len();
+            flags = 0;
+        }
+        // An empty document still has to truncate the file
+        if failure.is_none() && flags != 0 {
+            if let Err(e) = sys_fs_write(&path, &[], 0, flags) { failure = Some(Err(e)); }
+        }
+
+        self.save_failed = failure.is_some();
+        self.status_msg = match failure {
+            None => { self.is_dirty = false; String::from("Saved") }
+            Some(Ok(_)) => String::from("SAVE FAILED: short write"),
+            Some(Err(NyxError::Denied)) => String::from("SAVE FAILED: file is read-only"),
+            Some(Err(e)) => alloc::format!("SAVE FAILED: {}", e.message()),
+        };
+    }
+
+    fn open_file(&mut self, name: &str) {
+        let path = self.join_path(name);
+        self.active_file = String::from(name);
+        match load_file(&path) {
+            Ok(buffer) => { self.editor = buffer; self.status_msg.clear(); },
+            Err(e) => { self.editor = GapBuffer::new(); self.status_msg = alloc::format!("Could not read file: {}", e.message()); },
+        }
+        self.editor_lines = WrappedRows::new(&self.editor, self.editor_lines.cols());
+        self.cursor = 0;
+        self.anchor = None;
+        self.editor_scroll = 0;
+        self.is_dirty = false;
+        self.save_failed = false;
+        self.state = AppState::Editor;
+        self.remember();
+    }
+
+    /// Call after removing `removed` bytes at `pos` and then inserting `inserted`
+    /// there. Rewraps just the lines touched; drawing, clicks and Up/Down all go
+    /// by the rows this leaves.
+    fn edited(&mut self, pos: usize, removed: usize, inserted: usize) {
+        self.editor_lines.edited(&self.editor, pos, removed, inserted);
+        self.is_dirty = true;
+    }
+
+    /// Selected byte range, start first; None when nothing is selected.
+    fn selection(&self) -> Option<(usize, usize)> {
+        let anchor = self.anchor?;
+        if anchor == self.cursor { return None; }
+        Some((anchor.min(self.cursor), anchor.max(self.cursor)))
+    }
+
+    /// Removes the selected text, leaving the caret where it started. False if there was none.
+    fn delete_selection(&mut self) -> bool {
+        let sel = self.selection();
+        self.anchor = None;
+        match sel {
+            Some((start, end)) => {
+                self.editor.delete(start, end - start);
+                self.cursor = start;
+                self.edited(start, end - start, 0);
+                true
+            },
+            None => false,
+        }
+    }
+
+    fn editor_key(&mut self, event: KeyEvent) -> bool {
+        if !event.pressed { return false; }
+
+        // Shift + navigation grows the selection, plain navigation drops it
+        if matches!(event.ch, KEY_UP | KEY_DOWN | KEY_HOME | KEY_END | KEY_LEFT | KEY_RIGHT) {
+            if !event.shift() { self.anchor = None; }
+            else if self.anchor.is_none() { self.anchor = Some(self.cursor); }
+        }
+
+        match event.ch {
+            KEY_UP | KEY_DOWN | KEY_HOME | KEY_END => {
+                let lines = self.editor_lines.rows();
+                let row = self.editor_lines.row_of(self.cursor);
+                let col = self.editor.chars(lines[row].0, self.cursor).count();
+                let (start, end) = lines[row];
+                self.cursor = match event.ch {
+                    KEY_UP if row > 0 => index_at_col(&self.editor, lines[row - 1], col),
+                    KEY_DOWN if row + 1 < lines.len() => index_at_col(&self.editor, lines[row + 1], col),
+                    KEY_HOME => start,
+                    KEY_END => end,
+                    _ => self.cursor,
+                };
+            },
+            KEY_LEFT => self.cursor -= self.editor.prev_char_len(self.cursor),
+            KEY_RIGHT => self.cursor += self.editor.next_char_len(self.cursor),
+            // With a selection, both delete keys just remove it
+            '\x08' | '\x7f' if self.selection().is_some() => { self.delete_selection(); },
+            '\x08' => {
+                let n = self.editor.prev_char_len(self.cursor);
+                if n > 0 {
+                    self.cursor -= n;
+                    self.editor.delete(self.cursor, n);
+                    self.edited(self.cursor, n, 0);
+                }
+            },
+            '\x7f' => {
+                let n = self.editor.next_char_len(self.cursor);
+                if n > 0 {
+                    self.editor.delete(self.cursor, n);
+                    self.edited(self.cursor, n, 0);
+                }
+            },
+            '\r' | '\n' => {
+                self.delete_selection();
+                self.editor.insert_char(self.cursor, '\n');
+                self.edited(self.cursor, 0, 1);
+                self.cursor += 1;
+            },
+            c if c != '\0' && (c == '\t' || !c.is_control()) && !('\u{E000}'..='\u{F8FF}').contains(&c) => {
+                self.delete_selection();
+                self.editor.insert_char(self.cursor, c);
+                self.edited(self.cursor, 0, c.len_utf8());
+                self.cursor += c.len_utf8();
+            },
+            _ => return false,
+        }
+        true
+    }
+
+    fn commit_rename(&mut self) {
+        self.renaming = false;
+        let idx = match self.selected { Some(i) if i < self.files.len() => i, _ => return };
+
+        let old_name = String::from(self.files[idx].trim_end_matches('/'));
+        let new_name = String::from(self.rename_buffer.trim());
+
+        if new_name.is_empty() || new_name.contains('/') {
+            self.status_msg = String::from("Invalid name");
+            return;
+        }
+        if new_name == old_name { return; }
+
+        let old_path = self.join_path(&old_name);
+        let new_path = self.join_path(&new_name);
+
+        match sys_fs_rename(&old_path, &new_path) {
+            Ok(()) => {
+                self.status_msg = alloc::format!("Renamed to {}", new_name);
+                self.reload();
+                self.selected = self.files.iter().position(|f| f.trim_end_matches('/') == new_name);
+            }
+            Err(NyxError::Exists) => self.status_msg = alloc::format!("'{}' already exists", new_name),
+            Err(NyxError::Denied) => self.status_msg = String::from("File is read-only"),
+            Err(e) => self.status_msg = alloc::format!("Rename failed: {}", e.message()),
+        }
+    }
+
+    /// The selected entry as a path, if it's a file (folders can't be copied).
+    fn selected_file(&self) -> Option<String> {
+        let name = self.files.get(self.selected?)?;
+        if name.ends_with('/') { None } else { Some(self.join_path(name)) }
+    }
+
+    /// Copy (`cut` false) or Cut: remembers the selection for Paste.
+    fn pick_for_paste(&mut self, cut: bool) -> bool {
+        let picked = if cut {
+            self.selected.and_then(|i| self.files.get(i)).map(|name| self.join_path(name.trim_end_matches('/')))
+        } else {
+            self.selected_file()
+        };
+        let Some(path) = picked else { return false };
+        self.status_msg = alloc::format!("{} {}", if cut { "Cut" } else { "Copied" }, path::file_name(&path));
+        self.file_clip = Some((path, cut));
+        true
+    }
+
+    /// Puts the Copy/Cut path into the current folder, asking first if that
+    /// would replace a file.
+    fn paste(&mut self) -> bool {
+        let Some((src, cut)) = self.file_clip.clone() else { return false };
+        let dst = self.join_path(path::file_name(&src));
+        if path::normalize(&src).eq_ignore_ascii_case(&dst) {
+            self.status_msg = String::from("Already in this folder");
+        } else if fileops::file_exists(&dst) {
+            self.confirm_overwrite = Some((src, dst, cut));
+        } else {
+            self.start_transfer(&src, &dst, cut);
+        }
+        true
+    }
+
+    fn start_transfer(&mut self, src: &str, dst: &str, cut: bool) {
+        let started = if cut {
+            fileops::move_file(src, dst)
+        } else {
+            FileCopy::new(src, dst).map(Some)
+        };
+        match started {
+            Ok(Some(copy)) => self.transfer = Some(copy),
+            // Renamed within the volume; nothing left to do
+            Ok(None) => self.transfer_done(cut, dst),
+            Err(e) => self.transfer_failed(cut, e),
+        }
+    }
+
+    fn transfer_done(&mut self, moved: bool, dst: &str) {
+        // A cut file isn't where the clipboard says any more
+        if moved { self.file_clip = None; }
+        self.status_msg = alloc::format!("{} {}", if moved { "Moved" } else { "Copied" }, path::file_name(dst));
+        self.refresh();
+        self.selected = self.files.iter().position(|f| f.trim_end_matches('/') == path::file_name(dst));
+    }
+
+    fn transfer_failed(&mut self, moved: bool, err: NyxError) {
+        let what = if moved { "Move" } else { "Copy" };
+        self.status_msg = match err {
+            NyxError::IsDir => String::from("Folders can't be copied"),
+            NyxError::Denied => alloc::format!("{} failed: file is read-only", what),
+            e => alloc::format!("{} failed: {}", what, e.message()),
+        };
+        self.refresh();
+    }
+
+    /// Answers the overwrite dialog.
+    fn resolve_overwrite(&mut self, replace: bool) {
+        let Some((src, dst, cut)) = self.confirm_overwrite.take() else { return };
+        if !replace { return; }
+        // A rename won't replace a file, so it goes first; a copy truncates it anyway
+        if cut {
+            if let Err(e) = sys_fs_delete(&dst) { return self.transfer_failed(cut, e); }
+        }
+        self.start_transfer(&src, &dst, cut);
+    }
+
+    /// Copy / Cut / Paste along the status bar, greyed out when they'd do nothing.
+    fn draw_file_buttons(&self, canvas: &mut Canvas, bar_y: usize) {
+        let theme = theme::current();
+        let s = scale::current();
+        let can_cut = self.selected.is_some();
+        let buttons = [("Copy", self.selected_file().is_some()), ("Cut", can_cut), ("Paste", self.file_clip.is_some())];
+        for (i, (label, enabled)) in buttons.into_iter().enumerate() {
+            let x = file_button_x(canvas.width, i);
+            let y = bar_y + px(3);
+            if enabled {
+                Button { x, y, w: file_btn_w(), h: status_bar_h() - px(6), text: String::from(label), is_hovered: false, is_pressed: false }.draw_scaled(canvas, s);
+            } else {
+                canvas.fill_rect(x, y, file_btn_w(), status_bar_h() - px(6), theme.surface);
+                canvas.fill_rect(x, y, file_btn_w(), 1, theme.border);
+                canvas.fill_rect(x, y + status_bar_h() - px(6) - 1, file_btn_w(), 1, theme.border);
+                canvas.print_str(x + px(10), y + (status_bar_h() - px(6)) / 2 - px(4), label, theme.text_secondary, s);
+            }
+        }
+    }
+
+    /// The progress or overwrite dialog over a dimmed window.
+    fn draw_modal(&mut self, canvas: &mut Canvas) {
+        let theme = theme::current();
+        let s = scale::current();
+        let (modal_w, modal_h) = (modal_w(), modal_h());
+        let (width, height) = (canvas.width, canvas.height);
+        let (x, y) = (width.saturating_sub(modal_w) / 2, height.saturating_sub(modal_h) / 2);
+        self.modal_at = (x, y);
+
+        // Glass: a veil over the window, a translucent card, a light edge at the top
+        canvas.fill_rect(0, 0, width, height, 0x80_000000);
+        canvas.fill_rect(x, y, modal_w, modal_h, 0xE0_000000 | (theme.surface & 0x00FF_FFFF));
+        canvas.fill_rect(x, y, modal_w, 1, 0x88_FFFFFF);
+        canvas.fill_rect(x, y + modal_h - 1, modal_w, 1, 0x44_FFFFFF);
+        canvas.fill_rect(x, y, 1, modal_h, 0x88_FFFFFF);
+        canvas.fill_rect(x + modal_w - 1, y, 1, modal_h, 0x44_FFFFFF);
+
+        if let Some((_, dst, _)) = &self.confirm_overwrite {
+            let name: String = path::file_name(dst).chars().take(30).collect();
+            canvas.print_str(x + px(20), y + px(20), &alloc::format!("'{}' already exists.", name), theme.text_primary, s);
+            canvas.print_str(x + px(20), y + px(40), "Replace it?", theme.text_secondary, s);
+            Button { x: x + modal_w - px(200), y: y + modal_h - px(45), w: px(85), h: px(30), text: String::from("Replace"), is_hovered: true, is_pressed: false }.draw_scaled(canvas, s);
+        } else if let Some(copy) = &self.transfer {
+            let verb = if copy.is_move() { "Moving" } else { "Copying" };
+            let name: String = path::file_name(copy.source()).chars().take(32).collect();
+            canvas.print_str(x + px(20), y + px(16), &alloc::format!("{} {}", verb, name), theme.text_primary, s);
+
+            let bar_w = modal_w - px(40);
+            let done = if copy.size() == 0 { bar_w } else { (bar_w as u64 * copy.copied() as u64 / copy.size() as u64) as usize };
+            canvas.fill_rect(x + px(20), y + px(36), bar_w, px(10), theme.field_bg);
+            canvas.fill_rect(x + px(20), y + px(36), done, px(10), theme.accent);
+            let progress = alloc::format!("{} of {}", format_size(copy.copied() as i64), format_size(copy.size() as i64));
+            canvas.print_str(x + px(20), y + px(54), &progress, theme.text_secondary, s);
         }
+        Button { x: x + modal_w - px(105), y: y + modal_h - px(45), w: px(85), h: px(30), text: String::from("Cancel"), is_hovered: false, is_pressed: false }.draw_scaled(canvas, s);
+    }
+
+    /// A copy is running or waiting on an answer; nothing behind its dialog gets input.
+    fn modal(&self) -> bool { self.transfer.is_some() || self.confirm_overwrite.is_some() }
+
+    /// A click while a dialog is up.
+    fn modal_click(&mut self, mx: usize, my: usize) -> bool {
+        let (x, y) = self.modal_at;
+        let on = |bx: usize| mx >= bx && mx <= bx + px(85) && my >= y + modal_h() - px(45) && my <= y + modal_h() - px(15);
+        if on(x + modal_w() - px(105)) {
+            if let Some(copy) = self.transfer.take() {
+                copy.cancel();
+                self.status_msg = String::from("Cancelled");
+                self.refresh();
+            }
+            self.confirm_overwrite = None;
+            return true;
+        }
+        if self.confirm_overwrite.is_some() && on(x + modal_w() - px(200)) {
+            self.resolve_overwrite(true);
+            return true;
+        }
+        false
     }
 }
 
+/// Left edge of the `i`-th status bar file button.
+fn file_button_x(width: usize, i: usize) -> usize {
+    width.saturating_sub(px(10) + (3 - i) * (file_btn_w() + file_btn_gap()) - file_btn_gap())
+}
+
 impl NyxApp for ExplorerApp {
     fn title(&self) -> &str { "Nyx Explorer Suite" }
-    fn initial_width(&self) -> usize { 650 }
-    fn initial_height(&self) -> usize { 450 }
+    fn initial_width(&self) -> usize { px(650) }
+    fn initial_height(&self) -> usize { px(450) }
+
+    // Picks up files other programs create, write, rename or delete here
+    fn update(&mut self) -> bool {
+        // One chunk per frame, so the progress bar moves with the copy
+        if let Some(copy) = &mut self.transfer {
+            let moving = copy.is_move();
+            match copy.step() {
+                Ok(false) => {},
+                Ok(true) => {
+                    let dst = String::from(self.transfer.take().unwrap().destination());
+                    self.transfer_done(moving, &dst);
+                },
+                Err(e) => { self.transfer = None; self.transfer_failed(moving, e); },
+            }
+            return true;
+        }
+        if self.state != AppState::Explorer || self.renaming { return false; }
+        let now = sys_get_time();
+        if now.wrapping_sub(self.last_watch) < WATCH_MS { return false; }
+        self.last_watch = now;
+        if sys_fs_generation() == self.listed_generation { return false; }
+        self.refresh();
+        true
+    }
+
+    fn init(&mut self) {
+        // Back to the file or folder the last session ended in, if it's still there
+        let saved = state::load();
+        self.show_hidden = saved.get("explorer.show_hidden") == Some("1");
+        let file = saved.get("editor.file").filter(|f| !f.is_empty() && sys_fs_size(f).is_ok());
+        let dir = saved.get("explorer.path").filter(|d| sys_fs_opendir(d).map(|fd| { let _ = sys_close(fd); }).is_ok());
+        if let Some(path) = file.or(dir) { self.open_path(path); }
+    }
 
     fn draw(&mut self, canvas: &mut Canvas) {
+        let theme = theme::current();
         let width = canvas.width;
         let height = canvas.height;
+        let s = scale::current();
+        // Toolbar buttons and the text beside them
+        let (btn_y, btn_h, text_y) = (px(10), px(30), px(17));
+        // Toolbar items hang off the right edge; in a narrow window they bunch up at the left
+        let right = |n| width.saturating_sub(px(n));
 
-        canvas.fill_rect(0, 0, width, height, Color::WARM_BG); 
-        canvas.fill_rect(0, 0, width, 50, Color::WARM_SURFACE); 
-        canvas.fill_rect(0, 50, width, 1, Color::WARM_BORDER);
+        canvas.fill_rect(0, 0, width, height, theme.window_bg); 
+        canvas.fill_rect(0, 0, width, toolbar_h(), theme.surface); 
+        canvas.fill_rect(0, toolbar_h(), width, 1, theme.border);
 
         if self.state == AppState::Explorer {
-            let mut up_btn = Button { x: 10, y: 10, w: 60, h: 30, text: String::from("Up"), is_hovered: false, is_pressed: false };
-            up_btn.draw(canvas);
+            let mut up_btn = Button { x: px(10), y: btn_y, w: px(60), h: btn_h, text: String::from("Up"), is_hovered: false, is_pressed: false };
+            up_btn.draw_scaled(canvas, s);
+
+            canvas.fill_rect(px(80), btn_y, width.saturating_sub(px(540)), btn_h, theme.field_bg);
+            canvas.fill_rect(px(80), btn_y, width.saturating_sub(px(540)), 1, theme.border);
+            self.crumbs.clear();
+            if self.status_msg.is_empty() {
+                self.draw_breadcrumbs(canvas, px(90), px(80) + width.saturating_sub(px(550)));
+            } else {
+                canvas.print_str(px(90), text_y, &self.status_msg, theme.accent_hover, s);
+            }
 
-            canvas.fill_rect(80, 10, width.saturating_sub(340), 30, Color::WHITE);
-            canvas.fill_rect(80, 10, width.saturating_sub(340), 1, Color::WARM_BORDER);
-            canvas.print_str(90, 17, &self.current_path, Color::TEXT_DARK, 1);
+            let mut rename_btn = Button { x: right(340), y: btn_y, w: px(80), h: btn_h, text: String::from("Rename"), is_hovered: self.renaming, is_pressed: false };
+            rename_btn.draw_scaled(canvas, s);
+            let mut hidden_btn = Button { x: right(450), y: btn_y, w: px(100), h: btn_h, text: String::from("Show hidden"), is_hovered: self.show_hidden, is_pressed: false };
+            hidden_btn.draw_scaled(canvas, s);
 
             let items_per_page = 24;
             let total_pages = if self.files.is_empty() { 1 } else { (self.files.len() + items_per_page - 1) / items_per_page };
             
             if total_pages > 1 {
-                let mut prev_btn = Button { x: width - 250, y: 10, w: 30, h: 30, text: String::from("<"), is_hovered: false, is_pressed: false };
-                let mut next_btn = Button { x: width - 130, y: 10, w: 30, h: 30, text: String::from(">"), is_hovered: false, is_pressed: false };
-                prev_btn.draw(canvas);
-                next_btn.draw(canvas);
+                let mut prev_btn = Button { x: right(250), y: btn_y, w: px(30), h: btn_h, text: String::from("<"), is_hovered: false, is_pressed: false };
+                let mut next_btn = Button { x: right(130), y: btn_y, w: px(30), h: btn_h, text: String::from(">"), is_hovered: false, is_pressed: false };
+                prev_btn.draw_scaled(canvas, s);
+                next_btn.draw_scaled(canvas, s);
                 
                 let page_text = alloc::format!("{} / {}", self.current_page + 1, total_pages);
-                canvas.print_str(width - 210, 17, &page_text, Color::TEXT_DARK, 1);
+                canvas.print_str(right(210), text_y, &page_text, theme.text_primary, s);
             }
 
-            let mut refresh_btn = Button { x: width - 90, y: 10, w: 80, h: 30, text: String::from("Refresh"), is_hovered: false, is_pressed: false };
-            refresh_btn.draw(canvas);
+            let mut refresh_btn = Button { x: right(90), y: btn_y, w: px(80), h: btn_h, text: String::from("Refresh"), is_hovered: false, is_pressed: false };
+            refresh_btn.draw_scaled(canvas, s);
 
             let start_idx = self.current_page * items_per_page;
             let end_idx = core::cmp::min(start_idx + items_per_page, self.files.len());
             let visible_files = &self.files[start_idx..end_idx];
 
-            let mut fx = 20; let mut fy = 70;
+            let mut fx = px(20); let mut fy = toolbar_h() + px(20);
             if self.files.is_empty() {
-                canvas.print_str(width/2 - 50, height/2, "Folder is Empty", Color::TEXT_MUTED, 1);
+                canvas.print_str((width / 2).saturating_sub(px(50)), height / 2, "Folder is Empty", theme.text_secondary, s);
             } else {
-                for file in visible_files.iter() {
-                    canvas.fill_rect(fx, fy, 130, 40, Color::WARM_SURFACE); 
-                    canvas.fill_rect(fx, fy, 5, 40, Color::ACCENT_PRIMARY); 
+                for (i, file) in visible_files.iter().enumerate() {
+                    let is_selected = self.selected == Some(start_idx + i);
+                    canvas.fill_rect(fx, fy, px(130), px(40), if is_selected { theme.border } else { theme.surface }); 
+                    canvas.fill_rect(fx, fy, px(5), px(40), if is_selected { theme.accent_hover } else { theme.accent }); 
                     
-                    let display_name = if file.len() > 14 { alloc::format!("{}...", &file[..11]) } else { file.clone() };
-                    canvas.print_str(fx + 15, fy + 12, &display_name, Color::TEXT_DARK, 1);
+                    let kind = FileKind::classify(file);
+                    draw_file_icon(canvas, fx + px(8), fy + (px(40) - ICON_SIZE) / 2, kind);
+                    let tx = fx + px(12) + ICON_SIZE;
+                    if self.read_only.get(start_idx + i) == Some(&true) {
+                        draw_lock(canvas, fx + px(120), fy + px(4), theme.text_secondary);
+                    }
+
+                    if is_selected && self.renaming {
+                        // Inline edit box: show the tail of the buffer so the cursor stays visible
+                        canvas.fill_rect(tx - 2, fy + px(8), px(130) - (tx - fx) - 2, px(24), theme.field_bg);
+                        let tail_start = self.rename_buffer.len().saturating_sub(9);
+                        let visible = &self.rename_buffer[tail_start..];
+                        canvas.print_str(tx, fy + px(16), visible, theme.text_primary, s);
+                        canvas.fill_rect(tx + visible.len() * px(8), fy + px(12), px(2), px(16), theme.text_primary);
+                    } else {
+                        let name = file.trim_end_matches('/');
+                        let display_name = if name.chars().count() > 10 { alloc::format!("{}...", name.chars().take(7).collect::<String>()) } else { String::from(name) };
+                        canvas.print_str(tx, fy + px(8), &display_name, theme.text_primary, s);
+                        match self.sizes.get(start_idx + i) {
+                            Some(&size) if size >= 0 => canvas.print_str(tx, fy + px(24), &format_size(size), theme.text_secondary, s),
+                            _ if kind == FileKind::Directory => canvas.print_str(tx, fy + px(24), "Folder", theme.text_secondary, s),
+                            _ => {}
+                        }
+                    }
                     
-                    fx += 150;
-                    if fx > width - 150 { fx = 20; fy += 60; }
+                    fx += px(150);
+                    if fx > width.saturating_sub(px(150)) { fx = px(20); fy += px(60); }
                 }
             }
+
+            let bar_y = height.saturating_sub(status_bar_h());
+            let bar_text_y = bar_y + (status_bar_h() - px(8)) / 2;
+            canvas.fill_rect(0, bar_y, width, status_bar_h(), theme.surface);
+            canvas.fill_rect(0, bar_y, width, 1, theme.border);
+            let count = self.files.len();
+            let items = alloc::format!("{} item{}", count, if count == 1 { "" } else { "s" });
+            let status = match self.free_bytes {
+                Some(free) => alloc::format!("{} - {} free", items, format_size(free as i64)),
+                None => items,
+            };
+            canvas.print_str(px(20), bar_text_y, &status, theme.text_secondary, s);
+            let mtime = self.selected.and_then(|i| self.mtimes.get(i)).copied().unwrap_or(0);
+            if mtime != 0 {
+                let modified = alloc::format!("Modified: {}", DateTime::from_unix(mtime));
+                let x = file_button_x(width, 0).saturating_sub(px(20 + modified.len() * 8));
+                canvas.print_str(x, bar_text_y, &modified, theme.text_secondary, s);
+            }
+            self.draw_file_buttons(canvas, bar_y);
+            self.status_bar = (width, bar_y);
+
+            if self.modal() { self.draw_modal(canvas); }
         } 
         else if self.state == AppState::Editor {
-            let mut back_btn = Button { x: 10, y: 10, w: 70, h: 30, text: String::from("Back"), is_hovered: false, is_pressed: false };
-            back_btn.draw(canvas);
-            let title_str = alloc::format!("Reading: {}{}{}", self.current_path, if self.current_path.ends_with('/') {""} else {"/"}, self.active_file);
-            canvas.print_str(95, 17, &title_str, Color::TEXT_DARK, 1);
+            if self.save_failed {
+                canvas.fill_rect(0, 0, width, toolbar_h(), Color::ACCENT_RED);
+            }
+            let header_text = if self.save_failed { Color::WHITE } else { theme.text_primary };
+            let mut back_btn = Button { x: px(10), y: btn_y, w: px(70), h: btn_h, text: String::from("Back"), is_hovered: false, is_pressed: false };
+            back_btn.draw_scaled(canvas, s);
+            let title_str = alloc::format!("Editing: {}{}", self.join_path(&self.active_file), if self.is_dirty {" *"} else {""});
+            canvas.print_str(px(95), text_y, &title_str, header_text, s);
+            let counter = alloc::format!("{} bytes / {} lines", self.editor.len(), self.editor.line_count());
+            let counter_x = width.saturating_sub(px(20 + counter.len() * 8));
+            canvas.print_str(counter_x, text_y, &counter, header_text, s);
+            if !self.status_msg.is_empty() {
+                let status_color = if self.save_failed { Color::WHITE } else { theme.accent_hover };
+                canvas.print_str(counter_x.saturating_sub(px(20 + self.status_msg.len() * 8)), text_y, &self.status_msg, status_color, s);
+            }
+
+            canvas.fill_rect(px(10), toolbar_h() + px(10), width.saturating_sub(px(20)), height.saturating_sub(toolbar_h() + px(20)), theme.field_bg); 
+
+            let (char_w, line_h) = (edit_char_w(), edit_line_h());
+            let cols = (width.saturating_sub(px(30) + char_w) / char_w + 1).max(1);
+            if cols != self.editor_lines.cols() { self.editor_lines = WrappedRows::new(&self.editor, cols); }
+            self.editor_rows = (height.saturating_sub(edit_y() + px(15)) / line_h).max(1);
+
+            // Keep the caret on screen
+            let row = self.editor_lines.row_of(self.cursor);
+            if row < self.editor_scroll { self.editor_scroll = row; }
+            if row >= self.editor_scroll + self.editor_rows { self.editor_scroll = row + 1 - self.editor_rows; }
 
-            canvas.fill_rect(10, 60, width - 20, height - 70, 0xFF_1E1E1E); 
-            draw_text_wrapped(canvas, 15, 65, width - 30, height - 80, &self.editor_content, 0xFF_CCCCCC);
+            draw_text_wrapped(canvas, edit_x(), edit_y(), &self.editor, &self.editor_lines, self.editor_scroll, self.editor_rows, theme.text_primary, theme.field_bg, Some(self.cursor), self.selection());
         }
     }
 
     fn on_mouse(&mut self, mx: usize, my: usize, _clicked: bool) -> bool {
-        let width = 650; 
+        let width = self.status_bar.0;
         let items_per_page = 24;
+        // Toolbar buttons share one row
+        let in_toolbar = my >= px(10) && my <= px(40);
+        let right = |n| width.saturating_sub(px(n));
 
+        if self.modal() { return self.modal_click(mx, my); }
         if self.state == AppState::Explorer {
             let total_pages = if self.files.is_empty() { 1 } else { (self.files.len() + items_per_page - 1) / items_per_page };
+            let (bar_w, bar_y) = self.status_bar;
+            if my >= bar_y + px(3) && my < bar_y + status_bar_h() - px(3) {
+                let button = (0..3).find(|&i| mx >= file_button_x(bar_w, i) && mx < file_button_x(bar_w, i) + file_btn_w());
+                match button {
+                    Some(0) => return self.pick_for_paste(false),
+                    Some(1) => return self.pick_for_paste(true),
+                    Some(_) => return self.paste(),
+                    None => {},
+                }
+            }
 
-            if mx >= 10 && mx <= 70 && my >= 10 && my <= 40 {
-                if self.current_path != "/" {
-                    let mut parts: Vec<&str> = self.current_path.split('/').filter(|s| !s.is_empty()).collect();
-                    parts.pop();
-                    self.current_path = if parts.is_empty() { String::from("/") } else { alloc::format!("/{}", parts.join("/")) };
-                    self.files = get_directory_contents(&self.current_path);
-                    self.current_page = 0; 
+            if in_toolbar && mx >= px(10) && mx <= px(70) {
+                // Up is a no-op at the root
+                if path::normalize(&self.current_path) != "/" {
+                    self.current_path = path::parent(&self.current_path);
+                    self.status_msg.clear();
+                    self.reload();
                     return true;
                 }
             }
-            else if mx >= width - 90 && mx <= width - 10 && my >= 10 && my <= 40 {
-                self.files = get_directory_contents(&self.current_path);
-                self.current_page = 0;
+            else if in_toolbar && mx >= px(80) && mx <= px(80) + width.saturating_sub(px(540)) {
+                let target = self.crumbs.iter().find(|(x0, x1, _)| mx >= *x0 && mx < *x1).map(|(_, _, p)| p.clone());
+                if let Some(path) = target {
+                    if path != self.current_path {
+                        self.current_path = path;
+                        self.reload();
+                    }
+                    return true;
+                }
+                // Clicking the bar while a message is shown brings the path back
+                if !self.status_msg.is_empty() { self.status_msg.clear(); return true; }
+            }
+            else if in_toolbar && mx >= right(340) && mx <= right(260) {
+                if let Some(idx) = self.selected {
+                    self.rename_buffer = String::from(self.files[idx].trim_end_matches('/'));
+                    self.renaming = true;
+                    self.status_msg = String::from("Enter new name, Esc to cancel");
+                } else {
+                    self.status_msg = String::from("Select a file to rename");
+                }
+                return true;
+            }
+            else if in_toolbar && mx >= right(450) && mx <= right(350) {
+                self.show_hidden = !self.show_hidden;
+                state::save("explorer.show_hidden", if self.show_hidden { "1" } else { "0" });
+                self.reload();
+                return true;
+            }
+            else if in_toolbar && mx >= right(90) && mx <= right(10) {
+                self.status_msg.clear();
+                self.reload();
                 return true;
             } 
-            else if total_pages > 1 && mx >= width - 250 && mx <= width - 220 && my >= 10 && my <= 40 {
+            else if total_pages > 1 && in_toolbar && mx >= right(250) && mx <= right(220) {
                 if self.current_page > 0 { self.current_page -= 1; return true; }
             }
-            else if total_pages > 1 && mx >= width - 130 && mx <= width - 100 && my >= 10 && my <= 40 {
+            else if total_pages > 1 && in_toolbar && mx >= right(130) && mx <= right(100) {
                 if self.current_page < total_pages - 1 { self.current_page += 1; return true; }
             }
-            else {
-                let start_idx = self.current_page * items_per_page;
-                let end_idx = core::cmp::min(start_idx + items_per_page, self.files.len());
-                let visible_files = &self.files[start_idx..end_idx];
-
-                let mut fx = 20; let mut fy = 70;
-                for file in visible_files.iter() {
-                    if mx >= fx && mx <= fx + 130 && my >= fy && my <= fy + 40 {
-                        let target_path = alloc::format!("{}{}{}", self.current_path, if self.current_path.ends_with('/') {""} else {"/"}, file);
-                        
-                        // 🚨 YOUR ORIGINAL LOGIC RESTORED
-                        let dir_contents = get_directory_contents(&target_path);
-                        
-                        if !dir_contents.is_empty() {
-                            self.current_path = target_path;
-                            self.files = dir_contents;
-                            self.current_page = 0;
-                        } else {
-                            self.active_file = file.clone();
-                            self.editor_content = read_file(&target_path);
-                            self.state = AppState::Editor;
-                        }
-                        return true;
-                    }
-                    fx += 150; if fx > width - 150 { fx = 20; fy += 60; }
+            else if let Some(idx) = self.tile_at(mx, my) {
+                // A click only selects; opening takes a double-click
+                if self.selected != Some(idx) {
+                    self.selected = Some(idx);
+                    self.renaming = false;
+                    self.status_msg.clear();
+                    return true;
                 }
             }
         } 
         else if self.state == AppState::Editor {
-            if mx >= 10 && mx <= 80 && my >= 10 && my <= 40 {
+            if in_toolbar && mx >= px(10) && mx <= px(80) {
                 self.state = AppState::Explorer;
-                self.files = get_directory_contents(&self.current_path); 
+                self.reload();
+                return true;
+            }
+            if my >= edit_y() && mx >= px(10) {
+                // Same grid as draw_text_wrapped; clicks past the end of a line clamp to it
+                let lines = self.editor_lines.rows();
+                let row = (self.editor_scroll + (my - edit_y()) / edit_line_h()).min(lines.len() - 1);
+                let col = (mx.saturating_sub(edit_x()) + edit_char_w() / 2) / edit_char_w();
+                self.cursor = index_at_col(&self.editor, lines[row], col);
+                self.anchor = None;
                 return true;
             }
         }
         false
     }
+
+    fn on_double_click(&mut self, mx: usize, my: usize) -> bool {
+        if self.state != AppState::Explorer || self.renaming || self.modal() { return false; }
+        let idx = match self.tile_at(mx, my) { Some(idx) => idx, None => return false };
+        let file = self.files[idx].clone();
+        match FileKind::classify(&file) {
+            FileKind::Directory => {
+                self.current_path = self.join_path(&file);
+                self.reload();
+            },
+            FileKind::Image => {
+                let request = alloc::format!("{}\n{}", IMAGE_VIEWER_PATH, self.join_path(&file));
+                let _ = sys_ipc_send_str(COMPOSITOR_PID, MSG_OPEN_WITH, &request);
+                return false;
+            },
+            _ => self.open_file(&file),
+        }
+        true
+    }
+
+    fn on_paste(&mut self, text: &str) -> bool {
+        if self.state != AppState::Editor || text.is_empty() { return false; }
+        self.delete_selection();
+        self.editor.insert_str(self.cursor, text);
+        self.edited(self.cursor, 0, text.len());
+        self.cursor += text.len();
+        true
+    }
+
+    fn on_wheel(&mut self, _mx: usize, _my: usize, notches: i32) -> bool {
+        // The grid is paged, so each notch flips a page
+        if self.state != AppState::Explorer || self.renaming || self.modal() { return false; }
+        let items_per_page = 24;
+        let last_page = self.files.len().saturating_sub(1) / items_per_page;
+        let old = self.current_page;
+        self.current_page = if notches > 0 {
+            self.current_page.saturating_sub(notches as usize)
+        } else {
+            (self.current_page + notches.unsigned_abs() as usize).min(last_page)
+        };
+        self.current_page != old
+    }
+
+    fn on_message(&mut self, msg: &IpcMessage) -> bool {
+        if msg.msg_type != MSG_OPEN_PATH { return false; }
+        ipc_read_str(msg, |path| self.open_path(path)).unwrap_or(false)
+    }
+
+    fn begin_drag(&mut self, mx: usize, my: usize) -> Option<String> {
+        if self.state != AppState::Explorer || self.renaming || self.modal() { return None; }
+        let idx = self.tile_at(mx, my)?;
+        Some(self.join_path(self.files[idx].trim_end_matches('/')))
+    }
+
+    fn accept_drop(&mut self, payload: &str) -> bool {
+        if self.modal() { return false; }
+        self.open_path(payload)
+    }
+
+    fn on_shortcut(&mut self, key: char, _shift: bool) -> bool {
+        if self.modal() { return false; }
+        let editing = self.state == AppState::Editor;
+        match key {
+            's' if editing => { self.save_file(); true },
+            'a' if editing => { self.anchor = Some(0); self.cursor = self.editor.len(); true },
+            'c' | 'x' if editing => {
+                // Nothing selected: still swallow the key so it isn't typed
+                let (start, end) = match self.selection() { Some(sel) => sel, None => return true };
+                self.clipboard.copy(&self.editor.text(start, end));
+                if key == 'x' { self.delete_selection(); }
+                true
+            },
+            'v' if editing => { self.clipboard.request_paste(); true },
+            'r' if self.state == AppState::Explorer && !self.renaming => { self.status_msg.clear(); self.reload(); true },
+            'c' | 'x' if self.state == AppState::Explorer && !self.renaming => self.pick_for_paste(key == 'x'),
+            'v' if self.state == AppState::Explorer && !self.renaming => self.paste(),
+            _ => false,
+        }
+    }
+
+    fn on_key_event(&mut self, event: KeyEvent) -> bool {
+        if self.state == AppState::Editor { return self.editor_key(event); }
+        if event.pressed && event.ch != '\0' { self.on_key(event.ch) } else { false }
+    }
+
+    fn on_key(&mut self, key: char) -> bool {
+        if self.confirm_overwrite.is_some() {
+            match key {
+                'y' | 'Y' | '\n' | '\r' => self.resolve_overwrite(true),
+                'n' | 'N' | '\x1b' => self.resolve_overwrite(false),
+                _ => return false,
+            }
+            return true;
+        }
+        if self.state != AppState::Explorer || !self.renaming { return false; }
+
+        match key {
+            '\n' | '\r' => self.commit_rename(),
+            '\x1b' => { self.renaming = false; self.status_msg.clear(); },
+            '\x08' => { self.rename_buffer.pop(); },
+            c if c.is_ascii() && !c.is_ascii_control() && self.rename_buffer.len() < 255 => self.rename_buffer.push(c),
+            _ => return false,
+        }
+        true
+    }
 }
 
-fn draw_text_wrapped(canvas: &mut Canvas, x: usize, y: usize, w: usize, h: usize, text: &str, color: u32) {
-    let mut cx = x; let mut cy = y;
-    for c in text.chars() {
-        if c == '\n' { cx = x; cy += 16; continue; }
-        canvas.draw_char(cx, cy, c, color, 1);
-        cx += 9; 
-        if cx > x + w - 9 { cx = x; cy += 16; }
-        if cy > y + h - 16 { break; } 
+// Where the editor's text starts, and its grid; the UI scale grows them all
+fn edit_x() -> usize { px(15) }
+fn edit_y() -> usize { toolbar_h() + px(15) }
+fn edit_char_w() -> usize { font::char_width(scale::current()) }
+fn edit_line_h() -> usize { px(16) }
+fn index_at_col(text: &GapBuffer, line: (usize, usize), col: usize) -> usize {
+    text.chars(line.0, line.1).nth(col).map(|(i, _)| i).unwrap_or(line.1)
+}
+
+/// Selected chars (byte range `selection`) are drawn inverted: `color` cell, `bg` glyph.
+/// 8x10 padlock marking a read-only file.
+fn draw_lock(canvas: &mut Canvas, x: usize, y: usize, color: u32) {
+    // Shackle, then the body
+    canvas.fill_rect(x + 2, y, 4, 1, color);
+    canvas.fill_rect(x + 1, y + 1, 1, 3, color);
+    canvas.fill_rect(x + 6, y + 1, 1, 3, color);
+    canvas.fill_rect(x, y + 4, 8, 6, color);
+}
+
+fn draw_text_wrapped(canvas: &mut Canvas, x: usize, y: usize, text: &GapBuffer, lines: &WrappedRows, first_row: usize, max_rows: usize, color: u32, bg: u32, cursor: Option<usize>, selection: Option<(usize, usize)>) {
+    let (s, char_w, line_h) = (scale::current(), edit_char_w(), edit_line_h());
+    let caret_row = cursor.map(|c| lines.row_of(c));
+    let (sel_start, sel_end) = selection.unwrap_or((0, 0));
+    for (r, &(start, end)) in lines.rows().iter().enumerate().skip(first_row).take(max_rows) {
+        let cy = y + (r - first_row) * line_h;
+        let mut cx = x;
+        for (idx, c) in text.chars(start, end) {
+            if idx >= sel_start && idx < sel_end {
+                canvas.fill_rect(cx, cy - px(2), char_w, line_h, color);
+                canvas.draw_char(cx, cy, c, bg, s);
+            } else {
+                canvas.draw_char(cx, cy, c, color, s);
+            }
+            cx += char_w;
+        }
+        if caret_row == Some(r) {
+            let col = text.chars(start, cursor.unwrap()).count();
+            canvas.fill_rect(x + col * char_w, cy, px(2), px(12), color);
+        }
     }
 }
 
 #[unsafe(no_mangle)]
 #[unsafe(link_section = ".text.entry")]
 pub extern "C" fn _start() -> ! {
-    let heap_start = sys_alloc_pages(256);
-    if heap_start == 0 { sys_exit(1); }
-    unsafe { ALLOCATOR.lock().init(heap_start as *mut u8, 256 * 4096); }
+    if !ALLOCATOR.init(256 * 4096) { sys_exit(1); }
 
     nyx_gui::app::run(ExplorerApp::new());
 }
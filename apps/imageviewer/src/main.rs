@@ -0,0 +1,139 @@
+#![no_std]
+#![no_main]
+#![allow(warnings)]
+
+extern crate alloc;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use nyx_api::*;
+use nyx_core::bmp::fit;
+use nyx_gui::heap::BrkHeap;
+use nyx_gui::app::NyxApp;
+use nyx_gui::bmp::{self, Image};
+use nyx_gui::canvas::{Canvas, Color};
+use nyx_gui::state;
+use nyx_gui::theme;
+
+#[global_allocator]
+static ALLOCATOR: BrkHeap = BrkHeap::empty();
+
+// ==========================================
+// IMAGE VIEWER
+// ==========================================
+// Shows one BMP, scaled to fit the window with bars around it, and scaled
+// again whenever the window changes size. The Explorer opens images here
+// through MSG_OPEN_WITH; the file arrives as MSG_OPEN_PATH. Images bigger
+// than MAX_PIXELS are shrunk while they're decoded, so a huge file costs
+// no more heap than a 1024x1024 one.
+
+const MAX_PIXELS: usize = 1024 * 1024;
+const INFO_H: usize = 28;
+
+struct ImageViewerApp {
+    path: String,
+    image: Option<Image>,
+    // Why `path` isn't showing
+    error: Option<&'static str>,
+    // One output row, reused for every row drawn
+    row: Vec<u32>,
+}
+
+impl ImageViewerApp {
+    fn new() -> Self {
+        Self { path: String::new(), image: None, error: None, row: Vec::new() }
+    }
+
+    fn open(&mut self, path: &str) -> bool {
+        self.path = String::from(path);
+        // Free the old image before decoding the next one
+        self.image = None;
+        match bmp::load(path, MAX_PIXELS) {
+            Ok(image) => { self.image = Some(image); self.error = None; },
+            Err(e) => self.error = Some(e.message()),
+        }
+        state::save("viewer.file", path);
+        true
+    }
+
+    fn file_name(&self) -> &str {
+        self.path.rsplit('/').next().unwrap_or(&self.path)
+    }
+}
+
+impl NyxApp for ImageViewerApp {
+    fn title(&self) -> &str { "Image Viewer" }
+    fn initial_width(&self) -> usize { 640 }
+    fn initial_height(&self) -> usize { 480 }
+
+    fn init(&mut self) {
+        // Back to the image the last session showed, if it's still there
+        let saved = state::load();
+        if let Some(path) = saved.get("viewer.file").filter(|f| !f.is_empty() && sys_fs_size(f).is_ok()) {
+            let path = String::from(path);
+            self.open(&path);
+        }
+    }
+
+    fn draw(&mut self, canvas: &mut Canvas) {
+        let theme = theme::current();
+        let (width, height) = (canvas.width, canvas.height);
+
+        canvas.fill_rect(0, 0, width, INFO_H, theme.surface);
+        canvas.fill_rect(0, INFO_H, width, 1, theme.border);
+        let info = match &self.image {
+            Some(image) => alloc::format!("{}  ({} x {})", self.file_name(), image.width, image.height),
+            None if self.path.is_empty() => String::from("Open a .bmp file from the Explorer"),
+            None => String::from(self.file_name()),
+        };
+        canvas.print_str(10, (INFO_H - 8) / 2, &info, theme.text_primary, 1);
+
+        let (area_y, area_w, area_h) = (INFO_H + 1, width, height.saturating_sub(INFO_H + 1));
+        canvas.fill_rect(0, area_y, area_w, area_h, theme.console_bg);
+
+        if let Some(error) = self.error {
+            let text_w = error.len() * 8;
+            canvas.print_str(area_w.saturating_sub(text_w) / 2, area_y + area_h / 2 - 4, error, Color::ACCENT_RED, 1);
+            return;
+        }
+        let Some(image) = &self.image else { return };
+
+        // Scaled to fit both ways, centered with bars on the short sides
+        let (dst_w, dst_h) = fit(image.width, image.height, area_w, area_h, true);
+        if dst_w == 0 || dst_h == 0 { return; }
+        let (x, y) = ((area_w - dst_w) / 2, area_y + (area_h - dst_h) / 2);
+        self.row.resize(dst_w, 0);
+        for dy in 0..dst_h {
+            let src = &image.pixels[(dy * image.height / dst_h) * image.width..][..image.width];
+            for (dx, pixel) in self.row.iter_mut().enumerate() {
+                *pixel = src[dx * image.width / dst_w];
+            }
+            canvas.composite_buffer(x, y + dy, &self.row, dst_w, 1, 255);
+        }
+    }
+
+    fn on_message(&mut self, msg: &IpcMessage) -> bool {
+        if msg.msg_type != MSG_OPEN_PATH { return false; }
+        match ipc_read_str(msg, |path| String::from(path)) {
+            Some(path) => self.open(&path),
+            None => false,
+        }
+    }
+
+    // Images dragged in from the Explorer open too
+    fn accept_drop(&mut self, payload: &str) -> bool {
+        let path = String::from(payload);
+        self.open(&path)
+    }
+}
+
+#[unsafe(no_mangle)]
+#[unsafe(link_section = ".text.entry")]
+pub extern "C" fn _start() -> ! {
+    if !ALLOCATOR.init(256 * 4096) { sys_exit(1); }
+
+    nyx_gui::app::run(ImageViewerApp::new());
+}
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! { sys_exit(111); }
@@ -8,10 +8,16 @@ use nyx_api::*;
 pub extern "C" fn _start() -> ! {
     sys_print("[INIT] NyxOS Init Orchestrator Started (PID 1)\n");
 
+    // Every app talks to the kernel through the same registers; if they
+    // disagree, nothing past this point would work anyway
+    if !sys_abi_check() {
+        sys_print("[INIT] FATAL: syscall ABI mismatch between nyx_api and the kernel!\n");
+        sys_exit(2);
+    }
+
     // 1. Spawn the Window Server dynamically from the NVMe Drive!
     sys_print("[INIT] Spawning WindowServer.nyx from SSD...\n");
-    let gui_pid = sys_fork();
-    if gui_pid == 0 {
+    if let Ok(0) = sys_fork() {
         // 🚨 THE FIX: Point to the new App Bundle path
         sys_execve("/mnt/nvme/apps/WindowServer.nyx/run.bin\0");
         sys_exit(1);
@@ -4,16 +4,17 @@
 
 extern crate alloc;
 use alloc::string::String;
-use linked_list_allocator::LockedHeap;
 
 use nyx_api::*;
+use nyx_gui::heap::BrkHeap;
 use nyx_gui::app::NyxApp;
 use nyx_gui::canvas::{Canvas, Color};
+use nyx_gui::theme;
 // 🚨 FIX 1: Import the Widget trait
 use nyx_gui::ui::{Button, Widget};
 
 #[global_allocator]
-static ALLOCATOR: LockedHeap = LockedHeap::empty();
+static ALLOCATOR: BrkHeap = BrkHeap::empty();
 
 #[derive(PartialEq, Clone, Copy)]
 enum NetState { Dns, Fetch, Chat, Https, Browser }
@@ -62,12 +63,11 @@ impl NyxApp for NetworkSuite {
         if self.async_status == AsyncState::WaitingForData && self.active_fd >= 0 {
             let res = sys_read(self.active_fd, &mut self.receive_buffer);
             
-            if res > 0 {
-                sys_close(self.active_fd);
+            if let Ok(len @ 1..) = res {
+                let _ = sys_close(self.active_fd);
                 self.active_fd = -1;
                 self.async_status = AsyncState::Idle;
                 
-                let len = res as usize;
                 if self.state == NetState::Dns {
                     if len > 12 && self.receive_buffer[3] & 0x0F == 0 {
                         let ip1 = self.receive_buffer[len - 4]; let ip2 = self.receive_buffer[len - 3];
@@ -80,14 +80,14 @@ impl NyxApp for NetworkSuite {
                     self.log_buffer = String::from_utf8_lossy(&self.receive_buffer[..len]).into_owned();
                 }
                 requested_redraw = true; 
-            } else if res < 0 && res != -11 {
-                sys_close(self.active_fd);
+            } else if let Some(e) = res.err().filter(|&e| e != NyxError::WouldBlock) {
+                let _ = sys_close(self.active_fd);
                 self.active_fd = -1;
                 self.async_status = AsyncState::Idle;
-                self.log_buffer = alloc::format!("Hardware Socket Fault: Error code {}", res);
+                self.log_buffer = alloc::format!("Hardware Socket Fault: {}", e.message());
                 requested_redraw = true; 
             } else if sys_get_time().wrapping_sub(self.request_start_time) > 2500 {
-                sys_close(self.active_fd);
+                let _ = sys_close(self.active_fd);
                 self.active_fd = -1;
                 self.async_status = AsyncState::Idle;
                 self.log_buffer = String::from("Network Error: Connection timed out.");
@@ -98,14 +98,15 @@ impl NyxApp for NetworkSuite {
     }
 
     fn draw(&mut self, canvas: &mut Canvas) {
+        let theme = theme::current();
         let width = canvas.width;
         let height = canvas.height;
 
-        canvas.fill_rect(0, 0, width, height, Color::WARM_BG);
-        canvas.fill_rect(0, 0, 150, height, Color::WARM_SURFACE);
-        canvas.fill_rect(150, 0, 1, height, Color::WARM_BORDER); 
+        canvas.fill_rect(0, 0, width, height, theme.window_bg);
+        canvas.fill_rect(0, 0, 150, height, theme.surface);
+        canvas.fill_rect(150, 0, 1, height, theme.border); 
 
-        canvas.print_str(15, 20, "NET SUITE", Color::ACCENT_PRIMARY, 2);
+        canvas.print_str(15, 20, "NET SUITE", theme.accent, 2);
 
         let tabs = [
             (NetState::Dns, "DNS Lookup", 80),
@@ -117,8 +118,8 @@ impl NyxApp for NetworkSuite {
 
         for (s, text, y) in tabs.iter() {
             let is_active = self.state == *s;
-            if is_active { canvas.fill_rect(10, *y - 5, 130, 30, Color::ACCENT_PRIMARY); }
-            let text_color = if is_active { Color::WHITE } else { Color::TEXT_MUTED };
+            if is_active { canvas.fill_rect(10, *y - 5, 130, 30, theme.accent); }
+            let text_color = if is_active { Color::WHITE } else { theme.text_secondary };
             canvas.print_str(20, *y + 2, text, text_color, 1);
         }
 
@@ -131,11 +132,11 @@ impl NyxApp for NetworkSuite {
                     NetState::Fetch => "HTTP Raw Resource Fetch Engine",
                     _ => "NyxOS Vector Web Browser",
                 };
-                canvas.print_str(cx, 20, title_text, Color::TEXT_DARK, 1);
+                canvas.print_str(cx, 20, title_text, theme.text_primary, 1);
                 
-                canvas.fill_rect(cx, 50, cw.saturating_sub(80), 30, Color::WHITE);
-                canvas.fill_rect(cx, 50, cw.saturating_sub(80), 1, Color::WARM_BORDER);
-                canvas.print_str(cx + 10, 57, &self.input_buffer, Color::TEXT_DARK, 1);
+                canvas.fill_rect(cx, 50, cw.saturating_sub(80), 30, theme.field_bg);
+                canvas.fill_rect(cx, 50, cw.saturating_sub(80), 1, theme.border);
+                canvas.print_str(cx + 10, 57, &self.input_buffer, theme.text_primary, 1);
 
                 let btn_label = if self.async_status == AsyncState::Idle { "EXEC" } else { "WAIT" };
                 
@@ -152,8 +153,8 @@ impl NyxApp for NetworkSuite {
                 draw_text_wrapped(canvas, cx + 15, 115, cw.saturating_sub(30), log_height.saturating_sub(20), &self.log_buffer, Color::ACCENT_GREEN);
             },
             _ => {
-                canvas.print_str(cx, 20, "Module Standby", Color::TEXT_DARK, 2);
-                draw_text_wrapped(canvas, cx, 80, cw, height.saturating_sub(100), "This service requires structural adjustments.", Color::TEXT_MUTED);
+                canvas.print_str(cx, 20, "Module Standby", theme.text_primary, 2);
+                draw_text_wrapped(canvas, cx, 80, cw, height.saturating_sub(100), "This service requires structural adjustments.", theme.text_secondary);
             }
         }
     }
@@ -226,9 +227,7 @@ impl NyxApp for NetworkSuite {
 #[unsafe(no_mangle)]
 #[unsafe(link_section = ".text.entry")]
 pub extern "C" fn _start() -> ! {
-    let heap_start = sys_alloc_pages(256);
-    if heap_start == 0 { sys_exit(1); }
-    unsafe { ALLOCATOR.lock().init(heap_start as *mut u8, 256 * 4096); }
+    if !ALLOCATOR.init(256 * 4096) { sys_exit(1); }
 
     nyx_gui::app::run(NetworkSuite::new());
 }
@@ -0,0 +1,194 @@
+#![no_std]
+#![no_main]
+#![allow(warnings)]
+
+extern crate alloc;
+use alloc::vec::Vec;
+
+use nyx_api::*;
+use nyx_gui::heap::BrkHeap;
+use nyx_gui::app::{NyxApp, COMPOSITOR_PID};
+use nyx_gui::canvas::{Canvas, Color};
+use nyx_gui::theme;
+
+#[global_allocator]
+static ALLOCATOR: BrkHeap = BrkHeap::empty();
+
+// ==========================================
+// ON-SCREEN KEYBOARD
+// ==========================================
+// A window of keys that types into whichever window has focus. Its window
+// is WIN_FLAG_NO_FOCUS, so clicking it leaves that window focused, and each
+// key goes to the compositor as MSG_INJECT_KEY, which handles it exactly as
+// a key from the keyboard: Alt+Tab, the start menu, then the focused app.
+
+// Keys are laid out in half-key steps so rows can be staggered
+const HALF: usize = 27;
+const GAP: usize = 6;
+const KEY_H: usize = 40;
+const ROW_H: usize = KEY_H + GAP;
+const MARGIN: usize = 10;
+const KEY_HALVES: usize = 2;
+// Backspace, Enter and Shift are one and a half keys wide
+const WIDE_HALVES: usize = 3;
+
+// (keys, with shift, set-1 scancode of the first key, indent in halves).
+// Each row's scancodes run on from its first, as they do on a PC keyboard.
+const CHAR_ROWS: [(&str, &str, u8, usize); 4] = [
+    ("1234567890", "!@#$%^&*()", 0x02, 0),
+    ("qwertyuiop", "QWERTYUIOP", 0x10, 1),
+    ("asdfghjkl", "ASDFGHJKL", 0x1E, 2),
+    ("zxcvbnm,.", "ZXCVBNM<>", 0x2C, WIDE_HALVES),
+];
+
+const SC_BACKSPACE: u8 = 0x0E;
+const SC_ENTER: u8 = 0x1C;
+const SC_SHIFT: u8 = 0x2A;
+const SC_SPACE: u8 = 0x39;
+
+#[derive(Clone, Copy, PartialEq)]
+enum KeyAction {
+    // (plain, with shift)
+    Char(char, char),
+    Backspace,
+    Enter,
+    Space,
+    Shift,
+}
+
+#[derive(Clone, Copy)]
+struct Key {
+    action: KeyAction,
+    scancode: u8,
+    x: usize, y: usize, w: usize,
+}
+
+impl Key {
+    fn new(action: KeyAction, scancode: u8, row: usize, half: usize, halves: usize) -> Self {
+        Self { action, scancode, x: MARGIN + half * HALF, y: MARGIN + row * ROW_H, w: halves * HALF - GAP }
+    }
+
+    fn contains(&self, mx: usize, my: usize) -> bool {
+        mx >= self.x && mx < self.x + self.w && my >= self.y && my < self.y + KEY_H
+    }
+}
+
+fn layout() -> Vec<Key> {
+    let mut keys = Vec::new();
+    for (row, &(plain, shifted, first_scancode, indent)) in CHAR_ROWS.iter().enumerate() {
+        for (i, (c, s)) in plain.chars().zip(shifted.chars()).enumerate() {
+            keys.push(Key::new(KeyAction::Char(c, s), first_scancode + i as u8, row, indent + i * KEY_HALVES, KEY_HALVES));
+        }
+    }
+    let row_end = |row: usize| { let (plain, _, _, indent) = CHAR_ROWS[row]; indent + plain.len() * KEY_HALVES };
+    keys.push(Key::new(KeyAction::Backspace, SC_BACKSPACE, 0, row_end(0), WIDE_HALVES));
+    keys.push(Key::new(KeyAction::Enter, SC_ENTER, 2, row_end(2), WIDE_HALVES));
+    keys.push(Key::new(KeyAction::Shift, SC_SHIFT, 3, 0, WIDE_HALVES));
+    keys.push(Key::new(KeyAction::Space, SC_SPACE, 4, 5, 6 * KEY_HALVES));
+    keys
+}
+
+struct KeyboardApp {
+    keys: Vec<Key>,
+    shift: bool,
+    // The key clicked last, lit until one frame has shown it
+    pressed: Option<usize>,
+    pressed_drawn: bool,
+}
+
+impl KeyboardApp {
+    fn new() -> Self {
+        Self { keys: layout(), shift: false, pressed: None, pressed_drawn: false }
+    }
+
+    /// The char `key` types with the current shift state; '\0' for Shift.
+    fn key_char(&self, key: &Key) -> char {
+        match key.action {
+            KeyAction::Char(c, s) => if self.shift { s } else { c },
+            KeyAction::Backspace => '\x08',
+            KeyAction::Enter => '\n',
+            KeyAction::Space => ' ',
+            KeyAction::Shift => '\0',
+        }
+    }
+
+    /// Presses and releases key `idx` in the focused window.
+    fn press(&mut self, idx: usize) -> bool {
+        let key = self.keys[idx];
+        self.pressed = Some(idx);
+        self.pressed_drawn = false;
+        if key.action == KeyAction::Shift {
+            self.shift = !self.shift;
+            return true;
+        }
+
+        let modifiers = if self.shift { KEYMOD_SHIFT } else { 0 };
+        let mut event = KeyEvent { ch: self.key_char(&key), scancode: key.scancode, modifiers, pressed: true, extended: false };
+        let _ = sys_ipc_send(COMPOSITOR_PID, MSG_INJECT_KEY, 0, event.to_packed());
+        event.pressed = false;
+        let _ = sys_ipc_send(COMPOSITOR_PID, MSG_INJECT_KEY, 0, event.to_packed());
+        true
+    }
+
+    fn click(&mut self, mx: usize, my: usize) -> bool {
+        match self.keys.iter().position(|k| k.contains(mx, my)) {
+            Some(idx) => self.press(idx),
+            None => false,
+        }
+    }
+}
+
+impl NyxApp for KeyboardApp {
+    fn title(&self) -> &str { "Keyboard" }
+    fn initial_width(&self) -> usize { 2 * MARGIN + 23 * HALF - GAP }
+    fn initial_height(&self) -> usize { 2 * MARGIN + 5 * ROW_H - GAP }
+    fn window_flags(&self) -> u32 { WIN_FLAG_NO_FOCUS }
+
+    fn update(&mut self) -> bool {
+        if self.pressed.is_some() && self.pressed_drawn {
+            self.pressed = None;
+            return true;
+        }
+        false
+    }
+
+    fn draw(&mut self, canvas: &mut Canvas) {
+        let theme = theme::current();
+        canvas.fill_rect(0, 0, canvas.width, canvas.height, theme.window_bg);
+
+        for (idx, key) in self.keys.iter().enumerate() {
+            let lit = self.pressed == Some(idx) || (key.action == KeyAction::Shift && self.shift);
+            let (bg, fg) = if lit { (theme.accent, Color::WHITE) } else { (theme.surface, theme.text_primary) };
+            canvas.fill_rect(key.x, key.y, key.w, KEY_H, theme.border);
+            canvas.fill_rect(key.x + 1, key.y + 1, key.w - 2, KEY_H - 2, bg);
+
+            let mut buf = [0u8; 4];
+            let (label, scale) = match key.action {
+                KeyAction::Char(..) => (&*self.key_char(key).encode_utf8(&mut buf), 2),
+                KeyAction::Backspace => ("Bksp", 1),
+                KeyAction::Enter => ("Enter", 1),
+                KeyAction::Space => ("Space", 1),
+                KeyAction::Shift => ("Shift", 1),
+            };
+            let text_w = label.len() * 8 * scale;
+            canvas.print_str(key.x + key.w.saturating_sub(text_w) / 2, key.y + (KEY_H - 8 * scale) / 2, label, fg, scale);
+        }
+        if self.pressed.is_some() { self.pressed_drawn = true; }
+    }
+
+    fn on_mouse(&mut self, mx: usize, my: usize, _clicked: bool) -> bool { self.click(mx, my) }
+
+    // Typing the same key twice quickly is two presses, not an open
+    fn on_double_click(&mut self, mx: usize, my: usize) -> bool { self.click(mx, my) }
+}
+
+#[unsafe(no_mangle)]
+#[unsafe(link_section = ".text.entry")]
+pub extern "C" fn _start() -> ! {
+    if !ALLOCATOR.init(128 * 4096) { sys_exit(1); }
+
+    nyx_gui::app::run(KeyboardApp::new());
+}
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! { sys_exit(111); }
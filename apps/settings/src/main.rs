@@ -6,16 +6,44 @@ extern crate alloc;
 use alloc::string::String;
 use alloc::vec::Vec;
 use alloc::vec;
-use linked_list_allocator::LockedHeap;
 
 use nyx_api::*;
+use nyx_gui::heap::BrkHeap;
 use nyx_gui::app::NyxApp;
-use nyx_gui::canvas::{Canvas, Color};
+use nyx_gui::app::COMPOSITOR_PID;
+use nyx_gui::canvas::Canvas;
+use nyx_gui::scale;
+use nyx_gui::theme::{self, ACCENTS};
 // Import the new widgets!
 use nyx_gui::ui::{Widget, Button, CheckBox, Menu, TextBox, Label};
 
 #[global_allocator]
-static ALLOCATOR: LockedHeap = LockedHeap::empty();
+static ALLOCATOR: BrkHeap = BrkHeap::empty();
+
+// Accent swatches on the Personalization tab, left to right in ACCENTS order
+const SWATCH_X: usize = 210;
+const SWATCH_Y: usize = 190;
+const SWATCH_SIZE: usize = 32;
+const SWATCH_GAP: usize = 12;
+
+/// Index into ACCENTS of the swatch under (mx, my).
+fn swatch_at(mx: usize, my: usize) -> Option<usize> {
+    if my < SWATCH_Y || my >= SWATCH_Y + SWATCH_SIZE || mx < SWATCH_X { return None; }
+    let slot = (mx - SWATCH_X) / (SWATCH_SIZE + SWATCH_GAP);
+    let inside = (mx - SWATCH_X) % (SWATCH_SIZE + SWATCH_GAP) < SWATCH_SIZE;
+    if inside && slot < ACCENTS.len() { Some(slot) } else { None }
+}
+
+/// Asks the compositor to switch every window to this theme; it answers
+/// with MSG_THEME_CHANGED like everyone else gets.
+fn request_theme(accent: usize, dark: bool) {
+    let _ = sys_ipc_send(COMPOSITOR_PID, MSG_SET_THEME, accent as u64, dark as u64);
+}
+
+/// The same for the UI scale, answered with MSG_UI_SCALE_CHANGED.
+fn request_ui_scale(scale: usize) {
+    let _ = sys_ipc_send(COMPOSITOR_PID, MSG_SET_UI_SCALE, scale as u64, 0);
+}
 
 #[derive(PartialEq, Clone, Copy)]
 enum SettingsTab { Display, Personalization, System, Security }
@@ -51,10 +79,10 @@ impl SettingsApp {
 
             // Personalization Widgets
             chk_animations: CheckBox { x: 210, y: 80, text: String::from("Enable Window Animations"), is_checked: true },
-            chk_dark_mode: CheckBox { x: 210, y: 120, text: String::from("Force Dark Mode UI"), is_checked: false },
+            chk_dark_mode: CheckBox { x: 210, y: 120, text: String::from("Dark Mode"), is_checked: false },
 
             // Display Widgets
-            menu_scale: Menu { x: 210, y: 120, w: 150, items: vec![String::from("100%"), String::from("125%"), String::from("150%")], is_open: false, selected_idx: 0 },
+            menu_scale: Menu { x: 210, y: 120, w: 150, items: vec![String::from("100%"), String::from("200%")], is_open: false, selected_idx: 0 },
             txt_resolution: TextBox { x: 210, y: 80, w: 150, h: 25, text: String::from("1920x1080"), is_focused: false },
         }
     }
@@ -65,16 +93,23 @@ impl NyxApp for SettingsApp {
     fn initial_width(&self) -> usize { 680 }
     fn initial_height(&self) -> usize { 450 }
 
+    fn init(&mut self) {
+        self.chk_dark_mode.is_checked = theme::current().dark;
+        // Item i is scale i + 1
+        self.menu_scale.selected_idx = scale::current() - 1;
+    }
+
     fn draw(&mut self, canvas: &mut Canvas) {
+        let theme = theme::current();
         let width = canvas.width;
         let height = canvas.height;
 
-        canvas.fill_rect(0, 0, width, height, Color::WARM_BG);
+        canvas.fill_rect(0, 0, width, height, theme.window_bg);
 
         // Sidebar Background
-        canvas.fill_rect(0, 0, 180, height, Color::WARM_SURFACE);
-        canvas.fill_rect(180, 0, 1, height, Color::WARM_BORDER);
-        canvas.print_str(15, 20, "SETTINGS", Color::TEXT_DARK, 2);
+        canvas.fill_rect(0, 0, 180, height, theme.surface);
+        canvas.fill_rect(180, 0, 1, height, theme.border);
+        canvas.print_str(15, 20, "SETTINGS", theme.text_primary, 2);
 
         // 1. Draw Sidebar Widgets
         self.btn_display.draw(canvas);
@@ -86,32 +121,43 @@ impl NyxApp for SettingsApp {
         let cx = 210;
         match self.active_tab {
             SettingsTab::Display => {
-                canvas.print_str(cx, 30, "Display Settings", Color::TEXT_DARK, 2);
-                canvas.fill_rect(cx, 60, width - cx - 30, 1, Color::WARM_BORDER);
+                canvas.print_str(cx, 30, "Display Settings", theme.text_primary, 2);
+                canvas.fill_rect(cx, 60, width - cx - 30, 1, theme.border);
                 
-                canvas.print_str(cx, 160, "Global Scale Factor", Color::TEXT_DARK, 1);
+                canvas.print_str(cx, 160, "Global Scale Factor", theme.text_primary, 1);
                 
                 // Draw display widgets
                 self.txt_resolution.draw(canvas);
                 self.menu_scale.draw(canvas); // Draw menu last so it overlaps everything else
             },
             SettingsTab::Personalization => {
-                canvas.print_str(cx, 30, "Personalization", Color::TEXT_DARK, 2);
-                canvas.fill_rect(cx, 60, width - cx - 30, 1, Color::WARM_BORDER);
+                canvas.print_str(cx, 30, "Personalization", theme.text_primary, 2);
+                canvas.fill_rect(cx, 60, width - cx - 30, 1, theme.border);
 
                 // Draw personalization widgets
                 self.chk_animations.draw(canvas);
                 self.chk_dark_mode.draw(canvas);
+
+                canvas.print_str(cx, SWATCH_Y - 20, "Accent Color", theme.text_primary, 1);
+                for (i, &(_, color, _)) in ACCENTS.iter().enumerate() {
+                    let sx = SWATCH_X + i * (SWATCH_SIZE + SWATCH_GAP);
+                    // The current accent gets a ring around it
+                    if color == theme.accent {
+                        canvas.fill_rect(sx - 3, SWATCH_Y - 3, SWATCH_SIZE + 6, SWATCH_SIZE + 6, theme.text_primary);
+                        canvas.fill_rect(sx - 1, SWATCH_Y - 1, SWATCH_SIZE + 2, SWATCH_SIZE + 2, theme.window_bg);
+                    }
+                    canvas.fill_rect(sx, SWATCH_Y, SWATCH_SIZE, SWATCH_SIZE, color);
+                }
             },
             SettingsTab::System => {
-                canvas.print_str(cx, 30, "System Specifications", Color::TEXT_DARK, 2);
-                canvas.fill_rect(cx, 60, width - cx - 30, 1, Color::WARM_BORDER);
-                canvas.print_str(cx, 80, "OS: NyxOS v0.1 (Lethe Build)", Color::TEXT_DARK, 1);
-                canvas.print_str(cx, 110, "Architecture: x86_64", Color::TEXT_DARK, 1);
-                canvas.print_str(cx, 140, "Window Server: Nyx Compositor Phase 3", Color::TEXT_DARK, 1);
+                canvas.print_str(cx, 30, "System Specifications", theme.text_primary, 2);
+                canvas.fill_rect(cx, 60, width - cx - 30, 1, theme.border);
+                canvas.print_str(cx, 80, "OS: NyxOS v0.1 (Lethe Build)", theme.text_primary, 1);
+                canvas.print_str(cx, 110, "Architecture: x86_64", theme.text_primary, 1);
+                canvas.print_str(cx, 140, "Window Server: Nyx Compositor Phase 3", theme.text_primary, 1);
             },
             _ => {
-                canvas.print_str(cx, 30, "Module Pending", Color::TEXT_DARK, 2);
+                canvas.print_str(cx, 30, "Module Pending", theme.text_primary, 2);
             }
         }
     }
@@ -136,10 +182,20 @@ impl NyxApp for SettingsApp {
         // 2. Pass events to active tab widgets
         if self.active_tab == SettingsTab::Personalization {
             needs_redraw |= self.chk_animations.on_mouse(mx, my, clicked);
-            needs_redraw |= self.chk_dark_mode.on_mouse(mx, my, clicked);
+            let theme = theme::current();
+            let accent = ACCENTS.iter().position(|&(_, c, _)| c == theme.accent).unwrap_or(0);
+            if self.chk_dark_mode.on_mouse(mx, my, clicked) {
+                request_theme(accent, self.chk_dark_mode.is_checked);
+                needs_redraw = true;
+            }
+            if let Some(picked) = swatch_at(mx, my).filter(|_| clicked) {
+                if picked != accent { request_theme(picked, theme.dark); }
+            }
         } else if self.active_tab == SettingsTab::Display {
             // Priority: Pass to menu first, because if it's open, it swallows clicks!
+            let old_scale = self.menu_scale.selected_idx;
             needs_redraw |= self.menu_scale.on_mouse(mx, my, clicked);
+            if self.menu_scale.selected_idx != old_scale { request_ui_scale(self.menu_scale.selected_idx + 1); }
             if !self.menu_scale.is_open {
                 needs_redraw |= self.txt_resolution.on_mouse(mx, my, clicked);
             }
@@ -158,14 +214,22 @@ impl NyxApp for SettingsApp {
         
         needs_redraw
     }
+
+    fn on_message(&mut self, msg: &IpcMessage) -> bool {
+        // Both also sent when another app made the change
+        match msg.msg_type {
+            MSG_THEME_CHANGED => self.chk_dark_mode.is_checked = msg.data2 != 0,
+            MSG_UI_SCALE_CHANGED => self.menu_scale.selected_idx = scale::current() - 1,
+            _ => return false,
+        }
+        true
+    }
 }
 
 #[unsafe(no_mangle)]
 #[unsafe(link_section = ".text.entry")]
 pub extern "C" fn _start() -> ! {
-    let heap_start = sys_alloc_pages(256);
-    if heap_start == 0 { sys_exit(1); }
-    unsafe { ALLOCATOR.lock().init(heap_start as *mut u8, 256 * 4096); }
+    if !ALLOCATOR.init(256 * 4096) { sys_exit(1); }
 
     nyx_gui::app::run(SettingsApp::new());
 }
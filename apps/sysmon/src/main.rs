@@ -5,30 +5,55 @@
 extern crate alloc;
 use alloc::string::String;
 use alloc::vec::Vec;
-use linked_list_allocator::LockedHeap;
 
 use nyx_api::*;
-use nyx_gui::app::NyxApp;
+use nyx_gui::heap::BrkHeap;
+use nyx_gui::app::{NyxApp, COMPOSITOR_PID};
 use nyx_gui::canvas::{Canvas, Color};
+use nyx_gui::theme;
 
 #[global_allocator]
-static ALLOCATOR: LockedHeap = LockedHeap::empty();
+static ALLOCATOR: BrkHeap = BrkHeap::empty();
 
 #[derive(PartialEq, Clone, Copy)]
-enum SysMonState { Vitals, Tasks, Bootlog }
+enum SysMonState { Vitals, Tasks, Bootlog, Resources }
+
+// Samples kept for the usage graph; one per refresh, so a minute of history
+const HISTORY_LEN: usize = 120;
 
 struct SysMonApp {
     state: SysMonState,
     last_update_time: usize,
     entity_stats: [f32; 4],
     active_cores: usize,
+    tasks_exited: u64,
     sys_info: SystemInfo,
     bootlog_buf: Vec<u8>,
     bootlog_lines: Vec<String>,
-    bootlog_last_len: usize,
+    // Absolute kernel log offset read up to, and the unfinished last line
+    bootlog_offset: u64,
+    bootlog_partial: String,
     bootlog_scroll: usize,
+    // Reported by the compositor in reply to MSG_GET_FRAME_STATS
+    frame_time_us: u64,
+    frames_composed: u64,
+    mem: MemInfo,
+    cpu_load: u64,
+    // (cpu, ram, work queue) usage in per mille, oldest first
+    history: Vec<(u64, u64, u64)>,
+    tasks: Vec<TaskStat>,
+    // Share of the ticks handed out since the last refresh, per mille, parallel to `tasks`
+    task_share: Vec<u64>,
+    // (pid, cpu_ticks) at the last refresh
+    prev_ticks: Vec<(u64, u64)>,
 }
 
+const CPU_COLOR: u32 = 0xFF_3498DB;
+const RAM_COLOR: u32 = 0xFF_2ECC71;
+const QUEUE_COLOR: u32 = 0xFF_E67E22;
+const QUEUE_SCALE: u64 = 100;
+const BOOTLOG_MAX_LINES: usize = 2000;
+
 impl SysMonApp {
     fn new() -> Self {
         Self {
@@ -36,13 +61,82 @@ impl SysMonApp {
             last_update_time: 0,
             entity_stats: [0.0; 4],
             active_cores: 0,
+            tasks_exited: 0,
             sys_info: unsafe { core::mem::zeroed() },
             bootlog_buf: alloc::vec![0u8; 16384],
             bootlog_lines: Vec::new(),
-            bootlog_last_len: 0,
+            bootlog_offset: 0,
+            bootlog_partial: String::new(),
             bootlog_scroll: 0,
+            frame_time_us: 0,
+            frames_composed: 0,
+            mem: MemInfo::default(),
+            cpu_load: 0,
+            history: Vec::new(),
+            tasks: Vec::new(),
+            task_share: Vec::new(),
+            prev_ticks: Vec::new(),
         }
     }
+
+    fn refresh_tasks(&mut self) {
+        let mut stats = alloc::vec![TaskStat::default(); 64];
+        let count = match sys_task_stats(&mut stats) { Ok(n) => n, Err(_) => return };
+        stats.truncate(count);
+
+        let deltas: Vec<u64> = stats.iter().map(|t| {
+            let before = self.prev_ticks.iter().find(|(pid, _)| *pid == t.pid).map_or(0, |&(_, ticks)| ticks);
+            t.cpu_ticks.saturating_sub(before)
+        }).collect();
+        let total: u64 = deltas.iter().sum();
+        self.task_share = deltas.iter().map(|&d| if total > 0 { d * 1000 / total } else { 0 }).collect();
+        self.prev_ticks = stats.iter().map(|t| (t.pid, t.cpu_ticks)).collect();
+        self.tasks = stats;
+    }
+
+    /// Appends whatever the kernel logged since the last poll.
+    fn poll_bootlog(&mut self) {
+        let max_lines = 24;
+        let at_bottom = self.bootlog_scroll >= self.bootlog_lines.len().saturating_sub(max_lines);
+        loop {
+            let (start, len) = sys_read_boot_log(&mut self.bootlog_buf, self.bootlog_offset);
+            if start > self.bootlog_offset {
+                self.bootlog_partial.clear();
+                self.bootlog_lines.push(alloc::format!("-- {} bytes lost to the kernel ring --", start - self.bootlog_offset));
+            }
+            if len == 0 { break; }
+            self.bootlog_offset = start + len as u64;
+
+            for &b in &self.bootlog_buf[..len] {
+                match b {
+                    b'\n' => {
+                        let line = core::mem::take(&mut self.bootlog_partial);
+                        if !line.trim().is_empty() { self.bootlog_lines.push(line); }
+                    }
+                    b'\r' => {}
+                    _ => self.bootlog_partial.push(if b.is_ascii() { b as char } else { '?' }),
+                }
+            }
+            if len < self.bootlog_buf.len() { break; }
+        }
+
+        if self.bootlog_lines.len() > BOOTLOG_MAX_LINES {
+            let excess = self.bootlog_lines.len() - BOOTLOG_MAX_LINES;
+            self.bootlog_lines.drain(..excess);
+            self.bootlog_scroll = self.bootlog_scroll.saturating_sub(excess);
+        }
+        if at_bottom { self.bootlog_scroll = self.bootlog_lines.len().saturating_sub(max_lines); }
+    }
+}
+
+fn state_name(state: u8) -> &'static str {
+    match state {
+        TASK_RUNNING => "Running",
+        TASK_READY => "Ready",
+        TASK_BLOCKED => "Blocked",
+        TASK_ZOMBIE => "Zombie",
+        _ => "?",
+    }
 }
 
 impl NyxApp for SysMonApp {
@@ -57,22 +151,20 @@ impl NyxApp for SysMonApp {
         if now.wrapping_sub(self.last_update_time) > 500 {
             sys_get_entity_stats(&mut self.entity_stats);
             self.active_cores = sys_get_active_cores();
-            sys_get_system_info(&mut self.sys_info);
-
-            let len = sys_get_boot_logs(&mut self.bootlog_buf);
-            if len != self.bootlog_last_len {
-                self.bootlog_last_len = len;
-                self.bootlog_lines.clear();
-                if let Ok(text) = core::str::from_utf8(&self.bootlog_buf[..len]) {
-                    for line in text.split('\n') {
-                        if !line.trim().is_empty() {
-                            self.bootlog_lines.push(String::from(line));
-                        }
-                    }
-                }
-                let max_lines = 24;
-                self.bootlog_scroll = self.bootlog_lines.len().saturating_sub(max_lines);
-            }
+            self.tasks_exited = sys_get_tasks_exited();
+            let _ = sys_get_system_info(&mut self.sys_info);
+            self.refresh_tasks();
+            let _ = sys_ipc_send(COMPOSITOR_PID, MSG_GET_FRAME_STATS, 0, 0);
+
+            if let Ok(mem) = sys_get_meminfo() { self.mem = mem; }
+            self.cpu_load = sys_get_cpu_load().min(1000);
+            let ram_load = if self.mem.total > 0 { self.mem.used * 1000 / self.mem.total } else { 0 };
+            if self.history.len() == HISTORY_LEN { self.history.remove(0); }
+            // Queue depth is charted on a 0..QUEUE_SCALE jobs axis
+            let queue_load = self.sys_info.pending_jobs.min(QUEUE_SCALE) * 1000 / QUEUE_SCALE;
+            self.history.push((self.cpu_load, ram_load, queue_load));
+
+            self.poll_bootlog();
             self.last_update_time = now;
             return true; // Data refreshed, force UI redraw
         }
@@ -80,25 +172,27 @@ impl NyxApp for SysMonApp {
     }
 
     fn draw(&mut self, canvas: &mut Canvas) {
+        let theme = theme::current();
         let width = canvas.width;
         let height = canvas.height;
 
-        canvas.fill_rect(0, 0, width, height, Color::WARM_BG);
-        canvas.fill_rect(0, 0, 150, height, Color::WARM_SURFACE);
-        canvas.fill_rect(150, 0, 1, height, Color::WARM_BORDER); 
+        canvas.fill_rect(0, 0, width, height, theme.window_bg);
+        canvas.fill_rect(0, 0, 150, height, theme.surface);
+        canvas.fill_rect(150, 0, 1, height, theme.border); 
 
-        canvas.print_str(15, 20, "SYS MON", Color::ACCENT_PRIMARY, 2);
+        canvas.print_str(15, 20, "SYS MON", theme.accent, 2);
 
         let tabs = [
             (SysMonState::Vitals, "Entity Vitals", 80),
             (SysMonState::Tasks, "Task Scheduler", 120),
             (SysMonState::Bootlog, "Kernel Bootlog", 160),
+            (SysMonState::Resources, "CPU & Memory", 200),
         ];
 
         for (s, text, y) in tabs.iter() {
             let is_active = self.state == *s;
-            if is_active { canvas.fill_rect(10, *y - 5, 130, 30, Color::ACCENT_PRIMARY); }
-            let text_color = if is_active { Color::WHITE } else { Color::TEXT_MUTED };
+            if is_active { canvas.fill_rect(10, *y - 5, 130, 30, theme.accent); }
+            let text_color = if is_active { Color::WHITE } else { theme.text_secondary };
             canvas.print_str(20, *y + 2, text, text_color, 1);
         }
 
@@ -106,11 +200,15 @@ impl NyxApp for SysMonApp {
 
         match self.state {
             SysMonState::Vitals => {
-                canvas.print_str(cx, 20, "Entity Live Telemetry", Color::TEXT_DARK, 2);
+                canvas.print_str(cx, 20, "Entity Live Telemetry", theme.text_primary, 2);
                 
-                let core_text = alloc::format!("Architecture: x86_64 SMP | Active Hardware Cores: {}", self.active_cores);
-                canvas.print_str(cx, 60, &core_text, Color::TEXT_MUTED, 1);
+                let core_text = alloc::format!("Architecture: x86_64 SMP | Active Hardware Cores: {} | Tasks Exited: {}",
+                    self.active_cores, self.tasks_exited);
+                canvas.print_str(cx, 60, &core_text, theme.text_secondary, 1);
                 canvas.print_str(cx, 80, "NVMe Lossless Compression: ACTIVE", Color::ACCENT_GREEN, 1);
+                let frame_text = alloc::format!("Compositor Frame Time: {}.{:02} ms avg | Frames Composed: {}",
+                    self.frame_time_us / 1000, (self.frame_time_us % 1000) / 10, self.frames_composed);
+                canvas.print_str(cx, 100, &frame_text, theme.text_secondary, 1);
 
                 let bars = [
                     ("Energy", self.entity_stats[0], 130, 0xFF_E74C3C),
@@ -121,9 +219,9 @@ impl NyxApp for SysMonApp {
 
                 for (label, val, y, color) in bars.iter() {
                     let text = alloc::format!("{}: {:.2}", label, val);
-                    canvas.print_str(cx, *y, &text, Color::TEXT_DARK, 1);
+                    canvas.print_str(cx, *y, &text, theme.text_primary, 1);
                     
-                    canvas.fill_rect(cx, *y + 20, cw, 12, Color::WARM_BORDER); // Scaled dynamically
+                    canvas.fill_rect(cx, *y + 20, cw, 12, theme.border); // Scaled dynamically
                     let fill_w = ((val.clamp(0.0, 100.0) / 100.0) * cw as f32) as usize;
                     if fill_w > 0 {
                         canvas.fill_rect(cx, *y + 20, fill_w, 12, *color);
@@ -131,28 +229,77 @@ impl NyxApp for SysMonApp {
                 }
             },
             SysMonState::Tasks => {
-                canvas.print_str(cx, 20, "Hardware & Scheduler", Color::TEXT_DARK, 2);
+                canvas.print_str(cx, 20, "Hardware & Scheduler", theme.text_primary, 2);
                 
                 let temp_color = if self.sys_info.current_temp >= 80 { 0xFF_E74C3C } else { Color::ACCENT_GREEN };
                 canvas.print_str(cx, 70, &alloc::format!("Silicon Temp: {} C", self.sys_info.current_temp), temp_color, 1);
-                canvas.print_str(cx, 90, &alloc::format!("CPU Fan Speed: {} RPM", self.sys_info.cpu_fan_rpm), Color::TEXT_DARK, 1);
-                canvas.print_str(cx, 110, &alloc::format!("GPU Fan Speed: {} RPM", self.sys_info.gpu_fan_rpm), Color::TEXT_DARK, 1);
+                canvas.print_str(cx, 90, &alloc::format!("CPU Fan Speed: {} RPM", self.sys_info.cpu_fan_rpm), theme.text_primary, 1);
+                canvas.print_str(cx, 110, &alloc::format!("GPU Fan Speed: {} RPM", self.sys_info.gpu_fan_rpm), theme.text_primary, 1);
 
-                canvas.fill_rect(cx, 140, cw, 1, Color::WARM_BORDER);
-                canvas.print_str(cx, 155, &alloc::format!("Total Kernel Tasks: {}", self.sys_info.task_count), Color::TEXT_DARK, 1);
-                
-                let mut ty = 185;
-                let limit = core::cmp::min(self.sys_info.task_count as usize, 10);
-                for i in 0..limit {
-                    let t = &self.sys_info.tasks[i];
-                    let name = core::str::from_utf8(&t.name).unwrap_or("Unknown").trim_matches(char::from(0));
-                    let t_str = alloc::format!("PID {:02} | {} | {} Ticks", t.pid, name, t.cpu_ticks);
-                    canvas.print_str(cx, ty, &t_str, Color::TEXT_MUTED, 1);
+                canvas.fill_rect(cx, 140, cw, 1, theme.border);
+                canvas.print_str(cx, 155, &alloc::format!("Total Kernel Tasks: {}", self.tasks.len()), theme.text_primary, 1);
+                canvas.print_str(cx, 175, "PID | Name | Tickets | CPU (last 0.5 s) | State", theme.text_primary, 1);
+
+                // Busiest first; the boosted foreground app shows up by its ticket count
+                let mut order: Vec<usize> = (0..self.tasks.len()).collect();
+                order.sort_by_key(|&i| core::cmp::Reverse(self.task_share[i]));
+                let mut ty = 200;
+                for &i in order.iter().take(height.saturating_sub(ty + 20) / 20) {
+                    let t = &self.tasks[i];
+                    let share = self.task_share[i];
+                    let name = if t.is_idle != 0 { "idle" } else { t.name() };
+                    let t_str = alloc::format!("PID {:02} | {} | {} | {}.{} % | {}",
+                        t.pid, name, t.tickets, share / 10, share % 10, state_name(t.state));
+                    let color = if t.tickets > DEFAULT_TICKETS { theme.accent } else { theme.text_secondary };
+                    canvas.print_str(cx, ty, &t_str, color, 1);
                     ty += 20;
                 }
             },
+            SysMonState::Resources => {
+                canvas.print_str(cx, 20, "CPU & Memory", theme.text_primary, 2);
+
+                const MIB: u64 = 1024 * 1024;
+                let cpu_text = alloc::format!("CPU Load: {}.{} % (last second, all cores)", self.cpu_load / 10, self.cpu_load % 10);
+                canvas.print_str(cx, 60, &cpu_text, CPU_COLOR, 1);
+                let ram_text = alloc::format!("RAM: {} MiB used / {} MiB total | {} MiB free",
+                    self.mem.used / MIB, self.mem.total / MIB, self.mem.free / MIB);
+                canvas.print_str(cx, 80, &ram_text, RAM_COLOR, 1);
+                let heap_text = alloc::format!("Kernel Heap: {} KiB in use | peak {} KiB | {} MiB mapped",
+                    self.mem.kernel_heap_used / 1024, self.mem.kernel_heap_peak / 1024, self.mem.kernel_heap_size / MIB);
+                canvas.print_str(cx, 100, &heap_text, theme.text_secondary, 1);
+                let queue_text = alloc::format!("Kernel Work Queue: {} pending | {} done",
+                    self.sys_info.pending_jobs, self.sys_info.completed_jobs);
+                canvas.print_str(cx, 115, &queue_text, QUEUE_COLOR, 1);
+                let audio_text = match self.sys_info.audio & AUDIO_HDA {
+                    0 => "Audio: PC speaker",
+                    _ => "Audio: PC speaker | HD Audio (no driver)",
+                };
+                canvas.print_str(cx + queue_text.len() * 8 + 24, 115, audio_text, theme.text_secondary, 1);
+
+                // Usage graph: newest sample on the right, 0% at the bottom
+                let gy = 140; let gh = height.saturating_sub(gy + 40).max(40);
+                canvas.fill_rect(cx, gy, cw, gh, 0xFF_1E1E1E);
+                for quarter in 1..4 {
+                    canvas.fill_rect(cx, gy + gh * quarter / 4, cw, 1, 0xFF_333333);
+                }
+                let step = (cw / HISTORY_LEN).max(1);
+                let start_x = cx + cw.saturating_sub(self.history.len() * step);
+                for (i, &(cpu, ram, queue)) in self.history.iter().enumerate() {
+                    let x = start_x + i * step;
+                    for (value, color) in [(queue, QUEUE_COLOR), (ram, RAM_COLOR), (cpu, CPU_COLOR)] {
+                        let y = gy + gh - 2 - ((gh - 2) as u64 * value / 1000) as usize;
+                        canvas.fill_rect(x, y, step, 2, color);
+                    }
+                }
+                canvas.fill_rect(cx, gy + gh + 12, 10, 10, CPU_COLOR);
+                canvas.print_str(cx + 16, gy + gh + 13, "CPU", theme.text_primary, 1);
+                canvas.fill_rect(cx + 70, gy + gh + 12, 10, 10, RAM_COLOR);
+                canvas.print_str(cx + 86, gy + gh + 13, "RAM", theme.text_primary, 1);
+                canvas.fill_rect(cx + 140, gy + gh + 12, 10, 10, QUEUE_COLOR);
+                canvas.print_str(cx + 156, gy + gh + 13, &alloc::format!("Queue (0-{} jobs)", QUEUE_SCALE), theme.text_primary, 1);
+            },
             SysMonState::Bootlog => {
-                canvas.print_str(cx, 20, "Kernel Ring Buffer (dmesg)", Color::TEXT_DARK, 2);
+                canvas.print_str(cx, 20, "Kernel Ring Buffer (dmesg)", theme.text_primary, 2);
                 
                 let log_y = 60; let log_h = height.saturating_sub(80);
                 canvas.fill_rect(cx, log_y, cw, log_h, 0xFF_1E1E1E); 
@@ -184,6 +331,13 @@ impl NyxApp for SysMonApp {
         }
     }
 
+    fn on_message(&mut self, msg: &IpcMessage) -> bool {
+        if msg.msg_type != MSG_FRAME_STATS { return false; }
+        self.frame_time_us = msg.data1;
+        self.frames_composed = msg.data2;
+        self.state == SysMonState::Vitals
+    }
+
     fn on_mouse(&mut self, mx: usize, my: usize, _clicked: bool) -> bool {
         let mut needs_redraw = false;
 
@@ -192,6 +346,7 @@ impl NyxApp for SysMonApp {
             if my >= 75 && my <= 105 { self.state = SysMonState::Vitals; }
             else if my >= 115 && my <= 145 { self.state = SysMonState::Tasks; }
             else if my >= 155 && my <= 185 { self.state = SysMonState::Bootlog; }
+            else if my >= 195 && my <= 225 { self.state = SysMonState::Resources; }
             if self.state != old_state { needs_redraw = true; }
         }
 
@@ -218,9 +373,7 @@ impl NyxApp for SysMonApp {
 #[unsafe(no_mangle)]
 #[unsafe(link_section = ".text.entry")]
 pub extern "C" fn _start() -> ! {
-    let heap_start = sys_alloc_pages(512);
-    if heap_start == 0 { sys_exit(1); }
-    unsafe { ALLOCATOR.lock().init(heap_start as *mut u8, 512 * 4096); }
+    if !ALLOCATOR.init(512 * 4096) { sys_exit(1); }
 
     nyx_gui::app::run(SysMonApp::new());
 }
@@ -0,0 +1,261 @@
+#![no_std]
+#![no_main]
+#![allow(warnings)]
+
+extern crate alloc;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use nyx_api::*;
+use nyx_gui::heap::BrkHeap;
+use nyx_gui::app::NyxApp;
+use nyx_gui::canvas::{Canvas, Color};
+use nyx_gui::theme;
+
+#[global_allocator]
+static ALLOCATOR: BrkHeap = BrkHeap::empty();
+
+// ==========================================
+// TASK MANAGER
+// ==========================================
+// Every kernel task with its tickets, its share of the CPU over the last
+// second and its state, refreshed once a second. Each row has buttons to
+// halve or double the task's tickets and to kill it; the kernel refuses
+// both for its own tasks, init and the compositor, and the row flashes red.
+
+const REFRESH_MS: usize = 1000;
+const FLASH_MS: usize = 600;
+const MAX_TASKS: usize = 128;
+
+const HEADER_Y: usize = 44;
+const LIST_Y: usize = HEADER_Y + 22;
+const ROW_H: usize = 24;
+const STATUS_H: usize = 24;
+
+// Column x positions
+const COL_PID: usize = 14;
+const COL_NAME: usize = 64;
+const COL_TICKETS: usize = 214;
+const COL_CPU: usize = 294;
+const COL_STATE: usize = 364;
+// (x, width) of the row buttons
+const BTN_LOWER: (usize, usize) = (450, 24);
+const BTN_RAISE: (usize, usize) = (480, 24);
+const BTN_KILL: (usize, usize) = (512, 48);
+const BTN_H: usize = 18;
+
+#[derive(Clone, Copy, PartialEq)]
+enum SortBy { Cpu, Pid }
+
+#[derive(Clone, Copy, PartialEq)]
+enum RowAction { Lower, Raise, Kill }
+
+struct TaskManagerApp {
+    tasks: Vec<TaskStat>,
+    // CPU share since the last refresh in per mille, parallel to `tasks`
+    share: Vec<u64>,
+    // (pid, cpu_ticks) at the last refresh
+    prev_ticks: Vec<(u64, u64)>,
+    // Indices into `tasks` in display order
+    order: Vec<usize>,
+    sort: SortBy,
+    scroll: usize,
+    last_refresh: usize,
+    // (pid, until) of the row whose last request was refused
+    flash: Option<(u64, usize)>,
+    status: String,
+    height: usize,
+}
+
+fn state_name(state: u8) -> &'static str {
+    match state {
+        TASK_RUNNING => "Running",
+        TASK_READY => "Ready",
+        TASK_BLOCKED => "Blocked",
+        TASK_ZOMBIE => "Zombie",
+        _ => "?",
+    }
+}
+
+fn in_button(mx: usize, (x, w): (usize, usize)) -> bool { mx >= x && mx < x + w }
+
+impl TaskManagerApp {
+    fn new() -> Self {
+        Self {
+            tasks: Vec::new(), share: Vec::new(), prev_ticks: Vec::new(), order: Vec::new(),
+            sort: SortBy::Cpu, scroll: 0, last_refresh: 0,
+            flash: None, status: String::new(),
+            height: 420,
+        }
+    }
+
+    fn refresh(&mut self) {
+        let mut stats = alloc::vec![TaskStat::default(); MAX_TASKS];
+        let count = match sys_task_stats(&mut stats) { Ok(n) => n, Err(_) => return };
+        stats.truncate(count);
+
+        let deltas: Vec<u64> = stats.iter().map(|t| {
+            let before = self.prev_ticks.iter().find(|(pid, _)| *pid == t.pid).map_or(0, |&(_, ticks)| ticks);
+            t.cpu_ticks.saturating_sub(before)
+        }).collect();
+        let total: u64 = deltas.iter().sum();
+        self.share = deltas.iter().map(|&d| if total > 0 { d * 1000 / total } else { 0 }).collect();
+        self.prev_ticks = stats.iter().map(|t| (t.pid, t.cpu_ticks)).collect();
+        self.tasks = stats;
+        self.sort_rows();
+    }
+
+    fn sort_rows(&mut self) {
+        self.order = (0..self.tasks.len()).collect();
+        match self.sort {
+            SortBy::Cpu => self.order.sort_by_key(|&i| (core::cmp::Reverse(self.share[i]), self.tasks[i].pid)),
+            SortBy::Pid => self.order.sort_by_key(|&i| self.tasks[i].pid),
+        }
+        self.scroll = self.scroll.min(self.max_scroll());
+    }
+
+    fn visible_rows(&self) -> usize {
+        self.height.saturating_sub(LIST_Y + STATUS_H) / ROW_H
+    }
+
+    fn max_scroll(&self) -> usize {
+        self.order.len().saturating_sub(self.visible_rows())
+    }
+
+    /// Asks the kernel to do `action` to `task`; a refusal flashes its row.
+    fn apply(&mut self, task: TaskStat, action: RowAction) {
+        let result = match action {
+            RowAction::Lower => sys_set_tickets(task.pid, (task.tickets / 2).max(1)),
+            RowAction::Raise => sys_set_tickets(task.pid, task.tickets.saturating_mul(2)),
+            RowAction::Kill => sys_kill(task.pid),
+        };
+        match result {
+            Ok(()) => {
+                self.flash = None;
+                self.status = match action {
+                    RowAction::Kill => alloc::format!("Asked PID {} to exit", task.pid),
+                    _ => String::new(),
+                };
+            }
+            Err(e) => {
+                self.flash = Some((task.pid, sys_get_time() + FLASH_MS));
+                self.status = alloc::format!("PID {} ({}): {}", task.pid, task.name(), e.message());
+            }
+        }
+        self.refresh();
+    }
+}
+
+impl NyxApp for TaskManagerApp {
+    fn title(&self) -> &str { "Task Manager" }
+    fn initial_width(&self) -> usize { 580 }
+    fn initial_height(&self) -> usize { 420 }
+
+    fn update(&mut self) -> bool {
+        let now = sys_get_time();
+        let mut redraw = false;
+        if let Some((_, until)) = self.flash {
+            if now >= until { self.flash = None; redraw = true; }
+        }
+        if self.last_refresh == 0 || now.wrapping_sub(self.last_refresh) >= REFRESH_MS {
+            self.refresh();
+            self.last_refresh = now;
+            redraw = true;
+        }
+        redraw
+    }
+
+    fn draw(&mut self, canvas: &mut Canvas) {
+        let theme = theme::current();
+        let (width, height) = (canvas.width, canvas.height);
+        self.height = height;
+        self.scroll = self.scroll.min(self.max_scroll());
+
+        canvas.fill_rect(0, 0, width, height, theme.window_bg);
+        canvas.print_str(COL_PID, 14, "Task Manager", theme.accent, 2);
+        canvas.print_str(COL_STATE, 18, &alloc::format!("{} tasks", self.tasks.len()), theme.text_secondary, 1);
+
+        // Clicking PID or CPU sorts by it
+        canvas.fill_rect(0, HEADER_Y, width, LIST_Y - HEADER_Y, theme.surface);
+        canvas.fill_rect(0, LIST_Y - 1, width, 1, theme.border);
+        let header = |sort: SortBy| if self.sort == sort { theme.accent } else { theme.text_primary };
+        canvas.print_str(COL_PID, HEADER_Y + 7, "PID", header(SortBy::Pid), 1);
+        canvas.print_str(COL_NAME, HEADER_Y + 7, "Name", theme.text_primary, 1);
+        canvas.print_str(COL_TICKETS, HEADER_Y + 7, "Tickets", theme.text_primary, 1);
+        canvas.print_str(COL_CPU, HEADER_Y + 7, "CPU", header(SortBy::Cpu), 1);
+        canvas.print_str(COL_STATE, HEADER_Y + 7, "State", theme.text_primary, 1);
+
+        let flashing = self.flash.map(|(pid, _)| pid);
+        for (row, &i) in self.order.iter().skip(self.scroll).take(self.visible_rows()).enumerate() {
+            let task = &self.tasks[i];
+            let y = LIST_Y + row * ROW_H;
+            let (bg, fg) = if flashing == Some(task.pid) { (Color::ACCENT_RED, Color::WHITE) }
+                else if row % 2 == 1 { (theme.surface, theme.text_primary) }
+                else { (theme.window_bg, theme.text_primary) };
+            canvas.fill_rect(0, y, width, ROW_H, bg);
+
+            let text_y = y + (ROW_H - 8) / 2;
+            let share = self.share[i];
+            let name = if task.is_idle != 0 { "idle" } else { task.name() };
+            canvas.print_str(COL_PID, text_y, &alloc::format!("{}", task.pid), fg, 1);
+            canvas.print_str(COL_NAME, text_y, name, fg, 1);
+            let tickets_color = if task.tickets > DEFAULT_TICKETS && bg != Color::ACCENT_RED { theme.accent } else { fg };
+            canvas.print_str(COL_TICKETS, text_y, &alloc::format!("{}", task.tickets), tickets_color, 1);
+            canvas.print_str(COL_CPU, text_y, &alloc::format!("{}.{} %", share / 10, share % 10), fg, 1);
+            canvas.print_str(COL_STATE, text_y, state_name(task.state), fg, 1);
+
+            let btn_y = y + (ROW_H - BTN_H) / 2;
+            for ((x, w), label) in [(BTN_LOWER, "-"), (BTN_RAISE, "+"), (BTN_KILL, "Kill")] {
+                canvas.fill_rect(x, btn_y, w, BTN_H, theme.border);
+                canvas.print_str(x + (w - label.len() * 8) / 2, btn_y + (BTN_H - 8) / 2, label, theme.text_primary, 1);
+            }
+        }
+
+        let status_y = height.saturating_sub(STATUS_H);
+        canvas.fill_rect(0, status_y, width, STATUS_H, theme.surface);
+        canvas.fill_rect(0, status_y, width, 1, theme.border);
+        let status_color = if self.flash.is_some() { Color::ACCENT_RED } else { theme.text_secondary };
+        canvas.print_str(COL_PID, status_y + 8, &self.status, status_color, 1);
+    }
+
+    fn on_mouse(&mut self, mx: usize, my: usize, _clicked: bool) -> bool {
+        if my >= HEADER_Y && my < LIST_Y {
+            let sort = if mx >= COL_PID && mx < COL_NAME { SortBy::Pid }
+                else if mx >= COL_CPU && mx < COL_STATE { SortBy::Cpu }
+                else { return false };
+            self.sort = sort;
+            self.sort_rows();
+            return true;
+        }
+        if my < LIST_Y { return false; }
+
+        let row = (my - LIST_Y) / ROW_H;
+        if row >= self.visible_rows() { return false; }
+        let Some(&i) = self.order.get(self.scroll + row) else { return false };
+        let action = if in_button(mx, BTN_LOWER) { RowAction::Lower }
+            else if in_button(mx, BTN_RAISE) { RowAction::Raise }
+            else if in_button(mx, BTN_KILL) { RowAction::Kill }
+            else { return false };
+        let task = self.tasks[i];
+        self.apply(task, action);
+        true
+    }
+
+    fn on_wheel(&mut self, _mx: usize, _my: usize, notches: i32) -> bool {
+        let old = self.scroll;
+        self.scroll = if notches > 0 { self.scroll.saturating_sub(notches as usize * 3) }
+            else { (self.scroll + notches.unsigned_abs() as usize * 3).min(self.max_scroll()) };
+        self.scroll != old
+    }
+}
+
+#[unsafe(no_mangle)]
+#[unsafe(link_section = ".text.entry")]
+pub extern "C" fn _start() -> ! {
+    if !ALLOCATOR.init(256 * 4096) { sys_exit(1); }
+
+    nyx_gui::app::run(TaskManagerApp::new());
+}
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! { sys_exit(111); }
@@ -4,44 +4,1103 @@
 
 extern crate alloc;
 use alloc::string::String;
-use linked_list_allocator::LockedHeap;
+use alloc::vec::Vec;
+use alloc::collections::VecDeque;
 
 use nyx_api::*;
-use nyx_gui::app::NyxApp;
-use nyx_gui::canvas::{Canvas, Color};
+use nyx_gui::heap::BrkHeap;
+use nyx_gui::app::{NyxApp, COMPOSITOR_PID};
+use nyx_gui::clipboard::{Clipboard, SystemClipboard};
+use nyx_gui::canvas::Canvas;
+use nyx_gui::effects::blend_color;
+use nyx_gui::fileops::{self, FileCopy};
+use nyx_gui::font;
+use nyx_gui::scale;
+use nyx_gui::theme;
+use nyx_core::ansi::{self, Action, Erase, Style};
+use nyx_core::cmdline::{self, Output};
+use nyx_core::date::DateTime;
+use nyx_core::line_edit::{History, LineEditor};
+use nyx_core::lines::LineIndex;
+use nyx_core::path;
+use nyx_core::sntp;
+use nyx_core::wrap::wrap_line;
 
 #[global_allocator]
-static ALLOCATOR: LockedHeap = LockedHeap::empty();
+static ALLOCATOR: BrkHeap = BrkHeap::empty();
 
-const BG_COLOR: u32 = 0xFF0D0D0D; 
-const FG_COLOR: u32 = 0xFF00FF66; 
+// Cell metrics at 1x; the UI scale multiplies them all
 const FONT_W: usize = 8;
 const FONT_H: usize = 8;
+const LINE_H: usize = FONT_H + 4;
+
+// The 16 ANSI colors: normal 0-7, then bright 8-15
+const ANSI_PALETTE: [u32; 16] = [
+    0xFF_000000, 0xFF_CD3131, 0xFF_0DBC79, 0xFF_E5E510, 0xFF_2472C8, 0xFF_BC3FBC, 0xFF_11A8CD, 0xFF_E5E5E5,
+    0xFF_666666, 0xFF_F14C4C, 0xFF_23D18B, 0xFF_F5F543, 0xFF_3B8EEA, 0xFF_D670D6, 0xFF_29B8DB, 0xFF_FFFFFF,
+];
+// SGR codes the built-ins color their own output with
+const SGR_RED: &str = "\x1b[31m";
+const SGR_CYAN: &str = "\x1b[36m";
+const SGR_RESET: &str = "\x1b[0m";
+// Furthest an escape sequence can move the cursor along a line
+const MAX_COLUMN: usize = 1024;
+
+// Lines kept for PageUp/PageDown once they scroll off the top
+const SCROLLBACK_LINES: usize = 500;
+// Rows scrolled per wheel notch
+const WHEEL_ROWS: usize = 3;
+// Run by each Terminal as it starts, if it exists
+const AUTORUN_PATH: &str = "/mnt/nvme/autorun.nsh";
+// How deep scripts may run other scripts, so one running itself stops
+const SCRIPT_DEPTH_MAX: usize = 8;
+// Scripts are read whole; anything bigger is refused rather than risk the heap
+const SCRIPT_MAX: usize = 64 * 1024;
+// Bytes read per syscall while indexing a file for the pager
+const INDEX_CHUNK: usize = 4096;
+
+const PROMPT: &str = "N> ";
+// Commands Tab completes as the first word
+const COMMANDS: [&str; 24] = [
+    "help", "clear", "colortest", "echo", "ls", "cat", "less", "write", "cp", "mv", "run", "spawn", "mkdir", "heaptest",
+    "lsblk", "lsdisk", "df", "display", "wallpaper", "netclock", "settings", "explorer", "sysmon", "network",
+];
+// Entered lines, kept across reboots on the data disk
+const HISTORY_PATH: &str = "/mnt/nvme/term_history";
+const HISTORY_MAX: usize = 100;
+// HISTORY_MAX lines of any sensible length; a longer file is cut here
+const HISTORY_BYTES: usize = 16 * 1024;
+
+/// Tab completion in progress: what further Tabs cycle through.
+struct Completion {
+    // Byte offset in the input where the completed word starts
+    start: usize,
+    matches: Vec<String>,
+    next: usize,
+}
+
+/// One character of the scrollback, in the colors it was printed in.
+#[derive(Clone, Copy)]
+struct TerminalCell {
+    ch: char,
+    style: Style,
+}
+
+impl TerminalCell {
+    const BLANK: Self = Self { ch: ' ', style: Style { fg: None, bg: None, bold: false } };
+}
+
+/// A cp or mv held back by "overwrite?" until the next key answers it.
+struct PendingTransfer {
+    moving: bool,
+    src: String,
+    dst: String,
+}
+
+/// Where built-in commands print: the screen, or for a redirected command
+/// a buffer that's handed on to the file or the pager once it finishes.
+enum Sink {
+    Terminal,
+    Capture(String),
+}
+
+enum PagerText {
+    // Read from disk as the pager scrolls
+    File(String),
+    // The output of `cmd | less`, held whole
+    Piped(String),
+}
+
+/// `less`: text shown a screenful at a time. For a file only where each
+/// line starts stays in memory; the lines on screen are read as it scrolls.
+struct Pager {
+    name: String,
+    text: PagerText,
+    index: LineIndex,
+    // First file line on screen
+    top: usize,
+    // Screen rows for the lines from `top`, and the (top, cols, rows) they were read for
+    view: Vec<String>,
+    view_key: Option<(usize, usize, usize)>,
+    // Lines at least partly on screen
+    shown: usize,
+    // Where a page down goes, and whether the last line is already fully shown
+    next_top: usize,
+    at_end: bool,
+}
+
+impl Pager {
+    fn new(name: String, text: PagerText, index: LineIndex) -> Self {
+        Self { name, text, index, top: 0, view: Vec::new(), view_key: None, shown: 0, next_top: 0, at_end: true }
+    }
+
+    fn file(path: String, index: LineIndex) -> Self {
+        let name = String::from(path.rsplit('/').next().unwrap_or(&path));
+        Self::new(name, PagerText::File(path), index)
+    }
+
+    fn piped(name: &str, text: String) -> Self {
+        let mut index = LineIndex::new();
+        index.push(text.as_bytes());
+        Self::new(alloc::format!("{} |", name), PagerText::Piped(text), index)
+    }
+
+    /// Line `i` as text, at most `max` bytes of it; a read error cuts it short.
+    fn read_line(&self, i: usize, max: usize) -> String {
+        let range = self.index.line(i);
+        let len = range.len().min(max);
+        let text = match &self.text {
+            PagerText::Piped(text) => String::from_utf8_lossy(&text.as_bytes()[range.start..range.start + len]).into_owned(),
+            PagerText::File(path) => {
+                let mut buf = alloc::vec![0u8; len];
+                let mut done = 0;
+                while done < buf.len() {
+                    match sys_fs_read(path, &mut buf[done..], range.start + done) {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => done += n,
+                    }
+                }
+                buf.truncate(done);
+                String::from_utf8_lossy(&buf).into_owned()
+            }
+        };
+        String::from(text.trim_end_matches('\r'))
+    }
+
+    /// Reads and wraps the lines that fill `rows` rows of `cols` from `top`.
+    fn layout(&mut self, cols: usize, rows: usize) {
+        if self.view_key == Some((self.top, cols, rows)) { return; }
+        self.view_key = Some((self.top, cols, rows));
+        self.view.clear();
+
+        let mut line = self.top;
+        let mut cut = false;
+        while line < self.index.lines() && self.view.len() < rows {
+            // Nothing past a screenful of a long line can show
+            let text = self.read_line(line, cols * rows);
+            let wrapped = wrap_line(&text, cols);
+            let room = rows - self.view.len();
+            cut = wrapped.len() > room;
+            self.view.extend(wrapped.into_iter().take(room).map(String::from));
+            line += 1;
+        }
+        self.shown = line - self.top;
+        self.at_end = line >= self.index.lines() && !cut;
+        // A line cut off at the bottom starts the next page, unless it's the only one on screen
+        let next = if cut && line - 1 > self.top { line - 1 } else { line };
+        self.next_top = next.min(self.index.lines().saturating_sub(1));
+    }
+
+    fn status(&self) -> String {
+        let first = if self.shown == 0 { 0 } else { self.top + 1 };
+        let end = if self.at_end { " (END)" } else { "" };
+        // A plain '-': the console font has no em dash
+        alloc::format!("{} - lines {}-{}/{}{}  [space/b page, arrows scroll, q quit]",
+            self.name, first, self.top + self.shown, self.index.lines(), end)
+    }
+}
+
+fn load_history() -> History {
+    let mut buf = alloc::vec![0u8; HISTORY_BYTES];
+    let n = sys_fs_read(HISTORY_PATH, &mut buf, 0).unwrap_or(0);
+    History::parse(&String::from_utf8_lossy(&buf[..n]), HISTORY_MAX)
+}
+
+/// Where every line of the `size` byte file at `path` starts.
+fn index_file(path: &str, size: usize) -> NyxResult<LineIndex> {
+    let mut index = LineIndex::new();
+    let mut buf = alloc::vec![0u8; INDEX_CHUNK];
+    while index.len() < size {
+        match sys_fs_read(path, &mut buf, index.len())? {
+            0 => break,
+            n => index.push(&buf[..n]),
+        }
+    }
+    Ok(index)
+}
 
 struct TerminalApp {
-    input_buffer: String,
-    output_history: String,
+    input: LineEditor,
+    history: History,
+    // Set from a Tab until any other key
+    completion: Option<Completion>,
+    // Logical output lines; the last entry is the line currently being written
+    scrollback: VecDeque<Vec<TerminalCell>>,
+    // Escape sequences in output, which may arrive split across prints
+    ansi: ansi::Parser,
+    // Where the next char goes in the last line; escape sequences can move it back
+    column: usize,
+    // How many wrapped rows the view is scrolled up from the bottom
+    scroll_offset: usize,
+    max_scroll: usize,
+    page_rows: usize,
+    cols: usize,
+    // Set while `less` (or a long `cat`) has the window
+    pager: Option<Pager>,
+    // Set while cp or mv waits for y/n; the prompt is hidden meanwhile
+    confirm: Option<PendingTransfer>,
+    sink: Sink,
+    // Set when the command being run reports an error
+    failed: bool,
+    // Scripts running inside one another right now
+    script_depth: usize,
     blink_timer: usize,
     cursor_visible: bool,
+    cwd: String,
+    clipboard: SystemClipboard,
 }
 
 impl TerminalApp {
     fn new() -> Self {
-        Self {
-            input_buffer: String::new(),
-            output_history: String::from("NyxOS v0.1 Shell\nType 'help' for commands.\n"),
+        let mut term = Self {
+            input: LineEditor::new(),
+            history: load_history(),
+            completion: None,
+            scrollback: VecDeque::new(),
+            ansi: ansi::Parser::new(),
+            column: 0,
+            scroll_offset: 0,
+            max_scroll: 0,
+            page_rows: 1,
+            cols: 80,
+            pager: None,
+            confirm: None,
+            sink: Sink::Terminal,
+            failed: false,
+            script_depth: 0,
             blink_timer: 0,
             cursor_visible: true,
+            cwd: String::from("/mnt/nvme"),
+            clipboard: SystemClipboard {},
+        };
+        term.scrollback.push_back(Vec::new());
+        term.print("NyxOS v0.1 Shell\nType 'help' for commands.\n");
+        term
+    }
+
+    /// Command output: to the screen, or captured while redirected.
+    fn print(&mut self, text: &str) {
+        match &mut self.sink {
+            Sink::Capture(out) => out.push_str(text),
+            Sink::Terminal => self.print_screen(text),
+        }
+    }
+
+    /// Appends to the screen whatever the sink, and snaps the view back to the bottom.
+    /// Color and cursor escape sequences take effect rather than showing.
+    fn print_screen(&mut self, text: &str) {
+        for c in text.chars() {
+            let Some(action) = self.ansi.feed(c) else { continue };
+            if action == Action::Print('\n') {
+                self.scrollback.push_back(Vec::new());
+                self.column = 0;
+                continue;
+            }
+            let Some(line) = self.scrollback.back_mut() else { continue };
+            match action {
+                Action::Print(ch) => {
+                    let cell = TerminalCell { ch, style: self.ansi.style };
+                    if self.column < line.len() {
+                        line[self.column] = cell;
+                    } else {
+                        line.resize(self.column, TerminalCell::BLANK);
+                        line.push(cell);
+                    }
+                    self.column += 1;
+                },
+                Action::Column(n) => self.column = n.min(MAX_COLUMN),
+                Action::EraseLine(Erase::ToEnd) => line.truncate(self.column),
+                Action::EraseLine(Erase::ToStart) => line.iter_mut().take(self.column + 1).for_each(|cell| *cell = TerminalCell::BLANK),
+                Action::EraseLine(Erase::All) => line.clear(),
+            }
+        }
+        while self.scrollback.len() > SCROLLBACK_LINES { self.scrollback.pop_front(); }
+        self.scroll_offset = 0;
+    }
+
+    fn clear(&mut self) {
+        self.scrollback.clear();
+        self.scrollback.push_back(Vec::new());
+        self.column = 0;
+        self.scroll_offset = 0;
+    }
+
+    /// `text` in an SGR color when it's going to the screen; a file or the
+    /// pager gets it plain.
+    fn paint(&self, text: &str, sgr: &str) -> String {
+        match self.sink {
+            Sink::Terminal => alloc::format!("{}{}{}", sgr, text, SGR_RESET),
+            Sink::Capture(_) => String::from(text),
+        }
+    }
+
+    /// Turns a user-typed path into an absolute VFS path relative to the working directory.
+    fn resolve_path(&self, arg: &str) -> String { path::normalize(&path::join(&self.cwd, arg)) }
+
+    /// Runs a line from the prompt, sending its output wherever the line
+    /// says. False if the command reported an error.
+    fn process_line(&mut self, line: &str) -> bool {
+        self.failed = false;
+        let parsed = match cmdline::parse(line) {
+            Ok(parsed) => parsed,
+            Err(e) => { self.print_failure(&alloc::format!("terminal: {}\n", e.message())); return false; }
+        };
+        if parsed.output == Output::Terminal {
+            self.process_command(parsed.command);
+            return !self.failed;
+        }
+
+        // A script's lines can redirect while the script itself is redirected
+        let outer = core::mem::replace(&mut self.sink, Sink::Capture(String::new()));
+        self.process_command(parsed.command);
+        let Sink::Capture(out) = core::mem::replace(&mut self.sink, outer) else { return false };
+        match parsed.output {
+            Output::File { path, append } => self.write_output(path, append, out),
+            Output::Pager => self.pager = Some(Pager::piped(parsed.command, out)),
+            Output::Terminal => {}
+        }
+        !self.failed
+    }
+
+    /// Runs each line of a .nsh script as if it were typed at the prompt,
+    /// echoing it first. Blank lines and lines starting with # are skipped,
+    /// `set echo off` / `set echo on` switch the echo, and the first line
+    /// that fails stops the script unless it ends with `;ignore`.
+    fn run_script(&mut self, path: &str) {
+        if self.script_depth >= SCRIPT_DEPTH_MAX {
+            return self.print_failure(&alloc::format!("run: {}: scripts nested too deeply\n", path));
+        }
+        let mut text = Vec::new();
+        match sys_fs_size(path) {
+            Ok(size) if size > SCRIPT_MAX => return self.print_failure(&alloc::format!("run: {}: script too large\n", path)),
+            Ok(size) => text.resize(size, 0),
+            Err(e) => return self.print_error("run", path, e),
+        }
+        match sys_fs_read(path, &mut text, 0) {
+            Ok(n) => text.truncate(n),
+            Err(e) => return self.print_error("run", path, e),
+        }
+        let text = String::from_utf8_lossy(&text).into_owned();
+
+        self.script_depth += 1;
+        let mut echo = true;
+        let mut ok = true;
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') { continue; }
+            match line {
+                "set echo off" => { echo = false; continue; },
+                "set echo on" => { echo = true; continue; },
+                _ => {},
+            }
+            let (line, ignore) = match line.strip_suffix(";ignore") {
+                Some(line) => (line.trim_end(), true),
+                None => (line, false),
+            };
+            if echo { self.print_screen(&alloc::format!("{}{}\n", PROMPT, line)); }
+            if !self.process_line(line) && !ignore {
+                self.print_failure(&alloc::format!("run: {}: stopped at line {}\n", path, n + 1));
+                ok = false;
+                break;
+            }
+        }
+        self.script_depth -= 1;
+        // Ignored failures don't make the whole script fail
+        self.failed = !ok;
+    }
+
+    /// Puts a redirected command's output in `path`; `>>` keeps what was
+    /// there and adds to the end.
+    fn write_output(&mut self, path: &str, append: bool, out: String) {
+        let path = self.resolve_path(path);
+        let mut data = Vec::new();
+        if append {
+            match sys_fs_size(&path) {
+                Ok(size) => {
+                    data.resize(size, 0);
+                    match sys_fs_read(&path, &mut data, 0) {
+                        Ok(n) => data.truncate(n),
+                        Err(e) => return self.print_error("terminal", &path, e),
+                    }
+                }
+                Err(NyxError::NotFound) => {}
+                Err(e) => return self.print_error("terminal", &path, e),
+            }
+        }
+        data.extend_from_slice(out.as_bytes());
+        match sys_fs_write(&path, &data, 0, FS_WRITE_TRUNCATE) {
+            Ok(n) if n == data.len() => {}
+            Ok(_) => self.print_failure("terminal: short write\n"),
+            Err(e) => self.print_error("terminal", &path, e),
+        }
+    }
+
+    /// Runs one built-in command; whatever it prints goes to the current sink.
+    fn process_command(&mut self, cmd: &str) {
+        if cmd == "help" {
+            self.print("Commands: help, clear, colortest, echo <text>, ls [-a] [-l] [path], cat <file>, less <file>, write <file> <text>, cp [-f] <src> <dst>, mv [-f] <src> <dst>, run <program | script.nsh>, spawn <program>, mkdir <path>, heaptest [MiB], lsblk, lsdisk, df [path], display [add-virtual WxH], wallpaper <file>, netclock [server-ip], settings, explorer, sysmon, network. End a command with > file, >> file or | less to send its output there.\n");
+        } else if cmd == "clear" {
+            self.clear();
+        } else if cmd == "colortest" {
+            self.cmd_colortest();
+        } else if cmd == "settings" {
+            self.print("Launching Settings...\n");
+            if let Ok(0) = sys_fork() { sys_execve("/mnt/nvme/apps/Settings.nyx/run.bin\0"); sys_exit(1); }
+        } else if cmd == "explorer" {
+            self.print("Launching Explorer...\n");
+            if let Ok(0) = sys_fork() { sys_execve("/mnt/nvme/apps/Explorer.nyx/run.bin\0"); sys_exit(1); }
+        } else if cmd == "sysmon" {
+            self.print("Launching System Monitor...\n");
+            if let Ok(0) = sys_fork() { sys_execve("/mnt/nvme/apps/SystemMonitor.nyx/run.bin\0"); sys_exit(1); }
+        } else if cmd == "network" {
+            self.print("Launching Network Suite...\n");
+            if let Ok(0) = sys_fork() { sys_execve("/mnt/nvme/apps/Network.nyx/run.bin\0"); sys_exit(1); }
+        } else if cmd == "lsblk" {
+            self.cmd_lsblk();
+        } else if cmd == "lsdisk" {
+            self.cmd_lsdisk();
+        } else if cmd == "df" || cmd.starts_with("df ") {
+            self.cmd_df(cmd[2..].trim());
+        } else if cmd == "display" || cmd.starts_with("display ") {
+            self.cmd_display(cmd[7..].trim());
+        } else if cmd == "heaptest" || cmd.starts_with("heaptest ") {
+            self.cmd_heaptest(cmd[8..].trim());
+        } else if cmd == "badptr" {
+            self.cmd_badptr();
+        } else if cmd == "ls" || cmd.starts_with("ls ") {
+            self.cmd_ls(cmd[2..].trim());
+        } else if cmd == "cat" || cmd.starts_with("cat ") {
+            self.cmd_cat(cmd[3..].trim());
+        } else if cmd == "less" || cmd.starts_with("less ") {
+            self.cmd_less(cmd[4..].trim());
+        } else if cmd == "mkdir" || cmd.starts_with("mkdir ") {
+            self.cmd_mkdir(cmd[5..].trim());
+        } else if cmd == "netclock" || cmd.starts_with("netclock ") {
+            self.cmd_netclock(cmd[8..].trim());
+        } else if cmd == "wallpaper" || cmd.starts_with("wallpaper ") {
+            self.cmd_wallpaper(cmd[9..].trim());
+        } else if cmd == "cp" || cmd.starts_with("cp ") {
+            self.cmd_transfer(false, cmd[2..].trim());
+        } else if cmd == "mv" || cmd.starts_with("mv ") {
+            self.cmd_transfer(true, cmd[2..].trim());
+        } else if cmd == "write" || cmd.starts_with("write ") {
+            self.cmd_write(cmd[5..].trim_start());
+        } else if cmd == "run" || cmd.starts_with("run ") {
+            self.cmd_run(cmd[3..].trim());
+        } else if cmd == "spawn" || cmd.starts_with("spawn ") {
+            self.cmd_spawn(cmd[5..].trim());
+        } else if cmd.starts_with("echo ") {
+            self.print(&cmd[5..]);
+            self.print("\n");
+        } else if !cmd.is_empty() {
+            let _ = sys_beep(880, 80);
+            self.print_failure("Unknown command. Type 'help'.\n");
+        }
+    }
+
+    /// `ls [-a] [-l] [path]`: names only, or with -l a column each for name,
+    /// size and modified time. Dot files and system entries need -a.
+    fn cmd_ls(&mut self, arg: &str) {
+        let (mut long, mut all, mut arg) = (false, false, arg);
+        while let Some(opts) = arg.split(' ').next().and_then(|w| w.strip_prefix('-')) {
+            for c in opts.chars() {
+                match c {
+                    'l' => long = true,
+                    'a' => all = true,
+                    _ => return self.print_failure(&alloc::format!("ls: unknown option -{}\n", c)),
+                }
+            }
+            arg = arg[1 + opts.len()..].trim_start();
+        }
+        let path = if arg.is_empty() { self.cwd.clone() } else { self.resolve_path(arg) };
+        let fd = match sys_fs_opendir(&path) {
+            Ok(fd) => fd,
+            Err(e) => return self.print_error("ls", &path, e),
+        };
+
+        // (name, size, mtime); sizes of directories are None
+        let mut rows: Vec<(String, Option<u64>, u64)> = Vec::new();
+        let mut buf = alloc::vec![0u8; 8192];
+        loop {
+            let n = match sys_fs_readdir(fd, &mut buf) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) => { self.print_error("ls", &path, e); break; }
+            };
+            for e in DirEntries::new(&buf, n) {
+                if (e.hidden || e.system) && !all { continue; }
+                if !long {
+                    let name = if e.is_dir { self.paint(&alloc::format!("{}/", e.name), SGR_CYAN) } else { String::from(e.name) };
+                    self.print(&name);
+                    self.print("\n");
+                    continue;
+                }
+                let name = if e.is_dir { alloc::format!("{}/", e.name) } else { String::from(e.name) };
+                rows.push((name, if e.is_dir { None } else { Some(e.size) }, e.mtime));
+            }
+        }
+        let _ = sys_close(fd);
+
+        let name_w = rows.iter().map(|(name, _, _)| name.chars().count()).max().unwrap_or(0);
+        for (name, size, mtime) in &rows {
+            let size = size.map_or(String::from("-"), |s| alloc::format!("{}", s));
+            // 0 means the file was written without a clock, or before there was one
+            let modified = if *mtime == 0 { String::from("-") } else { alloc::format!("{}", DateTime::from_unix(*mtime)) };
+            let mut padded = alloc::format!("{:<w$}", name, w = name_w);
+            if name.ends_with('/') { padded = self.paint(&padded, SGR_CYAN); }
+            let line = alloc::format!("{}  {:>10}  {}\n", padded, size, modified);
+            self.print(&line);
+        }
+    }
+
+    /// Prints "cmd: path: reason" for a failed syscall.
+    fn print_error(&mut self, cmd: &str, path: &str, err: NyxError) {
+        let line = alloc::format!("{}: {}: {}\n", cmd, path, err.message());
+        self.print_failure(&line);
+    }
+
+    /// Reports that the command failed. Errors stay on the screen when
+    /// output is redirected, and stop a script.
+    fn print_failure(&mut self, text: &str) {
+        self.failed = true;
+        self.print_screen(&alloc::format!("{}{}{}", SGR_RED, text, SGR_RESET));
+    }
+
+    /// Ends a half-written last line on screen so the prompt starts a row of
+    /// its own. Captured output is left exactly as the file had it.
+    fn end_line(&mut self) {
+        if matches!(self.sink, Sink::Terminal) && self.scrollback.back().map_or(false, |l| !l.is_empty()) {
+            self.print_screen("\n");
+        }
+    }
+
+    fn cmd_cat(&mut self, arg: &str) {
+        if arg.is_empty() {
+            self.print_failure("usage: cat <file>\n");
+            return;
+        }
+        let path = self.resolve_path(arg);
+        let size = match sys_fs_size(&path) {
+            Ok(size) => size,
+            Err(e) => return self.print_error("cat", &path, e),
+        };
+
+        // Output that wouldn't fit on screen goes to the pager instead. Bytes
+        // stand in for chars here, which only ever overcounts.
+        if let (Sink::Terminal, Ok(index)) = (&self.sink, index_file(&path, size)) {
+            let rows: usize = (0..index.lines()).map(|i| index.line(i).len().max(1).div_ceil(self.cols)).sum();
+            if rows >= self.page_rows {
+                self.pager = Some(Pager::file(path, index));
+                return;
+            }
+        }
+
+        let mut buf = [0u8; 256];
+        let mut offset = 0usize;
+        while offset < size {
+            match sys_fs_read(&path, &mut buf, offset) {
+                Ok(0) => break,
+                Ok(n) => {
+                    self.print(&String::from_utf8_lossy(&buf[..n]));
+                    offset += n;
+                }
+                Err(e) => {
+                    self.end_line();
+                    let reason = if e == NyxError::Io { "read error" } else { e.message() };
+                    let line = alloc::format!("cat: {}: {}\n", path, reason);
+                    return self.print_failure(&line);
+                }
+            }
+        }
+        self.end_line();
+    }
+
+    /// Tab: completes the word before the cursor, as a command if it's the
+    /// first word and as a path otherwise. Another Tab puts in the next match.
+    fn complete(&mut self) -> bool {
+        if self.completion.is_none() {
+            let (start, word) = self.input.word_before_cursor();
+            let mut matches: Vec<String> = if self.input.text()[..start].trim().is_empty() {
+                COMMANDS.iter().filter(|c| c.starts_with(word)).map(|c| String::from(*c)).collect()
+            } else {
+                self.path_matches(word)
+            };
+            if matches.is_empty() { return false; }
+            matches.sort();
+            self.completion = Some(Completion { start, matches, next: 0 });
+        }
+        let Some(completion) = &mut self.completion else { return false };
+        let choice = &completion.matches[completion.next];
+        self.input.replace_before_cursor(completion.start, choice);
+        completion.next = (completion.next + 1) % completion.matches.len();
+        true
+    }
+
+    /// Entries of the directory `word` points into whose names start with
+    /// its last component, each written the way `word` began.
+    fn path_matches(&self, word: &str) -> Vec<String> {
+        let (dir_part, prefix) = match word.rfind('/') {
+            Some(i) => (&word[..=i], &word[i + 1..]),
+            None => ("", word),
+        };
+        let dir = if dir_part.is_empty() { self.cwd.clone() } else { self.resolve_path(dir_part) };
+
+        let mut matches = Vec::new();
+        let mut name = [0u8; 256];
+        for i in 0..sys_fs_count(&dir).unwrap_or(0) {
+            let Ok(n) = sys_fs_get_name(&dir, i, &mut name) else { break };
+            let Ok(name) = core::str::from_utf8(&name[..n]) else { continue };
+            if name.starts_with(prefix) { matches.push(alloc::format!("{}{}", dir_part, name)); }
+        }
+        matches
+    }
+
+    fn save_history(&self) {
+        let _ = sys_fs_write(HISTORY_PATH, self.history.to_text().as_bytes(), 0, FS_WRITE_TRUNCATE);
+    }
+
+    fn cmd_less(&mut self, arg: &str) {
+        if arg.is_empty() {
+            return self.print_failure("usage: less <file>\n");
+        }
+        let path = self.resolve_path(arg);
+        match sys_fs_size(&path).and_then(|size| index_file(&path, size)) {
+            Ok(index) => self.pager = Some(Pager::file(path, index)),
+            Err(e) => self.print_error("less", &path, e),
+        }
+    }
+
+    /// Keys while the pager is showing a file; none reach the prompt.
+    fn pager_key(&mut self, key: char) -> bool {
+        let page = self.page_rows.saturating_sub(1).max(1);
+        let Some(pager) = &mut self.pager else { return false };
+        match key {
+            ' ' | KEY_PAGE_DOWN => if !pager.at_end { pager.top = pager.next_top },
+            'b' | KEY_PAGE_UP => pager.top = pager.top.saturating_sub(page),
+            KEY_DOWN | '\n' => if !pager.at_end { pager.top += 1 },
+            KEY_UP => pager.top = pager.top.saturating_sub(1),
+            KEY_HOME | 'g' => pager.top = 0,
+            KEY_END | 'G' => pager.top = pager.index.lines().saturating_sub(page),
+            'q' => self.pager = None,
+            _ => return false,
+        }
+        true
+    }
+
+    /// The pager's lines, with a status bar as the bottom row.
+    fn draw_pager(&mut self, canvas: &mut Canvas, fg: u32, bg: u32) {
+        let (cols, rows) = (self.cols, self.page_rows.saturating_sub(1).max(1));
+        let Some(pager) = &mut self.pager else { return };
+        pager.layout(cols, rows);
+
+        let s = scale::current();
+        let mut cy = 10;
+        for row in &pager.view {
+            draw_row(canvas, cy, row, fg);
+            cy += LINE_H * s;
         }
+
+        let status_y = 10 + rows * LINE_H * s;
+        canvas.fill_rect(0, status_y - s * 2, canvas.width, LINE_H * s, fg);
+        let status: String = pager.status().chars().take(cols).collect();
+        draw_row(canvas, status_y, &status, bg);
     }
+
+    /// Replaces the contents of a file with the rest of the line.
+    fn cmd_write(&mut self, arg: &str) {
+        let (file, text) = match arg.split_once(' ') {
+            Some((file, text)) => (file, text),
+            None if !arg.is_empty() => (arg, ""),
+            None => return self.print_failure("usage: write <file> <text>\n"),
+        };
+        let path = self.resolve_path(file);
+        let mut data = String::from(text);
+        data.push('\n');
+        match sys_fs_write(&path, data.as_bytes(), 0, FS_WRITE_TRUNCATE) {
+            Ok(n) if n == data.len() => {}
+            Ok(_) => self.print_failure("write: short write\n"),
+            Err(e) => self.print_error("write", &path, e),
+        }
+    }
+
+    /// A swatch of every foreground (normal, then bright with 90-97) on every
+    /// background, then bold, to check escape sequences draw right.
+    fn cmd_colortest(&mut self) {
+        let mut out = String::from("     ");
+        for bg in 40..48 { out.push_str(&alloc::format!("  {}  ", bg)); }
+        out.push('\n');
+        for fg in (30..38).chain(90..98) {
+            out.push_str(&alloc::format!("{:>3}  ", fg));
+            for bg in 40..48 { out.push_str(&alloc::format!("\x1b[{};{}m Nyx \x1b[0m ", fg, bg)); }
+            out.push('\n');
+        }
+        out.push_str("bold: ");
+        for fg in 30..38 { out.push_str(&alloc::format!("\x1b[1;{}m{} \x1b[0m", fg, fg)); }
+        out.push('\n');
+        self.print(&out);
+    }
+
+    /// `cp [-f] <src> <dst>` and `mv`: copies or moves a file, into `dst` if
+    /// that's a directory. Replacing a file asks first, or with -f doesn't;
+    /// scripts and redirected commands can't answer, so there it needs -f.
+    fn cmd_transfer(&mut self, moving: bool, args: &str) {
+        let name = if moving { "mv" } else { "cp" };
+        let mut words: Vec<&str> = args.split_whitespace().collect();
+        let force = words.first() == Some(&"-f");
+        if force { words.remove(0); }
+        let [src, dst] = words[..] else {
+            return self.print_failure(&alloc::format!("usage: {} [-f] <src> <dst>\n", name));
+        };
+        let src = self.resolve_path(src);
+        // Nothing to move means nothing to ask about, and nothing to replace
+        if !fileops::file_exists(&src) && !fileops::is_dir(&src) {
+            return self.print_error(name, &src, NyxError::NotFound);
+        }
+        let dst = fileops::destination_path(&src, &self.resolve_path(dst));
+        // mv to the same name in another case is a rename, not a clash
+        let replaces = fileops::file_exists(&dst) && !src.eq_ignore_ascii_case(&dst);
+        if replaces && !force {
+            if self.script_depth > 0 || !matches!(self.sink, Sink::Terminal) {
+                return self.print_failure(&alloc::format!("{}: {}: file exists (use -f to replace it)\n", name, dst));
+            }
+            self.print_screen(&alloc::format!("{}: replace {}? [y/N] ", name, dst));
+            self.confirm = Some(PendingTransfer { moving, src, dst });
+            return;
+        }
+        self.transfer(PendingTransfer { moving, src, dst }, replaces);
+    }
+
+    /// Runs a cp or mv. A file being replaced is renamed out of the way
+    /// first (a rename won't replace one) and only deleted once the new file
+    /// is in place; if the move or copy fails, it's put back.
+    fn transfer(&mut self, t: PendingTransfer, replace: bool) {
+        let name = if t.moving { "mv" } else { "cp" };
+        let aside = if replace {
+            let aside = aside_name(&t.dst);
+            if let Err(e) = sys_fs_rename(&t.dst, &aside) { return self.print_error(name, &t.dst, e); }
+            Some(aside)
+        } else { None };
+
+        let copy = if t.moving {
+            fileops::move_file(&t.src, &t.dst)
+        } else {
+            FileCopy::new(&t.src, &t.dst).map(Some)
+        };
+        let result = match copy {
+            Ok(Some(mut copy)) => fileops::copy_all(&mut copy),
+            Ok(None) => Ok(()),
+            Err(e) => Err(e),
+        };
+
+        if let Some(aside) = aside {
+            let _ = if result.is_ok() { sys_fs_delete(&aside) } else { sys_fs_rename(&aside, &t.dst) };
+        }
+        if let Err(e) = result { self.print_error(name, &t.src, e); }
+    }
+
+    fn cmd_lsblk(&mut self) {
+        let mut devices = [BlockDeviceInfo::default(); 16];
+        let count = sys_block_devices(&mut devices).unwrap_or(0);
+        if count == 0 {
+            self.print("lsblk: no block devices\n");
+            return;
+        }
+
+        self.print("NSID  BLOCK  SIZE       MOUNT\n");
+        for dev in &devices[..count] {
+            let mib = dev.capacity_lbas * dev.lba_size as u64 / (1024 * 1024);
+            let line = alloc::format!("{:<5} {:<6} {:>6} MiB {}\n", dev.nsid, dev.lba_size, mib,
+                if dev.mounted != 0 { "/mnt/nvme" } else { "-" });
+            self.print(&line);
+        }
+    }
+
+    /// `display` lists the displays; `display add-virtual WxH` adds one.
+    fn cmd_display(&mut self, args: &str) {
+        if let Some(size) = args.strip_prefix("add-virtual") {
+            let size = size.trim();
+            let parsed = size.split_once('x').and_then(|(w, h)| Some((w.parse::<usize>().ok()?, h.parse::<usize>().ok()?)));
+            let (w, h) = match parsed {
+                Some(s) => s,
+                None => { self.print_failure("usage: display add-virtual <W>x<H>\n"); return; }
+            };
+            match sys_add_virtual_display(w, h) {
+                Ok(index) => {
+                    let _ = sys_ipc_send(COMPOSITOR_PID, MSG_DISPLAYS_CHANGED, 0, 0);
+                    self.print(&alloc::format!("display {}: {}x{} (virtual)\n", index, w, h));
+                },
+                Err(e) => self.print_failure(&alloc::format!("display: {}x{}: {:?}\n", w, h, e)),
+            }
+            return;
+        }
+        if !args.is_empty() {
+            self.print_failure("usage: display [add-virtual <W>x<H>]\n");
+            return;
+        }
+
+        let mut displays = [DisplayInfo::default(); MAX_DISPLAYS];
+        let count = sys_get_displays(&mut displays);
+        self.print("#  X      Y      SIZE\n");
+        for (i, d) in displays[..count].iter().enumerate() {
+            let size = alloc::format!("{}x{}", d.w, d.h);
+            let line = alloc::format!("{:<2} {:<6} {:<6} {:<11}{}\n", i, d.x, d.y, size,
+                if d.is_virtual != 0 { " virtual" } else if i == 0 { " primary" } else { "" });
+            self.print(&line);
+        }
+    }
+
+    fn cmd_lsdisk(&mut self) {
+        let mut disks = [DiskInfoRecord::default(); 16];
+        let count = sys_disk_info(&mut disks).unwrap_or(0);
+        if count == 0 {
+            self.print("lsdisk: no disks\n");
+            return;
+        }
+
+        self.print("DISK     SIZE        MODEL\n");
+        for disk in &disks[..count] {
+            let name = match disk.kind {
+                DISK_KIND_NVME => alloc::format!("nvme{}", disk.id),
+                DISK_KIND_USB => alloc::format!("usb{}", disk.id),
+                _ => alloc::format!("sata{}", disk.id),
+            };
+            let mib = disk.sectors * disk.sector_size as u64 / (1024 * 1024);
+            let line = alloc::format!("{:<8} {:>7} MiB {}\n", name, mib, disk.model());
+            self.print(&line);
+        }
+    }
+
+    /// Size and free space of the volume `arg` (or the working directory) is on.
+    fn cmd_df(&mut self, arg: &str) {
+        let path = if arg.is_empty() { self.cwd.clone() } else { self.resolve_path(arg) };
+        let info = match sys_fs_volume_info(&path) {
+            Ok(info) => info,
+            Err(e) => return self.print_error("df", &path, e),
+        };
+        const MIB: u64 = 1024 * 1024;
+        let total = info.total_bytes();
+        let percent = if total > 0 { info.used_bytes() * 100 / total } else { 0 };
+        let label = if info.label().is_empty() { "-" } else { info.label() };
+
+        self.print("LABEL            TYPE  USED/TOTAL MiB     USE%  FREE MiB  PATH\n");
+        let usage = alloc::format!("{}/{}", info.used_bytes() / MIB, total / MIB);
+        let line = alloc::format!("{:<16} {:<5} {:<18} {:>3}%  {:>8}  {}\n",
+            label, info.fs_name(), usage, percent, info.free_bytes() / MIB, path);
+        self.print(&line);
+    }
+
+    /// Starts a program whose stdout/stderr end up in this window, or runs
+    /// a .nsh script.
+    fn cmd_run(&mut self, arg: &str) {
+        if arg.is_empty() {
+            self.print_failure("usage: run <program | script.nsh>\n");
+            return;
+        }
+        let mut path = self.resolve_path(arg);
+        if let Err(e) = sys_fs_size(&path) { return self.print_error("run", &path, e); }
+        if path.ends_with(".nsh") { return self.run_script(&path); }
+        path.push('\0');
+        if let Ok(0) = sys_fork() {
+            // The child shares our console, so the reason shows up in this window
+            let err = sys_execve(&path);
+            sys_print(&alloc::format!("run: {}: {}\n", path.trim_end_matches('\0'), err.message()));
+            sys_exit(1);
+        }
+    }
+
+    /// Debug aid: hands the kernel pointers it must refuse and prints what each
+    /// syscall returned. Every line should read -14 (EFAULT).
+    fn cmd_badptr(&mut self) {
+        const KERNEL_ADDR: u64 = 0xFFFF_8000_0000_0000;
+        const UNMAPPED_ADDR: u64 = 0x0000_7FFF_0000_0000;
+        let path = "/mnt/nvme";
+        let cases: [(&str, u64); 4] = [
+            ("open(kernel path)", syscall(2, KERNEL_ADDR, 16, 0, 0, 0, 0)),
+            ("open(unmapped path)", syscall(2, UNMAPPED_ADDR, 16, 0, 0, 0, 0)),
+            ("fs_read(kernel dest)", syscall(537, path.as_ptr() as u64, path.len() as u64, KERNEL_ADDR, 64, 0, 0)),
+            ("sysinfo(unmapped dest)", syscall(524, UNMAPPED_ADDR, 0, 0, 0, 0, 0)),
+        ];
+        for (name, ret) in cases {
+            let line = alloc::format!("{:<24} {}\n", name, ret as i64);
+            self.print(&line);
+        }
+    }
+
+    /// Pushes the kernel heap past its boot size and back to check that it grows.
+    fn cmd_heaptest(&mut self, arg: &str) {
+        let mib = if arg.is_empty() { 128 } else {
+            match arg.parse::<usize>() { Ok(n) if n > 0 => n, _ => return self.print_failure("usage: heaptest [MiB]\n") }
+        };
+        self.print(&alloc::format!("heaptest: allocating {} MiB in the kernel...\n", mib));
+        let held = match sys_heap_test(mib) {
+            Ok(held) => held,
+            Err(e) => return self.print_failure(&alloc::format!("heaptest: {}\n", e.message())),
+        };
+        let heap = sys_get_meminfo().map(|m| m.kernel_heap_size / (1024 * 1024)).unwrap_or(0);
+        let line = if held == mib {
+            alloc::format!("heaptest: ok, {} MiB held and freed (heap now {} MiB)\n", held, heap)
+        } else {
+            alloc::format!("heaptest: out of memory after {} of {} MiB (heap now {} MiB)\n", held, mib, heap)
+        };
+        self.print(&line);
+    }
+
+    /// Like run, but the program starts from a clean address space instead of a fork of ours.
+    fn cmd_spawn(&mut self, arg: &str) {
+        if arg.is_empty() {
+            return self.print_failure("usage: spawn <program>\n");
+        }
+        let path = self.resolve_path(arg);
+        match sys_spawn(&path) {
+            Ok(pid) => self.print(&alloc::format!("spawn: started pid {}\n", pid)),
+            Err(e) => self.print_error("spawn", &path, e),
+        }
+    }
+
+    fn cmd_mkdir(&mut self, arg: &str) {
+        if arg.is_empty() {
+            self.print_failure("usage: mkdir <path>\n");
+            return;
+        }
+        let path = self.resolve_path(arg);
+        if let Err(e) = sys_fs_mkdir(&path) { self.print_error("mkdir", &path, e); }
+    }
+
+    /// `netclock [server-ip]`: asks an SNTP server for the time and sets the
+    /// wall clock (which the taskbar shows) from its answer, half the round
+    /// trip later. A server given here is remembered for next time.
+    fn cmd_netclock(&mut self, arg: &str) {
+        let server = match arg {
+            "" => nyx_gui::state::load().get(NTP_SERVER_KEY).filter(|s| !s.is_empty())
+                .map(String::from).unwrap_or_else(|| String::from(DEFAULT_NTP_SERVER)),
+            _ => String::from(arg),
+        };
+        let ip = match parse_ipv4(&server) {
+            Some(ip) => ip,
+            None => return self.print_failure(&alloc::format!("netclock: {}: not an IPv4 address\n", server)),
+        };
+        if !arg.is_empty() { nyx_gui::state::save(NTP_SERVER_KEY, arg); }
+        let socket = match UdpSocket::new() {
+            Some(s) => s,
+            None => return self.print_failure("netclock: no socket\n"),
+        };
+
+        let mut nonce = [0u8; 8];
+        let _ = sys_getrandom(&mut nonce);
+        let nonce = u64::from_ne_bytes(nonce);
+        let sent_at = sys_get_time();
+        if let Err(e) = socket.send_to(ip, sntp::NTP_PORT, &sntp::request(nonce)) {
+            return self.print_error("netclock", &server, e);
+        }
+
+        let mut buf = [0u8; 128];
+        loop {
+            match socket.recv_from(&mut buf) {
+                Ok((n, from, port)) if from == ip && port == sntp::NTP_PORT => match sntp::parse_reply(&buf[..n], nonce) {
+                    Ok(reply) => return self.set_clock(&server, reply, sys_get_time() - sent_at),
+                    // Some earlier request's answer
+                    Err(sntp::ReplyError::NotOurs) => {}
+                    Err(e) => return self.print_failure(&alloc::format!("netclock: {}: bad reply ({:?})\n", server, e)),
+                },
+                Ok(_) | Err(NyxError::WouldBlock) => {}
+                Err(e) => return self.print_error("netclock", &server, e),
+            }
+            if sys_get_time() - sent_at >= NTP_TIMEOUT_MS {
+                return self.print_failure(&alloc::format!("netclock: {}: no reply\n", server));
+            }
+            sys_sleep_ms(10);
+        }
+    }
+
+    fn set_clock(&mut self, server: &str, reply: sntp::Reply, round_trip_ms: usize) {
+        let now = (reply.unix_ms + round_trip_ms as u64 / 2 + 500) / 1000;
+        let before = sys_wall_clock();
+        // Only init and the compositor may set the clock; it takes this from the focused window
+        if sys_ipc_send(COMPOSITOR_PID, MSG_SET_CLOCK, now, 0).is_err() {
+            return self.print_failure(&alloc::format!("netclock: {}: the compositor isn't running\n", server));
+        }
+        let change = match now as i64 - before as i64 {
+            _ if before == 0 => String::from("the clock was unset"),
+            0 => String::from("already right"),
+            d if d > 0 => alloc::format!("was {} s slow", d),
+            d => alloc::format!("was {} s fast", -d),
+        };
+        self.print(&alloc::format!("netclock: {} UTC from {} (stratum {}, {} ms round trip); {}\n",
+            DateTime::from_unix(now), server, reply.stratum, round_trip_ms, change));
+    }
+
+    fn cmd_wallpaper(&mut self, arg: &str) {
+        if arg.is_empty() {
+            self.print_failure("usage: wallpaper <file.bmp>\n");
+            return;
+        }
+        let path = self.resolve_path(arg);
+        if let Err(e) = sys_fs_size(&path) { return self.print_error("wallpaper", &path, e); }
+
+        // The path is handed to the compositor through a shared page
+        if let Err(e) = sys_ipc_send_str(COMPOSITOR_PID, MSG_SET_WALLPAPER, &path) {
+            self.print_error("wallpaper", &path, e);
+        }
+    }
+}
+
+/// time.cloudflare.com, until `netclock <ip>` picks another server
+const DEFAULT_NTP_SERVER: &str = "162.159.200.1";
+/// Desktop state key the last server given to netclock is kept under
+const NTP_SERVER_KEY: &str = "terminal.ntp_server";
+const NTP_TIMEOUT_MS: usize = 3000;
+
+/// Dotted-quad IPv4; no host names (there is no resolver call here).
+/// An unused name beside `path` to park a file that's about to be replaced.
+fn aside_name(path: &str) -> String {
+    let mut aside = alloc::format!("{}.old", path);
+    let mut n = 1;
+    while fileops::file_exists(&aside) || fileops::is_dir(&aside) {
+        aside = alloc::format!("{}.old{}", path, n);
+        n += 1;
+    }
+    aside
+}
+
+fn parse_ipv4(s: &str) -> Option<[u8; 4]> {
+    let mut ip = [0u8; 4];
+    let mut parts = s.split('.');
+    for byte in ip.iter_mut() { *byte = parts.next()?.parse().ok()?; }
+    if parts.next().is_some() { return None; }
+    Some(ip)
+}
+
+/// Plain text on the cell grid at y, from the left margin. print_str advances
+/// by the font's own width, which is wider than a cell, so text wrapped to
+/// the grid's columns would run past the window's edge.
+fn draw_row(canvas: &mut Canvas, y: usize, text: &str, color: u32) {
+    let s = scale::current();
+    for (i, c) in text.chars().enumerate() { canvas.draw_char(10 + i * FONT_W * s, y, c, color, s); }
 }
 
 impl NyxApp for TerminalApp {
     fn title(&self) -> &str { "Nyx Matrix Terminal" }
-    fn initial_width(&self) -> usize { 640 }
-    fn initial_height(&self) -> usize { 400 }
+    fn initial_width(&self) -> usize { scale::px(640) }
+    fn initial_height(&self) -> usize { scale::px(400) }
+
+    fn init(&mut self) {
+        // Wallpaper, windows, files: whatever the user wants done on boot
+        if sys_fs_size(AUTORUN_PATH).is_ok() { self.run_script(AUTORUN_PATH); }
+    }
 
     fn update(&mut self) -> bool {
+        // Programs started from here share our console; show what they printed
+        let mut buf = [0u8; 512];
+        let mut printed = false;
+        loop {
+            let n = match sys_console_read(&mut buf) { Ok(n) if n > 0 => n, _ => break };
+            self.print(&String::from_utf8_lossy(&buf[..n]));
+            printed = true;
+        }
+        if printed { return true; }
+        // The pager has no cursor to blink
+        if self.pager.is_some() { return false; }
+
         self.blink_timer += 1;
         if self.blink_timer > 30 {
             self.blink_timer = 0;
@@ -52,76 +1111,171 @@ impl NyxApp for TerminalApp {
     }
 
     fn draw(&mut self, canvas: &mut Canvas) {
-        canvas.fill_rect(0, 0, canvas.width, canvas.height, BG_COLOR);
+        // Text and background follow the theme, so light mode stays readable
+        let theme = theme::current();
+        let (bg, fg) = (theme.console_bg, theme.console_fg);
+        canvas.fill_rect(0, 0, canvas.width, canvas.height, bg);
         
-        let mut cx = 10;
+        // Columns and rows follow the window size, so a resize or a new UI
+        // scale rewraps everything on the next draw
+        let s = scale::current();
+        let (font_w, font_h, line_h) = (FONT_W * s, FONT_H * s, LINE_H * s);
+        let cols = (canvas.width.saturating_sub(25) / font_w).max(1);
+        self.page_rows = (canvas.height.saturating_sub(20) / line_h).max(1);
+        self.cols = cols;
+        if self.pager.is_some() { return self.draw_pager(canvas, fg, bg); }
+
+        // Wrap the scrollback into screen rows. The prompt and input continue the last line.
+        let prompt = if self.confirm.is_some() { "" } else { PROMPT };
+        let mut rows: Vec<Vec<TerminalCell>> = Vec::new();
+        let last = self.scrollback.len() - 1;
+        let mut prompt_row = 0;
+        for (i, line) in self.scrollback.iter().enumerate() {
+            let with_input;
+            let line = if i == last {
+                prompt_row = rows.len();
+                let typed = prompt.chars().chain(self.input.text().chars()).map(|ch| TerminalCell { ch, style: Style::default() });
+                with_input = line.iter().copied().chain(typed).collect::<Vec<_>>();
+                &with_input
+            } else { line };
+            if line.is_empty() { rows.push(Vec::new()); }
+            rows.extend(line.chunks(cols).map(|row| row.to_vec()));
+        }
+
+        // The cursor counts along the wrapped prompt line; leave room for it
+        // if it sits right at the wrap column
+        let cursor_at = self.scrollback[last].len() + prompt.len() + self.input.cursor_chars();
+        let (cursor_row, cursor_col) = (prompt_row + cursor_at / cols, cursor_at % cols);
+        if cursor_row >= rows.len() { rows.push(Vec::new()); }
+
+        self.max_scroll = rows.len().saturating_sub(self.page_rows);
+        if self.scroll_offset > self.max_scroll { self.scroll_offset = self.max_scroll; }
+
+        let end = rows.len() - self.scroll_offset;
+        let start = end.saturating_sub(self.page_rows);
+
         let mut cy = 10;
-        
-        // Draw History
-        for c in self.output_history.chars() {
-            if c == '\n' { cx = 10; cy += FONT_H + 4; continue; }
-            canvas.draw_char(cx, cy, c, FG_COLOR, 1);
-            cx += FONT_W;
-            if cx >= canvas.width - 15 { cx = 10; cy += FONT_H + 4; }
+        for row in &rows[start..end] {
+            let mut cx = 10;
+            for cell in row {
+                if let Some(b) = cell.style.bg { canvas.fill_rect(cx, cy - s * 2, font_w, line_h, ANSI_PALETTE[b as usize]); }
+                let color = cell.style.fg_color().map_or(fg, |c| ANSI_PALETTE[c as usize]);
+                canvas.draw_char(cx, cy, cell.ch, color, s);
+                cx += font_w;
+            }
+            cy += line_h;
+        }
+
+        if self.scroll_offset == 0 {
+            // Draw Cursor
+            if self.cursor_visible && cursor_row >= start {
+                let (x, y) = (10 + cursor_col * font_w, 10 + (cursor_row - start) * line_h);
+                canvas.fill_rect(x, y, font_w, font_h, fg);
+                // A cursor inside the line shows the char under it inverted
+                if let Some(cell) = rows[cursor_row].get(cursor_col) { canvas.draw_char(x, y, cell.ch, bg, s); }
+            }
+        } else {
+            // Scroll indicator: a thumb on the right edge plus how far back we are
+            let track_h = canvas.height.saturating_sub(20);
+            let thumb_h = (track_h * self.page_rows / rows.len()).max(8);
+            let thumb_y = 10 + track_h.saturating_sub(thumb_h) * start / self.max_scroll.max(1);
+            canvas.fill_rect(canvas.width - 8, 10, 3, track_h, 0xFF00_0000 | blend_color(fg, bg, 48));
+            canvas.fill_rect(canvas.width - 8, thumb_y, 3, thumb_h, fg);
+
+            let label = alloc::format!("[-{}]", self.scroll_offset);
+            canvas.print_str(canvas.width.saturating_sub(16 + label.len() * font::char_width(s)), 10, &label, fg, s);
         }
+    }
+
+    fn on_message(&mut self, msg: &IpcMessage) -> bool {
+        if msg.msg_type != MSG_WALLPAPER_STATUS { return false; }
+        self.print(if msg.data1 != 0 { "Wallpaper updated.\n" } else { "wallpaper: not an uncompressed 24/32-bit BMP\n" });
+        true
+    }
 
-        // Draw Prompt
-        let prompt = "N> ";
-        for c in prompt.chars() {
-            canvas.draw_char(cx, cy, c, FG_COLOR, 1);
-            cx += FONT_W;
+    fn on_shortcut(&mut self, key: char, shift: bool) -> bool {
+        match key {
+            'l' => { self.clear(); true },
+            // Plain Ctrl+V is left for programs that may want it one day
+            'v' if shift => { self.clipboard.request_paste(); true },
+            _ => false,
         }
+    }
 
-        // Draw Input Buffer
-        for c in self.input_buffer.chars() {
-            canvas.draw_char(cx, cy, c, FG_COLOR, 1);
-            cx += FONT_W;
-            if cx >= canvas.width - 15 { cx = 10; cy += FONT_H + 4; }
+    /// Pasted text is typed in: every newline submits the line before it.
+    fn on_paste(&mut self, text: &str) -> bool {
+        for c in text.chars() {
+            match c {
+                '\r' => {},
+                '\n' | '\t' => { self.on_key(if c == '\t' { ' ' } else { c }); },
+                c if !c.is_control() => { self.on_key(c); },
+                _ => {},
+            }
         }
+        true
+    }
 
-        // Draw Cursor
-        if self.cursor_visible {
-            canvas.fill_rect(cx, cy, FONT_W, FONT_H, FG_COLOR);
+    fn on_wheel(&mut self, _mx: usize, _my: usize, notches: i32) -> bool {
+        if self.pager.is_some() {
+            let key = if notches > 0 { KEY_UP } else { KEY_DOWN };
+            for _ in 0..notches.unsigned_abs() as usize * WHEEL_ROWS { self.pager_key(key); }
+            return true;
         }
+        let rows = notches.unsigned_abs() as usize * WHEEL_ROWS;
+        let old = self.scroll_offset;
+        self.scroll_offset = if notches > 0 {
+            (self.scroll_offset + rows).min(self.max_scroll)
+        } else {
+            self.scroll_offset.saturating_sub(rows)
+        };
+        self.scroll_offset != old
     }
 
     fn on_key(&mut self, key: char) -> bool {
         self.cursor_visible = true;
         self.blink_timer = 0;
 
-        if key == '\n' || key == '\r' {
-            let cmd = self.input_buffer.trim();
-            self.output_history.push_str("N> ");
-            self.output_history.push_str(cmd);
-            self.output_history.push('\n');
-
-            if cmd == "help" {
-                self.output_history.push_str("Commands: help, clear, echo <text>, settings, explorer, sysmon, network\n");
-            } else if cmd == "clear" {
-                self.output_history.clear();
-            } else if cmd == "settings" {
-                self.output_history.push_str("Launching Settings...\n");
-                if sys_fork() == 0 { sys_execve("/mnt/nvme/apps/Settings.nyx/run.bin\0"); sys_exit(1); }
-            } else if cmd == "explorer" {
-                self.output_history.push_str("Launching Explorer...\n");
-                if sys_fork() == 0 { sys_execve("/mnt/nvme/apps/Explorer.nyx/run.bin\0"); sys_exit(1); }
-            } else if cmd == "sysmon" {
-                self.output_history.push_str("Launching System Monitor...\n");
-                if sys_fork() == 0 { sys_execve("/mnt/nvme/apps/SystemMonitor.nyx/run.bin\0"); sys_exit(1); }
-            } else if cmd == "network" {
-                self.output_history.push_str("Launching Network Suite...\n");
-                if sys_fork() == 0 { sys_execve("/mnt/nvme/apps/Network.nyx/run.bin\0"); sys_exit(1); }
-            } else if cmd.starts_with("echo ") {
-                self.output_history.push_str(&cmd[5..]);
-                self.output_history.push('\n');
-            } else if !cmd.is_empty() {
-                self.output_history.push_str("Unknown command. Type 'help'.\n");
-            }
-            self.input_buffer.clear();
-        } else if key == '\x08' { 
-            self.input_buffer.pop();
+        if self.pager.is_some() { return self.pager_key(key); }
+        if let Some(pending) = self.confirm.take() {
+            // Anything but y keeps the file
+            let yes = key == 'y' || key == 'Y';
+            self.print_screen(if yes { "y\n" } else { "n\n" });
+            if yes { self.transfer(pending, true); }
+            return true;
+        }
+        if key != '\t' { self.completion = None; }
+        if key == KEY_PAGE_UP {
+            let step = self.page_rows.saturating_sub(1).max(1);
+            self.scroll_offset = (self.scroll_offset + step).min(self.max_scroll);
+        } else if key == KEY_PAGE_DOWN {
+            let step = self.page_rows.saturating_sub(1).max(1);
+            self.scroll_offset = self.scroll_offset.saturating_sub(step);
+        } else if key == '\n' || key == '\r' {
+            let line = String::from(self.input.text());
+            let cmd = line.trim();
+            self.print(PROMPT);
+            self.print(cmd);
+            self.print("\n");
+            if self.history.push(cmd) { self.save_history(); }
+
+            self.process_line(cmd);
+            self.input.clear();
         } else {
-            self.input_buffer.push(key);
+            // Everything else edits the line, which brings it back into view
+            match key {
+                KEY_LEFT => { self.input.left(); },
+                KEY_RIGHT => { self.input.right(); },
+                KEY_HOME => self.input.home(),
+                KEY_END => self.input.end(),
+                KEY_UP => if let Some(line) = self.history.older(self.input.text()) { self.input.set(line) },
+                KEY_DOWN => if let Some(line) = self.history.newer() { self.input.set(line) },
+                '\t' => { self.complete(); },
+                '\x08' => { self.input.backspace(); },
+                '\x7f' => { self.input.delete(); },
+                c if ('\u{E000}'..='\u{F8FF}').contains(&c) => return false,
+                c => self.input.insert(c),
+            }
+            self.scroll_offset = 0;
         }
         true // Redraw instantly on keypress
     }
@@ -130,9 +1284,7 @@ impl NyxApp for TerminalApp {
 #[unsafe(no_mangle)]
 #[unsafe(link_section = ".text.entry")]
 pub extern "C" fn _start() -> ! {
-    let heap_start = sys_alloc_pages(256);
-    if heap_start == 0 { sys_exit(1); }
-    unsafe { ALLOCATOR.lock().init(heap_start as *mut u8, 256 * 4096); }
+    if !ALLOCATOR.init(256 * 4096) { sys_exit(1); }
 
     nyx_gui::app::run(TerminalApp::new());
 }
@@ -17,6 +17,9 @@ pub const WIN_MAGIC: u32 = 0x4E595857;
 pub const WIN_FLAG_NONE: u32 = 0;
 pub const WIN_FLAG_FRAMELESS: u32 = 1;
 pub const WIN_FLAG_TRANSPARENT: u32 = 2;
+// Never gets the keyboard: clicking it leaves the focused window focused,
+// and it stays above the other windows (the on-screen keyboard)
+pub const WIN_FLAG_NO_FOCUS: u32 = 4;
 
 // ─────────────────────────────────────────────────────────────────────────
 // NYX-OS IPC CORE PROTOCOL CONSTANTS
@@ -29,6 +32,105 @@ pub const MSG_MOUSE_EVENT: u64 = 5;
 pub const MSG_WINDOW_CLOSE: u64 = 6;
 pub const MSG_WINDOW_RESIZED: u64 = 7; 
 pub const MSG_WINDOW_UPDATE_SHM: u64 = 8;
+// Ask the compositor for frame timing; it replies with MSG_FRAME_STATS
+// (data1 = average compose time in microseconds, data2 = frames composed)
+pub const MSG_GET_FRAME_STATS: u64 = 9;
+pub const MSG_FRAME_STATS: u64 = 10;
+// data1 = SHM id holding the wallpaper path, data2 = path length.
+// The compositor answers with MSG_WALLPAPER_STATUS (data1 = 1 on success)
+pub const MSG_SET_WALLPAPER: u64 = 11;
+pub const MSG_WALLPAPER_STATUS: u64 = 12;
+// Tells an app to show a path; same SHM convention as MSG_SET_WALLPAPER
+pub const MSG_OPEN_PATH: u64 = 13;
+// Drag and drop. The compositor sends MSG_DRAG_BEGIN (data1/data2 = window-relative
+// press position); the app answers MSG_DRAG_PAYLOAD with a string, or data1 = 0 to
+// decline. MSG_DROP delivers the payload string to the window it was dropped on.
+pub const MSG_DRAG_BEGIN: u64 = 14;
+pub const MSG_DRAG_PAYLOAD: u64 = 15;
+pub const MSG_DROP: u64 = 16;
+// Wheel turned over a window. data1 = notches as i64 (positive scrolls up /
+// away from the user), data2 = window-relative position, x << 32 | y
+pub const MSG_MOUSE_WHEEL: u64 = 17;
+// Second click of a double-click in a window; data1/data2 = window-relative
+// position. The first click arrived as MSG_MOUSE_EVENT, the second doesn't.
+pub const MSG_MOUSE_DOUBLE_CLICK: u64 = 18;
+// Clipboard kept by the compositor. SET carries the text as a string
+// (sys_ipc_send_str); GET is answered with DATA, data1 = 0 when empty.
+pub const MSG_CLIPBOARD_SET: u64 = 19;
+pub const MSG_CLIPBOARD_GET: u64 = 20;
+pub const MSG_CLIPBOARD_DATA: u64 = 21;
+// Sent to the compositor after the display list changed (sys_add_virtual_display)
+pub const MSG_DISPLAYS_CHANGED: u64 = 22;
+// A "key=value" string (sys_ipc_send_str) for the compositor to keep in the
+// desktop state file, which it writes out along with the window layout
+pub const MSG_SAVE_STATE: u64 = 23;
+// data1 = index into nyx_gui's theme::ACCENTS, data2 = 1 for dark. SET asks
+// the compositor to switch and save it; CHANGED goes to every window after
+pub const MSG_SET_THEME: u64 = 24;
+pub const MSG_THEME_CHANGED: u64 = 25;
+// data2 = a packed KeyEvent the compositor handles as if it came from the
+// keyboard; only taken from the owner of a WIN_FLAG_NO_FOCUS window
+pub const MSG_INJECT_KEY: u64 = 26;
+// A "<binary>\n<path>" string: the compositor launches the binary and sends
+// it MSG_OPEN_PATH with the path once its window exists
+pub const MSG_OPEN_WITH: u64 = 27;
+// data1 = the UI scale factor (1 or 2). SET asks the compositor to switch
+// and save it; CHANGED goes to every window after
+pub const MSG_SET_UI_SCALE: u64 = 28;
+pub const MSG_UI_SCALE_CHANGED: u64 = 29;
+// data1 = Unix seconds for the compositor to set the wall clock to; only
+// taken from the owner of the focused window
+pub const MSG_SET_CLOCK: u64 = 30;
+
+// Navigation keys delivered by sys_read_key / MSG_KEY_EVENT (Private Use Area codepoints)
+pub const KEY_UP: char = '\u{E000}';
+pub const KEY_DOWN: char = '\u{E001}';
+pub const KEY_LEFT: char = '\u{E002}';
+pub const KEY_RIGHT: char = '\u{E003}';
+pub const KEY_PAGE_UP: char = '\u{E004}';
+pub const KEY_PAGE_DOWN: char = '\u{E005}';
+pub const KEY_HOME: char = '\u{E006}';
+pub const KEY_END: char = '\u{E007}';
+
+pub const KEYMOD_SHIFT: u8 = 1 << 0;
+pub const KEYMOD_CTRL: u8 = 1 << 1;
+pub const KEYMOD_ALT: u8 = 1 << 2;
+pub const KEYMOD_CAPS: u8 = 1 << 3;
+
+// A decoded keyboard event as packed by the kernel (see sys_read_key_event)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub ch: char,
+    pub scancode: u8,
+    pub modifiers: u8,
+    pub pressed: bool,
+    pub extended: bool,
+}
+
+impl KeyEvent {
+    pub fn from_packed(v: u64) -> Option<Self> {
+        if v == 0 { return None; }
+        Some(Self {
+            ch: core::char::from_u32((v & 0xFFFF_FFFF) as u32).unwrap_or('\0'),
+            scancode: ((v >> 32) & 0xFF) as u8,
+            modifiers: ((v >> 40) & 0xFF) as u8,
+            pressed: (v >> 48) & 1 == 1,
+            extended: (v >> 49) & 1 == 1,
+        })
+    }
+
+    pub fn to_packed(&self) -> u64 {
+        (self.ch as u64)
+            | ((self.scancode as u64) << 32)
+            | ((self.modifiers as u64) << 40)
+            | ((self.pressed as u64) << 48)
+            | ((self.extended as u64) << 49)
+    }
+
+    pub fn shift(&self) -> bool { self.modifiers & KEYMOD_SHIFT != 0 }
+    pub fn ctrl(&self) -> bool { self.modifiers & KEYMOD_CTRL != 0 }
+    pub fn alt(&self) -> bool { self.modifiers & KEYMOD_ALT != 0 }
+}
 
 #[repr(C)]
 #[derive(Clone, Copy, Debug)]
@@ -48,6 +150,111 @@ pub struct TaskInfo {
     pub name: [u8; 16],
 }
 
+pub const TASK_RUNNING: u8 = 0;
+pub const TASK_READY: u8 = 1;
+pub const TASK_BLOCKED: u8 = 2;
+pub const TASK_ZOMBIE: u8 = 3;
+/// Tickets every task starts with; the focused app holds more
+pub const DEFAULT_TICKETS: u64 = 100;
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct TaskStat {
+    pub pid: u64,
+    pub tickets: u64,
+    pub cpu_ticks: u64,
+    pub last_run_ms: u64,
+    pub state: u8, // TASK_*
+    pub is_idle: u8,
+    pub name: [u8; 16],
+}
+
+impl TaskStat {
+    pub fn name(&self) -> &str {
+        let len = self.name.iter().position(|&b| b == 0).unwrap_or(self.name.len());
+        core::str::from_utf8(&self.name[..len]).unwrap_or("?")
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct BlockDeviceInfo {
+    pub nsid: u32,
+    pub lba_size: u32,
+    pub capacity_lbas: u64,
+    pub mounted: u32, // 1 = backs the /mnt/nvme volume
+    pub _pad: u32,
+}
+
+pub const DISK_KIND_NVME: u32 = 0;
+pub const DISK_KIND_SATA: u32 = 1;
+pub const DISK_KIND_USB: u32 = 2;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct DiskInfoRecord {
+    pub kind: u32, // DISK_KIND_*
+    pub id: u32,   // NSID for NVMe, port number for SATA, slot for USB
+    pub sector_size: u32,
+    pub model_len: u32,
+    pub sectors: u64,
+    pub model: [u8; 40],
+}
+
+impl Default for DiskInfoRecord {
+    fn default() -> Self {
+        Self { kind: 0, id: 0, sector_size: 0, model_len: 0, sectors: 0, model: [0; 40] }
+    }
+}
+
+impl DiskInfoRecord {
+    pub fn model(&self) -> &str {
+        core::str::from_utf8(&self.model[..(self.model_len as usize).min(40)]).unwrap_or("?")
+    }
+}
+
+pub const FS_TYPE_EXT4: u32 = 1;
+
+/// Size and free space of the volume a path is on (sys_fs_volume_info)
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct VolumeInfo {
+    pub total_blocks: u64,
+    pub free_blocks: u64,
+    pub block_size: u32,
+    /// One of the FS_TYPE_ constants
+    pub fs_type: u32,
+    pub label: [u8; 16],
+}
+
+impl VolumeInfo {
+    pub fn total_bytes(&self) -> u64 { self.total_blocks * self.block_size as u64 }
+    pub fn free_bytes(&self) -> u64 { self.free_blocks * self.block_size as u64 }
+    pub fn used_bytes(&self) -> u64 { self.total_bytes().saturating_sub(self.free_bytes()) }
+
+    pub fn label(&self) -> &str {
+        let len = self.label.iter().position(|&b| b == 0).unwrap_or(self.label.len());
+        core::str::from_utf8(&self.label[..len]).unwrap_or("?")
+    }
+
+    pub fn fs_name(&self) -> &'static str {
+        match self.fs_type { FS_TYPE_EXT4 => "ext4", _ => "?" }
+    }
+}
+
+/// Physical memory and kernel heap usage in bytes (sys_get_meminfo)
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct MemInfo {
+    pub total: u64,
+    pub used: u64,
+    pub free: u64,
+    pub kernel_heap_used: u64,
+    pub kernel_heap_peak: u64,
+    /// Bytes mapped for the kernel heap; grows on demand
+    pub kernel_heap_size: u64,
+}
+
 #[repr(C)]
 pub struct SystemInfo {
     pub current_temp: u8,
@@ -56,6 +263,118 @@ pub struct SystemInfo {
     pub gpu_fan_rpm: u32,
     pub task_count: u64,
     pub tasks: [TaskInfo; 64],
+    /// Kernel work queue: jobs waiting (deferred file writes and such) and jobs run since boot
+    pub pending_jobs: u64,
+    pub completed_jobs: u64,
+    /// AUDIO_* bits for the sound hardware found at boot
+    pub audio: u32,
+}
+
+/// SystemInfo.audio: the PC speaker, which sys_beep plays on
+pub const AUDIO_PC_SPEAKER: u32 = 1;
+/// SystemInfo.audio: an HD Audio controller was found on PCI (not driven yet)
+pub const AUDIO_HDA: u32 = 2;
+
+// ─────────────────────────────────────────────────────────────────────────
+// SYSCALL ERRORS
+// Failing syscalls leave -(errno) in rax, numbered like Linux. The
+// wrappers below decode that into NyxError so callers can tell a missing
+// file from a full disk without comparing magic numbers.
+// ─────────────────────────────────────────────────────────────────────────
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NyxError {
+    NotPermitted,  // EPERM
+    NotFound,      // ENOENT
+    NoSuchTask,    // ESRCH
+    Io,            // EIO
+    NotExecutable, // ENOEXEC
+    BadFd,         // EBADF
+    WouldBlock,    // EAGAIN
+    OutOfMemory,   // ENOMEM
+    Denied,        // EACCES
+    BadAddress,    // EFAULT
+    Exists,        // EEXIST
+    CrossDevice,   // EXDEV
+    IsDir,         // EISDIR
+    Invalid,       // EINVAL
+    TooManyFiles,  // EMFILE
+    DiskFull,      // ENOSPC
+    NotSeekable,   // ESPIPE
+    ReadOnly,      // EROFS
+    NameTooLong,   // ENAMETOOLONG
+    Unsupported,   // ENOSYS
+    BadName,       // EILSEQ
+    TooBig,        // EMSGSIZE
+    AddrInUse,     // EADDRINUSE
+    Other(i64),
+}
+
+impl NyxError {
+    pub fn from_errno(errno: i64) -> Self {
+        match -errno {
+            1 => NyxError::NotPermitted,
+            2 => NyxError::NotFound,
+            3 => NyxError::NoSuchTask,
+            5 => NyxError::Io,
+            8 => NyxError::NotExecutable,
+            9 => NyxError::BadFd,
+            11 => NyxError::WouldBlock,
+            12 => NyxError::OutOfMemory,
+            13 => NyxError::Denied,
+            14 => NyxError::BadAddress,
+            17 => NyxError::Exists,
+            18 => NyxError::CrossDevice,
+            21 => NyxError::IsDir,
+            22 => NyxError::Invalid,
+            24 => NyxError::TooManyFiles,
+            28 => NyxError::DiskFull,
+            29 => NyxError::NotSeekable,
+            30 => NyxError::ReadOnly,
+            36 => NyxError::NameTooLong,
+            38 => NyxError::Unsupported,
+            84 => NyxError::BadName,
+            90 => NyxError::TooBig,
+            98 => NyxError::AddrInUse,
+            _ => NyxError::Other(errno),
+        }
+    }
+
+    /// Short lowercase description, suitable after "cmd: ".
+    pub fn message(&self) -> &'static str {
+        match self {
+            NyxError::NotPermitted => "operation not permitted",
+            NyxError::NotFound => "not found",
+            NyxError::NoSuchTask => "no such task",
+            NyxError::Io => "I/O error",
+            NyxError::NotExecutable => "not an executable",
+            NyxError::BadFd => "bad file descriptor",
+            NyxError::WouldBlock => "try again",
+            NyxError::OutOfMemory => "out of memory",
+            NyxError::Denied => "permission denied",
+            NyxError::BadAddress => "bad address",
+            NyxError::Exists => "already exists",
+            NyxError::CrossDevice => "crosses a mount",
+            NyxError::IsDir => "is a directory",
+            NyxError::Invalid => "invalid argument",
+            NyxError::TooManyFiles => "too many open files",
+            NyxError::DiskFull => "disk full",
+            NyxError::NotSeekable => "not seekable",
+            NyxError::ReadOnly => "read-only filesystem",
+            NyxError::NameTooLong => "name too long",
+            NyxError::Unsupported => "not supported",
+            NyxError::BadName => "name can't contain \\ : * ? \" < > |",
+            NyxError::TooBig => "message too long",
+            NyxError::AddrInUse => "address in use",
+            NyxError::Other(_) => "unknown error",
+        }
+    }
+}
+
+pub type NyxResult<T> = Result<T, NyxError>;
+
+/// Splits a raw syscall return into a value or the error it encodes.
+pub fn check(ret: u64) -> NyxResult<usize> {
+    if (ret as i64) < 0 { Err(NyxError::from_errno(ret as i64)) } else { Ok(ret as usize) }
 }
 
 // ─────────────────────────────────────────────────────────────────────────
@@ -70,11 +389,13 @@ pub const SYS_LSEEK: u64 = 8;
 pub const SYS_MMAP: u64 = 9;
 pub const SYS_MPROTECT: u64 = 10;
 pub const SYS_MUNMAP: u64 = 11;
+pub const SYS_BRK: u64 = 12;
 pub const SYS_IOCTL: u64 = 16;
 pub const SYS_SOCKET: u64 = 41;
 pub const SYS_CONNECT: u64 = 42;
 pub const SYS_SENDTO: u64 = 44;
 pub const SYS_RECVFROM: u64 = 45;
+pub const SYS_BIND: u64 = 49;
 pub const SYS_CLONE: u64 = 56;
 pub const SYS_FORK: u64 = 57;
 pub const SYS_EXECVE: u64 = 59;
@@ -84,6 +405,8 @@ pub const SYS_CLOCK_GETTIME: u64 = 228;
 pub const SYS_PIPE: u64 = 22;
 pub const SYS_DUP2: u64 = 33;
 
+/// The one syscall ABI: number in rax, arguments in rdi, rsi, rdx, r10, r8,
+/// r9, result in rax; rcx and r11 are clobbered.
 #[inline(always)]
 pub fn syscall(n: u64, arg1: u64, arg2: u64, arg3: u64, arg4: u64, arg5: u64, arg6: u64) -> u64 {
     let mut ret: u64;
@@ -106,20 +429,31 @@ pub fn syscall(n: u64, arg1: u64, arg2: u64, arg3: u64, arg4: u64, arg5: u64, ar
     ret
 }
 
-pub fn sys_read(fd: i64, buf: &mut [u8]) -> i64 {
-    syscall(SYS_READ, fd as u64, buf.as_mut_ptr() as u64, buf.len() as u64, 0, 0, 0) as i64
+/// Reads into `buf`; Ok(0) means end of file.
+pub fn sys_read(fd: i64, buf: &mut [u8]) -> NyxResult<usize> {
+    check(syscall(SYS_READ, fd as u64, buf.as_mut_ptr() as u64, buf.len() as u64, 0, 0, 0))
+}
+
+pub fn sys_write(fd: i64, buf: &[u8]) -> NyxResult<usize> {
+    check(syscall(SYS_WRITE, fd as u64, buf.as_ptr() as u64, buf.len() as u64, 0, 0, 0))
 }
 
-pub fn sys_write(fd: i64, buf: &[u8]) -> i64 {
-    syscall(SYS_WRITE, fd as u64, buf.as_ptr() as u64, buf.len() as u64, 0, 0, 0) as i64
+/// Opens a file and returns its descriptor.
+pub fn sys_open(path: &str) -> NyxResult<i64> {
+    check(syscall(SYS_OPEN, path.as_ptr() as u64, path.len() as u64, 0, 0, 0, 0)).map(|fd| fd as i64)
 }
 
-pub fn sys_open(path: &str) -> i64 {
-    syscall(SYS_OPEN, path.as_ptr() as u64, path.len() as u64, 0, 0, 0, 0) as i64
+pub const SEEK_SET: u64 = 0;
+pub const SEEK_CUR: u64 = 1;
+pub const SEEK_END: u64 = 2;
+
+/// Moves the offset of an open file and returns the new offset.
+pub fn sys_lseek(fd: i64, offset: i64, whence: u64) -> NyxResult<usize> {
+    check(syscall(SYS_LSEEK, fd as u64, offset as u64, whence, 0, 0, 0))
 }
 
-pub fn sys_close(fd: i64) -> i64 {
-    syscall(SYS_CLOSE, fd as u64, 0, 0, 0, 0, 0) as i64
+pub fn sys_close(fd: i64) -> NyxResult<()> {
+    check(syscall(SYS_CLOSE, fd as u64, 0, 0, 0, 0, 0)).map(|_| ())
 }
 
 pub fn sys_exit(code: i64) -> ! {
@@ -127,24 +461,40 @@ pub fn sys_exit(code: i64) -> ! {
     loop {}
 }
 
-pub fn sys_pipe(fds: &mut [i32; 2]) -> i64 {
-    syscall(SYS_PIPE, fds.as_mut_ptr() as u64, 0, 0, 0, 0, 0) as i64
+/// Opens a pipe: fds[0] reads what fds[1] writes. TooManyFiles without two free slots.
+pub fn sys_pipe(fds: &mut [i32; 2]) -> NyxResult<()> {
+    check(syscall(SYS_PIPE, fds.as_mut_ptr() as u64, 0, 0, 0, 0, 0)).map(|_| ())
+}
+
+/// Makes `newfd` another handle on whatever `oldfd` is, and returns it.
+pub fn sys_dup2(oldfd: i64, newfd: i64) -> NyxResult<i64> {
+    check(syscall(SYS_DUP2, oldfd as u64, newfd as u64, 0, 0, 0, 0)).map(|fd| fd as i64)
 }
 
-pub fn sys_dup2(oldfd: i64, newfd: i64) -> i64 {
-    syscall(SYS_DUP2, oldfd as u64, newfd as u64, 0, 0, 0, 0) as i64
+/// Replaces the current program. Only returns if that failed.
+pub fn sys_execve(path: &str) -> NyxError {
+    NyxError::from_errno(syscall(SYS_EXECVE, path.as_ptr() as u64, path.len() as u64, 0, 0, 0, 0) as i64)
 }
 
-pub fn sys_execve(path: &str) -> i64 {
-    syscall(SYS_EXECVE, path.as_ptr() as u64, path.len() as u64, 0, 0, 0, 0) as i64
+/// Starts the ELF at `path` as a separate process with its own address space.
+/// It shares this task's console (fds 0-2) and nothing else. Returns its PID.
+pub fn sys_spawn(path: &str) -> NyxResult<u64> {
+    check(syscall(547, path.as_ptr() as u64, path.len() as u64, 0, 0, 0, 0)).map(|pid| pid as u64)
 }
 
-pub fn sys_fork() -> i64 {
-    syscall(SYS_FORK, 0, 0, 0, 0, 0, 0) as i64
+/// Copies this process. The parent gets the child's PID, the child 0.
+pub fn sys_fork() -> NyxResult<u64> {
+    check(syscall(SYS_FORK, 0, 0, 0, 0, 0, 0)).map(|pid| pid as u64)
 }
 
 pub fn sys_print(text: &str) {
-    sys_write(1, text.as_bytes());
+    let _ = sys_write(1, text.as_bytes());
+}
+
+/// Takes whatever this task and the children sharing its console wrote to stdout/stderr.
+/// BadFd if fd 1 isn't a console.
+pub fn sys_console_read(buf: &mut [u8]) -> NyxResult<usize> {
+    check(syscall(545, buf.as_mut_ptr() as u64, buf.len() as u64, 0, 0, 0, 0))
 }
 
 pub fn sys_gpu_sync() {
@@ -155,6 +505,12 @@ pub fn sys_swap_buffers() {
     syscall(502, 0, 0, 0, 0, 0, 0);
 }
 
+/// sys_swap_buffers for when only this part of the framebuffer changed.
+pub fn sys_swap_buffers_rect(x: usize, y: usize, w: usize, h: usize) {
+    if w == 0 || h == 0 { return; }
+    syscall(502, x as u64, y as u64, w as u64, h as u64, 0, 0);
+}
+
 pub fn sys_gpu_fill_rect(x: usize, y: usize, w: usize, h: usize, color: u32) {
     syscall(501, x as u64, y as u64, w as u64, h as u64, color as u64, 0);
 }
@@ -163,14 +519,34 @@ pub fn sys_get_time() -> usize {
     syscall(504, 0, 0, 0, 0, 0, 0) as usize
 }
 
+/// Microseconds on the TSC. Only differences mean anything; use it to time
+/// work that finishes inside one sys_get_time() tick.
+pub fn sys_uptime_us() -> u64 {
+    syscall(569, 0, 0, 0, 0, 0, 0)
+}
+
 pub fn sys_get_context_switches() -> u64 {
     syscall(523, 0, 0, 0, 0, 0, 0)
 }
 
+/// Number of tasks that have exited (or been killed) since boot.
+pub fn sys_get_tasks_exited() -> u64 {
+    syscall(546, 0, 0, 0, 0, 0, 0)
+}
+
 pub fn sys_get_boot_logs(buf: &mut [u8]) -> usize {
     syscall(518, buf.as_mut_ptr() as u64, buf.len() as u64, 0, 0, 0, 0) as usize
 }
 
+/// Reads kernel log text from absolute `offset` on (Syscall 518). Returns the
+/// offset it actually read from, which is later than `offset` if the kernel's
+/// 64 KiB ring overwrote that text, and the number of bytes copied.
+pub fn sys_read_boot_log(buf: &mut [u8], offset: u64) -> (u64, usize) {
+    let mut start = 0u64;
+    let len = syscall(518, buf.as_mut_ptr() as u64, buf.len() as u64, offset, &mut start as *mut u64 as u64, 0, 0) as usize;
+    (start, len)
+}
+
 pub fn sys_get_hw_info(buf: &mut [u8]) -> usize {
     syscall(517, buf.as_mut_ptr() as u64, buf.len() as u64, 0, 0, 0, 0) as usize
 }
@@ -191,13 +567,33 @@ pub fn sys_wait_vsync() {
     syscall(513, 0, 0, 0, 0, 0, 0);
 }
 
-pub fn sys_get_mouse() -> (usize, usize, bool, bool) {
+#[derive(Clone, Copy, Default)]
+pub struct MouseSample {
+    pub x: usize,
+    pub y: usize,
+    pub left: bool,
+    pub right: bool,
+    pub middle: bool,
+    /// Wheel notches since the previous read, positive away from the user
+    pub wheel: i8,
+}
+
+/// Reads the pointer. The wheel count is handed out once, to whoever reads first.
+pub fn sys_get_mouse_state() -> MouseSample {
     let m = syscall(505, 0, 0, 0, 0, 0, 0);
-    let x = (m >> 32) as usize;
-    let y = ((m >> 16) & 0xFFFF) as usize;
-    let left = ((m >> 1) & 1) == 1;
-    let right = (m & 1) == 1;
-    (x, y, left, right)
+    MouseSample {
+        x: (m >> 32) as usize,
+        y: ((m >> 16) & 0xFFFF) as usize,
+        left: ((m >> 1) & 1) == 1,
+        right: (m & 1) == 1,
+        middle: ((m >> 2) & 1) == 1,
+        wheel: (m >> 8) as u8 as i8,
+    }
+}
+
+pub fn sys_get_mouse() -> (usize, usize, bool, bool) {
+    let m = sys_get_mouse_state();
+    (m.x, m.y, m.left, m.right)
 }
 
 pub fn sys_read_key() -> Option<char> {
@@ -205,6 +601,10 @@ pub fn sys_read_key() -> Option<char> {
     if k == 0 { None } else { core::char::from_u32(k as u32) }
 }
 
+pub fn sys_read_key_event() -> Option<KeyEvent> {
+    KeyEvent::from_packed(syscall(538, 0, 0, 0, 0, 0, 0))
+}
+
 pub fn sys_get_screen_info() -> (usize, usize, usize) {
     let mut w: u64 = 0;
     let mut h: u64 = 0;
@@ -213,6 +613,87 @@ pub fn sys_get_screen_info() -> (usize, usize, usize) {
     (w as usize, h as usize, s as usize)
 }
 
+/// How the framebuffer stores a pixel, byte by byte from the lowest address.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PixelFormat { Bgr, Rgb, Gray }
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FbFormat {
+    pub bytes_per_pixel: usize,
+    pub pixel_format: PixelFormat,
+}
+
+impl FbFormat {
+    /// Whether a 0xAARRGGBB u32 can be stored into the framebuffer as is.
+    pub fn is_native_argb(&self) -> bool {
+        self.bytes_per_pixel == 4 && self.pixel_format == PixelFormat::Bgr
+    }
+}
+
+/// Layout of the pixels behind sys_map_framebuffer. Without a screen this
+/// says 4-byte BGR, which is what everything assumed before it existed.
+pub fn sys_get_fb_format() -> FbFormat {
+    let packed = syscall(554, 0, 0, 0, 0, 0, 0);
+    let bytes_per_pixel = (packed & 0xFF) as usize;
+    if bytes_per_pixel == 0 { return FbFormat { bytes_per_pixel: 4, pixel_format: PixelFormat::Bgr }; }
+    let pixel_format = match (packed >> 8) & 0xFF {
+        1 => PixelFormat::Rgb,
+        2 => PixelFormat::Gray,
+        _ => PixelFormat::Bgr,
+    };
+    FbFormat { bytes_per_pixel, pixel_format }
+}
+
+/// Asks for the desktop to be drawn at w x h and scaled to the screen; (0, 0)
+/// means the screen's own resolution. Fails with Invalid for sizes
+/// under 640x480 or over the native mode. Under QEMU's standard VGA the
+/// screen itself switches to w x h (bigger ones too, as far as video memory
+/// goes); sys_get_screen_info then reports the new mode and the framebuffer
+/// has to be mapped again with sys_map_framebuffer.
+pub fn sys_set_resolution(w: usize, h: usize) -> NyxResult<()> {
+    check(syscall(555, w as u64, h as u64, 0, 0, 0, 0)).map(|_| ())
+}
+
+/// The resolution the desktop renders at, which is the native one from
+/// sys_get_screen_info unless sys_set_resolution picked another.
+pub fn sys_get_resolution() -> (usize, usize) {
+    let mode = syscall(556, 0, 0, 0, 0, 0, 0);
+    ((mode >> 32) as usize, (mode & 0xFFFF_FFFF) as usize)
+}
+
+pub const MAX_DISPLAYS: usize = 4;
+
+/// One display of the desktop, in the pointer's coordinates; the primary
+/// is the first and sits at the origin.
+#[repr(C)]
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct DisplayInfo {
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+    /// Registered with sys_add_virtual_display; shares the framebuffer
+    pub is_virtual: u32,
+}
+
+/// Fills `out` with the displays, primary first, and returns how many there are.
+pub fn sys_get_displays(out: &mut [DisplayInfo]) -> usize {
+    check(syscall(557, out.as_mut_ptr() as u64, out.len() as u64, 0, 0, 0, 0)).unwrap_or(0)
+}
+
+/// Adds a w x h virtual display to the right of the others and returns its
+/// index; at least 640x480, and at most four displays in all. The compositor
+/// only picks it up once told with MSG_DISPLAYS_CHANGED.
+pub fn sys_add_virtual_display(w: usize, h: usize) -> NyxResult<usize> {
+    check(syscall(558, w as u64, h as u64, 0, 0, 0, 0))
+}
+
+/// Whether the GPU draws the mouse pointer itself (virtio-gpu's cursor), so
+/// the compositor doesn't have to.
+pub fn sys_has_hw_cursor() -> bool {
+    syscall(559, 0, 0, 0, 0, 0, 0) == 1
+}
+
 pub fn sys_map_framebuffer() -> u64 {
     syscall(508, 0, 0, 0, 0, 0, 0)
 }
@@ -227,26 +708,267 @@ pub fn sys_gpu_copy_rect(src_gva: u32, dst_gva: u32, w: usize, h: usize, dst_x:
 
 
 
-pub fn sys_fs_count(path: &str) -> usize {
-    syscall(510, path.as_ptr() as u64, path.len() as u64, 0, 0, 0, 0) as usize
+/// Number of entries in `path`. NotFound for a missing directory.
+pub fn sys_fs_count(path: &str) -> NyxResult<usize> {
+    check(syscall(510, path.as_ptr() as u64, path.len() as u64, 0, 0, 0, 0))
+}
+
+/// Copies the name of the `idx`-th entry of `path` into `buf`. NotFound past the end.
+pub fn sys_fs_get_name(path: &str, idx: usize, buf: &mut [u8]) -> NyxResult<usize> {
+    check(syscall(511, idx as u64, buf.as_mut_ptr() as u64, path.as_ptr() as u64, path.len() as u64, 0, 0))
+}
+
+/// The volume `path` is on. Read-only mounts that can't tell fail with ReadOnly.
+pub fn sys_fs_volume_info(path: &str) -> NyxResult<VolumeInfo> {
+    let mut info = VolumeInfo::default();
+    check(syscall(563, path.as_ptr() as u64, path.len() as u64, &mut info as *mut VolumeInfo as u64, 0, 0, 0))?;
+    Ok(info)
+}
+
+/// Renames a file or directory. Fails with Exists if `new` is taken.
+pub fn sys_fs_rename(old: &str, new: &str) -> NyxResult<()> {
+    check(syscall(535, old.as_ptr() as u64, old.len() as u64, new.as_ptr() as u64, new.len() as u64, 0, 0)).map(|_| ())
+}
+
+pub const FS_WRITE_TRUNCATE: u64 = 1;
+pub const FS_WRITE_ASYNC: u64 = 2;
+
+/// Writes `buf` into `path` at `offset` and returns the bytes written.
+/// With FS_WRITE_TRUNCATE the file is recreated empty first. With FS_WRITE_ASYNC
+/// the kernel queues the write (in order with other queued writes) and returns at
+/// once; failures are then only logged.
+pub fn sys_fs_write(path: &str, buf: &[u8], offset: usize, flags: u64) -> NyxResult<usize> {
+    check(syscall(539, path.as_ptr() as u64, path.len() as u64, buf.as_ptr() as u64, buf.len() as u64, offset as u64, flags))
+}
+
+/// Deletes a file. Fails with NotFound if there isn't one.
+pub fn sys_fs_delete(path: &str) -> NyxResult<()> {
+    check(syscall(560, path.as_ptr() as u64, path.len() as u64, 0, 0, 0, 0)).map(|_| ())
+}
+
+/// Creates a directory, including any missing parent directories.
+pub fn sys_fs_mkdir(path: &str) -> NyxResult<()> {
+    check(syscall(536, path.as_ptr() as u64, path.len() as u64, 0, 0, 0, 0)).map(|_| ())
 }
 
-pub fn sys_fs_get_name(path: &str, idx: usize, buf: &mut [u8]) -> usize {
-    syscall(511, idx as u64, buf.as_mut_ptr() as u64, path.as_ptr() as u64, path.len() as u64, 0, 0) as usize
+/// Reads up to `buf.len()` bytes of `path` starting at `offset` and returns the bytes copied.
+pub fn sys_fs_read(path: &str, buf: &mut [u8], offset: usize) -> NyxResult<usize> {
+    check(syscall(537, path.as_ptr() as u64, path.len() as u64, buf.as_mut_ptr() as u64, buf.len() as u64, offset as u64, 0))
+}
+
+/// Size of `path` in bytes.
+pub fn sys_fs_size(path: &str) -> NyxResult<usize> {
+    check(syscall(537, path.as_ptr() as u64, path.len() as u64, 0, 0, 0, 0))
+}
+
+// ─────────────────────────────────────────────────────────────────────────
+// BATCHED DIRECTORY LISTING (Syscalls 541/542)
+// sys_fs_readdir packs records back to back, little endian:
+//   u16 name length, u16 flags (DIRENT_FLAG_*), u64 size,
+//   u64 modified time (Unix seconds, 0 if unknown), name bytes
+// Names carry no trailing '/'; use the flag instead. Close with sys_close.
+// ─────────────────────────────────────────────────────────────────────────
+pub const DIRENT_HEADER_LEN: usize = 20;
+pub const DIRENT_FLAG_DIR: u16 = 1;
+/// Dot files
+pub const DIRENT_FLAG_HIDDEN: u16 = 2;
+/// Kept by the file system itself, like lost+found
+pub const DIRENT_FLAG_SYSTEM: u16 = 4;
+/// Writing, renaming or deleting it fails with Denied
+pub const DIRENT_FLAG_READONLY: u16 = 8;
+
+pub fn sys_fs_opendir(path: &str) -> NyxResult<i64> {
+    check(syscall(541, path.as_ptr() as u64, path.len() as u64, 0, 0, 0, 0)).map(|fd| fd as i64)
+}
+
+/// Fills `buf` with as many entries as fit and returns the number written, 0 at the
+/// end of the directory. Fails with Invalid if the next entry can't fit in `buf`.
+pub fn sys_fs_readdir(fd: i64, buf: &mut [u8]) -> NyxResult<usize> {
+    check(syscall(542, fd as u64, buf.as_mut_ptr() as u64, buf.len() as u64, 0, 0, 0))
+}
+
+/// Goes up by at least one whenever a file anywhere is created, written,
+/// renamed or deleted. Cheap enough to poll; a listing only needs reading
+/// again when it has changed.
+pub fn sys_fs_generation() -> u64 {
+    syscall(564, 0, 0, 0, 0, 0, 0)
+}
+
+/// The sys_fs_generation() the listing behind `fd` was taken at. If the
+/// current one is newer, the listing may already be out of date.
+pub fn sys_fs_dir_generation(fd: i64) -> NyxResult<u64> {
+    check(syscall(565, fd as u64, 0, 0, 0, 0, 0)).map(|g| g as u64)
+}
+
+pub struct DirEntry<'a> {
+    pub name: &'a str,
+    pub is_dir: bool,
+    pub hidden: bool,
+    pub system: bool,
+    pub read_only: bool,
+    pub size: u64,
+    /// Unix seconds, 0 if the file system or the clock didn't record one
+    pub mtime: u64,
+}
+
+/// Walks the records of one sys_fs_readdir batch.
+pub struct DirEntries<'a> {
+    buf: &'a [u8],
+    remaining: usize,
+}
+
+impl<'a> DirEntries<'a> {
+    pub fn new(buf: &'a [u8], count: usize) -> Self { Self { buf, remaining: count } }
+}
+
+impl<'a> Iterator for DirEntries<'a> {
+    type Item = DirEntry<'a>;
+
+    fn next(&mut self) -> Option<DirEntry<'a>> {
+        if self.remaining == 0 || self.buf.len() < DIRENT_HEADER_LEN { return None; }
+        let name_len = u16::from_le_bytes([self.buf[0], self.buf[1]]) as usize;
+        let flags = u16::from_le_bytes([self.buf[2], self.buf[3]]);
+        let (mut size, mut mtime) = ([0u8; 8], [0u8; 8]);
+        size.copy_from_slice(&self.buf[4..12]);
+        mtime.copy_from_slice(&self.buf[12..20]);
+        let end = DIRENT_HEADER_LEN + name_len;
+        if self.buf.len() < end { return None; }
+
+        let name = core::str::from_utf8(&self.buf[DIRENT_HEADER_LEN..end]).unwrap_or("?");
+        self.buf = &self.buf[end..];
+        self.remaining -= 1;
+        Some(DirEntry {
+            name,
+            is_dir: flags & DIRENT_FLAG_DIR != 0,
+            hidden: flags & DIRENT_FLAG_HIDDEN != 0,
+            system: flags & DIRENT_FLAG_SYSTEM != 0,
+            read_only: flags & DIRENT_FLAG_READONLY != 0,
+            size: u64::from_le_bytes(size),
+            mtime: u64::from_le_bytes(mtime),
+        })
+    }
+}
+
+/// Moves the program break (the end of the heap) to `addr` and returns where it
+/// ended up. sys_brk(0) just reports it. Pages are backed on first touch.
+pub fn sys_brk(addr: u64) -> u64 {
+    syscall(SYS_BRK, addr, 0, 0, 0, 0, 0)
 }
 
 pub fn sys_alloc_pages(pages: usize) -> u64 {
     syscall(519, pages as u64, 0, 0, 0, 0, 0)
 }
 
-pub fn sys_get_system_info(info_ptr: *mut SystemInfo) -> u64 {
-    syscall(524, info_ptr as u64, 0, 0, 0, 0, 0)
+pub fn sys_get_system_info(info: &mut SystemInfo) -> NyxResult<()> {
+    check(syscall(524, info as *mut SystemInfo as u64, 0, 0, 0, 0, 0)).map(|_| ())
+}
+
+pub fn sys_get_meminfo() -> NyxResult<MemInfo> {
+    let mut info = MemInfo::default();
+    check(syscall(548, &mut info as *mut MemInfo as u64, 0, 0, 0, 0, 0))?;
+    Ok(info)
+}
+
+/// Share of CPU time spent outside the idle tasks over the last second, in per mille.
+pub fn sys_get_cpu_load() -> u64 {
+    syscall(549, 0, 0, 0, 0, 0, 0)
+}
+
+/// Has the kernel allocate, check and free `mib` MiB of heap (at most 128, one
+/// test at a time). Returns how many MiB it held at once.
+pub fn sys_heap_test(mib: usize) -> NyxResult<usize> {
+    check(syscall(550, mib as u64, 0, 0, 0, 0, 0))
+}
+
+/// Fills `out` with scheduler statistics for every task. Returns how many entries were filled.
+pub fn sys_task_stats(out: &mut [TaskStat]) -> NyxResult<usize> {
+    check(syscall(551, out.as_mut_ptr() as u64, out.len() as u64, 0, 0, 0, 0))
+}
+
+/// Tells the scheduler which task owns the focused window, so it gets the
+/// foreground ticket boost. 0 when nothing has focus. Ignored unless the
+/// caller owns the screen (the compositor).
+pub fn sys_set_foreground(pid: u64) {
+    syscall(552, pid, 0, 0, 0, 0, 0);
+}
+
+/// Gives user task `pid` this many lottery tickets (clamped to the kernel's
+/// 1..=10000). NotPermitted for the kernel's tasks, init and the compositor,
+/// and for raising the caller's own tickets (any other task may raise them).
+pub fn sys_set_tickets(pid: u64, tickets: u64) -> NyxResult<()> {
+    check(syscall(561, pid, tickets, 0, 0, 0, 0)).map(|_| ())
+}
+
+/// Ends user task `pid` with exit code -9 at its next syscall, or at the next
+/// timer tick if it is running its own code. NotPermitted for the kernel's
+/// tasks, init and the compositor.
+pub fn sys_kill(pid: u64) -> NyxResult<()> {
+    check(syscall(562, pid, 0, 0, 0, 0, 0)).map(|_| ())
+}
+
+/// What syscall 553 XORs each argument with. Must match the kernel's ABI_ECHO_XOR.
+pub const ABI_ECHO_XOR: u64 = 0x4E59_5841_4249_4543;
+
+/// Passes six different values through syscall 553 and checks each comes
+/// back, XOR'd, in the register it went out in. False means the kernel and
+/// this library disagree about where the arguments live.
+pub fn sys_abi_check() -> bool {
+    let args: [u64; 6] = [0x0101_0101_0101_0101, 0x0202_0202_0202_0202, 0x0303_0303_0303_0303,
+                          0x0404_0404_0404_0404, 0x0505_0505_0505_0505, 0x0606_0606_0606_0606];
+    let mut regs = args;
+    let ret: u64;
+    unsafe {
+        asm!(
+            "syscall",
+            inlateout("rax") 553u64 => ret,
+            inout("rdi") regs[0],
+            inout("rsi") regs[1],
+            inout("rdx") regs[2],
+            inout("r10") regs[3],
+            inout("r8") regs[4],
+            inout("r9") regs[5],
+            out("rcx") _,
+            out("r11") _,
+            options(nostack)
+        );
+    }
+    ret == 0 && regs.iter().zip(args.iter()).all(|(&got, &sent)| got == sent ^ ABI_ECHO_XOR)
+}
+
+/// Lists the NVMe namespaces into `out`. Returns how many entries were filled.
+pub fn sys_block_devices(out: &mut [BlockDeviceInfo]) -> NyxResult<usize> {
+    check(syscall(543, out.as_mut_ptr() as u64, out.len() as u64, 0, 0, 0, 0))
+}
+
+/// Lists every disk the kernel identified (NVMe namespaces, then SATA ports) into `out`.
+pub fn sys_disk_info(out: &mut [DiskInfoRecord]) -> NyxResult<usize> {
+    check(syscall(544, out.as_mut_ptr() as u64, out.len() as u64, 0, 0, 0, 0))
 }
 
 pub fn sys_sleep_ms(ms: u64) {
     syscall(525, ms, 0, 0, 0, 0, 0);
 }
 
+/// Wall-clock time in Unix seconds (UTC); 0 if the machine has no usable clock.
+pub fn sys_wall_clock() -> u64 {
+    syscall(567, 0, 0, 0, 0, 0, 0)
+}
+
+/// Sets the wall clock (until reboot; the CMOS chip isn't written).
+/// NotPermitted unless the caller is init or the compositor; apps ask
+/// the compositor with MSG_SET_CLOCK.
+pub fn sys_set_wall_clock(unix_secs: u64) -> NyxResult<()> {
+    check(syscall(568, unix_secs, 0, 0, 0, 0, 0)).map(|_| ())
+}
+
+/// Plays `freq_hz` (20..=20000) on the PC speaker for `ms` (up to 5000)
+/// after whatever is already playing, without waiting for it. Frequency 0
+/// silences the speaker and drops queued tones. Invalid when out of range,
+/// WouldBlock when too many tones are queued.
+pub fn sys_beep(freq_hz: u32, ms: u32) -> NyxResult<()> {
+    check(syscall(566, freq_hz as u64, ms as u64, 0, 0, 0, 0)).map(|_| ())
+}
+
 pub fn sys_get_dsdt(buf: &mut [u8]) -> usize {
     syscall(526, buf.as_mut_ptr() as u64, buf.len() as u64, 0, 0, 0, 0) as usize
 }
@@ -259,22 +981,86 @@ pub fn sys_map_shm(shm_id: u64) -> u64 {
     syscall(531, shm_id, 0, 0, 0, 0, 0)
 }
 
-pub fn sys_ipc_send(target_pid: u64, msg_type: u64, data1: u64, data2: u64) -> bool {
-    syscall(532, target_pid, msg_type, data1, data2, 0, 0) == 1
+/// Unmaps a block sys_map_shm put at `addr`. With `release` the block is freed
+/// once every process that mapped it has unmapped it. Invalid if `addr` isn't it.
+pub fn sys_unmap_shm(shm_id: u64, addr: u64, release: bool) -> NyxResult<()> {
+    check(syscall(570, shm_id, addr, release as u64, 0, 0, 0)).map(|_| ())
 }
 
-pub fn sys_ipc_recv(msg_ptr: *mut IpcMessage, block: bool) -> bool {
-    syscall(533, msg_ptr as u64, if block { 1 } else { 0 }, 0, 0, 0, 0) == 1
+/// Queues a message for `target_pid`. NoSuchTask if nothing has that PID.
+pub fn sys_ipc_send(target_pid: u64, msg_type: u64, data1: u64, data2: u64) -> NyxResult<()> {
+    check(syscall(532, target_pid, msg_type, data1, data2, 0, 0)).map(|_| ())
 }
 
-pub fn sys_socket(domain: u64, typ: u64, protocol: u64) -> i64 {
-    syscall(SYS_SOCKET, domain, typ, protocol, 0, 0, 0) as i64
+/// Takes the next message into `msg`, waiting for one if `block`. WouldBlock
+/// if there's none and `block` is false.
+pub fn sys_ipc_recv(msg: &mut IpcMessage, block: bool) -> NyxResult<()> {
+    check(syscall(533, msg as *mut IpcMessage as u64, if block { 1 } else { 0 }, 0, 0, 0, 0)).map(|_| ())
 }
 
-pub fn sys_connect(fd: i64, addr: *const u8, len: usize) -> i64 {
-    syscall(SYS_CONNECT, fd as u64, addr as u64, len as u64, 0, 0, 0) as i64
+/// Sends `text` in a fresh shared page: data1 = SHM id, data2 = length in bytes.
+/// The receiver frees the page when it reads it with ipc_read_str.
+pub fn sys_ipc_send_str(target_pid: u64, msg_type: u64, text: &str) -> NyxResult<()> {
+    let shm_id = sys_create_shm(text.len().max(1));
+    if shm_id == 0 { return Err(NyxError::OutOfMemory); }
+    let dest = sys_map_shm(shm_id);
+    if dest == 0 { return Err(NyxError::OutOfMemory); }
+    unsafe { core::ptr::copy_nonoverlapping(text.as_ptr(), dest as *mut u8, text.len()); }
+    let sent = sys_ipc_send(target_pid, msg_type, shm_id, text.len() as u64);
+    // Nobody will read it if the send failed
+    let _ = sys_unmap_shm(shm_id, dest, sent.is_err());
+    sent
 }
 
+/// Hands `f` a string attached with sys_ipc_send_str, then frees its page; copy
+/// out whatever should outlive the call. Read each message once.
+pub fn ipc_read_str<R>(msg: &IpcMessage, f: impl FnOnce(&str) -> R) -> Option<R> {
+    if msg.data1 == 0 { return None; }
+    let src = sys_map_shm(msg.data1);
+    if src == 0 { return None; }
+    let bytes = unsafe { core::slice::from_raw_parts(src as *const u8, (msg.data2 as usize).min(4096)) };
+    let result = core::str::from_utf8(bytes).ok().map(f);
+    let _ = sys_unmap_shm(msg.data1, src, true);
+    result
+}
+
+/// Opens a socket and returns its fd.
+pub fn sys_socket(domain: u64, typ: u64, protocol: u64) -> NyxResult<i64> {
+    check(syscall(SYS_SOCKET, domain, typ, protocol, 0, 0, 0)).map(|fd| fd as i64)
+}
+
+pub fn sys_connect(fd: i64, addr: &sockaddr_in) -> NyxResult<()> {
+    let len = core::mem::size_of::<sockaddr_in>() as u64;
+    check(syscall(SYS_CONNECT, fd as u64, addr as *const _ as u64, len, 0, 0, 0)).map(|_| ())
+}
+
+/// Gives socket `fd` the local port in `addr`. AddrInUse if another UDP
+/// socket has it.
+pub fn sys_bind(fd: i64, addr: &sockaddr_in) -> NyxResult<()> {
+    let len = core::mem::size_of::<sockaddr_in>() as u64;
+    check(syscall(SYS_BIND, fd as u64, addr as *const _ as u64, len, 0, 0, 0)).map(|_| ())
+}
+
+/// Sends `data` as one datagram to `addr`. TooBig past MAX_UDP_PAYLOAD (the
+/// kernel doesn't fragment), WouldBlock while the send queue is full.
+pub fn sys_sendto(fd: i64, data: &[u8], addr: &sockaddr_in) -> NyxResult<usize> {
+    let len = core::mem::size_of::<sockaddr_in>() as u64;
+    check(syscall(SYS_SENDTO, fd as u64, data.as_ptr() as u64, data.len() as u64, 0, addr as *const _ as u64, len))
+}
+
+/// The next queued datagram, cut to `buf`, and who sent it. Never waits:
+/// WouldBlock when nothing has arrived.
+pub fn sys_recvfrom(fd: i64, buf: &mut [u8]) -> NyxResult<(usize, sockaddr_in)> {
+    let mut from = sockaddr_in { sin_family: 0, sin_port: 0, sin_addr: [0; 4], sin_zero: [0; 8] };
+    let mut from_len = core::mem::size_of::<sockaddr_in>() as u32;
+    let n = check(syscall(SYS_RECVFROM, fd as u64, buf.as_mut_ptr() as u64, buf.len() as u64, 0,
+        &mut from as *mut _ as u64, &mut from_len as *mut u32 as u64))?;
+    Ok((n, from))
+}
+
+/// Largest payload sys_sendto takes: one 1500-byte frame less the IPv4 and UDP headers
+pub const MAX_UDP_PAYLOAD: usize = 1472;
+
 #[repr(C)]
 pub struct sockaddr_in {
     pub sin_family: u16,
@@ -283,37 +1069,56 @@ pub struct sockaddr_in {
     pub sin_zero: [u8; 8],
 }
 
+impl sockaddr_in {
+    pub fn new(ip: [u8; 4], port: u16) -> Self {
+        Self { sin_family: 2, sin_port: port.to_be(), sin_addr: ip, sin_zero: [0; 8] }
+    }
+
+    pub fn port(&self) -> u16 { u16::from_be(self.sin_port) }
+}
+
 pub struct UdpSocket {
     pub fd: i64,
 }
 
 impl UdpSocket {
     pub fn new() -> Option<Self> {
-        let fd = sys_socket(2, 2, 0); 
-        if fd >= 0 { Some(Self { fd }) } else { None }
+        sys_socket(2, 2, 0).ok().map(|fd| Self { fd })
     }
 
     pub fn connect(&self, ip_a: u8, ip_b: u8, ip_c: u8, ip_d: u8, port: u16) -> bool {
-        let addr = sockaddr_in {
-            sin_family: 2,          
-            sin_port: port.to_be(), 
-            sin_addr: [ip_a, ip_b, ip_c, ip_d],
-            sin_zero: [0; 8],
-        };
-        sys_connect(self.fd, &addr as *const _ as *const u8, core::mem::size_of::<sockaddr_in>()) == 0
+        sys_connect(self.fd, &sockaddr_in::new([ip_a, ip_b, ip_c, ip_d], port)).is_ok()
     }
 
     pub fn send(&self, data: &[u8]) -> bool {
-        sys_write(self.fd, data) > 0
+        matches!(sys_write(self.fd, data), Ok(n) if n > 0)
     }
 
-    pub fn recv(&self, buf: &mut [u8]) -> i64 {
+    pub fn recv(&self, buf: &mut [u8]) -> NyxResult<usize> {
         sys_read(self.fd, buf)
     }
+
+    /// Receives on `port` rather than the port the kernel picked.
+    pub fn bind(&self, port: u16) -> NyxResult<()> {
+        sys_bind(self.fd, &sockaddr_in::new([0; 4], port))
+    }
+
+    pub fn send_to(&self, ip: [u8; 4], port: u16, data: &[u8]) -> NyxResult<usize> {
+        sys_sendto(self.fd, data, &sockaddr_in::new(ip, port))
+    }
+
+    /// One datagram and its sender's address and port; WouldBlock if none is queued.
+    pub fn recv_from(&self, buf: &mut [u8]) -> NyxResult<(usize, [u8; 4], u16)> {
+        sys_recvfrom(self.fd, buf).map(|(n, from)| (n, from.sin_addr, from.port()))
+    }
+}
+
+impl Drop for UdpSocket {
+    fn drop(&mut self) { let _ = sys_close(self.fd); }
 }
 
-pub fn sys_getrandom(buf: &mut [u8]) -> i64 {
-    syscall(318, buf.as_mut_ptr() as u64, buf.len() as u64, 0, 0, 0, 0) as i64
+pub fn sys_getrandom(buf: &mut [u8]) -> NyxResult<usize> {
+    check(syscall(318, buf.as_mut_ptr() as u64, buf.len() as u64, 0, 0, 0, 0))
 }
 
 pub fn sys_yield() {
@@ -0,0 +1,75 @@
+#!/usr/bin/env python3
+"""Writes the partition table images the nyx-core tests read.
+
+Only the sectors the parsers look at are kept, so the images are a few KiB
+rather than whole disks. The layouts follow what sgdisk and fdisk write for
+a 1 GiB disk: a protective MBR and a 128-entry GPT with an ESP and a Linux
+root, and an MBR with a primary FAT32 partition and an extended partition
+holding two logicals. Run from this directory after changing it.
+"""
+import struct
+import uuid
+import zlib
+
+SECTOR = 512
+DISK_SECTORS = 2 * 1024 * 1024  # 1 GiB
+
+LINUX_FS = uuid.UUID("0FC63DAF-8483-4772-8E79-3D69D8477DE4")
+EFI_SYSTEM = uuid.UUID("C12A7328-F81F-11D2-BA4B-00A0C93EC93B")
+
+
+def mbr_entry(type_byte, start, sectors, bootable=False):
+    # CHS fields as fdisk fills them for LBA-only disks
+    return struct.pack("<B3sB3sII", 0x80 if bootable else 0, b"\xfe\xff\xff", type_byte, b"\xfe\xff\xff", start, sectors)
+
+
+def boot_sector(entries):
+    table = b"".join(entries).ljust(64, b"\0")
+    return b"\0" * 446 + table + b"\x55\xaa"
+
+
+def gpt_entry(type_guid, first, last, name):
+    unique = uuid.uuid5(uuid.NAMESPACE_OID, name)
+    encoded = name.encode("utf-16-le").ljust(72, b"\0")
+    return type_guid.bytes_le + unique.bytes_le + struct.pack("<QQQ", first, last, 0) + encoded
+
+
+def gpt_image(block):
+    entries = [
+        gpt_entry(EFI_SYSTEM, 2048, 206847, "EFI System Partition"),
+        gpt_entry(LINUX_FS, 206848, DISK_SECTORS - 34, "nyx-root"),
+    ]
+    array = b"".join(entries).ljust(128 * 128, b"\0")
+    array_blocks = len(array) // block
+    last_lba = DISK_SECTORS * SECTOR // block - 1
+
+    header = struct.pack(
+        "<8sIIIIQQQQ16sQIII",
+        b"EFI PART", 0x00010000, 92, 0, 0,
+        1, last_lba, 2 + array_blocks, last_lba - 1 - array_blocks,
+        uuid.UUID("5B3E0E8D-4CC4-4C3E-9E43-7E0D5A7C9F10").bytes_le,
+        2, 128, 128, zlib.crc32(array),
+    )
+    header = header[:16] + struct.pack("<I", zlib.crc32(header)) + header[20:]
+
+    protective = boot_sector([mbr_entry(0xEE, 1, min(last_lba, 0xFFFFFFFF))]).ljust(block, b"\0")
+    return protective + header.ljust(block, b"\0") + array
+
+
+def mbr_image():
+    extended_start = 4
+    sectors = [b"\0" * SECTOR] * 11
+    sectors[0] = boot_sector([
+        mbr_entry(0x0C, 2048, 204800, bootable=True),
+        mbr_entry(0x05, extended_start, 1000),
+    ])
+    # Each EBR: its logical, relative to itself; the next EBR, relative to the extended partition
+    sectors[4] = boot_sector([mbr_entry(0x83, 2, 4), mbr_entry(0x05, 6, 60)])
+    sectors[10] = boot_sector([mbr_entry(0x82, 2, 50)])
+    return b"".join(sectors)
+
+
+if __name__ == "__main__":
+    with open("gpt.img", "wb") as f: f.write(gpt_image(512))
+    with open("gpt_4k.img", "wb") as f: f.write(gpt_image(4096))
+    with open("mbr.img", "wb") as f: f.write(mbr_image())
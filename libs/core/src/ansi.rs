@@ -0,0 +1,263 @@
+// ==========================================
+// ANSI ESCAPE SEQUENCES
+// ==========================================
+// What the Terminal understands of program output beyond plain text: SGR
+// colors (30-37 and 90-97 foreground, 40-47 and 100-107 background, 0 to
+// reset, 1 for bold, which shows as the bright color), CSI n G to move to a
+// column, and CSI n K to erase part of the line. Anything else that looks
+// like an escape sequence is swallowed whole instead of printed. The parser
+// is fed one char at a time and keeps its state in between, so a sequence
+// split across two writes comes out the same as one written at once.
+
+const ESC: char = '\x1b';
+/// CAN and SUB abandon a sequence half way
+const CAN: char = '\x18';
+const SUB: char = '\x1a';
+const BEL: char = '\x07';
+/// Numbers past this in one sequence are dropped
+const MAX_PARAMS: usize = 16;
+
+/// Colors are indices into the 16-color palette: 0-7 normal, 8-15 bright.
+/// None is the terminal's own color.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Style {
+    pub fg: Option<u8>,
+    pub bg: Option<u8>,
+    pub bold: bool,
+}
+
+impl Style {
+    /// The foreground to draw with: bold turns a normal color bright.
+    pub fn fg_color(&self) -> Option<u8> {
+        self.fg.map(|c| if self.bold && c < 8 { c + 8 } else { c })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Erase {
+    /// From the cursor to the end of the line (CSI 0 K, or just CSI K)
+    ToEnd,
+    /// From the start of the line to the cursor, inclusive (CSI 1 K)
+    ToStart,
+    /// The whole line (CSI 2 K)
+    All,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    /// A char to put at the cursor in the parser's current style
+    Print(char),
+    /// Move to this column of the line, counting from 0 (CSI n G, and '\r')
+    Column(usize),
+    EraseLine(Erase),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    Ground,
+    /// Just had ESC
+    Escape,
+    /// In "ESC [", collecting parameters up to the final byte
+    Csi,
+    /// In "ESC ]" (an OS command, like a window title) until BEL or ESC \
+    Osc,
+    /// Had ESC inside an OS command; a '\' ends it
+    OscEscape,
+}
+
+pub struct Parser {
+    /// What printed chars are drawn in; SGR sequences change it
+    pub style: Style,
+    state: State,
+    params: [u16; MAX_PARAMS],
+    count: usize,
+    // A private marker ('?' and the like) or intermediate byte was seen; none
+    // of those sequences are ours
+    foreign: bool,
+}
+
+impl Default for Parser {
+    fn default() -> Self { Self::new() }
+}
+
+impl Parser {
+    pub const fn new() -> Self {
+        Self { style: Style { fg: None, bg: None, bold: false }, state: State::Ground, params: [0; MAX_PARAMS], count: 0, foreign: false }
+    }
+
+    /// Takes the next char of output; returns what it does once it completes
+    /// something, None while inside a sequence (or for one that only changes
+    /// the style).
+    pub fn feed(&mut self, c: char) -> Option<Action> {
+        match self.state {
+            State::Ground => match c {
+                ESC => { self.state = State::Escape; None },
+                '\r' => Some(Action::Column(0)),
+                c => Some(Action::Print(c)),
+            },
+            State::Escape => {
+                self.state = match c {
+                    '[' => {
+                        self.params = [0; MAX_PARAMS];
+                        self.count = 0;
+                        self.foreign = false;
+                        State::Csi
+                    },
+                    ']' => State::Osc,
+                    ESC => State::Escape,
+                    // Any other two-char sequence (ESC 7, ESC c, ...) ends here
+                    _ => State::Ground,
+                };
+                None
+            },
+            State::Csi => self.csi(c),
+            State::Osc => {
+                self.state = match c {
+                    BEL => State::Ground,
+                    ESC => State::OscEscape,
+                    _ => State::Osc,
+                };
+                None
+            },
+            State::OscEscape => {
+                self.state = if c == '\\' { State::Ground } else { State::Osc };
+                None
+            },
+        }
+    }
+
+    fn csi(&mut self, c: char) -> Option<Action> {
+        match c {
+            '0'..='9' => {
+                if self.count == 0 { self.count = 1; }
+                if let Some(p) = self.params.get_mut(self.count - 1) {
+                    *p = p.saturating_mul(10).saturating_add(c as u16 - '0' as u16);
+                }
+                None
+            },
+            ';' => {
+                // An empty parameter counts as 0
+                self.count = (self.count.max(1) + 1).min(MAX_PARAMS + 1);
+                None
+            },
+            ESC => { self.state = State::Escape; None },
+            CAN | SUB => { self.state = State::Ground; None },
+            // Private markers and intermediate bytes
+            '\x20'..='\x2f' | '<'..='?' | ':' => { self.foreign = true; None },
+            '\x40'..='\x7e' => {
+                self.state = State::Ground;
+                if self.foreign { return None; }
+                self.finish(c)
+            },
+            // Stray control chars inside a sequence are ignored
+            _ => None,
+        }
+    }
+
+    fn param(&self, i: usize) -> u16 {
+        if i < self.count.min(MAX_PARAMS) { self.params[i] } else { 0 }
+    }
+
+    fn finish(&mut self, final_byte: char) -> Option<Action> {
+        match final_byte {
+            'm' => { self.sgr(); None },
+            'G' => Some(Action::Column(self.param(0).max(1) as usize - 1)),
+            'K' => match self.param(0) {
+                0 => Some(Action::EraseLine(Erase::ToEnd)),
+                1 => Some(Action::EraseLine(Erase::ToStart)),
+                2 => Some(Action::EraseLine(Erase::All)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn sgr(&mut self) {
+        // "ESC [ m" is a reset, like "ESC [ 0 m"
+        for i in 0..self.count.clamp(1, MAX_PARAMS) {
+            match self.param(i) {
+                0 => self.style = Style::default(),
+                1 => self.style.bold = true,
+                22 => self.style.bold = false,
+                n @ 30..=37 => self.style.fg = Some((n - 30) as u8),
+                39 => self.style.fg = None,
+                n @ 40..=47 => self.style.bg = Some((n - 40) as u8),
+                49 => self.style.bg = None,
+                n @ 90..=97 => self.style.fg = Some((n - 90 + 8) as u8),
+                n @ 100..=107 => self.style.bg = Some((n - 100 + 8) as u8),
+                _ => {},
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    fn run(parser: &mut Parser, text: &str) -> Vec<Action> {
+        text.chars().filter_map(|c| parser.feed(c)).collect()
+    }
+
+    fn printed(actions: &[Action]) -> alloc::string::String {
+        actions.iter().filter_map(|a| if let Action::Print(c) = a { Some(*c) } else { None }).collect()
+    }
+
+    #[test]
+    fn sets_and_resets_colors() {
+        let mut p = Parser::new();
+        assert_eq!(printed(&run(&mut p, "\x1b[31;42mred")), "red");
+        assert_eq!(p.style, Style { fg: Some(1), bg: Some(2), bold: false });
+        run(&mut p, "\x1b[94;103m");
+        assert_eq!((p.style.fg, p.style.bg), (Some(12), Some(11)));
+        run(&mut p, "\x1b[39;49m");
+        assert_eq!(p.style, Style::default());
+        run(&mut p, "\x1b[35m\x1b[m");
+        assert_eq!(p.style, Style::default());
+    }
+
+    #[test]
+    fn bold_is_bright() {
+        let mut p = Parser::new();
+        run(&mut p, "\x1b[1;32m");
+        assert_eq!(p.style.fg_color(), Some(10));
+        run(&mut p, "\x1b[22m");
+        assert_eq!(p.style.fg_color(), Some(2));
+        run(&mut p, "\x1b[1;93m");
+        assert_eq!(p.style.fg_color(), Some(11));
+    }
+
+    #[test]
+    fn survives_splits_anywhere() {
+        let text = "a\x1b[1;31mb\x1b[5Gc\x1b[2Kd\x1b]0;title\x07e";
+        let mut whole = Parser::new();
+        let expected = run(&mut whole, text);
+        for split in 0..text.len() {
+            let mut p = Parser::new();
+            let mut actions = run(&mut p, &text[..split]);
+            actions.extend(run(&mut p, &text[split..]));
+            assert_eq!(actions, expected, "split at {}", split);
+            assert_eq!(p.style, whole.style);
+        }
+    }
+
+    #[test]
+    fn moves_and_erases() {
+        let mut p = Parser::new();
+        assert_eq!(run(&mut p, "\x1b[12G\x1b[G\r"), [Action::Column(11), Action::Column(0), Action::Column(0)]);
+        assert_eq!(run(&mut p, "\x1b[K\x1b[1K\x1b[2K\x1b[3K"),
+            [Action::EraseLine(Erase::ToEnd), Action::EraseLine(Erase::ToStart), Action::EraseLine(Erase::All)]);
+    }
+
+    #[test]
+    fn swallows_unknown_sequences() {
+        let mut p = Parser::new();
+        let actions = run(&mut p, "x\x1b[?25ly\x1b[2Jz\x1b7w\x1b]2;t\x1b\\v\x1b[4;5Hu");
+        assert_eq!(printed(&actions), "xyzwvu");
+        assert_eq!(p.style, Style::default());
+        // A sequence cut off by a new one starts over
+        assert_eq!(printed(&run(&mut p, "\x1b[3\x1b[32mok")), "ok");
+        assert_eq!(p.style.fg, Some(2));
+    }
+}
@@ -0,0 +1,31 @@
+// ==========================================
+// BLOCK SOURCES
+// ==========================================
+// What the partition parsers read a disk through. The kernel hands them a
+// closure around its NVMe or AHCI driver; tests hand them an image in
+// memory. Blocks are whatever size the buffer passed in is.
+
+pub trait BlockSource {
+    /// Fills `buf` with block `lba`, buf.len() bytes per block. False when the
+    /// read failed or ran off the end of the disk.
+    fn read_block(&mut self, lba: u64, buf: &mut [u8]) -> bool;
+}
+
+impl<F: FnMut(u64, &mut [u8]) -> bool + ?Sized> BlockSource for F {
+    fn read_block(&mut self, lba: u64, buf: &mut [u8]) -> bool { self(lba, buf) }
+}
+
+/// A disk image held in memory.
+pub struct ImageSource<'a> {
+    pub image: &'a [u8],
+}
+
+impl BlockSource for ImageSource<'_> {
+    fn read_block(&mut self, lba: u64, buf: &mut [u8]) -> bool {
+        let start = match (lba as usize).checked_mul(buf.len()) { Some(s) => s, None => return false };
+        match self.image.get(start..start + buf.len()) {
+            Some(block) => { buf.copy_from_slice(block); true },
+            None => false,
+        }
+    }
+}
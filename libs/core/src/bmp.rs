@@ -0,0 +1,210 @@
+// ==========================================
+// BMP IMAGES
+// ==========================================
+// Header checks and pixel layout of uncompressed 24/32-bit BMP files, the
+// one image format the desktop reads (wallpapers, the Image Viewer). Only
+// the header is needed up front; callers then read one row at a time
+// wherever the bytes live, so nothing here holds a whole file.
+
+/// The file header plus a BITMAPINFOHEADER, which is all that is read up front.
+pub const HEADER_LEN: usize = 54;
+/// Largest width or height accepted, checked before anything is allocated.
+pub const MAX_DIM: usize = 8192;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BmpError {
+    /// No "BM" signature, or a header too short to be one
+    NotBmp,
+    /// Compressed, paletted, or some other layout this decoder doesn't do
+    Unsupported,
+    /// Wider or taller than MAX_DIM
+    TooLarge,
+    /// The pixel data runs past the end of the file
+    Truncated,
+    /// The file couldn't be read
+    Io,
+}
+
+impl BmpError {
+    pub fn message(&self) -> &'static str {
+        match self {
+            BmpError::NotBmp => "not a BMP file",
+            BmpError::Unsupported => "only uncompressed 24/32-bit BMPs are supported",
+            BmpError::TooLarge => "image is too large",
+            BmpError::Truncated => "file is truncated",
+            BmpError::Io => "could not read the file",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BmpInfo {
+    pub width: usize,
+    pub height: usize,
+    pub bytes_per_pixel: usize,
+    /// Where the pixel rows start in the file
+    pub data_offset: usize,
+    /// Bytes per row in the file, padded to 4
+    pub row_stride: usize,
+    bottom_up: bool,
+}
+
+fn le_u16(b: &[u8], off: usize) -> u16 { u16::from_le_bytes([b[off], b[off + 1]]) }
+fn le_u32(b: &[u8], off: usize) -> u32 { u32::from_le_bytes([b[off], b[off + 1], b[off + 2], b[off + 3]]) }
+
+impl BmpInfo {
+    /// Checks the first HEADER_LEN bytes of a `file_size` byte file.
+    pub fn parse(header: &[u8], file_size: usize) -> Result<Self, BmpError> {
+        if header.len() < HEADER_LEN || &header[0..2] != b"BM" { return Err(BmpError::NotBmp); }
+
+        let data_offset = le_u32(header, 10) as usize;
+        let width = le_u32(header, 18) as i32;
+        let height = le_u32(header, 22) as i32;
+        let bpp = le_u16(header, 28) as usize;
+        let compression = le_u32(header, 30);
+
+        // BI_RGB, or BI_BITFIELDS with the standard BGRA masks for 32-bit files
+        if bpp != 24 && bpp != 32 { return Err(BmpError::Unsupported); }
+        if compression != 0 && !(compression == 3 && bpp == 32) { return Err(BmpError::Unsupported); }
+        if width <= 0 || height == 0 { return Err(BmpError::NotBmp); }
+
+        let (width, height_abs) = (width as usize, height.unsigned_abs() as usize);
+        if width > MAX_DIM || height_abs > MAX_DIM { return Err(BmpError::TooLarge); }
+
+        let row_stride = (bpp * width).div_ceil(32) * 4;
+        if data_offset < HEADER_LEN || data_offset + row_stride * height_abs > file_size { return Err(BmpError::Truncated); }
+
+        Ok(Self { width, height: height_abs, bytes_per_pixel: bpp / 8, data_offset, row_stride, bottom_up: height > 0 })
+    }
+
+    /// File offset of image row `y`, counted from the top.
+    pub fn row_offset(&self, y: usize) -> usize {
+        let file_row = if self.bottom_up { self.height - 1 - y } else { y };
+        self.data_offset + file_row * self.row_stride
+    }
+
+    /// Pixel `x` of a row read from row_offset, as opaque 0xAARRGGBB.
+    pub fn pixel(&self, row: &[u8], x: usize) -> u32 {
+        let i = x * self.bytes_per_pixel;
+        let (b, g, r) = (row[i] as u32, row[i + 1] as u32, row[i + 2] as u32);
+        0xFF00_0000 | (r << 16) | (g << 8) | b
+    }
+}
+
+/// The largest size with `w`:`h`'s aspect ratio that fits a `box_w` x
+/// `box_h` box, at least 1x1. Smaller images keep their own size unless
+/// `upscale` is set.
+pub fn fit(w: usize, h: usize, box_w: usize, box_h: usize, upscale: bool) -> (usize, usize) {
+    if w == 0 || h == 0 || box_w == 0 || box_h == 0 { return (0, 0); }
+    if !upscale && w <= box_w && h <= box_h { return (w, h); }
+    if w * box_h > h * box_w {
+        (box_w, (h * box_w / w).max(1))
+    } else {
+        ((w * box_h / h).max(1), box_h)
+    }
+}
+
+/// The size a `w` x `h` image is decoded at to stay within `max_pixels`,
+/// keeping its aspect ratio.
+pub fn fit_pixels(w: usize, h: usize, max_pixels: usize) -> (usize, usize) {
+    if w * h <= max_pixels { return (w, h); }
+    // Largest scale s with (w * s) * (h * s) <= max_pixels; start from the
+    // float-free estimate and step down until it fits
+    let mut dw = isqrt(max_pixels * w / h).clamp(1, w);
+    while dw > 1 && dw * (h * dw / w).max(1) > max_pixels { dw -= 1; }
+    (dw, (h * dw / w).max(1))
+}
+
+fn isqrt(n: usize) -> usize {
+    if n < 2 { return n; }
+    let mut x = n;
+    let mut y = x.div_ceil(2);
+    while y < x {
+        x = y;
+        y = (x + n / x) / 2;
+    }
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec::Vec;
+
+    /// A w x h file with the given bits per pixel, height sign and pixel bytes.
+    fn bmp(w: i32, h: i32, bpp: u16, pixels: &[u8]) -> Vec<u8> {
+        let mut file = std::vec![0u8; HEADER_LEN];
+        file[0..2].copy_from_slice(b"BM");
+        file[2..6].copy_from_slice(&((HEADER_LEN + pixels.len()) as u32).to_le_bytes());
+        file[10..14].copy_from_slice(&(HEADER_LEN as u32).to_le_bytes());
+        file[14..18].copy_from_slice(&40u32.to_le_bytes());
+        file[18..22].copy_from_slice(&w.to_le_bytes());
+        file[22..26].copy_from_slice(&h.to_le_bytes());
+        file[26..28].copy_from_slice(&1u16.to_le_bytes());
+        file[28..30].copy_from_slice(&bpp.to_le_bytes());
+        file.extend_from_slice(pixels);
+        file
+    }
+
+    #[test]
+    fn decodes_bottom_up_24_bit_rows() {
+        // 2x2: each row is 6 bytes of BGR padded to 8; the bottom row comes first
+        let pixels = [
+            0x00, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0, 0, // bottom: red, green
+            0xFF, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0, 0, // top: blue, white
+        ];
+        let file = bmp(2, 2, 24, &pixels);
+        let info = BmpInfo::parse(&file, file.len()).unwrap();
+        assert_eq!((info.width, info.height, info.row_stride), (2, 2, 8));
+
+        let row = |y: usize| &file[info.row_offset(y)..info.row_offset(y) + info.row_stride];
+        assert_eq!(info.pixel(row(0), 0), 0xFF_0000FF);
+        assert_eq!(info.pixel(row(0), 1), 0xFF_FFFFFF);
+        assert_eq!(info.pixel(row(1), 0), 0xFF_FF0000);
+        assert_eq!(info.pixel(row(1), 1), 0xFF_00FF00);
+    }
+
+    #[test]
+    fn top_down_32_bit_reads_rows_in_order() {
+        let pixels = [1, 2, 3, 0xFF, 4, 5, 6, 0xFF];
+        let file = bmp(1, -2, 32, &pixels);
+        let info = BmpInfo::parse(&file, file.len()).unwrap();
+        assert_eq!(info.row_offset(0), HEADER_LEN);
+        assert_eq!(info.pixel(&file[info.row_offset(1)..], 0), 0xFF_060504);
+    }
+
+    #[test]
+    fn rejects_bad_files() {
+        let file = bmp(4, 4, 24, &[0; 48]);
+        assert_eq!(BmpInfo::parse(&file[..20], file.len()), Err(BmpError::NotBmp));
+        assert_eq!(BmpInfo::parse(&file, file.len() - 1), Err(BmpError::Truncated));
+        assert_eq!(BmpInfo::parse(&bmp(4, 4, 8, &[0; 16]), 70), Err(BmpError::Unsupported));
+        assert_eq!(BmpInfo::parse(&bmp(9000, 1, 24, &[]), usize::MAX / 2), Err(BmpError::TooLarge));
+        assert_eq!(BmpInfo::parse(&bmp(0, 4, 24, &[]), 54), Err(BmpError::NotBmp));
+
+        let mut gif = file.clone();
+        gif[0..2].copy_from_slice(b"GI");
+        assert_eq!(BmpInfo::parse(&gif, gif.len()), Err(BmpError::NotBmp));
+    }
+
+    #[test]
+    fn fit_letterboxes_and_keeps_aspect() {
+        assert_eq!(fit(1600, 900, 800, 600, false), (800, 450));
+        assert_eq!(fit(600, 1200, 800, 600, false), (300, 600));
+        assert_eq!(fit(100, 50, 800, 600, false), (100, 50));
+        assert_eq!(fit(100, 50, 800, 600, true), (800, 400));
+        assert_eq!(fit(10_000, 1, 100, 100, false), (100, 1));
+        assert_eq!(fit(0, 5, 100, 100, true), (0, 0));
+    }
+
+    #[test]
+    fn fit_pixels_stays_under_budget() {
+        assert_eq!(fit_pixels(640, 480, 1 << 20), (640, 480));
+        for &(w, h) in &[(4000, 3000), (8192, 8192), (8192, 1), (3, 8192)] {
+            let (dw, dh) = fit_pixels(w, h, 1 << 20);
+            assert!(dw * dh <= 1 << 20, "{}x{} -> {}x{}", w, h, dw, dh);
+            assert!(dw >= 1 && dh >= 1 && dw <= w && dh <= h);
+        }
+        assert_eq!(fit_pixels(4000, 3000, 1 << 20).0, 1182);
+    }
+}
@@ -0,0 +1,133 @@
+// ==========================================
+// SHELL COMMAND LINES
+// ==========================================
+// Splits what was typed at the Terminal prompt into the command and where
+// its output goes: the screen, a file (`> file`, `>> file`) or the pager
+// (`| less`, the one pipe there is). A `>` or `|` inside quotes is just
+// text, and a file name may be quoted to hold spaces. The command itself is
+// passed on exactly as typed, quotes included.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Output<'a> {
+    Terminal,
+    File { path: &'a str, append: bool },
+    Pager,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CommandLine<'a> {
+    pub command: &'a str,
+    pub output: Output<'a>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    UnterminatedQuote,
+    /// Nothing before the `>` or `|`
+    MissingCommand,
+    /// Nothing after the `>` or `|`
+    MissingTarget,
+    /// More after the file name of a `>`
+    TrailingText,
+    /// A pipe into anything other than less
+    UnsupportedPipe,
+}
+
+impl ParseError {
+    pub fn message(&self) -> &'static str {
+        match self {
+            ParseError::UnterminatedQuote => "unterminated quote",
+            ParseError::MissingCommand => "missing command before redirection",
+            ParseError::MissingTarget => "missing file name or command after redirection",
+            ParseError::TrailingText => "unexpected text after the file name",
+            ParseError::UnsupportedPipe => "only `| less` is supported",
+        }
+    }
+}
+
+pub fn parse(line: &str) -> Result<CommandLine<'_>, ParseError> {
+    let mut quote = None;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '>' | '|') => {
+                let command = line[..i].trim();
+                if command.is_empty() { return Err(ParseError::MissingCommand); }
+                let rest = &line[i + 1..];
+                let output = if c == '|' {
+                    match rest.trim() {
+                        "" => return Err(ParseError::MissingTarget),
+                        "less" => Output::Pager,
+                        _ => return Err(ParseError::UnsupportedPipe),
+                    }
+                } else {
+                    let (append, rest) = match rest.strip_prefix('>') {
+                        Some(rest) => (true, rest),
+                        None => (false, rest),
+                    };
+                    Output::File { path: file_name(rest)?, append }
+                };
+                return Ok(CommandLine { command, output });
+            }
+            _ => {}
+        }
+    }
+    if quote.is_some() { return Err(ParseError::UnterminatedQuote); }
+    Ok(CommandLine { command: line.trim(), output: Output::Terminal })
+}
+
+/// The one file name in `rest`, without its quotes.
+fn file_name(rest: &str) -> Result<&str, ParseError> {
+    let rest = rest.trim();
+    let (name, after) = match rest.chars().next() {
+        None => return Err(ParseError::MissingTarget),
+        Some(q @ ('"' | '\'')) => match rest[1..].find(q) {
+            Some(end) => (&rest[1..1 + end], &rest[2 + end..]),
+            None => return Err(ParseError::UnterminatedQuote),
+        },
+        Some(_) => match rest.find(|c: char| c.is_whitespace() || matches!(c, '>' | '|' | '"' | '\'')) {
+            Some(end) => (&rest[..end], &rest[end..]),
+            None => (rest, ""),
+        },
+    };
+    if name.is_empty() { return Err(ParseError::MissingTarget); }
+    if !after.trim().is_empty() { return Err(ParseError::TrailingText); }
+    Ok(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output(line: &str) -> Result<Output<'_>, ParseError> { parse(line).map(|l| l.output) }
+
+    #[test]
+    fn splits_off_redirections() {
+        assert_eq!(parse("ls  "), Ok(CommandLine { command: "ls", output: Output::Terminal }));
+        assert_eq!(parse("ls > files.txt"), Ok(CommandLine { command: "ls", output: Output::File { path: "files.txt", append: false } }));
+        assert_eq!(output("echo hi>>log"), Ok(Output::File { path: "log", append: true }));
+        assert_eq!(parse("cat a | less"), Ok(CommandLine { command: "cat a", output: Output::Pager }));
+    }
+
+    #[test]
+    fn quotes_hide_operators_and_spaces() {
+        assert_eq!(parse("echo \"a > b | c\""), Ok(CommandLine { command: "echo \"a > b | c\"", output: Output::Terminal }));
+        assert_eq!(output("ls > \"my files.txt\""), Ok(Output::File { path: "my files.txt", append: false }));
+        assert_eq!(output("ls >> 'x|y'"), Ok(Output::File { path: "x|y", append: true }));
+    }
+
+    #[test]
+    fn reports_bad_lines() {
+        assert_eq!(output("ls >"), Err(ParseError::MissingTarget));
+        assert_eq!(output("ls > \"\""), Err(ParseError::MissingTarget));
+        assert_eq!(output("ls |  "), Err(ParseError::MissingTarget));
+        assert_eq!(output("> out"), Err(ParseError::MissingCommand));
+        assert_eq!(output("ls > a b"), Err(ParseError::TrailingText));
+        assert_eq!(output("ls > a | less"), Err(ParseError::TrailingText));
+        assert_eq!(output("ls | grep x"), Err(ParseError::UnsupportedPipe));
+        assert_eq!(output("echo \"hi"), Err(ParseError::UnterminatedQuote));
+        assert_eq!(output("ls > \"out"), Err(ParseError::UnterminatedQuote));
+    }
+}
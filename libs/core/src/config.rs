@@ -0,0 +1,60 @@
+use alloc::string::String;
+
+// ==========================================
+// BOOT CONFIG
+// ==========================================
+// nyx.cfg is a KvFile on the root disk that the kernel reads at boot and
+// the compositor rewrites when a setting changes. Keys whose values both
+// sides have to agree on are parsed here, so neither can drift from the
+// other.
+
+/// Settings that outlive a reboot, one key=value per line
+pub const CONFIG_PATH: &str = "/mnt/nvme/nyx.cfg";
+
+/// The key the desktop resolution is kept under
+pub const RESOLUTION_KEY: &str = "resolution";
+
+/// (w, h) from a resolution value, "WxH" or "native"; native is (0, 0).
+pub fn parse_resolution(value: &str) -> Option<(usize, usize)> {
+    let value = value.trim();
+    if value == "native" { return Some((0, 0)); }
+    let (w, h) = value.split_once('x')?;
+    Some((w.trim().parse().ok()?, h.trim().parse().ok()?))
+}
+
+/// The value parse_resolution reads back as (w, h); "native" when that is
+/// the screen's own size.
+pub fn resolution_value(w: usize, h: usize, native: (usize, usize)) -> String {
+    if (w, h) == native || (w, h) == (0, 0) { String::from("native") } else { alloc::format!("{}x{}", w, h) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_sizes_and_native() {
+        assert_eq!(parse_resolution("1280x720"), Some((1280, 720)));
+        assert_eq!(parse_resolution(" 800 x 600 "), Some((800, 600)));
+        assert_eq!(parse_resolution("native"), Some((0, 0)));
+    }
+
+    #[test]
+    fn rejects_malformed_values() {
+        assert_eq!(parse_resolution(""), None);
+        assert_eq!(parse_resolution("1280"), None);
+        assert_eq!(parse_resolution("1280x"), None);
+        assert_eq!(parse_resolution("wide"), None);
+        assert_eq!(parse_resolution("-1x600"), None);
+    }
+
+    #[test]
+    fn values_round_trip() {
+        assert_eq!(resolution_value(1024, 768, (1920, 1080)), "1024x768");
+        assert_eq!(resolution_value(1920, 1080, (1920, 1080)), "native");
+        for (w, h) in [(1024, 768), (1920, 1080)] {
+            let back = parse_resolution(&resolution_value(w, h, (1920, 1080))).unwrap();
+            assert_eq!(if back == (0, 0) { (1920, 1080) } else { back }, (w, h));
+        }
+    }
+}
@@ -0,0 +1,209 @@
+use core::fmt;
+
+// ==========================================
+// CALENDAR DATES
+// ==========================================
+// Wall-clock time is kept as Unix seconds, which is what ext4 stores, and
+// only turned into a date to show it. The CMOS clock is read as UTC; there
+// are no time zones. The day arithmetic is the usual proleptic Gregorian
+// days-from-civil conversion, so it needs no tables and no floats.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DateTime {
+    pub year: u32,
+    /// 1-12
+    pub month: u8,
+    /// 1-31
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl DateTime {
+    /// The date `secs` after 1970-01-01 00:00:00.
+    pub fn from_unix(secs: u64) -> Self {
+        let (days, rem) = (secs / 86_400, secs % 86_400);
+        let (year, month, day) = civil_from_days(days as i64);
+        Self {
+            year: year as u32,
+            month,
+            day,
+            hour: (rem / 3600) as u8,
+            minute: (rem / 60 % 60) as u8,
+            second: (rem % 60) as u8,
+        }
+    }
+
+    /// Seconds since 1970-01-01 00:00:00; None before it or if any field
+    /// is out of range.
+    pub fn to_unix(&self) -> Option<u64> {
+        if !self.is_valid() || self.year < 1970 { return None; }
+        let days = days_from_civil(self.year as i64, self.month, self.day) as u64;
+        Some(days * 86_400 + self.hour as u64 * 3600 + self.minute as u64 * 60 + self.second as u64)
+    }
+
+    /// Every field in range, checking the day against the month and leap
+    /// years. A clock with a flat battery reads as anything.
+    pub fn is_valid(&self) -> bool {
+        (1..=12).contains(&self.month)
+            && self.day >= 1 && self.day <= days_in_month(self.year, self.month)
+            && self.hour < 24 && self.minute < 60 && self.second < 60
+    }
+}
+
+/// "YYYY-MM-DD HH:MM", which is as fine as the Explorer and `ls -l` go.
+impl fmt::Display for DateTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02} {:02}:{:02}", self.year, self.month, self.day, self.hour, self.minute)
+    }
+}
+
+pub fn is_leap_year(year: u32) -> bool {
+    year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400))
+}
+
+pub fn days_in_month(year: u32, month: u8) -> u8 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// ==========================================
+// CMOS RTC REGISTERS
+// ==========================================
+// The clock chip's time and date registers as read, and how to decode them:
+// status register B says whether they're BCD or binary and whether the hour
+// is 12- or 24-hour. In 12-hour mode bit 7 of the hour is PM, and 12 AM is
+// midnight. The year register is two digits; the century register isn't
+// reliable across firmware, so years are taken to be 20xx.
+
+/// Status register B: hours count 0-23 rather than 1-12 with a PM bit
+pub const RTC_24_HOUR: u8 = 0x02;
+/// Status register B: registers are binary rather than BCD
+pub const RTC_BINARY: u8 = 0x04;
+const RTC_PM: u8 = 0x80;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RtcRegisters {
+    pub second: u8,
+    pub minute: u8,
+    pub hour: u8,
+    pub day: u8,
+    pub month: u8,
+    pub year: u8,
+}
+
+impl RtcRegisters {
+    /// The date the registers hold, given status register B. Not checked;
+    /// see is_valid().
+    pub fn decode(&self, status_b: u8) -> DateTime {
+        let num = |v: u8| if status_b & RTC_BINARY != 0 { v } else { bcd_to_binary(v) };
+        let mut hour = num(self.hour & !RTC_PM);
+        if status_b & RTC_24_HOUR == 0 {
+            let pm = self.hour & RTC_PM != 0;
+            hour = match (hour, pm) {
+                (12, false) => 0,
+                (12, true) => 12,
+                (h, true) => h + 12,
+                (h, false) => h,
+            };
+        }
+        DateTime {
+            year: 2000 + num(self.year) as u32,
+            month: num(self.month),
+            day: num(self.day),
+            hour,
+            minute: num(self.minute),
+            second: num(self.second),
+        }
+    }
+}
+
+pub fn bcd_to_binary(v: u8) -> u8 { (v >> 4) * 10 + (v & 0x0F) }
+
+// Years are counted from March, so the leap day is the last day of a year
+// and every "year" starts with the same run of month lengths
+fn days_from_civil(year: i64, month: u8, day: u8) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let m = month as i64;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+fn civil_from_days(days: i64) -> (i64, u8, u8) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u8;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u8;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::string::ToString;
+
+    fn date(year: u32, month: u8, day: u8, hour: u8, minute: u8, second: u8) -> DateTime {
+        DateTime { year, month, day, hour, minute, second }
+    }
+
+    #[test]
+    fn converts_known_dates() {
+        assert_eq!(DateTime::from_unix(0), date(1970, 1, 1, 0, 0, 0));
+        assert_eq!(date(2024, 2, 29, 12, 34, 56).to_unix(), Some(1_709_210_096));
+        assert_eq!(DateTime::from_unix(946_684_799), date(1999, 12, 31, 23, 59, 59));
+        assert_eq!(DateTime::from_unix(4_107_542_400), date(2100, 3, 1, 0, 0, 0));
+    }
+
+    #[test]
+    fn round_trips_across_midnights_and_leap_days() {
+        let mut secs = 951_782_000;
+        while secs < 951_782_000 + 3 * 86_400 {
+            assert_eq!(DateTime::from_unix(secs).to_unix(), Some(secs));
+            secs += 599;
+        }
+        // 2000-02-29 23:59:59 rolls over to March, not Feb 30
+        assert_eq!(DateTime::from_unix(951_868_799 + 1), date(2000, 3, 1, 0, 0, 0));
+    }
+
+    #[test]
+    fn rejects_impossible_dates() {
+        assert!(!date(2023, 2, 29, 0, 0, 0).is_valid());
+        assert!(!date(2100, 2, 29, 0, 0, 0).is_valid());
+        assert!(date(2000, 2, 29, 0, 0, 0).is_valid());
+        assert_eq!(date(2024, 13, 1, 0, 0, 0).to_unix(), None);
+        assert_eq!(date(2024, 4, 31, 0, 0, 0).to_unix(), None);
+        assert_eq!(date(2024, 1, 1, 24, 0, 0).to_unix(), None);
+        assert_eq!(date(1969, 12, 31, 0, 0, 0).to_unix(), None);
+    }
+
+    #[test]
+    fn decodes_bcd_and_12_hour_registers() {
+        let regs = RtcRegisters { second: 0x59, minute: 0x30, hour: 0x12, day: 0x31, month: 0x12, year: 0x24 };
+        assert_eq!(regs.decode(RTC_24_HOUR), date(2024, 12, 31, 12, 30, 59));
+        // 12 AM is midnight, 12 PM is noon, 11 PM is 23
+        assert_eq!(regs.decode(0).hour, 0);
+        assert_eq!(RtcRegisters { hour: 0x92, ..regs }.decode(0).hour, 12);
+        assert_eq!(RtcRegisters { hour: 0x91, ..regs }.decode(0).hour, 23);
+
+        let binary = RtcRegisters { second: 59, minute: 30, hour: 0x80 | 7, day: 31, month: 12, year: 24 };
+        assert_eq!(binary.decode(RTC_BINARY), date(2024, 12, 31, 19, 30, 59));
+    }
+
+    #[test]
+    fn formats_to_the_minute() {
+        assert_eq!(date(2024, 3, 5, 7, 8, 59).to_string(), "2024-03-05 07:08");
+    }
+}
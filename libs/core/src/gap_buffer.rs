@@ -0,0 +1,241 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use alloc::vec;
+
+// ==========================================
+// GAP BUFFER
+// ==========================================
+// Text lives in one Vec with a movable hole at the caret, so typing and
+// deleting at the same spot only shifts bytes when the caret jumps.
+// Positions are byte offsets into the logical (gap-free) UTF-8 text; only
+// whole chars are ever inserted, so the text stays valid UTF-8 as long as
+// deletes start and end on char boundaries.
+const MIN_GAP: usize = 1024;
+
+pub struct GapBuffer {
+    buf: Vec<u8>,
+    gap_start: usize,
+    gap_end: usize,
+    // '\n' bytes in the text, kept up to date so line_count doesn't scan
+    newlines: usize,
+}
+
+impl GapBuffer {
+    pub fn new() -> Self {
+        Self { buf: vec![0; MIN_GAP], gap_start: 0, gap_end: MIN_GAP, newlines: 0 }
+    }
+
+    pub fn from_bytes(mut data: Vec<u8>) -> Self {
+        let len = data.len();
+        let newlines = data.iter().filter(|&&c| c == b'\n').count();
+        data.resize(len + MIN_GAP, 0);
+        Self { buf: data, gap_start: len, gap_end: len + MIN_GAP, newlines }
+    }
+
+    pub fn len(&self) -> usize { self.buf.len() - (self.gap_end - self.gap_start) }
+
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+    pub fn byte_at(&self, pos: usize) -> u8 {
+        if pos < self.gap_start { self.buf[pos] } else { self.buf[pos + (self.gap_end - self.gap_start)] }
+    }
+
+    /// The text before and after the gap. Together they form the whole document.
+    pub fn as_slices(&self) -> (&[u8], &[u8]) {
+        (&self.buf[..self.gap_start], &self.buf[self.gap_end..])
+    }
+
+    /// Copies out the bytes `start..end`.
+    pub fn text(&self, start: usize, end: usize) -> String {
+        let end = end.min(self.len());
+        let bytes: Vec<u8> = (start.min(end)..end).map(|i| self.byte_at(i)).collect();
+        String::from_utf8(bytes).unwrap_or_else(|e| String::from_utf8_lossy(e.as_bytes()).into_owned())
+    }
+
+    /// The chars in `start..end` with the byte offset of each.
+    pub fn chars(&self, start: usize, end: usize) -> Chars<'_> {
+        Chars { buf: self, pos: start, end: end.min(self.len()) }
+    }
+
+    pub fn line_count(&self) -> usize { 1 + self.newlines }
+
+    /// Offset of the first byte of the line holding `pos`.
+    pub fn line_start(&self, pos: usize) -> usize {
+        let mut i = pos.min(self.len());
+        while i > 0 && self.byte_at(i - 1) != b'\n' { i -= 1; }
+        i
+    }
+
+    /// Offset of the '\n' ending the line holding `pos`, or the length on the last line.
+    pub fn line_end(&self, pos: usize) -> usize {
+        let mut i = pos.min(self.len());
+        while i < self.len() && self.byte_at(i) != b'\n' { i += 1; }
+        i
+    }
+
+    fn move_gap(&mut self, pos: usize) {
+        if pos < self.gap_start {
+            let n = self.gap_start - pos;
+            self.buf.copy_within(pos..self.gap_start, self.gap_end - n);
+            self.gap_start -= n;
+            self.gap_end -= n;
+        } else if pos > self.gap_start {
+            let n = pos - self.gap_start;
+            self.buf.copy_within(self.gap_end..self.gap_end + n, self.gap_start);
+            self.gap_start += n;
+            self.gap_end += n;
+        }
+    }
+
+    fn ensure_gap(&mut self, needed: usize) {
+        let gap = self.gap_end - self.gap_start;
+        if gap >= needed { return; }
+
+        // Double the buffer so repeated inserts stay amortized O(1)
+        let extra = (self.buf.len().max(MIN_GAP)).max(needed - gap);
+        let tail_len = self.buf.len() - self.gap_end;
+        self.buf.resize(self.buf.len() + extra, 0);
+        let new_gap_end = self.buf.len() - tail_len;
+        self.buf.copy_within(self.gap_end..self.gap_end + tail_len, new_gap_end);
+        self.gap_end = new_gap_end;
+    }
+
+    pub fn insert_str(&mut self, pos: usize, s: &str) {
+        let pos = pos.min(self.len());
+        self.move_gap(pos);
+        self.ensure_gap(s.len());
+        self.buf[self.gap_start..self.gap_start + s.len()].copy_from_slice(s.as_bytes());
+        self.gap_start += s.len();
+        self.newlines += s.bytes().filter(|&c| c == b'\n').count();
+    }
+
+    pub fn insert_char(&mut self, pos: usize, c: char) {
+        let mut tmp = [0u8; 4];
+        self.insert_str(pos, c.encode_utf8(&mut tmp));
+    }
+
+    /// Removes `count` bytes starting at `pos`.
+    pub fn delete(&mut self, pos: usize, count: usize) {
+        let pos = pos.min(self.len());
+        let count = count.min(self.len() - pos);
+        self.move_gap(pos);
+        self.newlines -= self.buf[self.gap_end..self.gap_end + count].iter().filter(|&&c| c == b'\n').count();
+        self.gap_end += count;
+    }
+
+    /// Byte length of the char ending at `pos` (0 at the start of the text).
+    pub fn prev_char_len(&self, pos: usize) -> usize {
+        let mut n = 0;
+        while n < pos && n < 4 {
+            n += 1;
+            if self.byte_at(pos - n) & 0xC0 != 0x80 { return n; }
+        }
+        n
+    }
+
+    /// Byte length of the char starting at `pos` (0 at the end of the text).
+    pub fn next_char_len(&self, pos: usize) -> usize {
+        if pos >= self.len() { return 0; }
+        let mut n = 1;
+        while pos + n < self.len() && n < 4 && self.byte_at(pos + n) & 0xC0 == 0x80 { n += 1; }
+        n
+    }
+}
+
+impl Default for GapBuffer {
+    fn default() -> Self { Self::new() }
+}
+
+/// Iterator from GapBuffer::chars; decodes across the gap.
+pub struct Chars<'a> {
+    buf: &'a GapBuffer,
+    pos: usize,
+    end: usize,
+}
+
+impl Iterator for Chars<'_> {
+    type Item = (usize, char);
+
+    fn next(&mut self) -> Option<(usize, char)> {
+        if self.pos >= self.end { return None; }
+        let n = self.buf.next_char_len(self.pos);
+        let mut bytes = [0u8; 4];
+        for (i, b) in bytes.iter_mut().enumerate().take(n) { *b = self.buf.byte_at(self.pos + i); }
+        let c = core::str::from_utf8(&bytes[..n]).ok().and_then(|s| s.chars().next()).unwrap_or('\u{FFFD}');
+        let at = self.pos;
+        self.pos += n;
+        Some((at, c))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    fn contents(buf: &GapBuffer) -> String { buf.text(0, buf.len()) }
+
+    #[test]
+    fn inserts_and_deletes_anywhere() {
+        let mut buf = GapBuffer::new();
+        buf.insert_str(0, "hello world");
+        buf.insert_str(5, ",");
+        buf.insert_char(0, '>');
+        assert_eq!(contents(&buf), ">hello, world");
+        buf.delete(1, 7);
+        assert_eq!(contents(&buf), ">world");
+        buf.delete(4, 100);
+        assert_eq!(contents(&buf), ">wor");
+        assert_eq!(buf.len(), 4);
+    }
+
+    #[test]
+    fn grows_past_the_initial_gap() {
+        let mut buf = GapBuffer::from_bytes(b"tail".to_vec());
+        let long = "x".repeat(3 * MIN_GAP);
+        buf.insert_str(0, &long);
+        assert_eq!(buf.len(), long.len() + 4);
+        assert_eq!(contents(&buf), long + "tail");
+    }
+
+    #[test]
+    fn tracks_line_count() {
+        let mut buf = GapBuffer::from_bytes(b"a\nb\nc".to_vec());
+        assert_eq!(buf.line_count(), 3);
+        buf.insert_str(1, "\n\n");
+        assert_eq!(buf.line_count(), 5);
+        buf.delete(0, 4);
+        assert_eq!(contents(&buf), "b\nc");
+        assert_eq!(buf.line_count(), 2);
+    }
+
+    #[test]
+    fn finds_line_bounds() {
+        let buf = GapBuffer::from_bytes(b"one\ntwo\n\nthree".to_vec());
+        assert_eq!((buf.line_start(5), buf.line_end(5)), (4, 7));
+        assert_eq!((buf.line_start(8), buf.line_end(8)), (8, 8));
+        assert_eq!((buf.line_start(0), buf.line_end(0)), (0, 3));
+        assert_eq!(buf.line_end(12), 14);
+    }
+
+    #[test]
+    fn steps_over_whole_chars() {
+        let mut buf = GapBuffer::new();
+        buf.insert_str(0, "aé€");
+        assert_eq!(buf.next_char_len(1), 2);
+        assert_eq!(buf.prev_char_len(buf.len()), 3);
+        assert_eq!(buf.prev_char_len(0), 0);
+        assert_eq!(buf.next_char_len(buf.len()), 0);
+    }
+
+    #[test]
+    fn chars_decode_across_the_gap() {
+        let mut buf = GapBuffer::from_bytes("äb€".as_bytes().to_vec());
+        // Put the gap in the middle of the text
+        buf.insert_str(2, "x");
+        buf.delete(2, 1);
+        let chars: Vec<(usize, char)> = buf.chars(0, buf.len()).collect();
+        assert_eq!(chars, [(0, 'ä'), (2, 'b'), (3, '€')]);
+        assert_eq!(buf.chars(2, 3).map(|(_, c)| c).collect::<String>(), "b".to_string());
+    }
+}
@@ -0,0 +1,328 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::convert::TryInto;
+use crate::block::BlockSource;
+
+// ==========================================
+// GUID PARTITION TABLE
+// ==========================================
+// Reads the primary GPT through a BlockSource, so it works the same on an
+// NVMe namespace, a SATA disk and an image in a test. Both CRCs are checked; a
+// table that fails either is rejected rather than half-trusted. build_table
+// goes the other way, for tools that make disk images on the host.
+
+pub const LINUX_FS_GUID: [u8; 16] = [
+    0xAF, 0x3D, 0xC6, 0x0F, 0x83, 0x84, 0x72, 0x47,
+    0x8E, 0x79, 0x3D, 0x69, 0xD8, 0x47, 0x7D, 0xE4,
+];
+pub const BASIC_DATA_GUID: [u8; 16] = [
+    0xA2, 0xA0, 0xD0, 0xEB, 0xE5, 0xB9, 0x33, 0x44,
+    0x87, 0xC0, 0x68, 0xB6, 0xB7, 0x26, 0x99, 0xC7,
+];
+pub const EFI_SYSTEM_GUID: [u8; 16] = [
+    0x28, 0x73, 0x2A, 0xC1, 0x1F, 0xF8, 0xD2, 0x11,
+    0xBA, 0x4B, 0x00, 0xA0, 0xC9, 0x3E, 0xC9, 0x3B,
+];
+
+// Sanity limits before we allocate anything based on the header
+const MAX_ENTRIES: u32 = 1024;
+const MAX_ENTRY_SIZE: u32 = 4096;
+// What build_table writes: the usual 128 entries of 128 bytes
+const BUILD_ENTRIES: usize = 128;
+const BUILD_ENTRY_SIZE: usize = 128;
+const HEADER_SIZE: usize = 92;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GptError {
+    ReadFailed,
+    NoSignature,
+    BadHeaderCrc,
+    BadHeader,
+    BadEntriesCrc,
+}
+
+#[derive(Debug, Clone)]
+pub struct GptPartition {
+    pub type_guid: [u8; 16],
+    pub first_lba: u64,
+    pub last_lba: u64,
+    pub name: String,
+}
+
+impl GptPartition {
+    pub fn type_name(&self) -> &'static str {
+        match self.type_guid {
+            LINUX_FS_GUID => "Linux filesystem",
+            BASIC_DATA_GUID => "Basic data",
+            EFI_SYSTEM_GUID => "EFI System",
+            _ => "Unknown",
+        }
+    }
+
+    /// Lower is tried first when picking a partition to mount. The ESP goes last so
+    /// a data partition listed after it still wins.
+    pub fn mount_priority(&self) -> u8 {
+        match self.type_guid {
+            LINUX_FS_GUID => 0,
+            BASIC_DATA_GUID => 1,
+            EFI_SYSTEM_GUID => 3,
+            _ => 2,
+        }
+    }
+}
+
+/// Standard CRC-32 (IEEE 802.3, reflected, as used by GPT).
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn le_u32(b: &[u8], off: usize) -> u32 { u32::from_le_bytes(b[off..off + 4].try_into().unwrap()) }
+fn le_u64(b: &[u8], off: usize) -> u64 { u64::from_le_bytes(b[off..off + 8].try_into().unwrap()) }
+
+/// Reads and validates the primary GPT of a disk with `block_size`-byte blocks and
+/// returns every used entry.
+pub fn read_partitions<D: BlockSource + ?Sized>(disk: &mut D, block_size: usize) -> Result<Vec<GptPartition>, GptError> {
+    let mut header = alloc::vec![0u8; block_size];
+    if !disk.read_block(1, &mut header) { return Err(GptError::ReadFailed); }
+    if &header[0..8] != b"EFI PART" { return Err(GptError::NoSignature); }
+
+    // The header CRC covers header_size bytes with its own field zeroed
+    let header_size = le_u32(&header, 12) as usize;
+    if header_size < 92 || header_size > block_size { return Err(GptError::BadHeader); }
+    let stored_crc = le_u32(&header, 16);
+    let mut crc_copy = header[..header_size].to_vec();
+    crc_copy[16..20].fill(0);
+    if crc32(&crc_copy) != stored_crc { return Err(GptError::BadHeaderCrc); }
+
+    let entries_lba = le_u64(&header, 72);
+    let entry_count = le_u32(&header, 80);
+    let entry_size = le_u32(&header, 84);
+    let entries_crc = le_u32(&header, 88);
+    if entry_count > MAX_ENTRIES || !(128..=MAX_ENTRY_SIZE).contains(&entry_size) || !entry_size.is_multiple_of(8) {
+        return Err(GptError::BadHeader);
+    }
+
+    // The array may span any number of blocks; read it whole so the CRC can cover it
+    let array_bytes = entry_count as usize * entry_size as usize;
+    let blocks = array_bytes.div_ceil(block_size);
+    let mut array = alloc::vec![0u8; blocks * block_size];
+    for i in 0..blocks {
+        if !disk.read_block(entries_lba + i as u64, &mut array[i * block_size..(i + 1) * block_size]) {
+            return Err(GptError::ReadFailed);
+        }
+    }
+    if crc32(&array[..array_bytes]) != entries_crc { return Err(GptError::BadEntriesCrc); }
+
+    let mut partitions = Vec::new();
+    for entry in array[..array_bytes].chunks_exact(entry_size as usize) {
+        let type_guid: [u8; 16] = entry[0..16].try_into().unwrap();
+        if type_guid == [0; 16] { continue; }
+
+        // Name is 36 UTF-16LE code units, NUL padded
+        let units = (0..36).map(|i| u16::from_le_bytes([entry[56 + i * 2], entry[57 + i * 2]])).take_while(|&u| u != 0);
+        let name = char::decode_utf16(units).map(|c| c.unwrap_or('?')).collect();
+
+        partitions.push(GptPartition { type_guid, first_lba: le_u64(entry, 32), last_lba: le_u64(entry, 40), name });
+    }
+    Ok(partitions)
+}
+
+fn entry_array_blocks(block_size: usize) -> u64 {
+    (BUILD_ENTRIES * BUILD_ENTRY_SIZE).div_ceil(block_size) as u64
+}
+
+/// First block a partition may start at in a table from build_table.
+pub fn first_usable_lba(block_size: usize) -> u64 { 2 + entry_array_blocks(block_size) }
+
+/// Last block a partition may end at, before the backup table.
+pub fn last_usable_lba(disk_blocks: u64, block_size: usize) -> u64 {
+    disk_blocks.saturating_sub(2 + entry_array_blocks(block_size))
+}
+
+/// The blocks a fresh GPT occupies on a disk of `disk_blocks` blocks: the
+/// protective MBR, the primary header and array, and the backup array and
+/// header at the end, as (first lba, bytes) pairs to write. Each partition's
+/// own GUID is the disk GUID with its last byte changed, which is unique
+/// enough within one disk. At most 128 partitions; the caller keeps them
+/// between first_usable_lba and last_usable_lba.
+pub fn build_table(disk_blocks: u64, block_size: usize, disk_guid: [u8; 16], partitions: &[GptPartition]) -> Vec<(u64, Vec<u8>)> {
+    let mut array = alloc::vec![0u8; entry_array_blocks(block_size) as usize * block_size];
+    for (i, (part, entry)) in partitions.iter().zip(array.as_chunks_mut::<BUILD_ENTRY_SIZE>().0).enumerate() {
+        let mut unique = disk_guid;
+        unique[15] ^= i as u8 + 1;
+        entry[0..16].copy_from_slice(&part.type_guid);
+        entry[16..32].copy_from_slice(&unique);
+        entry[32..40].copy_from_slice(&part.first_lba.to_le_bytes());
+        entry[40..48].copy_from_slice(&part.last_lba.to_le_bytes());
+        for (j, unit) in part.name.encode_utf16().take(36).enumerate() {
+            entry[56 + j * 2..58 + j * 2].copy_from_slice(&unit.to_le_bytes());
+        }
+    }
+    let entries_crc = crc32(&array[..BUILD_ENTRIES * BUILD_ENTRY_SIZE]);
+
+    let last_lba = disk_blocks - 1;
+    let backup_array_lba = last_lba - entry_array_blocks(block_size);
+    let header = |own_lba: u64, other_lba: u64, array_lba: u64| {
+        let mut h = alloc::vec![0u8; block_size];
+        h[0..8].copy_from_slice(b"EFI PART");
+        h[8..12].copy_from_slice(&0x0001_0000u32.to_le_bytes());
+        h[12..16].copy_from_slice(&(HEADER_SIZE as u32).to_le_bytes());
+        h[24..32].copy_from_slice(&own_lba.to_le_bytes());
+        h[32..40].copy_from_slice(&other_lba.to_le_bytes());
+        h[40..48].copy_from_slice(&first_usable_lba(block_size).to_le_bytes());
+        h[48..56].copy_from_slice(&last_usable_lba(disk_blocks, block_size).to_le_bytes());
+        h[56..72].copy_from_slice(&disk_guid);
+        h[72..80].copy_from_slice(&array_lba.to_le_bytes());
+        h[80..84].copy_from_slice(&(BUILD_ENTRIES as u32).to_le_bytes());
+        h[84..88].copy_from_slice(&(BUILD_ENTRY_SIZE as u32).to_le_bytes());
+        h[88..92].copy_from_slice(&entries_crc.to_le_bytes());
+        let crc = crc32(&h[..HEADER_SIZE]);
+        h[16..20].copy_from_slice(&crc.to_le_bytes());
+        h
+    };
+
+    // One 0xEE partition over the whole disk keeps MBR-only tools off it
+    let mut mbr = alloc::vec![0u8; block_size];
+    let covered = (disk_blocks - 1).min(u32::MAX as u64) as u32;
+    mbr[446..462].copy_from_slice(&[0, 0x00, 0x02, 0x00, 0xEE, 0xFF, 0xFF, 0xFF, 1, 0, 0, 0, 0, 0, 0, 0]);
+    mbr[458..462].copy_from_slice(&covered.to_le_bytes());
+    mbr[510] = 0x55;
+    mbr[511] = 0xAA;
+
+    alloc::vec![
+        (0, mbr),
+        (1, header(1, last_lba, 2)),
+        (2, array.clone()),
+        (backup_array_lba, array),
+        (last_lba, header(last_lba, 1, backup_array_lba)),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::ImageSource;
+    use std::vec::Vec;
+
+    const IMAGE: &[u8] = include_bytes!("../fixtures/gpt.img");
+    const IMAGE_4K: &[u8] = include_bytes!("../fixtures/gpt_4k.img");
+
+    fn read(image: &[u8], block_size: usize) -> Result<Vec<GptPartition>, GptError> {
+        read_partitions(&mut ImageSource { image }, block_size)
+    }
+
+    #[test]
+    fn crc32_matches_the_ieee_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn reads_both_entries() {
+        let parts = read(IMAGE, 512).unwrap();
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].type_guid, EFI_SYSTEM_GUID);
+        assert_eq!(parts[0].name, "EFI System Partition");
+        assert_eq!((parts[0].first_lba, parts[0].last_lba), (2048, 206847));
+        assert_eq!(parts[1].type_name(), "Linux filesystem");
+        assert_eq!(parts[1].name, "nyx-root");
+        assert_eq!(parts[1].first_lba, 206848);
+    }
+
+    #[test]
+    fn reads_4k_sector_disks() {
+        let parts = read(IMAGE_4K, 4096).unwrap();
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[1].name, "nyx-root");
+    }
+
+    #[test]
+    fn the_root_partition_is_mounted_before_the_esp() {
+        let mut parts = read(IMAGE, 512).unwrap();
+        parts.sort_by_key(|p| p.mount_priority());
+        assert_eq!(parts[0].type_guid, LINUX_FS_GUID);
+    }
+
+    #[test]
+    fn a_corrupted_header_is_rejected() {
+        let mut image = IMAGE.to_vec();
+        image[512 + 40] ^= 1; // backup LBA
+        assert_eq!(read(&image, 512).unwrap_err(), GptError::BadHeaderCrc);
+    }
+
+    #[test]
+    fn a_corrupted_entry_array_is_rejected() {
+        let mut image = IMAGE.to_vec();
+        image[1024 + 56] ^= 1; // first entry's name
+        assert_eq!(read(&image, 512).unwrap_err(), GptError::BadEntriesCrc);
+    }
+
+    #[test]
+    fn missing_signature_and_short_disks() {
+        assert_eq!(read(&[0u8; 4096], 512).unwrap_err(), GptError::NoSignature);
+        assert_eq!(read(&IMAGE[..512], 512).unwrap_err(), GptError::ReadFailed);
+        // Header intact, entry array cut off
+        assert_eq!(read(&IMAGE[..2048], 512).unwrap_err(), GptError::ReadFailed);
+    }
+
+    #[test]
+    fn reads_through_a_closure() {
+        let mut reads = 0;
+        let mut disk = |lba: u64, buf: &mut [u8]| {
+            reads += 1;
+            ImageSource { image: IMAGE }.read_block(lba, buf)
+        };
+        assert_eq!(read_partitions(&mut disk, 512).unwrap().len(), 2);
+        // The header plus 32 blocks of entries
+        assert_eq!(reads, 33);
+    }
+
+    fn built_image(disk_blocks: u64, block_size: usize, parts: &[GptPartition]) -> Vec<u8> {
+        let mut image = std::vec![0u8; disk_blocks as usize * block_size];
+        for (lba, bytes) in build_table(disk_blocks, block_size, [0x42; 16], parts) {
+            let start = lba as usize * block_size;
+            image[start..start + bytes.len()].copy_from_slice(&bytes);
+        }
+        image
+    }
+
+    #[test]
+    fn a_built_table_reads_back() {
+        let disk_blocks = 64 * 2048; // 64 MiB
+        let data = GptPartition {
+            type_guid: BASIC_DATA_GUID,
+            first_lba: 2048,
+            last_lba: last_usable_lba(disk_blocks, 512),
+            name: String::from("NYX-DATA"),
+        };
+        let image = built_image(disk_blocks, 512, core::slice::from_ref(&data));
+        assert_eq!(&image[510..512], &[0x55, 0xAA]);
+        assert_eq!(image[446 + 4], 0xEE);
+
+        let parts = read(&image, 512).unwrap();
+        assert_eq!(parts.len(), 1);
+        assert_eq!((parts[0].first_lba, parts[0].last_lba), (data.first_lba, data.last_lba));
+        assert_eq!(parts[0].name, "NYX-DATA");
+        assert_eq!(parts[0].type_name(), "Basic data");
+    }
+
+    #[test]
+    fn a_built_table_has_a_valid_backup() {
+        let disk_blocks = 4096;
+        let image = built_image(disk_blocks, 4096, &[]);
+        // The backup header points back at the primary, and its array holds the same CRC
+        let backup = &image[(disk_blocks as usize - 1) * 4096..];
+        assert_eq!(&backup[0..8], b"EFI PART");
+        assert_eq!(le_u64(backup, 32), 1);
+        assert_eq!(le_u32(backup, 88), le_u32(&image[4096..], 88));
+        assert_eq!(first_usable_lba(4096), 6);
+        assert!(read(&image, 4096).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,115 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+// ==========================================
+// KEY=VALUE FILES
+// ==========================================
+// The format of nyx.cfg and the desktop state file: one `key=value` per
+// line, blanks and '#' comments skipped, whitespace around either side
+// trimmed. Anything else is ignored rather than rejected, so a file cut
+// short by a crash mid-write loses at most its last line.
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct KvFile {
+    // In file order, so rewriting a file keeps the lines where they were
+    entries: Vec<(String, String)>,
+}
+
+impl KvFile {
+    pub fn new() -> Self { Self { entries: Vec::new() } }
+
+    /// Reads every well-formed line of `text`; for a key given twice the
+    /// last one wins.
+    pub fn parse(text: &str) -> Self {
+        let mut file = Self::new();
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') { continue; }
+            let Some((key, value)) = line.split_once('=') else { continue };
+            let key = key.trim();
+            if key.is_empty() { continue; }
+            file.set(key, value.trim());
+        }
+        file
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+
+    /// The value parsed as a T; None when it's missing or doesn't parse.
+    pub fn get_parsed<T: core::str::FromStr>(&self, key: &str) -> Option<T> {
+        self.get(key)?.parse().ok()
+    }
+
+    /// Replaces the value of `key`, or adds it at the end. Line breaks in
+    /// the value would start a new line, so they become spaces.
+    pub fn set(&mut self, key: &str, value: &str) {
+        let value: String = value.chars().map(|c| if c == '\n' || c == '\r' { ' ' } else { c }).collect();
+        match self.entries.iter_mut().find(|(k, _)| k == key) {
+            Some(entry) => entry.1 = value,
+            None => self.entries.push((String::from(key), value)),
+        }
+    }
+
+    pub fn remove(&mut self, key: &str) {
+        self.entries.retain(|(k, _)| k != key);
+    }
+
+    /// Drops every key starting with `prefix`, e.g. all of "window.".
+    pub fn remove_prefix(&mut self, prefix: &str) {
+        self.entries.retain(|(k, _)| !k.starts_with(prefix));
+    }
+
+    pub fn is_empty(&self) -> bool { self.entries.is_empty() }
+
+    pub fn clear(&mut self) { self.entries.clear(); }
+
+    /// The file's text, each line ending in '\n'.
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for (key, value) in &self.entries {
+            text.push_str(key);
+            text.push('=');
+            text.push_str(value);
+            text.push('\n');
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_skips_comments_and_junk() {
+        let file = KvFile::parse("# state\n\n x = 10 \nno equals sign\n=orphan\nname=a=b\n");
+        assert_eq!(file.get("x"), Some("10"));
+        assert_eq!(file.get("name"), Some("a=b"));
+        assert_eq!(file.get_parsed::<usize>("x"), Some(10));
+        assert_eq!(file.get_parsed::<usize>("name"), None);
+        assert_eq!(file.to_text(), "x=10\nname=a=b\n");
+    }
+
+    #[test]
+    fn truncated_file_keeps_complete_lines() {
+        let file = KvFile::parse("window.0.x=40\nwindow.0.y=60\nwindow.0.w");
+        assert_eq!(file.get_parsed::<usize>("window.0.y"), Some(60));
+        assert_eq!(file.get("window.0.w"), None);
+    }
+
+    #[test]
+    fn set_replaces_in_place_and_round_trips() {
+        let mut file = KvFile::parse("a=1\nb=2\na=3\n");
+        assert_eq!(file.get("a"), Some("3"));
+        file.set("b", "two\nlines");
+        file.set("c", "");
+        assert_eq!(file.to_text(), "a=3\nb=two lines\nc=\n");
+        assert_eq!(KvFile::parse(&file.to_text()), file);
+
+        file.remove_prefix("b");
+        file.remove("a");
+        assert_eq!(file.to_text(), "c=\n");
+    }
+}
@@ -0,0 +1,23 @@
+#![no_std]
+extern crate alloc;
+// Host tests (`cargo test -p nyx-core`) get std; the OS never does
+#[cfg(test)]
+extern crate std;
+
+pub mod ansi;
+pub mod block;
+pub mod bmp;
+pub mod cmdline;
+pub mod config;
+pub mod date;
+pub mod gap_buffer;
+pub mod gpt;
+pub mod kv;
+pub mod line_edit;
+pub mod lines;
+pub mod mbr;
+pub mod path;
+pub mod pointer;
+pub mod rect;
+pub mod sntp;
+pub mod wrap;
@@ -0,0 +1,222 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+// ==========================================
+// LINE EDITING
+// ==========================================
+// The line being typed at a prompt, with a cursor that can be anywhere in
+// it, and the lines typed before it for Up/Down. The cursor is a byte
+// offset that only ever sits on a char boundary; the terminal draws it by
+// counting chars up to it.
+
+pub struct LineEditor {
+    text: String,
+    cursor: usize,
+}
+
+impl LineEditor {
+    pub fn new() -> Self { Self { text: String::new(), cursor: 0 } }
+
+    pub fn text(&self) -> &str { &self.text }
+
+    /// Byte offset of the cursor.
+    pub fn cursor(&self) -> usize { self.cursor }
+
+    /// Chars before the cursor, which is its column on an unwrapped line.
+    pub fn cursor_chars(&self) -> usize { self.text[..self.cursor].chars().count() }
+
+    pub fn insert(&mut self, c: char) {
+        self.text.insert(self.cursor, c);
+        self.cursor += c.len_utf8();
+    }
+
+    /// Removes the char before the cursor; false at the start of the line.
+    pub fn backspace(&mut self) -> bool {
+        match self.text[..self.cursor].chars().next_back() {
+            Some(c) => { self.cursor -= c.len_utf8(); self.text.remove(self.cursor); true },
+            None => false,
+        }
+    }
+
+    /// Removes the char under the cursor; false at the end of the line.
+    pub fn delete(&mut self) -> bool {
+        if self.cursor == self.text.len() { return false; }
+        self.text.remove(self.cursor);
+        true
+    }
+
+    pub fn left(&mut self) -> bool {
+        match self.text[..self.cursor].chars().next_back() {
+            Some(c) => { self.cursor -= c.len_utf8(); true },
+            None => false,
+        }
+    }
+
+    pub fn right(&mut self) -> bool {
+        match self.text[self.cursor..].chars().next() {
+            Some(c) => { self.cursor += c.len_utf8(); true },
+            None => false,
+        }
+    }
+
+    pub fn home(&mut self) { self.cursor = 0; }
+    pub fn end(&mut self) { self.cursor = self.text.len(); }
+
+    /// Replaces the whole line, with the cursor at its end.
+    pub fn set(&mut self, text: &str) {
+        self.text = String::from(text);
+        self.cursor = self.text.len();
+    }
+
+    pub fn clear(&mut self) { self.set(""); }
+
+    /// The space-separated word ending at the cursor, and its byte offset.
+    pub fn word_before_cursor(&self) -> (usize, &str) {
+        let before = &self.text[..self.cursor];
+        let start = before.rfind(' ').map_or(0, |i| i + 1);
+        (start, &before[start..])
+    }
+
+    /// Replaces the text from byte `start` up to the cursor, which then
+    /// follows what was put in.
+    pub fn replace_before_cursor(&mut self, start: usize, with: &str) {
+        self.text.replace_range(start..self.cursor, with);
+        self.cursor = start + with.len();
+    }
+}
+
+impl Default for LineEditor {
+    fn default() -> Self { Self::new() }
+}
+
+/// Lines entered at a prompt, oldest first, for Up/Down.
+pub struct History {
+    entries: Vec<String>,
+    max: usize,
+    // Entry shown while browsing; entries.len() is the line being typed
+    pos: usize,
+    // What was typed before Up started browsing
+    draft: String,
+}
+
+impl History {
+    pub fn new(max: usize) -> Self {
+        Self { entries: Vec::new(), max, pos: 0, draft: String::new() }
+    }
+
+    /// Loads one entry per line, as written by to_text().
+    pub fn parse(text: &str, max: usize) -> Self {
+        let mut history = Self::new(max);
+        for line in text.lines() { history.push(line); }
+        history
+    }
+
+    /// One entry per line, oldest first.
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for entry in &self.entries {
+            text.push_str(entry);
+            text.push('\n');
+        }
+        text
+    }
+
+    pub fn entries(&self) -> &[String] { &self.entries }
+
+    /// Adds an entered line, unless it's blank or repeats the last one, and
+    /// stops browsing. False if nothing was added.
+    pub fn push(&mut self, line: &str) -> bool {
+        let line = line.trim();
+        let added = !line.is_empty() && self.entries.last().map(String::as_str) != Some(line);
+        if added {
+            self.entries.push(String::from(line));
+            if self.entries.len() > self.max { self.entries.remove(0); }
+        }
+        self.pos = self.entries.len();
+        self.draft.clear();
+        added
+    }
+
+    /// The entry before the one shown; `current` is kept as the draft when
+    /// browsing starts. None at the oldest.
+    pub fn older(&mut self, current: &str) -> Option<&str> {
+        if self.pos == 0 { return None; }
+        if self.pos == self.entries.len() { self.draft = String::from(current); }
+        self.pos -= 1;
+        Some(&self.entries[self.pos])
+    }
+
+    /// The entry after the one shown, then the draft. None when not browsing.
+    pub fn newer(&mut self) -> Option<&str> {
+        if self.pos >= self.entries.len() { return None; }
+        self.pos += 1;
+        Some(self.entries.get(self.pos).map_or(self.draft.as_str(), String::as_str))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edits_in_the_middle_of_the_line() {
+        let mut line = LineEditor::new();
+        for c in "ct file".chars() { line.insert(c); }
+        line.home();
+        line.right();
+        line.insert('a');
+        assert_eq!((line.text(), line.cursor_chars()), ("cat file", 2));
+
+        line.end();
+        assert!(line.backspace());
+        assert!(!line.delete());
+        line.home();
+        assert!(!line.backspace());
+        assert!(line.delete());
+        assert_eq!(line.text(), "at fil");
+    }
+
+    #[test]
+    fn cursor_moves_by_char_not_byte() {
+        let mut line = LineEditor::new();
+        line.set("é€x");
+        assert!(line.left());
+        assert!(line.left());
+        assert_eq!((line.cursor(), line.cursor_chars()), (2, 1));
+        assert!(line.backspace());
+        assert_eq!(line.text(), "€x");
+        assert!(!line.left());
+    }
+
+    #[test]
+    fn replaces_the_word_at_the_cursor() {
+        let mut line = LineEditor::new();
+        line.set("cat /mnt/nv tail");
+        for _ in 0.." tail".len() { line.left(); }
+        assert_eq!(line.word_before_cursor(), (4, "/mnt/nv"));
+        line.replace_before_cursor(4, "/mnt/nvme");
+        assert_eq!((line.text(), line.cursor()), ("cat /mnt/nvme tail", 13));
+    }
+
+    #[test]
+    fn history_browses_back_to_the_draft() {
+        let mut history = History::parse("ls\ncat a\n", 100);
+        assert!(!history.push("cat a"));
+        assert!(!history.push("  "));
+
+        assert_eq!(history.older("ec"), Some("cat a"));
+        assert_eq!(history.older(""), Some("ls"));
+        assert_eq!(history.older(""), None);
+        assert_eq!(history.newer(), Some("cat a"));
+        assert_eq!(history.newer(), Some("ec"));
+        assert_eq!(history.newer(), None);
+    }
+
+    #[test]
+    fn history_drops_the_oldest_past_max() {
+        let mut history = History::new(2);
+        for line in ["a", "b", "c"] { history.push(line); }
+        assert_eq!(history.entries(), ["b", "c"]);
+        assert_eq!(history.to_text(), "b\nc\n");
+    }
+}
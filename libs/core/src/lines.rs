@@ -0,0 +1,89 @@
+use alloc::vec::Vec;
+use core::ops::Range;
+
+// ==========================================
+// LINE INDEX
+// ==========================================
+// Where each line of a file starts, built from the file one chunk at a time
+// so a pager can jump to any line and read just the lines it shows. Costs
+// one usize per line however long the lines are.
+
+pub struct LineIndex {
+    starts: Vec<usize>,
+    len: usize,
+    // The last byte fed was a '\n', so the next byte starts a line
+    at_line_start: bool,
+}
+
+impl LineIndex {
+    pub fn new() -> Self {
+        Self { starts: Vec::new(), len: 0, at_line_start: true }
+    }
+
+    /// Feeds the next `chunk` of the file; chunks must arrive in order.
+    pub fn push(&mut self, chunk: &[u8]) {
+        for (i, &b) in chunk.iter().enumerate() {
+            if self.at_line_start {
+                self.starts.push(self.len + i);
+                self.at_line_start = false;
+            }
+            if b == b'\n' { self.at_line_start = true; }
+        }
+        self.len += chunk.len();
+    }
+
+    /// Number of lines; a final '\n' doesn't start another one.
+    pub fn lines(&self) -> usize { self.starts.len() }
+
+    /// Bytes fed so far.
+    pub fn len(&self) -> usize { self.len }
+
+    pub fn is_empty(&self) -> bool { self.len == 0 }
+
+    /// File offsets of line `i` without its '\n'.
+    pub fn line(&self, i: usize) -> Range<usize> {
+        let start = self.starts[i];
+        let end = match self.starts.get(i + 1) {
+            Some(&next) => next - 1,
+            None if self.at_line_start => self.len - 1,
+            None => self.len,
+        };
+        start..end
+    }
+}
+
+impl Default for LineIndex {
+    fn default() -> Self { Self::new() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index(chunks: &[&[u8]]) -> LineIndex {
+        let mut index = LineIndex::new();
+        for chunk in chunks { index.push(chunk); }
+        index
+    }
+
+    #[test]
+    fn lines_split_across_chunks() {
+        let index = index(&[b"one\ntw", b"o\n\nthr", b"ee"]);
+        assert_eq!(index.lines(), 4);
+        assert_eq!(index.line(0), 0..3);
+        assert_eq!(index.line(1), 4..7);
+        assert_eq!(index.line(2), 8..8);
+        assert_eq!(index.line(3), 9..14);
+        assert_eq!(index.len(), 14);
+    }
+
+    #[test]
+    fn trailing_newline_ends_the_last_line() {
+        let index = index(&[b"a\nb\n"]);
+        assert_eq!(index.lines(), 2);
+        assert_eq!(index.line(1), 2..3);
+
+        assert_eq!(self::index(&[]).lines(), 0);
+        assert_eq!(self::index(&[b"\n"]).line(0), 0..0);
+    }
+}
@@ -0,0 +1,129 @@
+use alloc::vec::Vec;
+use core::convert::TryInto;
+use crate::block::BlockSource;
+
+// ==========================================
+// MASTER BOOT RECORD
+// ==========================================
+// Primary entries come straight from LBA 0. An extended partition
+// (0x05/0x0F) holds a chain of EBRs: each one describes a single logical
+// partition (relative to that EBR) and links to the next EBR (relative to
+// the start of the extended partition).
+
+pub const MBR_TYPE_PROTECTIVE_GPT: u8 = 0xEE;
+pub const MBR_TYPE_LINUX: u8 = 0x83;
+
+// Corrupt chains can loop back on themselves; no real disk has this many logicals
+const MAX_LOGICAL: usize = 128;
+
+#[derive(Debug, Clone, Copy)]
+pub struct MbrPartition {
+    pub type_byte: u8,
+    pub first_lba: u64,
+    pub sectors: u64,
+    pub logical: bool,
+}
+
+impl MbrPartition {
+    pub fn type_name(&self) -> &'static str {
+        match self.type_byte {
+            MBR_TYPE_LINUX => "Linux",
+            0x06 => "FAT16",
+            0x0B | 0x0C => "FAT32",
+            0x0E => "FAT16 LBA",
+            0x07 => "NTFS/exFAT",
+            0x82 => "Linux swap",
+            MBR_TYPE_PROTECTIVE_GPT => "GPT protective",
+            _ => "Unknown",
+        }
+    }
+}
+
+fn is_extended(type_byte: u8) -> bool { type_byte == 0x05 || type_byte == 0x0F }
+
+/// The four 16-byte entries of an MBR/EBR sector as (type, start, sectors).
+fn entries(sector: &[u8]) -> [(u8, u64, u64); 4] {
+    core::array::from_fn(|i| {
+        let e = &sector[446 + i * 16..462 + i * 16];
+        (e[4], u32::from_le_bytes(e[8..12].try_into().unwrap()) as u64, u32::from_le_bytes(e[12..16].try_into().unwrap()) as u64)
+    })
+}
+
+/// Lists the primary and logical partitions of a disk with `block_size`-byte blocks.
+/// None if LBA 0 carries no boot signature.
+pub fn read_partitions<D: BlockSource + ?Sized>(disk: &mut D, block_size: usize) -> Option<Vec<MbrPartition>> {
+    let mut sector = alloc::vec![0u8; block_size];
+    if !disk.read_block(0, &mut sector) || sector[510] != 0x55 || sector[511] != 0xAA { return None; }
+
+    let mut partitions = Vec::new();
+    let mut extended_base = None;
+    for (type_byte, first_lba, sectors) in entries(&sector) {
+        if type_byte == 0 || sectors == 0 { continue; }
+        if is_extended(type_byte) {
+            extended_base.get_or_insert(first_lba);
+        } else {
+            partitions.push(MbrPartition { type_byte, first_lba, sectors, logical: false });
+        }
+    }
+
+    if let Some(base) = extended_base {
+        let mut ebr_lba = base;
+        for _ in 0..MAX_LOGICAL {
+            if !disk.read_block(ebr_lba, &mut sector) || sector[510] != 0x55 || sector[511] != 0xAA { break; }
+            let [logical, next, ..] = entries(&sector);
+
+            if logical.0 != 0 && logical.2 != 0 {
+                partitions.push(MbrPartition { type_byte: logical.0, first_lba: ebr_lba + logical.1, sectors: logical.2, logical: true });
+            }
+            if !is_extended(next.0) || next.1 == 0 { break; }
+            ebr_lba = base + next.1;
+        }
+    }
+    Some(partitions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::ImageSource;
+
+    const IMAGE: &[u8] = include_bytes!("../fixtures/mbr.img");
+
+    #[test]
+    fn lists_primaries_then_logicals() {
+        let parts = read_partitions(&mut ImageSource { image: IMAGE }, 512).unwrap();
+        assert_eq!(parts.len(), 3);
+        assert_eq!((parts[0].type_byte, parts[0].first_lba, parts[0].sectors, parts[0].logical), (0x0C, 2048, 204800, false));
+        assert_eq!(parts[0].type_name(), "FAT32");
+        // Logicals start relative to their own EBR, at 4 and at 4 + 6
+        assert_eq!((parts[1].type_byte, parts[1].first_lba, parts[1].sectors, parts[1].logical), (MBR_TYPE_LINUX, 6, 4, true));
+        assert_eq!((parts[2].type_byte, parts[2].first_lba, parts[2].logical), (0x82, 12, true));
+    }
+
+    #[test]
+    fn no_boot_signature_means_no_table() {
+        let mut image = IMAGE.to_vec();
+        image[511] = 0;
+        assert!(read_partitions(&mut ImageSource { image: &image }, 512).is_none());
+        assert!(read_partitions(&mut ImageSource { image: &[] }, 512).is_none());
+    }
+
+    #[test]
+    fn a_broken_chain_keeps_what_came_before() {
+        // The second EBR loses its signature
+        let mut image = IMAGE.to_vec();
+        image[10 * 512 + 510] = 0;
+        let parts = read_partitions(&mut ImageSource { image: &image }, 512).unwrap();
+        assert_eq!(parts.len(), 2);
+    }
+
+    #[test]
+    fn a_looping_chain_stops() {
+        // The second EBR links back to itself
+        let mut image = IMAGE.to_vec();
+        image[10 * 512 + 446 + 16..10 * 512 + 446 + 32].copy_from_slice(&[0, 0xFE, 0xFF, 0xFF, 0x05, 0xFE, 0xFF, 0xFF, 6, 0, 0, 0, 60, 0, 0, 0]);
+        let parts = read_partitions(&mut ImageSource { image: &image }, 512).unwrap();
+        // Every pass adds its logical again, until MAX_LOGICAL passes are up
+        assert_eq!(parts.len(), 1 + MAX_LOGICAL);
+    }
+}
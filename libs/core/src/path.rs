@@ -0,0 +1,140 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+// ==========================================
+// VFS PATHS
+// ==========================================
+// Paths are absolute, '/'-separated, with no drive letters or escapes. These
+// only look at the text; nothing here asks the VFS whether a path exists.
+
+/// Collapses repeated slashes, drops a trailing one and resolves "." and
+/// "..", so "/mnt//nvme/./apps/../" becomes "/mnt/nvme". ".." at the root
+/// stays at the root. Relative input is taken as relative to "/".
+pub fn normalize(path: &str) -> String {
+    let mut parts: Vec<&str> = Vec::new();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {},
+            ".." => { parts.pop(); },
+            _ => parts.push(part),
+        }
+    }
+    if parts.is_empty() { String::from("/") } else { alloc::format!("/{}", parts.join("/")) }
+}
+
+/// Everything but the last component, normalized; the root is its own parent.
+pub fn parent(path: &str) -> String {
+    let path = normalize(path);
+    match path.rfind('/') {
+        Some(0) | None => String::from("/"),
+        Some(i) => String::from(&path[..i]),
+    }
+}
+
+/// `name` inside `dir`, or `name` itself when it is already absolute. Not
+/// normalized: join("/a", "../b") is "/a/../b".
+pub fn join(dir: &str, name: &str) -> String {
+    if name.starts_with('/') { return String::from(name); }
+    if dir.ends_with('/') { alloc::format!("{}{}", dir, name) } else { alloc::format!("{}/{}", dir, name) }
+}
+
+/// The last component, ignoring a trailing slash; empty for the root.
+pub fn file_name(path: &str) -> &str {
+    let path = path.trim_end_matches('/');
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// What follows the last '.' of the last component. None without a dot,
+/// and for dot files like ".profile", whose dot starts the name.
+pub fn extension(path: &str) -> Option<&str> {
+    let name = file_name(path);
+    match name.rfind('.') {
+        Some(i) if i > 0 => Some(&name[i + 1..]),
+        _ => None,
+    }
+}
+
+/// Characters a file name may not contain. ext4 would store most of them,
+/// but FAT and the desktop's own quoting can't, so names stay portable.
+pub const RESERVED_NAME_CHARS: &str = "\\:*?\"<>|";
+
+/// The first char that can't be in a file name: a reserved one, a control
+/// char, or '/'. None if `name` is fine.
+pub fn invalid_name_char(name: &str) -> Option<char> {
+    name.chars().find(|&c| c == '/' || c.is_control() || RESERVED_NAME_CHARS.contains(c))
+}
+
+/// The entry of `names` that `want` means once case is ignored: the exact
+/// match if there is one, else the first that differs only in ASCII case.
+pub fn match_ignore_case<'a>(names: impl IntoIterator<Item = &'a str>, want: &str) -> Option<&'a str> {
+    let mut folded = None;
+    for name in names {
+        if name == want { return Some(name); }
+        if folded.is_none() && name.eq_ignore_ascii_case(want) { folded = Some(name); }
+    }
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_collapses_and_resolves() {
+        assert_eq!(normalize("/mnt//nvme/"), "/mnt/nvme");
+        assert_eq!(normalize("/mnt/nvme/./apps/../"), "/mnt/nvme");
+        assert_eq!(normalize("/.."), "/");
+        assert_eq!(normalize("/a/b/../../.."), "/");
+        assert_eq!(normalize(""), "/");
+        assert_eq!(normalize("apps/bin"), "/apps/bin");
+        // Dots inside a name are just part of it
+        assert_eq!(normalize("/a/..b/c."), "/a/..b/c.");
+    }
+
+    #[test]
+    fn parent_of_root_and_nested() {
+        assert_eq!(parent("/"), "/");
+        assert_eq!(parent("/mnt"), "/");
+        assert_eq!(parent("/mnt/nvme/"), "/mnt");
+        assert_eq!(parent("/mnt/nvme/a.txt"), "/mnt/nvme");
+    }
+
+    #[test]
+    fn join_keeps_one_slash() {
+        assert_eq!(join("/", "mnt"), "/mnt");
+        assert_eq!(join("/mnt", "nvme"), "/mnt/nvme");
+        assert_eq!(join("/mnt/", "nvme"), "/mnt/nvme");
+        assert_eq!(join("/mnt", "/etc/nyx.cfg"), "/etc/nyx.cfg");
+        assert_eq!(normalize(&join("/mnt/nvme", "../usb")), "/mnt/usb");
+    }
+
+    #[test]
+    fn names_and_extensions() {
+        assert_eq!(file_name("/mnt/nvme/readme.md"), "readme.md");
+        assert_eq!(file_name("/mnt/nvme/"), "nvme");
+        assert_eq!(file_name("/"), "");
+        assert_eq!(extension("/a/photo.BMP"), Some("BMP"));
+        assert_eq!(extension("archive.tar.gz"), Some("gz"));
+        assert_eq!(extension("/home/.profile"), None);
+        assert_eq!(extension("/a.d/Makefile"), None);
+        assert_eq!(extension("trailing."), Some(""));
+    }
+
+    #[test]
+    fn names_match_ignoring_case() {
+        let names = ["Readme.TXT", "notes.txt", "NOTES.txt"];
+        assert_eq!(match_ignore_case(names, "readme.txt"), Some("Readme.TXT"));
+        assert_eq!(match_ignore_case(names, "NOTES.txt"), Some("NOTES.txt"));
+        assert_eq!(match_ignore_case(names, "Notes.TXT"), Some("notes.txt"));
+        assert_eq!(match_ignore_case(names, "todo.txt"), None);
+    }
+
+    #[test]
+    fn reserved_chars_make_bad_names() {
+        assert_eq!(invalid_name_char("report (final).txt"), None);
+        assert_eq!(invalid_name_char("a:b"), Some(':'));
+        assert_eq!(invalid_name_char("why?.txt"), Some('?'));
+        assert_eq!(invalid_name_char("tab\there"), Some('\t'));
+        assert_eq!(invalid_name_char("back\\slash"), Some('\\'));
+    }
+}
@@ -0,0 +1,216 @@
+use alloc::vec::Vec;
+
+// ==========================================
+// POINTER INPUT
+// ==========================================
+// Turns the raw mouse state, sampled once per frame, into the events apps
+// act on. Every press starts with Press and ends with Release; in between it
+// turns into either a Click (released without travelling past
+// DRAG_THRESHOLD) or a DragStart / DragMove / DragEnd sequence. A second
+// press near the first one within DOUBLE_CLICK_MS also yields DoubleClick,
+// and its release yields no Click of its own. Only the first button pressed
+// is tracked until it is let go.
+
+/// Pixels the pointer may travel with a button held before the press becomes a drag
+pub const DRAG_THRESHOLD: usize = 4;
+/// Longest gap between two presses that still makes a double-click
+pub const DOUBLE_CLICK_MS: usize = 400;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MouseButton { Left, Right, Middle }
+
+/// The mouse as read once per frame.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct Sample {
+    pub x: usize,
+    pub y: usize,
+    pub left: bool,
+    pub right: bool,
+    pub middle: bool,
+    /// Wheel notches since the previous sample, positive away from the user
+    pub wheel: i8,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PointerEvent {
+    Press { x: usize, y: usize, button: MouseButton },
+    /// Position of the press, not of the release
+    Click { x: usize, y: usize, button: MouseButton },
+    DoubleClick { x: usize, y: usize, button: MouseButton },
+    /// Position of the press the drag started from
+    DragStart { x: usize, y: usize, button: MouseButton },
+    DragMove { x: usize, y: usize, button: MouseButton },
+    DragEnd { x: usize, y: usize, button: MouseButton },
+    Release { x: usize, y: usize, button: MouseButton },
+    /// Positive notches scroll up / away from the user
+    Wheel { x: usize, y: usize, notches: i32 },
+}
+
+struct Held {
+    button: MouseButton,
+    x: usize, y: usize,
+    dragging: bool,
+    // This press completed a double-click, so its release isn't a click
+    double: bool,
+}
+
+#[derive(Default)]
+pub struct PointerTracker {
+    held: Option<Held>,
+    // Button, position and time of the last press that could start a double-click
+    last_press: Option<(MouseButton, usize, usize, usize)>,
+    prev_x: usize, prev_y: usize,
+}
+
+fn is_down(m: &Sample, button: MouseButton) -> bool {
+    match button { MouseButton::Left => m.left, MouseButton::Right => m.right, MouseButton::Middle => m.middle }
+}
+
+impl PointerTracker {
+    pub const fn new() -> Self {
+        Self { held: None, last_press: None, prev_x: 0, prev_y: 0 }
+    }
+
+    /// Whether a press is in progress
+    pub fn is_pressed(&self) -> bool { self.held.is_some() }
+
+    /// Feeds one sample taken at `now` (in ms) and returns what happened since the last one.
+    pub fn update(&mut self, m: Sample, now: usize) -> Vec<PointerEvent> {
+        let mut events = Vec::new();
+        let (x, y) = (m.x, m.y);
+        if m.wheel != 0 { events.push(PointerEvent::Wheel { x, y, notches: m.wheel as i32 }); }
+
+        match self.held.as_mut() {
+            Some(held) if is_down(&m, held.button) => {
+                let button = held.button;
+                if !held.dragging && x.abs_diff(held.x).max(y.abs_diff(held.y)) > DRAG_THRESHOLD {
+                    held.dragging = true;
+                    events.push(PointerEvent::DragStart { x: held.x, y: held.y, button });
+                    events.push(PointerEvent::DragMove { x, y, button });
+                } else if held.dragging && (x != self.prev_x || y != self.prev_y) {
+                    events.push(PointerEvent::DragMove { x, y, button });
+                }
+            },
+            Some(_) => {
+                let held = self.held.take().unwrap();
+                let button = held.button;
+                if held.dragging {
+                    events.push(PointerEvent::DragEnd { x, y, button });
+                } else if !held.double {
+                    events.push(PointerEvent::Click { x: held.x, y: held.y, button });
+                }
+                events.push(PointerEvent::Release { x, y, button });
+            },
+            None => {
+                let pressed = [MouseButton::Left, MouseButton::Right, MouseButton::Middle].into_iter().find(|&b| is_down(&m, b));
+                if let Some(button) = pressed {
+                    events.push(PointerEvent::Press { x, y, button });
+                    let double = self.last_press.is_some_and(|(b, px, py, t)| {
+                        b == button && now.wrapping_sub(t) < DOUBLE_CLICK_MS && x.abs_diff(px).max(y.abs_diff(py)) <= DRAG_THRESHOLD
+                    });
+                    if double {
+                        events.push(PointerEvent::DoubleClick { x, y, button });
+                        // A third press starts over instead of making another double-click
+                        self.last_press = None;
+                    } else {
+                        self.last_press = Some((button, x, y, now));
+                    }
+                    self.held = Some(Held { button, x, y, dragging: false, double });
+                }
+            },
+        }
+
+        self.prev_x = x;
+        self.prev_y = y;
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec;
+
+    fn at(x: usize, y: usize) -> Sample { Sample { x, y, ..Sample::default() } }
+    fn left(x: usize, y: usize) -> Sample { Sample { left: true, ..at(x, y) } }
+
+    const L: MouseButton = MouseButton::Left;
+
+    #[test]
+    fn press_and_release_in_place_is_a_click() {
+        let mut p = PointerTracker::new();
+        assert_eq!(p.update(left(10, 10), 0), vec![PointerEvent::Press { x: 10, y: 10, button: L }]);
+        assert!(p.is_pressed());
+        // Jitter within DRAG_THRESHOLD doesn't start a drag
+        assert!(p.update(left(12, 13), 10).is_empty());
+        assert_eq!(p.update(at(12, 13), 20), vec![
+            PointerEvent::Click { x: 10, y: 10, button: L },
+            PointerEvent::Release { x: 12, y: 13, button: L },
+        ]);
+        assert!(!p.is_pressed());
+    }
+
+    #[test]
+    fn travelling_past_the_threshold_drags() {
+        let mut p = PointerTracker::new();
+        p.update(left(10, 10), 0);
+        assert_eq!(p.update(left(30, 10), 10), vec![
+            PointerEvent::DragStart { x: 10, y: 10, button: L },
+            PointerEvent::DragMove { x: 30, y: 10, button: L },
+        ]);
+        // Holding still sends nothing; moving sends DragMove
+        assert!(p.update(left(30, 10), 20).is_empty());
+        assert_eq!(p.update(left(31, 12), 30), vec![PointerEvent::DragMove { x: 31, y: 12, button: L }]);
+        assert_eq!(p.update(at(31, 12), 40), vec![
+            PointerEvent::DragEnd { x: 31, y: 12, button: L },
+            PointerEvent::Release { x: 31, y: 12, button: L },
+        ]);
+    }
+
+    #[test]
+    fn quick_second_press_is_a_double_click() {
+        let mut p = PointerTracker::new();
+        p.update(left(10, 10), 1000);
+        p.update(at(10, 10), 1050);
+        assert_eq!(p.update(left(11, 10), 1200), vec![
+            PointerEvent::Press { x: 11, y: 10, button: L },
+            PointerEvent::DoubleClick { x: 11, y: 10, button: L },
+        ]);
+        // The double-click's release isn't a click of its own
+        assert_eq!(p.update(at(11, 10), 1250), vec![PointerEvent::Release { x: 11, y: 10, button: L }]);
+        // A third press starts over
+        assert_eq!(p.update(left(11, 10), 1300), vec![PointerEvent::Press { x: 11, y: 10, button: L }]);
+    }
+
+    #[test]
+    fn slow_or_distant_second_press_is_not_a_double_click() {
+        let mut p = PointerTracker::new();
+        p.update(left(10, 10), 0);
+        p.update(at(10, 10), 10);
+        assert_eq!(p.update(left(10, 10), DOUBLE_CLICK_MS + 1).len(), 1);
+        p.update(at(10, 10), DOUBLE_CLICK_MS + 2);
+        assert_eq!(p.update(left(50, 10), DOUBLE_CLICK_MS + 3).len(), 1);
+        p.update(at(50, 10), DOUBLE_CLICK_MS + 4);
+        // Another button never completes a double-click
+        let right = Sample { right: true, ..at(50, 10) };
+        assert_eq!(p.update(right, DOUBLE_CLICK_MS + 5), vec![PointerEvent::Press { x: 50, y: 10, button: MouseButton::Right }]);
+    }
+
+    #[test]
+    fn only_the_first_button_is_tracked() {
+        let mut p = PointerTracker::new();
+        p.update(Sample { right: true, ..at(5, 5) }, 0);
+        // Adding the left button changes nothing; releasing right ends the press
+        assert!(p.update(Sample { left: true, right: true, ..at(5, 5) }, 10).is_empty());
+        assert_eq!(p.update(left(5, 5), 20), vec![
+            PointerEvent::Click { x: 5, y: 5, button: MouseButton::Right },
+            PointerEvent::Release { x: 5, y: 5, button: MouseButton::Right },
+        ]);
+    }
+
+    #[test]
+    fn wheel_is_reported_with_any_sample() {
+        let mut p = PointerTracker::new();
+        assert_eq!(p.update(Sample { wheel: -2, ..at(3, 4) }, 0), vec![PointerEvent::Wheel { x: 3, y: 4, notches: -2 }]);
+    }
+}
@@ -0,0 +1,114 @@
+// ==========================================
+// RECTANGLES AND DIRTY REGIONS
+// ==========================================
+// Both compositors (the kernel's window manager and the userspace one) and
+// the kernel's back buffer repaint only what changed, tracked as the
+// bounding box of everything marked since the last repaint. Areas are
+// (x0, y0, x1, y1), exclusive at the far edges, in pixels.
+
+pub type Area = (usize, usize, usize, usize);
+
+/// (x, y, w, h) as an Area; sizes that run past usize::MAX stop there.
+pub fn from_rect(x: usize, y: usize, w: usize, h: usize) -> Area {
+    (x, y, x.saturating_add(w), y.saturating_add(h))
+}
+
+pub fn is_empty(a: Area) -> bool { a.0 >= a.2 || a.1 >= a.3 }
+
+/// The smallest Area holding both.
+pub fn union(a: Area, b: Area) -> Area {
+    (a.0.min(b.0), a.1.min(b.1), a.2.max(b.2), a.3.max(b.3))
+}
+
+pub fn intersects(a: Area, b: Area) -> bool {
+    a.0 < b.2 && b.0 < a.2 && a.1 < b.3 && b.1 < a.3
+}
+
+/// The part of `a` inside a w x h screen; empty when none of it is.
+pub fn clip(a: Area, w: usize, h: usize) -> Area {
+    (a.0, a.1, a.2.min(w), a.3.min(h))
+}
+
+/// The bounding box of every area marked since the last take().
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DirtyRegion {
+    area: Option<Area>,
+}
+
+impl DirtyRegion {
+    pub const fn new() -> Self { Self { area: None } }
+
+    /// Adds the part of (x, y, w, h) that lies on a bound_w x bound_h screen.
+    pub fn mark(&mut self, x: usize, y: usize, w: usize, h: usize, bound_w: usize, bound_h: usize) {
+        self.mark_area(from_rect(x, y, w, h), bound_w, bound_h);
+    }
+
+    /// mark() for an Area.
+    pub fn mark_area(&mut self, area: Area, bound_w: usize, bound_h: usize) {
+        let area = clip(area, bound_w, bound_h);
+        if is_empty(area) { return; }
+        self.area = Some(match self.area { Some(d) => union(d, area), None => area });
+    }
+
+    pub fn mark_all(&mut self, w: usize, h: usize) {
+        self.area = if w == 0 || h == 0 { None } else { Some((0, 0, w, h)) };
+    }
+
+    pub fn get(&self) -> Option<Area> { self.area }
+
+    pub fn is_clean(&self) -> bool { self.area.is_none() }
+
+    /// What was marked, leaving the region clean.
+    pub fn take(&mut self) -> Option<Area> { self.area.take() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn union_and_intersection() {
+        assert_eq!(union((0, 0, 10, 10), (5, 5, 20, 8)), (0, 0, 20, 10));
+        assert!(intersects((0, 0, 10, 10), (9, 9, 12, 12)));
+        // Far edges are exclusive, so touching rectangles don't overlap
+        assert!(!intersects((0, 0, 10, 10), (10, 0, 20, 10)));
+        assert!(!intersects((0, 0, 10, 10), (0, 10, 10, 20)));
+        assert!(intersects((5, 5, 6, 6), (0, 0, 100, 100)));
+    }
+
+    #[test]
+    fn empty_and_clipped_areas() {
+        assert!(is_empty((5, 5, 5, 10)));
+        assert!(is_empty((5, 5, 10, 4)));
+        assert!(!is_empty((0, 0, 1, 1)));
+        assert_eq!(clip((10, 10, 2000, 900), 1024, 768), (10, 10, 1024, 768));
+        assert!(is_empty(clip((1100, 0, 1200, 10), 1024, 768)));
+        assert_eq!(from_rect(usize::MAX - 1, 0, 10, 10), (usize::MAX - 1, 0, usize::MAX, 10));
+    }
+
+    #[test]
+    fn dirty_region_grows_and_empties() {
+        let mut dirty = DirtyRegion::new();
+        assert!(dirty.is_clean());
+        dirty.mark(10, 10, 5, 5, 100, 100);
+        dirty.mark(50, 2, 10, 3, 100, 100);
+        assert_eq!(dirty.get(), Some((10, 2, 60, 15)));
+        assert_eq!(dirty.take(), Some((10, 2, 60, 15)));
+        assert!(dirty.is_clean());
+    }
+
+    #[test]
+    fn dirty_region_ignores_offscreen_and_empty_marks() {
+        let mut dirty = DirtyRegion::new();
+        dirty.mark(200, 10, 10, 10, 100, 100);
+        dirty.mark(10, 10, 0, 10, 100, 100);
+        assert!(dirty.is_clean());
+        // Partly off screen keeps the part that's on it
+        dirty.mark(90, 95, 20, 20, 100, 100);
+        assert_eq!(dirty.get(), Some((90, 95, 100, 100)));
+        dirty.mark_all(100, 100);
+        assert_eq!(dirty.get(), Some((0, 0, 100, 100)));
+        dirty.mark_all(0, 100);
+        assert!(dirty.is_clean());
+    }
+}
@@ -0,0 +1,127 @@
+// ==========================================
+// SNTP
+// ==========================================
+// The little of RFC 4330 the Terminal's `netclock` needs: a client request,
+// and reading the server's transmit time back out of the reply. NTP counts
+// seconds from 1900 in 32 bits, which wraps in 2036; as the RFC suggests, a
+// seconds field with the top bit clear is taken to be after the wrap. The
+// request's transmit field carries a nonce the server copies back as the
+// reply's originate field, so a stray or forged reply is told apart.
+
+pub const NTP_PORT: u16 = 123;
+pub const PACKET_LEN: usize = 48;
+
+/// Seconds from 1900-01-01 to 1970-01-01
+const NTP_TO_UNIX: u64 = 2_208_988_800;
+/// Leap indicator 0, version 4, mode 3 (client)
+const CLIENT_HEADER: u8 = (4 << 3) | 3;
+const MODE_SERVER: u8 = 4;
+const MODE_BROADCAST: u8 = 5;
+/// Leap indicator 3: the server's own clock isn't synchronized
+const LEAP_ALARM: u8 = 3;
+
+const ORIGINATE: usize = 24;
+const TRANSMIT: usize = 40;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Reply {
+    /// The server's clock as it sent the reply, in Unix milliseconds
+    pub unix_ms: u64,
+    /// 1 for a server with its own reference clock, more the further from one
+    pub stratum: u8,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReplyError {
+    /// Fewer than PACKET_LEN bytes
+    TooShort,
+    /// Not a server or broadcast packet
+    NotServer,
+    /// Stratum 0: the server refused ("kiss-o'-death"), usually rate limiting
+    Refused,
+    /// The server says its own clock can't be trusted
+    Unsynchronized,
+    /// The originate field isn't the request's nonce
+    NotOurs,
+}
+
+/// A client request carrying `nonce` in its transmit field.
+pub fn request(nonce: u64) -> [u8; PACKET_LEN] {
+    let mut packet = [0u8; PACKET_LEN];
+    packet[0] = CLIENT_HEADER;
+    packet[TRANSMIT..TRANSMIT + 8].copy_from_slice(&nonce.to_be_bytes());
+    packet
+}
+
+/// Checks a reply to the request sent with `nonce` and reads its time.
+pub fn parse_reply(reply: &[u8], nonce: u64) -> Result<Reply, ReplyError> {
+    if reply.len() < PACKET_LEN { return Err(ReplyError::TooShort); }
+    let mode = reply[0] & 0x07;
+    if mode != MODE_SERVER && mode != MODE_BROADCAST { return Err(ReplyError::NotServer); }
+    let stratum = reply[1];
+    if stratum == 0 { return Err(ReplyError::Refused); }
+    if reply[0] >> 6 == LEAP_ALARM { return Err(ReplyError::Unsynchronized); }
+    if u64::from_be_bytes(field(reply, ORIGINATE)) != nonce { return Err(ReplyError::NotOurs); }
+
+    let transmit = u64::from_be_bytes(field(reply, TRANSMIT));
+    let (secs, fraction) = ((transmit >> 32) as u32, transmit as u32);
+    if secs == 0 { return Err(ReplyError::Unsynchronized); }
+    Ok(Reply { unix_ms: ntp_to_unix_ms(secs, fraction), stratum })
+}
+
+fn field(packet: &[u8], at: usize) -> [u8; 8] {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&packet[at..at + 8]);
+    bytes
+}
+
+fn ntp_to_unix_ms(secs: u32, fraction: u32) -> u64 {
+    // Top bit clear: past the 2036 wrap, in era 1
+    let secs = if secs & 0x8000_0000 == 0 { secs as u64 + (1 << 32) } else { secs as u64 };
+    let ms = (fraction as u64 * 1000) >> 32;
+    (secs - NTP_TO_UNIX) * 1000 + ms
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reply(header: u8, stratum: u8, originate: u64, transmit: u64) -> [u8; PACKET_LEN] {
+        let mut packet = [0u8; PACKET_LEN];
+        packet[0] = header;
+        packet[1] = stratum;
+        packet[ORIGINATE..ORIGINATE + 8].copy_from_slice(&originate.to_be_bytes());
+        packet[TRANSMIT..TRANSMIT + 8].copy_from_slice(&transmit.to_be_bytes());
+        packet
+    }
+
+    #[test]
+    fn builds_a_client_request() {
+        let packet = request(0x0102_0304_0506_0708);
+        assert_eq!(packet[0], 0x23);
+        assert_eq!(&packet[TRANSMIT..], &[1, 2, 3, 4, 5, 6, 7, 8]);
+        assert!(packet[1..TRANSMIT].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn reads_the_server_time() {
+        // 2024-02-29 12:34:56.5 UTC, and a version 3 server
+        let secs = 1_709_210_096 + NTP_TO_UNIX;
+        let packet = reply(0x1C, 2, 42, (secs << 32) | 0x8000_0000);
+        assert_eq!(parse_reply(&packet, 42), Ok(Reply { unix_ms: 1_709_210_096_500, stratum: 2 }));
+        // 2036-02-07 06:28:16, the moment the seconds field wraps to 0, then a second on
+        let packet = reply(0x24, 1, 7, 1 << 32);
+        assert_eq!(parse_reply(&packet, 7).map(|r| r.unix_ms / 1000), Ok(2_085_978_497));
+    }
+
+    #[test]
+    fn rejects_bad_replies() {
+        let good_time = (3_900_000_000u64) << 32;
+        assert_eq!(parse_reply(&[0x24; 47], 1), Err(ReplyError::TooShort));
+        assert_eq!(parse_reply(&reply(0x23, 2, 1, good_time), 1), Err(ReplyError::NotServer));
+        assert_eq!(parse_reply(&reply(0x24, 0, 1, good_time), 1), Err(ReplyError::Refused));
+        assert_eq!(parse_reply(&reply(0xE4, 2, 1, good_time), 1), Err(ReplyError::Unsynchronized));
+        assert_eq!(parse_reply(&reply(0x24, 2, 2, good_time), 1), Err(ReplyError::NotOurs));
+        assert_eq!(parse_reply(&reply(0x24, 2, 1, 0), 1), Err(ReplyError::Unsynchronized));
+    }
+}
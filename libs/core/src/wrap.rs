@@ -0,0 +1,179 @@
+use alloc::vec::Vec;
+
+use crate::gap_buffer::GapBuffer;
+
+// ==========================================
+// LINE WRAPPING
+// ==========================================
+// Terminals show text in a fixed-width font, so a line wraps after `cols`
+// characters exactly, wherever that falls; no word breaking. Counting is by
+// char, never by byte, so a row always ends on a char boundary.
+
+/// Splits `line` into rows of at most `cols` chars; an empty line is one empty row.
+pub fn wrap_line(line: &str, cols: usize) -> Vec<&str> {
+    let cols = cols.max(1);
+    let mut rows = Vec::new();
+    let mut start = 0;
+    for (n, (i, _)) in line.char_indices().enumerate() {
+        if n > 0 && n % cols == 0 {
+            rows.push(&line[start..i]);
+            start = i;
+        }
+    }
+    rows.push(&line[start..]);
+    rows
+}
+
+/// How many rows wrap_line(line, cols) returns, without building them.
+pub fn wrapped_rows(line: &str, cols: usize) -> usize {
+    let cols = cols.max(1);
+    line.chars().count().max(1).div_ceil(cols)
+}
+
+/// A GapBuffer wrapped at `cols` chars the same way, as byte ranges that
+/// leave out the '\n'. A line of exactly `cols` chars is followed by an
+/// empty row, where the caret goes after its last char. An edit only
+/// rewraps the lines it touched.
+pub struct WrappedRows {
+    rows: Vec<(usize, usize)>,
+    cols: usize,
+}
+
+impl WrappedRows {
+    pub fn new(buf: &GapBuffer, cols: usize) -> Self {
+        let cols = cols.max(1);
+        let mut rows = Vec::new();
+        wrap_range(buf, 0, buf.len(), cols, &mut rows);
+        Self { rows, cols }
+    }
+
+    pub fn rows(&self) -> &[(usize, usize)] { &self.rows }
+
+    pub fn cols(&self) -> usize { self.cols }
+
+    /// The row holding `pos`. At a soft wrap it belongs to the following row.
+    pub fn row_of(&self, pos: usize) -> usize {
+        self.rows.partition_point(|&(start, _)| start <= pos).saturating_sub(1)
+    }
+
+    /// Catches up with an edit at `pos` that removed `removed` bytes and then
+    /// inserted `inserted`. `buf` is the text after the edit.
+    pub fn edited(&mut self, buf: &GapBuffer, pos: usize, removed: usize, inserted: usize) {
+        // The lines the edit touched, in the new text and in the old
+        let start = buf.line_start(pos);
+        let end = buf.line_end(pos + inserted);
+        let old_end = end + removed - inserted;
+        let first = self.rows.partition_point(|&(s, _)| s < start);
+        let last = self.rows.partition_point(|&(s, _)| s <= old_end);
+
+        let mut fresh = Vec::new();
+        wrap_range(buf, start, end, self.cols, &mut fresh);
+        for row in &mut self.rows[last..] {
+            row.0 = row.0 + inserted - removed;
+            row.1 = row.1 + inserted - removed;
+        }
+        self.rows.splice(first..last, fresh);
+    }
+}
+
+/// Appends the rows of `start..end`, which begins a line and ends one.
+fn wrap_range(buf: &GapBuffer, start: usize, end: usize, cols: usize, rows: &mut Vec<(usize, usize)>) {
+    let mut row_start = start;
+    let mut count = 0;
+    for (i, c) in buf.chars(start, end) {
+        if c == '\n' {
+            rows.push((row_start, i));
+            row_start = i + 1;
+            count = 0;
+            continue;
+        }
+        count += 1;
+        if count == cols {
+            rows.push((row_start, i + c.len_utf8()));
+            row_start = i + c.len_utf8();
+            count = 0;
+        }
+    }
+    rows.push((row_start, end));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wraps_at_exact_columns() {
+        assert_eq!(wrap_line("abcdefgh", 3), ["abc", "def", "gh"]);
+        assert_eq!(wrap_line("abcdef", 3), ["abc", "def"]);
+        assert_eq!(wrap_line("ab", 3), ["ab"]);
+    }
+
+    #[test]
+    fn empty_line_is_one_row() {
+        assert_eq!(wrap_line("", 80), [""]);
+        assert_eq!(wrapped_rows("", 80), 1);
+    }
+
+    #[test]
+    fn counts_chars_not_bytes() {
+        // Each of these is two bytes in UTF-8
+        assert_eq!(wrap_line("äöüß", 3), ["äöü", "ß"]);
+        assert_eq!(wrapped_rows("äöüß", 3), 2);
+    }
+
+    #[test]
+    fn row_count_matches_wrap() {
+        for line in ["", "a", "abc", "abcd", "abcdefghijklmnop"] {
+            for cols in 1..6 {
+                assert_eq!(wrapped_rows(line, cols), wrap_line(line, cols).len(), "{:?} at {}", line, cols);
+            }
+        }
+    }
+
+    #[test]
+    fn zero_columns_act_as_one() {
+        assert_eq!(wrap_line("ab", 0), ["a", "b"]);
+        assert_eq!(wrapped_rows("ab", 0), 2);
+    }
+
+    #[test]
+    fn wraps_a_buffer_into_rows() {
+        let buf = GapBuffer::from_bytes(b"abcdefg\n\nabc".to_vec());
+        let wrapped = WrappedRows::new(&buf, 3);
+        // A line that fills its last row exactly leaves an empty one for the caret
+        assert_eq!(wrapped.rows(), [(0, 3), (3, 6), (6, 7), (8, 8), (9, 12), (12, 12)]);
+    }
+
+    #[test]
+    fn soft_wrap_belongs_to_the_next_row() {
+        let buf = GapBuffer::from_bytes(b"abcdef\nx".to_vec());
+        let wrapped = WrappedRows::new(&buf, 3);
+        assert_eq!(wrapped.row_of(2), 0);
+        assert_eq!(wrapped.row_of(3), 1);
+        assert_eq!(wrapped.row_of(6), 2);
+        assert_eq!(wrapped.row_of(7), 3);
+    }
+
+    #[test]
+    fn edits_match_a_full_rewrap() {
+        let mut buf = GapBuffer::from_bytes(b"first line\nsecond\n\nfourth line here".to_vec());
+        let mut wrapped = WrappedRows::new(&buf, 4);
+        // A fixed pseudo-random walk of inserts and deletes, newlines included
+        let mut seed: u32 = 12345;
+        for _ in 0..500 {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            let mut pos = (seed >> 8) as usize % (buf.len() + 1);
+            while pos < buf.len() && buf.byte_at(pos) & 0xC0 == 0x80 { pos -= 1; }
+            if seed.is_multiple_of(3) && pos < buf.len() {
+                let n = buf.next_char_len(pos) + if seed.is_multiple_of(2) { buf.next_char_len(pos + 1) } else { 0 };
+                buf.delete(pos, n);
+                wrapped.edited(&buf, pos, n, 0);
+            } else {
+                let text = ["a", "\n", "xyz", "é\nq"][(seed >> 4) as usize % 4];
+                buf.insert_str(pos, text);
+                wrapped.edited(&buf, pos, 0, text.len());
+            }
+            assert_eq!(wrapped.rows(), WrappedRows::new(&buf, 4).rows());
+        }
+    }
+}
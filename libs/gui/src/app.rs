@@ -2,22 +2,50 @@ use alloc::string::String;
 use nyx_api::*;
 use crate::canvas::Canvas;
 
+// Frame budget of the run loop (~60 fps)
+const FRAME_MS: u64 = 16;
+
+pub const COMPOSITOR_PID: u64 = 4;
+
 pub trait NyxApp {
     fn title(&self) -> &str;
     fn initial_width(&self) -> usize { 640 }
     fn initial_height(&self) -> usize { 400 }
+    // WIN_FLAG_* bits for the window header
+    fn window_flags(&self) -> u32 { WIN_FLAG_NONE }
     
     fn init(&mut self) {}
     
     fn update(&mut self) -> bool { false }
     fn draw(&mut self, canvas: &mut Canvas);
     fn on_mouse(&mut self, _mx: usize, _my: usize, _clicked: bool) -> bool { false }
+    // Follows the on_mouse of the first click
+    fn on_double_click(&mut self, _mx: usize, _my: usize) -> bool { false }
+    // Wheel turned over the window; positive notches scroll up / away from the user
+    fn on_wheel(&mut self, _mx: usize, _my: usize, _notches: i32) -> bool { false }
     fn on_key(&mut self, _key: char) -> bool { false }
+    // IPC messages the run loop doesn't handle itself
+    fn on_message(&mut self, _msg: &IpcMessage) -> bool { false }
+
+    // Drag and drop: return a payload (usually a path) to start dragging from (mx, my)
+    fn begin_drag(&mut self, _mx: usize, _my: usize) -> Option<String> { None }
+    // Payload dropped onto this window. Returns true if it needs a redraw.
+    fn accept_drop(&mut self, _payload: &str) -> bool { false }
+    
+    // Ctrl+<letter or digit>, lowercased. Return false to let the key through to on_key_event.
+    fn on_shortcut(&mut self, _key: char, _shift: bool) -> bool { false }
+    // Clipboard text asked for with Clipboard::request_paste
+    fn on_paste(&mut self, _text: &str) -> bool { false }
+
+    // Full key events (modifiers, releases). By default only presses with a char reach on_key.
+    fn on_key_event(&mut self, event: KeyEvent) -> bool {
+        if event.pressed && event.ch != '\0' { self.on_key(event.ch) } else { false }
+    }
 }
 
 pub fn run<T: NyxApp>(mut app: T) -> ! {
-    const COMPOSITOR_PID: u64 = 4;
-    
+    // Before the size is asked for, so an app can open bigger at 2x
+    crate::scale::load();
     let mut width = app.initial_width();
     let mut height = app.initial_height();
     
@@ -33,21 +61,22 @@ pub fn run<T: NyxApp>(mut app: T) -> ! {
     header.requested_y = -1;
     header.width = width as u32;
     header.height = height as u32;
-    header.flags = WIN_FLAG_NONE;
+    header.flags = app.window_flags();
     
     let title_bytes = app.title().as_bytes();
     header.title.fill(0);
     let len = title_bytes.len().min(64);
     header.title[..len].copy_from_slice(&title_bytes[..len]);
 
-    if !sys_ipc_send(COMPOSITOR_PID, MSG_REQ_WINDOW, shm_id, 0) { sys_exit(1); }
+    if sys_ipc_send(COMPOSITOR_PID, MSG_REQ_WINDOW, shm_id, 0).is_err() { sys_exit(1); }
     let mut msg = IpcMessage { sender_pid: 0, msg_type: 0, data1: 0, data2: 0 };
     loop { 
-        if sys_ipc_recv(&mut msg, true) && msg.msg_type == MSG_WINDOW_CREATED { break; } 
+        if sys_ipc_recv(&mut msg, true).is_ok() && msg.msg_type == MSG_WINDOW_CREATED { break; } 
     }
 
     let mut pixels_ptr = unsafe { buffer_ptr.add(core::mem::size_of::<WindowHeader>()) } as *mut u32;
     
+    crate::theme::load();
     app.init();
 
     let mut needs_redraw = true;
@@ -56,9 +85,11 @@ pub fn run<T: NyxApp>(mut app: T) -> ! {
     let mut pending_shm_swap: Option<u64> = None;
 
     loop {
+        let frame_start = sys_get_time();
         let mut event_redraw = false;
 
-        if sys_ipc_recv(&mut msg, false) {
+        let had_msg = sys_ipc_recv(&mut msg, false).is_ok();
+        if had_msg {
             match msg.msg_type {
                 MSG_WINDOW_CLOSE => sys_exit(0),
                 MSG_WINDOW_RESIZED => {
@@ -85,12 +116,50 @@ pub fn run<T: NyxApp>(mut app: T) -> ! {
                 MSG_MOUSE_EVENT => {
                     event_redraw |= app.on_mouse(msg.data1 as usize, msg.data2 as usize, true);
                 },
+                MSG_MOUSE_DOUBLE_CLICK => {
+                    event_redraw |= app.on_double_click(msg.data1 as usize, msg.data2 as usize);
+                },
+                MSG_MOUSE_WHEEL => {
+                    let (mx, my) = ((msg.data2 >> 32) as usize, (msg.data2 & 0xFFFF_FFFF) as usize);
+                    event_redraw |= app.on_wheel(mx, my, msg.data1 as i64 as i32);
+                },
                 MSG_KEY_EVENT => {
-                    if let Some(key) = core::char::from_u32(msg.data1 as u32) {
+                    if let Some(event) = KeyEvent::from_packed(msg.data2) {
+                        let shortcut = event.pressed && event.ctrl() && event.ch.is_ascii_alphanumeric();
+                        if shortcut && app.on_shortcut(event.ch.to_ascii_lowercase(), event.shift()) {
+                            event_redraw = true;
+                        } else {
+                            event_redraw |= app.on_key_event(event);
+                        }
+                    } else if let Some(key) = core::char::from_u32(msg.data1 as u32) {
                         event_redraw |= app.on_key(key);
                     }
                 },
-                _ => {}
+                MSG_DRAG_BEGIN => {
+                    match app.begin_drag(msg.data1 as usize, msg.data2 as usize) {
+                        Some(payload) => { let _ = sys_ipc_send_str(COMPOSITOR_PID, MSG_DRAG_PAYLOAD, &payload); },
+                        None => { let _ = sys_ipc_send(COMPOSITOR_PID, MSG_DRAG_PAYLOAD, 0, 0); },
+                    }
+                },
+                MSG_CLIPBOARD_DATA => {
+                    if let Some(redraw) = ipc_read_str(&msg, |text| app.on_paste(text)) { event_redraw |= redraw; }
+                },
+                MSG_DROP => {
+                    if let Some(redraw) = ipc_read_str(&msg, |payload| app.accept_drop(payload)) { event_redraw |= redraw; }
+                },
+                MSG_THEME_CHANGED => {
+                    crate::theme::set(msg.data1 as usize, msg.data2 != 0);
+                    // Apps that cache anything theme-colored get to see it too
+                    app.on_message(&msg);
+                    event_redraw = true;
+                },
+                MSG_UI_SCALE_CHANGED => {
+                    crate::scale::set(msg.data1 as usize);
+                    // For layout worked out ahead of a draw
+                    app.on_message(&msg);
+                    event_redraw = true;
+                },
+                _ => { event_redraw |= app.on_message(&msg); }
             }
         }
 
@@ -105,16 +174,20 @@ pub fn run<T: NyxApp>(mut app: T) -> ! {
             
             // 2. NOW safely tell the Compositor the buffer is ready
             if let Some(shm_id) = pending_shm_swap {
-                sys_ipc_send(COMPOSITOR_PID, MSG_WINDOW_UPDATE_SHM, shm_id, 0);
+                let _ = sys_ipc_send(COMPOSITOR_PID, MSG_WINDOW_UPDATE_SHM, shm_id, 0);
                 pending_shm_swap = None;
             } else {
                 // If it wasn't a resize event, just flush a normal frame update
-                sys_ipc_send(COMPOSITOR_PID, MSG_FLUSH_WINDOW, 0, 0);
+                let _ = sys_ipc_send(COMPOSITOR_PID, MSG_FLUSH_WINDOW, 0, 0);
             }
             
             needs_redraw = false;
         }
         
-        sys_sleep_ms(16);
+        // Drain queued messages back to back; otherwise sleep out the rest of the frame
+        if !had_msg {
+            let spent = sys_get_time().wrapping_sub(frame_start) as u64;
+            sys_sleep_ms(FRAME_MS.saturating_sub(spent).max(1));
+        }
     }
 }
\ No newline at end of file
@@ -0,0 +1,71 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use nyx_api::{sys_fs_read, sys_fs_size};
+use nyx_core::bmp::{BmpError, BmpInfo, HEADER_LEN};
+
+// ─────────────────────────────────────────────────────────────────────────
+// BMP FILES
+// Reads BMPs through the fs syscalls one row at a time, scaling (nearest
+// neighbour) as it goes, so only the decoded image has to fit in the heap.
+// The header checks and pixel layout are nyx_core::bmp's.
+// ─────────────────────────────────────────────────────────────────────────
+
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u32>,
+}
+
+/// Reads and checks the header of `path`.
+pub fn open(path: &str) -> Result<BmpInfo, BmpError> {
+    let size = sys_fs_size(path).map_err(|_| BmpError::Io)?;
+    let mut header = [0u8; HEADER_LEN];
+    match sys_fs_read(path, &mut header, 0) {
+        Ok(n) if n == HEADER_LEN => BmpInfo::parse(&header, size),
+        Ok(_) => Err(BmpError::NotBmp),
+        Err(_) => Err(BmpError::Io),
+    }
+}
+
+/// Where decode_into() puts the image: a `w` x `h` box with its top-left
+/// corner at (x, y) in `out`, a buffer `stride` pixels wide.
+pub struct Dest<'a> {
+    pub out: &'a mut [u32],
+    pub stride: usize,
+    pub x: usize,
+    pub y: usize,
+    pub w: usize,
+    pub h: usize,
+}
+
+/// Streams `path` into `dst`, scaled to fill its box.
+pub fn decode_into(path: &str, info: &BmpInfo, dst: Dest) -> Result<(), BmpError> {
+    let Dest { out, stride, x, y, w: dst_w, h: dst_h } = dst;
+    let mut row = vec![0u8; info.row_stride];
+    let mut loaded_row = usize::MAX;
+    for dy in 0..dst_h {
+        let sy = dy * info.height / dst_h;
+        if sy != loaded_row {
+            match sys_fs_read(path, &mut row, info.row_offset(sy)) {
+                Ok(n) if n == info.row_stride => {},
+                Ok(_) => return Err(BmpError::Truncated),
+                Err(_) => return Err(BmpError::Io),
+            }
+            loaded_row = sy;
+        }
+        let start = (y + dy) * stride + x;
+        for (dx, pixel) in out[start..start + dst_w].iter_mut().enumerate() {
+            *pixel = info.pixel(&row, dx * info.width / dst_w);
+        }
+    }
+    Ok(())
+}
+
+/// Loads `path` at its own size, or shrunk to at most `max_pixels` pixels.
+pub fn load(path: &str, max_pixels: usize) -> Result<Image, BmpError> {
+    let info = open(path)?;
+    let (width, height) = nyx_core::bmp::fit_pixels(info.width, info.height, max_pixels);
+    let mut pixels = vec![0u32; width * height];
+    decode_into(path, &info, Dest { out: &mut pixels, stride: width, x: 0, y: 0, w: width, h: height })?;
+    Ok(Image { width, height, pixels })
+}
@@ -1,8 +1,9 @@
-use crate::effects::{alpha_blend, apply_opacity};
+use crate::effects::{alpha_blend, apply_opacity, blend_color};
+use crate::pixel;
 
 // Import x86_64 SIMD Intrinsics
 #[cfg(target_arch = "x86_64")]
-use core::arch::x86_64::{__m128i, _mm_loadu_si128, _mm_set1_epi32, _mm_storeu_si128};
+use core::arch::x86_64::{__m128i, _mm_and_si128, _mm_cmpeq_epi32, _mm_loadu_si128, _mm_movemask_epi8, _mm_set1_epi32, _mm_storeu_si128};
 
 pub struct Color;
 impl Color {
@@ -16,6 +17,7 @@ impl Color {
     pub const ACCENT_PRIMARY: u32 = 0xFF_E67E22; 
     pub const ACCENT_HOVER: u32   = 0xFF_D35400; 
     pub const ACCENT_GREEN: u32   = 0xFF_27AE60; 
+    pub const ACCENT_RED: u32     = 0xFF_C0392B; 
     
     pub const BLACK: u32 = 0xFF_000000;
     pub const WHITE: u32 = 0xFF_FFFFFF;
@@ -26,16 +28,49 @@ pub struct Canvas<'a> {
     pub buffer: &'a mut [u32],
     pub width: usize,
     pub height: usize,
+    // Drawing outside this rectangle is discarded (exclusive max bounds)
+    clip_min_x: usize, clip_min_y: usize,
+    clip_max_x: usize, clip_max_y: usize,
+}
+
+/// Blends an ARGB source pixel over an opaque destination using its own alpha byte.
+#[inline(always)]
+fn blend_pixel(src: u32, dst: u32) -> u32 {
+    let a = pixel::alpha(src);
+    if a == 255 { return src; }
+    pixel::opaque(blend_color(src, dst, a))
 }
 
 impl<'a> Canvas<'a> {
     pub fn new(buffer: &'a mut [u32], width: usize, height: usize) -> Self {
-        Self { buffer, width, height }
+        Self { buffer, width, height, clip_min_x: 0, clip_min_y: 0, clip_max_x: width, clip_max_y: height }
+    }
+
+    /// Restricts all drawing to the given rectangle (clamped to the canvas).
+    pub fn set_clip(&mut self, x: usize, y: usize, w: usize, h: usize) {
+        self.clip_min_x = x.min(self.width);
+        self.clip_min_y = y.min(self.height);
+        self.clip_max_x = x.saturating_add(w).min(self.width);
+        self.clip_max_y = y.saturating_add(h).min(self.height);
+    }
+
+    pub fn reset_clip(&mut self) {
+        self.set_clip(0, 0, self.width, self.height);
+    }
+
+    /// Intersects a rectangle with the clip, returning (x0, y0, x1, y1) or None if nothing is visible.
+    fn clip_rect(&self, x: usize, y: usize, w: usize, h: usize) -> Option<(usize, usize, usize, usize)> {
+        let x0 = x.max(self.clip_min_x);
+        let y0 = y.max(self.clip_min_y);
+        let x1 = x.saturating_add(w).min(self.clip_max_x);
+        let y1 = y.saturating_add(h).min(self.clip_max_y);
+        if x0 < x1 && y0 < y1 { Some((x0, y0, x1, y1)) } else { None }
     }
 
     pub fn fill_rect(&mut self, x: usize, y: usize, w: usize, h: usize, color: u32) {
-        let a = (color >> 24) & 0xFF;
+        let a = pixel::alpha(color);
         if a == 0 { return; }
+        let (x0, y0, x1, y1) = match self.clip_rect(x, y, w, h) { Some(r) => r, None => return };
 
         // --- SIMD FAST PATH FOR OPAQUE COLORS ---
         #[cfg(target_arch = "x86_64")]
@@ -44,24 +79,21 @@ impl<'a> Canvas<'a> {
                 // Broadcast our 32-bit color across all 4 slots of a 128-bit XMM register
                 let color_chunk = _mm_set1_epi32(color as i32);
 
-                for cy in 0..h {
-                    let dst_y = y + cy;
-                    if dst_y >= self.height { break; }
-                    
-                    let mut cx = 0;
-                    let dst_row_start = dst_y * self.width + x;
+                for dst_y in y0..y1 {
+                    let row = dst_y * self.width;
+                    let mut dx = x0;
 
                     // Fill 4 pixels (128 bits) per clock cycle
-                    while cx + 4 <= w && (x + cx + 4) <= self.width {
-                        let dst_ptr = self.buffer.as_mut_ptr().add(dst_row_start + cx) as *mut __m128i;
+                    while dx + 4 <= x1 {
+                        let dst_ptr = self.buffer.as_mut_ptr().add(row + dx) as *mut __m128i;
                         _mm_storeu_si128(dst_ptr, color_chunk);
-                        cx += 4;
+                        dx += 4;
                     }
 
                     // Catch the remainder pixels that didn't fit in a 4-pixel chunk
-                    while cx < w && (x + cx) < self.width {
-                        self.buffer[dst_row_start + cx] = color;
-                        cx += 1;
+                    while dx < x1 {
+                        self.buffer[row + dx] = color;
+                        dx += 1;
                     }
                 }
             }
@@ -69,65 +101,73 @@ impl<'a> Canvas<'a> {
         }
 
         // --- STANDARD PATH FOR TRANSPARENT COLORS ---
-        for cy in 0..h {
-            for cx in 0..w {
-                let idx = (y + cy) * self.width + (x + cx);
-                if idx < self.buffer.len() { 
-                    self.buffer[idx] = alpha_blend(color, self.buffer[idx]);
-                }
+        for dst_y in y0..y1 {
+            let row = dst_y * self.width;
+            for dx in x0..x1 {
+                self.buffer[row + dx] = alpha_blend(color, self.buffer[row + dx]);
             }
         }
     }
 
     pub fn composite_buffer(&mut self, x: usize, y: usize, src: &[u32], src_w: usize, src_h: usize, opacity: u8) {
         if opacity == 0 { return; }
-        
-        for cy in 0..src_h {
-            let dst_y = y + cy;
-            if dst_y >= self.height { break; }
+        let (x0, y0, x1, y1) = match self.clip_rect(x, y, src_w, src_h) { Some(r) => r, None => return };
+        if src.len() < src_w * src_h { return; }
 
-            let mut cx = 0;
-            let dst_row_start = dst_y * self.width + x;
-            let src_row_start = cy * src_w;
+        for dst_y in y0..y1 {
+            let dst_row_start = dst_y * self.width;
+            let src_row_start = (dst_y - y) * src_w;
+            let mut dx = x0;
 
             if opacity == 255 {
                 // --- SIMD FAST PATH (SSE2 MEMCPY) ---
                 #[cfg(target_arch = "x86_64")]
                 unsafe {
+                    let alpha_mask = _mm_set1_epi32(0xFF00_0000u32 as i32);
+
                     // Copy 4 pixels (128 bits) per clock cycle
-                    while cx + 4 <= src_w && (x + cx + 4) <= self.width {
-                        let src_ptr = src.as_ptr().add(src_row_start + cx) as *const __m128i;
-                        let dst_ptr = self.buffer.as_mut_ptr().add(dst_row_start + cx) as *mut __m128i;
+                    while dx + 4 <= x1 {
+                        let src_ptr = src.as_ptr().add(src_row_start + (dx - x)) as *const __m128i;
+                        let dst_ptr = self.buffer.as_mut_ptr().add(dst_row_start + dx) as *mut __m128i;
 
-                        // Load 128-bits from source, write instantly to destination
+                        // Load 128-bits from source; fully opaque chunks are written straight through
                         let chunk = _mm_loadu_si128(src_ptr);
-                        _mm_storeu_si128(dst_ptr, chunk);
+                        let opaque = _mm_cmpeq_epi32(_mm_and_si128(chunk, alpha_mask), alpha_mask);
+                        if _mm_movemask_epi8(opaque) == 0xFFFF {
+                            _mm_storeu_si128(dst_ptr, chunk);
+                        } else {
+                            for k in 0..4 {
+                                let dst = dst_row_start + dx + k;
+                                self.buffer[dst] = blend_pixel(src[src_row_start + (dx - x) + k], self.buffer[dst]);
+                            }
+                        }
 
-                        cx += 4;
+                        dx += 4;
                     }
                 }
 
                 // Catch the remainder pixels
-                while cx < src_w && (x + cx) < self.width {
-                    self.buffer[dst_row_start + cx] = src[src_row_start + cx];
-                    cx += 1;
+                while dx < x1 {
+                    let dst = dst_row_start + dx;
+                    self.buffer[dst] = blend_pixel(src[src_row_start + (dx - x)], self.buffer[dst]);
+                    dx += 1;
                 }
             } else {
                 // --- STANDARD ALPHA BLENDING PATH ---
                 // (SIMD alpha blending requires complex 8-bit to 16-bit unpacking/multiplication logic)
-                while cx < src_w && (x + cx) < self.width {
-                    let pixel = src[src_row_start + cx];
+                while dx < x1 {
+                    let pixel = src[src_row_start + (dx - x)];
                     let faded_pixel = apply_opacity(pixel, opacity);
-                    self.buffer[dst_row_start + cx] = alpha_blend(faded_pixel, self.buffer[dst_row_start + cx]);
-                    cx += 1;
+                    self.buffer[dst_row_start + dx] = alpha_blend(faded_pixel, self.buffer[dst_row_start + dx]);
+                    dx += 1;
                 }
             }
         }
     }
 
     pub fn print_str(&mut self, mut cx: usize, mut cy: usize, text: &str, color: u32, scale: usize) {
-        let font_w = crate::font::CHAR_WIDTH * scale;
-        let font_h = crate::font::CHAR_HEIGHT * scale;
+        let font_w = crate::font::char_width(scale);
+        let font_h = crate::font::char_height(scale);
         
         let start_x = cx; 
         
@@ -140,7 +180,7 @@ impl<'a> Canvas<'a> {
                 cx += font_w;
             }
             
-            if cx + font_w >= self.width - 10 {
+            if cx + font_w >= self.width.saturating_sub(10) {
                 cx = start_x;
                 cy += font_h;
             }
@@ -148,24 +188,26 @@ impl<'a> Canvas<'a> {
     }
 
     pub fn draw_char(&mut self, x: usize, y: usize, c: char, color: u32, scale: usize) {
-        if let Some(raster) = crate::font::get_char_raster(c) {
-            for (ri, row) in raster.raster().iter().enumerate() {
-                for (ci, val) in row.iter().enumerate() {
-                    if *val > 50 { 
-                        for dy in 0..scale {
-                            for dx in 0..scale {
-                                let px = x + (ci * scale) + dx;
-                                let py = y + (ri * scale) + dy;
-                                
-                                if px < self.width && py < self.height {
-                                    let idx = py * self.width + px;
-                                    self.buffer[idx] = color; 
-                                }
-                            }
-                        }
-                    }
-                }
+        let scale = scale.max(1);
+        crate::font::with_runs(c, |runs| {
+            for run in runs {
+                let px = x + run.x as usize * scale;
+                let py = y + run.y as usize * scale;
+                self.fill_opaque(px, py, run.len as usize * scale, scale, color);
             }
+        });
+    }
+
+    // Glyphs are written straight over what's there, not blended
+    fn fill_opaque(&mut self, x: usize, y: usize, w: usize, h: usize, color: u32) {
+        let x0 = x.max(self.clip_min_x);
+        let x1 = x.saturating_add(w).min(self.clip_max_x);
+        let y0 = y.max(self.clip_min_y);
+        let y1 = y.saturating_add(h).min(self.clip_max_y);
+        if x0 >= x1 { return; }
+        for py in y0..y1 {
+            let row = py * self.width;
+            self.buffer[row + x0..row + x1].fill(color);
         }
     }
 }
\ No newline at end of file
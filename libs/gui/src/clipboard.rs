@@ -0,0 +1,37 @@
+use nyx_api::*;
+use crate::app::COMPOSITOR_PID;
+
+// ─────────────────────────────────────────────────────────────────────────
+// CLIPBOARD
+// Text shared between apps. For now the compositor holds it: copy hands it
+// a string, paste asks for it and the answer arrives later through
+// NyxApp::on_paste. Apps only see the Clipboard trait, so a kernel-backed
+// clipboard syscall can replace the compositor without touching them.
+// ─────────────────────────────────────────────────────────────────────────
+
+/// Longest text the clipboard holds: one IPC string page
+pub const CLIPBOARD_MAX: usize = 4096;
+
+pub trait Clipboard {
+    /// Replaces the contents; text past CLIPBOARD_MAX is cut at a char boundary
+    fn copy(&mut self, text: &str);
+    /// Asks for the contents, which are delivered to NyxApp::on_paste
+    fn request_paste(&mut self);
+}
+
+/// The clipboard apps use
+pub type SystemClipboard = CompositorClipboard;
+
+pub struct CompositorClipboard;
+
+impl Clipboard for CompositorClipboard {
+    fn copy(&mut self, text: &str) {
+        let mut end = text.len().min(CLIPBOARD_MAX);
+        while !text.is_char_boundary(end) { end -= 1; }
+        let _ = sys_ipc_send_str(COMPOSITOR_PID, MSG_CLIPBOARD_SET, &text[..end]);
+    }
+
+    fn request_paste(&mut self) {
+        let _ = sys_ipc_send(COMPOSITOR_PID, MSG_CLIPBOARD_GET, 0, 0);
+    }
+}
@@ -1,5 +1,7 @@
 use crate::font;
 use crate::effects::{blend_color, box_blur};
+use crate::wallpaper::Wallpaper;
+use nyx_core::rect::{self, Area};
 
 /// Draws a simple solid color rectangle
 pub fn draw_rect_simple(fb: &mut [u32], w: usize, h: usize, x: usize, y: usize, rw: usize, rh: usize, color: u32) {
@@ -36,7 +38,7 @@ pub fn draw_text(fb: &mut [u32], w: usize, h: usize, x: usize, y: usize, text: &
     let mut cx = x;
     for c in text.chars() {
         draw_char(fb, w, h, cx, y, c, color);
-        cx += font::CHAR_WIDTH;
+        cx += font::char_width(1);
     }
 }
 
@@ -69,17 +71,21 @@ pub fn draw_rect(
     }
 }
 
-/// Restores the wallpaper for a specific dirty rectangle.
-/// UPDATED: Sets the background to PITCH BLACK (0xFF000000) as requested.
-pub fn restore_wallpaper_rect(fb: &mut [u32], w: usize, h: usize, x: usize, y: usize, dw: usize, dh: usize) {
-    for row in 0..dh {
-        let sy = y + row;
-        if sy >= h { break; }
-        // Optimization: Standard loop for safety
-        for col in 0..dw {
-            let sx = x + col;
-            if sx >= w { break; }
-            fb[sy * w + sx] = 0xFF000000; // Pure Black
+/// Restores the wallpaper for a specific dirty area (x0, y0, x1, y1).
+/// Copies from the cached image when one is loaded, otherwise fills with PITCH BLACK (0xFF000000).
+pub fn restore_wallpaper_rect(fb: &mut [u32], w: usize, h: usize, area: Area, wallpaper: Option<&Wallpaper>) {
+    let (x, y, x_end, y_end) = rect::clip(area, w, h);
+    if x >= x_end { return; }
+
+    for sy in y..y_end {
+        let dst = &mut fb[sy * w + x..sy * w + x_end];
+
+        match wallpaper {
+            Some(wp) if sy < wp.height && x_end <= wp.width => dst.copy_from_slice(&wp.row(sy)[x..x_end]),
+            Some(wp) => {
+                for (i, px) in dst.iter_mut().enumerate() { *px = wp.pixel(x + i, sy).unwrap_or(0xFF000000); }
+            },
+            None => dst.fill(0xFF000000), // Pure Black
         }
     }
 }
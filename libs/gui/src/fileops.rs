@@ -0,0 +1,128 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use nyx_api::*;
+use nyx_core::path;
+
+// ─────────────────────────────────────────────────────────────────────────
+// FILE COPIES
+// The Terminal's cp and mv and the Explorer's Paste. A copy moves one chunk
+// per step(), so a multi-MB file never needs more heap than a chunk and the
+// Explorer can redraw its progress bar in between. The first chunk truncates
+// the destination; if any chunk fails the partial destination is deleted, so
+// a half-copied file is never left looking like a whole one. Only files are
+// copied, not directories.
+// ─────────────────────────────────────────────────────────────────────────
+
+/// Bytes per step; reads this big become multi-block NVMe commands
+pub const COPY_CHUNK: usize = 64 * 1024;
+
+pub struct FileCopy {
+    src: String,
+    dst: String,
+    size: usize,
+    copied: usize,
+    // Nothing is written until the first step, even for an empty file
+    started: bool,
+    // A move across mounts: the source goes once the copy is complete
+    delete_source: bool,
+    buf: Vec<u8>,
+}
+
+impl FileCopy {
+    /// Fails with IsDir for a directory (or anything without a size) and
+    /// Invalid when both paths are the same file.
+    pub fn new(src: &str, dst: &str) -> NyxResult<Self> {
+        // Names resolve case-insensitively, so "a.txt" onto "A.TXT" would truncate the source
+        if path::normalize(src).eq_ignore_ascii_case(&path::normalize(dst)) { return Err(NyxError::Invalid); }
+        let size = match sys_fs_size(src) {
+            Ok(size) => size,
+            Err(_) if is_dir(src) => return Err(NyxError::IsDir),
+            Err(e) => return Err(e),
+        };
+        Ok(Self {
+            src: String::from(src),
+            dst: String::from(dst),
+            size,
+            copied: 0,
+            started: false,
+            delete_source: false,
+            buf: alloc::vec![0u8; size.min(COPY_CHUNK)],
+        })
+    }
+
+    pub fn size(&self) -> usize { self.size }
+    pub fn copied(&self) -> usize { self.copied }
+    pub fn source(&self) -> &str { &self.src }
+    pub fn destination(&self) -> &str { &self.dst }
+    pub fn is_move(&self) -> bool { self.delete_source }
+
+    /// Copies the next chunk; Ok(true) once the whole file is across (and,
+    /// for a move, the source is gone). After an error the copy is over.
+    pub fn step(&mut self) -> NyxResult<bool> {
+        match self.copy_chunk() {
+            Ok(false) => Ok(false),
+            Ok(true) if self.delete_source => {
+                // The copy is whole by now, so it stays even if this fails
+                sys_fs_delete(&self.src)?;
+                Ok(true)
+            },
+            Ok(true) => Ok(true),
+            Err(e) => {
+                if self.started { let _ = sys_fs_delete(&self.dst); }
+                Err(e)
+            },
+        }
+    }
+
+    fn copy_chunk(&mut self) -> NyxResult<bool> {
+        let want = (self.size - self.copied).min(COPY_CHUNK);
+        let n = if want == 0 { 0 } else { sys_fs_read(&self.src, &mut self.buf[..want], self.copied)? };
+        // The source shrank under us
+        if n == 0 && want > 0 { return Err(NyxError::Io); }
+        let flags = if self.started { 0 } else { FS_WRITE_TRUNCATE };
+        self.started = true;
+        if sys_fs_write(&self.dst, &self.buf[..n], self.copied, flags)? != n { return Err(NyxError::DiskFull); }
+        self.copied += n;
+        Ok(self.copied == self.size)
+    }
+
+    /// Stops part way and deletes what was written.
+    pub fn cancel(self) {
+        if self.started && self.copied < self.size { let _ = sys_fs_delete(&self.dst); }
+    }
+}
+
+/// Moves `src` to `dst`: a rename when both are on one mount, which is done
+/// by the time this returns (None); otherwise a copy that deletes the source
+/// when it completes, for the caller to step through.
+pub fn move_file(src: &str, dst: &str) -> NyxResult<Option<FileCopy>> {
+    match sys_fs_rename(src, dst) {
+        Ok(()) => Ok(None),
+        Err(NyxError::CrossDevice) => {
+            let mut copy = FileCopy::new(src, dst)?;
+            copy.delete_source = true;
+            Ok(Some(copy))
+        },
+        Err(e) => Err(e),
+    }
+}
+
+/// Runs a copy to the end with no progress shown.
+pub fn copy_all(copy: &mut FileCopy) -> NyxResult<()> {
+    while !copy.step()? {}
+    Ok(())
+}
+
+/// Whether `path` is a directory that can be listed.
+pub fn is_dir(path: &str) -> bool {
+    sys_fs_opendir(path).map(|fd| { let _ = sys_close(fd); }).is_ok()
+}
+
+/// Where copying or moving `src` to `dst` puts it: inside `dst` when that's
+/// an existing directory, like cp and mv elsewhere.
+pub fn destination_path(src: &str, dst: &str) -> String {
+    if is_dir(dst) { path::join(dst, path::file_name(src)) } else { String::from(dst) }
+}
+
+/// A file is already at `path` (a directory doesn't count).
+pub fn file_exists(path: &str) -> bool { sys_fs_size(path).is_ok() }
@@ -1,8 +1,79 @@
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicBool, Ordering};
 use noto_sans_mono_bitmap::{get_raster, FontWeight, RasterHeight, RasterizedChar};
 
-pub const CHAR_WIDTH: usize = 9;
-pub const CHAR_HEIGHT: usize = 16;
+// ─────────────────────────────────────────────────────────────────────────
+// FONT
+// Noto Sans Mono at 16 px, drawn at any whole-number scale by doubling (or
+// tripling...) its pixels. What Canvas draws from is each glyph reduced to
+// the horizontal runs of its covered pixels, so a 2x glyph is a handful of
+// 2-pixel-tall fills rather than four writes per source pixel. Runs are
+// worked out once per codepoint and kept, like the kernel's glyph cache;
+// codepoints past the table, or a draw that finds the table busy (a second
+// thread mid-draw), are worked out again on the spot.
+// ─────────────────────────────────────────────────────────────────────────
+
+/// Glyph cell at 1x; chars advance by this much
+const BASE_WIDTH: usize = 9;
+const BASE_HEIGHT: usize = 16;
+/// Coverage above which a pixel is drawn
+const THRESHOLD: u8 = 50;
+/// ASCII, Latin-1 and Latin Extended-A
+const CACHED_CODEPOINTS: usize = 0x180;
+
+/// Advance of one char drawn at `scale`.
+pub fn char_width(scale: usize) -> usize { BASE_WIDTH * scale.max(1) }
+
+/// Height of one char drawn at `scale`.
+pub fn char_height(scale: usize) -> usize { BASE_HEIGHT * scale.max(1) }
 
 pub fn get_char_raster(c: char) -> Option<RasterizedChar> {
     get_raster(c, FontWeight::Regular, RasterHeight::Size16)
-}
\ No newline at end of file
+}
+
+/// Covered pixels `len` long from column `x` of row `y`, at 1x
+#[derive(Clone, Copy)]
+pub struct Run {
+    pub y: u8,
+    pub x: u8,
+    pub len: u8,
+}
+
+fn runs_of(c: char) -> Vec<Run> {
+    let mut runs = Vec::new();
+    let Some(raster) = get_char_raster(c) else { return runs };
+    for (y, row) in raster.raster().iter().enumerate() {
+        let mut x = 0;
+        while x < row.len() {
+            if row[x] <= THRESHOLD { x += 1; continue; }
+            let start = x;
+            while x < row.len() && row[x] > THRESHOLD { x += 1; }
+            runs.push(Run { y: y as u8, x: start as u8, len: (x - start) as u8 });
+        }
+    }
+    runs
+}
+
+struct RunCache {
+    busy: AtomicBool,
+    glyphs: UnsafeCell<Vec<Option<Vec<Run>>>>,
+}
+
+// Only touched by whoever set `busy`
+unsafe impl Sync for RunCache {}
+
+static CACHE: RunCache = RunCache { busy: AtomicBool::new(false), glyphs: UnsafeCell::new(Vec::new()) };
+
+/// Runs `f` on the runs of `c`; empty for a char the font doesn't have.
+pub fn with_runs<R>(c: char, f: impl FnOnce(&[Run]) -> R) -> R {
+    let code = c as usize;
+    if code < CACHED_CODEPOINTS && !CACHE.busy.swap(true, Ordering::Acquire) {
+        let glyphs = unsafe { &mut *CACHE.glyphs.get() };
+        if glyphs.is_empty() { glyphs.resize_with(CACHED_CODEPOINTS, || None); }
+        let result = f(glyphs[code].get_or_insert_with(|| runs_of(c)));
+        CACHE.busy.store(false, Ordering::Release);
+        return result;
+    }
+    f(&runs_of(c))
+}
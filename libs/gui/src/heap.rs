@@ -0,0 +1,47 @@
+use core::alloc::{GlobalAlloc, Layout};
+use core::ptr::{null_mut, NonNull};
+
+use linked_list_allocator::LockedHeap;
+use nyx_api::sys_brk;
+
+// ─────────────────────────────────────────────────────────────────────────
+// BRK HEAP
+// A linked-list heap that sits on the program break. When an allocation
+// doesn't fit, the break is pushed out and the new space appended, so apps
+// start small and only ever pay for pages they touch.
+// ─────────────────────────────────────────────────────────────────────────
+const GROW_STEP: usize = 256 * 1024;
+
+pub struct BrkHeap(LockedHeap);
+
+impl BrkHeap {
+    pub const fn empty() -> Self { Self(LockedHeap::empty()) }
+
+    /// Places the heap at the current break with `initial` bytes. False if the
+    /// kernel refused to move the break.
+    pub fn init(&self, initial: usize) -> bool {
+        let base = sys_brk(0);
+        let end = base + initial as u64;
+        if sys_brk(end) != end { return false; }
+        unsafe { self.0.lock().init(base as *mut u8, initial); }
+        true
+    }
+}
+
+unsafe impl GlobalAlloc for BrkHeap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let mut heap = self.0.lock();
+        if let Ok(ptr) = heap.allocate_first_fit(layout) { return ptr.as_ptr(); }
+
+        // Room for the block plus worst-case alignment, in whole pages
+        let grow = ((layout.size() + layout.align()).max(GROW_STEP) + 0xFFF) & !0xFFF;
+        let new_end = heap.top() as u64 + grow as u64;
+        if sys_brk(new_end) != new_end { return null_mut(); }
+        heap.extend(grow);
+        heap.allocate_first_fit(layout).map_or(null_mut(), |ptr| ptr.as_ptr())
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.0.lock().deallocate(NonNull::new_unchecked(ptr), layout);
+    }
+}
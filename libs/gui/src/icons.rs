@@ -0,0 +1,84 @@
+use crate::canvas::{Canvas, Color};
+
+// ─────────────────────────────────────────────────────────────────────────
+// FILE ICONS
+// Entries are classified by name alone (directory listings carry a
+// trailing '/' for folders), then drawn as a 32x32 procedural icon so the
+// Explorer and the desktop agree on what each type looks like.
+// ─────────────────────────────────────────────────────────────────────────
+pub const ICON_SIZE: usize = 32;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FileKind {
+    Directory,
+    Text,
+    Image,
+    Executable,
+    Other,
+}
+
+impl FileKind {
+    /// Classifies a listing entry. Names ending in '/' are directories,
+    /// everything else goes by its (case-insensitive) extension.
+    pub fn classify(name: &str) -> Self {
+        if name.ends_with('/') { return FileKind::Directory; }
+        let ext = match nyx_core::path::extension(name) { Some(e) => e, None => return FileKind::Other };
+        let is = |e: &str| ext.eq_ignore_ascii_case(e);
+        if is("txt") || is("md") { FileKind::Text }
+        else if is("bmp") { FileKind::Image }
+        else if is("bin") || is("elf") { FileKind::Executable }
+        else { FileKind::Other }
+    }
+}
+
+/// Draws the icon for `kind` with its top-left corner at (x, y).
+pub fn draw_file_icon(canvas: &mut Canvas, x: usize, y: usize, kind: FileKind) {
+    match kind {
+        FileKind::Directory => {
+            // Tab, then the folder body, in the accent color
+            let theme = crate::theme::current();
+            canvas.fill_rect(x + 2, y + 5, 12, 5, theme.accent_hover);
+            canvas.fill_rect(x + 2, y + 9, 28, 19, theme.accent);
+            canvas.fill_rect(x + 2, y + 9, 28, 2, theme.accent_hover);
+        }
+        FileKind::Text => {
+            draw_page(canvas, x, y);
+            for i in 0..5 {
+                let w = if i == 4 { 10 } else { 16 };
+                canvas.fill_rect(x + 9, y + 8 + i * 4, w, 2, Color::TEXT_MUTED);
+            }
+        }
+        FileKind::Image => {
+            canvas.fill_rect(x + 2, y + 5, 28, 22, Color::WARM_BORDER);
+            canvas.fill_rect(x + 3, y + 6, 26, 20, 0xFF_AED6F1);
+            // Sun
+            canvas.fill_rect(x + 21, y + 9, 4, 4, 0xFF_F4D03F);
+            // Mountain, one widening row at a time
+            for row in 0..10 {
+                let half = row + 1;
+                canvas.fill_rect(x + 13 - half.min(10), y + 15 + row, half.min(10) * 2, 1, Color::ACCENT_GREEN);
+            }
+            canvas.fill_rect(x + 3, y + 25, 26, 1, Color::ACCENT_GREEN);
+        }
+        FileKind::Executable => {
+            // A gear: hub plus four teeth, with a hole punched in the middle
+            let gear = Color::TEXT_MUTED;
+            canvas.fill_rect(x + 8, y + 8, 16, 16, gear);
+            canvas.fill_rect(x + 13, y + 3, 6, 26, gear);
+            canvas.fill_rect(x + 3, y + 13, 26, 6, gear);
+            canvas.fill_rect(x + 6, y + 6, 4, 4, gear);
+            canvas.fill_rect(x + 22, y + 6, 4, 4, gear);
+            canvas.fill_rect(x + 6, y + 22, 4, 4, gear);
+            canvas.fill_rect(x + 22, y + 22, 4, 4, gear);
+            canvas.fill_rect(x + 13, y + 13, 6, 6, Color::WARM_SURFACE);
+        }
+        FileKind::Other => draw_page(canvas, x, y),
+    }
+}
+
+// Blank sheet with a folded corner
+fn draw_page(canvas: &mut Canvas, x: usize, y: usize) {
+    canvas.fill_rect(x + 5, y + 2, 22, 28, Color::WARM_BORDER);
+    canvas.fill_rect(x + 6, y + 3, 20, 26, Color::WHITE);
+    canvas.fill_rect(x + 20, y + 3, 6, 6, Color::WARM_BORDER);
+}
@@ -6,4 +6,14 @@ pub mod draw;
 pub mod ui;
 pub mod canvas; 
 pub mod effects;
-pub mod app;
\ No newline at end of file
+pub mod app;
+pub mod wallpaper;
+pub mod bmp;
+pub mod icons;
+pub mod heap;
+pub mod clipboard;
+pub mod state;
+pub mod theme;
+pub mod scale;
+pub mod pixel;
+pub mod fileops;
\ No newline at end of file
@@ -0,0 +1,120 @@
+use nyx_api::{FbFormat, PixelFormat};
+use nyx_core::rect::{self, Area};
+
+// ─────────────────────────────────────────────────────────────────────────
+// PIXEL PACKING
+// Everything in nyx_gui draws 0xAARRGGBB u32s, which is also the layout of
+// a 4-byte BGR framebuffer. Screens that store pixels any other way (RGB,
+// 3 bytes per pixel, greyscale) get the frame drawn off-screen and
+// converted here on its way out, one row of the dirty rectangle at a time.
+// A frame of another size than the screen (a lower resolution, or several
+// displays side by side) is scaled to it here as well, nearest-neighbour.
+// ─────────────────────────────────────────────────────────────────────────
+
+/// A framebuffer as the hardware stores it: `width` x `height` pixels in
+/// `format`, rows `stride` pixels apart.
+pub struct Framebuffer<'a> {
+    pub bytes: &'a mut [u8],
+    pub format: FbFormat,
+    pub width: usize,
+    pub height: usize,
+    pub stride: usize,
+}
+
+/// A `width` x `height` ARGB frame, rows `stride` pixels apart.
+pub struct Frame<'a> {
+    pub pixels: &'a [u32],
+    pub width: usize,
+    pub height: usize,
+    pub stride: usize,
+}
+
+/// The alpha byte of an 0xAARRGGBB pixel.
+#[inline(always)]
+pub fn alpha(argb: u32) -> u8 { (argb >> 24) as u8 }
+
+/// An 0xRRGGBB color with the alpha byte set to opaque.
+#[inline(always)]
+pub fn opaque(rgb: u32) -> u32 { 0xFF00_0000 | rgb }
+
+/// (a, r, g, b) of an 0xAARRGGBB pixel.
+#[inline(always)]
+pub fn unpack_argb(argb: u32) -> (u8, u8, u8, u8) {
+    (alpha(argb), (argb >> 16) as u8, (argb >> 8) as u8, argb as u8)
+}
+
+/// One ARGB pixel in the framebuffer's byte order; only the first
+/// bytes_per_pixel bytes are meant to be stored.
+#[inline(always)]
+pub fn pack(argb: u32, format: FbFormat) -> [u8; 4] {
+    let (a, r, g, b) = unpack_argb(argb);
+    match format.pixel_format {
+        PixelFormat::Bgr => [b, g, r, a],
+        PixelFormat::Rgb => [r, g, b, a],
+        PixelFormat::Gray => {
+            // BT.601 luma in 8.8 fixed point
+            let y = ((r as u32 * 77 + g as u32 * 150 + b as u32 * 29) >> 8) as u8;
+            [y, y, y, a]
+        },
+    }
+}
+
+/// Copies the area (x0, y0, x1, y1) of an ARGB frame into the same place
+/// in the framebuffer.
+pub fn present_rect(fb: &mut Framebuffer, src: &Frame, area: Area) {
+    let bpp = fb.format.bytes_per_pixel;
+    if bpp == 0 || bpp > 4 || src.stride == 0 || fb.stride == 0 { return; }
+    let rows = (src.pixels.len() / src.stride).min(fb.bytes.len() / (fb.stride * bpp));
+    let (x, y, x1, y1) = rect::clip(area, src.stride.min(fb.stride), rows);
+    if x >= x1 { return; }
+
+    for row in y..y1 {
+        let src_row = &src.pixels[row * src.stride + x..row * src.stride + x1];
+        let dst_row = &mut fb.bytes[(row * fb.stride + x) * bpp..(row * fb.stride + x1) * bpp];
+        if fb.format.is_native_argb() {
+            let (dst_px, _) = dst_row.as_chunks_mut::<4>();
+            for (px, out) in src_row.iter().zip(dst_px) { *out = px.to_le_bytes(); }
+        } else {
+            for (px, out) in src_row.iter().zip(dst_row.chunks_exact_mut(bpp)) { out.copy_from_slice(&pack(*px, fb.format)[..bpp]); }
+        }
+    }
+}
+
+/// The screen pixels whose nearest frame pixel lies inside an area of a
+/// src_w x src_h frame stretched over fb_w x fb_h, as (x, y, w, h).
+pub fn scaled_rect(fb_w: usize, fb_h: usize, src_w: usize, src_h: usize, area: Area) -> (usize, usize, usize, usize) {
+    let (x, y, x1, y1) = rect::clip(area, src_w, src_h);
+    if x >= x1 || y >= y1 { return (0, 0, 0, 0); }
+    let (dx0, dx1) = ((x * fb_w).div_ceil(src_w), (x1 * fb_w).div_ceil(src_w));
+    let (dy0, dy1) = ((y * fb_h).div_ceil(src_h), (y1 * fb_h).div_ceil(src_h).min(fb_h));
+    (dx0, dy0, dx1 - dx0, dy1.saturating_sub(dy0))
+}
+
+/// Scales an area of an ARGB frame over the part of the framebuffer it
+/// covers once the whole frame is stretched to fill the framebuffer.
+pub fn present_scaled(fb: &mut Framebuffer, src: &Frame, area: Area) {
+    let bpp = fb.format.bytes_per_pixel;
+    let (src_w, src_h, fb_w) = (src.width, src.height, fb.width);
+    if bpp == 0 || bpp > 4 || src_w == 0 || src_h == 0 || src_w > src.stride || fb_w > fb.stride
+        || src.pixels.len() < src.stride * src_h { return; }
+    let fb_h = fb.height.min(fb.bytes.len() / (fb.stride * bpp).max(1));
+    let (dx0, dy0, dw, dh) = scaled_rect(fb_w, fb_h, src_w, src_h, area);
+    if dw == 0 || dh == 0 { return; }
+    let dy1 = dy0 + dh;
+    let row_bytes = dw * bpp;
+
+    let mut last_row: Option<(usize, usize)> = None;
+    for dy in dy0..dy1 {
+        let sy = dy * src_h / fb_h;
+        let dst = (dy * fb.stride + dx0) * bpp;
+        // Rows stretched from the same frame row come out identical
+        if let Some((prev_sy, prev_dst)) = last_row {
+            if prev_sy == sy { fb.bytes.copy_within(prev_dst..prev_dst + row_bytes, dst); continue; }
+        }
+        let src_row = &src.pixels[sy * src.stride..sy * src.stride + src_w];
+        for (i, out) in fb.bytes[dst..dst + row_bytes].chunks_exact_mut(bpp).enumerate() {
+            out.copy_from_slice(&pack(src_row[(dx0 + i) * src_w / fb_w], fb.format)[..bpp]);
+        }
+        last_row = Some((sy, dst));
+    }
+}
@@ -0,0 +1,36 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+use nyx_core::kv::KvFile;
+
+// ─────────────────────────────────────────────────────────────────────────
+// UI SCALE
+// A whole-number factor the desktop's chrome and text are drawn at, so a 4K
+// panel isn't read through a magnifier: 1x, or 2x with every glyph pixel
+// doubled. Like the theme, each process keeps its own copy, which app::run
+// loads from the boot config (ui_scale=) and replaces when the compositor
+// broadcasts MSG_UI_SCALE_CHANGED. Layout that grows with it goes through
+// px(); anything drawn at a fixed size keeps its plain numbers.
+// ─────────────────────────────────────────────────────────────────────────
+
+pub const MAX_SCALE: usize = 2;
+
+static SCALE: AtomicUsize = AtomicUsize::new(1);
+
+/// The factor this process draws at, 1 to MAX_SCALE.
+pub fn current() -> usize { SCALE.load(Ordering::Relaxed) }
+
+pub fn set(scale: usize) {
+    SCALE.store(scale.clamp(1, MAX_SCALE), Ordering::Relaxed);
+}
+
+/// `n` pixels of 1x layout at the current scale.
+pub fn px(n: usize) -> usize { n * current() }
+
+/// The boot config's ui_scale= line; 1 if it's missing or out of range.
+pub fn from_config(config: &KvFile) -> usize {
+    config.get("ui_scale").and_then(|s| s.parse().ok()).filter(|s| (1..=MAX_SCALE).contains(s)).unwrap_or(1)
+}
+
+/// Sets the scale the boot config names.
+pub fn load() {
+    set(from_config(&crate::state::load_config()));
+}
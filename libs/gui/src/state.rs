@@ -0,0 +1,39 @@
+use nyx_api::*;
+use nyx_core::kv::KvFile;
+use crate::app::COMPOSITOR_PID;
+
+// ─────────────────────────────────────────────────────────────────────────
+// DESKTOP STATE
+// What the desktop remembers across reboots: the window layout, and the few
+// bits of app state worth restoring with it (the Explorer's folder, the file
+// open in its editor). The compositor owns the file and is the only writer,
+// so apps read it when they start and send changes through save(). The
+// compositor writes the boot config too (resolution, theme), which the
+// kernel also reads.
+// ─────────────────────────────────────────────────────────────────────────
+
+pub const STATE_PATH: &str = "/mnt/nvme/nyx_state.cfg";
+pub use nyx_core::config::CONFIG_PATH;
+/// Both files are small; anything past this is taken as garbage and dropped
+pub const STATE_MAX: usize = 16 * 1024;
+
+fn read_kv(path: &str) -> KvFile {
+    let size = match sys_fs_size(path) { Ok(size) => size.min(STATE_MAX), Err(_) => return KvFile::new() };
+    let mut buf = alloc::vec![0u8; size];
+    match sys_fs_read(path, &mut buf, 0) {
+        Ok(n) => KvFile::parse(&alloc::string::String::from_utf8_lossy(&buf[..n])),
+        Err(_) => KvFile::new(),
+    }
+}
+
+/// The state file as it is on disk; empty if it's missing or unreadable.
+pub fn load() -> KvFile { read_kv(STATE_PATH) }
+
+/// The boot config, the same way.
+pub fn load_config() -> KvFile { read_kv(CONFIG_PATH) }
+
+/// Asks the compositor to remember `value` under `key`; an empty value is
+/// kept as one, which callers read back as "nothing".
+pub fn save(key: &str, value: &str) {
+    let _ = sys_ipc_send_str(COMPOSITOR_PID, MSG_SAVE_STATE, &alloc::format!("{}={}", key, value));
+}
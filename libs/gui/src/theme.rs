@@ -0,0 +1,101 @@
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use nyx_core::kv::KvFile;
+use crate::canvas::Color;
+
+// ─────────────────────────────────────────────────────────────────────────
+// THEME
+// The colors the desktop and every app draw their chrome in: one of a few
+// accents, light or dark. Each process has its own copy, which app::run
+// loads from the boot config and replaces whenever the compositor
+// broadcasts MSG_THEME_CHANGED, so drawing code only calls current().
+// Colors that mean something (a failed save, a hot CPU) stay in Color.
+// ─────────────────────────────────────────────────────────────────────────
+
+/// (config name, accent, accent while pressed or hovered)
+pub const ACCENTS: [(&str, u32, u32); 5] = [
+    ("orange", Color::ACCENT_PRIMARY, Color::ACCENT_HOVER),
+    ("blue", 0xFF_3498DB, 0xFF_2176AE),
+    ("green", Color::ACCENT_GREEN, 0xFF_1E8449),
+    ("purple", 0xFF_8E44AD, 0xFF_6C3483),
+    ("pink", 0xFF_E84393, 0xFF_C2185B),
+];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
+    pub dark: bool,
+    pub accent: u32,
+    pub accent_hover: u32,
+    pub window_bg: u32,
+    // Panels, sidebars and header strips on top of window_bg
+    pub surface: u32,
+    pub border: u32,
+    // Text boxes, lists and the editor
+    pub field_bg: u32,
+    pub titlebar_active: u32,
+    pub titlebar_inactive: u32,
+    pub taskbar: u32,
+    pub text_primary: u32,
+    pub text_secondary: u32,
+    // The terminal's default colors
+    pub console_bg: u32,
+    pub console_fg: u32,
+}
+
+impl Theme {
+    /// The theme for ACCENTS[accent] (the first for an unknown index).
+    pub fn new(accent: usize, dark: bool) -> Self {
+        let (_, accent, accent_hover) = ACCENTS.get(accent).copied().unwrap_or(ACCENTS[0]);
+        if dark {
+            Self {
+                dark, accent, accent_hover,
+                window_bg: 0xFF_1E1E1E, surface: 0xFF_2D2D30, border: 0xFF_3F3F46,
+                field_bg: 0xFF_252526,
+                titlebar_active: 0xFF_2D2D30, titlebar_inactive: 0xFF_232325,
+                taskbar: 0xFF_18181A,
+                text_primary: 0xFF_E8E8E6, text_secondary: 0xFF_9A9A96,
+                console_bg: 0xFF_0D0D0D, console_fg: 0xFF_00FF66,
+            }
+        } else {
+            Self {
+                dark, accent, accent_hover,
+                window_bg: Color::WARM_BG, surface: Color::WARM_SURFACE, border: Color::WARM_BORDER,
+                field_bg: Color::WHITE,
+                titlebar_active: Color::WARM_SURFACE, titlebar_inactive: 0xFF_EFEFEA,
+                taskbar: Color::WHITE,
+                text_primary: Color::TEXT_DARK, text_secondary: Color::TEXT_MUTED,
+                console_bg: Color::WHITE, console_fg: Color::TEXT_DARK,
+            }
+        }
+    }
+}
+
+static ACCENT: AtomicUsize = AtomicUsize::new(0);
+static DARK: AtomicBool = AtomicBool::new(false);
+
+/// The theme this process draws with.
+pub fn current() -> Theme {
+    Theme::new(ACCENT.load(Ordering::Relaxed), DARK.load(Ordering::Relaxed))
+}
+
+pub fn set(accent: usize, dark: bool) {
+    ACCENT.store(accent.min(ACCENTS.len() - 1), Ordering::Relaxed);
+    DARK.store(dark, Ordering::Relaxed);
+}
+
+/// Index into ACCENTS of the accent called `name`.
+pub fn accent_index(name: &str) -> Option<usize> {
+    ACCENTS.iter().position(|(n, _, _)| *n == name)
+}
+
+/// (accent, dark) from the boot config's accent= and theme= lines; anything
+/// missing or unknown is the default light orange.
+pub fn from_config(config: &KvFile) -> (usize, bool) {
+    let accent = config.get("accent").and_then(accent_index).unwrap_or(0);
+    (accent, config.get("theme") == Some("dark"))
+}
+
+/// Sets the theme the boot config names.
+pub fn load() {
+    let (accent, dark) = from_config(&crate::state::load_config());
+    set(accent, dark);
+}
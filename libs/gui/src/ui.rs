@@ -3,6 +3,8 @@ use alloc::vec::Vec;
 use alloc::boxed::Box;
 use crate::canvas::{Canvas, Color};
 use crate::effects::{alpha_blend, apply_opacity};
+use crate::theme;
+use crate::scale::{self, px};
 
 // ─────────────────────────────────────────────────────────────────────────
 // COMPOSITOR & KERNEL UI ELEMENTS (Used by nyx-user)
@@ -17,18 +19,171 @@ pub struct Window {
     pub saved_x: usize, pub saved_y: usize, pub saved_w: usize, pub saved_h: usize,
 }
 
-pub fn draw_taskbar(buffer: &mut [u32], stride: usize, screen_h: usize) {
-    let mut canvas = Canvas::new(buffer, stride, screen_h);
-    let bar_h = 36; let start_y = screen_h - bar_h;
+// Chrome sizes grow with the UI scale, so these are worked out on each use
+pub fn taskbar_h() -> usize { px(36) }
+pub fn taskbar_btn_w() -> usize { px(120) }
+pub fn taskbar_btn_h() -> usize { px(24) }
+fn taskbar_btn_gap() -> usize { px(6) }
+/// Height of a window's title bar, above its client area
+pub fn title_bar_h() -> usize { px(30) }
+
+/// X position of the `slot`-th window button. Buttons start right of the NYX button
+/// and stop before the network indicator; returns None once they run out of room.
+pub fn taskbar_button_x(stride: usize, slot: usize) -> Option<usize> {
+    let x = (stride / 2) + px(45) + slot * (taskbar_btn_w() + taskbar_btn_gap());
+    if x + taskbar_btn_w() <= stride.saturating_sub(px(60)) { Some(x) } else { None }
+}
+
+/// Draws along the bottom of the screen_w x screen_h display at the canvas's
+/// origin, the primary. `windows` are the taskbar entries in slot order;
+/// `active_id` is the focused window; `clock` goes at the left end.
+pub fn draw_taskbar(canvas: &mut Canvas, screen_w: usize, screen_h: usize, windows: &[&Window], active_id: Option<usize>, clock: &str) {
+    let theme = theme::current();
+    let stride = screen_w;
+    let s = scale::current();
+    let start_y = screen_h - taskbar_h();
+    let btn_y = start_y + px(6);
     
-    canvas.fill_rect(0, start_y, stride, bar_h, 0xD8_FFFFFF); 
-    canvas.fill_rect(0, start_y, stride, 1, 0xFF_D1D1D1);     
+    canvas.fill_rect(0, start_y, stride, taskbar_h(), theme.taskbar); 
+    canvas.fill_rect(0, start_y, stride, 1, theme.border);     
     
-    canvas.print_str(20, start_y + 14, "10:20 AM", Color::TEXT_DARK, 1);
+    canvas.print_str(px(20), start_y + px(14), clock, theme.text_primary, s);
     
-    let btn_x = (stride / 2) - 35;
-    canvas.fill_rect(btn_x, start_y + 6, 70, 24, Color::ACCENT_PRIMARY);
-    canvas.print_str(btn_x + 15, start_y + 8, "NYX", Color::WHITE, 1);
+    let btn_x = (stride / 2) - px(35);
+    canvas.fill_rect(btn_x, btn_y, px(70), taskbar_btn_h(), theme.accent);
+    canvas.print_str(btn_x + px(15), start_y + px(8), "NYX", Color::WHITE, s);
+
+    for (slot, win) in windows.iter().enumerate() {
+        let x = match taskbar_button_x(stride, slot) { Some(x) => x, None => break };
+        let is_active = active_id == Some(win.id);
+        let (bg, fg) = if is_active { (theme.accent, Color::WHITE) }
+            else if win.is_minimized { (theme.window_bg, theme.text_secondary) }
+            else { (theme.border, theme.text_primary) };
+        canvas.fill_rect(x, btn_y, taskbar_btn_w(), taskbar_btn_h(), bg);
+
+        // Clip the title to the button width
+        let title = core::str::from_utf8(&win.title[..win.title_len]).unwrap_or("App");
+        let max_chars = (taskbar_btn_w() - px(16)) / px(8);
+        let end = title.char_indices().nth(max_chars).map(|(i, _)| i).unwrap_or(title.len());
+        canvas.print_str(x + px(8), btn_y + px(8), &title[..end], fg, s);
+    }
+
+    let net_x = stride - px(50);
+    canvas.print_str(net_x, btn_y + px(4), "[WIFI]", theme.text_secondary, s);
+}
+
+// ─────────────────────────────────────────────────────────────────────────
+// START MENU
+// Entries are (label, launch path). Geometry is derived from the entry list
+// so drawing and hit-testing can never disagree about where an item is.
+// ─────────────────────────────────────────────────────────────────────────
+fn menu_w() -> usize { px(180) }
+fn menu_item_h() -> usize { px(40) }
+
+pub enum StartMenuAction {
+    None,
+    Redraw,
+    Launch(&'static str),
+}
+
+pub struct StartMenu {
+    pub entries: Vec<(&'static str, &'static str)>,
+    pub is_open: bool,
+    // Item under the mouse or picked with Up/Down
+    pub highlight: Option<usize>,
+    stride: usize, screen_h: usize,
+}
+
+impl StartMenu {
+    pub fn new(entries: Vec<(&'static str, &'static str)>, stride: usize, screen_h: usize) -> Self {
+        Self { entries, is_open: false, highlight: None, stride, screen_h }
+    }
+
+    /// (x, y, w, h) of the open menu, anchored above the NYX button.
+    pub fn bounds(&self) -> (usize, usize, usize, usize) {
+        let h = self.entries.len() * menu_item_h();
+        ((self.stride / 2).saturating_sub(menu_w() / 2), self.screen_h.saturating_sub(taskbar_h() + h + px(10)), menu_w(), h)
+    }
+
+    pub fn contains(&self, mx: usize, my: usize) -> bool {
+        let (x, y, w, h) = self.bounds();
+        self.is_open && mx >= x && mx < x + w && my >= y && my < y + h
+    }
+
+    pub fn hit_test(&self, mx: usize, my: usize) -> Option<usize> {
+        if !self.contains(mx, my) { return None; }
+        let (_, y, _, _) = self.bounds();
+        Some((my - y) / menu_item_h()).filter(|&i| i < self.entries.len())
+    }
+
+    pub fn toggle(&mut self) {
+        self.is_open = !self.is_open;
+        self.highlight = None;
+    }
+
+    pub fn close(&mut self) {
+        self.is_open = false;
+        self.highlight = None;
+    }
+
+    /// Re-anchors the menu after the screen changed size.
+    pub fn resize(&mut self, stride: usize, screen_h: usize) {
+        self.stride = stride;
+        self.screen_h = screen_h;
+    }
+
+    /// Returns true if the highlighted item changed.
+    pub fn update_hover(&mut self, mx: usize, my: usize) -> bool {
+        if !self.is_open { return false; }
+        match self.hit_test(mx, my) {
+            Some(idx) if self.highlight != Some(idx) => { self.highlight = Some(idx); true },
+            _ => false,
+        }
+    }
+
+    /// Clicking an item closes the menu and returns its launch path.
+    pub fn on_click(&mut self, mx: usize, my: usize) -> Option<&'static str> {
+        let idx = self.hit_test(mx, my)?;
+        self.close();
+        Some(self.entries[idx].1)
+    }
+
+    /// Up/Down move the highlight, Enter launches it, Escape closes the menu.
+    pub fn on_key(&mut self, key: char) -> StartMenuAction {
+        if !self.is_open || self.entries.is_empty() { return StartMenuAction::None; }
+        let last = self.entries.len() - 1;
+        match key {
+            nyx_api::KEY_UP => {
+                self.highlight = Some(self.highlight.map_or(last, |i| if i == 0 { last } else { i - 1 }));
+                StartMenuAction::Redraw
+            },
+            nyx_api::KEY_DOWN => {
+                self.highlight = Some(self.highlight.map_or(0, |i| if i == last { 0 } else { i + 1 }));
+                StartMenuAction::Redraw
+            },
+            '\n' | '\r' => match self.highlight {
+                Some(idx) => { self.close(); StartMenuAction::Launch(self.entries[idx].1) },
+                None => StartMenuAction::None,
+            },
+            '\x1b' => { self.close(); StartMenuAction::Redraw },
+            _ => StartMenuAction::None,
+        }
+    }
+
+    pub fn draw(&self, canvas: &mut Canvas) {
+        if !self.is_open { return; }
+        let (x, y, w, h) = self.bounds();
+        let s = scale::current();
+
+        canvas.fill_rect(x, y, w, h, 0xFF_111111);
+        for (i, (label, _)) in self.entries.iter().enumerate() {
+            let item_y = y + i * menu_item_h();
+            if self.highlight == Some(i) { canvas.fill_rect(x, item_y, w, menu_item_h(), 0xFF_2A2A2A); }
+            canvas.print_str(x + px(20), item_y + px(12), "> ", Color::WHITE, s);
+            canvas.print_str(x + px(36), item_y + px(12), label, Color::WHITE, s);
+        }
+        canvas.fill_rect(x, y, w, px(2), theme::current().accent);
+    }
 }
 
 #[derive(PartialEq, Clone, Copy)]
@@ -95,8 +250,7 @@ const HAND_BITMAP: [[u8; 11]; 16] = [
     [0,0,0,0,1,1,1,0,0,0,0],
 ];
 
-pub fn draw_cursor(buffer: &mut [u32], stride: usize, screen_h: usize, mx: usize, my: usize, c_type: CursorType) {
-    let mut canvas = Canvas::new(buffer, stride, screen_h);
+pub fn draw_cursor(canvas: &mut Canvas, mx: usize, my: usize, c_type: CursorType) {
     
     match c_type {
         CursorType::Arrow => {
@@ -127,12 +281,52 @@ pub fn draw_cursor(buffer: &mut [u32], stride: usize, screen_h: usize, mx: usize
     }
 }
 
-pub fn draw_window_rounded(buffer: &mut [u32], stride: usize, screen_h: usize, win: &Window) {
-    let mut canvas = Canvas::new(buffer, stride, screen_h);
-    let surface = apply_opacity(Color::WARM_SURFACE, win.opacity);
-    let border = apply_opacity(Color::WARM_BORDER, win.opacity);
-    let total_h = if win.is_minimized { 30 } else { win.h + 30 };
+pub const SHADOW_SIZE: usize = 8;
+const SHADOW_OFFSET_Y: usize = 3;
+const SHADOW_ALPHA: u32 = 0x38;
+
+/// Screen area a window can paint, including its border and drop shadow. Anything that
+/// moves, resizes or focuses a window must mark this whole rect dirty.
+pub fn window_bounds(win: &Window) -> (usize, usize, usize, usize) {
+    let total_h = if win.is_minimized { title_bar_h() } else { win.h + title_bar_h() };
+    (
+        win.x.saturating_sub(SHADOW_SIZE),
+        win.y.saturating_sub(SHADOW_SIZE),
+        win.w + 1 + SHADOW_SIZE * 2,
+        total_h + 1 + SHADOW_SIZE * 2 + SHADOW_OFFSET_Y,
+    )
+}
+
+/// Soft shadow: one-pixel rings of black with alpha falling off away from the window.
+pub fn draw_window_shadow(canvas: &mut Canvas, win: &Window) {
+    let total_h = if win.is_minimized { title_bar_h() } else { win.h + title_bar_h() };
+    for i in 1..=SHADOW_SIZE {
+        let a = (SHADOW_ALPHA * (SHADOW_SIZE + 1 - i) as u32) / SHADOW_SIZE as u32;
+        let color = apply_opacity(a << 24, win.opacity);
+
+        let right = win.x + win.w + i;
+        let bottom = win.y + total_h + SHADOW_OFFSET_Y + i;
+        let top = (win.y + SHADOW_OFFSET_Y).saturating_sub(i);
+        let ring_w = right + 1 - win.x.saturating_sub(i);
+
+        if win.x >= i { canvas.fill_rect(win.x - i, top + 1, 1, bottom - top - 1, color); }
+        if win.y + SHADOW_OFFSET_Y >= i { canvas.fill_rect(win.x.saturating_sub(i), top, ring_w, 1, color); }
+        canvas.fill_rect(right, top + 1, 1, bottom - top - 1, color);
+        canvas.fill_rect(win.x.saturating_sub(i), bottom, ring_w, 1, color);
+    }
+}
+
+/// Frame, title bar and buttons; `focused` picks the title bar color.
+pub fn draw_window_rounded(canvas: &mut Canvas, win: &Window, focused: bool) {
+    let theme = theme::current();
+    let surface = apply_opacity(theme.surface, win.opacity);
+    let titlebar = apply_opacity(if focused { theme.titlebar_active } else { theme.titlebar_inactive }, win.opacity);
+    // Translucent so the edge picks up whatever is behind the window
+    let border = apply_opacity(0x50_000000, win.opacity);
+    let bar_h = title_bar_h();
+    let total_h = if win.is_minimized { bar_h } else { win.h + bar_h };
     canvas.fill_rect(win.x, win.y, win.w, total_h, surface);
+    canvas.fill_rect(win.x, win.y, win.w, total_h.min(bar_h), titlebar);
     canvas.fill_rect(win.x, win.y, win.w, 1, border); 
     canvas.fill_rect(win.x, win.y + total_h, win.w, 1, border); 
     canvas.fill_rect(win.x, win.y, 1, total_h, border); 
@@ -141,17 +335,19 @@ pub fn draw_window_rounded(buffer: &mut [u32], stride: usize, screen_h: usize, w
     // Header Controls (Now with symbols!)
     let icon_color = apply_opacity(0x60_000000, win.opacity); // Dark semi-transparent text
 
-    canvas.fill_rect(win.x + 12, win.y + 10, 12, 12, apply_opacity(0xFF_FF5F56, win.opacity)); // Close
-    canvas.print_str(win.x + 14, win.y + 12, "x", icon_color, 1);
+    let s = scale::current();
+    canvas.fill_rect(win.x + px(12), win.y + px(10), px(12), px(12), apply_opacity(0xFF_FF5F56, win.opacity)); // Close
+    canvas.print_str(win.x + px(14), win.y + px(12), "x", icon_color, s);
 
-    canvas.fill_rect(win.x + 28, win.y + 10, 12, 12, apply_opacity(0xFF_FFBD2E, win.opacity)); // Min
-    canvas.print_str(win.x + 30, win.y + 11, "-", icon_color, 1);
+    canvas.fill_rect(win.x + px(28), win.y + px(10), px(12), px(12), apply_opacity(0xFF_FFBD2E, win.opacity)); // Min
+    canvas.print_str(win.x + px(30), win.y + px(11), "-", icon_color, s);
 
-    canvas.fill_rect(win.x + 44, win.y + 10, 12, 12, apply_opacity(0xFF_28C940, win.opacity)); // Max
-    canvas.print_str(win.x + 46, win.y + 12, "+", icon_color, 1);
+    canvas.fill_rect(win.x + px(44), win.y + px(10), px(12), px(12), apply_opacity(0xFF_28C940, win.opacity)); // Max
+    canvas.print_str(win.x + px(46), win.y + px(12), "+", icon_color, s);
     
     let title_str = core::str::from_utf8(&win.title[..win.title_len]).unwrap_or("App");
-    canvas.print_str(win.x + (win.w / 2) - ((title_str.len() * 8) / 2), win.y + 12, title_str, apply_opacity(Color::TEXT_DARK, win.opacity), 1);
+    let title_x = (win.x + win.w / 2).saturating_sub(px(title_str.len() * 8) / 2);
+    canvas.print_str(title_x, win.y + px(12), title_str, apply_opacity(theme.text_primary, win.opacity), s);
 }
 
 // ─────────────────────────────────────────────────────────────────────────
@@ -203,12 +399,17 @@ pub struct Button {
     pub text: String,
     pub is_hovered: bool, pub is_pressed: bool,
 }
-impl Widget for Button {
-    fn draw(&mut self, canvas: &mut Canvas) {
-        let bg = if self.is_pressed { Color::ACCENT_HOVER } else if self.is_hovered { Color::ACCENT_PRIMARY } else { Color::WARM_BORDER };
+impl Button {
+    /// Draws the label at `scale`; the bounds are taken as already scaled.
+    pub fn draw_scaled(&self, canvas: &mut Canvas, scale: usize) {
+        let theme = theme::current();
+        let bg = if self.is_pressed { theme.accent_hover } else if self.is_hovered { theme.accent } else { theme.border };
         canvas.fill_rect(self.x, self.y, self.w, self.h, bg);
-        canvas.print_str(self.x + 10, self.y + (self.h/2) - 4, &self.text, if self.is_hovered {Color::WHITE} else {Color::TEXT_DARK}, 1);
+        canvas.print_str(self.x + 10 * scale, (self.y + self.h / 2).saturating_sub(4 * scale), &self.text, if self.is_hovered {Color::WHITE} else {theme.text_primary}, scale);
     }
+}
+impl Widget for Button {
+    fn draw(&mut self, canvas: &mut Canvas) { self.draw_scaled(canvas, 1); }
     fn on_mouse(&mut self, mx: usize, my: usize, clicked: bool) -> bool {
         let in_bounds = mx >= self.x && mx <= self.x + self.w && my >= self.y && my <= self.y + self.h;
         let old_hover = self.is_hovered; let old_pressed = self.is_pressed;
@@ -226,14 +427,15 @@ pub struct TextBox {
 }
 impl Widget for TextBox {
     fn draw(&mut self, canvas: &mut Canvas) {
-        let border = if self.is_focused { Color::ACCENT_PRIMARY } else { Color::WARM_BORDER };
-        canvas.fill_rect(self.x, self.y, self.w, self.h, Color::WHITE);
+        let theme = theme::current();
+        let border = if self.is_focused { theme.accent } else { theme.border };
+        canvas.fill_rect(self.x, self.y, self.w, self.h, theme.field_bg);
         canvas.fill_rect(self.x, self.y, self.w, 1, border);
         canvas.fill_rect(self.x, self.y + self.h, self.w, 1, border);
         canvas.fill_rect(self.x, self.y, 1, self.h, border);
         canvas.fill_rect(self.x + self.w, self.y, 1, self.h, border);
-        canvas.print_str(self.x + 5, self.y + 8, &self.text, Color::TEXT_DARK, 1);
-        if self.is_focused { canvas.fill_rect(self.x + 5 + (self.text.len() * 8), self.y + 6, 2, 12, Color::TEXT_DARK); }
+        canvas.print_str(self.x + 5, self.y + 8, &self.text, theme.text_primary, 1);
+        if self.is_focused { canvas.fill_rect(self.x + 5 + (self.text.len() * 8), self.y + 6, 2, 12, theme.text_primary); }
     }
     fn on_mouse(&mut self, mx: usize, my: usize, clicked: bool) -> bool {
         if clicked {
@@ -258,13 +460,14 @@ pub struct CheckBox {
 }
 impl Widget for CheckBox {
     fn draw(&mut self, canvas: &mut Canvas) {
-        let bg = if self.is_checked { Color::ACCENT_PRIMARY } else { Color::WHITE };
+        let theme = theme::current();
+        let bg = if self.is_checked { theme.accent } else { theme.field_bg };
         canvas.fill_rect(self.x, self.y, 16, 16, bg);
-        canvas.fill_rect(self.x, self.y, 16, 1, Color::WARM_BORDER);
-        canvas.fill_rect(self.x, self.y+16, 16, 1, Color::WARM_BORDER);
-        canvas.fill_rect(self.x, self.y, 1, 16, Color::WARM_BORDER);
-        canvas.fill_rect(self.x+16, self.y, 1, 16, Color::WARM_BORDER);
-        canvas.print_str(self.x + 25, self.y + 4, &self.text, Color::TEXT_DARK, 1);
+        canvas.fill_rect(self.x, self.y, 16, 1, theme.border);
+        canvas.fill_rect(self.x, self.y+16, 16, 1, theme.border);
+        canvas.fill_rect(self.x, self.y, 1, 16, theme.border);
+        canvas.fill_rect(self.x+16, self.y, 1, 16, theme.border);
+        canvas.print_str(self.x + 25, self.y + 4, &self.text, theme.text_primary, 1);
     }
     fn on_mouse(&mut self, mx: usize, my: usize, clicked: bool) -> bool {
         if clicked && mx >= self.x && mx <= self.x + 16 && my >= self.y && my <= self.y + 16 {
@@ -282,15 +485,16 @@ pub struct ListBox {
 }
 impl Widget for ListBox {
     fn draw(&mut self, canvas: &mut Canvas) {
-        canvas.fill_rect(self.x, self.y, self.w, self.h, Color::WHITE);
+        let theme = theme::current();
+        canvas.fill_rect(self.x, self.y, self.w, self.h, theme.field_bg);
         for (i, item) in self.items.iter().enumerate() {
             let item_y = self.y + (i * 20);
             if item_y + 20 > self.y + self.h { break; } 
             if Some(i) == self.selected_idx {
-                canvas.fill_rect(self.x, item_y, self.w, 20, Color::ACCENT_PRIMARY);
+                canvas.fill_rect(self.x, item_y, self.w, 20, theme.accent);
                 canvas.print_str(self.x + 5, item_y + 6, item, Color::WHITE, 1);
             } else {
-                canvas.print_str(self.x + 5, item_y + 6, item, Color::TEXT_DARK, 1);
+                canvas.print_str(self.x + 5, item_y + 6, item, theme.text_primary, 1);
             }
         }
     }
@@ -311,18 +515,19 @@ pub struct Menu {
 }
 impl Widget for Menu {
     fn draw(&mut self, canvas: &mut Canvas) {
+        let theme = theme::current();
         // 🚨 FIX E0716: Treat it purely as a string slice (&str) rather than an allocated String reference
         let text = self.items.get(self.selected_idx).map(|s| s.as_str()).unwrap_or("Select");
         
-        canvas.fill_rect(self.x, self.y, self.w, 25, Color::WARM_SURFACE);
-        canvas.print_str(self.x + 5, self.y + 8, text, Color::TEXT_DARK, 1);
-        canvas.print_str(self.x + self.w - 15, self.y + 8, "v", Color::TEXT_DARK, 1);
+        canvas.fill_rect(self.x, self.y, self.w, 25, theme.surface);
+        canvas.print_str(self.x + 5, self.y + 8, text, theme.text_primary, 1);
+        canvas.print_str(self.x + self.w - 15, self.y + 8, "v", theme.text_primary, 1);
         
         if self.is_open {
             let drop_y = self.y + 25;
-            canvas.fill_rect(self.x, drop_y, self.w, self.items.len() * 25, Color::WHITE);
+            canvas.fill_rect(self.x, drop_y, self.w, self.items.len() * 25, theme.field_bg);
             for (i, item) in self.items.iter().enumerate() {
-                canvas.print_str(self.x + 5, drop_y + (i * 25) + 8, item, Color::TEXT_DARK, 1);
+                canvas.print_str(self.x + 5, drop_y + (i * 25) + 8, item, theme.text_primary, 1);
             }
         }
     }
@@ -349,10 +554,11 @@ pub struct ScrollBar {
 }
 impl Widget for ScrollBar {
     fn draw(&mut self, canvas: &mut Canvas) {
-        canvas.fill_rect(self.x, self.y, self.w, self.h, 0xFF_E0E0E0);
+        let theme = theme::current();
+        canvas.fill_rect(self.x, self.y, self.w, self.h, theme.border);
         let thumb_h = core::cmp::max(20, self.h / core::cmp::max(1, self.max_value));
         let thumb_y = self.y + ((self.h - thumb_h) * self.value) / core::cmp::max(1, self.max_value);
-        canvas.fill_rect(self.x + 2, thumb_y, self.w - 4, thumb_h, 0xFF_999999);
+        canvas.fill_rect(self.x + 2, thumb_y, self.w - 4, thumb_h, theme.text_secondary);
     }
     fn on_mouse(&mut self, mx: usize, my: usize, clicked: bool) -> bool {
         if clicked && mx >= self.x && mx <= self.x + self.w && my >= self.y && my <= self.y + self.h {
@@ -386,14 +592,15 @@ pub struct Dialog {
 }
 impl Widget for Dialog {
     fn draw(&mut self, canvas: &mut Canvas) {
+        let theme = theme::current();
         canvas.fill_rect(self.x + 5, self.y + 5, self.w, self.h, 0x40_000000); 
-        canvas.fill_rect(self.x, self.y, self.w, self.h, Color::WARM_BG);
-        canvas.fill_rect(self.x, self.y, self.w, 30, Color::WARM_SURFACE); 
-        canvas.fill_rect(self.x, self.y, self.w, 1, Color::WARM_BORDER);
-        canvas.fill_rect(self.x, self.y + self.h, self.w, 1, Color::WARM_BORDER);
-        canvas.fill_rect(self.x, self.y, 1, self.h, Color::WARM_BORDER);
-        canvas.fill_rect(self.x + self.w, self.y, 1, self.h, Color::WARM_BORDER);
-        canvas.print_str(self.x + 10, self.y + 8, &self.title, Color::TEXT_DARK, 1);
+        canvas.fill_rect(self.x, self.y, self.w, self.h, theme.window_bg);
+        canvas.fill_rect(self.x, self.y, self.w, 30, theme.surface); 
+        canvas.fill_rect(self.x, self.y, self.w, 1, theme.border);
+        canvas.fill_rect(self.x, self.y + self.h, self.w, 1, theme.border);
+        canvas.fill_rect(self.x, self.y, 1, self.h, theme.border);
+        canvas.fill_rect(self.x + self.w, self.y, 1, self.h, theme.border);
+        canvas.print_str(self.x + 10, self.y + 8, &self.title, theme.text_primary, 1);
         for child in &mut self.children { child.draw(canvas); }
     }
     fn on_mouse(&mut self, mx: usize, my: usize, clicked: bool) -> bool {
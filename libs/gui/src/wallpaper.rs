@@ -0,0 +1,42 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use nyx_core::bmp::fit;
+use crate::bmp;
+
+// ─────────────────────────────────────────────────────────────────────────
+// DESKTOP WALLPAPER
+// Decodes an uncompressed 24/32-bit BMP (see bmp.rs) into a screen-sized
+// ARGB image. Rows are streamed from disk one at a time, so only the output
+// image has to fit in the compositor heap. Images larger than the screen
+// are scaled down to fit (nearest neighbour); smaller ones are centered.
+// ─────────────────────────────────────────────────────────────────────────
+pub const DEFAULT_WALLPAPER: &str = "/mnt/nvme/wallpaper.bmp";
+
+pub struct Wallpaper {
+    pub width: usize,
+    pub height: usize,
+    pixels: Vec<u32>,
+}
+
+impl Wallpaper {
+    /// Loads `path` and fits it to a `screen_w` x `screen_h` image. None if the file
+    /// is missing or isn't a BMP we understand.
+    pub fn load(path: &str, screen_w: usize, screen_h: usize, background: u32) -> Option<Self> {
+        let info = bmp::open(path).ok()?;
+        // Fit inside the screen, never upscale
+        let (dst_w, dst_h) = fit(info.width, info.height, screen_w, screen_h, false);
+        let mut pixels = vec![background; screen_w * screen_h];
+        let dest = bmp::Dest { out: &mut pixels, stride: screen_w, x: (screen_w - dst_w) / 2, y: (screen_h - dst_h) / 2, w: dst_w, h: dst_h };
+        bmp::decode_into(path, &info, dest).ok()?;
+        Some(Self { width: screen_w, height: screen_h, pixels })
+    }
+
+    #[inline]
+    pub fn pixel(&self, x: usize, y: usize) -> Option<u32> {
+        if x < self.width && y < self.height { Some(self.pixels[y * self.width + x]) } else { None }
+    }
+
+    pub fn row(&self, y: usize) -> &[u32] {
+        &self.pixels[y * self.width..(y + 1) * self.width]
+    }
+}
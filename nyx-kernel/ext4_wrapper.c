@@ -6,10 +6,15 @@
 // 1. These are the Rust NVMe functions we will call from C
 extern bool nyx_nvme_read_block(uint64_t sector, uint8_t* buf);
 extern bool nyx_nvme_write_block(uint64_t sector, const uint8_t* buf);
+extern bool nyx_nvme_read_blocks(uint64_t sector, uint32_t count, uint8_t* buf);
+// Wall-clock Unix seconds from the CMOS clock (0 if there is none)
+extern uint32_t nyx_unix_time(void);
 
 // 2. Map lwext4 block requests to your Rust NVMe driver
 static int bridge_bread(struct ext4_blockdev *bdev, void *buf, uint64_t blk_id, uint32_t blk_cnt) {
     uint8_t* p = (uint8_t*)buf;
+    // Runs of sectors go out as multi-block NVMe commands
+    if (blk_cnt > 1) return nyx_nvme_read_blocks(blk_id, blk_cnt, p) ? EOK : EIO;
     for (uint32_t i = 0; i < blk_cnt; i++) {
         if (!nyx_nvme_read_block(blk_id + i, p + (i * 512))) return EIO;
     }
@@ -80,18 +85,32 @@ int nyx_fs_read_file(const char* path, uint32_t offset, uint8_t* buf, uint32_t l
     return (int)bytes_read;
 }
 
+// lwext4 never stamps times itself. Sets the inode's modify and change
+// times to now; a machine without a clock leaves them alone.
+static void touch(const char* path) {
+    uint32_t now = nyx_unix_time();
+    if (now == 0) return;
+    ext4_mtime_set(path, now);
+    ext4_ctime_set(path, now);
+}
+
+// Returns the bytes written, or -errno (e.g. -EROFS, -ENOSPC) if nothing could be written
 int nyx_fs_write_file(const char* path, uint32_t offset, const uint8_t* buf, uint32_t len) {
     ext4_file f;
     // "r+" opens for read/write. If it fails, "w+" creates it.
-    if (ext4_fopen(&f, path, "r+") != EOK) {
-        if (ext4_fopen(&f, path, "w+") != EOK) return 0;
+    int r = ext4_fopen(&f, path, "r+");
+    if (r != EOK) {
+        r = ext4_fopen(&f, path, "w+");
+        if (r != EOK) return -r;
     }
     
     ext4_fseek(&f, offset, SEEK_SET);
     size_t bytes_written = 0;
-    ext4_fwrite(&f, buf, len, &bytes_written);
+    r = ext4_fwrite(&f, buf, len, &bytes_written);
     ext4_fclose(&f);
     
+    if (r != EOK && bytes_written == 0) return -r;
+    touch(path);
     return (int)bytes_written;
 }
 
@@ -103,8 +122,12 @@ int nyx_fs_get_size(const char* path) {
     return size;
 }
 
+// 1 on success, or -errno (e.g. -ENOSPC, -EEXIST)
 int nyx_fs_create_dir(const char* path) {
-    return ext4_dir_mk(path) == EOK ? 1 : 0;
+    int r = ext4_dir_mk(path);
+    if (r != EOK) return -r;
+    touch(path);
+    return 1;
 }
 
 // ==========================================
@@ -133,13 +156,16 @@ void nyx_fs_list_dir(const char* path, void (*cb)(const char*, unsigned char, vo
     ext4_dir_close(&dir);
 }
 
+// Returns 1 on success or -errno
 int nyx_fs_create_file(const char* path) {
     ext4_file f;
     // "w+" creates an empty file for reading and writing.
-    if (ext4_fopen(&f, path, "w+") != EOK) return 0;
+    int r = ext4_fopen(&f, path, "w+");
+    if (r != EOK) return -r;
     
     // Close it immediately since we just want to create it
     ext4_fclose(&f);
+    touch(path);
     return 1;
 }
 
@@ -150,6 +176,40 @@ int nyx_fs_delete_file(const char* path) {
     }
     return 0; // Failed (e.g., file doesn't exist, or folder not empty)
 }
+// Renames a file or directory. Returns -1 if the destination already exists
+// so the VFS can tell a name clash apart from a real I/O failure.
+int nyx_fs_rename(const char* old_path, const char* new_path) {
+    if (ext4_inode_exist(new_path, EXT4_DE_REG_FILE) == EOK ||
+        ext4_inode_exist(new_path, EXT4_DE_DIR) == EOK) {
+        return -1;
+    }
+    if (ext4_frename(old_path, new_path) == EOK) {
+        return 1;
+    }
+    return 0;
+}
+
+// Block counts, free space and label of the mounted volume; EOK or an errno
+int nyx_fs_stats(struct ext4_mount_stats* out) {
+    return ext4_mount_point_stats("/mnt/", out);
+}
+
+// 1 if `path` names a file or a directory, else 0
+int nyx_fs_exists(const char* path) {
+    return ext4_inode_exist(path, EXT4_DE_REG_FILE) == EOK ||
+           ext4_inode_exist(path, EXT4_DE_DIR) == EOK;
+}
+
+// Permission and type bits of a file or directory; EOK or an errno
+int nyx_fs_mode(const char* path, uint32_t* out) {
+    return ext4_mode_get(path, out);
+}
+
+// Last-modified time of a file or directory in Unix seconds; EOK or an errno
+int nyx_fs_mtime(const char* path, uint32_t* out) {
+    return ext4_mtime_get(path, out);
+}
+
 // Forces the block cache to flush its journal to the physical NVMe drive
 int nyx_fs_sync(const char* path) {
     if (ext4_cache_flush(path) == EOK) {
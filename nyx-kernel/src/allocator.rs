@@ -1,16 +1,44 @@
+use core::alloc::{GlobalAlloc, Layout};
+use core::ptr::{null_mut, NonNull};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use x86_64::{
     structures::paging::{
         mapper::MapToError, FrameAllocator, Mapper, Page, PageTableFlags, Size4KiB,
     },
     VirtAddr,
 };
-use linked_list_allocator::LockedHeap;
+use linked_list_allocator::Heap;
+use spin::Mutex;
 
 pub const HEAP_START: usize = 0x_4444_4444_0000;
-pub const HEAP_SIZE: usize = 64 * 1024 * 1024; // 64 MiB
+pub const HEAP_SIZE: usize = 64 * 1024 * 1024; // 64 MiB mapped at boot
+/// Ceiling the heap may grow to when it runs out
+pub const HEAP_MAX: usize = 1024 * 1024 * 1024; // 1 GiB
+/// PML4 slot of the heap. Address spaces share it instead of copying it, so
+/// pages mapped while growing are visible to every task at once.
+pub const HEAP_PML4_SLOT: usize = (HEAP_START >> 39) & 0x1FF;
+
+// Grow by at least this much so a burst of small allocations doesn't map page by page
+const GROW_STEP: usize = 4 * 1024 * 1024;
+// The memory manager may be held by this very core (an allocation made while
+// editing page tables), so growth gives up after a while instead of deadlocking
+const MM_LOCK_SPINS: usize = 1_000_000;
+
+// ==========================================
+// GROWABLE KERNEL HEAP
+// ==========================================
+// A linked-list heap that starts at HEAP_SIZE. When an allocation doesn't
+// fit, fresh frames are mapped at the top of the heap (up to HEAP_MAX) and
+// the allocation is retried; only running out of frames or address range
+// reaches alloc_error_handler.
+
+pub struct KernelHeap {
+    heap: Mutex<Heap>,
+    high_water: AtomicUsize,
+}
 
 #[global_allocator]
-static ALLOCATOR: LockedHeap = LockedHeap::empty();
+static ALLOCATOR: KernelHeap = KernelHeap { heap: Mutex::new(Heap::empty()), high_water: AtomicUsize::new(0) };
 
 pub fn init_heap(
     mapper: &mut impl Mapper<Size4KiB>,
@@ -36,8 +64,120 @@ pub fn init_heap(
 
     unsafe {
         // FIX: Pass HEAP_START directly (it is already a usize)
-        ALLOCATOR.lock().init(HEAP_START, HEAP_SIZE);
+        ALLOCATOR.heap.lock().init(HEAP_START, HEAP_SIZE);
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Maps at least enough pages above the heap for `layout` and hands them to
+/// the heap. False if nothing could be mapped.
+fn grow(heap: &mut Heap, layout: Layout) -> bool {
+    let top = heap.top();
+    let room = HEAP_START + HEAP_MAX - top;
+    let wanted = ((layout.size() + layout.align()).max(GROW_STEP) + 0xFFF) & !0xFFF;
+    let target = wanted.min(room);
+    if target == 0 { return false; }
+
+    let mut lock = match (0..MM_LOCK_SPINS).find_map(|_| {
+        core::hint::spin_loop();
+        crate::memory::MEMORY_MANAGER.try_lock()
+    }) {
+        Some(lock) => lock,
+        None => return false,
+    };
+    let system = match lock.as_mut() { Some(s) => s, None => return false };
+
+    let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE;
+    let mut mapped = 0;
+    while mapped < target {
+        let page = Page::<Size4KiB>::containing_address(VirtAddr::new((top + mapped) as u64));
+        let frame = match system.frame_allocator.allocate_frame() { Some(f) => f, None => break };
+        match unsafe { system.mapper.map_to(page, frame, flags, &mut system.frame_allocator) } {
+            Ok(flush) => flush.flush(),
+            Err(_) => { system.frame_allocator.deallocate_frame(frame); break; }
+        }
+        mapped += 4096;
+    }
+    drop(lock);
+
+    if mapped == 0 { return false; }
+    unsafe { heap.extend(mapped); }
+    true
+}
+
+unsafe impl GlobalAlloc for KernelHeap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let mut heap = self.heap.lock();
+        let ptr = match heap.allocate_first_fit(layout) {
+            Ok(ptr) => ptr.as_ptr(),
+            // A short growth (frames nearly gone) may still not fit; then it's a real failure
+            Err(()) if grow(&mut heap, layout) => heap.allocate_first_fit(layout).map_or(null_mut(), |p| p.as_ptr()),
+            Err(()) => null_mut(),
+        };
+        self.high_water.fetch_max(heap.used(), Ordering::Relaxed);
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.heap.lock().deallocate(NonNull::new_unchecked(ptr), layout);
+    }
+}
+
+/// Bytes of the kernel heap currently handed out
+pub fn heap_used() -> usize {
+    ALLOCATOR.heap.lock().used()
+}
+
+/// Most bytes ever handed out at once
+pub fn heap_peak() -> usize {
+    ALLOCATOR.high_water.load(Ordering::Relaxed)
+}
+
+/// Bytes currently mapped for the heap
+pub fn heap_size() -> usize {
+    ALLOCATOR.heap.lock().size()
+}
+
+/// Whether some core is inside the allocator right now. Interrupt handlers
+/// that want to allocate check this first, since the holder may be the code
+/// they interrupted.
+pub fn is_busy() -> bool {
+    ALLOCATOR.heap.is_locked()
+}
+
+/// Largest stress_test: enough to push the heap past its boot size, not
+/// enough for a user task to claim the rest of HEAP_MAX through it
+pub const STRESS_TEST_MAX_MIB: usize = 2 * HEAP_SIZE / (1024 * 1024);
+static STRESS_TEST_RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Allocates `mib` one-MiB blocks, fills and verifies them, then frees them all.
+/// Uses fallible allocation so running out ends the test instead of the kernel.
+/// Returns how many blocks were held at once; EINVAL past STRESS_TEST_MAX_MIB,
+/// EAGAIN while another test runs and ENOMEM if the blocks came back damaged.
+pub fn stress_test(mib: usize) -> Result<usize, i64> {
+    if mib > STRESS_TEST_MAX_MIB { return Err(crate::errno::EINVAL); }
+    // One at a time, so tests can't stack up to more than the cap together
+    if STRESS_TEST_RUNNING.swap(true, Ordering::Acquire) { return Err(crate::errno::EAGAIN); }
+    let result = hold_blocks(mib);
+    STRESS_TEST_RUNNING.store(false, Ordering::Release);
+    result
+}
+
+fn hold_blocks(mib: usize) -> Result<usize, i64> {
+    const BLOCK: usize = 1024 * 1024;
+    let mut blocks: alloc::vec::Vec<alloc::vec::Vec<u8>> = alloc::vec::Vec::new();
+    if blocks.try_reserve_exact(mib).is_err() { return Err(crate::errno::ENOMEM); }
+
+    for i in 0..mib {
+        let mut block = alloc::vec::Vec::new();
+        if block.try_reserve_exact(BLOCK).is_err() { break; }
+        block.resize(BLOCK, i as u8);
+        blocks.push(block);
+    }
+    let held = blocks.len();
+    let intact = blocks.iter().enumerate().all(|(i, b)| b.iter().all(|&byte| byte == i as u8));
+    drop(blocks);
+
+    if intact { Ok(held) } else { Err(crate::errno::ENOMEM) }
+}
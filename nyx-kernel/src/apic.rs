@@ -1,12 +1,23 @@
 use crate::acpi::ACPI_INFO;
 use crate::memory::phys_to_virt;
 use core::ptr::{read_volatile, write_volatile};
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use alloc::vec::Vec;
+use spin::Mutex;
 
 // --- FAST HARDWARE CACHE ---
 // This prevents the OS from re-parsing ACPI tables during high-speed interrupts!
 static mut LOCAL_APIC_VIRT: u64 = 0;
 
+/// Boot flag: true once the local APIC is up. False means the MADT was missing
+/// and interrupts run through the legacy 8259 PIC, with the PIT as the tick.
+pub static APIC_MODE: AtomicBool = AtomicBool::new(false);
+
+// Local APIC timer count for one scheduler tick, measured against the PIT
+static TIMER_COUNT_PER_TICK: AtomicU32 = AtomicU32::new(0);
+// Used when calibration fails; about 1 ms on the machines this was tuned on
+const FALLBACK_TIMER_COUNT: u32 = 0x0000_A000;
+
 #[repr(C, packed)]
 struct MadtHeader {
     signature: [u8; 4],
@@ -37,21 +48,128 @@ pub struct ProcessorLocalApic {
     pub flags: u32,
 }
 
-pub fn init() {
-    let madt_phys = unsafe { ACPI_INFO.madt_addr };
-    if madt_phys.is_none() { 
-        crate::serial_println!("[APIC] Cannot init: MADT address is missing.");
-        return; 
+#[repr(C, packed)]
+struct InterruptSourceOverrideEntry {
+    entry_type: u8,
+    length: u8,
+    bus: u8,
+    source: u8,
+    gsi: u32,
+    flags: u16,
+}
+
+#[repr(C, packed)]
+struct LocalApicAddressOverride {
+    entry_type: u8,
+    length: u8,
+    reserved: u16,
+    address: u64,
+}
+
+const MADT_LOCAL_APIC: u8 = 0;
+const MADT_IO_APIC: u8 = 1;
+const MADT_SOURCE_OVERRIDE: u8 = 2;
+const MADT_LOCAL_APIC_ADDRESS: u8 = 5;
+
+/// An ISA IRQ the firmware wired to a different IOAPIC input, or with
+/// non-default polarity/trigger (`flags` as in the MADT: bits 0-1 polarity,
+/// bits 2-3 trigger mode; 0 means "conforms to the bus", i.e. ISA edge/high).
+#[derive(Clone, Copy, Debug)]
+pub struct InterruptOverride {
+    pub irq: u8,
+    pub gsi: u32,
+    pub flags: u16,
+}
+
+/// Everything the interrupt setup needs from the MADT.
+pub struct MadtInfo {
+    pub local_apic_addr: u64,
+    /// Enabled processors, BSP first
+    pub apic_ids: Vec<u32>,
+    pub ioapic_addr: Option<u64>,
+    pub ioapic_gsi_base: u32,
+    pub overrides: Vec<InterruptOverride>,
+}
+
+static MADT_INFO: Mutex<Option<MadtInfo>> = Mutex::new(None);
+
+/// Walks the MADT entries. None if ACPI never handed us a MADT.
+pub fn parse_madt() -> Option<MadtInfo> {
+    let madt_phys = unsafe { ACPI_INFO.madt_addr }?;
+    let madt_virt = phys_to_virt(madt_phys)?;
+    let header = unsafe { &*(madt_virt as *const MadtHeader) };
+
+    let mut info = MadtInfo {
+        local_apic_addr: header.local_apic_addr as u64,
+        apic_ids: Vec::new(),
+        ioapic_addr: None,
+        ioapic_gsi_base: 0,
+        overrides: Vec::new(),
+    };
+
+    let end_ptr = madt_virt + header.length as u64;
+    let mut current_ptr = madt_virt + core::mem::size_of::<MadtHeader>() as u64;
+    while current_ptr + 2 <= end_ptr {
+        let entry = unsafe { &*(current_ptr as *const MadtEntryHeader) };
+        if entry.length < 2 || current_ptr + entry.length as u64 > end_ptr { break; }
+
+        match entry.entry_type {
+            MADT_LOCAL_APIC => {
+                let proc = unsafe { &*(current_ptr as *const ProcessorLocalApic) };
+                if (proc.flags & 1) != 0 { info.apic_ids.push(proc.apic_id as u32); }
+            }
+            // Only the first IOAPIC is used; it carries the ISA interrupts on every board we run on
+            MADT_IO_APIC if info.ioapic_addr.is_none() => {
+                let io_apic = unsafe { &*(current_ptr as *const IoApicEntry) };
+                info.ioapic_addr = Some(io_apic.io_apic_address as u64);
+                info.ioapic_gsi_base = io_apic.global_system_interrupt_base;
+            }
+            MADT_SOURCE_OVERRIDE => {
+                let iso = unsafe { &*(current_ptr as *const InterruptSourceOverrideEntry) };
+                info.overrides.push(InterruptOverride { irq: iso.source, gsi: iso.gsi, flags: iso.flags });
+            }
+            MADT_LOCAL_APIC_ADDRESS => {
+                let ovr = unsafe { &*(current_ptr as *const LocalApicAddressOverride) };
+                info.local_apic_addr = ovr.address;
+            }
+            _ => {}
+        }
+        current_ptr += entry.length as u64;
+    }
+    Some(info)
+}
+
+/// IOAPIC input and MADT flags for ISA `irq`, after interrupt source overrides.
+pub fn isa_irq_route(irq: u8) -> (u32, u16) {
+    let madt = MADT_INFO.lock();
+    let overrides = madt.as_ref().map(|m| m.overrides.as_slice()).unwrap_or(&[]);
+    match overrides.iter().find(|o| o.irq == irq) {
+        Some(o) => (o.gsi, o.flags),
+        None => (irq as u32, 0),
     }
+}
 
-    let madt_virt = phys_to_virt(madt_phys.unwrap()).unwrap();
-    let madt = unsafe { &*(madt_virt as *const MadtHeader) };
-    
-    let apic_phys = madt.local_apic_addr as u64;
+/// Enables the BSP's local APIC. False (and APIC_MODE stays off) if there is
+/// no MADT or the APIC can't be mapped; the caller then boots in PIC mode.
+pub fn init() -> bool {
+    let madt = match parse_madt() {
+        Some(m) => m,
+        None => {
+            crate::serial_println!("[APIC] Cannot init: MADT address is missing.");
+            return false;
+        }
+    };
+    let apic_phys = madt.local_apic_addr;
+    crate::serial_println!("[APIC] MADT: {} cores, IOAPIC {:?}, {} source overrides",
+        madt.apic_ids.len(), madt.ioapic_addr, madt.overrides.len());
+    for o in &madt.overrides {
+        crate::serial_println!("[APIC]   ISA IRQ {} -> GSI {} (flags {:#x})", o.irq, o.gsi, o.flags);
+    }
+    *MADT_INFO.lock() = Some(madt);
     
     if unsafe { crate::memory::map_mmio(apic_phys, 4096) }.is_err() { 
         crate::serial_println!("[APIC] FATAL: Failed to map Local APIC MMIO.");
-        return; 
+        return false; 
     }
     
     let apic_virt = phys_to_virt(apic_phys).unwrap();
@@ -65,35 +183,19 @@ pub fn init() {
         write_volatile(sivr_ptr, current_sivr | 0x1FF); 
     }
 
+    APIC_MODE.store(true, Ordering::SeqCst);
     crate::vga_println!("[APIC] Local APIC Enabled! Ready for MSI (Modern Interrupts).");
+    true
 }
 
 pub fn get_cpu_apic_ids() -> Vec<u32> {
-    let madt_phys = unsafe { ACPI_INFO.madt_addr };
-    if madt_phys.is_none() {
-        crate::serial_println!("[APIC] MADT missing. Defaulting to 1 core.");
-        return alloc::vec![0]; 
-    }
-
-    let madt_virt = phys_to_virt(madt_phys.unwrap()).unwrap();
-    let header = unsafe { &*(madt_virt as *const MadtHeader) };
-    
-    let end_ptr = madt_virt + header.length as u64;
-    let mut current_ptr = madt_virt + core::mem::size_of::<MadtHeader>() as u64;
-    
-    let mut ids = Vec::new();
-
-    while current_ptr < end_ptr {
-        let entry = unsafe { &*(current_ptr as *const MadtEntryHeader) };
-        if entry.entry_type == 0 { 
-            let proc = unsafe { &*(current_ptr as *const ProcessorLocalApic) };
-            if (proc.flags & 1) != 0 {
-                ids.push(proc.apic_id as u32);
-            }
+    let ids = match MADT_INFO.lock().as_ref() {
+        Some(madt) if !madt.apic_ids.is_empty() => madt.apic_ids.clone(),
+        _ => {
+            crate::serial_println!("[APIC] MADT missing. Defaulting to 1 core.");
+            return alloc::vec![0];
         }
-        if entry.length == 0 { break; }
-        current_ptr += entry.length as u64;
-    }
+    };
 
     crate::serial_println!("[APIC] Detected {} active CPU cores dynamically.", ids.len());
     ids
@@ -136,6 +238,17 @@ pub fn send_sipi(target_apic_id: u32, vector: u8) {
     }
 }
 
+/// NMI to every other core (ICR shorthand "all excluding self"). Does
+/// nothing before the local APIC is mapped, when there's only this core.
+pub fn send_nmi_to_others() {
+    let apic_virt = unsafe { LOCAL_APIC_VIRT };
+    if apic_virt == 0 { return; }
+    unsafe {
+        let icr_low = (apic_virt + ICR_LOW) as *mut u32;
+        write_volatile(icr_low, 0x000C_4400);
+    }
+}
+
 pub fn init_ap() {
     let apic_virt = get_apic_virt_base();
     unsafe {
@@ -155,27 +268,22 @@ pub struct IoApicEntry {
     pub global_system_interrupt_base: u32,
 }
 
-pub fn get_ioapic_phys_addr() -> Option<u64> {
-    let madt_phys = unsafe { ACPI_INFO.madt_addr }?;
-    let madt_virt = crate::memory::phys_to_virt(madt_phys)?;
-    let header = unsafe { &*(madt_virt as *const MadtHeader) };
-    
-    let end_ptr = madt_virt + header.length as u64;
-    let mut current_ptr = madt_virt + core::mem::size_of::<MadtHeader>() as u64;
-
-    while current_ptr < end_ptr {
-        let entry = unsafe { &*(current_ptr as *const MadtEntryHeader) };
-        if entry.entry_type == 1 { 
-            let io_apic = unsafe { &*(current_ptr as *const IoApicEntry) };
-            return Some(io_apic.io_apic_address as u64);
-        }
-        if entry.length == 0 { break; }
-        current_ptr += entry.length as u64;
-    }
-    None
+/// (physical address, first GSI) of the IOAPIC.
+pub fn get_ioapic_phys_addr() -> Option<(u64, u32)> {
+    let madt = MADT_INFO.lock();
+    let madt = madt.as_ref()?;
+    Some((madt.ioapic_addr?, madt.ioapic_gsi_base))
 }
 
 pub fn end_of_interrupt() {
+    if !APIC_MODE.load(Ordering::Relaxed) {
+        // PIC mode: non-specific EOI to both chips; the slave ignores it when idle
+        unsafe {
+            x86_64::instructions::port::Port::<u8>::new(0xA0).write(0x20);
+            x86_64::instructions::port::Port::<u8>::new(0x20).write(0x20);
+        }
+        return;
+    }
     let apic_virt = get_apic_virt_base(); 
     unsafe {
         let eoi_ptr = (apic_virt + 0xB0) as *mut u32;
@@ -185,7 +293,49 @@ pub fn end_of_interrupt() {
 
 const LVT_TIMER: u64 = 0x320;
 const TIMER_INITIAL_COUNT: u64 = 0x380;
+const TIMER_CURRENT_COUNT: u64 = 0x390;
 const TIMER_DIVIDE_CONFIG: u64 = 0x3E0;
+const LVT_MASKED: u32 = 0x10000;
+
+/// Measures the local APIC timer against the PIT so init_timer can fire at
+/// exactly TICK_HZ. All cores share the bus clock, so the BSP does this once.
+pub fn calibrate_timer() {
+    let apic_virt = get_apic_virt_base();
+    let reg = |off: u64| (apic_virt + off) as *mut u32;
+    let elapsed = unsafe {
+        write_volatile(reg(TIMER_DIVIDE_CONFIG), 0x3); // Divide by 16, as init_timer uses
+        write_volatile(reg(LVT_TIMER), LVT_MASKED);
+        crate::time::pit_gate_10ms(
+            || write_volatile(reg(TIMER_INITIAL_COUNT), u32::MAX),
+            || u32::MAX - read_volatile(reg(TIMER_CURRENT_COUNT)),
+        )
+    };
+    unsafe { write_volatile(reg(TIMER_INITIAL_COUNT), 0); }
+
+    match elapsed {
+        Some(per_10ms) if per_10ms >= 1000 => {
+            let per_tick = (per_10ms as u64 * crate::time::MS_PER_TICK / 10) as u32;
+            TIMER_COUNT_PER_TICK.store(per_tick, Ordering::SeqCst);
+            crate::serial_println!("[APIC] Timer calibrated: {} counts per {} ms tick ({} Hz)",
+                per_tick, crate::time::MS_PER_TICK, crate::time::TICK_HZ);
+        }
+        _ => crate::serial_println!("[APIC] Timer calibration failed. Using the default count {:#x}.", FALLBACK_TIMER_COUNT),
+    }
+}
+
+/// Starts this core's scheduler tick on `vector`: the local APIC timer, or the
+/// PIT through the PIC when booted without a MADT.
+pub fn start_ticks(vector: u8) {
+    if APIC_MODE.load(Ordering::Relaxed) {
+        init_timer(vector);
+        return;
+    }
+    crate::time::start_pit_ticks();
+    // Unmask IRQ0 (timer), IRQ1 (keyboard), IRQ2 (cascade) and IRQ12 (PS/2 mouse)
+    unsafe { crate::interrupts::PICS.lock().write_masks(0xF8, 0xEF); }
+    crate::serial_println!("[PIC] Legacy mode: PIT ticking at {} Hz", crate::time::TICK_HZ);
+}
+
 pub fn init_timer(vector: u8) {
     let apic_virt = get_apic_virt_base();
     unsafe {
@@ -201,8 +351,9 @@ pub fn init_timer(vector: u8) {
         let lvt_timer_ptr = (apic_virt + 0x320) as *mut u32;
         core::ptr::write_volatile(lvt_timer_ptr, 0x20000 | (vector as u32));
 
-        //  THE SCHEDULER FIX: Set the initial count to a fast 1ms tick rate!
+        // Periodic at TICK_HZ once calibrated
+        let count = match TIMER_COUNT_PER_TICK.load(Ordering::Relaxed) { 0 => FALLBACK_TIMER_COUNT, c => c };
         let icr_ptr = (apic_virt + 0x380) as *mut u32;
-        core::ptr::write_volatile(icr_ptr, 0x0000_A000); 
+        core::ptr::write_volatile(icr_ptr, count); 
     }
 }
\ No newline at end of file
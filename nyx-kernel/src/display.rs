@@ -0,0 +1,110 @@
+use spin::Mutex;
+
+// ==========================================
+// DISPLAY LIST
+// ==========================================
+// The desktop is a set of rectangles in one coordinate space that the
+// pointer moves over, the primary display at the origin. Only the boot
+// framebuffer exists as hardware today; it is the primary. Virtual displays
+// (Syscall 558, behind the Terminal's `display add-virtual`) sit to the
+// right of the others with their top edges aligned and have no screen of
+// their own: the compositor draws the whole desktop and scales it into the
+// one framebuffer, so they're there to exercise the multi-rect paths. The
+// table is fixed-size and its lock is only taken with interrupts off, since
+// the mouse interrupt clamps against it.
+
+pub const MAX_DISPLAYS: usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Display {
+    pub x: usize,
+    pub y: usize,
+    pub w: usize,
+    pub h: usize,
+    /// Registered by hand rather than found on the hardware
+    pub is_virtual: bool,
+}
+
+impl Display {
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        x >= self.x && x < self.x + self.w && y >= self.y && y < self.y + self.h
+    }
+
+    /// The point of this display nearest to (x, y).
+    fn nearest(&self, x: usize, y: usize) -> (usize, usize) {
+        (x.clamp(self.x, self.x + self.w - 1), y.clamp(self.y, self.y + self.h - 1))
+    }
+}
+
+struct DisplayList {
+    displays: [Option<Display>; MAX_DISPLAYS],
+    count: usize,
+}
+
+static DISPLAYS: Mutex<DisplayList> = Mutex::new(DisplayList { displays: [None; MAX_DISPLAYS], count: 0 });
+
+fn with_list<R>(f: impl FnOnce(&mut DisplayList) -> R) -> R {
+    x86_64::instructions::interrupts::without_interrupts(|| f(&mut DISPLAYS.lock()))
+}
+
+/// Makes the boot framebuffer the primary display, dropping any others.
+pub fn init(w: usize, h: usize) {
+    with_list(|list| {
+        list.displays = [None; MAX_DISPLAYS];
+        list.displays[0] = Some(Display { x: 0, y: 0, w, h, is_virtual: false });
+        list.count = 1;
+    });
+}
+
+/// Adds a w x h virtual display to the right of the rightmost one and
+/// returns its index, or None when the table is full or the size is zero.
+pub fn add_virtual(w: usize, h: usize) -> Option<usize> {
+    if w == 0 || h == 0 { return None; }
+    with_list(|list| {
+        if list.count == 0 || list.count == MAX_DISPLAYS { return None; }
+        let x = list.displays.iter().flatten().map(|d| d.x + d.w).max().unwrap_or(0);
+        let index = list.count;
+        list.displays[index] = Some(Display { x, y: 0, w, h, is_virtual: true });
+        list.count += 1;
+        Some(index)
+    })
+}
+
+/// Resizes the primary display after a mode change. Virtual displays move
+/// to stay side by side to its right.
+pub fn resize_primary(w: usize, h: usize) {
+    with_list(|list| {
+        let mut x = 0;
+        for d in list.displays.iter_mut().flatten() {
+            if d.is_virtual { d.x = x; } else { d.w = w; d.h = h; }
+            x = d.x + d.w;
+        }
+    });
+}
+
+pub fn count() -> usize { with_list(|list| list.count) }
+
+pub fn get(index: usize) -> Option<Display> {
+    with_list(|list| list.displays.get(index).copied().flatten())
+}
+
+/// Width and height of the box around every display.
+pub fn extent() -> (usize, usize) {
+    with_list(|list| list.displays.iter().flatten().fold((0, 0), |(w, h), d| (w.max(d.x + d.w), h.max(d.y + d.h))))
+}
+
+/// Keeps a pointer position on some display: points off every display move
+/// to the nearest edge of the closest one. With no displays yet, (0, 0).
+pub fn clamp(x: usize, y: usize) -> (usize, usize) {
+    with_list(|list| {
+        let mut best = (0, 0);
+        let mut best_dist = usize::MAX;
+        for d in list.displays.iter().flatten() {
+            if d.contains(x, y) { return (x, y); }
+            let (nx, ny) = d.nearest(x, y);
+            let dist = nx.abs_diff(x) + ny.abs_diff(y);
+            if dist < best_dist { best = (nx, ny); best_dist = dist; }
+        }
+        best
+    })
+}
@@ -1,4 +1,5 @@
 use crate::pci::{PciDriver, PciDevice};
+use alloc::string::String;
 use core::mem::size_of;
 
 // --- AHCI MEMORY STRUCTURES ---
@@ -71,10 +72,56 @@ pub struct PrdtEntry {
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub enum PortType { None, SATA, SATAPI, SEMB, PM, Unknown(u32) }
 
+pub const SECTOR_SIZE: usize = 512;
+/// Sectors per command: one bounce page
+pub const MAX_SECTORS_PER_CMD: usize = 4096 / SECTOR_SIZE;
+
+const HBA_PORT_CMD_ST: u32 = 1 << 0;
+const HBA_PORT_CMD_FRE: u32 = 1 << 4;
+const HBA_PORT_CMD_FR: u32 = 1 << 14;
+const HBA_PORT_CMD_CR: u32 = 1 << 15;
+const HBA_PORT_IS_TFES: u32 = 1 << 30;
+const ATA_DEV_BUSY: u32 = 0x80;
+const ATA_DEV_DRQ: u32 = 0x08;
+const ATA_CMD_READ_DMA_EXT: u8 = 0x25;
+const ATA_CMD_WRITE_DMA_EXT: u8 = 0x35;
+const ATA_CMD_IDENTIFY: u8 = 0xEC;
+
+// The spec gives the command and FIS engines 500 ms to stop. A command may
+// take much longer than the rest while the disk spins up.
+const ENGINE_TIMEOUT_MS: u64 = 500;
+const BUSY_TIMEOUT_MS: u64 = 1000;
+const COMMAND_TIMEOUT_MS: u64 = 5000;
+
+// Layout of the per-port control page (all offsets satisfy the HBA alignment rules)
+const CLB_OFFSET: usize = 0;      // 32 command headers, 1 KiB aligned
+const FB_OFFSET: usize = 1024;    // Received FIS area, 256 B aligned
+const CTBA_OFFSET: usize = 1280;  // Command table for slot 0, 128 B aligned
+
+/// Memory we own for one port: the control page (command list, FIS area,
+/// command table) and a bounce page the PRDT points at.
+struct PortMemory {
+    ctrl_phys: u64,
+    ctrl_virt: u64,
+    data_phys: u64,
+    data_virt: u64,
+}
+
+/// What IDENTIFY DEVICE told us about the disk behind a port.
+#[derive(Clone)]
+pub struct DiskInfo {
+    pub model: String,
+    pub serial: String,
+    pub sectors: u64,
+    pub sector_size: u32,
+}
+
 pub struct AhciDriver {
     pub device: PciDevice,
     pub abar: u64, 
     pub mem: &'static mut HbaMemory, 
+    ports: [Option<PortMemory>; 32],
+    pub disks: [Option<DiskInfo>; 32],
 }
 
 impl AhciDriver {
@@ -88,7 +135,7 @@ impl AhciDriver {
                     unsafe {
                         if crate::memory::map_mmio(bar5, 0x2000).is_ok() {
                             let hba_mem = &mut *(bar5 as *mut HbaMemory);
-                            let mut driver = Self { device: dev, abar: bar5, mem: hba_mem };
+                            let mut driver = Self { device: dev, abar: bar5, mem: hba_mem, ports: core::array::from_fn(|_| None), disks: core::array::from_fn(|_| None) };
                             driver.configure();
                             return Some(driver);
                         }
@@ -112,10 +159,7 @@ impl AhciDriver {
                 
                 // Stop engine
                 port.cmd &= !0x11;
-                for _ in 0..1000 {
-                    if (port.cmd & 0xC000) == 0 { break; }
-                    core::hint::spin_loop();
-                }
+                crate::watchdog::spin_until("AHCI", "port stop", ENGINE_TIMEOUT_MS, || (port.cmd & 0xC000) == 0);
 
                 if supports_spinup {
                     port.cmd |= 1 << 1; // SUD
@@ -126,20 +170,84 @@ impl AhciDriver {
 
                 // COMRESET
                 port.sctl = (port.sctl & !0x0F) | 1;
-                for _ in 0..1_000_000 { core::hint::spin_loop(); }
+                crate::time::sleep_ms(1); // DET=1 must be held for at least 1 ms
                 port.sctl &= !0x0F;
 
                 // Wait for link
                 for _ in 0..200 {
                     if (port.ssts & 0x0F) == 3 { break; }
-                    for _ in 0..1_000_000 { core::hint::spin_loop(); }
+                    crate::time::sleep_ms(1);
                 }
 
                 port.serr = 0xFFFFFFFF;
+
+                // After our own COMRESET clb/fb still point at whatever firmware left there
+                if self.check_type(i) != PortType::None {
+                    if !self.setup_port(i) {
+                        crate::serial_println!("[AHCI] Port {}: could not allocate command memory", i);
+                        continue;
+                    }
+                    if self.check_type(i) == PortType::SATA {
+                        self.disks[i] = self.identify_device(i);
+                        if let Some(disk) = &self.disks[i] {
+                            crate::serial_println!("[AHCI] Port {}: {} ({} MiB, {}-byte sectors)", i, disk.model,
+                                disk.sectors * disk.sector_size as u64 / (1024 * 1024), disk.sector_size);
+                        }
+                    }
+                }
             }
         }
     }
 
+    /// Allocates and programs the command list, FIS area and command table of a port,
+    /// then starts its command engine.
+    fn setup_port(&mut self, port_no: usize) -> bool {
+        // Without 64-bit addressing (CAP.S64A) the HBA can only reach the low 4 GiB
+        let below_4gb = self.mem.cap & (1 << 31) == 0;
+        let frame = match crate::memory::allocate_contiguous(2, 4096, below_4gb) { Some(f) => f, None => return false };
+        let ctrl_phys = frame.start_address().as_u64();
+        let data_phys = ctrl_phys + 4096;
+        let (ctrl_virt, data_virt) = match (crate::memory::phys_to_virt(ctrl_phys), crate::memory::phys_to_virt(data_phys)) {
+            (Some(c), Some(d)) => (c, d),
+            _ => return false,
+        };
+        unsafe { core::ptr::write_bytes(ctrl_virt as *mut u8, 0, 4096); }
+
+        let port = &mut self.mem.ports[port_no];
+        Self::stop_engine(port);
+
+        let clb = ctrl_phys + CLB_OFFSET as u64;
+        let fb = ctrl_phys + FB_OFFSET as u64;
+        port.clb = clb as u32; port.clbu = (clb >> 32) as u32;
+        port.fb = fb as u32;   port.fbu = (fb >> 32) as u32;
+
+        // Every header we use points at the single command table
+        let ctba = ctrl_phys + CTBA_OFFSET as u64;
+        let header = unsafe { &mut *((ctrl_virt + CLB_OFFSET as u64) as *mut CommandHeader) };
+        header.ctba = ctba as u32;
+        header.ctbau = (ctba >> 32) as u32;
+
+        port.serr = 0xFFFFFFFF;
+        port.is = 0xFFFFFFFF;
+        Self::start_engine(port);
+
+        self.ports[port_no] = Some(PortMemory { ctrl_phys, ctrl_virt, data_phys, data_virt });
+        true
+    }
+
+    fn stop_engine(port: &mut HbaPort) {
+        port.cmd &= !HBA_PORT_CMD_ST;
+        port.cmd &= !HBA_PORT_CMD_FRE;
+        crate::watchdog::spin_until("AHCI", "engine stop", ENGINE_TIMEOUT_MS,
+            || port.cmd & (HBA_PORT_CMD_FR | HBA_PORT_CMD_CR) == 0);
+    }
+
+    fn start_engine(port: &mut HbaPort) {
+        crate::watchdog::spin_until("AHCI", "engine start", ENGINE_TIMEOUT_MS, || port.cmd & HBA_PORT_CMD_CR == 0);
+        port.cmd |= HBA_PORT_CMD_FRE;
+        port.cmd |= HBA_PORT_CMD_ST;
+    }
+
     pub fn check_type(&self, port_index: usize) -> PortType {
         let port = &self.mem.ports[port_index];
         let ssts = port.ssts;
@@ -152,26 +260,82 @@ impl AhciDriver {
         }
     }
 
-    pub unsafe fn read(&mut self, port_no: usize, sector: u64, buf: &mut [u8]) -> bool {
+    /// Sends IDENTIFY DEVICE to `port_no` and decodes model, serial and capacity.
+    pub fn identify_device(&mut self, port_no: usize) -> Option<DiskInfo> {
+        if !self.issue(port_no, false, SECTOR_SIZE, |fis| fis.command = ATA_CMD_IDENTIFY) { return None; }
+
+        let data_virt = self.ports[port_no].as_ref()?.data_virt;
+        let words = unsafe { &*(data_virt as *const [u16; 256]) };
+
+        // Word 83 bit 10: 48-bit addressing, capacity in words 100..103; otherwise words 60..61
+        let sectors = if words[83] & (1 << 10) != 0 {
+            (0..4).fold(0u64, |acc, i| acc | (words[100 + i] as u64) << (16 * i))
+        } else {
+            words[60] as u64 | (words[61] as u64) << 16
+        };
+
+        // Word 106 is valid when bit 14 is set and bit 15 clear; bit 12 means words 117..118
+        // hold the logical sector size in 16-bit words
+        let w106 = words[106];
+        let sector_size = if w106 & 0xC000 == 0x4000 && w106 & (1 << 12) != 0 {
+            (words[117] as u32 | (words[118] as u32) << 16) * 2
+        } else {
+            SECTOR_SIZE as u32
+        };
+
+        Some(DiskInfo {
+            model: ata_string(&words[27..47]),
+            serial: ata_string(&words[10..20]),
+            sectors,
+            sector_size,
+        })
+    }
+
+    /// First port with an initialized SATA disk behind it.
+    pub fn first_sata_port(&self) -> Option<usize> {
+        (0..32).find(|&i| self.ports[i].is_some() && self.check_type(i) == PortType::SATA)
+    }
+
+    /// Issues one ATA command on slot 0 that moves `bytes` through the port's bounce page.
+    /// The H2D FIS is built by `fill`.
+    fn issue(&mut self, port_no: usize, write: bool, bytes: usize, fill: impl FnOnce(&mut FisRegH2D)) -> bool {
+        let (ctrl_virt, data_phys) = match &self.ports[port_no] { Some(m) => (m.ctrl_virt, m.data_phys), None => return false };
         let port = &mut self.mem.ports[port_no];
+
+        // Wait for the device to finish whatever it was doing
+        if !crate::watchdog::spin_until("AHCI", "device busy", BUSY_TIMEOUT_MS, || port.tfd & (ATA_DEV_BUSY | ATA_DEV_DRQ) == 0) {
+            return false;
+        }
         port.is = 0xFFFFFFFF;
 
-        let clb = (port.clb as u64) | ((port.clbu as u64) << 32);
-        let cmd_header = &mut *(clb as *mut CommandHeader);
-        
-        // CFL is length in DWORDS
-        let cfl = (size_of::<FisRegH2D>() / 4) as u16;
-        cmd_header.opts = cfl; // Bit 6 (Write) is 0 for Read
-        cmd_header.prdtl = 1;
+        unsafe {
+            let header = &mut *((ctrl_virt + CLB_OFFSET as u64) as *mut CommandHeader);
+            // CFL is length in DWORDS, bit 6 marks host-to-device data
+            header.opts = (size_of::<FisRegH2D>() / 4) as u16 | if write { 1 << 6 } else { 0 };
+            header.prdtl = 1;
+            header.prdbc = 0;
 
-        let ctba = (cmd_header.ctba as u64) | ((cmd_header.ctbau as u64) << 32);
-        let cmd_table = &mut *(ctba as *mut CommandTable);
-        
-        for i in 0..64 { cmd_table.cfis[i] = 0; }
-        let fis = &mut *(cmd_table.cfis.as_mut_ptr() as *mut FisRegH2D);
-        
-        fis.fis_type = 0x27;
-        fis.command = 0x25; // READ DMA EXT
+            let table = &mut *((ctrl_virt + CTBA_OFFSET as u64) as *mut CommandTable);
+            table.cfis = [0; 64];
+            let fis = &mut *(table.cfis.as_mut_ptr() as *mut FisRegH2D);
+            fis.fis_type = 0x27;
+            fis.pmport = 1 << 7; // Command, not control
+            fill(fis);
+
+            table.prdt[0].dba = data_phys as u32;
+            table.prdt[0].dbau = (data_phys >> 32) as u32;
+            table.prdt[0].dbc = (bytes - 1) as u32;
+        }
+
+        port.ci = 1;
+        // A task file error ends the wait as well as a completion does
+        let finished = crate::watchdog::spin_until("AHCI", "command", COMMAND_TIMEOUT_MS,
+            || port.is & HBA_PORT_IS_TFES != 0 || port.ci & 1 == 0);
+        finished && port.is & HBA_PORT_IS_TFES == 0
+    }
+
+    fn rw_fis(fis: &mut FisRegH2D, command: u8, sector: u64, count: usize) {
+        fis.command = command;
         fis.lba0 = sector as u8;
         fis.lba1 = (sector >> 8) as u8;
         fis.lba2 = (sector >> 16) as u8;
@@ -179,24 +343,55 @@ impl AhciDriver {
         fis.lba3 = (sector >> 24) as u8;
         fis.lba4 = (sector >> 32) as u8;
         fis.lba5 = (sector >> 40) as u8;
-        fis.countl = 1;
+        fis.countl = count as u8;
+        fis.counth = (count >> 8) as u8;
+    }
 
-        let buf_phys = crate::memory::virt_to_phys(buf.as_ptr() as u64).unwrap_or(0);
-        if buf_phys == 0 { return false; }
+    /// Reads `count` (at most MAX_SECTORS_PER_CMD) sectors into `buf`.
+    pub fn read(&mut self, port_no: usize, sector: u64, count: usize, buf: &mut [u8]) -> bool {
+        let bytes = count * SECTOR_SIZE;
+        if count == 0 || count > MAX_SECTORS_PER_CMD || buf.len() < bytes { return false; }
+        if !self.issue(port_no, false, bytes, |fis| Self::rw_fis(fis, ATA_CMD_READ_DMA_EXT, sector, count)) { return false; }
 
-        cmd_table.prdt[0].dba = buf_phys as u32;
-        cmd_table.prdt[0].dbau = (buf_phys >> 32) as u32;
-        cmd_table.prdt[0].dbc = 511; 
+        let data_virt = self.ports[port_no].as_ref().map(|m| m.data_virt).unwrap_or(0);
+        unsafe { core::ptr::copy_nonoverlapping(data_virt as *const u8, buf.as_mut_ptr(), bytes); }
+        true
+    }
 
-        while (port.ci & 1) != 0 { core::hint::spin_loop(); }
-        port.ci |= 1;
+    /// Writes `count` (at most MAX_SECTORS_PER_CMD) sectors from `buf`.
+    pub fn write(&mut self, port_no: usize, sector: u64, count: usize, buf: &[u8]) -> bool {
+        let bytes = count * SECTOR_SIZE;
+        if count == 0 || count > MAX_SECTORS_PER_CMD || buf.len() < bytes { return false; }
+        let data_virt = match &self.ports[port_no] { Some(m) => m.data_virt, None => return false };
+        unsafe { core::ptr::copy_nonoverlapping(buf.as_ptr(), data_virt as *mut u8, bytes); }
+        self.issue(port_no, true, bytes, |fis| Self::rw_fis(fis, ATA_CMD_WRITE_DMA_EXT, sector, count))
+    }
+}
 
-        loop {
-            if (port.ci & 1) == 0 { break; }
-            if (port.is & (1 << 30)) != 0 { return false; }
-            core::hint::spin_loop();
+/// ATA strings store two characters per word, high byte first, padded with spaces.
+fn ata_string(words: &[u16]) -> String {
+    let mut s = String::with_capacity(words.len() * 2);
+    for w in words {
+        for b in [(w >> 8) as u8, *w as u8] {
+            s.push(if b.is_ascii_graphic() || b == b' ' { b as char } else { ' ' });
         }
+    }
+    String::from(s.trim())
+}
 
-        true
+/// One SATA disk as a sector device, so the FS bridge can fall back to it
+/// when there is no NVMe controller.
+pub struct AhciDisk {
+    pub driver: AhciDriver,
+    pub port: usize,
+}
+
+impl crate::drivers::block::BlockDevice for AhciDisk {
+    fn read_sector(&mut self, sector: u64, buf: &mut [u8]) -> bool {
+        self.driver.read(self.port, sector, 1, buf)
     }
-}
\ No newline at end of file
+
+    fn write_sector(&mut self, sector: u64, buf: &[u8]) -> bool {
+        self.driver.write(self.port, sector, 1, buf)
+    }
+}
@@ -0,0 +1,124 @@
+use spin::Mutex;
+use x86_64::instructions::port::Port;
+use crate::pci::{PciDevice, PciDriver};
+
+// ==========================================
+// BOCHS / QEMU STDVGA ("DISPI")
+// ==========================================
+// The emulated adapter behind QEMU's -vga std (and Bochs, and VirtualBox's
+// older VGA) takes a new mode through a handful of 16-bit registers on
+// ports 0x1CE/0x1CF, no firmware call needed, which makes it the one screen
+// whose resolution can change while the kernel runs. Its linear framebuffer
+// is BAR 0; GOP already put the boot mode there, so the driver is only
+// used when the boot framebuffer lies inside that BAR. Modes are always
+// 32 bpp and rows exactly as wide as the screen.
+
+pub const VENDOR_ID: u16 = 0x1234;
+pub const DEVICE_ID: u16 = 0x1111;
+
+const PORT_INDEX: u16 = 0x01CE;
+const PORT_DATA: u16 = 0x01CF;
+
+const REG_ID: u16 = 0x0;
+const REG_XRES: u16 = 0x1;
+const REG_YRES: u16 = 0x2;
+const REG_BPP: u16 = 0x3;
+const REG_ENABLE: u16 = 0x4;
+const REG_BANK: u16 = 0x5;
+const REG_VIRT_WIDTH: u16 = 0x6;
+const REG_VIRT_HEIGHT: u16 = 0x7;
+const REG_X_OFFSET: u16 = 0x8;
+const REG_Y_OFFSET: u16 = 0x9;
+const REG_VIDEO_MEMORY_64K: u16 = 0xA;
+
+// 0xB0C2 is the first interface revision with 32 bpp
+const ID_MIN: u16 = 0xB0C2;
+const ID_MAX: u16 = 0xB0CF;
+const ENABLED: u16 = 0x01;
+const LFB_ENABLED: u16 = 0x40;
+
+pub const MIN_W: usize = 640;
+pub const MIN_H: usize = 480;
+// The registers are 16-bit; QEMU stops well before this anyway
+const MAX_DIM: usize = 8192;
+
+pub struct BochsVbe {
+    vram_phys: u64,
+    vram_size: usize,
+    /// The mode GOP set up, which "native" goes back to
+    pub boot_mode: (usize, usize),
+}
+
+pub static BOCHS_VBE: Mutex<Option<BochsVbe>> = Mutex::new(None);
+
+fn read_reg(index: u16) -> u16 {
+    unsafe {
+        Port::<u16>::new(PORT_INDEX).write(index);
+        Port::<u16>::new(PORT_DATA).read()
+    }
+}
+
+fn write_reg(index: u16, value: u16) {
+    unsafe {
+        Port::<u16>::new(PORT_INDEX).write(index);
+        Port::<u16>::new(PORT_DATA).write(value);
+    }
+}
+
+impl BochsVbe {
+    /// Programs a w x h x 32 mode and maps the part of VRAM it uses. Returns
+    /// the framebuffer's address and its stride in pixels.
+    pub fn set_mode(&mut self, w: usize, h: usize) -> Option<(u64, usize)> {
+        if w < MIN_W || h < MIN_H || w > MAX_DIM || h > MAX_DIM || w * h * 4 > self.vram_size { return None; }
+        let len = w * h * 4;
+        let virt = unsafe { crate::memory::map_mmio(self.vram_phys, len) }.ok()?;
+
+        write_reg(REG_ENABLE, 0);
+        write_reg(REG_BPP, 32);
+        write_reg(REG_XRES, w as u16);
+        write_reg(REG_YRES, h as u16);
+        write_reg(REG_BANK, 0);
+        write_reg(REG_VIRT_WIDTH, w as u16);
+        write_reg(REG_VIRT_HEIGHT, h as u16);
+        write_reg(REG_X_OFFSET, 0);
+        write_reg(REG_Y_OFFSET, 0);
+        write_reg(REG_ENABLE, ENABLED | LFB_ENABLED);
+
+        // The adapter rounds sizes it can't do; trust what it reports
+        let (got_w, got_h) = (read_reg(REG_XRES) as usize, read_reg(REG_YRES) as usize);
+        if (got_w, got_h) != (w, h) {
+            crate::serial_println!("[BOCHS] Asked for {}x{}, got {}x{}", w, h, got_w, got_h);
+            return None;
+        }
+        let stride = (read_reg(REG_VIRT_WIDTH) as usize).max(w);
+        Some((virt, stride))
+    }
+}
+
+/// Takes the adapter found by the PCI scan, if it is the one the boot
+/// framebuffer is on.
+pub fn probe(dev: &PciDevice) {
+    let id = read_reg(REG_ID);
+    if !(ID_MIN..=ID_MAX).contains(&id) {
+        crate::serial_println!("[BOCHS] DISPI interface {:#06x} is too old", id);
+        return;
+    }
+    let mut cmd = PciDriver::read_config(dev.bus, dev.device, dev.func, 0x04);
+    cmd |= 0x02;
+    PciDriver::write_config(dev.bus, dev.device, dev.func, 0x04, cmd);
+
+    let vram_phys = match PciDriver::new().get_bar_address(dev, 0) { Some(a) => a, None => return };
+    let vram_size = read_reg(REG_VIDEO_MEMORY_64K) as usize * 64 * 1024;
+    let fb_phys = unsafe { crate::gui::FRAMEBUFFER_PHYS_ADDR };
+    if fb_phys < vram_phys || fb_phys >= vram_phys + vram_size as u64 {
+        crate::serial_println!("[BOCHS] Not the boot display; leaving it alone");
+        return;
+    }
+    let bpp = unsafe { crate::gui::SCREEN_PAINTER.as_ref().map_or(0, |p| p.info.bytes_per_pixel) };
+    if bpp != 4 { return; }
+
+    let boot_mode = crate::gui::native_size();
+    crate::serial_println!("[BOCHS] DISPI {:#06x}, {} KiB VRAM at {:#x}, boot mode {}x{}",
+        id, vram_size / 1024, vram_phys, boot_mode.0, boot_mode.1);
+    *BOCHS_VBE.lock() = Some(BochsVbe { vram_phys, vram_size, boot_mode });
+}
@@ -259,7 +259,7 @@ impl IntelGpuDriver {
         sr_data.write(sr01);
         
         // Brief wait
-        for _ in 0..100_000 { core::hint::spin_loop(); }
+        crate::time::sleep_ms(1);
 
         // 2. Disable VGA routing in MMIO
         let mut vga_ctl = self.read_reg(VGA_CONTROL);
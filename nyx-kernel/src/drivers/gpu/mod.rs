@@ -1 +1,2 @@
-pub mod intel;
\ No newline at end of file
+pub mod intel;
+pub mod bochs;
@@ -2,4 +2,5 @@ pub mod block;
 pub mod nvme;
 pub mod ahci;
 pub mod net;  
-pub mod gpu;
\ No newline at end of file
+pub mod gpu;
+pub mod virtio_gpu;
@@ -0,0 +1,346 @@
+use crate::pci::{PciDevice, PciDriver};
+use core::ptr::{addr_of, addr_of_mut, read_volatile, write_volatile};
+use core::sync::atomic::{fence, Ordering};
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use smoltcp::phy::{Device, DeviceCapabilities, ChecksumCapabilities};
+use smoltcp::time::Instant;
+
+// ==========================================
+// INTEL 8254x GIGABIT ETHERNET (E1000)
+// ==========================================
+// QEMU's default NIC (-nic user gives an 82540EM) and the one most other
+// hypervisors emulate. The card walks two rings of 16-byte descriptors in
+// memory: on receive it fills the buffer the descriptor points at and sets
+// its DD bit; on transmit it sends the buffer and sets DD once it's done.
+// The head registers are the card's place in each ring and the tail ones
+// ours. Interrupts stay masked: the idle task and every socket syscall
+// already call poll_network, so received frames are picked up from there.
+
+pub const VENDOR_ID: u16 = 0x8086;
+/// 82540EM (QEMU's e1000), 82545EM (VMware's)
+pub const DEVICE_IDS: [u16; 2] = [0x100E, 0x100F];
+
+const MMIO_SIZE: usize = 0x20000;
+
+const REG_CTRL: usize = 0x0000;
+const REG_STATUS: usize = 0x0008;
+const REG_ICR: usize = 0x00C0;
+const REG_IMC: usize = 0x00D8;
+const REG_RCTL: usize = 0x0100;
+const REG_TCTL: usize = 0x0400;
+const REG_TIPG: usize = 0x0410;
+const REG_RDBAL: usize = 0x2800;
+const REG_RDBAH: usize = 0x2804;
+const REG_RDLEN: usize = 0x2808;
+const REG_RDH: usize = 0x2810;
+const REG_RDT: usize = 0x2818;
+const REG_TDBAL: usize = 0x3800;
+const REG_TDBAH: usize = 0x3804;
+const REG_TDLEN: usize = 0x3808;
+const REG_TDH: usize = 0x3810;
+const REG_TDT: usize = 0x3818;
+/// Multicast table: 128 words, cleared so only broadcast and our MAC get in
+const REG_MTA: usize = 0x5200;
+const REG_RAL0: usize = 0x5400;
+const REG_RAH0: usize = 0x5404;
+
+const CTRL_ASDE: u32 = 1 << 5;
+const CTRL_SLU: u32 = 1 << 6;
+const CTRL_RST: u32 = 1 << 26;
+const STATUS_LU: u32 = 1 << 1;
+/// RAH: the address in RAL/RAH is valid and frames to it are accepted
+const RAH_AV: u32 = 1 << 31;
+
+// RCTL: receiver on, broadcasts accepted, 2048-byte buffers (BSIZE 00) and
+// the CRC stripped before the frame reaches the buffer
+const RCTL_EN: u32 = 1 << 1;
+const RCTL_BAM: u32 = 1 << 15;
+const RCTL_SECRC: u32 = 1 << 26;
+// TCTL: transmitter on, short frames padded to 64 bytes, and the
+// collision settings the manual gives for full duplex
+const TCTL_EN: u32 = 1 << 1;
+const TCTL_PSP: u32 = 1 << 3;
+const TCTL_CT: u32 = 0x0F << 4;
+const TCTL_COLD: u32 = 0x40 << 12;
+/// Inter-packet gap the manual gives for the 82540EM on copper
+const TIPG_DEFAULT: u32 = 0x0060_200A;
+
+const DESC_DD: u8 = 1 << 0;
+const DESC_EOP: u8 = 1 << 1;
+const CMD_EOP: u8 = 1 << 0;
+const CMD_IFCS: u8 = 1 << 1;
+const CMD_RS: u8 = 1 << 3;
+
+const RESET_TIMEOUT_MS: u64 = 100;
+
+// Ring lengths have to be multiples of 128 bytes, i.e. of 8 descriptors
+const NUM_RX_DESC: usize = 32;
+const NUM_TX_DESC: usize = 32;
+const BUFFER_SIZE: usize = 2048;
+/// Biggest frame smoltcp hands over: a 1500-byte MTU plus the Ethernet header
+const MAX_FRAME: usize = 1514;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct RxDesc {
+    addr: u64,
+    length: u16,
+    checksum: u16,
+    status: u8,
+    errors: u8,
+    special: u16,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct TxDesc {
+    addr: u64,
+    length: u16,
+    cso: u8,
+    cmd: u8,
+    status: u8,
+    css: u8,
+    special: u16,
+}
+
+// Aligned to their own size, so each one sits inside a single page of the
+// heap and virt_to_phys of its start covers all of it
+#[repr(C, align(512))]
+struct RxRing { descs: [RxDesc; NUM_RX_DESC] }
+
+#[repr(C, align(512))]
+struct TxRing { descs: [TxDesc; NUM_TX_DESC] }
+
+#[repr(C, align(2048))]
+struct DmaBuffer { data: [u8; BUFFER_SIZE] }
+
+pub struct E1000Driver {
+    mmio_base: u64,
+    pub mac_address: [u8; 6],
+    rx_ring: Box<RxRing>,
+    tx_ring: Box<TxRing>,
+    rx_buffers: Vec<Box<DmaBuffer>>,
+    tx_buffers: Vec<Box<DmaBuffer>>,
+    /// Next descriptor the card fills / we hand it
+    rx_index: usize,
+    tx_index: usize,
+}
+
+impl E1000Driver {
+    fn read32(&self, reg: usize) -> u32 { unsafe { read_volatile((self.mmio_base + reg as u64) as *const u32) } }
+    fn write32(&mut self, reg: usize, val: u32) { unsafe { write_volatile((self.mmio_base + reg as u64) as *mut u32, val) } }
+
+    fn new(mmio_base: u64) -> Self {
+        let empty_rx = RxDesc { addr: 0, length: 0, checksum: 0, status: 0, errors: 0, special: 0 };
+        let empty_tx = TxDesc { addr: 0, length: 0, cso: 0, cmd: 0, status: 0, css: 0, special: 0 };
+        Self {
+            mmio_base,
+            mac_address: [0; 6],
+            rx_ring: Box::new(RxRing { descs: [empty_rx; NUM_RX_DESC] }),
+            tx_ring: Box::new(TxRing { descs: [empty_tx; NUM_TX_DESC] }),
+            rx_buffers: (0..NUM_RX_DESC).map(|_| Box::new(DmaBuffer { data: [0; BUFFER_SIZE] })).collect(),
+            tx_buffers: (0..NUM_TX_DESC).map(|_| Box::new(DmaBuffer { data: [0; BUFFER_SIZE] })).collect(),
+            rx_index: 0,
+            tx_index: 0,
+        }
+    }
+
+    /// Resets the card, reads its MAC and starts both rings. False if the
+    /// reset never finished or DMA memory couldn't be translated.
+    fn initialize(&mut self) -> bool {
+        let ctrl = self.read32(REG_CTRL);
+        self.write32(REG_CTRL, ctrl | CTRL_RST);
+        let base = self.mmio_base;
+        let reset_done = crate::watchdog::spin_until("E1000", "reset", RESET_TIMEOUT_MS, || {
+            unsafe { read_volatile((base + REG_CTRL as u64) as *const u32) & CTRL_RST == 0 }
+        });
+        if !reset_done { return false; }
+
+        self.write32(REG_IMC, 0xFFFF_FFFF);
+        self.read32(REG_ICR);
+        let ctrl = self.read32(REG_CTRL);
+        self.write32(REG_CTRL, ctrl | CTRL_SLU | CTRL_ASDE);
+        for i in 0..128 { self.write32(REG_MTA + i * 4, 0); }
+
+        // The reset loads the EEPROM's address into receive address 0
+        let (ral, rah) = (self.read32(REG_RAL0), self.read32(REG_RAH0));
+        self.mac_address = [ral as u8, (ral >> 8) as u8, (ral >> 16) as u8, (ral >> 24) as u8, rah as u8, (rah >> 8) as u8];
+        self.write32(REG_RAH0, rah | RAH_AV);
+
+        for i in 0..NUM_RX_DESC {
+            let phys = match crate::memory::virt_to_phys(self.rx_buffers[i].data.as_ptr() as u64) { Some(p) => p, None => return false };
+            self.rx_ring.descs[i].addr = phys;
+            self.rx_ring.descs[i].status = 0;
+        }
+        // Every transmit slot starts out "done", i.e. free
+        for i in 0..NUM_TX_DESC {
+            let phys = match crate::memory::virt_to_phys(self.tx_buffers[i].data.as_ptr() as u64) { Some(p) => p, None => return false };
+            self.tx_ring.descs[i].addr = phys;
+            self.tx_ring.descs[i].status = DESC_DD;
+        }
+        let rx_ring_phys = match crate::memory::virt_to_phys(self.rx_ring.descs.as_ptr() as u64) { Some(p) => p, None => return false };
+        let tx_ring_phys = match crate::memory::virt_to_phys(self.tx_ring.descs.as_ptr() as u64) { Some(p) => p, None => return false };
+        fence(Ordering::SeqCst);
+
+        self.write32(REG_RDBAL, rx_ring_phys as u32);
+        self.write32(REG_RDBAH, (rx_ring_phys >> 32) as u32);
+        self.write32(REG_RDLEN, (NUM_RX_DESC * core::mem::size_of::<RxDesc>()) as u32);
+        self.write32(REG_RDH, 0);
+        // The tail is the last descriptor the card may fill, so all but one
+        self.write32(REG_RDT, (NUM_RX_DESC - 1) as u32);
+        self.write32(REG_RCTL, RCTL_EN | RCTL_BAM | RCTL_SECRC);
+
+        self.write32(REG_TDBAL, tx_ring_phys as u32);
+        self.write32(REG_TDBAH, (tx_ring_phys >> 32) as u32);
+        self.write32(REG_TDLEN, (NUM_TX_DESC * core::mem::size_of::<TxDesc>()) as u32);
+        self.write32(REG_TDH, 0);
+        self.write32(REG_TDT, 0);
+        self.write32(REG_TIPG, TIPG_DEFAULT);
+        self.write32(REG_TCTL, TCTL_EN | TCTL_PSP | TCTL_CT | TCTL_COLD);
+        true
+    }
+
+    pub fn link_up(&self) -> bool { self.read32(REG_STATUS) & STATUS_LU != 0 }
+
+    /// Interrupts stay masked, but reading ICR clears anything latched.
+    pub fn ack_interrupt(&mut self) { self.read32(REG_ICR); }
+
+    /// Copies the next received frame into `buf` and gives its descriptor
+    /// back to the card. Frames the card flagged as bad, or that were split
+    /// across descriptors (bigger than the 2048-byte buffers), are skipped.
+    pub fn receive_frame(&mut self, buf: &mut [u8]) -> Option<usize> {
+        loop {
+            let i = self.rx_index;
+            let desc = addr_of_mut!(self.rx_ring.descs[i]);
+            let status = unsafe { read_volatile(addr_of!((*desc).status)) };
+            if status & DESC_DD == 0 { return None; }
+            fence(Ordering::Acquire);
+
+            let (length, errors) = unsafe { (read_volatile(addr_of!((*desc).length)) as usize, read_volatile(addr_of!((*desc).errors))) };
+            let good = errors == 0 && status & DESC_EOP != 0 && length <= buf.len();
+            if good { buf[..length].copy_from_slice(&self.rx_buffers[i].data[..length]); }
+
+            unsafe { write_volatile(addr_of_mut!((*desc).status), 0) };
+            fence(Ordering::Release);
+            self.write32(REG_RDT, i as u32);
+            self.rx_index = (i + 1) % NUM_RX_DESC;
+            if good { return Some(length); }
+        }
+    }
+
+    /// Whether the next transmit slot is free.
+    pub fn can_send(&self) -> bool {
+        unsafe { read_volatile(addr_of!(self.tx_ring.descs[self.tx_index].status)) & DESC_DD != 0 }
+    }
+
+    /// Queues one Ethernet frame (14-byte header onwards, no CRC). False if
+    /// the ring is full or the frame is too big.
+    pub fn send_frame(&mut self, frame: &[u8]) -> bool {
+        if frame.len() > MAX_FRAME || !self.can_send() { return false; }
+        let i = self.tx_index;
+        self.tx_buffers[i].data[..frame.len()].copy_from_slice(frame);
+        let desc = addr_of_mut!(self.tx_ring.descs[i]);
+        unsafe {
+            write_volatile(addr_of_mut!((*desc).length), frame.len() as u16);
+            write_volatile(addr_of_mut!((*desc).cmd), CMD_EOP | CMD_IFCS | CMD_RS);
+            write_volatile(addr_of_mut!((*desc).status), 0);
+        }
+        fence(Ordering::SeqCst);
+        self.tx_index = (i + 1) % NUM_TX_DESC;
+        self.write32(REG_TDT, self.tx_index as u32);
+        true
+    }
+}
+
+/// Binds a card the PCI scan found and hands it to the network stack.
+pub fn probe(dev: &PciDevice) {
+    // Memory space and bus mastering; the card can't DMA without the latter
+    let mut cmd = PciDriver::read_config(dev.bus, dev.device, dev.func, 0x04);
+    cmd |= 0x06;
+    PciDriver::write_config(dev.bus, dev.device, dev.func, 0x04, cmd);
+
+    let mmio_phys = match PciDriver::new().get_bar_address(dev, 0) { Some(a) => a, None => return };
+    let mmio_base = match unsafe { crate::memory::map_mmio(mmio_phys, MMIO_SIZE) } {
+        Ok(v) => v,
+        Err(e) => { crate::serial_println!("[E1000] {}", e); return; }
+    };
+
+    let mut driver = E1000Driver::new(mmio_base);
+    if !driver.initialize() {
+        crate::serial_println!("[E1000] Card did not come out of reset; leaving it alone");
+        return;
+    }
+    let m = driver.mac_address;
+    crate::serial_println!("[E1000] MMIO {:#x}, MAC {:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}, link {}",
+        mmio_phys, m[0], m[1], m[2], m[3], m[4], m[5], if driver.link_up() { "up" } else { "down" });
+    crate::drivers::net::attach(crate::drivers::net::NicDriver::E1000(driver));
+}
+
+// ==========================================
+// SMOLTCP BINDINGS
+// ==========================================
+impl Device for E1000Driver {
+    type RxToken<'a> = E1000RxToken where Self: 'a;
+    type TxToken<'a> = E1000TxToken<'a> where Self: 'a;
+
+    fn receive<'a>(&'a mut self, _timestamp: Instant) -> Option<(Self::RxToken<'a>, Self::TxToken<'a>)> {
+        let mut buf = crate::drivers::net::RX_BUFFER_POOL.lock().pop().unwrap_or_else(|| Box::new([0; 2048]));
+        match self.receive_frame(&mut buf[..]) {
+            Some(len) => Some((E1000RxToken { buf: Some(buf), len }, E1000TxToken(self))),
+            None => {
+                crate::drivers::net::RX_BUFFER_POOL.lock().push(buf);
+                None
+            }
+        }
+    }
+
+    fn transmit<'a>(&'a mut self, _timestamp: Instant) -> Option<Self::TxToken<'a>> {
+        if !self.can_send() { return None; }
+        Some(E1000TxToken(self))
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        let mut caps = DeviceCapabilities::default();
+        caps.max_transmission_unit = 1500;
+        caps.max_burst_size = Some(1);
+        caps.checksum = ChecksumCapabilities::default();
+        caps
+    }
+}
+
+/// A received frame in a buffer from RX_BUFFER_POOL, which it goes back to.
+pub struct E1000RxToken {
+    buf: Option<Box<[u8; 2048]>>,
+    len: usize,
+}
+
+impl smoltcp::phy::RxToken for E1000RxToken {
+    fn consume<R, F>(mut self, f: F) -> R where F: FnOnce(&mut [u8]) -> R {
+        let mut buffer = self.buf.take().unwrap();
+        let result = f(&mut buffer[..self.len]);
+        crate::drivers::net::RX_BUFFER_POOL.lock().push(buffer);
+        result
+    }
+}
+
+impl Drop for E1000RxToken {
+    fn drop(&mut self) {
+        if let Some(buffer) = self.buf.take() {
+            crate::drivers::net::RX_BUFFER_POOL.lock().push(buffer);
+        }
+    }
+}
+
+pub struct E1000TxToken<'a>(&'a mut E1000Driver);
+
+impl<'a> smoltcp::phy::TxToken for E1000TxToken<'a> {
+    fn consume<R, F>(self, len: usize, f: F) -> R where F: FnOnce(&mut [u8]) -> R {
+        let mut frame = [0u8; BUFFER_SIZE];
+        let len = len.min(MAX_FRAME);
+        let result = f(&mut frame[..len]);
+        // transmit() only hands out a token for a free slot
+        self.0.send_frame(&frame[..len]);
+        result
+    }
+}
@@ -1,16 +1,22 @@
 pub mod iwlwifi;
 pub mod rtl8168;
+pub mod e1000;
+pub mod ping;
 
 use spin::Mutex;
 use smoltcp::iface::{Interface, SocketSet, SocketHandle};
 use smoltcp::socket::dhcpv4::{Socket as DhcpSocket, Event as DhcpEvent};
 use smoltcp::socket::dns::{Socket as DnsSocket, GetQueryResultError};
 use smoltcp::time::Instant;
+use smoltcp::phy::{Device, DeviceCapabilities};
+use smoltcp::wire::{IpAddress, IpCidr, Ipv4Address, Ipv4Cidr};
 use core::sync::atomic::{AtomicBool, AtomicU64, Ordering}; 
 use alloc::boxed::Box;
 use alloc::vec::Vec;
+use self::e1000::{E1000Driver, E1000RxToken, E1000TxToken};
+use self::rtl8168::{Rtl8168Driver, RtkRxToken, RtkTxToken};
 
-pub static NET_DRIVER: Mutex<Option<crate::drivers::net::rtl8168::Rtl8168Driver>> = Mutex::new(None);
+pub static NET_DRIVER: Mutex<Option<NicDriver>> = Mutex::new(None);
 pub static NET_IFACE: Mutex<Option<Interface>> = Mutex::new(None);
 pub static GLOBAL_SOCKETS: Mutex<Option<SocketSet<'static>>> = Mutex::new(None);
 pub static DHCP_HANDLE: Mutex<Option<SocketHandle>> = Mutex::new(None);
@@ -20,6 +26,30 @@ pub static DNS_HANDLE: Mutex<Option<SocketHandle>> = Mutex::new(None);
 
 pub static NETWORK_PENDING: AtomicBool = AtomicBool::new(false);
 static LAST_TIME: AtomicU64 = AtomicU64::new(0);
+/// Set once `ifconfig` gives the interface an address; DHCP leases are
+/// ignored from then on so they can't replace it
+static STATIC_CONFIG: AtomicBool = AtomicBool::new(false);
+/// IPv4 fragments received and thrown away (see is_ipv4_fragment)
+pub static FRAGMENTS_DROPPED: AtomicU64 = AtomicU64::new(0);
+
+/// Largest UDP payload that fits one frame: the 1500-byte MTU less the 20-byte
+/// IPv4 and 8-byte UDP headers. Bigger datagrams would need fragmenting.
+pub const MAX_UDP_PAYLOAD: usize = 1472;
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+/// IPv4 flags/fragment offset word: more-fragments bit and the offset
+const IPV4_MORE_FRAGMENTS: u16 = 0x2000;
+const IPV4_FRAGMENT_OFFSET: u16 = 0x1FFF;
+
+/// The stack is built without reassembly, and would otherwise take a
+/// fragment's payload for a whole datagram. So any piece of a fragmented
+/// datagram (more to come, or not the first) is caught here and dropped.
+fn is_ipv4_fragment(frame: &[u8]) -> bool {
+    if frame.len() < 14 + 20 { return false; }
+    let ethertype = u16::from_be_bytes([frame[12], frame[13]]);
+    let flags_offset = u16::from_be_bytes([frame[14 + 6], frame[14 + 7]]);
+    ethertype == ETHERTYPE_IPV4 && flags_offset & (IPV4_MORE_FRAGMENTS | IPV4_FRAGMENT_OFFSET) != 0
+}
 
 lazy_static::lazy_static! {
     pub static ref RX_BUFFER_POOL: Mutex<Vec<Box<[u8; 2048]>>> = Mutex::new(Vec::new());
@@ -71,6 +101,7 @@ pub fn poll_network() {
         {
             let dhcp_socket = sockets.get_mut::<DhcpSocket>(*dhcp_handle);
             match dhcp_socket.poll() {
+                Some(DhcpEvent::Configured(_)) if STATIC_CONFIG.load(Ordering::Relaxed) => {}
                 Some(DhcpEvent::Configured(config)) => {
                     let mut dns_servers = alloc::vec::Vec::new();
                     for s in config.dns_servers.iter() {
@@ -78,6 +109,7 @@ pub fn poll_network() {
                     }
                     dhcp_config = Some((config.address, config.router, dns_servers));
                 }
+                Some(DhcpEvent::Deconfigured) if STATIC_CONFIG.load(Ordering::Relaxed) => {}
                 Some(DhcpEvent::Deconfigured) => {
                     dhcp_deconfig = true;
                 }
@@ -111,4 +143,175 @@ pub fn poll_network() {
             iface.routes_mut().remove_default_ipv4_route();
         }
     }
-}
\ No newline at end of file
+}
+// ==========================================
+// NETWORK CARDS
+// ==========================================
+// The stack runs on whichever card the PCI scan bound first. Each driver is
+// a smoltcp Device of its own; NicDriver forwards to the one present so the
+// rest of the kernel only ever sees one type.
+
+pub enum NicDriver {
+    Rtl8168(Rtl8168Driver),
+    E1000(E1000Driver),
+}
+
+impl NicDriver {
+    pub fn name(&self) -> &'static str {
+        match self {
+            NicDriver::Rtl8168(_) => "rtl8168",
+            NicDriver::E1000(_) => "e1000",
+        }
+    }
+
+    pub fn mac_address(&self) -> [u8; 6] {
+        match self {
+            NicDriver::Rtl8168(d) => d.mac_address,
+            NicDriver::E1000(d) => d.mac_address,
+        }
+    }
+
+    pub fn ack_interrupt(&mut self) {
+        match self {
+            NicDriver::Rtl8168(d) => d.ack_interrupt(),
+            NicDriver::E1000(d) => d.ack_interrupt(),
+        }
+    }
+}
+
+pub enum NicRxToken {
+    Rtl8168(RtkRxToken),
+    E1000(E1000RxToken),
+}
+
+pub enum NicTxToken<'a> {
+    Rtl8168(RtkTxToken<'a>),
+    E1000(E1000TxToken<'a>),
+}
+
+impl smoltcp::phy::RxToken for NicRxToken {
+    fn consume<R, F>(self, f: F) -> R where F: FnOnce(&mut [u8]) -> R {
+        // A fragment is handed over as an empty frame, which the stack discards
+        let f = |frame: &mut [u8]| {
+            if is_ipv4_fragment(frame) {
+                FRAGMENTS_DROPPED.fetch_add(1, Ordering::Relaxed);
+                return f(&mut []);
+            }
+            f(frame)
+        };
+        match self {
+            NicRxToken::Rtl8168(t) => t.consume(f),
+            NicRxToken::E1000(t) => t.consume(f),
+        }
+    }
+}
+
+impl<'a> smoltcp::phy::TxToken for NicTxToken<'a> {
+    fn consume<R, F>(self, len: usize, f: F) -> R where F: FnOnce(&mut [u8]) -> R {
+        match self {
+            NicTxToken::Rtl8168(t) => t.consume(len, f),
+            NicTxToken::E1000(t) => t.consume(len, f),
+        }
+    }
+}
+
+impl Device for NicDriver {
+    type RxToken<'a> = NicRxToken where Self: 'a;
+    type TxToken<'a> = NicTxToken<'a> where Self: 'a;
+
+    fn receive<'a>(&'a mut self, timestamp: Instant) -> Option<(Self::RxToken<'a>, Self::TxToken<'a>)> {
+        match self {
+            NicDriver::Rtl8168(d) => d.receive(timestamp).map(|(rx, tx)| (NicRxToken::Rtl8168(rx), NicTxToken::Rtl8168(tx))),
+            NicDriver::E1000(d) => d.receive(timestamp).map(|(rx, tx)| (NicRxToken::E1000(rx), NicTxToken::E1000(tx))),
+        }
+    }
+
+    fn transmit<'a>(&'a mut self, timestamp: Instant) -> Option<Self::TxToken<'a>> {
+        match self {
+            NicDriver::Rtl8168(d) => d.transmit(timestamp).map(NicTxToken::Rtl8168),
+            NicDriver::E1000(d) => d.transmit(timestamp).map(NicTxToken::E1000),
+        }
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        match self {
+            NicDriver::Rtl8168(d) => d.capabilities(),
+            NicDriver::E1000(d) => d.capabilities(),
+        }
+    }
+}
+
+/// Builds the interface for a freshly bound card. A second card is left
+/// alone: there is one interface and it stays on the first.
+pub fn attach(mut driver: NicDriver) {
+    use smoltcp::iface::{Config, Interface};
+    use smoltcp::wire::{EthernetAddress, HardwareAddress};
+
+    let mut driver_lock = NET_DRIVER.lock();
+    if let Some(current) = driver_lock.as_ref() {
+        crate::serial_println!("[NET] Already running on {}; not using the {}", current.name(), driver.name());
+        return;
+    }
+    let mut config = Config::new();
+    config.hardware_addr = Some(HardwareAddress::Ethernet(EthernetAddress::from_bytes(&driver.mac_address())));
+    let iface = Interface::new(config, &mut driver);
+
+    *driver_lock = Some(driver);
+    *NET_IFACE.lock() = Some(iface);
+}
+
+/// What `ifconfig` shows.
+pub struct NetConfig {
+    pub nic: &'static str,
+    pub mac: [u8; 6],
+    pub address: Option<Ipv4Cidr>,
+    pub gateway: Option<Ipv4Address>,
+    /// Set by `ifconfig` rather than leased over DHCP
+    pub is_static: bool,
+    pub fragments_dropped: u64,
+}
+
+/// None when no card was found.
+pub fn config() -> Option<NetConfig> {
+    let driver_lock = NET_DRIVER.lock();
+    let driver = driver_lock.as_ref()?;
+    let mut iface_lock = NET_IFACE.lock();
+    let iface = iface_lock.as_mut()?;
+    let address = iface.ip_addrs().iter().find_map(|cidr| match cidr {
+        IpCidr::Ipv4(c) => Some(*c),
+        _ => None,
+    });
+    let mut gateway = None;
+    iface.routes_mut().update(|routes| {
+        gateway = routes.iter().find_map(|r| match (r.cidr.prefix_len(), r.via_router) {
+            (0, IpAddress::Ipv4(gw)) => Some(gw),
+            _ => None,
+        });
+    });
+    Some(NetConfig {
+        nic: driver.name(),
+        mac: driver.mac_address(),
+        address,
+        gateway,
+        is_static: STATIC_CONFIG.load(Ordering::Relaxed),
+        fragments_dropped: FRAGMENTS_DROPPED.load(Ordering::Relaxed),
+    })
+}
+
+/// Gives the interface a fixed address (and default route, if `gateway`),
+/// replacing whatever DHCP leased. False when no card was found.
+pub fn set_static(address: Ipv4Cidr, gateway: Option<Ipv4Address>) -> bool {
+    let mut iface_lock = NET_IFACE.lock();
+    let iface = match iface_lock.as_mut() { Some(i) => i, None => return false };
+    STATIC_CONFIG.store(true, Ordering::Relaxed);
+    iface.update_ip_addrs(|addrs| {
+        addrs.clear();
+        let _ = addrs.push(IpCidr::Ipv4(address));
+    });
+    iface.routes_mut().remove_default_ipv4_route();
+    if let Some(gw) = gateway {
+        let _ = iface.routes_mut().add_default_ipv4_route(gw);
+    }
+    crate::serial_println!("[NET] Static address {} (gateway {:?})", address, gateway);
+    true
+}
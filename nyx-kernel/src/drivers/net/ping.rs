@@ -0,0 +1,89 @@
+use core::sync::atomic::{AtomicU16, Ordering};
+use smoltcp::phy::ChecksumCapabilities;
+use smoltcp::socket::icmp::{Endpoint, PacketBuffer, PacketMetadata, Socket as IcmpSocket};
+use smoltcp::wire::{IpAddress, Icmpv4Packet, Icmpv4Repr, Ipv4Address};
+
+// ==========================================
+// ICMP ECHO ("PING")
+// ==========================================
+// The kernel shell's ping. Answering other machines' pings, and the ARP
+// that comes before them, is smoltcp's job and happens in poll_network; this
+// is only the asking side. Each ping gets an ICMP socket bound to an ident of
+// its own, so a late reply to an earlier one is never taken for this one.
+
+/// Bytes after the 8-byte ICMP header, so a request is 64 bytes like other pings
+pub const PAYLOAD_LEN: usize = 56;
+
+static NEXT_IDENT: AtomicU16 = AtomicU16::new(0x4E58);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PingError {
+    /// No network card was found
+    NoInterface,
+    /// The interface has no address yet (DHCP hasn't answered, no ifconfig)
+    NoAddress,
+    /// The request couldn't be queued
+    SendFailed,
+    TimedOut,
+}
+
+/// Sends one echo request with sequence number `seq` and waits up to
+/// `timeout_ms` for its reply. Returns the round trip in milliseconds.
+pub fn ping(target: Ipv4Address, seq: u16, timeout_ms: u64) -> Result<u64, PingError> {
+    // Makes sure the socket set exists before a socket goes into it
+    super::poll_network();
+    match super::NET_IFACE.lock().as_ref() {
+        None => return Err(PingError::NoInterface),
+        Some(iface) if iface.ipv4_addr().is_none() => return Err(PingError::NoAddress),
+        Some(_) => {}
+    }
+
+    let ident = NEXT_IDENT.fetch_add(1, Ordering::Relaxed);
+    let handle = {
+        let mut sockets_lock = super::GLOBAL_SOCKETS.lock();
+        let sockets = sockets_lock.as_mut().ok_or(PingError::NoInterface)?;
+        let mut socket = IcmpSocket::new(
+            PacketBuffer::new(alloc::vec![PacketMetadata::EMPTY; 4], alloc::vec![0; 512]),
+            PacketBuffer::new(alloc::vec![PacketMetadata::EMPTY; 4], alloc::vec![0; 512]),
+        );
+        socket.bind(Endpoint::Ident(ident)).map_err(|_| PingError::SendFailed)?;
+        sockets.add(socket)
+    };
+
+    let result = exchange(handle, target, ident, seq, timeout_ms);
+    if let Some(sockets) = super::GLOBAL_SOCKETS.lock().as_mut() { sockets.remove(handle); }
+    result
+}
+
+fn exchange(handle: smoltcp::iface::SocketHandle, target: Ipv4Address, ident: u16, seq: u16, timeout_ms: u64) -> Result<u64, PingError> {
+    let caps = ChecksumCapabilities::default();
+    let payload = [0xA5u8; PAYLOAD_LEN];
+    let start = crate::time::monotonic_ms();
+    {
+        let mut sockets_lock = super::GLOBAL_SOCKETS.lock();
+        let sockets = sockets_lock.as_mut().ok_or(PingError::NoInterface)?;
+        let socket = sockets.get_mut::<IcmpSocket>(handle);
+        let request = Icmpv4Repr::EchoRequest { ident, seq_no: seq, data: &payload };
+        let buf = socket.send(request.buffer_len(), IpAddress::Ipv4(target)).map_err(|_| PingError::SendFailed)?;
+        request.emit(&mut Icmpv4Packet::new_unchecked(buf), &caps);
+    }
+
+    loop {
+        // Sends the request (after ARP, the first time) and takes in replies
+        super::poll_network();
+        {
+            let mut sockets_lock = super::GLOBAL_SOCKETS.lock();
+            let sockets = sockets_lock.as_mut().ok_or(PingError::NoInterface)?;
+            let socket = sockets.get_mut::<IcmpSocket>(handle);
+            while let Ok((data, from)) = socket.recv() {
+                if from != IpAddress::Ipv4(target) { continue; }
+                let packet = match Icmpv4Packet::new_checked(data) { Ok(p) => p, Err(_) => continue };
+                if let Ok(Icmpv4Repr::EchoReply { ident: i, seq_no, .. }) = Icmpv4Repr::parse(&packet, &caps) {
+                    if i == ident && seq_no == seq { return Ok(crate::time::monotonic_ms() - start); }
+                }
+            }
+        }
+        if crate::time::monotonic_ms() - start >= timeout_ms { return Err(PingError::TimedOut); }
+        crate::time::sleep_ms(1);
+    }
+}
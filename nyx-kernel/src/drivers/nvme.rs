@@ -1,6 +1,8 @@
 use crate::pci::{PciDriver, PciDevice};
 use alloc::vec::Vec;
+use alloc::string::String;
 use core::ptr::{read_volatile, write_volatile};
+use core::sync::atomic::{AtomicU64, Ordering};
 
 // --- CONSTANTS ---
 const NVME_ADMIN_OP_CREATE_SQ: u8 = 0x01;
@@ -9,6 +11,12 @@ const NVME_ADMIN_OP_IDENTIFY: u8 = 0x06;
 const NVME_IO_OP_READ: u8 = 0x02;
 const NVME_IO_OP_WRITE: u8 = 0x01;
 
+// How long the controller gets to flip CSTS.RDY, and a command to complete
+// while polling (see watchdog::spin_until)
+const READY_TIMEOUT_MS: u64 = 1000;
+const ADMIN_TIMEOUT_MS: u64 = 1000;
+const POLL_TIMEOUT_MS: u64 = 1000;
+
 // --- DMA BUFFERS (Aligned to 4096) ---
 #[repr(align(4096))]
 struct Page([u8; 4096]);
@@ -19,6 +27,32 @@ static mut IO_SQ: Page = Page([0; 4096]);
 static mut IO_CQ: Page = Page([0; 4096]); 
 static mut DATA_BUF: Page = Page([0; 4096]); 
 
+// Multi-block transfers: 64 KiB of data pages plus one page of PRP entries
+const BULK_PAGES: usize = 16;
+static mut BULK_BUF: [Page; BULK_PAGES] = [const { Page([0; 4096]) }; BULK_PAGES];
+static mut PRP_LIST: Page = Page([0; 4096]);
+
+// ==========================================
+// COMPLETION INTERRUPTS
+// ==========================================
+// With MSI/MSI-X on, an I/O command is submitted and the core halts until
+// the completion interrupt (or the next timer tick) wakes it to look at the
+// queue again, so the rest of the system keeps running during disk I/O.
+// If no completion shows up in IRQ_TIMEOUT_MS the driver falls back to
+// polling for the rest of that command.
+
+/// IDT vector of the controller's completion interrupt (0x30 is the RTL8168's)
+pub const NVME_VECTOR: u8 = 0x31;
+const IRQ_TIMEOUT_MS: u64 = 2000;
+// Bumped by the interrupt handler; reported when an interrupt wait times out
+static IRQ_COUNT: AtomicU64 = AtomicU64::new(0);
+
+/// Called from the NVMe interrupt handler. Completions are read by the
+/// waiting submitter, so this only records that one arrived.
+pub fn handle_interrupt() {
+    IRQ_COUNT.fetch_add(1, Ordering::Relaxed);
+}
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug)]
 pub struct NvmeCmd {
@@ -49,6 +83,16 @@ pub struct NvmeCpl {
     pub status: u16, 
 }
 
+/// One active namespace as reported by Identify Namespace (CNS 0).
+#[derive(Copy, Clone, Debug)]
+pub struct NamespaceInfo {
+    pub nsid: u32,
+    pub lba_size: u32,
+    /// Size in logical blocks (NSZE)
+    pub capacity: u64,
+    pub mounted: bool,
+}
+
 #[repr(C)]
 pub struct NvmeRegisters {
     pub cap: u64, pub vs: u32, pub intms: u32, pub intmc: u32,
@@ -69,6 +113,12 @@ pub struct NvmeDriver {
     pub io_cq_head: u16,
     pub io_phase: u16,    
     pub active_nsid: u32, 
+    pub namespaces: Vec<NamespaceInfo>,
+    /// Model number from Identify Controller, space padding trimmed
+    pub model: String,
+    max_transfer: usize,
+    /// Completions on I/O queue 1 raise NVME_VECTOR
+    irq_enabled: bool,
 }
 
 impl NvmeDriver {
@@ -96,10 +146,21 @@ impl NvmeDriver {
                                 device: dev, bar0, regs, doorbell_stride: stride,
                                 sq_tail: 0, cq_head: 0, admin_phase: 1, 
                                 io_sq_tail: 0, io_cq_head: 0, io_phase: 1,
-                                active_nsid: 0
+                                active_nsid: 0,
+                                namespaces: Vec::new(),
+                                model: String::new(),
+                                max_transfer: 4096,
+                                irq_enabled: false,
                             };
                             
-                            if driver.init_controller() { return Some(driver); }
+                            if driver.init_controller() {
+                                driver.identify_controller();
+                                driver.irq_enabled = pci.enable_msi(&dev, NVME_VECTOR);
+                                if !driver.irq_enabled {
+                                    crate::serial_println!("[NVME] No MSI/MSI-X; completions will be polled");
+                                }
+                                return Some(driver);
+                            }
                         }
                     }
                 }
@@ -114,7 +175,7 @@ impl NvmeDriver {
             let cc = read_volatile(&self.regs.cc);
             if (cc & 1) != 0 {
                 write_volatile(&mut self.regs.cc, cc & !1);
-                for _ in 0..50000 { if (read_volatile(&self.regs.csts) & 1) == 0 { break; } core::hint::spin_loop(); }
+                crate::watchdog::spin_until("NVME", "controller disable", READY_TIMEOUT_MS, || (read_volatile(&self.regs.csts) & 1) == 0);
             }
             
             let asq_phys = crate::memory::virt_to_phys(&ADMIN_SQ as *const _ as u64).unwrap();
@@ -127,7 +188,7 @@ impl NvmeDriver {
             // Enable
             write_volatile(&mut self.regs.cc, (6 << 16) | (4 << 20) | 1);
             
-            for _ in 0..50000 { if (read_volatile(&self.regs.csts) & 1) != 0 { break; } core::hint::spin_loop(); }
+            crate::watchdog::spin_until("NVME", "controller enable", READY_TIMEOUT_MS, || (read_volatile(&self.regs.csts) & 1) != 0);
         }
         true 
     }
@@ -147,25 +208,67 @@ impl NvmeDriver {
         
         let cq = &mut *(&mut ADMIN_CQ.0 as *mut _ as *mut [NvmeCpl; 256]);
         
-        for _ in 0..10_000_000 {
+        let mut success = false;
+        let completed = crate::watchdog::spin_until("NVME", "admin command", ADMIN_TIMEOUT_MS, || {
             let status_raw = read_volatile(&cq[self.cq_head as usize].status);
             let phase_tag = (status_raw & 1) as u16;
+            if phase_tag != self.admin_phase { return false; }
 
-            if phase_tag == self.admin_phase {
-                 let sc = (status_raw >> 1) & 0xFF;
-                 self.cq_head = (self.cq_head + 1) % 32;
-                 if self.cq_head == 0 { self.admin_phase ^= 1; }
-                 
-                 let cq_db = self.bar0 + 0x1000 + self.doorbell_stride as u64;
-                 write_volatile(cq_db as *mut u32, self.cq_head as u32);
-                 return sc == 0; 
-            }
-            core::hint::spin_loop();
-        }
-        false
+            let sc = (status_raw >> 1) & 0xFF;
+            self.cq_head = (self.cq_head + 1) % 32;
+            if self.cq_head == 0 { self.admin_phase ^= 1; }
+
+            let cq_db = self.bar0 + 0x1000 + self.doorbell_stride as u64;
+            write_volatile(cq_db as *mut u32, self.cq_head as u32);
+            success = sc == 0;
+            true
+        });
+        completed && success
     }
 
     pub fn find_active_namespace(&mut self) -> u32 {
+        if self.namespaces.is_empty() { self.enumerate_namespaces(); }
+        self.active_nsid = self.namespaces.first().map(|ns| ns.nsid).unwrap_or(1);
+        self.active_nsid
+    }
+
+    /// The namespace used by callers that don't pick one (the mounted one once the VFS is up).
+    pub fn default_nsid(&mut self) -> u32 {
+        if self.active_nsid == 0 { self.find_active_namespace(); }
+        self.active_nsid
+    }
+
+    /// Reads MDTS from Identify Controller (CNS 1) to bound multi-block transfers.
+    fn identify_controller(&mut self) {
+        let buf_phys = crate::memory::virt_to_phys(unsafe { &DATA_BUF } as *const _ as u64).unwrap();
+        let cmd = NvmeCmd {
+            opcode: NVME_ADMIN_OP_IDENTIFY,
+            flags: 0, cid: 1, nsid: 0, rsvd: 0, mptr: 0,
+            prp1: buf_phys, prp2: 0,
+            cdw10: 1, cdw11: 0, cdw12: 0, cdw13: 0, cdw14: 0, cdw15: 0
+        };
+        if !unsafe { self.submit_admin(cmd) } { return; }
+
+        // MN is 40 ASCII bytes at offset 24, unlike ATA strings not byte-swapped
+        let mn = unsafe { &DATA_BUF.0[24..64] };
+        self.model = String::from(core::str::from_utf8(mn).unwrap_or("").trim());
+
+        // MDTS is a power of two in units of the minimum page size (CAP.MPSMIN); 0 means no limit
+        let mdts = unsafe { DATA_BUF.0[77] } as u32;
+        let min_page = 1usize << (12 + ((self.regs.cap >> 48) & 0xF));
+        let controller_max = if mdts == 0 { usize::MAX } else { min_page.saturating_mul(1 << mdts.min(20)) };
+        self.max_transfer = controller_max.min(BULK_PAGES * 4096);
+        crate::serial_println!("[NVME] MDTS {} -> {} byte transfers", mdts, self.max_transfer);
+    }
+
+    /// Logical block size of `nsid` from its LBA format, 512 if it was never identified.
+    pub fn lba_size(&self, nsid: u32) -> usize {
+        self.namespaces.iter().find(|ns| ns.nsid == nsid).map(|ns| ns.lba_size as usize).unwrap_or(512)
+    }
+
+    /// Fills `namespaces` from the active NSID list (Identify CNS 2) and returns how many were found.
+    pub fn enumerate_namespaces(&mut self) -> usize {
+        self.namespaces.clear();
         let buf_phys = crate::memory::virt_to_phys(unsafe { &DATA_BUF } as *const _ as u64).unwrap();
         let cmd = NvmeCmd {
             opcode: NVME_ADMIN_OP_IDENTIFY,
@@ -174,25 +277,52 @@ impl NvmeDriver {
             cdw10: 2, cdw11: 0, cdw12: 0, cdw13: 0, cdw14: 0, cdw15: 0
         };
         
+        let mut nsids = Vec::new();
         if unsafe { self.submit_admin(cmd) } {
+            // The list is zero-terminated; copy it out before DATA_BUF is reused
             let ns_list = unsafe { &*(&DATA_BUF.0 as *const _ as *const [u32; 1024]) };
-            if ns_list[0] != 0 {
-                self.active_nsid = ns_list[0];
-                return ns_list[0];
+            nsids.extend(ns_list.iter().copied().take_while(|&id| id != 0));
+        }
+
+        for nsid in nsids {
+            if let Some(info) = self.identify_namespace(nsid) {
+                crate::serial_println!("[NVME] Namespace {}: {} blocks of {} bytes", info.nsid, info.capacity, info.lba_size);
+                self.namespaces.push(info);
             }
         }
-        self.active_nsid = 1; 
-        1
+        self.namespaces.len()
+    }
+
+    fn identify_namespace(&mut self, nsid: u32) -> Option<NamespaceInfo> {
+        let buf_phys = crate::memory::virt_to_phys(unsafe { &DATA_BUF } as *const _ as u64).unwrap();
+        let cmd = NvmeCmd {
+            opcode: NVME_ADMIN_OP_IDENTIFY,
+            flags: 0, cid: 7, nsid, rsvd: 0, mptr: 0,
+            prp1: buf_phys, prp2: 0,
+            cdw10: 0, cdw11: 0, cdw12: 0, cdw13: 0, cdw14: 0, cdw15: 0
+        };
+        if !unsafe { self.submit_admin(cmd) } { return None; }
+
+        let id = unsafe { &DATA_BUF.0 };
+        let capacity = u64::from_le_bytes(id[0..8].try_into().unwrap());
+        // FLBAS picks the active entry of the LBA format table at byte 128; LBADS is log2(block size)
+        let format = (id[26] & 0x0F) as usize;
+        let lbads = id[128 + format * 4 + 2];
+        if capacity == 0 || lbads < 9 { return None; }
+
+        Some(NamespaceInfo { nsid, lba_size: 1 << lbads, capacity, mounted: false })
     }
 
     pub fn create_io_queues(&mut self) -> bool {
         unsafe {
             // CQ
             let cq_phys = crate::memory::virt_to_phys(&IO_CQ as *const _ as u64).unwrap();
+            // Physically contiguous, interrupt vector 0 (the one message we set up), IEN when MSI is on
+            let ien = if self.irq_enabled { 1 << 1 } else { 0 };
             let cmd_cq = NvmeCmd {
                 opcode: NVME_ADMIN_OP_CREATE_CQ,
                 flags: 0, cid: 3, nsid: 0, rsvd: 0, mptr: 0, prp1: cq_phys, prp2: 0,
-                cdw10: (15 << 16) | 1, cdw11: 1, cdw12: 0, cdw13: 0, cdw14: 0, cdw15: 0
+                cdw10: (15 << 16) | 1, cdw11: ien | 1, cdw12: 0, cdw13: 0, cdw14: 0, cdw15: 0
             };
             if !self.submit_admin(cmd_cq) { return false; }
 
@@ -208,95 +338,135 @@ impl NvmeDriver {
         }
     }
 
-    pub fn read_block(&mut self, lba: u64, buffer: &mut [u8]) -> bool {
-        if self.active_nsid == 0 { self.find_active_namespace(); }
-        if buffer.len() != 4096 { return false; } 
-
-        unsafe {
-            let buf_phys = crate::memory::virt_to_phys(unsafe { &DATA_BUF } as *const _ as u64).unwrap();
-            
-            let sq = &mut *(&mut IO_SQ.0 as *mut _ as *mut [NvmeCmd; 64]);
-            sq[self.io_sq_tail as usize] = NvmeCmd {
-                opcode: NVME_IO_OP_READ,
-                flags: 0, cid: 5, nsid: self.active_nsid,
-                rsvd: 0, mptr: 0, prp1: buf_phys, prp2: 0,
-                cdw10: lba as u32, cdw11: (lba >> 32) as u32,
-                cdw12: 0, cdw13: 0, cdw14: 0, cdw15: 0
-            };
+    /// Submits one command on I/O queue 1 and waits for its completion: halting
+    /// between completion interrupts when MSI is on, polling otherwise.
+    unsafe fn submit_io(&mut self, cmd: NvmeCmd) -> bool {
+        let sq = &mut *(&mut IO_SQ.0 as *mut _ as *mut [NvmeCmd; 64]);
+        sq[self.io_sq_tail as usize] = cmd;
 
-            self.io_sq_tail = (self.io_sq_tail + 1) % 16;
-            let db_addr = self.bar0 + 0x1000 + (2 * self.doorbell_stride as u64);
-            write_volatile(db_addr as *mut u32, self.io_sq_tail as u32);
+        self.io_sq_tail = (self.io_sq_tail + 1) % 16;
+        let db_addr = self.bar0 + 0x1000 + (2 * self.doorbell_stride as u64);
+        write_volatile(db_addr as *mut u32, self.io_sq_tail as u32);
 
-            let cq = &mut *(&mut IO_CQ.0 as *mut _ as *mut [NvmeCpl; 256]);
-            
-            for _ in 0..10_000_000 {
-                let status_raw = read_volatile(&cq[self.io_cq_head as usize].status);
-                let phase = (status_raw & 1) as u16;
-                
-                if phase == self.io_phase {
-                    let sc = (status_raw >> 1) & 0xFF;
-                    self.io_cq_head = (self.io_cq_head + 1) % 16;
-                    
-                    if self.io_cq_head == 0 { self.io_phase ^= 1; }
-                    
-                    let cq_db = self.bar0 + 0x1000 + (3 * self.doorbell_stride as u64);
-                    write_volatile(cq_db as *mut u32, self.io_cq_head as u32);
-                    
-                    if sc == 0 {
-                        buffer.copy_from_slice(&DATA_BUF.0);
-                        return true;
-                    } else {
-                        return false;
-                    }
-                }
-                core::hint::spin_loop();
+        // Halting needs interrupts on and a running clock for the timeout
+        let now = || crate::time::UPTIME_MS.load(Ordering::Relaxed);
+        if self.irq_enabled && x86_64::instructions::interrupts::are_enabled() && now() > 0 {
+            let deadline = now() + IRQ_TIMEOUT_MS;
+            while now() < deadline {
+                if let Some(ok) = self.reap_io() { return ok; }
+                x86_64::instructions::hlt();
             }
+            crate::serial_println!("[NVME] No completion after {} ms ({} interrupts so far). Polling.",
+                IRQ_TIMEOUT_MS, IRQ_COUNT.load(Ordering::Relaxed));
+        }
+
+        let mut result = None;
+        crate::watchdog::spin_until("NVME", "I/O command", POLL_TIMEOUT_MS, || {
+            result = self.reap_io();
+            result.is_some()
+        });
+        result.unwrap_or(false)
+    }
+
+    /// Consumes the next I/O completion if the controller has posted it.
+    /// Some(success) when one was taken, None if the queue is still empty.
+    unsafe fn reap_io(&mut self) -> Option<bool> {
+        let cq = &mut *(&mut IO_CQ.0 as *mut _ as *mut [NvmeCpl; 256]);
+        let status_raw = read_volatile(&cq[self.io_cq_head as usize].status);
+        let phase = (status_raw & 1) as u16;
+        if phase != self.io_phase { return None; }
+
+        let sc = (status_raw >> 1) & 0xFF;
+        self.io_cq_head = (self.io_cq_head + 1) % 16;
+        
+        if self.io_cq_head == 0 { self.io_phase ^= 1; }
+        
+        let cq_db = self.bar0 + 0x1000 + (3 * self.doorbell_stride as u64);
+        write_volatile(cq_db as *mut u32, self.io_cq_head as u32);
+        
+        Some(sc == 0)
+    }
+
+    fn io_cmd(opcode: u8, cid: u16, nsid: u32, lba: u64, blocks: u32, prp1: u64, prp2: u64) -> NvmeCmd {
+        NvmeCmd {
+            opcode, flags: 0, cid, nsid,
+            rsvd: 0, mptr: 0, prp1, prp2,
+            cdw10: lba as u32, cdw11: (lba >> 32) as u32,
+            // NLB is zero-based
+            cdw12: blocks - 1, cdw13: 0, cdw14: 0, cdw15: 0
         }
-        false
+    }
+
+    /// Reads one logical block of `nsid` into the start of `buffer`, which must hold at least
+    /// lba_size(nsid) bytes. Blocks larger than the single DMA page aren't supported.
+    pub fn read_block(&mut self, nsid: u32, lba: u64, buffer: &mut [u8]) -> bool {
+        let block_size = self.lba_size(nsid);
+        if block_size > 4096 || buffer.len() < block_size { return false; } 
+
+        unsafe {
+            let buf_phys = crate::memory::virt_to_phys(&DATA_BUF as *const _ as u64).unwrap();
+            if !self.submit_io(Self::io_cmd(NVME_IO_OP_READ, 5, nsid, lba, 1, buf_phys, 0)) { return false; }
+            buffer[..block_size].copy_from_slice(&DATA_BUF.0[..block_size]);
+        }
+        true
     }
     
-    pub fn write_block(&mut self, lba: u64, data: &[u8]) -> bool {
-        if self.active_nsid == 0 { self.find_active_namespace(); }
-        if data.len() != 4096 { return false; } 
+    /// Writes the first lba_size(nsid) bytes of `data` to one logical block of `nsid`.
+    pub fn write_block(&mut self, nsid: u32, lba: u64, data: &[u8]) -> bool {
+        let block_size = self.lba_size(nsid);
+        if block_size > 4096 || data.len() < block_size { return false; } 
 
         unsafe {
             let dma = &mut DATA_BUF.0;
-            dma.copy_from_slice(data);
+            dma[..block_size].copy_from_slice(&data[..block_size]);
             let buf_phys = crate::memory::virt_to_phys(dma as *const _ as u64).unwrap();
-            
-            let sq = &mut *(&mut IO_SQ.0 as *mut _ as *mut [NvmeCmd; 64]);
-            sq[self.io_sq_tail as usize] = NvmeCmd {
-                opcode: NVME_IO_OP_WRITE,
-                flags: 0, cid: 6, nsid: self.active_nsid,
-                rsvd: 0, mptr: 0, prp1: buf_phys, prp2: 0,
-                cdw10: lba as u32, cdw11: (lba >> 32) as u32,
-                cdw12: 0, cdw13: 0, cdw14: 0, cdw15: 0
-            };
+            self.submit_io(Self::io_cmd(NVME_IO_OP_WRITE, 6, nsid, lba, 1, buf_phys, 0))
+        }
+    }
 
-            self.io_sq_tail = (self.io_sq_tail + 1) % 16;
-            let db_addr = self.bar0 + 0x1000 + (2 * self.doorbell_stride as u64);
-            write_volatile(db_addr as *mut u32, self.io_sq_tail as u32);
-
-            let cq = &mut *(&mut IO_CQ.0 as *mut _ as *mut [NvmeCpl; 256]);
-            for _ in 0..10_000_000 {
-                let status_raw = read_volatile(&cq[self.io_cq_head as usize].status);
-                let phase = (status_raw & 1) as u16;
-                
-                if phase == self.io_phase {
-                    let sc = (status_raw >> 1) & 0xFF;
-                    self.io_cq_head = (self.io_cq_head + 1) % 16;
-                    
-                    if self.io_cq_head == 0 { self.io_phase ^= 1; }
-                    
-                    let cq_db = self.bar0 + 0x1000 + (3 * self.doorbell_stride as u64);
-                    write_volatile(cq_db as *mut u32, self.io_cq_head as u32);
-                    
-                    return sc == 0;
-                }
-                core::hint::spin_loop();
+    /// Largest single transfer in bytes: the bulk DMA region, capped by the controller's MDTS.
+    pub fn max_transfer(&self) -> usize {
+        self.max_transfer
+    }
+
+    /// Reads `count` consecutive blocks starting at `lba` into `buffer` with as few
+    /// commands as max_transfer() allows.
+    pub fn read_blocks(&mut self, nsid: u32, lba: u64, count: usize, buffer: &mut [u8]) -> bool {
+        let block_size = self.lba_size(nsid);
+        if count == 0 || buffer.len() < count * block_size { return false; }
+        let per_cmd = (self.max_transfer / block_size).max(1);
+
+        let mut done = 0;
+        while done < count {
+            let n = (count - done).min(per_cmd);
+            let bytes = n * block_size;
+            unsafe {
+                let (prp1, prp2) = Self::bulk_prps(bytes);
+                let cmd = Self::io_cmd(NVME_IO_OP_READ, 8, nsid, lba + done as u64, n as u32, prp1, prp2);
+                if !self.submit_io(cmd) { return false; }
+                let bulk = core::slice::from_raw_parts(BULK_BUF.as_ptr() as *const u8, bytes);
+                buffer[done * block_size..done * block_size + bytes].copy_from_slice(bulk);
             }
+            done += n;
         }
-        false
+        true
+    }
+
+    /// PRP1/PRP2 for a `bytes`-long transfer from the start of BULK_BUF. One or two pages fit
+    /// in the entries directly; anything longer points PRP2 at a list of the remaining pages.
+    unsafe fn bulk_prps(bytes: usize) -> (u64, u64) {
+        let page_phys = |i: usize| crate::memory::virt_to_phys(&BULK_BUF[i] as *const _ as u64).unwrap();
+        let pages = (bytes + 4095) / 4096;
+
+        let prp2 = match pages {
+            0 | 1 => 0,
+            2 => page_phys(1),
+            _ => {
+                let list = &mut *(&mut PRP_LIST.0 as *mut _ as *mut [u64; 512]);
+                for i in 1..pages { list[i - 1] = page_phys(i); }
+                crate::memory::virt_to_phys(&PRP_LIST as *const _ as u64).unwrap()
+            }
+        };
+        (page_phys(0), prp2)
     }
 }
\ No newline at end of file
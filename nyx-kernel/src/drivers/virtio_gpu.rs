@@ -0,0 +1,579 @@
+use core::ptr::{read_volatile, write_volatile};
+use core::sync::atomic::{fence, AtomicBool, Ordering};
+use spin::Mutex;
+use crate::pci::{PciDevice, PciDriver};
+
+// ==========================================
+// VIRTIO-GPU (QEMU -device virtio-gpu / virtio-vga)
+// ==========================================
+// The modern (virtio 1.0) PCI device, 2D only. The host keeps its own copy of
+// every resource and shows whichever one is bound to scanout 0, so instead of
+// copying pixels into the framebuffer we tell it which rectangle of guest
+// memory changed (TRANSFER_TO_HOST_2D) and to repaint it (RESOURCE_FLUSH).
+//
+// Three resources exist: the kernel's back buffer (backed by BACK_BUFFER's
+// pages, wherever the heap put them), the boot framebuffer that userspace
+// draws into after Syscall 508, and a 64x64 arrow for the hardware cursor.
+// Scanout follows gui::kernel_owns_screen. The cursor lives on its own
+// queue and moving it sends a few bytes, no pixels at all.
+//
+// Commands are synchronous: one chain in flight per queue, waited for by
+// polling the used ring, so nothing here needs an interrupt. Every entry
+// point from interrupt context only try_locks the driver.
+
+pub const VENDOR_ID: u16 = 0x1AF4;
+pub const DEVICE_ID: u16 = 0x1050;
+
+const CAP_VENDOR: u8 = 0x09;
+const CFG_COMMON: u8 = 1;
+const CFG_NOTIFY: u8 = 2;
+
+const STATUS_ACKNOWLEDGE: u8 = 1;
+const STATUS_DRIVER: u8 = 2;
+const STATUS_DRIVER_OK: u8 = 4;
+const STATUS_FEATURES_OK: u8 = 8;
+// Bit 32, the first bit of feature word 1
+const FEATURE_VERSION_1: u32 = 1 << 0;
+
+// Common configuration layout
+const COMMON_DFSELECT: u64 = 0x00;
+const COMMON_DFEATURE: u64 = 0x04;
+const COMMON_GFSELECT: u64 = 0x08;
+const COMMON_GFEATURE: u64 = 0x0C;
+const COMMON_NUM_QUEUES: u64 = 0x12;
+const COMMON_STATUS: u64 = 0x14;
+const COMMON_Q_SELECT: u64 = 0x16;
+const COMMON_Q_SIZE: u64 = 0x18;
+const COMMON_Q_ENABLE: u64 = 0x1C;
+const COMMON_Q_NOTIFY_OFF: u64 = 0x1E;
+const COMMON_Q_DESC: u64 = 0x20;
+const COMMON_Q_DRIVER: u64 = 0x28;
+const COMMON_Q_DEVICE: u64 = 0x30;
+
+const QUEUE_CONTROL: u16 = 0;
+const QUEUE_CURSOR: u16 = 1;
+const MAX_QUEUE_SIZE: u16 = 64;
+// Offsets of the rings inside a queue's page
+const AVAIL_OFFSET: u64 = 1024;
+const USED_OFFSET: u64 = 2048;
+const DESC_NEXT: u16 = 1;
+const DESC_WRITE: u16 = 2;
+
+const RESET_TIMEOUT_MS: u64 = 100;
+const COMMAND_TIMEOUT_MS: u64 = 500;
+
+const CMD_GET_DISPLAY_INFO: u32 = 0x0100;
+const CMD_RESOURCE_CREATE_2D: u32 = 0x0101;
+const CMD_SET_SCANOUT: u32 = 0x0103;
+const CMD_RESOURCE_FLUSH: u32 = 0x0104;
+const CMD_TRANSFER_TO_HOST_2D: u32 = 0x0105;
+const CMD_RESOURCE_ATTACH_BACKING: u32 = 0x0106;
+const CMD_UPDATE_CURSOR: u32 = 0x0300;
+const CMD_MOVE_CURSOR: u32 = 0x0301;
+const RESP_OK_NODATA: u32 = 0x1100;
+const RESP_OK_DISPLAY_INFO: u32 = 0x1101;
+
+const FORMAT_B8G8R8A8: u32 = 1;
+const FORMAT_B8G8R8X8: u32 = 2;
+
+const RESOURCE_KERNEL: u32 = 1;
+const RESOURCE_USER: u32 = 2;
+const RESOURCE_CURSOR: u32 = 3;
+const CURSOR_SIZE: usize = 64;
+// Backing entries for the back buffer: one per physically contiguous run
+// of heap pages, which all fit in one page
+const MAX_BACKING_ENTRIES: usize = 4096 / core::mem::size_of::<MemEntry>();
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct CtrlHeader { ty: u32, flags: u32, fence_id: u64, ctx_id: u32, padding: u32 }
+
+impl CtrlHeader {
+    fn new(ty: u32) -> Self { Self { ty, ..Default::default() } }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct GpuRect { x: u32, y: u32, w: u32, h: u32 }
+
+#[repr(C)]
+struct ResourceCreate2d { hdr: CtrlHeader, resource_id: u32, format: u32, width: u32, height: u32 }
+
+#[repr(C)]
+struct AttachBacking { hdr: CtrlHeader, resource_id: u32, nr_entries: u32 }
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct MemEntry { addr: u64, length: u32, padding: u32 }
+
+#[repr(C)]
+struct SetScanout { hdr: CtrlHeader, rect: GpuRect, scanout_id: u32, resource_id: u32 }
+
+#[repr(C)]
+struct TransferToHost2d { hdr: CtrlHeader, rect: GpuRect, offset: u64, resource_id: u32, padding: u32 }
+
+#[repr(C)]
+struct ResourceFlush { hdr: CtrlHeader, rect: GpuRect, resource_id: u32, padding: u32 }
+
+#[repr(C)]
+struct UpdateCursor {
+    hdr: CtrlHeader,
+    scanout_id: u32, x: u32, y: u32, padding: u32,
+    resource_id: u32, hot_x: u32, hot_y: u32, padding2: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct DisplayOne { rect: GpuRect, enabled: u32, flags: u32 }
+
+#[repr(C)]
+struct DisplayInfo { hdr: CtrlHeader, pmodes: [DisplayOne; 16] }
+
+#[repr(C)]
+struct Desc { addr: u64, len: u32, flags: u16, next: u16 }
+
+struct Virtqueue {
+    index: u16,
+    size: u16,
+    /// Virtual address of the page holding the descriptor table and both rings
+    base: u64,
+    notify: u64,
+    avail_idx: u16,
+}
+
+impl Virtqueue {
+    /// Puts one chain of (phys, len, device-writable) buffers on the queue and
+    /// waits for the device to hand it back.
+    fn submit(&mut self, chain: &[(u64, u32, bool)]) -> bool {
+        unsafe {
+            let desc = self.base as *mut Desc;
+            for (i, &(addr, len, writable)) in chain.iter().enumerate() {
+                let mut flags = if writable { DESC_WRITE } else { 0 };
+                if i + 1 < chain.len() { flags |= DESC_NEXT; }
+                write_volatile(desc.add(i), Desc { addr, len, flags, next: (i + 1) as u16 });
+            }
+            // avail: flags, idx, ring[size]
+            let avail = (self.base + AVAIL_OFFSET) as *mut u16;
+            write_volatile(avail.add(2 + (self.avail_idx % self.size) as usize), 0);
+            fence(Ordering::SeqCst);
+            self.avail_idx = self.avail_idx.wrapping_add(1);
+            write_volatile(avail.add(1), self.avail_idx);
+            fence(Ordering::SeqCst);
+            write_volatile(self.notify as *mut u16, self.index);
+        }
+
+        // used: flags, idx, ring[size]
+        let used_idx = (self.base + USED_OFFSET + 2) as *const u16;
+        let target = self.avail_idx;
+        let done = || unsafe { read_volatile(used_idx) } == target;
+        if crate::vga_log::in_panic_mode() {
+            // The watchdog's table may be what the panicking code held
+            let deadline = crate::time::monotonic_ms() + COMMAND_TIMEOUT_MS;
+            while !done() {
+                if crate::time::monotonic_ms() >= deadline { return done(); }
+                core::hint::spin_loop();
+            }
+            true
+        } else {
+            crate::watchdog::spin_until("VIRTIO-GPU", "command", COMMAND_TIMEOUT_MS, done)
+        }
+    }
+}
+
+pub struct VirtioGpu {
+    common: u64,
+    control: Virtqueue,
+    cursor: Virtqueue,
+    /// One page: the request in the first half, the response in the second
+    cmd_phys: u64,
+    width: u32,
+    height: u32,
+    /// Pixels per row of both screen resources
+    stride: u32,
+    /// Resource bound to scanout 0
+    on_screen: u32,
+    cursor_at: (u32, u32),
+    has_cursor: bool,
+}
+
+pub static VIRTIO_GPU: Mutex<Option<VirtioGpu>> = Mutex::new(None);
+// Set once the device shows the kernel's resources, so hot paths skip the lock
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+static HW_CURSOR: AtomicBool = AtomicBool::new(false);
+
+/// Whether presents go through the device instead of the framebuffer.
+pub fn is_active() -> bool { ACTIVE.load(Ordering::Acquire) }
+
+/// Whether the pointer is drawn by the host, so nobody needs to paint it.
+pub fn has_hw_cursor() -> bool { HW_CURSOR.load(Ordering::Acquire) }
+
+struct Capability { bar: u8, offset: u32, length: u32, extra: u32 }
+
+/// Walks the capability list for every virtio vendor capability of one type.
+/// `extra` is the notify multiplier for the notify capability.
+fn find_virtio_cap(dev: &PciDevice, cfg_type: u8) -> Option<Capability> {
+    let read8 = |off: u8| (PciDriver::read_config(dev.bus, dev.device, dev.func, off & 0xFC) >> ((off & 3) * 8)) as u8;
+    if PciDriver::read_config(dev.bus, dev.device, dev.func, 0x04) & (1 << 20) == 0 { return None; }
+    let mut ptr = read8(0x34) & 0xFC;
+    let mut hops = 0;
+    while ptr != 0 && hops < 48 {
+        if read8(ptr) == CAP_VENDOR && read8(ptr + 3) == cfg_type {
+            return Some(Capability {
+                bar: read8(ptr + 4),
+                offset: PciDriver::read_config(dev.bus, dev.device, dev.func, ptr + 8),
+                length: PciDriver::read_config(dev.bus, dev.device, dev.func, ptr + 12),
+                extra: if cfg_type == CFG_NOTIFY { PciDriver::read_config(dev.bus, dev.device, dev.func, ptr + 16) } else { 0 },
+            });
+        }
+        ptr = read8(ptr + 1) & 0xFC;
+        hops += 1;
+    }
+    None
+}
+
+fn zeroed_page() -> Option<(u64, u64)> {
+    let phys = crate::memory::allocate_contiguous(1, 4096, false)?.start_address().as_u64();
+    let virt = crate::memory::phys_to_virt(phys)?;
+    unsafe { core::ptr::write_bytes(virt as *mut u8, 0, 4096); }
+    Some((phys, virt))
+}
+
+impl VirtioGpu {
+    fn read8(&self, off: u64) -> u8 { unsafe { read_volatile((self.common + off) as *const u8) } }
+    fn write8(&self, off: u64, v: u8) { unsafe { write_volatile((self.common + off) as *mut u8, v) } }
+    fn read16(&self, off: u64) -> u16 { unsafe { read_volatile((self.common + off) as *const u16) } }
+    fn write16(&self, off: u64, v: u16) { unsafe { write_volatile((self.common + off) as *mut u16, v) } }
+    fn read32(&self, off: u64) -> u32 { unsafe { read_volatile((self.common + off) as *const u32) } }
+    fn write32(&self, off: u64, v: u32) { unsafe { write_volatile((self.common + off) as *mut u32, v) } }
+    fn write64(&self, off: u64, v: u64) {
+        self.write32(off, v as u32);
+        self.write32(off + 4, (v >> 32) as u32);
+    }
+
+    /// Resets the device, negotiates VERSION_1 only and sets up both queues.
+    fn init(dev: &PciDevice) -> Result<Self, &'static str> {
+        let common_cap = find_virtio_cap(dev, CFG_COMMON).ok_or("no common config capability")?;
+        let notify_cap = find_virtio_cap(dev, CFG_NOTIFY).ok_or("no notify capability")?;
+
+        let mut pci = PciDriver::new();
+        let map = |pci: &mut PciDriver, cap: &Capability| -> Result<u64, &'static str> {
+            let bar = pci.get_bar_address(dev, cap.bar).ok_or("capability BAR unassigned")?;
+            unsafe { crate::memory::map_mmio(bar + cap.offset as u64, cap.length.max(1) as usize) }
+        };
+        let common = map(&mut pci, &common_cap)?;
+        let notify_base = map(&mut pci, &notify_cap)?;
+
+        let (cmd_phys, _) = zeroed_page().ok_or("out of memory")?;
+        let mut gpu = Self {
+            common,
+            control: Virtqueue { index: QUEUE_CONTROL, size: 0, base: 0, notify: 0, avail_idx: 0 },
+            cursor: Virtqueue { index: QUEUE_CURSOR, size: 0, base: 0, notify: 0, avail_idx: 0 },
+            cmd_phys,
+            width: 0, height: 0, stride: 0,
+            on_screen: 0,
+            cursor_at: (0, 0),
+            has_cursor: false,
+        };
+
+        gpu.write8(COMMON_STATUS, 0);
+        if !crate::watchdog::spin_until("VIRTIO-GPU", "reset", RESET_TIMEOUT_MS, || gpu.read8(COMMON_STATUS) == 0) {
+            return Err("device did not reset");
+        }
+        gpu.write8(COMMON_STATUS, STATUS_ACKNOWLEDGE | STATUS_DRIVER);
+
+        gpu.write32(COMMON_DFSELECT, 1);
+        if gpu.read32(COMMON_DFEATURE) & FEATURE_VERSION_1 == 0 { return Err("not a virtio 1.0 device"); }
+        gpu.write32(COMMON_GFSELECT, 0);
+        gpu.write32(COMMON_GFEATURE, 0);
+        gpu.write32(COMMON_GFSELECT, 1);
+        gpu.write32(COMMON_GFEATURE, FEATURE_VERSION_1);
+        gpu.write8(COMMON_STATUS, STATUS_ACKNOWLEDGE | STATUS_DRIVER | STATUS_FEATURES_OK);
+        if gpu.read8(COMMON_STATUS) & STATUS_FEATURES_OK == 0 { return Err("features refused"); }
+
+        if gpu.read16(COMMON_NUM_QUEUES) < 2 { return Err("missing the cursor queue"); }
+        gpu.control = gpu.setup_queue(QUEUE_CONTROL, notify_base, notify_cap.extra)?;
+        gpu.cursor = gpu.setup_queue(QUEUE_CURSOR, notify_base, notify_cap.extra)?;
+
+        gpu.write8(COMMON_STATUS, STATUS_ACKNOWLEDGE | STATUS_DRIVER | STATUS_FEATURES_OK | STATUS_DRIVER_OK);
+        Ok(gpu)
+    }
+
+    fn setup_queue(&self, index: u16, notify_base: u64, multiplier: u32) -> Result<Virtqueue, &'static str> {
+        self.write16(COMMON_Q_SELECT, index);
+        let size = self.read16(COMMON_Q_SIZE).min(MAX_QUEUE_SIZE);
+        if size == 0 { return Err("queue unavailable"); }
+        let (phys, virt) = zeroed_page().ok_or("out of memory")?;
+        self.write16(COMMON_Q_SIZE, size);
+        self.write64(COMMON_Q_DESC, phys);
+        self.write64(COMMON_Q_DRIVER, phys + AVAIL_OFFSET);
+        self.write64(COMMON_Q_DEVICE, phys + USED_OFFSET);
+        let notify_off = self.read16(COMMON_Q_NOTIFY_OFF) as u64;
+        self.write16(COMMON_Q_ENABLE, 1);
+        Ok(Virtqueue { index, size, base: virt, notify: notify_base + notify_off * multiplier as u64, avail_idx: 0 })
+    }
+
+    /// Sends `req` (plus an optional extra buffer after it) on the control or
+    /// cursor queue and returns the response type.
+    fn command<T>(&mut self, req: T, extra: Option<(u64, u32)>, resp_len: usize, cursor: bool) -> Option<u32> {
+        let size = core::mem::size_of::<T>();
+        let virt = crate::memory::phys_to_virt(self.cmd_phys)?;
+        unsafe {
+            core::ptr::write_volatile(virt as *mut T, req);
+            core::ptr::write_bytes((virt + 2048) as *mut u8, 0, resp_len);
+        }
+        let request = (self.cmd_phys, size as u32, false);
+        let response = (self.cmd_phys + 2048, resp_len as u32, true);
+        let ok = if cursor {
+            // The cursor queue has no responses
+            self.cursor.submit(&[request])
+        } else {
+            match extra {
+                Some((phys, len)) => self.control.submit(&[request, (phys, len, false), response]),
+                None => self.control.submit(&[request, response]),
+            }
+        };
+        if !ok { return None; }
+        if cursor { return Some(RESP_OK_NODATA); }
+        Some(unsafe { read_volatile((virt + 2048) as *const u32) })
+    }
+
+    fn simple<T>(&mut self, req: T) -> bool {
+        self.command(req, None, core::mem::size_of::<CtrlHeader>(), false) == Some(RESP_OK_NODATA)
+    }
+
+    fn host_display(&mut self) -> Option<(u32, u32)> {
+        let resp = self.command(CtrlHeader::new(CMD_GET_DISPLAY_INFO), None, core::mem::size_of::<DisplayInfo>(), false)?;
+        if resp != RESP_OK_DISPLAY_INFO { return None; }
+        let info = unsafe { &*((crate::memory::phys_to_virt(self.cmd_phys)? + 2048) as *const DisplayInfo) };
+        let mode = info.pmodes[0];
+        if mode.enabled == 0 { None } else { Some((mode.rect.w, mode.rect.h)) }
+    }
+
+    fn create(&mut self, resource_id: u32, format: u32, width: u32, height: u32) -> bool {
+        self.simple(ResourceCreate2d { hdr: CtrlHeader::new(CMD_RESOURCE_CREATE_2D), resource_id, format, width, height })
+    }
+
+    /// Backs a resource with `entries`, written into a page of their own.
+    fn attach(&mut self, resource_id: u32, entries: &[MemEntry]) -> bool {
+        let (phys, virt) = match zeroed_page() { Some(p) => p, None => return false };
+        let n = entries.len().min(MAX_BACKING_ENTRIES);
+        unsafe { core::ptr::copy_nonoverlapping(entries.as_ptr(), virt as *mut MemEntry, n); }
+        let len = (n * core::mem::size_of::<MemEntry>()) as u32;
+        let req = AttachBacking { hdr: CtrlHeader::new(CMD_RESOURCE_ATTACH_BACKING), resource_id, nr_entries: n as u32 };
+        self.command(req, Some((phys, len)), core::mem::size_of::<CtrlHeader>(), false) == Some(RESP_OK_NODATA)
+    }
+
+    fn set_scanout(&mut self, resource_id: u32) -> bool {
+        let rect = GpuRect { x: 0, y: 0, w: self.width, h: self.height };
+        let ok = self.simple(SetScanout { hdr: CtrlHeader::new(CMD_SET_SCANOUT), rect, scanout_id: 0, resource_id });
+        if ok { self.on_screen = resource_id; }
+        ok
+    }
+
+    /// Copies a rectangle of a screen resource's backing to the host and
+    /// repaints it there.
+    fn flush(&mut self, resource_id: u32, x: u32, y: u32, w: u32, h: u32) -> bool {
+        let x = x.min(self.width);
+        let y = y.min(self.height);
+        let rect = GpuRect { x, y, w: w.min(self.width - x), h: h.min(self.height - y) };
+        if rect.w == 0 || rect.h == 0 { return true; }
+        let offset = (y as u64 * self.stride as u64 + x as u64) * 4;
+        self.simple(TransferToHost2d { hdr: CtrlHeader::new(CMD_TRANSFER_TO_HOST_2D), rect, offset, resource_id, padding: 0 })
+            && self.simple(ResourceFlush { hdr: CtrlHeader::new(CMD_RESOURCE_FLUSH), rect, resource_id, padding: 0 })
+    }
+
+    fn update_cursor(&mut self, ty: u32, resource_id: u32) -> bool {
+        let (x, y) = self.cursor_at;
+        let req = UpdateCursor {
+            hdr: CtrlHeader::new(ty),
+            scanout_id: 0, x, y, padding: 0,
+            resource_id, hot_x: 0, hot_y: 0, padding2: 0,
+        };
+        self.command(req, None, 0, true).is_some()
+    }
+
+    /// Creates both screen resources and the cursor, and shows whichever
+    /// screen currently owns the display.
+    fn bring_up(&mut self, back: &crate::gui::BackBuffer, fb_phys: u64) -> Result<(), &'static str> {
+        let info = back.info;
+        if info.bytes_per_pixel != 4 { return Err("framebuffer is not 32-bit"); }
+        self.width = info.width as u32;
+        self.height = info.height as u32;
+        self.stride = info.stride as u32;
+
+        if let Some((w, h)) = self.host_display() {
+            crate::serial_println!("[VIRTIO-GPU] Host display {}x{}", w, h);
+        }
+
+        // Rows are `stride` pixels apart in both buffers, so the resources
+        // are that wide and scanout shows their left `width` columns
+        if !self.create(RESOURCE_KERNEL, FORMAT_B8G8R8X8, self.stride, self.height) { return Err("create kernel resource"); }
+        let mut entries = [MemEntry { addr: 0, length: 0, padding: 0 }; MAX_BACKING_ENTRIES];
+        let mut n = 0;
+        let start = back.buffer.as_ptr() as u64;
+        let end = start + back.buffer.len() as u64;
+        let mut page = start & !0xFFF;
+        while page < end {
+            let from = page.max(start);
+            let to = (page + 4096).min(end);
+            let phys = crate::memory::virt_to_phys(from).ok_or("back buffer not mapped")?;
+            let len = (to - from) as u32;
+            if n > 0 && entries[n - 1].addr + entries[n - 1].length as u64 == phys {
+                entries[n - 1].length += len;
+            } else {
+                if n == MAX_BACKING_ENTRIES { return Err("back buffer too fragmented"); }
+                entries[n] = MemEntry { addr: phys, length: len, padding: 0 };
+                n += 1;
+            }
+            page += 4096;
+        }
+        if !self.attach(RESOURCE_KERNEL, &entries[..n]) { return Err("attach kernel backing"); }
+
+        if !self.create(RESOURCE_USER, FORMAT_B8G8R8X8, self.stride, self.height) { return Err("create user resource"); }
+        let fb_len = (info.stride * info.height * 4) as u32;
+        if !self.attach(RESOURCE_USER, &[MemEntry { addr: fb_phys, length: fb_len, padding: 0 }]) { return Err("attach framebuffer"); }
+
+        self.has_cursor = self.create_cursor();
+
+        let resource = if crate::gui::kernel_owns_screen() { RESOURCE_KERNEL } else { RESOURCE_USER };
+        if !self.set_scanout(resource) { return Err("set scanout"); }
+        let (w, h) = (self.width, self.height);
+        self.flush(resource, 0, 0, w, h);
+        Ok(())
+    }
+
+    /// Draws the arrow into a resource of its own and hands it to the
+    /// cursor queue. Without it the software cursors stay in use.
+    fn create_cursor(&mut self) -> bool {
+        let pages = (CURSOR_SIZE * CURSOR_SIZE * 4).div_ceil(4096);
+        let phys = match crate::memory::allocate_contiguous(pages, 4096, false) { Some(f) => f.start_address().as_u64(), None => return false };
+        let virt = match crate::memory::phys_to_virt(phys) { Some(v) => v, None => return false };
+        let pixels = unsafe { core::slice::from_raw_parts_mut(virt as *mut u32, CURSOR_SIZE * CURSOR_SIZE) };
+        draw_arrow(pixels);
+
+        let size = CURSOR_SIZE as u32;
+        if !self.create(RESOURCE_CURSOR, FORMAT_B8G8R8A8, size, size) { return false; }
+        if !self.attach(RESOURCE_CURSOR, &[MemEntry { addr: phys, length: (pixels.len() * 4) as u32, padding: 0 }]) { return false; }
+        let rect = GpuRect { x: 0, y: 0, w: size, h: size };
+        if !self.simple(TransferToHost2d { hdr: CtrlHeader::new(CMD_TRANSFER_TO_HOST_2D), rect, offset: 0, resource_id: RESOURCE_CURSOR, padding: 0 }) {
+            return false;
+        }
+        self.update_cursor(CMD_UPDATE_CURSOR, RESOURCE_CURSOR)
+    }
+}
+
+// The compositor's arrow: 1 outline, 2 fill, tip at the top-left pixel
+const ARROW: [[u8; 11]; 16] = [
+    [1,1,0,0,0,0,0,0,0,0,0],
+    [1,2,1,0,0,0,0,0,0,0,0],
+    [1,2,2,1,0,0,0,0,0,0,0],
+    [1,2,2,2,1,0,0,0,0,0,0],
+    [1,2,2,2,2,1,0,0,0,0,0],
+    [1,2,2,2,2,2,1,0,0,0,0],
+    [1,2,2,2,2,2,2,1,0,0,0],
+    [1,2,2,2,2,2,2,2,1,0,0],
+    [1,2,2,2,2,2,2,2,2,1,0],
+    [1,2,2,2,2,2,2,2,2,2,1],
+    [1,2,2,2,2,2,2,1,1,1,1],
+    [1,2,2,1,2,2,1,0,0,0,0],
+    [1,2,1,0,1,2,2,1,0,0,0],
+    [1,1,0,0,1,2,2,1,0,0,0],
+    [1,0,0,0,0,1,2,2,1,0,0],
+    [0,0,0,0,0,0,1,1,0,0,0],
+];
+
+fn draw_arrow(pixels: &mut [u32]) {
+    pixels.fill(0);
+    for (y, row) in ARROW.iter().enumerate() {
+        for (x, &p) in row.iter().enumerate() {
+            pixels[y * CURSOR_SIZE + x] = match p { 1 => 0xFF2D_2D2A, 2 => 0xFFFF_FFFF, _ => 0 };
+        }
+    }
+}
+
+/// Brings up a device found by the PCI scan. Needs BACK_BUFFER.
+pub fn probe(dev: &PciDevice) {
+    let mut cmd = PciDriver::read_config(dev.bus, dev.device, dev.func, 0x04);
+    cmd |= 0x06;
+    PciDriver::write_config(dev.bus, dev.device, dev.func, 0x04, cmd);
+
+    let mut gpu = match VirtioGpu::init(dev) {
+        Ok(gpu) => gpu,
+        Err(e) => { crate::serial_println!("[VIRTIO-GPU] Init failed: {}", e); return; }
+    };
+    let result = unsafe {
+        match &crate::gui::BACK_BUFFER {
+            Some(back) => gpu.bring_up(back, crate::gui::FRAMEBUFFER_PHYS_ADDR),
+            None => Err("no back buffer"),
+        }
+    };
+    if let Err(e) = result {
+        crate::serial_println!("[VIRTIO-GPU] Setup failed: {}; staying on the framebuffer", e);
+        return;
+    }
+    crate::serial_println!("[VIRTIO-GPU] Scanout {}x{}, hardware cursor {}", gpu.width, gpu.height, if gpu.has_cursor { "on" } else { "off" });
+    HW_CURSOR.store(gpu.has_cursor, Ordering::Release);
+    *VIRTIO_GPU.lock() = Some(gpu);
+    ACTIVE.store(true, Ordering::Release);
+}
+
+/// Pushes a changed rectangle of the back buffer to the screen. False when
+/// the driver was busy, so the caller keeps the rectangle for next time.
+pub fn flush_kernel(x: usize, y: usize, w: usize, h: usize) -> bool {
+    let mut lock = match VIRTIO_GPU.try_lock() { Some(l) => l, None => return false };
+    match lock.as_mut() {
+        // While userspace has the screen the back buffer isn't shown anyway
+        Some(gpu) if gpu.on_screen == RESOURCE_KERNEL => { gpu.flush(RESOURCE_KERNEL, x as u32, y as u32, w as u32, h as u32); true }
+        _ => true,
+    }
+}
+
+/// Pushes a changed rectangle of the framebuffer userspace draws into.
+pub fn flush_user(x: usize, y: usize, w: usize, h: usize) {
+    if let Some(mut lock) = VIRTIO_GPU.try_lock() {
+        if let Some(gpu) = lock.as_mut() {
+            if gpu.on_screen == RESOURCE_USER { gpu.flush(RESOURCE_USER, x as u32, y as u32, w as u32, h as u32); }
+        }
+    }
+}
+
+/// Points scanout at the kernel's back buffer or at userspace's framebuffer.
+pub fn show(kernel: bool) {
+    if !is_active() { return; }
+    let resource = if kernel { RESOURCE_KERNEL } else { RESOURCE_USER };
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        if let Some(gpu) = VIRTIO_GPU.lock().as_mut() {
+            if gpu.on_screen != resource && gpu.set_scanout(resource) {
+                let (w, h) = (gpu.width, gpu.height);
+                gpu.flush(resource, 0, 0, w, h);
+            }
+        }
+    });
+}
+
+/// Moves the hardware cursor to the mouse. Called from the BSP's timer tick.
+pub fn track_pointer() {
+    if !has_hw_cursor() { return; }
+    let (mx, my) = match crate::mouse::MOUSE_STATE.try_lock() { Some(m) => (m.x, m.y), None => return };
+    let mut lock = match VIRTIO_GPU.try_lock() { Some(l) => l, None => return };
+    let gpu = match lock.as_mut() { Some(g) => g, None => return };
+
+    // The pointer moves over the whole desktop, which is scaled into the screen
+    let (ew, eh) = crate::display::extent();
+    let x = (mx * gpu.width as usize / ew.max(1)) as u32;
+    let y = (my * gpu.height as usize / eh.max(1)) as u32;
+    if (x, y) == gpu.cursor_at { return; }
+    gpu.cursor_at = (x, y);
+    gpu.update_cursor(CMD_MOVE_CURSOR, RESOURCE_CURSOR);
+}
+
+/// Shows the framebuffer the panic screen paints into. The lock is forced
+/// open, since the panicking code may have held it.
+pub fn show_panic_screen() {
+    if !is_active() { return; }
+    unsafe { VIRTIO_GPU.force_unlock(); }
+    if let Some(gpu) = VIRTIO_GPU.lock().as_mut() {
+        let (w, h) = (gpu.width, gpu.height);
+        if gpu.on_screen == RESOURCE_USER || gpu.set_scanout(RESOURCE_USER) { gpu.flush(RESOURCE_USER, 0, 0, w, h); }
+    }
+}
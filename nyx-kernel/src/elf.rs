@@ -0,0 +1,160 @@
+use alloc::vec::Vec;
+use core::convert::TryInto;
+
+// ==========================================
+// ELF64 LOADER
+// ==========================================
+// Every header field is bounds-checked before anything is mapped: a program
+// either loads completely or not at all. PT_LOAD segments go to the address
+// they ask for, with .bss zeroed; segments without PF_W end up read-only.
+// Flat binaries (no ELF magic) are still accepted by load_flat for images
+// that predate the ELF build.
+
+const PT_LOAD: u32 = 1;
+const PF_W: u32 = 2;
+const ET_EXEC: u16 = 2;
+const ET_DYN: u16 = 3;
+const EM_X86_64: u16 = 0x3E;
+
+const EHDR_SIZE: usize = 64;
+const PHDR_SIZE: usize = 56;
+const PAGE: u64 = 4096;
+
+/// Segments must end below the user stack window
+pub const USER_LOAD_LIMIT: u64 = crate::process::USER_STACK_BASE;
+/// Where flat binaries are placed; their entry point is the first byte
+pub const FLAT_LOAD_ADDR: u64 = 0x100_0000;
+/// Segments must start here or above, clear of the kernel image and the low
+/// identity mappings every address space inherits
+pub const USER_LOAD_BASE: u64 = FLAT_LOAD_ADDR;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Segment {
+    pub vaddr: u64,
+    pub memsz: u64,
+    pub offset: u64,
+    pub filesz: u64,
+    pub writable: bool,
+}
+
+impl Segment {
+    fn first_page(&self) -> u64 { self.vaddr & !(PAGE - 1) }
+    fn end_page(&self) -> u64 { (self.vaddr + self.memsz + PAGE - 1) & !(PAGE - 1) }
+}
+
+pub struct ElfImage {
+    pub entry: u64,
+    /// PT_LOAD segments sorted by address, none overlapping
+    pub segments: Vec<Segment>,
+}
+
+fn u16_at(b: &[u8], off: usize) -> u16 { u16::from_le_bytes(b[off..off + 2].try_into().unwrap()) }
+fn u32_at(b: &[u8], off: usize) -> u32 { u32::from_le_bytes(b[off..off + 4].try_into().unwrap()) }
+fn u64_at(b: &[u8], off: usize) -> u64 { u64::from_le_bytes(b[off..off + 8].try_into().unwrap()) }
+
+pub fn is_elf(data: &[u8]) -> bool {
+    data.len() >= 4 && data[0..4] == [0x7F, b'E', b'L', b'F']
+}
+
+/// Validates `data` as an x86_64 executable and lists its loadable segments.
+pub fn parse(data: &[u8]) -> Result<ElfImage, &'static str> {
+    if data.len() < EHDR_SIZE { return Err("ELF: file smaller than its header"); }
+    if !is_elf(data) { return Err("ELF: bad magic"); }
+    if data[4] != 2 || data[5] != 1 { return Err("ELF: not a little-endian 64-bit image"); }
+    let e_type = u16_at(data, 16);
+    if e_type != ET_EXEC && e_type != ET_DYN { return Err("ELF: not an executable"); }
+    if u16_at(data, 18) != EM_X86_64 { return Err("ELF: not built for x86_64"); }
+
+    let entry = u64_at(data, 24);
+    let phoff = u64_at(data, 32) as usize;
+    let phentsize = u16_at(data, 54) as usize;
+    let phnum = u16_at(data, 56) as usize;
+    if phentsize < PHDR_SIZE { return Err("ELF: program header entries too small"); }
+    let table_end = phnum.checked_mul(phentsize).and_then(|n| n.checked_add(phoff));
+    if table_end.map_or(true, |end| end > data.len()) { return Err("ELF: program headers run past the file"); }
+
+    let mut segments = Vec::new();
+    for i in 0..phnum {
+        let ph = &data[phoff + i * phentsize..phoff + i * phentsize + PHDR_SIZE];
+        if u32_at(ph, 0) != PT_LOAD { continue; }
+
+        let seg = Segment {
+            writable: u32_at(ph, 4) & PF_W != 0,
+            offset: u64_at(ph, 8),
+            vaddr: u64_at(ph, 16),
+            filesz: u64_at(ph, 32),
+            memsz: u64_at(ph, 40),
+        };
+        if seg.memsz == 0 { continue; }
+        if seg.filesz > seg.memsz { return Err("ELF: segment file size exceeds its memory size"); }
+        if seg.offset.checked_add(seg.filesz).map_or(true, |end| end > data.len() as u64) {
+            return Err("ELF: segment data runs past the file");
+        }
+        if seg.vaddr < USER_LOAD_BASE || seg.vaddr.checked_add(seg.memsz).map_or(true, |end| end > USER_LOAD_LIMIT) {
+            return Err("ELF: segment outside the user load range");
+        }
+        if (seg.vaddr >> 39) as usize <= crate::allocator::HEAP_PML4_SLOT
+            && ((seg.vaddr + seg.memsz - 1) >> 39) as usize >= crate::allocator::HEAP_PML4_SLOT {
+            return Err("ELF: segment overlaps the kernel heap");
+        }
+        segments.push(seg);
+    }
+    if segments.is_empty() { return Err("ELF: no loadable segments"); }
+
+    segments.sort_unstable_by_key(|s| s.vaddr);
+    for pair in segments.windows(2) {
+        if pair[0].vaddr + pair[0].memsz > pair[1].vaddr { return Err("ELF: overlapping segments"); }
+    }
+    if !segments.iter().any(|s| entry >= s.vaddr && entry < s.vaddr + s.memsz) {
+        return Err("ELF: entry point outside every segment");
+    }
+
+    Ok(ElfImage { entry, segments })
+}
+
+/// Maps and fills every segment of `data` in the active address space and
+/// returns the entry point.
+pub fn load(data: &[u8]) -> Result<u64, &'static str> {
+    let image = parse(data)?;
+
+    // Neighbouring segments may share a boundary page; it is only mapped once
+    let mut mapped_end = 0;
+    for seg in &image.segments {
+        let first_new = seg.first_page().max(mapped_end);
+        if first_new < seg.end_page() {
+            crate::memory::allocate_user_pages_at(first_new, ((seg.end_page() - first_new) / PAGE) as usize)?;
+        }
+        mapped_end = seg.end_page();
+
+        unsafe {
+            let dest = seg.vaddr as *mut u8;
+            core::ptr::copy_nonoverlapping(data.as_ptr().add(seg.offset as usize), dest, seg.filesz as usize);
+            core::ptr::write_bytes(dest.add(seg.filesz as usize), 0, (seg.memsz - seg.filesz) as usize);
+        }
+    }
+
+    // Pages are mapped writable for the copy; tighten the ones that belong to a
+    // read-only segment alone. A shared boundary page stays writable.
+    let segs = &image.segments;
+    for (i, seg) in segs.iter().enumerate() {
+        if seg.writable { continue; }
+        let mut start = seg.first_page();
+        let mut end = seg.end_page();
+        if i > 0 && segs[i - 1].end_page() > start { start += PAGE; }
+        if i + 1 < segs.len() && segs[i + 1].first_page() < end { end -= PAGE; }
+        if start < end {
+            crate::memory::protect_user_pages_readonly(start, ((end - start) / PAGE) as usize)?;
+        }
+    }
+
+    Ok(image.entry)
+}
+
+/// Places a flat binary at FLAT_LOAD_ADDR and returns that as the entry point.
+pub fn load_flat(data: &[u8]) -> Result<u64, &'static str> {
+    if data.is_empty() { return Err("flat binary is empty"); }
+    let pages = (data.len() as u64 + PAGE - 1) / PAGE;
+    crate::memory::allocate_user_pages_at(FLAT_LOAD_ADDR, pages as usize)?;
+    unsafe { core::ptr::copy_nonoverlapping(data.as_ptr(), FLAT_LOAD_ADDR as *mut u8, data.len()); }
+    Ok(FLAT_LOAD_ADDR)
+}
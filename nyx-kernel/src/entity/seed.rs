@@ -23,7 +23,8 @@ pub fn awaken_entity(nvme_opt: &mut Option<crate::drivers::nvme::NvmeDriver>) {
     // 1. ATTEMPT TO RESURRECT FROM SILICON
     // ==========================================
     if let Some(disk) = nvme_opt {
-        if disk.read_block(ENTITY_LBA, &mut raw_block) {
+        let nsid = disk.default_nsid();
+        if disk.read_block(nsid, ENTITY_LBA, &mut raw_block) {
             // Check for our magic signature
             if &raw_block[0..4] == MAGIC_SIG {
                 unsafe { GENETIC_SEED.copy_from_slice(&raw_block[4..36]); }
@@ -65,7 +66,8 @@ pub fn awaken_entity(nvme_opt: &mut Option<crate::drivers::nvme::NvmeDriver>) {
         raw_block[0..4].copy_from_slice(MAGIC_SIG);
         raw_block[4..36].copy_from_slice(&result);
 
-        if disk.write_block(ENTITY_LBA, &raw_block) {
+        let nsid = disk.default_nsid();
+        if disk.write_block(nsid, ENTITY_LBA, &raw_block) {
             crate::serial_println!("[ENTITY] Seed forged into NVMe Sector {}.", ENTITY_LBA);
             crate::vga_println!("[ENTITY] New soul permanently fused to hardware.");
         } else {
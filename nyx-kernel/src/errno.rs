@@ -0,0 +1,50 @@
+// ==========================================
+// SYSCALL ERROR CODES
+// ==========================================
+// A syscall leaves either a non-negative result or -(errno) in rax. The
+// numbers follow Linux so ported code keeps working; nyx_api::NyxError
+// decodes the same table on the user side.
+//
+// Calls that hand back an address (alloc_pages, map_shm, map_framebuffer)
+// keep returning 0 on failure: no user mapping ever lives at 0.
+
+pub const EPERM: i64 = -1;
+pub const ENOENT: i64 = -2;
+pub const ESRCH: i64 = -3;
+pub const EIO: i64 = -5;
+pub const ENOEXEC: i64 = -8;
+pub const EBADF: i64 = -9;
+pub const EAGAIN: i64 = -11;
+pub const ENOMEM: i64 = -12;
+pub const EACCES: i64 = -13;
+pub const EFAULT: i64 = -14;
+pub const EEXIST: i64 = -17;
+pub const EXDEV: i64 = -18;
+pub const EISDIR: i64 = -21;
+pub const EINVAL: i64 = -22;
+pub const EMFILE: i64 = -24;
+pub const ENOSPC: i64 = -28;
+pub const ESPIPE: i64 = -29;
+pub const EROFS: i64 = -30;
+pub const ENAMETOOLONG: i64 = -36;
+pub const ENOSYS: i64 = -38;
+pub const EILSEQ: i64 = -84;
+pub const EMSGSIZE: i64 = -90;
+pub const EADDRINUSE: i64 = -98;
+
+/// Maps a VFS failure onto the errno user space sees.
+pub fn fs_errno(e: crate::vfs::FsError) -> i64 {
+    use crate::vfs::FsError;
+    match e {
+        FsError::NotFound => ENOENT,
+        FsError::InvalidPath => EINVAL,
+        FsError::OutOfSpace => ENOSPC,
+        FsError::PermissionDenied => EACCES,
+        // Read-only mounts: drivers without write support (TarFs) or ext4 mounted ro
+        FsError::Unsupported => EROFS,
+        FsError::AlreadyExists => EEXIST,
+        // A new name with a character file names can't hold
+        FsError::InvalidName => EILSEQ,
+        FsError::IoError => EIO,
+    }
+}
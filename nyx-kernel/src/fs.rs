@@ -1,54 +1,318 @@
 use alloc::string::String;
 use alloc::vec::Vec;
-use core::convert::TryInto;
 use crate::drivers::nvme::NvmeDriver;
+use crate::drivers::ahci::{AhciDisk, MAX_SECTORS_PER_CMD};
+use crate::drivers::block::BlockDevice;
 use alloc::boxed::Box;
-use crate::vfs::FsError;
+use crate::vfs::{FsError, VolumeInfo, DIRENT_FLAG_READONLY, DIRENT_FLAG_SYSTEM, FS_TYPE_EXT4};
 
-// ==========================================
-// C-FFI HARDWARE BRIDGE (DMA ALIGNED)
-// ==========================================
 pub static mut GLOBAL_NVME: Option<NvmeDriver> = None;
+/// First SATA disk (and the AHCI controller it sits on). Backs the root
+/// filesystem only when the machine has no NVMe controller.
+pub static mut GLOBAL_SATA: Option<AhciDisk> = None;
+/// First USB mass-storage device; the root filesystem's last resort when
+/// there is neither NVMe nor SATA.
+pub static mut GLOBAL_USB_DISK: Option<crate::usb_storage::UsbDisk> = None;
 
-#[no_mangle]
-pub extern "C" fn nyx_nvme_read_block(sector: u64, buf: *mut u8) -> bool {
+/// The disk behind the cache when there is no NVMe namespace: SATA, else USB.
+fn fallback_disk() -> Option<&'static mut dyn BlockDevice> {
     unsafe {
-        if let Some(ref mut driver) = GLOBAL_NVME {
-            // 🔥 MILESTONE 1.6 VERIFICATION:
-            // The NVMe driver requires strict 4096-byte page-aligned buffers for PRP DMA transfers.
-            // However, lwext4 natively addresses logical sectors in 512-byte increments.
-            // We safely allocate a 4K aligned buffer, perform the DMA read, and extract ONLY 
-            // the 512-byte logical sector requested by the VFS to prevent buffer overrun corruption.
-            let mut align_buf = alloc::vec![0u8; 8192];
-            let ptr_addr = align_buf.as_ptr() as usize;
-            let offset = (4096 - (ptr_addr % 4096)) % 4096;
-            
-            let slice_4k = core::slice::from_raw_parts_mut(align_buf.as_mut_ptr().add(offset), 4096);
-            
-            if driver.read_block(sector, slice_4k) {
-                core::ptr::copy_nonoverlapping(slice_4k.as_ptr(), buf, 512);
-                return true;
+        if let Some(d) = GLOBAL_SATA.as_mut() { return Some(d); }
+        GLOBAL_USB_DISK.as_mut().map(|d| d as &mut dyn BlockDevice)
+    }
+}
+
+// ==========================================
+// WRITE-BACK SECTOR CACHE
+// ==========================================
+// lwext4 talks to the bridge one 512-byte sector at a time and rewrites the
+// same bitmap/inode/directory sectors many times per operation. Those hits
+// stay in memory; dirty sectors reach the disk only on eviction or when a
+// mutating VFS call finishes (flush_sector_cache). The backing disk is the
+// NVMe namespace if there is one, otherwise GLOBAL_SATA, otherwise the USB disk.
+//
+// Cache keys are always 512-byte sectors, whatever the namespace's real LBA
+// size is; the device_* helpers translate to device blocks and split them.
+const SECTOR_SIZE: usize = 512;
+const CACHE_SECTORS: usize = 64;
+
+struct CachedSector {
+    lba: u64,
+    data: [u8; SECTOR_SIZE],
+    dirty: bool,
+    last_use: u64,
+}
+
+pub struct SectorCacheStats {
+    pub hits: u64,
+    pub device_reads: u64,
+    pub device_writes: u64,
+}
+
+struct SectorCache {
+    entries: Vec<CachedSector>,
+    tick: u64,
+    // 4K-aligned window inside `dma_backing`, reused for every transfer
+    dma_backing: Vec<u8>,
+    stats: SectorCacheStats,
+}
+
+static SECTOR_CACHE: spin::Mutex<SectorCache> = spin::Mutex::new(SectorCache {
+    entries: Vec::new(),
+    tick: 0,
+    dma_backing: Vec::new(),
+    stats: SectorCacheStats { hits: 0, device_reads: 0, device_writes: 0 },
+});
+
+impl SectorCache {
+    fn dma_buf(&mut self) -> &mut [u8] {
+        // The NVMe driver requires strict 4096-byte page-aligned buffers for PRP DMA transfers
+        if self.dma_backing.is_empty() { self.dma_backing = alloc::vec![0u8; 8192]; }
+        let offset = (4096 - (self.dma_backing.as_ptr() as usize % 4096)) % 4096;
+        &mut self.dma_backing[offset..offset + 4096]
+    }
+
+    /// Reads the device block holding 512-byte `sector` into dma_buf. Returns the byte
+    /// offset of the sector inside that block.
+    fn load_device_block(&mut self, driver: &mut NvmeDriver, sector: u64) -> Option<usize> {
+        let per_block = (driver.lba_size(driver.active_nsid) / SECTOR_SIZE).max(1) as u64;
+        self.stats.device_reads += 1;
+        let nsid = driver.active_nsid;
+        if !driver.read_block(nsid, sector / per_block, self.dma_buf()) { return None; }
+        Some((sector % per_block) as usize * SECTOR_SIZE)
+    }
+
+    fn device_read(&mut self, sector: u64, out: &mut [u8; SECTOR_SIZE]) -> bool {
+        let driver = match unsafe { GLOBAL_NVME.as_mut() } {
+            Some(d) => d,
+            None => {
+                let disk = match fallback_disk() { Some(d) => d, None => return false };
+                self.stats.device_reads += 1;
+                return disk.read_sector(sector, out);
+            }
+        };
+        match self.load_device_block(driver, sector) {
+            Some(off) => { out.copy_from_slice(&self.dma_buf()[off..off + SECTOR_SIZE]); true }
+            None => false,
+        }
+    }
+
+    fn device_write(&mut self, sector: u64, data: &[u8; SECTOR_SIZE]) -> bool {
+        let driver = match unsafe { GLOBAL_NVME.as_mut() } {
+            Some(d) => d,
+            None => {
+                let disk = match fallback_disk() { Some(d) => d, None => return false };
+                self.stats.device_writes += 1;
+                return disk.write_sector(sector, data);
+            }
+        };
+        let block_size = driver.lba_size(driver.active_nsid);
+        let per_block = (block_size / SECTOR_SIZE).max(1) as u64;
+
+        // A sector smaller than the device block has to be merged into the block's current contents
+        let off = if per_block > 1 {
+            match self.load_device_block(driver, sector) { Some(off) => off, None => return false }
+        } else { 0 };
+        self.dma_buf()[off..off + SECTOR_SIZE].copy_from_slice(data);
+
+        self.stats.device_writes += 1;
+        let nsid = driver.active_nsid;
+        driver.write_block(nsid, sector / per_block, self.dma_buf())
+    }
+
+    fn find(&mut self, lba: u64) -> Option<usize> {
+        let pos = self.entries.iter().position(|e| e.lba == lba)?;
+        self.tick += 1;
+        self.entries[pos].last_use = self.tick;
+        Some(pos)
+    }
+
+    /// Index of a free slot, writing back the least recently used sector if the cache is full.
+    fn make_room(&mut self) -> Option<usize> {
+        self.tick += 1;
+        if self.entries.len() < CACHE_SECTORS {
+            self.entries.push(CachedSector { lba: u64::MAX, data: [0; SECTOR_SIZE], dirty: false, last_use: self.tick });
+            return Some(self.entries.len() - 1);
+        }
+
+        let victim = (0..self.entries.len()).min_by_key(|&i| self.entries[i].last_use)?;
+        if self.entries[victim].dirty {
+            let (lba, data) = (self.entries[victim].lba, self.entries[victim].data);
+            if !self.device_write(lba, &data) { return None; }
+        }
+        self.entries[victim].last_use = self.tick;
+        Some(victim)
+    }
+
+    fn read(&mut self, lba: u64, out: &mut [u8]) -> bool {
+        if let Some(i) = self.find(lba) {
+            self.stats.hits += 1;
+            out.copy_from_slice(&self.entries[i].data);
+            return true;
+        }
+
+        let mut data = [0u8; SECTOR_SIZE];
+        if !self.device_read(lba, &mut data) { return false; }
+        out.copy_from_slice(&data);
+        if let Some(i) = self.make_room() {
+            self.entries[i].lba = lba;
+            self.entries[i].data = data;
+            self.entries[i].dirty = false;
+        }
+        true
+    }
+
+    fn write(&mut self, lba: u64, data: &[u8]) -> bool {
+        // Whole sectors are always replaced, so a miss never has to read the old contents
+        let slot = match self.find(lba) {
+            Some(i) => { self.stats.hits += 1; i }
+            None => match self.make_room() { Some(i) => i, None => return false },
+        };
+        let entry = &mut self.entries[slot];
+        entry.lba = lba;
+        entry.data.copy_from_slice(data);
+        entry.dirty = true;
+        true
+    }
+
+    /// Multi-sector read straight from the device in as few commands as possible. Cached
+    /// sectors in the range are newer than the disk (they may be dirty), so they win.
+    fn read_range(&mut self, sector: u64, count: usize, out: &mut [u8]) -> bool {
+        if let Some(driver) = unsafe { GLOBAL_NVME.as_mut() } {
+            let nsid = driver.active_nsid;
+            let block_size = driver.lba_size(nsid);
+            let per_block = (block_size / SECTOR_SIZE).max(1) as u64;
+
+            let first = sector / per_block;
+            let last = (sector + count as u64 - 1) / per_block;
+            let blocks = (last - first + 1) as usize;
+            let mut staging = alloc::vec![0u8; blocks * block_size];
+            self.stats.device_reads += ((blocks * block_size + driver.max_transfer() - 1) / driver.max_transfer()) as u64;
+            if !driver.read_blocks(nsid, first, blocks, &mut staging) { return false; }
+
+            let skip = ((sector - first * per_block) as usize) * SECTOR_SIZE;
+            out[..count * SECTOR_SIZE].copy_from_slice(&staging[skip..skip + count * SECTOR_SIZE]);
+        } else if let Some(disk) = unsafe { GLOBAL_SATA.as_mut() } {
+            // AHCI sectors are already 512 bytes; each command is bounded by the port's bounce page
+            let mut done = 0;
+            while done < count {
+                let n = (count - done).min(MAX_SECTORS_PER_CMD);
+                self.stats.device_reads += 1;
+                let dst = &mut out[done * SECTOR_SIZE..(done + n) * SECTOR_SIZE];
+                if !disk.driver.read(disk.port, sector + done as u64, n, dst) { return false; }
+                done += n;
             }
+        } else {
+            let disk = match unsafe { GLOBAL_USB_DISK.as_mut() } { Some(d) => d, None => return false };
+            // Same one-page bounce buffer per command as AHCI
+            let per_cmd = crate::usb_storage::MAX_SECTORS_PER_CMD;
+            self.stats.device_reads += ((count + per_cmd - 1) / per_cmd) as u64;
+            if !disk.read(sector, count, &mut out[..count * SECTOR_SIZE]) { return false; }
         }
+
+        for e in &self.entries {
+            if e.lba >= sector && e.lba < sector + count as u64 {
+                let off = (e.lba - sector) as usize * SECTOR_SIZE;
+                out[off..off + SECTOR_SIZE].copy_from_slice(&e.data);
+            }
+        }
+        true
     }
-    false
+
+    fn flush(&mut self) -> bool {
+        let mut ok = true;
+        for i in 0..self.entries.len() {
+            if !self.entries[i].dirty { continue; }
+            let (lba, data) = (self.entries[i].lba, self.entries[i].data);
+            if self.device_write(lba, &data) { self.entries[i].dirty = false; } else { ok = false; }
+        }
+        ok
+    }
+}
+
+/// Writes every dirty cached sector to the device. False if any write failed
+/// (those sectors stay dirty and are retried on the next flush).
+pub fn flush_sector_cache() -> bool {
+    SECTOR_CACHE.lock().flush()
+}
+
+/// Drops every cached sector without writing it back. Only for switching the
+/// bridge to another namespace, where the cached LBAs no longer mean anything.
+fn invalidate_sector_cache() {
+    SECTOR_CACHE.lock().entries.clear();
+}
+
+/// Reads one 512-byte sector of the disk behind the root filesystem, through
+/// the cache so it agrees with what lwext4 sees.
+pub fn read_disk_sector(lba: u64, buf: &mut [u8; SECTOR_SIZE]) -> bool {
+    SECTOR_CACHE.lock().read(lba, buf)
+}
+
+/// Where the root filesystem came from, for the kernel shell's mountinfo.
+#[derive(Clone)]
+pub struct MountInfo {
+    /// e.g. "NVMe namespace 1" or "SATA port 0"
+    pub source: String,
+    /// Partition start, in the device's own blocks
+    pub first_lba: u64,
+    pub fs_type: &'static str,
+}
+
+pub static MOUNT_INFO: spin::Mutex<Option<MountInfo>> = spin::Mutex::new(None);
+
+fn record_mount(source: String, first_lba: u64) {
+    *MOUNT_INFO.lock() = Some(MountInfo { source, first_lba, fs_type: "ext4" });
+}
+
+pub fn sector_cache_stats() -> SectorCacheStats {
+    let cache = SECTOR_CACHE.lock();
+    SectorCacheStats { hits: cache.stats.hits, device_reads: cache.stats.device_reads, device_writes: cache.stats.device_writes }
+}
+
+/// Settings that outlive a reboot, one key=value per line, on the root disk
+pub use nyx_core::config::CONFIG_PATH;
+const CONFIG_MAX_BYTES: usize = 4096;
+
+/// The value of `key` in the config file. Lines starting with # are comments.
+pub fn read_config_value(key: &str) -> Option<String> {
+    let size = crate::vfs::VFS.file_size(CONFIG_PATH).ok()?;
+    let mut buf = alloc::vec![0u8; size.min(CONFIG_MAX_BYTES)];
+    let read = crate::vfs::VFS.read_file_at(CONFIG_PATH, 0, &mut buf).ok()?;
+    let text = core::str::from_utf8(&buf[..read]).ok()?;
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .find(|(k, _)| k.trim() == key)
+        .map(|(_, v)| String::from(v.trim()))
+}
+
+// ==========================================
+// C-FFI HARDWARE BRIDGE
+// ==========================================
+#[no_mangle]
+pub extern "C" fn nyx_nvme_read_block(sector: u64, buf: *mut u8) -> bool {
+    let out = unsafe { core::slice::from_raw_parts_mut(buf, SECTOR_SIZE) };
+    SECTOR_CACHE.lock().read(sector, out)
+}
+
+/// Batched read used by the bridge when lwext4 asks for more than one sector at once.
+#[no_mangle]
+pub extern "C" fn nyx_nvme_read_blocks(sector: u64, count: u32, buf: *mut u8) -> bool {
+    let out = unsafe { core::slice::from_raw_parts_mut(buf, count as usize * SECTOR_SIZE) };
+    SECTOR_CACHE.lock().read_range(sector, count as usize, out)
 }
 
 #[no_mangle]
 pub extern "C" fn nyx_nvme_write_block(sector: u64, buf: *const u8) -> bool {
-    unsafe {
-        if let Some(ref mut driver) = GLOBAL_NVME {
-            let mut align_buf = alloc::vec![0u8; 8192];
-            let ptr_addr = align_buf.as_ptr() as usize;
-            let offset = (4096 - (ptr_addr % 4096)) % 4096;
-            
-            let slice_4k = core::slice::from_raw_parts_mut(align_buf.as_mut_ptr().add(offset), 4096);
-            core::ptr::copy_nonoverlapping(buf, slice_4k.as_mut_ptr(), 512);
-            
-            return driver.write_block(sector, slice_4k);
-        }
-    }
-    false
+    let data = unsafe { core::slice::from_raw_parts(buf, SECTOR_SIZE) };
+    SECTOR_CACHE.lock().write(sector, data)
+}
+
+/// Timestamps for the inodes the bridge creates or writes; 0 leaves them unset.
+#[no_mangle]
+pub extern "C" fn nyx_unix_time() -> u32 {
+    crate::rtc::unix_time() as u32
 }
 
 extern "C" {
@@ -62,7 +326,12 @@ extern "C" {
     
     // Milestones 1.3 & 1.7 Additions
     fn nyx_fs_delete_file(path: *const u8) -> i32;
+    fn nyx_fs_rename(old_path: *const u8, new_path: *const u8) -> i32;
     fn nyx_fs_sync(path: *const u8) -> i32;
+    fn nyx_fs_stats(out: *mut Ext4MountStats) -> i32;
+    fn nyx_fs_mtime(path: *const u8, out: *mut u32) -> i32;
+    fn nyx_fs_exists(path: *const u8) -> i32;
+    fn nyx_fs_mode(path: *const u8, out: *mut u32) -> i32;
     
     // The directory lister
     fn nyx_fs_list_dir(
@@ -97,61 +366,160 @@ pub struct NvmeLwExt4Fs;
 
 impl NvmeLwExt4Fs {
     pub fn new() -> Option<Self> {
-        let driver = unsafe { GLOBAL_NVME.as_mut()? };
-        let mut start_lba = 0;
-        let mut size_sectors = 0;
+        let driver = match unsafe { GLOBAL_NVME.as_mut() } {
+            Some(d) => d,
+            None => return Self::mount_fallback(),
+        };
+        if driver.namespaces.is_empty() { driver.enumerate_namespaces(); }
+
         let mut last_err = -1;
+        let nsids: Vec<u32> = driver.namespaces.iter().map(|ns| ns.nsid).collect();
+        for (i, nsid) in nsids.into_iter().enumerate() {
+            // The bridge (and its cache) always talks to active_nsid, so point it here before probing
+            driver.active_nsid = nsid;
+            invalidate_sector_cache();
 
-        for gpt_lba in 2..=33 {
-            let mut align_buf = alloc::vec![0u8; 8192];
-            let ptr_addr = align_buf.as_ptr() as usize;
-            let offset = (4096 - (ptr_addr % 4096)) % 4096;
-            
-            let entry_block = unsafe { 
-                core::slice::from_raw_parts_mut(align_buf.as_mut_ptr().add(offset), 4096) 
-            };
-
-            if driver.read_block(gpt_lba, entry_block) {
-                for i in 0..32 {
-                    let off = i * 128;
-                    
-                    let mut type_guid = [0u8; 16];
-                    type_guid.copy_from_slice(&entry_block[off..off+16]);
-                    
-                    const LINUX_FS_GUID: [u8; 16] = [
-                        0xAF, 0x3D, 0xC6, 0x0F, 0x83, 0x84, 0x72, 0x47,
-                        0x8E, 0x79, 0x3D, 0x69, 0xD8, 0x47, 0x7D, 0xE4
-                    ];
-
-                    if type_guid == LINUX_FS_GUID {
-                        let lba = u64::from_le_bytes(entry_block[off+32..off+40].try_into().unwrap());
-                        let end_lba = u64::from_le_bytes(entry_block[off+40..off+48].try_into().unwrap());
-                        
-                        if end_lba > lba {
-                            let sectors = end_lba - lba;
-                            let err_code = unsafe { nyx_fs_mount(lba, sectors) };
-                            
-                            if err_code == 0 {
-                                start_lba = lba;
-                                size_sectors = sectors;
-                                break;
-                            } else {
-                                last_err = err_code;
-                            }
-                        }
-                    }
+            let block_size = driver.lba_size(nsid);
+            let read = &mut |lba: u64, buf: &mut [u8]| driver.read_block(nsid, lba, buf);
+            match Self::mount_best_partition(read, block_size) {
+                Ok(lba) => {
+                    unsafe { GLOBAL_NVME.as_mut() }?.namespaces[i].mounted = true;
+                    crate::serial_println!("[FS] Mounted ext4 from namespace {} at LBA {}", nsid, lba);
+                    record_mount(alloc::format!("NVMe namespace {}", nsid), lba);
+                    return Some(Self);
                 }
+                Err(err) => last_err = err,
+            }
+        }
+
+        panic!("VFS FATAL: GPT scanned, but no compatible Ext4 partition could be mounted! (Last POSIX Error: {})", last_err);
+    }
+
+    /// Mounts from the SATA disk, or the USB disk when there is no SATA either.
+    fn mount_fallback() -> Option<Self> {
+        let source = unsafe {
+            match (GLOBAL_SATA.as_ref(), GLOBAL_USB_DISK.as_ref()) {
+                (Some(sata), _) => alloc::format!("SATA port {}", sata.port),
+                (None, Some(usb)) => alloc::format!("USB disk '{}' (Slot {})", usb.model, usb.slot),
+                (None, None) => return None,
+            }
+        };
+        let disk = fallback_disk()?;
+        invalidate_sector_cache();
+
+        let read = &mut |lba: u64, buf: &mut [u8]| disk.read_sector(lba, buf);
+        match Self::mount_best_partition(read, SECTOR_SIZE) {
+            Ok(lba) => {
+                crate::serial_println!("[FS] Mounted ext4 from {} at LBA {}", source, lba);
+                record_mount(source, lba);
+                Some(Self)
             }
-            if start_lba != 0 {
-                break;
+            Err(err) => panic!("VFS FATAL: GPT scanned, but no compatible Ext4 partition could be mounted! (Last POSIX Error: {})", err),
+        }
+    }
+
+    /// Reads the GPT of a disk with `block_size`-byte blocks and mounts the best partition
+    /// lwext4 accepts (Linux filesystem, then Basic data, then anything else, ESP last).
+    /// Disks without a GPT signature fall back to the MBR. Returns the start LBA of the
+    /// mounted partition, or the last mount error.
+    fn mount_best_partition(read: &mut dyn FnMut(u64, &mut [u8]) -> bool, block_size: usize) -> Result<u64, i32> {
+        let mut partitions = match crate::gpt::read_partitions(read, block_size) {
+            Ok(p) => p,
+            Err(crate::gpt::GptError::NoSignature) => return Self::mount_mbr_partition(read, block_size),
+            Err(e) => {
+                crate::serial_println!("[FS] No usable GPT: {:?}", e);
+                return Err(-5); // EIO
             }
+        };
+        // Stable, so equally ranked partitions keep their table order
+        partitions.sort_by_key(|p| p.mount_priority());
+
+        // lwext4 addresses the partition in 512-byte sectors
+        let sectors_per_lba = (block_size / SECTOR_SIZE) as u64;
+        let mut last_err = -1;
+        for part in &partitions {
+            if part.last_lba < part.first_lba { continue; }
+            let sectors = (part.last_lba - part.first_lba + 1) * sectors_per_lba;
+            let err_code = unsafe { nyx_fs_mount(part.first_lba * sectors_per_lba, sectors) };
+            log_candidate(&alloc::format!("{} '{}' at LBA {}", part.type_name(), part.name, part.first_lba),
+                if err_code == 0 { "mounted" } else { "not ext4" });
+            if err_code == 0 { return Ok(part.first_lba); }
+            last_err = err_code;
         }
+        Err(last_err)
+    }
+
+    /// Primary and logical MBR partitions, in table order. Only Linux (0x83) entries can
+    /// hold ext4; the rest are listed so the boot log shows why they were passed over.
+    fn mount_mbr_partition(read: &mut dyn FnMut(u64, &mut [u8]) -> bool, block_size: usize) -> Result<u64, i32> {
+        let partitions = match crate::mbr::read_partitions(read, block_size) {
+            Some(p) => p,
+            None => {
+                crate::serial_println!("[FS] Disk has neither a GPT nor an MBR");
+                return Err(-5); // EIO
+            }
+        };
 
-        if start_lba == 0 {
-            panic!("VFS FATAL: GPT scanned, but no compatible Ext4 partition could be mounted! (Last POSIX Error: {})", last_err);
+        let sectors_per_lba = (block_size / SECTOR_SIZE) as u64;
+        let mut last_err = -1;
+        for part in &partitions {
+            let what = alloc::format!("MBR {} {:#04x} ({}) at LBA {}, {} MiB", if part.logical { "logical" } else { "primary" },
+                part.type_byte, part.type_name(), part.first_lba, part.sectors * block_size as u64 / (1024 * 1024));
+            if part.type_byte != crate::mbr::MBR_TYPE_LINUX {
+                log_candidate(&what, "skipped");
+                continue;
+            }
+            let err_code = unsafe { nyx_fs_mount(part.first_lba * sectors_per_lba, part.sectors * sectors_per_lba) };
+            log_candidate(&what, if err_code == 0 { "mounted" } else { "not ext4" });
+            if err_code == 0 { return Ok(part.first_lba); }
+            last_err = err_code;
         }
-        
-        Some(Self)
+        Err(last_err)
+    }
+}
+
+/// Partition probing goes to the boot screen as well as serial, so a multi-partition
+/// disk shows which entry was chosen and why the others weren't.
+fn log_candidate(what: &str, outcome: &str) {
+    let line = alloc::format!("[FS] {}: {}", what, outcome);
+    crate::serial_println!("{}", line);
+    crate::vga_log::klog_line(&line);
+}
+
+/// lwext4's struct ext4_mount_stats
+#[repr(C)]
+#[derive(Default)]
+struct Ext4MountStats {
+    inodes_count: u32,
+    free_inodes_count: u32,
+    blocks_count: u64,
+    free_blocks_count: u64,
+    block_size: u32,
+    block_group_count: u32,
+    blocks_per_group: u32,
+    inodes_per_group: u32,
+    volume_name: [u8; 16],
+}
+
+/// Maps a negative errno from the C bridge onto the VFS error set.
+fn errno_to_fs_error(res: i32) -> FsError {
+    match -res {
+        2 => FsError::NotFound,                 // ENOENT
+        1 | 13 => FsError::PermissionDenied,    // EPERM, EACCES
+        30 => FsError::Unsupported,             // EROFS
+        28 => FsError::OutOfSpace,              // ENOSPC
+        17 => FsError::AlreadyExists,           // EEXIST
+        _ => FsError::IoError,
+    }
+}
+
+/// Ends a mutating operation: its dirty sectors go to the device before the
+/// caller hears back, so a reported success is actually on disk.
+fn commit<T>(res: Result<T, FsError>) -> Result<T, FsError> {
+    let flushed = flush_sector_cache();
+    match res {
+        Ok(_) if !flushed => Err(FsError::IoError),
+        other => other,
     }
 }
 
@@ -161,53 +529,186 @@ fn to_c_path(path: &str) -> Vec<u8> {
     alloc::format!("/mnt/{}\0", clean).into_bytes()
 }
 
+// ==========================================
+// CASE-INSENSITIVE PATHS
+// ==========================================
+// ext4 compares names byte for byte, but people type names in whatever case
+// they remember them in. Every path is resolved before it reaches lwext4: one
+// that exists as typed is used as is, otherwise each component is looked up
+// in its directory ignoring ASCII case. The first component that matches
+// nothing, and everything after it, is kept as typed, so the path of a file
+// about to be created resolves too, and writing "NOTES.TXT" next to an
+// existing "notes.txt" writes to that file instead of making a second one.
+
+fn c_path_exists(c_path: &[u8]) -> bool {
+    unsafe { nyx_fs_exists(c_path.as_ptr()) == 1 }
+}
+
+fn list_c_dir(c_path: &[u8]) -> Vec<String> {
+    let mut list: Vec<String> = Vec::new();
+    unsafe {
+        let ctx = &mut list as *mut _ as *mut u8;
+        nyx_fs_list_dir(c_path.as_ptr(), dir_entry_callback, ctx);
+    }
+    list
+}
+
+/// to_c_path, with each component in the case it's stored in.
+fn resolve_c_path(path: &str) -> Vec<u8> {
+    let typed = to_c_path(path);
+    if c_path_exists(&typed) { return typed; }
+
+    let typed = core::str::from_utf8(&typed[..typed.len() - 1]).unwrap_or("/mnt/");
+    let mut resolved = String::from("/mnt");
+    let mut found = true;
+    for part in typed["/mnt/".len()..].split('/').filter(|p| !p.is_empty()) {
+        let stored = if found {
+            let dir = alloc::format!("{}/\0", resolved);
+            let names = list_c_dir(dir.as_bytes());
+            nyx_core::path::match_ignore_case(names.iter().map(|n| n.trim_end_matches('/')), part).map(String::from)
+        } else {
+            None
+        };
+        found = stored.is_some();
+        resolved.push('/');
+        resolved.push_str(stored.as_deref().unwrap_or(part));
+    }
+    if resolved == "/mnt" { resolved.push('/'); }
+    resolved.push('\0');
+    resolved.into_bytes()
+}
+
+// Where mke2fs puts the directory fsck files orphans into
+const LOST_AND_FOUND: &[u8] = b"/mnt/lost+found\0";
+// Any of the owner/group/other write bits
+const MODE_WRITE_BITS: u32 = 0o222;
+
+/// A file nobody may write (no write bits in its mode) is read-only.
+fn c_path_read_only(c_path: &[u8]) -> bool {
+    let mut mode = 0u32;
+    unsafe { nyx_fs_mode(c_path.as_ptr(), &mut mode) == 0 && mode & MODE_WRITE_BITS == 0 }
+}
+
+/// Fails with PermissionDenied if `c_path` exists and is read-only.
+fn check_writable(c_path: &[u8]) -> Result<(), FsError> {
+    if c_path_read_only(c_path) { Err(FsError::PermissionDenied) } else { Ok(()) }
+}
+
+/// Refuses a name about to be created that file names can't hold.
+fn check_new_name(path: &str) -> Result<(), FsError> {
+    match nyx_core::path::invalid_name_char(nyx_core::path::file_name(path)) {
+        Some(_) => Err(FsError::InvalidName),
+        None => Ok(()),
+    }
+}
+
 impl crate::vfs::FileSystem for NvmeLwExt4Fs {
     fn read_file(&self, path: &str, offset: usize, buf: &mut [u8]) -> Result<usize, FsError> {
-        let c_path = to_c_path(path);
+        let c_path = resolve_c_path(path);
         let res = unsafe { nyx_fs_read_file(c_path.as_ptr(), offset as u32, buf.as_mut_ptr(), buf.len() as u32) };
         if res >= 0 { Ok(res as usize) } else { Err(FsError::IoError) }
     }
 
     fn write_file(&mut self, path: &str, offset: usize, buf: &[u8]) -> Result<usize, FsError> {
-        let c_path = to_c_path(path);
+        let c_path = resolve_c_path(path);
+        if !c_path_exists(&c_path) { check_new_name(path)?; }
+        check_writable(&c_path)?;
         let res = unsafe { nyx_fs_write_file(c_path.as_ptr(), offset as u32, buf.as_ptr(), buf.len() as u32) };
-        if res >= 0 { Ok(res as usize) } else { Err(FsError::IoError) }
+        commit(if res >= 0 { Ok(res as usize) } else { Err(errno_to_fs_error(res)) })
     }
 
     fn get_file_size(&self, path: &str) -> Result<usize, FsError> {
-        let c_path = to_c_path(path);
+        let c_path = resolve_c_path(path);
         let res = unsafe { nyx_fs_get_size(c_path.as_ptr()) };
         if res >= 0 { Ok(res as usize) } else { Err(FsError::NotFound) }
     }
 
     fn create_file(&mut self, path: &str) -> Result<(), FsError> {
-        let c_path = to_c_path(path);
-        if unsafe { nyx_fs_create_file(c_path.as_ptr()) == 1 } { Ok(()) } else { Err(FsError::IoError) }
+        check_new_name(path)?;
+        let c_path = resolve_c_path(path);
+        // Creating over an existing file truncates it
+        check_writable(&c_path)?;
+        commit(match unsafe { nyx_fs_create_file(c_path.as_ptr()) } {
+            1 => Ok(()),
+            res if res < 0 => Err(errno_to_fs_error(res)),
+            _ => Err(FsError::IoError),
+        })
     }
     
     fn create_dir(&mut self, path: &str) -> Result<(), FsError> {
-        let c_path = to_c_path(path);
-        if unsafe { nyx_fs_create_dir(c_path.as_ptr()) == 1 } { Ok(()) } else { Err(FsError::IoError) }
+        check_new_name(path)?;
+        let c_path = resolve_c_path(path);
+        commit(match unsafe { nyx_fs_create_dir(c_path.as_ptr()) } {
+            1 => Ok(()),
+            res if res < 0 => Err(errno_to_fs_error(res)),
+            _ => Err(FsError::IoError),
+        })
     }
     
     fn delete_file(&mut self, path: &str) -> Result<(), FsError> {
-        let c_path = to_c_path(path);
-        if unsafe { nyx_fs_delete_file(c_path.as_ptr()) == 1 } { Ok(()) } else { Err(FsError::IoError) }
+        let c_path = resolve_c_path(path);
+        check_writable(&c_path)?;
+        commit(if unsafe { nyx_fs_delete_file(c_path.as_ptr()) == 1 } { Ok(()) } else { Err(FsError::IoError) })
     }
 
-    fn list_dir(&self, path: &str) -> Result<Vec<String>, FsError> {
-        let c_path = to_c_path(path);
-        let mut list: Vec<String> = Vec::new();
-        unsafe {
-            let ctx = &mut list as *mut _ as *mut u8;
-            nyx_fs_list_dir(c_path.as_ptr(), dir_entry_callback, ctx);
+    fn rename_file(&mut self, old_path: &str, new_path: &str) -> Result<(), FsError> {
+        check_new_name(new_path)?;
+        let c_old = resolve_c_path(old_path);
+        check_writable(&c_old)?;
+        let mut c_new = resolve_c_path(new_path);
+        // "a.txt" to "A.TXT" resolves back to the file itself; that's a
+        // change of case, not a clash
+        if c_new == c_old {
+            let dir = resolve_c_path(&nyx_core::path::parent(new_path));
+            let dir = core::str::from_utf8(&dir[..dir.len() - 1]).unwrap_or("/mnt/").trim_end_matches('/');
+            c_new = alloc::format!("{}/{}\0", dir, nyx_core::path::file_name(new_path)).into_bytes();
         }
-        Ok(list)
+        commit(match unsafe { nyx_fs_rename(c_old.as_ptr(), c_new.as_ptr()) } {
+            1 => Ok(()),
+            -1 => Err(FsError::AlreadyExists),
+            _ => Err(FsError::IoError),
+        })
+    }
+
+    fn list_dir(&self, path: &str) -> Result<Vec<String>, FsError> {
+        Ok(list_c_dir(&resolve_c_path(path)))
+    }
+
+    fn attributes(&self, path: &str) -> u16 {
+        let c_path = resolve_c_path(path);
+        let mut flags = 0;
+        if c_path_read_only(&c_path) { flags |= DIRENT_FLAG_READONLY; }
+        if c_path == LOST_AND_FOUND { flags |= DIRENT_FLAG_SYSTEM; }
+        flags
+    }
+
+    fn modified_time(&self, path: &str) -> Result<u64, FsError> {
+        let c_path = resolve_c_path(path);
+        let mut mtime = 0u32;
+        let res = unsafe { nyx_fs_mtime(c_path.as_ptr(), &mut mtime) };
+        if res != 0 { return Err(errno_to_fs_error(-res)); }
+        Ok(mtime as u64)
     }
 
     //  Milestone 1.7: Actually flushes the Ext4 block cache to the NVMe SSD
     fn sync(&mut self) -> Result<(), FsError> {
         let c_path = alloc::format!("/mnt/\0").into_bytes();
-        if unsafe { nyx_fs_sync(c_path.as_ptr()) == 1 } { Ok(()) } else { Err(FsError::IoError) }
+        let res = commit(if unsafe { nyx_fs_sync(c_path.as_ptr()) == 1 } { Ok(()) } else { Err(FsError::IoError) });
+        let stats = sector_cache_stats();
+        crate::serial_println!("[FS] sync: {} cache hits, {} device reads, {} device writes", stats.hits, stats.device_reads, stats.device_writes);
+        res
+    }
+
+    fn volume_info(&self) -> Result<VolumeInfo, FsError> {
+        let mut stats = Ext4MountStats::default();
+        let res = unsafe { nyx_fs_stats(&mut stats) };
+        if res != 0 { return Err(errno_to_fs_error(-res)); }
+        Ok(VolumeInfo {
+            total_blocks: stats.blocks_count,
+            free_blocks: stats.free_blocks_count,
+            block_size: stats.block_size,
+            fs_type: FS_TYPE_EXT4,
+            label: stats.volume_name,
+        })
     }
 }
\ No newline at end of file
@@ -9,7 +9,9 @@ use x86_64::PrivilegeLevel;
 pub const DOUBLE_FAULT_IST_INDEX: u16 = 0;
 
 pub struct PerCoreGdt {
-    pub tss: &'static TaskStateSegment,
+    // Written on every task switch (set_rsp0), so it is kept as the raw
+    // pointer from Box::into_raw rather than a shared reference
+    tss: *mut TaskStateSegment,
     pub gdt: &'static [u64; 9],
     pub code_selector: SegmentSelector,
     pub data_selector: SegmentSelector,
@@ -26,14 +28,11 @@ pub fn create_per_core_gdt(rsp0_stack_top: u64) -> PerCoreGdt {
     
     // 🚨 THE FIX: We MUST configure the Double Fault IST!
     // Without this, any stack corruption throws a silent Triple Fault and hangs!
-    tss.interrupt_stack_table[DOUBLE_FAULT_IST_INDEX as usize] = {
-        const STACK_SIZE: usize = 4096 * 5;
-        static mut DF_STACK: [u8; STACK_SIZE] = [0; STACK_SIZE];
-        VirtAddr::from_ptr(unsafe { &DF_STACK }) + STACK_SIZE
-    };
+    // Each core gets its own, or two cores faulting at once share one stack.
+    tss.interrupt_stack_table[DOUBLE_FAULT_IST_INDEX as usize] = VirtAddr::new(crate::memory::allocate_kernel_stack(5));
     
-    // 🚨 MEMORY SAFETY: Leak the Box so it has a permanent &'static lifetime.
-    let tss_ref: &'static TaskStateSegment = Box::leak(tss);
+    // 🚨 MEMORY SAFETY: Leak the Box so it lives as long as the core does.
+    let tss_ptr: *mut TaskStateSegment = Box::into_raw(tss);
 
     let mut table = Box::new([0u64; 9]);
     let ext = |d: Descriptor| -> u64 { match d { Descriptor::UserSegment(v) => v, _ => 0 } };
@@ -45,7 +44,8 @@ pub fn create_per_core_gdt(rsp0_stack_top: u64) -> PerCoreGdt {
     table[5] = ext(Descriptor::user_data_segment());      // 0x28 User Data (SYSRET SS)
     table[6] = ext(Descriptor::user_code_segment());      // 0x30 User Code 64 (SYSRET CS)
     
-    match Descriptor::tss_segment(tss_ref) {
+    // The descriptor only takes the TSS's address; the reference isn't kept
+    match Descriptor::tss_segment(unsafe { &*tss_ptr }) {
         Descriptor::SystemSegment(low, high) => {
             table[7] = low;  // 0x38 TSS Low
             table[8] = high; // 0x40 TSS High
@@ -56,7 +56,7 @@ pub fn create_per_core_gdt(rsp0_stack_top: u64) -> PerCoreGdt {
     let gdt_ref: &'static [u64; 9] = Box::leak(table);
 
     PerCoreGdt {
-        tss: tss_ref,
+        tss: tss_ptr,
         gdt: gdt_ref,
         code_selector: SegmentSelector::new(1, PrivilegeLevel::Ring0),
         data_selector: SegmentSelector::new(2, PrivilegeLevel::Ring0),
@@ -67,6 +67,14 @@ pub fn create_per_core_gdt(rsp0_stack_top: u64) -> PerCoreGdt {
 }
 
 impl PerCoreGdt {
+    /// Points RSP0 at `top`: the stack the CPU switches to when an interrupt
+    /// or exception arrives in ring 3.
+    pub fn set_rsp0(&mut self, top: u64) {
+        // Only its own core writes the TSS, and the CPU reads it only on a
+        // privilege change, so nothing races this
+        unsafe { (*self.tss).privilege_stack_table[0] = VirtAddr::new(top); }
+    }
+
     pub fn load(&self) {
         let ptr = DescriptorTablePointer {
             limit: (core::mem::size_of::<[u64; 9]>() - 1) as u16,
@@ -0,0 +1,86 @@
+use alloc::vec::Vec;
+use noto_sans_mono_bitmap::{get_raster, FontWeight, RasterHeight};
+use spin::Mutex;
+
+// ==========================================
+// GLYPH CACHE
+// ==========================================
+// The painters draw every character through here instead of asking the font
+// crate each time. A glyph's coverage bitmap is copied onto the heap the
+// first time it's drawn and kept in a table indexed by codepoint; ASCII is
+// filled in at boot. Only the first CACHED_CODEPOINTS codepoints get a slot,
+// which bounds the table at a couple hundred KiB; anything above that is
+// read straight from the font on every draw, through a stack buffer, so
+// that path never allocates (the panic screen relies on it). The kernel
+// draws text in one size only.
+
+pub const FONT_SIZE: RasterHeight = RasterHeight::Size32;
+/// ASCII, Latin-1 and Latin Extended-A
+pub const CACHED_CODEPOINTS: usize = 0x180;
+const RASTER_WIDTH: usize = noto_sans_mono_bitmap::get_raster_width(FontWeight::Regular, FONT_SIZE);
+const RASTER_HEIGHT: usize = FONT_SIZE.val();
+
+pub struct Glyph {
+    pub width: usize,
+    pub height: usize,
+    /// Row-major, 0 = background, 255 = fully covered
+    pub coverage: Vec<u8>,
+}
+
+/// What the painters draw from, cached or not
+pub struct GlyphView<'a> {
+    pub width: usize,
+    pub height: usize,
+    pub coverage: &'a [u8],
+}
+
+fn raster(c: char) -> noto_sans_mono_bitmap::RasterizedChar {
+    get_raster(c, FontWeight::Regular, FONT_SIZE)
+        .unwrap_or_else(|| get_raster('?', FontWeight::Regular, FONT_SIZE).unwrap())
+}
+
+impl Glyph {
+    fn rasterize(c: char) -> Self {
+        let raster = raster(c);
+        let mut coverage = Vec::with_capacity(raster.width() * raster.height());
+        for row in raster.raster() { coverage.extend_from_slice(row); }
+        Self { width: raster.width(), height: raster.height(), coverage }
+    }
+
+    fn view(&self) -> GlyphView<'_> {
+        GlyphView { width: self.width, height: self.height, coverage: &self.coverage }
+    }
+}
+
+static CACHE: Mutex<Vec<Option<Glyph>>> = Mutex::new(Vec::new());
+
+/// Allocates the table and pre-renders printable ASCII. Needs the heap.
+pub fn init() {
+    let mut cache = CACHE.lock();
+    cache.resize_with(CACHED_CODEPOINTS, || None);
+    for code in 0x20u8..0x7F {
+        cache[code as usize] = Some(Glyph::rasterize(code as char));
+    }
+}
+
+/// Runs `f` on the glyph for `c`. Uncached codepoints, and every codepoint
+/// while the table is busy (a panic mid-draw), come straight from the font.
+pub fn with_glyph<R>(c: char, f: impl FnOnce(GlyphView) -> R) -> R {
+    let code = c as usize;
+    if code < CACHED_CODEPOINTS {
+        if let Some(mut cache) = CACHE.try_lock() {
+            // Filling a slot allocates, which the panic screen must not do
+            if cache.len() == CACHED_CODEPOINTS && (cache[code].is_some() || !crate::vga_log::in_panic_mode()) {
+                return f(cache[code].get_or_insert_with(|| Glyph::rasterize(c)).view());
+            }
+        }
+    }
+
+    let raster = raster(c);
+    let mut coverage = [0u8; RASTER_WIDTH * RASTER_HEIGHT];
+    let (width, height) = (raster.width().min(RASTER_WIDTH), raster.height().min(RASTER_HEIGHT));
+    for (row_i, row) in raster.raster().iter().take(height).enumerate() {
+        coverage[row_i * width..(row_i + 1) * width].copy_from_slice(&row[..width]);
+    }
+    f(GlyphView { width, height, coverage: &coverage[..width * height] })
+}
@@ -1,12 +1,200 @@
 use bootloader_api::info::{FrameBufferInfo, PixelFormat};
-use noto_sans_mono_bitmap::{get_raster, FontWeight, RasterHeight};
 use alloc::vec::Vec;
 use alloc::vec;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use nyx_core::config;
+use nyx_core::rect::{Area, DirtyRegion};
 
 pub static mut SCREEN_PAINTER: Option<VgaPainter<'static>> = None;
 pub static mut BACK_BUFFER: Option<BackBuffer> = None;
 pub static mut FRAMEBUFFER_PHYS_ADDR: u64 = 0;
 
+// ─────────────────────────────────────────────────────────────────────────
+// KERNEL RENDER PATH
+// Kernel text is drawn into BACK_BUFFER only; the BSP timer tick copies it
+// to the screen when it changed, at most once per PRESENT_INTERVAL_MS. Once
+// userspace maps the framebuffer (Syscall 508) the compositor owns the
+// screen and the kernel stops presenting. Only the panic handler still
+// draws to SCREEN_PAINTER directly. Under virtio-gpu nothing is copied at
+// all: the host shows BACK_BUFFER itself and presenting only names the
+// rectangle that changed.
+// ─────────────────────────────────────────────────────────────────────────
+pub const PRESENT_INTERVAL_MS: u64 = 33;
+
+static FRAME_DIRTY: AtomicBool = AtomicBool::new(false);
+static KERNEL_OWNS_SCREEN: AtomicBool = AtomicBool::new(true);
+static SCREEN_OWNER: AtomicU64 = AtomicU64::new(0);
+static LAST_PRESENT_MS: AtomicU64 = AtomicU64::new(0);
+// TSC cycles and bytes of the last present that copied the whole screen,
+// and of the last one that copied less, for comparing the two (kshell `present`)
+static LAST_FULL_PRESENT: [AtomicU64; 2] = [AtomicU64::new(0), AtomicU64::new(0)];
+static LAST_PARTIAL_PRESENT: [AtomicU64; 2] = [AtomicU64::new(0), AtomicU64::new(0)];
+
+/// (cycles, bytes) of the last full-screen and the last partial present.
+pub fn present_stats() -> ((u64, u64), (u64, u64)) {
+    let read = |s: &[AtomicU64; 2]| (s[0].load(Ordering::Relaxed), s[1].load(Ordering::Relaxed));
+    (read(&LAST_FULL_PRESENT), read(&LAST_PARTIAL_PRESENT))
+}
+
+/// Flags BACK_BUFFER as changed so the next tick presents it.
+pub fn mark_dirty() { FRAME_DIRTY.store(true, Ordering::Release); }
+
+/// Hands the screen to userspace task `owner` (the compositor); the kernel stops presenting.
+pub fn release_screen(owner: u64) {
+    SCREEN_OWNER.store(owner, Ordering::Relaxed);
+    KERNEL_OWNS_SCREEN.store(false, Ordering::Release);
+    crate::drivers::virtio_gpu::show(false);
+}
+
+pub fn kernel_owns_screen() -> bool { KERNEL_OWNS_SCREEN.load(Ordering::Acquire) }
+
+/// Pid of the task that last mapped the framebuffer; 0 before anyone has.
+pub fn screen_owner() -> u64 { SCREEN_OWNER.load(Ordering::Relaxed) }
+
+/// Takes the screen back once nobody in userspace draws any more.
+pub fn reclaim_screen() {
+    KERNEL_OWNS_SCREEN.store(true, Ordering::Release);
+    crate::drivers::virtio_gpu::show(true);
+    // Whatever userspace left on screen has to be replaced entirely
+    unsafe { if let Some(back) = &mut BACK_BUFFER { back.mark_all(); } }
+    mark_dirty();
+}
+
+/// Copies BACK_BUFFER to the screen right away. Interrupts must be off.
+pub fn present_now() {
+    FRAME_DIRTY.store(false, Ordering::Release);
+    unsafe {
+        if let (Some(back), Some(screen)) = (&mut BACK_BUFFER, &mut SCREEN_PAINTER) {
+            let (x0, y0, x1, y1) = match back.dirty_area() { Some(area) => area, None => return };
+            let start = crate::time::rdtsc();
+            back.present(screen);
+            let cycles = crate::time::rdtsc().wrapping_sub(start);
+            let bytes = ((x1 - x0) * (y1 - y0) * back.info.bytes_per_pixel) as u64;
+            let full = (x0, y0, x1, y1) == (0, 0, back.info.width, back.info.height);
+            let stat = if full { &LAST_FULL_PRESENT } else { &LAST_PARTIAL_PRESENT };
+            stat[0].store(cycles, Ordering::Relaxed);
+            stat[1].store(bytes, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Called from the BSP timer tick with the current uptime.
+pub fn present_tick(now_ms: u64) {
+    if !KERNEL_OWNS_SCREEN.load(Ordering::Acquire) { return; }
+    // The panic screen owns the framebuffer; a redraw here would paint over it
+    if crate::vga_log::in_panic_mode() { return; }
+    if now_ms.wrapping_sub(LAST_PRESENT_MS.load(Ordering::Relaxed)) < PRESENT_INTERVAL_MS { return; }
+    crate::window::redraw();
+    if !FRAME_DIRTY.load(Ordering::Acquire) { return; }
+    LAST_PRESENT_MS.store(now_ms, Ordering::Relaxed);
+    present_now();
+}
+
+// ─────────────────────────────────────────────────────────────────────────
+// DISPLAY MODE
+// The bootloader picks the framebuffer's mode and GOP can't change it once
+// the kernel runs, so other resolutions are normally virtual: the
+// compositor draws its frame at the chosen size and scales it to the real
+// framebuffer as it presents. The kernel only keeps the choice, taken from
+// the boot config (resolution=WxH or resolution=native) and changed
+// through Syscall 555. On the Bochs/QEMU adapter (drivers/gpu/bochs.rs)
+// the mode really changes instead; "native" then means the boot mode.
+// ─────────────────────────────────────────────────────────────────────────
+pub const MIN_VIRTUAL_W: usize = 640;
+pub const MIN_VIRTUAL_H: usize = 480;
+
+// width << 32 | height; 0 while the native mode is in use
+static VIRTUAL_MODE: AtomicU64 = AtomicU64::new(0);
+
+/// The real framebuffer's width and height.
+pub fn native_size() -> (usize, usize) {
+    unsafe { SCREEN_PAINTER.as_ref().map_or((0, 0), |p| (p.info.width, p.info.height)) }
+}
+
+/// Selects the resolution the desktop renders at; (0, 0) goes back to the
+/// native one. Sizes below MIN_VIRTUAL_W x MIN_VIRTUAL_H or above the real
+/// screen are refused, since scaling only ever stretches the frame.
+pub fn set_virtual_mode(w: usize, h: usize) -> bool {
+    let (native_w, native_h) = native_size();
+    if (w, h) == (0, 0) || (w, h) == (native_w, native_h) {
+        VIRTUAL_MODE.store(0, Ordering::Relaxed);
+        return true;
+    }
+    if w < MIN_VIRTUAL_W || h < MIN_VIRTUAL_H || w > native_w || h > native_h { return false; }
+    VIRTUAL_MODE.store((w as u64) << 32 | h as u64, Ordering::Relaxed);
+    true
+}
+
+/// Changes the desktop's resolution, switching the framebuffer's own mode
+/// when the Bochs adapter is there and falling back to set_virtual_mode.
+/// Interrupts must be off.
+pub fn set_mode(w: usize, h: usize) -> bool {
+    if let Some((fb, stride)) = set_hardware_mode(w, h) {
+        unsafe { replace_framebuffer(fb, stride, (w, h)) };
+        VIRTUAL_MODE.store(0, Ordering::Relaxed);
+        return true;
+    }
+    set_virtual_mode(w, h)
+}
+
+fn set_hardware_mode(w: usize, h: usize) -> Option<(u64, usize)> {
+    // Its resources were sized for the boot mode
+    if crate::drivers::virtio_gpu::is_active() { return None; }
+    // The logger may be halfway through drawing into the old back buffer
+    if crate::vga_log::VGA_LOGGER.is_locked() { return None; }
+    let mut bochs = crate::drivers::gpu::bochs::BOCHS_VBE.lock();
+    let bochs = bochs.as_mut()?;
+    let (w, h) = if (w, h) == (0, 0) { bochs.boot_mode } else { (w, h) };
+    if (w, h) == native_size() { return None; }
+    bochs.set_mode(w, h)
+}
+
+/// Points SCREEN_PAINTER at a framebuffer whose mode just changed and
+/// resizes everything sized from it: BACK_BUFFER, the kernel window
+/// manager, the primary display and with it the pointer's range.
+unsafe fn replace_framebuffer(fb: u64, stride: usize, (w, h): (usize, usize)) {
+    let mut info = match &SCREEN_PAINTER { Some(p) => p.info, None => return };
+    info.width = w;
+    info.height = h;
+    info.stride = stride;
+    info.byte_len = stride * h * info.bytes_per_pixel;
+    SCREEN_PAINTER = Some(VgaPainter { buffer: core::slice::from_raw_parts_mut(fb as *mut u8, info.byte_len), info });
+    FRAMEBUFFER_PHYS_ADDR = fb;
+    // The old buffer goes first so both never have to fit in the heap at once
+    BACK_BUFFER = None;
+    BACK_BUFFER = Some(BackBuffer::new(info));
+
+    crate::window::WINDOW_MANAGER.lock().set_resolution(w, h);
+    crate::display::resize_primary(w, h);
+    let mut mouse = crate::mouse::MOUSE_STATE.lock();
+    (mouse.x, mouse.y) = crate::display::clamp(mouse.x, mouse.y);
+    drop(mouse);
+
+    if kernel_owns_screen() { BACK_BUFFER.as_mut().map(|b| b.mark_all()); mark_dirty(); }
+    crate::serial_println!("[GUI] Framebuffer now {}x{} (stride {})", w, h, stride);
+}
+
+/// The resolution the desktop should render at.
+pub fn virtual_mode() -> (usize, usize) {
+    match VIRTUAL_MODE.load(Ordering::Relaxed) {
+        0 => native_size(),
+        mode => ((mode >> 32) as usize, (mode & 0xFFFF_FFFF) as usize),
+    }
+}
+
+/// Applies the resolution line of the boot config, if there is one.
+pub fn load_display_config() {
+    let value = match crate::fs::read_config_value(config::RESOLUTION_KEY) { Some(v) => v, None => return };
+    let applied = config::parse_resolution(&value)
+        .is_some_and(|(w, h)| x86_64::instructions::interrupts::without_interrupts(|| set_mode(w, h)));
+    if applied {
+        let (w, h) = virtual_mode();
+        crate::serial_println!("[GUI] Desktop resolution {}x{} from {}", w, h, crate::fs::CONFIG_PATH);
+    } else {
+        crate::serial_println!("[GUI] Ignoring resolution={} in {}", value, crate::fs::CONFIG_PATH);
+    }
+}
+
 pub struct Rect {
     pub x: usize, pub y: usize, pub w: usize, pub h: usize,
 }
@@ -47,11 +235,120 @@ pub unsafe fn turbo_copy(dest: *mut u8, src: *const u8, count: usize) {
     }
 }
 
+/// The bytes of one pixel in `format`; only the first bytes_per_pixel are used.
+#[inline(always)]
+fn pixel_bytes(format: PixelFormat, color: Color) -> [u8; 4] {
+    match format {
+        PixelFormat::Rgb => [color.r, color.g, color.b, 0],
+        PixelFormat::Bgr | _ => [color.b, color.g, color.r, 0],
+    }
+}
+
+/// Fills `rect`, clipped to the screen. The first row is built pixel by pixel
+/// (whole 4-byte words on 32bpp) and then copied down to the others.
+fn fill_rect(buf: &mut [u8], info: &FrameBufferInfo, rect: Rect, color: Color) {
+    let bpp = info.bytes_per_pixel;
+    if bpp < 3 || bpp > 4 { return; }
+    let x1 = rect.x.saturating_add(rect.w).min(info.width);
+    let y1 = rect.y.saturating_add(rect.h).min(info.height);
+    if rect.x >= x1 || rect.y >= y1 { return; }
+
+    let row_bytes = info.stride * bpp;
+    let start = rect.y * row_bytes + rect.x * bpp;
+    let span = (x1 - rect.x) * bpp;
+    if start + span > buf.len() { return; }
+
+    let px = pixel_bytes(info.pixel_format, color);
+    for p in buf[start..start + span].chunks_exact_mut(bpp) {
+        p.copy_from_slice(&px[..bpp]);
+    }
+    for y in rect.y + 1..y1 {
+        let dst = y * row_bytes + rect.x * bpp;
+        if dst + span > buf.len() { break; }
+        unsafe { turbo_copy(buf.as_mut_ptr().add(dst), buf.as_ptr().add(start), span); }
+    }
+}
+
+/// Draws the set pixels of `c`'s glyph with its top-left corner at (x, y).
+/// With a background the whole cell is painted and edges are blended
+/// against it; without one only covered pixels are touched.
+/// Returns the cell size.
+fn draw_glyph(buf: &mut [u8], info: &FrameBufferInfo, x: usize, y: usize, c: char, fg: Color, bg: Option<Color>) -> (usize, usize) {
+    let bpp = info.bytes_per_pixel;
+    if bpp < 3 || bpp > 4 { return (0, 0); }
+    let fg_px = pixel_bytes(info.pixel_format, fg);
+    let bg_px = bg.map(|b| pixel_bytes(info.pixel_format, b));
+
+    crate::glyph_cache::with_glyph(c, |glyph| {
+        for (row_i, row) in glyph.coverage.chunks_exact(glyph.width).enumerate() {
+            let py = y + row_i;
+            if py >= info.height { break; }
+            let row_start = py * info.stride;
+            for (col_i, &alpha) in row.iter().enumerate() {
+                let cx = x + col_i;
+                if cx >= info.width { break; }
+                let px = match (bg, bg_px) {
+                    (_, Some(b)) if alpha == 0 => b,
+                    (Some(b), _) if alpha < 255 => pixel_bytes(info.pixel_format, blend(fg, b, alpha)),
+                    (None, _) if alpha == 0 => continue,
+                    _ => fg_px,
+                };
+                let idx = (row_start + cx) * bpp;
+                if let Some(dst) = buf.get_mut(idx..idx + bpp) { dst.copy_from_slice(&px[..bpp]); }
+            }
+        }
+        (glyph.width, glyph.height)
+    })
+}
+
+fn blend(fg: Color, bg: Color, alpha: u8) -> Color {
+    let mix = |f: u8, b: u8| ((f as u16 * alpha as u16 + b as u16 * (255 - alpha as u16)) / 255) as u8;
+    Color::new(mix(fg.r, bg.r), mix(fg.g, bg.g), mix(fg.b, bg.b))
+}
+
+/// Times a full-screen clear on the BackBuffer, once the way it used to be
+/// done (clear_per_byte) and once through fill_rect, and logs both to
+/// serial so the fill paths can be compared. Leaves the buffer black.
+pub fn benchmark_clear() {
+    unsafe {
+        if let Some(back) = &mut BACK_BUFFER {
+            let color = Color::new(0, 0, 30);
+            let start = crate::time::rdtsc();
+            clear_per_byte(&mut back.buffer, &back.info, color);
+            let before = crate::time::rdtsc().wrapping_sub(start);
+            let start = crate::time::rdtsc();
+            back.clear(color);
+            let after = crate::time::rdtsc().wrapping_sub(start);
+            crate::serial_println!("[GFX] Full-screen clear ({}x{}, {} bpp): {} cycles per byte, {} cycles by rows ({}x)",
+                back.info.width, back.info.height, back.info.bytes_per_pixel, before, after, before / after.max(1));
+            back.clear(Color::BLACK);
+        }
+    }
+}
+
+/// The clear fill_rect replaced: every byte written on its own through a
+/// bounds-checked index. Only kept as benchmark_clear's baseline.
+fn clear_per_byte(buf: &mut [u8], info: &FrameBufferInfo, color: Color) {
+    let bpp = info.bytes_per_pixel;
+    for y in 0..info.height {
+        for x in 0..info.width {
+            let idx = (y * info.stride + x) * bpp;
+            if idx + 2 >= buf.len() { return; }
+            match info.pixel_format {
+                PixelFormat::Rgb => { buf[idx] = color.r; buf[idx + 1] = color.g; buf[idx + 2] = color.b; },
+                _ => { buf[idx] = color.b; buf[idx + 1] = color.g; buf[idx + 2] = color.r; },
+            }
+        }
+    }
+}
+
 pub trait Painter {
     fn clear(&mut self, color: Color);
     fn draw_rect(&mut self, rect: Rect, color: Color);
-    fn draw_char(&mut self, x: usize, y: usize, c: char, color: Color);
-    fn draw_string(&mut self, x: usize, y: usize, s: &str, color: Color);
+    /// `bg` paints the character's cell opaquely, so text can be redrawn in
+    /// place without filling the rect underneath first
+    fn draw_char(&mut self, x: usize, y: usize, c: char, color: Color, bg: Option<Color>);
+    fn draw_string(&mut self, x: usize, y: usize, s: &str, color: Color, bg: Option<Color>);
     fn width(&self) -> usize;
     fn height(&self) -> usize;
 }
@@ -70,75 +367,17 @@ impl<'a> Painter for VgaPainter<'a> {
     }
 
     fn draw_rect(&mut self, rect: Rect, color: Color) {
-        let bpp = self.info.bytes_per_pixel;
-        let stride = self.info.stride;
-        for y in rect.y..(rect.y + rect.h) {
-            if y >= self.info.height { break; }
-            let offset = y * stride + rect.x;
-            let byte_offset = offset * bpp;
-            
-            if byte_offset >= self.buffer.len() { break; }
-
-            for x in 0..rect.w {
-                // FIX: Use saturating_add to prevent boundary check bypass on underflow
-                if rect.x.saturating_add(x) >= self.info.width { break; }
-                let idx = byte_offset + (x * bpp);
-                
-                if idx + 2 < self.buffer.len() {
-                    match self.info.pixel_format {
-                        PixelFormat::Rgb => {
-                            self.buffer[idx] = color.r;
-                            self.buffer[idx+1] = color.g;
-                            self.buffer[idx+2] = color.b;
-                        },
-                        PixelFormat::Bgr | _ => {
-                            self.buffer[idx] = color.b;
-                            self.buffer[idx+1] = color.g;
-                            self.buffer[idx+2] = color.r;
-                        }
-                    }
-                }
-            }
-        }
+        fill_rect(self.buffer, &self.info, rect, color);
     }
 
-    fn draw_char(&mut self, x: usize, y: usize, c: char, color: Color) {
-        let char_raster = get_raster(c, FontWeight::Regular, RasterHeight::Size32)
-            .unwrap_or_else(|| get_raster('?', FontWeight::Regular, RasterHeight::Size32).unwrap());
-        
-        for (row_i, row) in char_raster.raster().iter().enumerate() {
-            for (col_i, val) in row.iter().enumerate() {
-                if *val > 0 {
-                    let px = x + col_i;
-                    let py = y + row_i;
-                    if px < self.width() && py < self.height() {
-                        let bpp = self.info.bytes_per_pixel;
-                        let idx = (py * self.info.stride + px) * bpp;
-                        
-                        if idx + 2 < self.buffer.len() {
-                             match self.info.pixel_format {
-                                PixelFormat::Rgb => {
-                                    self.buffer[idx] = color.r;
-                                    self.buffer[idx+1] = color.g;
-                                    self.buffer[idx+2] = color.b;
-                                },
-                                PixelFormat::Bgr | _ => {
-                                    self.buffer[idx] = color.b;
-                                    self.buffer[idx+1] = color.g;
-                                    self.buffer[idx+2] = color.r;
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-        }
+    fn draw_char(&mut self, x: usize, y: usize, c: char, color: Color, bg: Option<Color>) {
+        draw_glyph(self.buffer, &self.info, x, y, c, color, bg);
     }
 
-    fn draw_string(&mut self, x: usize, y: usize, s: &str, color: Color) {
+    fn draw_string(&mut self, x: usize, y: usize, s: &str, color: Color, bg: Option<Color>) {
         let mut curr_x = x;
         for c in s.chars() {
-            self.draw_char(curr_x, y, c, color);
+            self.draw_char(curr_x, y, c, color, bg);
             curr_x += 16; 
         }
     }
@@ -147,6 +386,8 @@ impl<'a> Painter for VgaPainter<'a> {
 pub struct BackBuffer {
     pub buffer: Vec<u8>,
     pub info: FrameBufferInfo,
+    // Everything drawn since the last present
+    dirty: DirtyRegion,
 }
 
 impl BackBuffer {
@@ -155,10 +396,52 @@ impl BackBuffer {
         Self {
             buffer: vec![0; size],
             info,
+            dirty: DirtyRegion::new(),
+        }
+    }
+
+    /// Adds a region to what the next present() copies. Code writing into
+    /// `buffer` directly must call this itself.
+    pub fn mark_region(&mut self, x: usize, y: usize, w: usize, h: usize) {
+        self.dirty.mark(x, y, w, h, self.info.width, self.info.height);
+    }
+
+    pub fn mark_all(&mut self) {
+        self.dirty.mark_all(self.info.width, self.info.height);
+    }
+
+    /// What the next present() copies, as (x0, y0, x1, y1).
+    pub fn dirty_area(&self) -> Option<Area> { self.dirty.get() }
+
+    /// Copies the rows and columns drawn since the last present.
+    pub fn present(&mut self, screen: &mut VgaPainter) {
+        let (x0, y0, x1, y1) = match self.dirty.take() { Some(d) => d, None => return };
+        if crate::drivers::virtio_gpu::is_active() {
+            // Busy (this tick interrupted a flush); the next present retries
+            if !crate::drivers::virtio_gpu::flush_kernel(x0, y0, x1 - x0, y1 - y0) { self.dirty.mark_area((x0, y0, x1, y1), self.info.width, self.info.height); }
+            return;
+        }
+        let bpp = self.info.bytes_per_pixel;
+        let row_bytes = self.info.stride * bpp;
+        let len = self.buffer.len().min(screen.buffer.len());
+        for y in y0..y1 {
+            let start = y * row_bytes + x0 * bpp;
+            let count = ((x1 - x0) * bpp).min(len.saturating_sub(start));
+            if count == 0 { break; }
+            unsafe {
+                turbo_copy(screen.buffer.as_mut_ptr().add(start), self.buffer.as_ptr().add(start), count);
+            }
         }
     }
 
-    pub fn present(&self, screen: &mut VgaPainter) {
+    /// Copies the whole buffer, for when the screen contents can't be trusted
+    /// (resolution changes, the kernel taking the screen back).
+    pub fn present_full(&mut self, screen: &mut VgaPainter) {
+        self.dirty = DirtyRegion::new();
+        if crate::drivers::virtio_gpu::is_active() {
+            if !crate::drivers::virtio_gpu::flush_kernel(0, 0, self.info.width, self.info.height) { self.mark_all(); }
+            return;
+        }
         let len = self.buffer.len().min(screen.buffer.len());
         unsafe {
             turbo_copy(
@@ -169,23 +452,6 @@ impl BackBuffer {
         }
     }
 
-    #[inline(always)]
-    fn put_pixel(&mut self, idx: usize, color: Color) {
-        if idx + 2 < self.buffer.len() {
-            match self.info.pixel_format {
-                PixelFormat::Rgb => {
-                    self.buffer[idx] = color.r;
-                    self.buffer[idx+1] = color.g;
-                    self.buffer[idx+2] = color.b;
-                },
-                PixelFormat::Bgr | _ => {
-                    self.buffer[idx] = color.b;
-                    self.buffer[idx+1] = color.g;
-                    self.buffer[idx+2] = color.r;
-                }
-            }
-        }
-    }
 }
 
 impl Painter for BackBuffer {
@@ -195,54 +461,26 @@ impl Painter for BackBuffer {
     fn clear(&mut self, color: Color) {
         if color == Color::BLACK {
             self.buffer.fill(0);
+            self.mark_all();
             return;
         }
         self.draw_rect(Rect::new(0, 0, self.width(), self.height()), color);
     }
 
     fn draw_rect(&mut self, rect: Rect, color: Color) {
-        let bpp = self.info.bytes_per_pixel;
-        let stride = self.info.stride;
-        
-        for y in rect.y..(rect.y + rect.h) {
-            if y >= self.height() { break; }
-            let offset = y * stride + rect.x;
-            let mut idx = offset * bpp;
-
-            for x in 0..rect.w {
-                // FIX: Use saturating_add to prevent boundary check bypass on underflow
-                if rect.x.saturating_add(x) >= self.width() { break; }
-                self.put_pixel(idx, color);
-                idx += bpp;
-            }
-        }
+        self.mark_region(rect.x, rect.y, rect.w, rect.h);
+        fill_rect(&mut self.buffer, &self.info, rect, color);
     }
 
-    fn draw_char(&mut self, x: usize, y: usize, c: char, color: Color) {
-        let char_raster = get_raster(c, FontWeight::Regular, RasterHeight::Size32)
-            .unwrap_or_else(|| get_raster('?', FontWeight::Regular, RasterHeight::Size32).unwrap());
-        
-        let bpp = self.info.bytes_per_pixel;
-        let stride = self.info.stride;
-
-        for (row_i, row) in char_raster.raster().iter().enumerate() {
-            for (col_i, val) in row.iter().enumerate() {
-                if *val > 0 {
-                    let px = x + col_i;
-                    let py = y + row_i;
-                    if px < self.width() && py < self.height() {
-                        let idx = (py * stride + px) * bpp;
-                        self.put_pixel(idx, color);
-                    }
-                }
-            }
-        }
+    fn draw_char(&mut self, x: usize, y: usize, c: char, color: Color, bg: Option<Color>) {
+        let (w, h) = draw_glyph(&mut self.buffer, &self.info, x, y, c, color, bg);
+        self.mark_region(x, y, w, h);
     }
 
-    fn draw_string(&mut self, x: usize, y: usize, s: &str, color: Color) {
+    fn draw_string(&mut self, x: usize, y: usize, s: &str, color: Color, bg: Option<Color>) {
         let mut curr_x = x;
         for c in s.chars() {
-            self.draw_char(curr_x, y, c, color);
+            self.draw_char(curr_x, y, c, color, bg);
             curr_x += 16;
         }
     }
@@ -0,0 +1,101 @@
+use pc_keyboard::{layouts, DecodedKey, HandleControl, KeyCode, KeyState, Keyboard, ScancodeSet1};
+use spin::Mutex;
+use lazy_static::lazy_static;
+use crate::shell::{
+    KEY_UP, KEY_DOWN, KEY_LEFT, KEY_RIGHT, KEY_PAGE_UP, KEY_PAGE_DOWN, KEY_HOME, KEY_END,
+    MOD_SHIFT, MOD_CTRL, MOD_ALT, MOD_CAPS, KEY_EVENT_PRESSED, KEY_EVENT_EXTENDED,
+};
+
+// ==========================================
+// KEYBOARD INPUT
+// ==========================================
+// Every keyboard (the PS/2 interrupt and the USB boot keyboard replaying
+// set-1 codes) feeds scancodes into one pc_keyboard decoder here. Each
+// decoded key is packed once (layout in shell.rs) and then routed: while a
+// kernel window has focus it goes to the window manager, otherwise to the
+// userspace key queue behind Syscalls 506 and 538. Both see the same decoded
+// char, so Shift, Caps Lock and the layout apply the same way to each.
+
+struct KeyboardState {
+    keyboard: Keyboard<layouts::Us104Key, ScancodeSet1>,
+    modifiers: u8,
+    extended: bool,
+}
+
+lazy_static! {
+    static ref KEYBOARD: Mutex<KeyboardState> = Mutex::new(KeyboardState {
+        keyboard: Keyboard::new(ScancodeSet1::new(), layouts::Us104Key, HandleControl::Ignore),
+        modifiers: 0,
+        extended: false,
+    });
+}
+
+fn special_key_char(code: KeyCode) -> Option<char> {
+    match code {
+        KeyCode::ArrowUp => Some(KEY_UP),
+        KeyCode::ArrowDown => Some(KEY_DOWN),
+        KeyCode::ArrowLeft => Some(KEY_LEFT),
+        KeyCode::ArrowRight => Some(KEY_RIGHT),
+        KeyCode::PageUp => Some(KEY_PAGE_UP),
+        KeyCode::PageDown => Some(KEY_PAGE_DOWN),
+        KeyCode::Home => Some(KEY_HOME),
+        KeyCode::End => Some(KEY_END),
+        _ => None,
+    }
+}
+
+/// Feeds one set-1 scancode byte (0xE0 prefixes included) to the decoder.
+pub fn handle_scancode(scancode: u8) {
+    let mut state = KEYBOARD.lock();
+
+    if scancode == 0xE0 {
+        state.extended = true;
+    }
+
+    if let Ok(Some(key_event)) = state.keyboard.add_byte(scancode) {
+        let pressed = key_event.state != KeyState::Up;
+
+        let mod_bit = match key_event.code {
+            KeyCode::LShift | KeyCode::RShift => MOD_SHIFT,
+            KeyCode::LControl | KeyCode::RControl => MOD_CTRL,
+            KeyCode::LAlt | KeyCode::RAltGr => MOD_ALT,
+            _ => 0,
+        };
+        if mod_bit != 0 {
+            if pressed { state.modifiers |= mod_bit; } else { state.modifiers &= !mod_bit; }
+        }
+        if key_event.code == KeyCode::CapsLock && pressed {
+            state.modifiers ^= MOD_CAPS;
+        }
+
+        let code = key_event.code;
+        let ch = match state.keyboard.process_keyevent(key_event) {
+            Some(DecodedKey::Unicode(c)) => c,
+            Some(DecodedKey::RawKey(raw)) => special_key_char(raw).unwrap_or('\0'),
+            // Releases don't decode, but navigation keys should still carry their char
+            None => special_key_char(code).unwrap_or('\0'),
+        };
+
+        let mut packed = (ch as u64)
+            | (((scancode & 0x7F) as u64) << 32)
+            | ((state.modifiers as u64) << 40);
+        if pressed { packed |= KEY_EVENT_PRESSED; }
+        if state.extended { packed |= KEY_EVENT_EXTENDED; }
+        state.extended = false;
+
+        drop(state);
+        route(packed, ch, pressed);
+    }
+}
+
+fn route(packed: u64, ch: char, pressed: bool) {
+    // Called from the keyboard interrupt, so never wait on the window manager;
+    // if it's busy the key goes to userspace like it would without kernel windows
+    if let Some(mut wm) = crate::window::WINDOW_MANAGER.try_lock() {
+        if wm.has_focus() {
+            if pressed && ch != '\0' { wm.handle_key(ch); }
+            return;
+        }
+    }
+    crate::shell::push_key_event(packed);
+}
@@ -12,6 +12,7 @@ use crate::scheduler::{FileDescriptor, KernelSocket, SocketKind};
 use alloc::sync::Arc;
 use core::sync::atomic::{AtomicU64, AtomicU16, Ordering};
 use x86_64::registers::model_specific::GsBase;
+use crate::errno::*;
 
 pub const PIC_1_OFFSET: u8 = 32;
 pub const PIC_2_OFFSET: u8 = PIC_1_OFFSET + 8;
@@ -22,14 +23,6 @@ pub static KERNEL_CR3: AtomicU64 = AtomicU64::new(0);
 // Atomic counter prevents Ephemeral Port exhaustion!
 static NEXT_LOCAL_PORT: AtomicU16 = AtomicU16::new(49152);
 
-const EBADF: i64 = -9;
-const EAGAIN: i64 = -11;
-const ENOMEM: i64 = -12;
-const EFAULT: i64 = -14; 
-const EINVAL: i64 = -22;
-const EMFILE: i64 = -24;
-const ENOSYS: i64 = -38; 
-
 #[repr(C)]
 pub struct SockAddrIn {
     pub sin_family: u16,
@@ -38,6 +31,31 @@ pub struct SockAddrIn {
     pub sin_zero: [u8; 8],
 }
 
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct BlockDeviceInfo {
+    pub nsid: u32,
+    pub lba_size: u32,
+    pub capacity_lbas: u64,
+    pub mounted: u32, // 1 = backs the /mnt/nvme volume
+    pub _pad: u32,
+}
+
+pub const DISK_KIND_NVME: u32 = 0;
+pub const DISK_KIND_SATA: u32 = 1;
+pub const DISK_KIND_USB: u32 = 2;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct DiskInfoRecord {
+    pub kind: u32, // DISK_KIND_*
+    pub id: u32,   // NSID for NVMe, port number for SATA, slot for USB
+    pub sector_size: u32,
+    pub model_len: u32,
+    pub sectors: u64,
+    pub model: [u8; 40],
+}
+
 #[repr(C)]
 #[derive(Clone, Copy)]
 pub struct TaskInfo {
@@ -47,6 +65,28 @@ pub struct TaskInfo {
     pub name: [u8; 16],
 }
 
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct TaskStat {
+    pub pid: u64,
+    pub tickets: u64,
+    pub cpu_ticks: u64,
+    pub last_run_ms: u64,
+    pub state: u8, // scheduler::TaskState: 0 Running, 1 Ready, 2 Blocked, 3 Zombie
+    pub is_idle: u8,
+    pub name: [u8; 16],
+}
+
+#[repr(C)]
+pub struct MemInfo {
+    pub total: u64,
+    pub used: u64,
+    pub free: u64,
+    pub kernel_heap_used: u64,
+    pub kernel_heap_peak: u64,
+    pub kernel_heap_size: u64,
+}
+
 #[repr(C)]
 pub struct SystemInfo {
     pub current_temp: u8,
@@ -55,21 +95,26 @@ pub struct SystemInfo {
     pub gpu_fan_rpm: u32,
     pub task_count: u64,
     pub tasks: [TaskInfo; 64],
+    pub pending_jobs: u64, // work queue depth
+    pub completed_jobs: u64,
+    pub audio: u32, // sound::AUDIO_* bits
 }
 
+/// True if the current task may read `len` bytes at `ptr` (see uaccess).
 pub fn is_valid_user_ptr(ptr: *const u8, len: usize) -> bool {
-    let start = ptr as u64;
-    if start == 0 && len == 0 { return false; }
-    if let Some(end) = start.checked_add(len as u64) {
-        return end <= 0x0000_7FFF_FFFF_FFFF;
-    }
-    false
+    crate::uaccess::range_ok(ptr as u64, len, false)
+}
+
+/// Like is_valid_user_ptr, for buffers the kernel is about to write into.
+pub fn is_writable_user_ptr(ptr: *const u8, len: usize) -> bool {
+    crate::uaccess::range_ok(ptr as u64, len, true)
 }
 
 lazy_static! {
     static ref IDT: InterruptDescriptorTable = {
         let mut idt = InterruptDescriptorTable::new();
         idt.breakpoint.set_handler_fn(breakpoint_handler);
+        idt.non_maskable_interrupt.set_handler_fn(nmi_handler);
         unsafe {
             idt.double_fault.set_handler_fn(double_fault_handler)
                 .set_stack_index(gdt::DOUBLE_FAULT_IST_INDEX);
@@ -79,15 +124,22 @@ lazy_static! {
         
         unsafe {
             idt[0x40].set_handler_addr(VirtAddr::new(timer_interrupt_stub as *const () as u64));
+            // IRQ0 from the PIT, only unmasked in PIC mode (no MADT)
+            idt[InterruptIndex::Timer.as_usize()].set_handler_addr(VirtAddr::new(timer_interrupt_stub as *const () as u64));
             idt[0x41].set_handler_addr(VirtAddr::new(yield_interrupt_stub as *const () as u64));
             idt[InterruptIndex::Keyboard.as_usize()].set_handler_addr(VirtAddr::new(keyboard_interrupt_stub as *const () as u64));
             idt[InterruptIndex::Mouse.as_usize()].set_handler_addr(VirtAddr::new(mouse_interrupt_stub as *const () as u64));
+            // Fallback syscall gate, reachable from ring 3
+            idt[SYSCALL_VECTOR as usize].set_handler_addr(VirtAddr::new(int80_handler_asm as *const () as u64))
+                .set_privilege_level(x86_64::PrivilegeLevel::Ring3);
             
             // REMOVE the old ethernet_interrupt_stub line from inside the unsafe block
         }
         
         // new x86-interrupt handler directly to slot 0x30 (48) outside the unsafe block
         idt[0x30].set_handler_fn(rtl8168_interrupt_handler);
+        idt[crate::drivers::nvme::NVME_VECTOR as usize].set_handler_fn(nvme_interrupt_handler);
+        idt[crate::usb::XHCI_VECTOR as usize].set_handler_fn(xhci_interrupt_handler);
         
         idt
     };
@@ -110,14 +162,31 @@ impl InterruptIndex {
 
 extern "x86-interrupt" fn breakpoint_handler(_stack_frame: InterruptStackFrame) {}
 
-extern "x86-interrupt" fn double_fault_handler(stack_frame: InterruptStackFrame, _error_code: u64) -> ! {
+// The panicking core sends one to every other core so nothing keeps drawing
+// or taking locks under the panic screen. NMIs stay blocked until iretq, so
+// halting here holds the core for good.
+extern "x86-interrupt" fn nmi_handler(_stack_frame: InterruptStackFrame) {
+    if !crate::panic::in_progress() { return; }
+    loop { x86_64::instructions::interrupts::disable(); x86_64::instructions::hlt(); }
+}
+
+// Faults in ring 0 go straight to the panic screen through panic::fault,
+// which formats on the stack and forces its locks open: going through
+// panic!() from here used to deadlock when the fault hit while the screen
+// or heap lock was held. A fault in ring 3 only ends the task that caused it.
+
+extern "x86-interrupt" fn double_fault_handler(stack_frame: InterruptStackFrame, error_code: u64) -> ! {
     if (stack_frame.code_segment & 3) == 3 { unsafe { core::arch::asm!("swapgs", options(nostack)); } }
-    panic!("EXCEPTION: DOUBLE FAULT");
+    crate::panic::fault("DOUBLE FAULT", &stack_frame, error_code);
 }
 
 extern "x86-interrupt" fn gpf_handler(stack_frame: InterruptStackFrame, error_code: u64) {
-    if (stack_frame.code_segment & 3) == 3 { unsafe { core::arch::asm!("swapgs", options(nostack)); } }
-    panic!("EXCEPTION: GPF Error: {} ({:#x})\nIP: {:#x}", error_code, error_code, stack_frame.instruction_pointer.as_u64());
+    if (stack_frame.code_segment & 3) == 3 {
+        unsafe { core::arch::asm!("swapgs", options(nostack)); }
+        crate::scheduler::kill_current_user_task(format_args!("general protection fault ({:#x}) at IP {:#x}",
+            error_code, stack_frame.instruction_pointer.as_u64()));
+    }
+    crate::panic::fault("GENERAL PROTECTION FAULT", &stack_frame, error_code);
 }
 
 extern "x86-interrupt" fn pf_handler(stack_frame: InterruptStackFrame, error_code: PageFaultErrorCode) {
@@ -165,9 +234,20 @@ extern "x86-interrupt" fn pf_handler(stack_frame: InterruptStackFrame, error_cod
                                 
                                 // 3. Update the PTE: Point to new frame, clear CoW bit, set Writable bit
                                 let mut new_entry = (pt_entry & !0x000FFFFF_FFFFF000) | new_phys;
-                                new_entry &= !0x400; // Clear CoW
+                                new_entry &= !(0x400 | crate::memory::SHARED_FRAME); // Clear CoW
                                 new_entry |= 1 << 1; // Make Writable
-                                *pt.add(i1 as usize) = new_entry;
+
+                                // Threads share this table, and another one may have taken the same
+                                // fault on another core. Only the fault that swaps the PTE it read
+                                // drops the old frame; the loser frees its copy and retries the write.
+                                let slot = &*(pt.add(i1 as usize) as *const AtomicU64);
+                                if slot.compare_exchange(pt_entry, new_entry, Ordering::AcqRel, Ordering::Acquire).is_err() {
+                                    crate::memory::free_frame(new_frame);
+                                } else if pt_entry & crate::memory::SHARED_FRAME != 0 && crate::memory::release_shared_frame(old_phys) {
+                                    // The copy is taken before letting go, so whoever ends up last
+                                    // with the old frame can't have written it yet
+                                    crate::memory::free_frame(x86_64::structures::paging::PhysFrame::containing_address(x86_64::PhysAddr::new(old_phys)));
+                                }
                                 
                                 // 4. Flush the specific TLB page and instantly resume the app!
                                 core::arch::asm!("invlpg [{}]", in(reg) cr2);
@@ -182,49 +262,17 @@ extern "x86-interrupt" fn pf_handler(stack_frame: InterruptStackFrame, error_cod
         }
     }
 
-    if error_code.contains(PageFaultErrorCode::USER_MODE) {
-        crate::serial_println!("\n[SEGFAULT] User Process Terminated. Invalid Memory Access at: {:#x}", cr2);
-        if GsBase::read().as_u64() != 0 {
-            let percpu = crate::percpu::current();
-            let curr_idx = percpu.scheduler.core_task_idx[percpu.logical_id as usize % 32];
-            if curr_idx < percpu.scheduler.tasks.len() {
-                let task = &mut percpu.scheduler.tasks[curr_idx];
-                
-                for i in 0..32 { 
-                    if let Some(crate::scheduler::FileDescriptor::Socket(sock_mtx)) = &task.fd_table[i] {
-                        if alloc::sync::Arc::strong_count(sock_mtx) == 1 {
-                            let sock = sock_mtx.lock();
-                            if let Some(sockets) = crate::drivers::net::GLOBAL_SOCKETS.lock().as_mut() {
-                                match sock.kind {
-                                    crate::scheduler::SocketKind::Tcp(handle) => {
-                                        let socket = sockets.get_mut::<smoltcp::socket::tcp::Socket>(handle);
-                                        socket.abort();
-                                        sockets.remove(handle);
-                                    },
-                                    crate::scheduler::SocketKind::Udp(handle) => { sockets.remove(handle); }
-                                }
-                            }
-                        }
-                    }
-                    task.fd_table[i] = None; 
-                }
+    // Demand paging: first touch of a reserved heap or anonymous mmap page
+    if !error_code.contains(PageFaultErrorCode::PROTECTION_VIOLATION) && cr2 < crate::uaccess::USER_TOP && crate::process::demand_map(cr2) {
+        if was_user { unsafe { core::arch::asm!("swapgs", options(nostack)); } }
+        return;
+    }
 
-                crate::memory::clear_user_address_space(task.cr3);
-                task.state = crate::scheduler::TaskState::Zombie;
-            }
-        }
-        
-        crate::apic::end_of_interrupt();
-        
-        unsafe { 
-            x86_64::instructions::interrupts::enable();
-            loop { core::arch::asm!("hlt") } 
-        }
-    } else {
-        if !was_user && (stack_frame.code_segment & 3) == 3 { unsafe { core::arch::asm!("swapgs", options(nostack)); } }
-        panic!("KERNEL PAGE FAULT\nAddr: {:#x}\nError: {:?}\nIP: {:#x}\nCS: {:#x}\nCR3: {:#x}", 
-             cr2, error_code, stack_frame.instruction_pointer.as_u64(), stack_frame.code_segment, cr3);
+    if error_code.contains(PageFaultErrorCode::USER_MODE) {
+        crate::scheduler::kill_current_user_task(format_args!("invalid memory access at {:#x} (IP {:#x})",
+            cr2, stack_frame.instruction_pointer.as_u64()));
     }
+    crate::panic::fault("PAGE FAULT", &stack_frame, error_code.bits());
 }
 
 core::arch::global_asm!(r#"
@@ -376,6 +424,7 @@ extern "C" {
     fn mouse_interrupt_stub();
     fn ethernet_interrupt_stub();
     fn syscall_handler_asm();
+    fn int80_handler_asm();
     fn yield_interrupt_stub();
 }
 
@@ -388,16 +437,29 @@ pub extern "C" fn timer_context_switch(current_rsp: u64) -> u64 {
         return current_rsp; 
     }
 
+    let percpu = crate::percpu::current();
+
     // --- THE TRUE WALL CLOCK ---
-    crate::time::UPTIME_MS.fetch_add(1, core::sync::atomic::Ordering::Relaxed);
+    // Every core takes this tick; only the BSP's counts, or uptime would run N times fast
+    if percpu.logical_id == 0 {
+        let now = crate::time::UPTIME_MS.fetch_add(crate::time::MS_PER_TICK, core::sync::atomic::Ordering::Relaxed) + crate::time::MS_PER_TICK;
+        crate::gui::present_tick(now);
+        crate::drivers::virtio_gpu::track_pointer();
+        crate::watchdog::check();
+        crate::sound::check(now);
+    }
     // ---------------------------
     
-    let percpu = crate::percpu::current();
-    
     // Increment the tick counter BEFORE we schedule a new task
     let curr_idx = percpu.scheduler.core_task_idx[percpu.logical_id as usize % 32];
     if curr_idx < percpu.scheduler.tasks.len() {
         percpu.scheduler.tasks[curr_idx].cpu_ticks += 1;
+        crate::scheduler::account_tick(percpu.scheduler.tasks[curr_idx].is_idle, percpu.logical_id == 0);
+        // A killed task spinning in ring 3 never reaches the syscall that would end it.
+        // current_rsp points at the saved-register pointer; the iret frame's CS follows
+        // the 15 pushed registers and RIP.
+        let interrupted_cs = unsafe { *((*(current_rsp as *const u64) + 16 * 8) as *const u64) };
+        if interrupted_cs & 3 == 3 { crate::scheduler::exit_if_killed(); }
     }
     // ------------------------------------
 
@@ -415,14 +477,9 @@ pub extern "C" fn timer_context_switch(current_rsp: u64) -> u64 {
             if current_cr3 != task.cr3.as_u64() {
                 core::arch::asm!("mov cr3, {}", in(reg) task.cr3.as_u64());
             }
-
-            // Update Syscall and Hardware Interrupt Stacks
-            let percpu_base = percpu as *const _ as *mut u64;
-            *percpu_base = task_stack;
-            
-            let tss_ptr = percpu.gdt_state.tss as *const _ as *mut x86_64::structures::tss::TaskStateSegment;
-            (*tss_ptr).privilege_stack_table[0] = x86_64::VirtAddr::new(task_stack);
         }
+        // Update Syscall and Hardware Interrupt Stacks
+        percpu.set_kernel_stack(task_stack);
     }
     
     new_rsp
@@ -443,11 +500,8 @@ pub extern "C" fn yield_context_switch(current_rsp: u64) -> u64 {
         unsafe {
             let current_cr3 = x86_64::registers::control::Cr3::read().0.start_address().as_u64();
             if current_cr3 != task.cr3.as_u64() { core::arch::asm!("mov cr3, {}", in(reg) task.cr3.as_u64()); }
-            let percpu_base = percpu as *const _ as *mut u64;
-            *percpu_base = task_stack;
-            let tss_ptr = percpu.gdt_state.tss as *const _ as *mut x86_64::structures::tss::TaskStateSegment;
-            (*tss_ptr).privilege_stack_table[0] = x86_64::VirtAddr::new(task_stack);
         }
+        percpu.set_kernel_stack(task_stack);
     }
     new_rsp
 }
@@ -498,7 +552,7 @@ pub extern "C" fn keyboard_handler_impl() {
     use x86_64::instructions::port::Port;
     let mut port = Port::new(0x60);
     let scancode: u8 = unsafe { port.read() };
-    crate::shell::handle_key(scancode);
+    crate::input::handle_scancode(scancode);
     // 🚨 EOI REMOVED FROM HERE!
 }
 
@@ -548,11 +602,26 @@ pub fn init_syscalls() {
         let mut lstar_msr = Msr::new(0xC0000082);
         lstar_msr.write(syscall_handler_asm as *const () as u64);
 
+        // Interrupts stay off until the stub is on the kernel stack. DF, TF and
+        // AC are whatever userspace left them as, and the kernel expects them clear.
         let mut fmask_msr = Msr::new(0xC0000084);
-        fmask_msr.write(RFlags::INTERRUPT_FLAG.bits());
+        fmask_msr.write((RFlags::INTERRUPT_FLAG | RFlags::DIRECTION_FLAG | RFlags::TRAP_FLAG
+            | RFlags::ALIGNMENT_CHECK | RFlags::NESTED_TASK).bits());
     }
 }
 
+// ==========================================
+// SYSCALL ABI
+// ==========================================
+// RAX holds the number and arguments go in RDI, RSI, RDX, R10, R8, R9 (the
+// Linux order; RCX and R11 are taken by SYSCALL itself). The result comes
+// back in RAX. Both entry stubs push registers so SyscallStackFrame reads
+// them in this order, and syscall 553 echoes all six back so userspace can
+// catch any drift at boot.
+
+/// What SYS_ABI_ECHO XORs each argument with. nyx_api has the same value.
+pub const ABI_ECHO_XOR: u64 = 0x4E59_5841_4249_4543;
+
 #[repr(C)]
 pub struct SyscallStackFrame {
     pub r15: u64, pub r14: u64, pub r13: u64, pub r12: u64,
@@ -626,12 +695,105 @@ syscall_handler_asm:
     sysretq
 "#);
 
+// ==========================================
+// INT 0x80 FALLBACK
+// ==========================================
+// Same ABI as the SYSCALL path: on the way in RCX takes the return address
+// and R11 the flags, as the instruction would have left them, so the frame
+// syscall_dispatcher sees (and that fork and execve rewrite) is laid out
+// identically. On the way out RCX, R11 and user_rsp go back into the iret
+// frame. For callers that can't use SYSCALL, or CPUs that fault on it.
+
+/// IDT vector of the fallback syscall gate
+pub const SYSCALL_VECTOR: u8 = 0x80;
+
+core::arch::global_asm!(r#"
+.global int80_handler_asm
+int80_handler_asm:
+    test qword ptr [rsp + 8], 3
+    jz 1f
+    swapgs
+1:
+    // iret frame: RIP, CS, RFLAGS, RSP, SS
+    push qword ptr [rsp + 24] // user_rsp
+    push rax
+    push rbx
+    push qword ptr [rsp + 24] // rcx = RIP
+    push rdx
+    push rbp
+    push rsi
+    push rdi
+    push r8
+    push r9
+    push r10
+    push qword ptr [rsp + 104] // r11 = RFLAGS
+    push r12
+    push r13
+    push r14
+    push r15
+
+    mov rax, rsp
+    mov rdi, rsp
+
+    and rsp, -16
+    sub rsp, 512
+    fxsave [rsp]
+
+    sub rsp, 8
+    push rax
+
+    cld
+    call syscall_dispatcher
+
+    pop rax
+    add rsp, 8
+
+    fxrstor [rsp]
+    mov rsp, rax
+
+    // Hand RIP, RFLAGS (with SYSRET's mask, never IOPL) and RSP back to iretq
+    mov rax, [rsp + 96]
+    mov [rsp + 128], rax
+    mov rax, [rsp + 32]
+    and rax, 0x3C4FD7
+    or rax, 0x202
+    mov [rsp + 144], rax
+    mov rax, [rsp + 120]
+    mov [rsp + 152], rax
+
+    pop r15
+    pop r14
+    pop r13
+    pop r12
+    pop r11
+    pop r10
+    pop r9
+    pop r8
+    pop rdi
+    pop rsi
+    pop rbp
+    pop rdx
+    pop rcx
+    pop rbx
+    pop rax
+    add rsp, 8
+
+    test qword ptr [rsp + 8], 3
+    jz 2f
+    swapgs
+2:
+    iretq
+"#);
+
 #[no_mangle]
 pub extern "C" fn syscall_dispatcher(frame: &mut SyscallStackFrame) {
     if !is_valid_user_ptr(frame.rcx as *const u8, 1) { frame.rcx = 0; }
     if KERNEL_CR3.load(Ordering::Relaxed) == 0 { frame.rax = ENOSYS as u64; return; }
     if GsBase::read().as_u64() == 0 { frame.rax = ENOSYS as u64; return; }
     
+    // A killed task goes the moment it asks the kernel for anything
+    crate::scheduler::exit_if_killed();
+
     let percpu = crate::percpu::current();
     let id = frame.rax;
     let arg1 = frame.rdi;
@@ -648,28 +810,25 @@ pub extern "C" fn syscall_dispatcher(frame: &mut SyscallStackFrame) {
             let buf_ptr = arg1 as *const u8;
             let len = arg2 as usize;
             
-            if !is_valid_user_ptr(buf_ptr, len) { frame.rax = EFAULT as u64; return; }
-            
-            let path_slice = unsafe { core::slice::from_raw_parts(buf_ptr, len) };
-            if let Ok(path) = core::str::from_utf8(path_slice) {
-                if let Some(vnode) = crate::vfs::VFS.open_path(path) {
-                    let curr_idx = percpu.scheduler.core_task_idx[percpu.logical_id as usize % 32];
-                    if curr_idx >= percpu.scheduler.tasks.len() { frame.rax = EBADF as u64; return; }
-                    
-                    let task = &mut percpu.scheduler.tasks[curr_idx];
-                    let mut allocated_fd = -1isize;
-                    for i in 3..32 {
-                        if task.fd_table[i].is_none() {
-                            task.fd_table[i] = Some(crate::scheduler::FileDescriptor::File(
-                                alloc::sync::Arc::new(crate::vfs::OpenFile::new(vnode))
-                            ));
-                            allocated_fd = i as isize;
-                            break;
-                        }
+            match crate::uaccess::read_user_str(buf_ptr as u64, len) {
+                Ok(path) => match crate::vfs::VFS.open_path(&path) {
+                    Ok(open_file) => {
+                        let curr_idx = percpu.scheduler.core_task_idx[percpu.logical_id as usize % 32];
+                        if curr_idx >= percpu.scheduler.tasks.len() { frame.rax = EBADF as u64; return; }
+                        
+                        let task = &mut percpu.scheduler.tasks[curr_idx];
+                        frame.rax = match (3..32).find(|&i| task.fd_table[i].is_none()) {
+                            Some(fd) => {
+                                task.fd_table[fd] = Some(FileDescriptor::File(alloc::sync::Arc::new(open_file)));
+                                fd as u64
+                            }
+                            None => EMFILE as u64,
+                        };
                     }
-                    frame.rax = allocated_fd as u64; 
-                } else { frame.rax = EBADF as u64; } 
-            } else { frame.rax = EINVAL as u64; }
+                    Err(e) => frame.rax = fs_errno(e) as u64,
+                },
+                Err(e) => frame.rax = e as u64,
+            }
         },
         3 => { // SYS_CLOSE
             let curr_idx = percpu.scheduler.core_task_idx[percpu.logical_id as usize % 32];
@@ -697,6 +856,20 @@ pub extern "C" fn syscall_dispatcher(frame: &mut SyscallStackFrame) {
             }
             frame.rax = 0;
         },
+        8 => { // SYS_LSEEK (fd, offset, whence). Returns the new offset.
+            let curr_idx = percpu.scheduler.core_task_idx[percpu.logical_id as usize % 32];
+            if curr_idx >= percpu.scheduler.tasks.len() || arg1 >= 32 { frame.rax = EBADF as u64; return; }
+            let task = &mut percpu.scheduler.tasks[curr_idx];
+
+            frame.rax = match &task.fd_table[arg1 as usize] {
+                Some(FileDescriptor::File(open_file)) => match open_file.seek(arg2 as i64, arg3 as usize) {
+                    Ok(pos) => pos as u64,
+                    Err(e) => e as u64,
+                },
+                Some(_) => ESPIPE as u64,
+                None => EBADF as u64,
+            };
+        },
         9 => { 
             let addr = arg1 as u64;       
             let size = arg2 as usize;     
@@ -716,12 +889,13 @@ pub extern "C" fn syscall_dispatcher(frame: &mut SyscallStackFrame) {
                     let next_addr = task.mmap_bump;
                     task.mmap_bump += (num_pages as u64) * 0x1000;
                     next_addr
-                } else { addr };
+                } else { addr & !0xFFF };
+                let end = target_addr + (num_pages as u64) * 0x1000;
+                if end > crate::process::USER_STACK_BASE { frame.rax = ENOMEM as u64; return; }
 
-                match crate::memory::allocate_user_pages_at(target_addr, num_pages) {
-                    Ok(mapped_addr) => frame.rax = mapped_addr,
-                    Err(_) => frame.rax = ENOMEM as u64, 
-                }
+                // Anonymous memory is only reserved here; pages are backed on first touch
+                task.regions.lock().vmas.push(crate::process::Vma { start: target_addr, end });
+                frame.rax = target_addr;
             } else {
                 if fd >= 0 && fd < 32 {
                     if let Some(crate::scheduler::FileDescriptor::File(open_file)) = &task.fd_table[fd as usize] {
@@ -739,7 +913,40 @@ pub extern "C" fn syscall_dispatcher(frame: &mut SyscallStackFrame) {
         },
         
         10 => { frame.rax = 0; }, // SYS_MPROTECT
-        12 => { frame.rax = 0; }, // SYS_BRK 
+        11 => { // SYS_MUNMAP (anonymous mappings only, whole regions)
+            let start = arg1 & !0xFFF;
+            let end = (arg1 + arg2 + 0xFFF) & !0xFFF;
+            let curr_idx = percpu.scheduler.core_task_idx[percpu.logical_id as usize % 32];
+            if curr_idx >= percpu.scheduler.tasks.len() { frame.rax = EBADF as u64; return; }
+
+            let mut regions = percpu.scheduler.tasks[curr_idx].regions.lock();
+            match regions.vmas.iter().position(|v| v.start == start && v.end == end) {
+                Some(i) => {
+                    regions.vmas.remove(i);
+                    crate::memory::release_user_pages(start, ((end - start) / 0x1000) as usize);
+                    frame.rax = 0;
+                }
+                None => frame.rax = EINVAL as u64,
+            }
+        },
+        12 => { // SYS_BRK. Returns the break after the call; it stays put if the request can't be met.
+            use crate::process::{USER_HEAP_BASE, USER_HEAP_LIMIT};
+            let curr_idx = percpu.scheduler.core_task_idx[percpu.logical_id as usize % 32];
+            if curr_idx >= percpu.scheduler.tasks.len() { frame.rax = EBADF as u64; return; }
+
+            let mut regions = percpu.scheduler.tasks[curr_idx].regions.lock();
+            let requested = arg1;
+            if requested >= USER_HEAP_BASE && requested <= USER_HEAP_LIMIT {
+                // Shrinking hands whole pages above the new break back
+                let old_end = (regions.brk + 0xFFF) & !0xFFF;
+                let new_end = (requested + 0xFFF) & !0xFFF;
+                if new_end < old_end {
+                    crate::memory::release_user_pages(new_end, ((old_end - new_end) / 0x1000) as usize);
+                }
+                regions.brk = requested;
+            }
+            frame.rax = regions.brk;
+        },
         13 => { frame.rax = 0; }, // SYS_RT_SIGACTION
         14 => { frame.rax = 0; }, // SYS_RT_SIGPROCMASK
         
@@ -765,25 +972,24 @@ pub extern "C" fn syscall_dispatcher(frame: &mut SyscallStackFrame) {
             let iov_ptr = arg2 as *const u64; 
             let iovcnt = arg3 as usize;
             
-            if !is_valid_user_ptr(iov_ptr as *const u8, iovcnt * 16) { 
-                frame.rax = EFAULT as u64; 
-                return; 
-            }
+            let iovs = match iovcnt.checked_mul(16).map(|n| crate::uaccess::read_user_bytes(iov_ptr as u64, n)) {
+                Some(Ok(iovs)) => iovs,
+                Some(Err(e)) => { frame.rax = e as u64; return; }
+                None => { frame.rax = EFAULT as u64; return; }
+            };
             
             let mut total_written = 0isize;
-            for i in 0..iovcnt {
-                unsafe {
-                    let base = *iov_ptr.add(i * 2);
-                    let len = *iov_ptr.add(i * 2 + 1) as usize;
-                    
-                    if len > 0 {
-                        let written = sys_write_internal(fd, base as *const u8, len);
-                        if written < 0 {
-                            if total_written == 0 { total_written = written; }
-                            break;
-                        }
-                        total_written += written;
+            for iov in iovs.chunks_exact(16) {
+                let base = u64::from_ne_bytes(iov[..8].try_into().unwrap());
+                let len = u64::from_ne_bytes(iov[8..].try_into().unwrap()) as usize;
+                
+                if len > 0 {
+                    let written = sys_write_internal(fd, base as *const u8, len);
+                    if written < 0 {
+                        if total_written == 0 { total_written = written; }
+                        break;
                     }
+                    total_written += written;
                 }
             }
             frame.rax = total_written as u64;
@@ -791,7 +997,7 @@ pub extern "C" fn syscall_dispatcher(frame: &mut SyscallStackFrame) {
         
         22 => { // SYS_PIPE
             let fd_array_ptr = arg1 as *mut i32;
-            if !is_valid_user_ptr(fd_array_ptr as *const u8, 8) { frame.rax = EFAULT as u64; return; }
+            if !is_writable_user_ptr(fd_array_ptr as *const u8, 8) { frame.rax = EFAULT as u64; return; }
             
             let pipe = alloc::sync::Arc::new(spin::Mutex::new(alloc::collections::VecDeque::<u8>::new()));
 
@@ -836,19 +1042,25 @@ pub extern "C" fn syscall_dispatcher(frame: &mut SyscallStackFrame) {
 
         41 => frame.rax = sys_socket(arg1, arg2, arg3) as u64,
         42 => frame.rax = sys_connect(arg1 as usize, arg2 as *const u8, arg3 as usize) as u64,
-        44 => frame.rax = sys_write_internal(arg1 as usize, arg2 as *const u8, arg3 as usize) as u64, 
-        45 => frame.rax = sys_read_internal(arg1 as usize, arg2 as *mut u8, arg3 as usize) as u64,
+        44 => frame.rax = sys_sendto(arg1 as usize, arg2 as *const u8, arg3 as usize, arg5 as *const u8, arg6 as usize) as u64,
+        45 => frame.rax = sys_recvfrom(arg1 as usize, arg2 as *mut u8, arg3 as usize, arg5 as *mut u8, arg6 as *mut u32) as u64,
+        49 => frame.rax = sys_bind(arg1 as usize, arg2 as *const u8, arg3 as usize) as u64,
 
         57 => { // SYS_FORK
             let curr_idx = percpu.scheduler.core_task_idx[percpu.logical_id as usize % 32];
             if curr_idx >= percpu.scheduler.tasks.len() { frame.rax = ENOSYS as u64; return; }
             
-            let mut child = crate::process::Process::new().expect("Failed to create child process");
+            let mut child = match crate::process::Process::new() {
+                Ok(c) => c,
+                Err(_) => { frame.rax = ENOMEM as u64; return; }
+            };
             
             {
                 let parent = &percpu.scheduler.tasks[curr_idx];
                 child.parent_pid = Some(parent.pid);
+                child.user = parent.user;
                 child.mmap_bump = parent.mmap_bump; 
+                child.regions = Arc::new(Mutex::new(parent.regions.lock().clone()));
                 
                 // 1. Share memory frames (CoW implementation)
                 crate::memory::clone_user_address_space(parent.cr3, child.cr3);
@@ -920,7 +1132,8 @@ pub extern "C" fn syscall_dispatcher(frame: &mut SyscallStackFrame) {
             // 4. The parent process receives the child's actual PID!
             frame.rax = child.pid;
             
-            percpu.scheduler.tasks.push(child);
+            percpu.scheduler.add(child);
+            crate::scheduler::LIVE_USER_TASKS.fetch_add(1, Ordering::SeqCst);
         },
         58 => { // SYS_SPAWN_THREAD
             let entry_point = arg1;
@@ -935,7 +1148,9 @@ pub extern "C" fn syscall_dispatcher(frame: &mut SyscallStackFrame) {
             {
                 let parent = &percpu.scheduler.tasks[curr_idx];
                 thread.parent_pid = Some(parent.pid);
+                thread.user = Some(crate::process::UserTask { entry: entry_point, stack_top: user_stack });
                 thread.mmap_bump = parent.mmap_bump;
+                thread.regions = parent.regions.clone();
 
                 // Share the File Descriptors (Sockets)
                 for i in 0..32 {
@@ -999,7 +1214,8 @@ pub extern "C" fn syscall_dispatcher(frame: &mut SyscallStackFrame) {
                     crate::serial_println!("[SMP] Load Balancer: Offloading Thread to Core {} (Tasks: {})", target_core, min_tasks);
                     
                     // 3. Inject the thread directly into the idle core's hardware queue!
-                    all_cores[target_core].scheduler.tasks.push(thread);
+                    all_cores[target_core].scheduler.add(thread);
+                    crate::scheduler::LIVE_USER_TASKS.fetch_add(1, Ordering::SeqCst);
                 }
             }
             // ----------------------------------
@@ -1011,15 +1227,16 @@ pub extern "C" fn syscall_dispatcher(frame: &mut SyscallStackFrame) {
             let len = arg2 as usize;
             
             // 1. Copy the path to a safe Kernel String BEFORE shredding user memory!
-            let path_str = if let Ok(s) = core::str::from_utf8(unsafe { core::slice::from_raw_parts(ptr, len) }) {
-                alloc::string::String::from(s.trim_matches(char::from(0)).trim())
-            } else {
-                frame.rax = (-1i64) as u64;
-                return;
+            let path_str = match crate::uaccess::read_user_str(ptr as u64, len) {
+                Ok(s) => alloc::string::String::from(s.trim_matches(char::from(0)).trim()),
+                Err(e) => { frame.rax = e as u64; return; }
             };
 
             // 2. Read the file using the safe Kernel String
             if let Some(elf_data) = crate::vfs::VFS.read_file_alloc(&path_str) {
+                // Reject a bad image while the caller still has an address space to return to
+                if crate::elf::parse(&elf_data).is_err() { frame.rax = ENOEXEC as u64; return; }
+
                 let curr_idx = percpu.scheduler.core_task_idx[percpu.logical_id as usize % 32];
                 let task = &mut percpu.scheduler.tasks[curr_idx];
                 
@@ -1029,6 +1246,7 @@ pub extern "C" fn syscall_dispatcher(frame: &mut SyscallStackFrame) {
                 // 🚨 THE FIX: Reset the bump allocator to a VALID canonical address! 🚨
                 // 0x1000_0000_0000 is safely inside the lower user half.
                 task.mmap_bump = 0x1000_0000_0000;
+                task.regions = Arc::new(Mutex::new(crate::process::UserRegions::new()));
                 
                 // 4. Flush the CPU TLB
                 unsafe {
@@ -1037,7 +1255,7 @@ pub extern "C" fn syscall_dispatcher(frame: &mut SyscallStackFrame) {
                 }
                 
                 // 5. Load the new ELF
-                if let Ok(entry_point) = crate::process::load_elf(&elf_data) {
+                if let Ok(entry_point) = crate::elf::load(&elf_data) {
                     let stack_base = 0x7FFF_0000_0000;
                     let stack_pages = 32; 
                     if crate::memory::allocate_user_pages_at(stack_base, stack_pages).is_ok() {
@@ -1066,56 +1284,13 @@ pub extern "C" fn syscall_dispatcher(frame: &mut SyscallStackFrame) {
                         return;        // Bypass default block exit
                     }
                 }
+                // The old image is gone, so there's nothing left to return ENOEXEC to
+                crate::scheduler::exit_current_user_task(ENOEXEC);
             }
-            frame.rax = (-1i64) as u64; // File Not Found or Parse Error
+            frame.rax = ENOENT as u64;
         },
 
-        60 => { // SYS_EXIT
-            // 0. DISABLE INTERRUPTS to prevent getting buried alive!
-            x86_64::instructions::interrupts::disable();
-
-            let exit_code = arg1 as i64;
-            let curr_idx = percpu.scheduler.core_task_idx[percpu.logical_id as usize % 32];
-            let task = &mut percpu.scheduler.tasks[curr_idx];
-            
-            crate::serial_println!("[PID {}] Exited (Code: {})", task.pid, exit_code);
-            
-            // 1. Safe FD Teardown using Arc Reference Counting
-            for i in 0..32 { 
-                if let Some(FileDescriptor::Socket(sock_mtx)) = &task.fd_table[i] {
-                    if alloc::sync::Arc::strong_count(sock_mtx) == 1 {
-                        let sock = sock_mtx.lock();
-                        if let Some(sockets) = crate::drivers::net::GLOBAL_SOCKETS.lock().as_mut() {
-                            match sock.kind {
-                                SocketKind::Tcp(handle) => {
-                                    let socket = sockets.get_mut::<smoltcp::socket::tcp::Socket>(handle);
-                                    socket.abort(); // Send TCP RST
-                                    sockets.remove(handle); // Free the memory
-                                },
-                                SocketKind::Udp(handle) => {
-                                    sockets.remove(handle);
-                                }
-                            }
-                        }
-                    }
-                }
-                // Safely drop our reference to the FD
-                task.fd_table[i] = None; 
-            }
-
-            // 2. Shred ONLY the user memory tables securely. 
-            // DO NOT swap CR3 to KERNEL_CR3, or the CPU will instantly Triple Fault when trying to use the stack!
-            crate::memory::clear_user_address_space(task.cr3);
-
-            // 3. Mark as Zombie at the VERY END, once all locks are released
-            task.state = crate::scheduler::TaskState::Zombie;
-            
-            // 4. Re-enable interrupts and wait for the scheduler to context-switch away natively
-            unsafe {
-                x86_64::instructions::interrupts::enable();
-                loop { core::arch::asm!("hlt") }
-            }
-        },
+        60 => crate::scheduler::exit_current_user_task(arg1 as i64), // SYS_EXIT
 
         131 => { frame.rax = 0; }, // SYS_SIGALTSTACK
 
@@ -1135,7 +1310,7 @@ pub extern "C" fn syscall_dispatcher(frame: &mut SyscallStackFrame) {
         318 => { // SYS_GETRANDOM (Required for Rust HashMaps)
             let buf_ptr = arg1 as *mut u8;
             let len = arg2 as usize;
-            if is_valid_user_ptr(buf_ptr, len) {
+            if is_writable_user_ptr(buf_ptr, len) {
                 unsafe { core::ptr::write_bytes(buf_ptr, 42, len); }
                 frame.rax = len as u64;
             } else {
@@ -1192,7 +1367,11 @@ pub extern "C" fn syscall_dispatcher(frame: &mut SyscallStackFrame) {
             }
         },
 
-        502 => { // sys_swap_buffers
+        502 => { // sys_swap_buffers (x, y, w, h). The rectangle that changed; w == 0 means all of it
+             if crate::drivers::virtio_gpu::is_active() {
+                 let (w, h) = if arg3 == 0 { (usize::MAX, usize::MAX) } else { (arg3 as usize, arg4 as usize) };
+                 crate::drivers::virtio_gpu::flush_user(arg1 as usize, arg2 as usize, w, h);
+             }
              unsafe {
                  if let Some(gpu) = crate::drivers::gpu::intel::INTEL_GPU.lock().as_mut() {
                      if let Some(p) = &crate::gui::SCREEN_PAINTER {
@@ -1229,9 +1408,13 @@ pub extern "C" fn syscall_dispatcher(frame: &mut SyscallStackFrame) {
         505 => { 
             // THE FIX: Shield the spinlock from hardware interrupts!
             // This prevents IRQ 12 from firing while we are reading the mouse state.
+            // Bits 8..16 carry the wheel notches since the last call, which the read consumes
             let m_val = x86_64::instructions::interrupts::without_interrupts(|| {
-                let m = crate::mouse::MOUSE_STATE.lock();
-                (m.x as u64) << 32 | (m.y as u64) << 16 | (if m.left_click {1} else {0}) << 1 | (if m.right_click {1} else {0})
+                let mut m = crate::mouse::MOUSE_STATE.lock();
+                let wheel = m.wheel.clamp(i8::MIN as i32, i8::MAX as i32);
+                m.wheel -= wheel;
+                (m.x as u64) << 32 | (m.y as u64) << 16 | ((wheel as i8 as u8) as u64) << 8
+                    | (if m.middle_click {1} else {0}) << 2 | (if m.left_click {1} else {0}) << 1 | (if m.right_click {1} else {0})
             });
             frame.rax = m_val;
         },
@@ -1241,7 +1424,7 @@ pub extern "C" fn syscall_dispatcher(frame: &mut SyscallStackFrame) {
         507 => { 
              unsafe {
                  if let Some(p) = &crate::SCREEN_PAINTER {
-                     if is_valid_user_ptr(arg1 as *const u8, 8) && is_valid_user_ptr(arg2 as *const u8, 8) && is_valid_user_ptr(arg3 as *const u8, 8) {
+                     if is_writable_user_ptr(arg1 as *const u8, 8) && is_writable_user_ptr(arg2 as *const u8, 8) && is_writable_user_ptr(arg3 as *const u8, 8) {
                          *(arg1 as *mut u64) = p.info.width as u64;
                          *(arg2 as *mut u64) = p.info.height as u64;
                          *(arg3 as *mut u64) = if p.info.stride > 0 { p.info.stride } else { p.info.width } as u64;
@@ -1275,6 +1458,9 @@ pub extern "C" fn syscall_dispatcher(frame: &mut SyscallStackFrame) {
                 
                 if mapped_phys != 0 && size != 0 {
                     if let Ok(user_virt) = crate::memory::map_user_framebuffer(mapped_phys, size) {
+                        // The compositor draws from here on
+                        let curr_idx = percpu.scheduler.core_task_idx[percpu.logical_id as usize % 32];
+                        crate::gui::release_screen(percpu.scheduler.tasks.get(curr_idx).map_or(0, |t| t.pid));
                         frame.rax = user_virt;
                     } else { frame.rax = 0; }
                 } else { frame.rax = 0; }
@@ -1334,15 +1520,10 @@ pub extern "C" fn syscall_dispatcher(frame: &mut SyscallStackFrame) {
             let path_ptr = arg1 as *const u8;
             let path_len = arg2 as usize;
             
-            // 🔥 FIX: Wrap raw slice creation in an unsafe block
-            let path_slice = unsafe { core::slice::from_raw_parts(path_ptr, path_len) };
-            
-            if let Ok(path) = core::str::from_utf8(path_slice) {
-                let list = crate::vfs::VFS.list_dir(path);
-                frame.rax = list.len() as u64;
-            } else {
-                frame.rax = 0;
-            }
+            frame.rax = match crate::uaccess::read_user_str(path_ptr as u64, path_len) {
+                Ok(path) => crate::vfs::VFS.list_dir(&path).len() as u64,
+                Err(e) => e as u64,
+            };
         }
         
         // Syscall 511: Get Directory Item String by Index
@@ -1352,27 +1533,18 @@ pub extern "C" fn syscall_dispatcher(frame: &mut SyscallStackFrame) {
             let path_ptr = arg3 as *const u8;
             let path_len = arg4 as usize;
             
-            // 🔥 FIX: Wrap raw slice creation in an unsafe block
-            let path_slice = unsafe { core::slice::from_raw_parts(path_ptr, path_len) };
+            let path = match crate::uaccess::read_user_str(path_ptr as u64, path_len) {
+                Ok(p) => p,
+                Err(e) => { frame.rax = e as u64; return; }
+            };
             
-            if let Ok(path) = core::str::from_utf8(path_slice) {
-                let list = crate::vfs::VFS.list_dir(path);
-                
-                if let Some(entry) = list.get(index) {
-                    let bytes = entry.as_bytes();
-                    
-                    // 🔥 FIX: Wrap the memory copy in an unsafe block
-                    unsafe {
-                        core::ptr::copy_nonoverlapping(bytes.as_ptr(), buf_ptr, bytes.len());
-                    }
-                    
-                    frame.rax = bytes.len() as u64;
-                } else {
-                    frame.rax = 0;
-                }
-            } else {
-                frame.rax = 0;
-            }
+            frame.rax = match crate::vfs::VFS.list_dir(&path).get(index) {
+                Some(entry) => match crate::uaccess::copy_to_user(buf_ptr as u64, entry.as_bytes()) {
+                    Ok(()) => entry.len() as u64,
+                    Err(e) => e as u64,
+                },
+                None => ENOENT as u64,
+            };
         }
         513 => { // sys_wait_vsync
             unsafe {
@@ -1386,7 +1558,7 @@ pub extern "C" fn syscall_dispatcher(frame: &mut SyscallStackFrame) {
         517 => {
             let buf_ptr = arg1 as *mut u8;
             let buf_len = arg2 as usize;
-            if !is_valid_user_ptr(buf_ptr, buf_len) { frame.rax = EFAULT as u64; return; }
+            if !is_writable_user_ptr(buf_ptr, buf_len) { frame.rax = EFAULT as u64; return; }
             
             let mcfg = unsafe { crate::acpi::ACPI_INFO.mcfg_addr.unwrap_or(0) };
             let madt = unsafe { crate::acpi::ACPI_INFO.madt_addr.unwrap_or(0) };
@@ -1397,17 +1569,18 @@ pub extern "C" fn syscall_dispatcher(frame: &mut SyscallStackFrame) {
             frame.rax = len as u64;
         },
 
-        518 => { 
-            let buf_ptr = arg1 as *mut u8;
-            let buf_len = arg2 as usize;
-            if !is_valid_user_ptr(buf_ptr, buf_len) { frame.rax = EFAULT as u64; return; }
-            
-            unsafe {
-                let log_len = core::cmp::min(crate::serial::BOOT_LOG_IDX, 8192);
-                let copy_len = core::cmp::min(buf_len, log_len);
-                for i in 0..copy_len { *buf_ptr.add(i) = crate::serial::BOOT_LOG[i]; }
-                frame.rax = copy_len as u64;
+        518 => { // SYS_READ_BOOT_LOG (buf, len, offset, start_out). Copies kernel log text from the
+                 // absolute offset on; writes the offset actually read from (later if that part was
+                 // overwritten) to start_out when non-null. Returns the byte count.
+            // The log is copied out under its lock, and only reaches user memory
+            // (which may fault) once the lock is dropped
+            let mut out = alloc::vec![0u8; (arg2 as usize).min(crate::serial::BOOT_LOG_SIZE)];
+            let (start, len) = x86_64::instructions::interrupts::without_interrupts(|| crate::serial::BOOT_LOG.lock().read_at(arg3, &mut out));
+            if let Err(e) = crate::uaccess::copy_to_user(arg1, &out[..len]) { frame.rax = e as u64; return; }
+            if arg4 != 0 {
+                if let Err(e) = crate::uaccess::copy_to_user(arg4, &start.to_ne_bytes()) { frame.rax = e as u64; return; }
             }
+            frame.rax = len as u64;
         },
 
         519 => { 
@@ -1421,15 +1594,14 @@ pub extern "C" fn syscall_dispatcher(frame: &mut SyscallStackFrame) {
             let target_addr = task.mmap_bump;
             task.mmap_bump += (num_pages as u64) * 0x1000;
 
-            match crate::memory::allocate_user_pages_at(target_addr, num_pages) {
-                Ok(mapped_addr) => frame.rax = mapped_addr,
-                Err(_) => frame.rax = 0, 
-            }
+            // Reserved lazily like anonymous mmap: untouched pages cost nothing
+            task.regions.lock().vmas.push(crate::process::Vma { start: target_addr, end: task.mmap_bump });
+            frame.rax = target_addr;
         },
 
         520 => { 
             let buf_ptr = arg1 as *mut u8;
-            if arg2 as usize >= 32 && is_valid_user_ptr(buf_ptr, 32) { 
+            if arg2 as usize >= 32 && is_writable_user_ptr(buf_ptr, 32) { 
                 unsafe { for i in 0..32 { *buf_ptr.add(i) = crate::entity::seed::GENETIC_SEED[i]; } }
                 frame.rax = 1; 
             } else { frame.rax = EFAULT as u64; }
@@ -1437,7 +1609,7 @@ pub extern "C" fn syscall_dispatcher(frame: &mut SyscallStackFrame) {
 
         521 => { 
             let buf_ptr = arg1 as *mut f32;
-            if arg2 as usize >= 4 && is_valid_user_ptr(buf_ptr as *const u8, 16) {
+            if arg2 as usize >= 4 && is_writable_user_ptr(buf_ptr as *const u8, 16) {
                 unsafe {
                     *buf_ptr.add(0) = crate::entity::state::ENTITY_STATE.get_energy();
                     *buf_ptr.add(1) = crate::entity::state::ENTITY_STATE.get_entropy();
@@ -1450,12 +1622,227 @@ pub extern "C" fn syscall_dispatcher(frame: &mut SyscallStackFrame) {
 
         522 => { frame.rax = crate::smp::ACTIVE_CORES.load(Ordering::SeqCst) as u64; },
         523 => { frame.rax = crate::scheduler::CONTEXT_SWITCHES.load(Ordering::Relaxed); },
+        546 => { frame.rax = crate::scheduler::TASKS_EXITED.load(Ordering::Relaxed); }, // SYS_TASKS_EXITED
+        547 => { // SYS_SPAWN (path). Runs an ELF as a new process sharing only our console.
+            let path = match crate::uaccess::read_user_str(arg1, arg2 as usize) {
+                Ok(p) => p,
+                Err(e) => { frame.rax = e as u64; return; }
+            };
+            let image = match crate::vfs::VFS.read_file_alloc(&path) {
+                Some(data) => data,
+                None => { frame.rax = ENOENT as u64; return; }
+            };
+
+            let curr_idx = percpu.scheduler.core_task_idx[percpu.logical_id as usize % 32];
+            if curr_idx >= percpu.scheduler.tasks.len() { frame.rax = EBADF as u64; return; }
+
+            let name = path.rsplit('/').next().unwrap_or(&path);
+            let mut child = match crate::process::Process::spawn(&image, name) {
+                Ok(c) => c,
+                Err(_) => { frame.rax = ENOEXEC as u64; return; }
+            };
+
+            let parent = &percpu.scheduler.tasks[curr_idx];
+            child.parent_pid = Some(parent.pid);
+            for i in 0..3 { child.fd_table[i] = parent.fd_table[i].clone(); }
+
+            frame.rax = child.pid;
+            percpu.scheduler.add(child);
+            crate::scheduler::LIVE_USER_TASKS.fetch_add(1, Ordering::SeqCst);
+        },
+        548 => { // SYS_GET_MEMINFO (info). Physical frames and kernel heap, in bytes.
+            let (total, used) = crate::memory::frame_stats();
+            let info = MemInfo {
+                total: total * 4096,
+                used: used * 4096,
+                free: total.saturating_sub(used) * 4096,
+                kernel_heap_used: crate::allocator::heap_used() as u64,
+                kernel_heap_peak: crate::allocator::heap_peak() as u64,
+                kernel_heap_size: crate::allocator::heap_size() as u64,
+            };
+            let bytes = unsafe {
+                core::slice::from_raw_parts(&info as *const MemInfo as *const u8, core::mem::size_of::<MemInfo>())
+            };
+            frame.rax = match crate::uaccess::copy_to_user(arg1, bytes) { Ok(()) => 0, Err(e) => e as u64 };
+        },
+        549 => { frame.rax = crate::scheduler::CPU_LOAD_PERMILLE.load(Ordering::Relaxed); }, // SYS_CPU_LOAD
+        550 => { // SYS_HEAP_TEST (MiB). Kernel heap stress test, at most STRESS_TEST_MAX_MIB; returns the MiB held at once.
+            frame.rax = match crate::allocator::stress_test(arg1 as usize) {
+                Ok(held) => held as u64,
+                Err(e) => e as u64,
+            };
+        },
+        551 => { // SYS_TASK_STATS (buf, max). Fills up to max TaskStat records across all cores, returns the count.
+            let max = (arg2 as usize).min(256);
+            let mut stats = alloc::vec::Vec::new();
+            if let Some(cores) = unsafe { crate::percpu::PER_CPU.as_mut() } {
+                for core in cores.iter_mut() {
+                    core.scheduler.with_tasks(|tasks| {
+                        for task in tasks.iter().take(max - stats.len()) {
+                            stats.push(TaskStat {
+                                pid: task.pid,
+                                tickets: task.tickets,
+                                cpu_ticks: task.cpu_ticks,
+                                last_run_ms: task.last_run_ms,
+                                state: task.state as u8,
+                                is_idle: task.is_idle as u8,
+                                name: task.name,
+                            });
+                        }
+                    });
+                }
+            }
+            let bytes = unsafe {
+                core::slice::from_raw_parts(stats.as_ptr() as *const u8, stats.len() * core::mem::size_of::<TaskStat>())
+            };
+            frame.rax = match crate::uaccess::copy_to_user(arg1, bytes) { Ok(()) => stats.len() as u64, Err(e) => e as u64 };
+        },
+        552 => { // SYS_SET_FOREGROUND (pid). Moves the foreground ticket boost to pid; 0 clears it. EPERM unless the caller owns the screen
+            if crate::scheduler::current_pid() != crate::gui::screen_owner() { frame.rax = EPERM as u64; return; }
+            crate::scheduler::set_foreground(arg1);
+            frame.rax = 0;
+        },
+        553 => { // SYS_ABI_ECHO (a1..a6). Returns every argument in its own register, XOR'd with ABI_ECHO_XOR.
+            frame.rdi = arg1 ^ ABI_ECHO_XOR;
+            frame.rsi = arg2 ^ ABI_ECHO_XOR;
+            frame.rdx = arg3 ^ ABI_ECHO_XOR;
+            frame.r10 = arg4 ^ ABI_ECHO_XOR;
+            frame.r8 = arg5 ^ ABI_ECHO_XOR;
+            frame.r9 = arg6 ^ ABI_ECHO_XOR;
+            frame.rax = 0;
+        },
+        554 => { // SYS_FB_FORMAT. bytes per pixel in bits 0..8, layout in 8..16 (0 BGR, 1 RGB, 2 greyscale)
+            use bootloader_api::info::PixelFormat;
+            frame.rax = match unsafe { &crate::gui::SCREEN_PAINTER } {
+                Some(p) => {
+                    let format = match p.info.pixel_format {
+                        PixelFormat::Rgb => 1,
+                        PixelFormat::U8 => 2,
+                        // The kernel's own painters draw anything else as BGR too
+                        _ => 0,
+                    };
+                    p.info.bytes_per_pixel as u64 | format << 8
+                },
+                None => 0,
+            };
+        },
+        555 => { // SYS_SET_RESOLUTION (w, h). Picks the desktop's virtual resolution; 0, 0 is the native one.
+            frame.rax = if crate::gui::set_mode(arg1 as usize, arg2 as usize) { 0 } else { EINVAL as u64 };
+        },
+        556 => { // SYS_GET_RESOLUTION. Desktop width << 32 | height
+            let (w, h) = crate::gui::virtual_mode();
+            frame.rax = (w as u64) << 32 | h as u64;
+        },
+        557 => { // SYS_GET_DISPLAYS (buf, max). Fills buf with [x, y, w, h, is_virtual] u32s per display; returns the count
+            let mut out = [[0u32; 5]; crate::display::MAX_DISPLAYS];
+            let mut n = 0;
+            while n < (arg2 as usize).min(crate::display::MAX_DISPLAYS) {
+                let d = match crate::display::get(n) { Some(d) => d, None => break };
+                out[n] = [d.x as u32, d.y as u32, d.w as u32, d.h as u32, d.is_virtual as u32];
+                n += 1;
+            }
+            let bytes = unsafe { core::slice::from_raw_parts(out.as_ptr() as *const u8, n * core::mem::size_of::<[u32; 5]>()) };
+            frame.rax = match crate::uaccess::copy_to_user(arg1, bytes) { Ok(()) => n as u64, Err(e) => e as u64 };
+        },
+        558 => { // SYS_ADD_VIRTUAL_DISPLAY (w, h). Returns the new display's index
+            use crate::gui::{MIN_VIRTUAL_W, MIN_VIRTUAL_H};
+            let size_ok = (MIN_VIRTUAL_W as u64..=8192).contains(&arg1) && (MIN_VIRTUAL_H as u64..=8192).contains(&arg2);
+            let added = if size_ok { crate::display::add_virtual(arg1 as usize, arg2 as usize) } else { None };
+            frame.rax = match added { Some(index) => index as u64, None => EINVAL as u64 };
+        },
+        559 => { // SYS_HW_CURSOR. 1 when the GPU draws the pointer itself
+            frame.rax = crate::drivers::virtio_gpu::has_hw_cursor() as u64;
+        },
+        560 => { // SYS_FS_DELETE (path). Removes a file; ENOENT if there is none, EACCES if it's read-only
+            let path = match crate::uaccess::read_user_str(arg1, arg2 as usize) {
+                Ok(path) => path,
+                Err(e) => { frame.rax = e as u64; return; }
+            };
+            frame.rax = match crate::vfs::VFS.file_size(&path) {
+                Ok(_) => match crate::vfs::VFS.delete_file(&path) {
+                    Ok(()) => 0,
+                    Err(e) => fs_errno(e) as u64,
+                },
+                Err(e) => fs_errno(e) as u64,
+            };
+        },
+        561 => { // SYS_SET_TICKETS (pid, tickets). Reweights a user task; EPERM for protected ones or raising one's own, ESRCH if none
+            frame.rax = crate::scheduler::set_user_tickets(arg1, arg2) as u64;
+        },
+        562 => { // SYS_KILL (pid). Ends a user task at its next syscall or timer tick in ring 3; EPERM for protected ones, ESRCH if none
+            frame.rax = crate::scheduler::kill(arg1) as u64;
+        },
+        563 => { // SYS_FS_VOLUME_INFO (path, info). Size and free space of the volume holding path.
+            let path = match crate::uaccess::read_user_str(arg1, arg2 as usize) {
+                Ok(p) => p,
+                Err(e) => { frame.rax = e as u64; return; }
+            };
+            frame.rax = match crate::vfs::VFS.volume_info(&path) {
+                Ok(info) => {
+                    let bytes = unsafe {
+                        core::slice::from_raw_parts(&info as *const crate::vfs::VolumeInfo as *const u8, core::mem::size_of::<crate::vfs::VolumeInfo>())
+                    };
+                    match crate::uaccess::copy_to_user(arg3, bytes) { Ok(()) => 0, Err(e) => e as u64 }
+                }
+                Err(e) => fs_errno(e) as u64,
+            };
+        },
+        564 => { // SYS_FS_GENERATION. Counts every file change on any mount; poll it instead of rescanning
+            frame.rax = crate::vfs::generation();
+        },
+        565 => { // SYS_FS_DIR_GENERATION (fd). The SYS_FS_GENERATION a 541 listing was taken at
+            let fd = arg1 as usize;
+            let curr_idx = percpu.scheduler.core_task_idx[percpu.logical_id as usize % 32];
+            if curr_idx >= percpu.scheduler.tasks.len() || fd >= 32 { frame.rax = EBADF as u64; return; }
+            frame.rax = match &percpu.scheduler.tasks[curr_idx].fd_table[fd] {
+                Some(FileDescriptor::Dir(stream)) => stream.generation(),
+                _ => EBADF as u64,
+            };
+        },
+        566 => { // SYS_BEEP (freq_hz, ms). Queues a PC speaker tone and returns at once; freq 0 silences it
+            use crate::sound::{MAX_FREQ_HZ, MAX_TONE_MS, MIN_FREQ_HZ};
+            frame.rax = if arg1 == 0 {
+                crate::sound::stop();
+                0
+            } else if !(MIN_FREQ_HZ as u64..=MAX_FREQ_HZ as u64).contains(&arg1) || arg2 == 0 || arg2 > MAX_TONE_MS as u64 {
+                EINVAL as u64
+            } else if crate::sound::play_tone(arg1 as u32, arg2 as u32) {
+                0
+            } else {
+                // Too many tones already queued
+                EAGAIN as u64
+            };
+        },
+
+        567 => { // SYS_WALL_CLOCK (). Unix seconds, UTC; 0 if the machine has no usable clock.
+            frame.rax = crate::rtc::unix_time();
+        },
+
+        568 => { // SYS_SET_WALL_CLOCK (unix_secs). Wall clock only; the CMOS chip is left alone. EPERM unless init or the compositor
+            if !crate::scheduler::current_is_protected() { frame.rax = EPERM as u64; }
+            else if arg1 == 0 { frame.rax = EINVAL as u64; } else {
+                crate::rtc::set_time(arg1);
+                frame.rax = 0;
+            }
+        },
+
+        569 => { // SYS_UPTIME_US (). Microseconds from the TSC, for timing work shorter than a tick
+            let mhz = crate::time::TSC_MHZ.load(core::sync::atomic::Ordering::Relaxed).max(1);
+            frame.rax = crate::time::rdtsc() / mhz;
+        },
+
+        570 => { // SYS_UNMAP_SHM (id, addr, release). Undoes a 531 mapping; with release the block is freed once nobody maps it. EINVAL if addr isn't that block
+            frame.rax = match crate::memory::unmap_shm_block(arg1, arg2, arg3 != 0) {
+                Ok(()) => 0,
+                Err(_) => EINVAL as u64,
+            };
+        },
         524 => { 
             // SYSCALL 524: sys_get_system_info
             let info_ptr = arg1 as *mut SystemInfo;
             
             // SECURITY: Prevent Userspace from tricking the Kernel into overwriting Ring 0 memory!
-            if !is_valid_user_ptr(info_ptr as *const u8, core::mem::size_of::<SystemInfo>()) {
+            if !is_writable_user_ptr(info_ptr as *const u8, core::mem::size_of::<SystemInfo>()) {
                 frame.rax = EFAULT as u64;
                 return;
             }
@@ -1490,6 +1877,13 @@ pub extern "C" fn syscall_dispatcher(frame: &mut SyscallStackFrame) {
                     }
                 }
                 (*info_ptr).task_count = count as u64;
+
+                // 4. Work queue
+                (*info_ptr).pending_jobs = crate::workqueue::PENDING_JOBS.load(Ordering::Relaxed);
+                (*info_ptr).completed_jobs = crate::workqueue::COMPLETED_JOBS.load(Ordering::Relaxed);
+
+                // 5. Audio hardware
+                (*info_ptr).audio = crate::sound::devices();
             }
             frame.rax = 0;
         },
@@ -1519,7 +1913,7 @@ pub extern "C" fn syscall_dispatcher(frame: &mut SyscallStackFrame) {
                     let curr_idx = percpu.scheduler.core_task_idx[percpu.logical_id as usize % 32];
                     let task = &mut percpu.scheduler.tasks[curr_idx];
                     
-                    if task.wake_tsc == 0 { break; } // Human Input Override (Mouse Touched!)
+                    if task.wake_tsc == 0 { break; } // Human Input Override (Mouse Touched!), or a kill
                     if crate::time::UPTIME_MS.load(core::sync::atomic::Ordering::Relaxed) >= wake_ms { break; } // Time passed!
                     
                     // 4. If we woke up illegally (scheduler fallback), HALT to save battery!
@@ -1536,7 +1930,7 @@ pub extern "C" fn syscall_dispatcher(frame: &mut SyscallStackFrame) {
             let buf_ptr = arg1 as *mut u8;
             let max_len = arg2 as usize;
             
-            if !is_valid_user_ptr(buf_ptr, max_len) { 
+            if !is_writable_user_ptr(buf_ptr, max_len) { 
                 frame.rax = EFAULT as u64; 
                 return; 
             }
@@ -1572,7 +1966,7 @@ pub extern "C" fn syscall_dispatcher(frame: &mut SyscallStackFrame) {
                 } else { frame.rax = 0; }
             } else { frame.rax = 0; }
         },
-        532 => { // SYS_IPC_SEND
+        532 => { // SYS_IPC_SEND (pid, type, data1, data2). 1, or ESRCH if no task has that pid
             let target_pid = arg1;
             let curr_idx = percpu.scheduler.core_task_idx[percpu.logical_id as usize % 32];
             let sender_pid = percpu.scheduler.tasks[curr_idx].pid;
@@ -1586,31 +1980,30 @@ pub extern "C" fn syscall_dispatcher(frame: &mut SyscallStackFrame) {
                 let active_cores = crate::smp::ACTIVE_CORES.load(core::sync::atomic::Ordering::SeqCst);
                 if let Some(cores) = &mut crate::percpu::PER_CPU {
                     for i in 0..active_cores {
-                        for task in cores[i].scheduler.tasks.iter_mut() {
-                            if task.pid == target_pid {
+                        found = cores[i].scheduler.with_tasks(|tasks| match tasks.iter_mut().find(|t| t.pid == target_pid) {
+                            Some(task) => {
                                 task.mailbox.push_back(msg);
                                 // If the task was sleeping forever waiting for IPC, wake it up!
                                 if task.state == crate::scheduler::TaskState::Blocked && task.wake_tsc == u64::MAX {
                                     task.state = crate::scheduler::TaskState::Ready;
                                     task.wake_tsc = 0;
                                 }
-                                found = true;
-                                break;
+                                true
                             }
-                        }
+                            None => false,
+                        });
                         if found { break; }
                     }
                 }
             }
-            frame.rax = if found { 1 } else { 0 }; 
+            frame.rax = if found { 1 } else { ESRCH as u64 }; 
         },
 
-        533 => { 
-            // SYSCALL 533: sys_ipc_recv
+        533 => { // SYS_IPC_RECV (msg, block). 1 with the next message in msg; EAGAIN if none and not blocking
             let msg_ptr = arg1 as *mut crate::process::IpcMessage;
             let block = arg2 == 1;
             
-            if !is_valid_user_ptr(msg_ptr as *const u8, core::mem::size_of::<crate::process::IpcMessage>()) {
+            if !is_writable_user_ptr(msg_ptr as *const u8, core::mem::size_of::<crate::process::IpcMessage>()) {
                 frame.rax = EFAULT as u64; return;
             }
             
@@ -1638,6 +2031,7 @@ pub extern "C" fn syscall_dispatcher(frame: &mut SyscallStackFrame) {
                         let curr_idx = percpu.scheduler.core_task_idx[percpu.logical_id as usize % 32];
                         let task = &mut percpu.scheduler.tasks[curr_idx];
                         
+                        crate::scheduler::exit_if_killed();
                         if task.mailbox.is_empty() {
                             x86_64::instructions::hlt();
                         }
@@ -1652,20 +2046,251 @@ pub extern "C" fn syscall_dispatcher(frame: &mut SyscallStackFrame) {
                     unsafe { *msg_ptr = msg; }
                     frame.rax = 1; 
                 } else {
-                    frame.rax = 0; 
+                    frame.rax = EAGAIN as u64;
                 }
             }
         },
         534 => { 
             frame.rax = sys_dns_resolve(arg1 as usize, arg2 as usize); 
         },
-        _ => { frame.rax = EINVAL as u64; }
+        535 => { // SYS_FS_RENAME
+            let old_ptr = arg1 as *const u8;
+            let old_len = arg2 as usize;
+            let new_ptr = arg3 as *const u8;
+            let new_len = arg4 as usize;
+            
+            let (old_path, new_path) = match (crate::uaccess::read_user_str(old_ptr as u64, old_len), crate::uaccess::read_user_str(new_ptr as u64, new_len)) {
+                (Ok(old), Ok(new)) => (old, new),
+                (Err(e), _) | (_, Err(e)) => { frame.rax = e as u64; return; }
+            };
+            
+            frame.rax = match crate::vfs::VFS.rename_file(&old_path, &new_path) {
+                Ok(()) => 0,
+                // Unsupported here means the two paths sit on different mounts
+                Err(crate::vfs::FsError::Unsupported) => EXDEV as u64,
+                Err(e) => fs_errno(e) as u64,
+            };
+        },
+        536 => { // SYS_FS_MKDIR (creates missing parents too)
+            let path_ptr = arg1 as *const u8;
+            let path_len = arg2 as usize;
+            frame.rax = match crate::uaccess::read_user_str(path_ptr as u64, path_len) {
+                Ok(path) => match crate::vfs::VFS.create_dir_all(&path) { Ok(()) => 0, Err(e) => fs_errno(e) as u64 },
+                Err(e) => e as u64,
+            };
+        },
+        537 => { // SYS_FS_READ (path, dest, offset). A null dest returns the total file size instead.
+            let path_ptr = arg1 as *const u8;
+            let path_len = arg2 as usize;
+            let dest_ptr = arg3 as *mut u8;
+            let dest_len = arg4 as usize;
+            let offset = arg5 as usize;
+            
+            let path = match crate::uaccess::read_user_str(path_ptr as u64, path_len) {
+                Ok(p) => p,
+                Err(e) => { frame.rax = e as u64; return; }
+            };
+            
+            if dest_ptr.is_null() {
+                frame.rax = match crate::vfs::VFS.file_size(&path) {
+                    Ok(size) => size as u64,
+                    Err(e) => fs_errno(e) as u64,
+                };
+                return;
+            }
+            
+            // The copy is bounded by the caller's buffer, never by the file size
+            if !is_writable_user_ptr(dest_ptr, dest_len) { frame.rax = EFAULT as u64; return; }
+            let size = match crate::vfs::VFS.file_size(&path) {
+                Ok(size) => size,
+                Err(e) => { frame.rax = fs_errno(e) as u64; return; }
+            };
+            let mut dest = alloc::vec![0u8; dest_len.min(size.saturating_sub(offset))];
+            
+            frame.rax = match crate::vfs::VFS.read_file_at(&path, offset, &mut dest) {
+                Ok(n) => {
+                    let n = n.min(dest.len());
+                    match crate::uaccess::copy_to_user(dest_ptr as u64, &dest[..n]) { Ok(()) => n as u64, Err(e) => e as u64 }
+                }
+                Err(e) => fs_errno(e) as u64,
+            };
+        },
+        538 => { // SYS_READ_KEY_EVENT (packed layout documented in shell.rs, 0 = queue empty)
+            frame.rax = crate::shell::pop_key_event().unwrap_or(0);
+        },
+        539 => { // SYS_FS_WRITE (path, src, offset, flags). FS_WRITE_TRUNCATE recreates the file first.
+            const FS_WRITE_TRUNCATE: u64 = 1;
+            // Copy the data and let the work queue write it; errors only reach the serial log
+            const FS_WRITE_ASYNC: u64 = 2;
+            let path_ptr = arg1 as *const u8;
+            let path_len = arg2 as usize;
+            let src_ptr = arg3 as *const u8;
+            let src_len = arg4 as usize;
+            let offset = arg5 as usize;
+            
+            let path = match crate::uaccess::read_user_str(path_ptr as u64, path_len) {
+                Ok(p) => p,
+                Err(e) => { frame.rax = e as u64; return; }
+            };
+            
+            let src = match crate::uaccess::read_user_bytes(src_ptr as u64, src_len) {
+                Ok(bytes) => bytes,
+                Err(e) => { frame.rax = e as u64; return; }
+            };
+
+            if arg6 & FS_WRITE_ASYNC != 0 {
+                // A deferred write can't report failure, so a full disk is turned down here
+                let full = crate::vfs::VFS.volume_info(&path).map_or(false, |v| v.free_blocks == 0);
+                if full && src_len > 0 { frame.rax = ENOSPC as u64; return; }
+                if crate::vfs::VFS.attributes(&path) & crate::vfs::DIRENT_FLAG_READONLY != 0 { frame.rax = EACCES as u64; return; }
+                let data = src;
+                let truncate = arg6 & FS_WRITE_TRUNCATE != 0;
+                crate::workqueue::submit_job(move || {
+                    if truncate {
+                        if let Err(e) = crate::vfs::VFS.create_file(&path) {
+                            crate::serial_println!("[FS] Deferred truncate of {} failed: {:?}", path, e);
+                            return;
+                        }
+                    }
+                    if data.is_empty() { return; }
+                    if let Err(e) = crate::vfs::VFS.write_file_at(&path, offset, &data) {
+                        crate::serial_println!("[FS] Deferred write to {} failed: {:?}", path, e);
+                    }
+                });
+                frame.rax = src_len as u64;
+                return;
+            }
+
+            if arg6 & FS_WRITE_TRUNCATE != 0 {
+                if let Err(e) = crate::vfs::VFS.create_file(&path) { frame.rax = fs_errno(e) as u64; return; }
+            }
+            if src_len == 0 { frame.rax = 0; return; }
+
+            frame.rax = match crate::vfs::VFS.write_file_at(&path, offset, &src) {
+                Ok(n) => n as u64,
+                Err(e) => fs_errno(e) as u64,
+            };
+        },
+        541 => { // SYS_FS_OPENDIR (path). Returns a descriptor for 542; release it with SYS_CLOSE.
+            let path_ptr = arg1 as *const u8;
+            let path_len = arg2 as usize;
+            
+            let path = match crate::uaccess::read_user_str(path_ptr as u64, path_len) {
+                Ok(p) => p,
+                Err(e) => { frame.rax = e as u64; return; }
+            };
+            if !crate::vfs::VFS.is_mounted(&path) { frame.rax = ENOENT as u64; return; }
+            
+            let curr_idx = percpu.scheduler.core_task_idx[percpu.logical_id as usize % 32];
+            if curr_idx >= percpu.scheduler.tasks.len() { frame.rax = EBADF as u64; return; }
+            let task = &mut percpu.scheduler.tasks[curr_idx];
+            
+            frame.rax = match (3..32).find(|&i| task.fd_table[i].is_none()) {
+                Some(fd) => {
+                    let stream = alloc::sync::Arc::new(crate::vfs::DirStream::open(&path));
+                    task.fd_table[fd] = Some(FileDescriptor::Dir(stream));
+                    fd as u64
+                }
+                None => EMFILE as u64,
+            };
+        },
+        542 => { // SYS_FS_READDIR (fd, buf). Fills buf with packed entries, returns how many.
+            let fd = arg1 as usize;
+            let buf_ptr = arg2 as *mut u8;
+            let buf_len = arg3 as usize;
+            
+            if !is_writable_user_ptr(buf_ptr, buf_len) { frame.rax = EFAULT as u64; return; }
+            let curr_idx = percpu.scheduler.core_task_idx[percpu.logical_id as usize % 32];
+            if curr_idx >= percpu.scheduler.tasks.len() || fd >= 32 { frame.rax = EBADF as u64; return; }
+            
+            frame.rax = match &percpu.scheduler.tasks[curr_idx].fd_table[fd] {
+                Some(FileDescriptor::Dir(stream)) => {
+                    let mut buf = alloc::vec![0u8; buf_len];
+                    match stream.fill(&mut buf) {
+                        Some(n) => match crate::uaccess::copy_to_user(buf_ptr as u64, &buf) { Ok(()) => n as u64, Err(e) => e as u64 },
+                        None => EINVAL as u64,
+                    }
+                }
+                _ => EBADF as u64,
+            };
+        },
+        543 => { // SYS_BLOCK_DEVICES (buf, max). Fills up to max BlockDeviceInfo records, returns the count.
+            let buf_ptr = arg1 as *mut BlockDeviceInfo;
+            let max = arg2 as usize;
+            let bytes = max.saturating_mul(core::mem::size_of::<BlockDeviceInfo>());
+            if !is_writable_user_ptr(buf_ptr as *const u8, bytes) { frame.rax = EFAULT as u64; return; }
+            
+            let mut count = 0;
+            unsafe {
+                if let Some(ref driver) = crate::fs::GLOBAL_NVME {
+                    for ns in driver.namespaces.iter().take(max) {
+                        *buf_ptr.add(count) = BlockDeviceInfo {
+                            nsid: ns.nsid, lba_size: ns.lba_size, capacity_lbas: ns.capacity,
+                            mounted: ns.mounted as u32, _pad: 0,
+                        };
+                        count += 1;
+                    }
+                }
+            }
+            frame.rax = count as u64;
+        },
+        544 => { // SYS_DISK_INFO (buf, max). NVMe namespaces, then identified SATA ports. Returns the count.
+            let buf_ptr = arg1 as *mut DiskInfoRecord;
+            let max = arg2 as usize;
+            let bytes = max.saturating_mul(core::mem::size_of::<DiskInfoRecord>());
+            if !is_writable_user_ptr(buf_ptr as *const u8, bytes) { frame.rax = EFAULT as u64; return; }
+
+            let mut records = alloc::vec::Vec::new();
+            let record = |kind, id, sector_size, sectors, name: &str| {
+                let mut model = [0u8; 40];
+                let len = name.len().min(40);
+                model[..len].copy_from_slice(&name.as_bytes()[..len]);
+                DiskInfoRecord { kind, id, sector_size, model_len: len as u32, sectors, model }
+            };
+            unsafe {
+                if let Some(ref driver) = crate::fs::GLOBAL_NVME {
+                    for ns in &driver.namespaces {
+                        records.push(record(DISK_KIND_NVME, ns.nsid, ns.lba_size, ns.capacity, &driver.model));
+                    }
+                }
+                if let Some(ref sata) = crate::fs::GLOBAL_SATA {
+                    for (port, disk) in sata.driver.disks.iter().enumerate() {
+                        if let Some(d) = disk {
+                            records.push(record(DISK_KIND_SATA, port as u32, d.sector_size, d.sectors, &d.model));
+                        }
+                    }
+                }
+                if let Some(ref usb) = crate::fs::GLOBAL_USB_DISK {
+                    records.push(record(DISK_KIND_USB, usb.slot as u32, crate::usb_storage::SECTOR_SIZE as u32, usb.sectors, &usb.model));
+                }
+                for (i, r) in records.iter().take(max).enumerate() { *buf_ptr.add(i) = *r; }
+            }
+            frame.rax = records.len().min(max) as u64;
+        },
+        545 => { // SYS_CONSOLE_READ (buf, len). Drains output written to the caller's console (fd 1).
+            let buf_ptr = arg1 as *mut u8;
+            let len = arg2 as usize;
+            if !is_writable_user_ptr(buf_ptr, len) { frame.rax = EFAULT as u64; return; }
+
+            let curr_idx = percpu.scheduler.core_task_idx[percpu.logical_id as usize % 32];
+            if curr_idx >= percpu.scheduler.tasks.len() { frame.rax = EBADF as u64; return; }
+            frame.rax = match &percpu.scheduler.tasks[curr_idx].fd_table[1] {
+                Some(FileDescriptor::Console(console)) => {
+                    let mut dest = alloc::vec![0u8; len];
+                    let n = console.drain(&mut dest);
+                    match crate::uaccess::copy_to_user(buf_ptr as u64, &dest[..n]) { Ok(()) => n as u64, Err(e) => e as u64 }
+                }
+                _ => EBADF as u64,
+            };
+        },
+        _ => { frame.rax = ENOSYS as u64; }
     }
 }
 
 fn sys_read_internal(fd: usize, buf_ptr: *mut u8, len: usize) -> isize {
-    if !is_valid_user_ptr(buf_ptr, len) { return EFAULT as isize; }
-    if len == 0 || fd >= 32 { return EBADF as isize; }
+    if fd >= 32 { return EBADF as isize; }
+    if len == 0 { return 0; }
+    if !is_writable_user_ptr(buf_ptr, len) { return EFAULT as isize; }
     
     if KERNEL_CR3.load(Ordering::Relaxed) == 0 { return EBADF as isize; }
     let percpu = crate::percpu::current();
@@ -1680,8 +2305,11 @@ fn sys_read_internal(fd: usize, buf_ptr: *mut u8, len: usize) -> isize {
     if let Some(fd_enum) = &task.fd_table[fd] {
         match fd_enum {
             FileDescriptor::File(open_file) => {
-                let buf_slice = unsafe { core::slice::from_raw_parts_mut(buf_ptr, len) };
-                return open_file.read(buf_slice) as isize;
+                let mut buf = alloc::vec![0u8; len];
+                return match open_file.read(&mut buf) {
+                    Ok(n) => copied_out(buf_ptr, &buf[..n.min(len)]),
+                    Err(e) => fs_errno(e) as isize,
+                };
             },
             FileDescriptor::Socket(sock_mtx) => {
                 crate::drivers::net::poll_network();
@@ -1693,9 +2321,7 @@ fn sys_read_internal(fd: usize, buf_ptr: *mut u8, len: usize) -> isize {
                         SocketKind::Udp(handle) => {
                             let socket = sockets.get_mut::<smoltcp::socket::udp::Socket>(handle);
                             if let Ok((data, _meta)) = socket.recv() {
-                                let copy_len = core::cmp::min(data.len(), len);
-                                unsafe { core::ptr::copy_nonoverlapping(data.as_ptr(), buf_ptr, copy_len); }
-                                return copy_len as isize;
+                                return copied_out(buf_ptr, &data[..data.len().min(len)]);
                             }
                         },
                         SocketKind::Tcp(handle) => {
@@ -1708,9 +2334,9 @@ fn sys_read_internal(fd: usize, buf_ptr: *mut u8, len: usize) -> isize {
                                 if let Some(sockets) = sockets_lock.as_mut() {
                                     let socket = sockets.get_mut::<smoltcp::socket::tcp::Socket>(handle);
                                     if socket.can_recv() {
-                                        let user_slice = unsafe { core::slice::from_raw_parts_mut(buf_ptr, len) };
-                                        if let Ok(received) = socket.recv_slice(user_slice) {
-                                            if received > 0 { return received as isize; }
+                                        let mut buf = alloc::vec![0u8; len];
+                                        if let Ok(received) = socket.recv_slice(&mut buf) {
+                                            if received > 0 { return copied_out(buf_ptr, &buf[..received]); }
                                         }
                                     } else if !socket.may_recv() {
                                         return 0; // Connection closed gracefully (EOF)
@@ -1736,32 +2362,34 @@ fn sys_read_internal(fd: usize, buf_ptr: *mut u8, len: usize) -> isize {
             },
             FileDescriptor::PipeRead(pipe_mtx) => {
                 let mut pipe = pipe_mtx.lock();
-                let mut bytes_read = 0;
-                while bytes_read < len {
-                    if let Some(b) = pipe.pop_front() {
-                        unsafe { *buf_ptr.add(bytes_read) = b; }
-                        bytes_read += 1;
-                    } else { break; }
-                }
+                let n = len.min(pipe.len());
+                let bytes: alloc::vec::Vec<u8> = pipe.drain(..n).collect();
                 
-                if bytes_read == 0 { 
+                if bytes.is_empty() { 
                     if alloc::sync::Arc::strong_count(pipe_mtx) == 1 {
                         return 0; // EOF
                     } else {
                         return EAGAIN as isize; 
                     }
                 }
-                return bytes_read as isize;
+                return copied_out(buf_ptr, &bytes);
             },
             FileDescriptor::PipeWrite(_) => return EBADF as isize,
+            // stdin has no producer yet, so reads see end-of-file
+            FileDescriptor::Console(_) => return 0,
+            FileDescriptor::Dir(_) => return EISDIR as isize,
         }
     }
     EBADF as isize 
 }
 
 fn sys_write_internal(fd: usize, buf_ptr: *const u8, len: usize) -> isize {
-    if !is_valid_user_ptr(buf_ptr, len) { return EFAULT as isize; }
-    if len == 0 || fd >= 32 { return EBADF as isize; }
+    if fd >= 32 { return EBADF as isize; }
+    if len == 0 { return 0; }
+    let buf_slice = match crate::uaccess::read_user_bytes(buf_ptr as u64, len) {
+        Ok(bytes) => bytes,
+        Err(e) => return e as isize,
+    };
     
     if KERNEL_CR3.load(Ordering::Relaxed) == 0 { return EBADF as isize; }
     let percpu = crate::percpu::current();
@@ -1771,12 +2399,13 @@ fn sys_write_internal(fd: usize, buf_ptr: *const u8, len: usize) -> isize {
     let curr_idx = percpu.scheduler.core_task_idx[percpu.logical_id as usize % 32];
     if curr_idx >= percpu.scheduler.tasks.len() { return EBADF as isize; }
     let task = &mut percpu.scheduler.tasks[curr_idx];
-    
-    let buf_slice = unsafe { core::slice::from_raw_parts(buf_ptr, len) };
 
     if let Some(fd_enum) = &task.fd_table[fd] {
         match fd_enum {
-            FileDescriptor::File(open_file) => return open_file.write(buf_slice) as isize,
+            FileDescriptor::File(open_file) => return match open_file.write(&buf_slice) {
+                Ok(n) => n as isize,
+                Err(e) => fs_errno(e) as isize,
+            },
             FileDescriptor::Socket(sock_mtx) => {
                 crate::drivers::net::poll_network(); 
 
@@ -1788,7 +2417,7 @@ fn sys_write_internal(fd: usize, buf_ptr: *const u8, len: usize) -> isize {
                         SocketKind::Udp(handle) => {
                             let socket = sockets.get_mut::<smoltcp::socket::udp::Socket>(handle);
                             if let Some(endpoint) = sock.remote {
-                                if socket.send_slice(buf_slice, endpoint).is_ok() {
+                                if socket.send_slice(&buf_slice, endpoint).is_ok() {
                                     return buf_slice.len() as isize;
                                 }
                             }
@@ -1803,7 +2432,7 @@ fn sys_write_internal(fd: usize, buf_ptr: *const u8, len: usize) -> isize {
                                 if let Some(sockets) = sockets_lock.as_mut() {
                                     let socket = sockets.get_mut::<smoltcp::socket::tcp::Socket>(handle);
                                     if socket.can_send() {
-                                        if let Ok(sent) = socket.send_slice(buf_slice) {
+                                        if let Ok(sent) = socket.send_slice(&buf_slice) {
                                             return sent as isize;
                                         }
                                     }
@@ -1827,15 +2456,17 @@ fn sys_write_internal(fd: usize, buf_ptr: *const u8, len: usize) -> isize {
             },
             FileDescriptor::PipeWrite(pipe_mtx) => {
                 let mut pipe = pipe_mtx.lock();
-                for &b in buf_slice { pipe.push_back(b); }
+                pipe.extend(buf_slice.iter());
                 return len as isize;
             },
             FileDescriptor::PipeRead(_) => return EBADF as isize,
+            FileDescriptor::Console(console) => return console.write(&buf_slice) as isize,
+            FileDescriptor::Dir(_) => return EISDIR as isize,
         }
     }
 
     if fd == 1 || fd == 2 {
-        if let Ok(s) = core::str::from_utf8(buf_slice) {
+        if let Ok(s) = core::str::from_utf8(&buf_slice) {
             crate::serial_print!("{}", s); 
         }
         return len as isize;
@@ -1895,26 +2526,20 @@ pub extern "C" fn sys_socket(_domain: u64, _typ: u64, _protocol: u64) -> i64 {
 
 #[no_mangle]
 pub extern "C" fn sys_connect(fd: usize, addr_ptr: *const u8, addr_len: usize) -> i64 {
-    if addr_len < 16 || !is_valid_user_ptr(addr_ptr, addr_len) { return EFAULT; }
+    let remote = match read_sockaddr(addr_ptr, addr_len) { Ok(e) => e, Err(e) => return e };
     if KERNEL_CR3.load(Ordering::Relaxed) == 0 { return EBADF; }
     
     let percpu = crate::percpu::current();
     let curr_idx = percpu.scheduler.core_task_idx[percpu.logical_id as usize % 32];
     if curr_idx >= percpu.scheduler.tasks.len() { return EBADF; }
-    
-    let sockaddr = unsafe { &*(addr_ptr as *const SockAddrIn) };
-    if sockaddr.sin_family != 2 { return EINVAL; }
 
-    let port = u16::from_be(sockaddr.sin_port);
-    let ip = sockaddr.sin_addr;
     let task = &mut percpu.scheduler.tasks[curr_idx];
     
     if fd >= 32 { return EBADF; }
     
     if let Some(FileDescriptor::Socket(sock_mtx)) = &task.fd_table[fd] {
         let mut sock = sock_mtx.lock();
-        let addr = IpAddress::Ipv4(Ipv4Address::new(ip[0], ip[1], ip[2], ip[3]));
-        sock.remote = Some(IpEndpoint::new(addr, port));
+        sock.remote = Some(remote);
         
         if let SocketKind::Tcp(handle) = sock.kind {
             let local_port = sock.local_port;
@@ -1925,7 +2550,7 @@ pub extern "C" fn sys_connect(fd: usize, addr_ptr: *const u8, addr_len: usize) -
                 let mut iface_lock = crate::drivers::net::NET_IFACE.lock();
                 if let (Some(sockets), Some(iface)) = (sockets_lock.as_mut(), iface_lock.as_mut()) {
                     let socket = sockets.get_mut::<smoltcp::socket::tcp::Socket>(handle);
-                    if socket.connect(iface.context(), IpEndpoint::new(addr, port), local_port).is_err() {
+                    if socket.connect(iface.context(), remote, local_port).is_err() {
                         return ECONNREFUSED;
                     }
                 } else { return EBADF; }
@@ -1976,6 +2601,151 @@ pub extern "C" fn sys_connect(fd: usize, addr_ptr: *const u8, addr_len: usize) -
     EBADF
 }
 
+// ==========================================
+// DATAGRAM SOCKETS
+// ==========================================
+// bind/sendto/recvfrom in the Linux shape (same numbers, sockaddr_in, flags
+// ignored). A UDP socket's queues are fixed when it's created (8 datagrams,
+// 2 KiB each way), smoltcp hands each arriving datagram to the socket bound
+// to its port and checks and fills in the checksums. UDP never blocks:
+// recvfrom on an empty queue is EAGAIN. Without an address, sendto and
+// recvfrom are plain write and read.
+
+/// The calling task's socket on `fd`.
+fn current_socket(fd: usize) -> Option<Arc<Mutex<KernelSocket>>> {
+    if fd >= 32 || KERNEL_CR3.load(Ordering::Relaxed) == 0 { return None; }
+    let percpu = crate::percpu::current();
+    let curr_idx = percpu.scheduler.core_task_idx[percpu.logical_id as usize % 32];
+    if curr_idx >= percpu.scheduler.tasks.len() { return None; }
+    match &percpu.scheduler.tasks[curr_idx].fd_table[fd] {
+        Some(FileDescriptor::Socket(sock_mtx)) => Some(sock_mtx.clone()),
+        _ => None,
+    }
+}
+
+/// Copies `bytes` out to the caller's `buf_ptr`; their count, or EFAULT.
+fn copied_out(buf_ptr: *mut u8, bytes: &[u8]) -> isize {
+    match crate::uaccess::copy_to_user(buf_ptr as u64, bytes) {
+        Ok(()) => bytes.len() as isize,
+        Err(e) => e as isize,
+    }
+}
+
+/// The sockaddr_in a syscall was handed, copied in.
+fn read_sockaddr(addr_ptr: *const u8, addr_len: usize) -> Result<IpEndpoint, i64> {
+    if addr_len < core::mem::size_of::<SockAddrIn>() { return Err(EFAULT); }
+    let mut raw = [0u8; core::mem::size_of::<SockAddrIn>()];
+    crate::uaccess::copy_from_user(&mut raw, addr_ptr as u64)?;
+    if u16::from_ne_bytes([raw[0], raw[1]]) != 2 { return Err(EINVAL); }
+    let ip = Ipv4Address::new(raw[4], raw[5], raw[6], raw[7]);
+    Ok(IpEndpoint::new(IpAddress::Ipv4(ip), u16::from_be_bytes([raw[2], raw[3]])))
+}
+
+/// Whether a sockaddr_in fits at addr_ptr, by the length at addr_len_ptr.
+fn sockaddr_writable(addr_ptr: *mut u8, addr_len_ptr: *mut u32) -> bool {
+    let size = core::mem::size_of::<SockAddrIn>();
+    if !is_writable_user_ptr(addr_len_ptr as *const u8, 4) { return false; }
+    let mut addr_len = [0u8; 4];
+    if crate::uaccess::copy_from_user(&mut addr_len, addr_len_ptr as u64).is_err() { return false; }
+    u32::from_ne_bytes(addr_len) as usize >= size && is_writable_user_ptr(addr_ptr, size)
+}
+
+fn write_sockaddr(addr_ptr: *mut u8, addr_len_ptr: *mut u32, endpoint: IpEndpoint) -> bool {
+    let size = core::mem::size_of::<SockAddrIn>();
+    if !sockaddr_writable(addr_ptr, addr_len_ptr) { return false; }
+    let IpAddress::Ipv4(ip) = endpoint.addr else { return false };
+    // sockaddr_in byte for byte: family, port (big-endian), address, zero padding
+    let mut raw = [0u8; core::mem::size_of::<SockAddrIn>()];
+    raw[0..2].copy_from_slice(&2u16.to_ne_bytes());
+    raw[2..4].copy_from_slice(&endpoint.port.to_be_bytes());
+    raw[4..8].copy_from_slice(&ip.0);
+    crate::uaccess::copy_to_user(addr_ptr as u64, &raw).is_ok()
+        && crate::uaccess::copy_to_user(addr_len_ptr as u64, &(size as u32).to_ne_bytes()).is_ok()
+}
+
+/// SYS_BIND (fd, addr, len): the local port a socket sends from and, for
+/// UDP, receives on. EADDRINUSE if another UDP socket has the port.
+pub fn sys_bind(fd: usize, addr_ptr: *const u8, addr_len: usize) -> i64 {
+    let endpoint = match read_sockaddr(addr_ptr, addr_len) { Ok(e) => e, Err(e) => return e };
+    let sock_mtx = match current_socket(fd) { Some(s) => s, None => return EBADF };
+    // Port 0 asks for any port, which every socket already has
+    if endpoint.port == 0 { return 0; }
+    let mut sock = sock_mtx.lock();
+    if let SocketKind::Udp(handle) = sock.kind {
+        let mut sockets_lock = crate::drivers::net::GLOBAL_SOCKETS.lock();
+        let sockets = match sockets_lock.as_mut() { Some(s) => s, None => return EBADF };
+        let taken = sockets.iter().any(|(h, s)| h != handle && matches!(s, smoltcp::socket::Socket::Udp(u) if u.endpoint().port == endpoint.port));
+        if taken { return EADDRINUSE; }
+        let socket = sockets.get_mut::<smoltcp::socket::udp::Socket>(handle);
+        socket.close();
+        if socket.bind(endpoint.port).is_err() { return EINVAL; }
+    }
+    sock.local_port = endpoint.port;
+    0
+}
+
+/// SYS_SENDTO (fd, buf, len, flags, addr, addr_len): one datagram to addr.
+/// EMSGSIZE past MAX_UDP_PAYLOAD, since nothing here fragments; EAGAIN while
+/// the send queue is full.
+pub fn sys_sendto(fd: usize, buf_ptr: *const u8, len: usize, addr_ptr: *const u8, addr_len: usize) -> i64 {
+    if addr_ptr.is_null() { return sys_write_internal(fd, buf_ptr, len) as i64; }
+    let endpoint = match read_sockaddr(addr_ptr, addr_len) { Ok(e) => e, Err(e) => return e };
+    let sock_mtx = match current_socket(fd) { Some(s) => s, None => return EBADF };
+    // Cloned out so the write path can take the lock itself
+    let kind = sock_mtx.lock().kind.clone();
+    let handle = match kind {
+        SocketKind::Udp(handle) => handle,
+        // A stream is already connected and goes where it goes
+        SocketKind::Tcp(_) => return sys_write_internal(fd, buf_ptr, len) as i64,
+    };
+    if len > crate::drivers::net::MAX_UDP_PAYLOAD { return EMSGSIZE; }
+    let data = match crate::uaccess::read_user_bytes(buf_ptr as u64, len) { Ok(d) => d, Err(e) => return e };
+    {
+        let mut sockets_lock = crate::drivers::net::GLOBAL_SOCKETS.lock();
+        let sockets = match sockets_lock.as_mut() { Some(s) => s, None => return EBADF };
+        let socket = sockets.get_mut::<smoltcp::socket::udp::Socket>(handle);
+        match socket.send_slice(&data, endpoint) {
+            Ok(()) => {}
+            Err(smoltcp::socket::udp::SendError::BufferFull) => return EAGAIN,
+            Err(smoltcp::socket::udp::SendError::Unaddressable) => return EINVAL,
+        }
+    }
+    // Out on the wire now rather than at the idle task's next poll
+    crate::drivers::net::poll_network();
+    len as i64
+}
+
+/// SYS_RECVFROM (fd, buf, len, flags, addr, addr_len): the next queued
+/// datagram, cut to len, with its sender in addr. EAGAIN when none is queued.
+pub fn sys_recvfrom(fd: usize, buf_ptr: *mut u8, len: usize, addr_ptr: *mut u8, addr_len_ptr: *mut u32) -> i64 {
+    if addr_ptr.is_null() { return sys_read_internal(fd, buf_ptr, len) as i64; }
+    let sock_mtx = match current_socket(fd) { Some(s) => s, None => return EBADF };
+    let kind = sock_mtx.lock().kind.clone();
+    let handle = match kind {
+        SocketKind::Udp(handle) => handle,
+        // A stream's sender is whoever it's connected to
+        SocketKind::Tcp(_) => {
+            let n = sys_read_internal(fd, buf_ptr, len) as i64;
+            let remote = sock_mtx.lock().remote;
+            if let (true, Some(peer)) = (n >= 0, remote) {
+                if !write_sockaddr(addr_ptr, addr_len_ptr, peer) { return EFAULT; }
+            }
+            return n;
+        }
+    };
+    // Checked before recv() so a bad pointer doesn't cost the datagram
+    if (len > 0 && !is_writable_user_ptr(buf_ptr, len)) || !sockaddr_writable(addr_ptr, addr_len_ptr) { return EFAULT; }
+    crate::drivers::net::poll_network();
+    let mut sockets_lock = crate::drivers::net::GLOBAL_SOCKETS.lock();
+    let sockets = match sockets_lock.as_mut() { Some(s) => s, None => return EBADF };
+    let socket = sockets.get_mut::<smoltcp::socket::udp::Socket>(handle);
+    let (data, from) = match socket.recv() { Ok(d) => d, Err(_) => return EAGAIN };
+    let copy_len = data.len().min(len);
+    if crate::uaccess::copy_to_user(buf_ptr as u64, &data[..copy_len]).is_err() { return EFAULT; }
+    if !write_sockaddr(addr_ptr, addr_len_ptr, from) { return EFAULT; }
+    copy_len as i64
+}
+
 pub extern "x86-interrupt" fn rtl8168_interrupt_handler(_stack_frame: x86_64::structures::idt::InterruptStackFrame) {
    
     crate::serial_println!("[ISR] Hardware Interrupt Fired! NIC Woke up the CPU!");
@@ -1984,17 +2754,27 @@ pub extern "x86-interrupt" fn rtl8168_interrupt_handler(_stack_frame: x86_64::st
     crate::apic::end_of_interrupt();
 }
 
+pub extern "x86-interrupt" fn nvme_interrupt_handler(_stack_frame: x86_64::structures::idt::InterruptStackFrame) {
+    crate::drivers::nvme::handle_interrupt();
+    crate::apic::end_of_interrupt();
+}
+
+pub extern "x86-interrupt" fn xhci_interrupt_handler(_stack_frame: x86_64::structures::idt::InterruptStackFrame) {
+    crate::usb::handle_interrupt();
+    crate::apic::end_of_interrupt();
+}
+
 // Domain Name Resolution (DNS)
 #[no_mangle]
 pub extern "C" fn sys_dns_resolve(hostname_ptr: usize, hostname_len: usize) -> u64 {
     if hostname_len == 0 { return 0; }
     if KERNEL_CR3.load(Ordering::Relaxed) == 0 { return 0; }
     
-    let hostname_slice = unsafe { core::slice::from_raw_parts(hostname_ptr as *const u8, hostname_len) };
-    let hostname_str = match core::str::from_utf8(hostname_slice) {
+    let hostname = match crate::uaccess::read_user_str(hostname_ptr as u64, hostname_len) {
         Ok(s) => s,
         Err(_) => return 0,
     };
+    let hostname_str = hostname.as_str();
 
     crate::serial_println!("[DNS] Resolving: {}", hostname_str);
 
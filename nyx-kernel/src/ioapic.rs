@@ -3,6 +3,8 @@
 use core::ptr::{read_volatile, write_volatile};
 
 static mut IOAPIC_VIRT: Option<u64> = None;
+// First GSI served by this IOAPIC (pin 0)
+static mut GSI_BASE: u32 = 0;
 
 // IOAPIC Register Offsets
 const IOREGSEL: u64 = 0x00; // Index Register
@@ -12,7 +14,7 @@ pub fn init() {
     crate::serial_println!("[IOAPIC] Initializing Modern Hardware Routing...");
 
     let phys_addr = match crate::apic::get_ioapic_phys_addr() {
-        Some(addr) => addr,
+        Some((addr, gsi_base)) => { unsafe { GSI_BASE = gsi_base; } addr }
         None => {
             crate::serial_println!("[IOAPIC] ERR: I/O APIC not found in ACPI tables!");
             return;
@@ -46,19 +48,36 @@ unsafe fn write(reg: u8, data: u32) {
     write_volatile((base + IOWIN) as *mut u32, data);
 }
 
+// Redirection entry bits
+const RTE_ACTIVE_LOW: u32 = 1 << 13;
+const RTE_LEVEL: u32 = 1 << 15;
+
 /// Routes a specific hardware IRQ to a specific CPU core's Local APIC.
-/// `irq`: The hardware IRQ line (e.g., 1 for Keyboard, 12 for Mouse)
+/// `irq`: The ISA IRQ line (e.g., 1 for Keyboard, 12 for Mouse); MADT interrupt
+///        source overrides pick the real IOAPIC pin and its polarity/trigger
 /// `apic_id`: The destination CPU core (0 for BSP, 1-7 for APs)
 /// `vector`: The IDT vector index to trigger (e.g., 33 for Keyboard)
 pub fn route_irq(irq: u8, apic_id: u8, vector: u8) {
+    let (gsi, flags) = crate::apic::isa_irq_route(irq);
+    let pin = match gsi.checked_sub(unsafe { GSI_BASE }) {
+        Some(pin) if pin < 24 => pin as u8,
+        _ => {
+            crate::serial_println!("[IOAPIC] ERR: IRQ {} maps to GSI {}, not on this IOAPIC", irq, gsi);
+            return;
+        }
+    };
+
     // Each IRQ redirection entry is 64 bits wide (two 32-bit registers)
-    // IRQ 0 starts at register 0x10, IRQ 1 at 0x12, etc.
-    let reg_low = 0x10 + (irq * 2);
-    let reg_high = 0x10 + (irq * 2) + 1;
+    // Pin 0 starts at register 0x10, pin 1 at 0x12, etc.
+    let reg_low = 0x10 + (pin * 2);
+    let reg_high = 0x10 + (pin * 2) + 1;
 
     // Build the Redirection Table Entry (RTE)
-    // Low 32 bits: Vector, Delivery Mode (Fixed), Polarity, Trigger Mode, Unmasked
-    let low_value = vector as u32; 
+    // Low 32 bits: Vector, Delivery Mode (Fixed), Polarity, Trigger Mode, Unmasked.
+    // MADT flags: polarity 3 = active low, trigger 3 = level; anything else is ISA edge/high.
+    let mut low_value = vector as u32;
+    if flags & 0x3 == 0x3 { low_value |= RTE_ACTIVE_LOW; }
+    if (flags >> 2) & 0x3 == 0x3 { low_value |= RTE_LEVEL; }
     
     // High 32 bits: Destination Local APIC ID in the top 8 bits
     let high_value = (apic_id as u32) << 24;
@@ -68,5 +87,5 @@ pub fn route_irq(irq: u8, apic_id: u8, vector: u8) {
         write(reg_high, high_value);
     }
     
-    crate::serial_println!("[IOAPIC] Routed IRQ {} -> CPU {} (Vector {})", irq, apic_id, vector);
+    crate::serial_println!("[IOAPIC] Routed IRQ {} (pin {}) -> CPU {} (Vector {})", irq, pin, apic_id, vector);
 }
\ No newline at end of file
@@ -0,0 +1,392 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use crate::vfs::VFS;
+use nyx_core::path::join;
+
+// ==========================================
+// KERNEL SHELL COMMANDS
+// ==========================================
+// What a kernel Terminal window runs when Enter is pressed. Nearly all of it
+// only reads: it exists to look at the disk and the network when userspace
+// never came up (a failed mount on real hardware, say). Output comes back as lines; the
+// window keeps them in its scrollback and pages through long results. The
+// serial console runs the same commands.
+
+/// Longest file `cat` prints before cutting it off
+const CAT_MAX_BYTES: usize = 16 * 1024;
+/// Bytes `hexdump` shows: half a sector, 16 per row
+const HEXDUMP_BYTES: usize = 256;
+const SECTOR_SIZE: usize = 512;
+/// Scratch file the filesystem check writes, reads back and deletes
+const SELFTEST_FILE: &str = "/mnt/nvme/.selftest.tmp";
+const SELFTEST_TASK_TIMEOUT_MS: u64 = 2000;
+/// Files the two writer tasks of the stack check overwrite, equally long
+/// since the program below has the length built in
+const SELFTEST_WRITER_FILES: [&str; 2] = ["/mnt/nvme/.selftest-a.tmp", "/mnt/nvme/.selftest-b.tmp"];
+const SELFTEST_WRITER_BYTES: usize = 4096;
+/// Eight synchronous writes each, on whatever disk the root is
+const SELFTEST_WRITERS_TIMEOUT_MS: u64 = 10_000;
+/// Ring 3 program for the stack check, loaded flat: eight times, SYS_FS_WRITE
+/// (539) of the 4096 bytes at offset 128 to the 25-byte path at offset 96,
+/// truncating; exits 0, or 1 as soon as a write comes back short or r12 (a
+/// callee-saved register holding 0x5EED) changed across the syscall.
+const SELFTEST_WRITER_CODE: [u8; 91] = [
+    0xBB, 0x08, 0x00, 0x00, 0x00,               // mov ebx, 8
+    0x41, 0xBC, 0xED, 0x5E, 0x00, 0x00,         // mov r12d, 0x5EED
+    0xB8, 0x1B, 0x02, 0x00, 0x00,               // loop: mov eax, 539
+    0x48, 0x8D, 0x3D, 0x49, 0x00, 0x00, 0x00,   // lea rdi, [rip + path]
+    0xBE, 0x19, 0x00, 0x00, 0x00,               // mov esi, 25
+    0x48, 0x8D, 0x15, 0x5D, 0x00, 0x00, 0x00,   // lea rdx, [rip + data]
+    0x41, 0xBA, 0x00, 0x10, 0x00, 0x00,         // mov r10d, 4096
+    0x45, 0x31, 0xC0,                           // xor r8d, r8d
+    0x41, 0xB9, 0x01, 0x00, 0x00, 0x00,         // mov r9d, 1 (FS_WRITE_TRUNCATE)
+    0x0F, 0x05,                                 // syscall
+    0x48, 0x3D, 0x00, 0x10, 0x00, 0x00,         // cmp rax, 4096
+    0x75, 0x11,                                 // jne fail
+    0x41, 0x81, 0xFC, 0xED, 0x5E, 0x00, 0x00,   // cmp r12d, 0x5EED
+    0x75, 0x08,                                 // jne fail
+    0xFF, 0xCB,                                 // dec ebx
+    0x75, 0xC2,                                 // jnz loop
+    0x31, 0xFF,                                 // xor edi, edi
+    0xEB, 0x05,                                 // jmp exit
+    0xBF, 0x01, 0x00, 0x00, 0x00,               // fail: mov edi, 1
+    0xB8, 0x3C, 0x00, 0x00, 0x00,               // exit: mov eax, 60
+    0x0F, 0x05,                                 // syscall
+    0x0F, 0x0B,                                 // ud2
+];
+const SELFTEST_WRITER_PATH_AT: usize = 96;
+const SELFTEST_WRITER_DATA_AT: usize = 128;
+/// QEMU's isa-debug-exit device (the runner adds it with iobase=0xf4): a
+/// value v written here ends QEMU with exit status (v << 1) | 1
+const QEMU_EXIT_PORT: u16 = 0xF4;
+const QEMU_EXIT_PASS: u32 = 0x10;
+const QEMU_EXIT_FAIL: u32 = 0x11;
+const PING_COUNT: u16 = 4;
+const PING_TIMEOUT_MS: u64 = 1000;
+
+/// Runs one command line and returns its output.
+pub fn run(line: &str) -> Vec<String> {
+    let mut args = line.split_whitespace();
+    let cmd = match args.next() { Some(c) => c, None => return Vec::new() };
+    let args: Vec<&str> = args.collect();
+    match cmd {
+        "help" => help(),
+        "ls" => ls(args.first().copied().unwrap_or("/")),
+        "cat" => match args.first() {
+            Some(path) => cat(path),
+            None => alloc::vec![String::from("usage: cat <file>")],
+        },
+        "hexdump" => match args.first().and_then(|a| parse_number(a)) {
+            Some(lba) => hexdump(lba, args.get(1).and_then(|a| parse_number(a)).unwrap_or(0) as usize),
+            None => alloc::vec![String::from("usage: hexdump <sector> [offset]")],
+        },
+        "mountinfo" => mountinfo(),
+        "present" => present(),
+        "selftest" => selftest(args.first() == Some(&"--exit")),
+        "ifconfig" => ifconfig(&args),
+        "ping" => match args.first() {
+            Some(ip) => ping(ip),
+            None => alloc::vec![String::from("usage: ping <ip>")],
+        },
+        _ => alloc::vec![format!("{}: unknown command (try 'help')", cmd)],
+    }
+}
+
+fn help() -> Vec<String> {
+    [
+        "ls [path]               list a directory with file sizes",
+        "cat <file>              print a text file",
+        "hexdump <sector> [off]  256 bytes of a raw 512-byte disk sector",
+        "mountinfo               where the root filesystem was mounted from",
+        "present                 cost of the last full-screen and partial screen copies",
+        "selftest [--exit]       heap, filesystem, scheduler and stack checks; --exit quits QEMU",
+        "ifconfig [ip[/n] [gw]]  show the network address, or set a static one",
+        "ping <ip>               send 4 ICMP echo requests and show the round trips",
+        "PageUp / PageDown       scroll",
+    ].iter().map(|s| String::from(*s)).collect()
+}
+
+/// Decimal, or hex with a 0x prefix
+fn parse_number(s: &str) -> Option<u64> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+fn ls(path: &str) -> Vec<String> {
+    let entries = VFS.list_dir(path);
+    if entries.is_empty() && !VFS.is_mounted(path) {
+        return alloc::vec![format!("ls: {}: not mounted", path)];
+    }
+    let mut out: Vec<String> = entries.iter().map(|name| {
+        if name.ends_with('/') {
+            format!("{:>10}  {}", "<dir>", name)
+        } else {
+            match VFS.file_size(&join(path, name)) {
+                Ok(size) => format!("{:>10}  {}", size, name),
+                Err(_) => format!("{:>10}  {}", "?", name),
+            }
+        }
+    }).collect();
+    out.push(format!("{} entries", entries.len()));
+    out
+}
+
+fn cat(path: &str) -> Vec<String> {
+    let size = match VFS.file_size(path) {
+        Ok(s) => s,
+        Err(e) => return alloc::vec![format!("cat: {}: {:?}", path, e)],
+    };
+    let mut buf = alloc::vec![0u8; size.min(CAT_MAX_BYTES)];
+    let read = match VFS.read_file_at(path, 0, &mut buf) {
+        Ok(n) => n,
+        Err(e) => return alloc::vec![format!("cat: {}: {:?}", path, e)],
+    };
+
+    // Bytes that aren't printable ASCII show as '.', like hexdump's text column
+    let text: String = buf[..read].iter().map(|&b| match b {
+        b'\n' => '\n',
+        b'\t' => ' ',
+        0x20..=0x7E => b as char,
+        _ => '.',
+    }).collect();
+    let mut out: Vec<String> = text.split('\n').map(String::from).collect();
+    if size > CAT_MAX_BYTES {
+        out.push(format!("-- cut off after {} of {} bytes --", CAT_MAX_BYTES, size));
+    }
+    out
+}
+
+fn hexdump(lba: u64, offset: usize) -> Vec<String> {
+    let mut sector = [0u8; SECTOR_SIZE];
+    if !crate::fs::read_disk_sector(lba, &mut sector) {
+        return alloc::vec![format!("hexdump: sector {} could not be read", lba)];
+    }
+    let start = offset.min(SECTOR_SIZE - HEXDUMP_BYTES) & !0xF;
+    let mut out = alloc::vec![format!("sector {} (bytes {}..{})", lba, start, start + HEXDUMP_BYTES)];
+    for (i, row) in sector[start..start + HEXDUMP_BYTES].chunks(16).enumerate() {
+        let mut line = format!("{:04x}  ", start + i * 16);
+        for b in row { line.push_str(&format!("{:02x} ", b)); }
+        line.push(' ');
+        line.extend(row.iter().map(|&b| if (0x20..=0x7E).contains(&b) { b as char } else { '.' }));
+        out.push(line);
+    }
+    out
+}
+
+fn mountinfo() -> Vec<String> {
+    match crate::fs::MOUNT_INFO.lock().clone() {
+        Some(info) => alloc::vec![
+            format!("/ on {} ({})", info.source, info.fs_type),
+            format!("partition starts at LBA {}", info.first_lba),
+        ],
+        None => alloc::vec![String::from("nothing is mounted")],
+    }
+}
+
+fn present() -> Vec<String> {
+    let ((full_cycles, full_bytes), (part_cycles, part_bytes)) = crate::gui::present_stats();
+    let line = |what: &str, cycles: u64, bytes: u64| if cycles == 0 {
+        format!("{:<8} none yet", what)
+    } else {
+        format!("{:<8} {} cycles for {} bytes", what, cycles, bytes)
+    };
+    alloc::vec![line("full", full_cycles, full_bytes), line("partial", part_cycles, part_bytes)]
+}
+
+/// `ifconfig` shows the card and address; `ifconfig 10.0.2.15/24 10.0.2.2`
+/// sets a static address (a /24 without the prefix length) and default gateway.
+fn ifconfig(args: &[&str]) -> Vec<String> {
+    use smoltcp::wire::{Ipv4Address, Ipv4Cidr};
+    if let Some(addr) = args.first() {
+        let cidr = if addr.contains('/') {
+            addr.parse::<Ipv4Cidr>().ok()
+        } else {
+            addr.parse::<Ipv4Address>().ok().map(|a| Ipv4Cidr::new(a, 24))
+        };
+        let cidr = match cidr {
+            Some(c) => c,
+            None => return alloc::vec![format!("ifconfig: {}: not an IPv4 address", addr)],
+        };
+        let gateway = match args.get(1).map(|g| g.parse::<Ipv4Address>()) {
+            None => None,
+            Some(Ok(g)) => Some(g),
+            Some(Err(_)) => return alloc::vec![format!("ifconfig: {}: not an IPv4 address", args[1])],
+        };
+        if !crate::drivers::net::set_static(cidr, gateway) {
+            return alloc::vec![String::from("ifconfig: no network card")];
+        }
+    }
+    let config = match crate::drivers::net::config() {
+        Some(c) => c,
+        None => return alloc::vec![String::from("ifconfig: no network card")],
+    };
+    let m = config.mac;
+    let mut out = alloc::vec![format!("{}  ether {:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}", config.nic, m[0], m[1], m[2], m[3], m[4], m[5])];
+    let how = if config.is_static { "static" } else { "dhcp" };
+    out.push(match config.address {
+        Some(a) => format!("inet {} ({})", a, how),
+        None => String::from("inet none (waiting for DHCP)"),
+    });
+    if let Some(gw) = config.gateway { out.push(format!("gateway {}", gw)); }
+    if config.fragments_dropped > 0 { out.push(format!("{} IP fragments dropped (no reassembly)", config.fragments_dropped)); }
+    out
+}
+
+fn ping(target: &str) -> Vec<String> {
+    use crate::drivers::net::ping::{self, PingError, PAYLOAD_LEN};
+    let ip = match target.parse::<smoltcp::wire::Ipv4Address>() {
+        Ok(ip) => ip,
+        Err(_) => return alloc::vec![format!("ping: {}: not an IPv4 address", target)],
+    };
+    let mut out = alloc::vec![format!("PING {}: {} data bytes", ip, PAYLOAD_LEN)];
+    let mut received = 0;
+    for seq in 1..=PING_COUNT {
+        match ping::ping(ip, seq, PING_TIMEOUT_MS) {
+            Ok(ms) => {
+                received += 1;
+                out.push(format!("{} bytes from {}: icmp_seq={} time={} ms", PAYLOAD_LEN + 8, ip, seq, ms));
+            }
+            Err(PingError::TimedOut) => out.push(format!("request timeout for icmp_seq={}", seq)),
+            Err(PingError::NoInterface) => return alloc::vec![String::from("ping: no network card")],
+            Err(PingError::NoAddress) => return alloc::vec![String::from("ping: no address yet (DHCP hasn't answered; try ifconfig <ip>)")],
+            Err(PingError::SendFailed) => out.push(format!("ping: icmp_seq={} could not be sent", seq)),
+        }
+    }
+    out.push(format!("{} sent, {} received", PING_COUNT, received));
+    out
+}
+
+/// Runs each check and reports it on its own line, then a SELFTEST: line
+/// the runner's test mode looks for. With `exit`, QEMU is told the result
+/// through isa-debug-exit; on anything else the write goes nowhere.
+fn selftest(exit: bool) -> Vec<String> {
+    let checks: [(&str, fn() -> Result<(), String>); 4] = [
+        ("heap", selftest_heap),
+        ("fs", selftest_fs),
+        ("sched", selftest_sched),
+        ("stacks", selftest_stacks),
+    ];
+    let mut out = Vec::new();
+    let mut failed = 0;
+    for (name, check) in checks {
+        match check() {
+            Ok(()) => out.push(format!("selftest: {:<6} ok", name)),
+            Err(e) => { failed += 1; out.push(format!("selftest: {:<6} FAILED: {}", name, e)); }
+        }
+    }
+    out.push(if failed == 0 {
+        String::from("SELFTEST: PASS")
+    } else {
+        format!("SELFTEST: FAIL ({} of {} checks)", failed, checks.len())
+    });
+
+    if exit {
+        // QEMU stops as soon as the port is written, so the report goes out first
+        for line in &out { crate::serial_println!("{}", line); }
+        let code = if failed == 0 { QEMU_EXIT_PASS } else { QEMU_EXIT_FAIL };
+        unsafe { x86_64::instructions::port::Port::<u32>::new(QEMU_EXIT_PORT).write(code); }
+        out.push(String::from("selftest: no isa-debug-exit device, still running"));
+    }
+    out
+}
+
+/// A few MiB allocated, filled, verified and freed, leaving the heap as it was.
+fn selftest_heap() -> Result<(), String> {
+    const MIB: usize = 4;
+    let before = crate::allocator::heap_used();
+    match crate::allocator::stress_test(MIB) {
+        Ok(held) if held == MIB => {}
+        Ok(held) => return Err(format!("only {} of {} MiB could be allocated", held, MIB)),
+        Err(crate::errno::EAGAIN) => return Err(String::from("another heap test was running")),
+        Err(_) => return Err(String::from("blocks came back corrupted")),
+    }
+    let after = crate::allocator::heap_used();
+    // Other tasks allocate too; only a leak the size of a block counts
+    if after > before + 1024 * 1024 { return Err(format!("{} bytes still in use after freeing", after - before)); }
+    Ok(())
+}
+
+/// Writes a scratch file on the root filesystem, reads it back and deletes it.
+fn selftest_fs() -> Result<(), String> {
+    let data: Vec<u8> = (0..6000u32).map(|i| (i * 7 + i / 251) as u8).collect();
+    if !VFS.write_file(SELFTEST_FILE, &data) { return Err(format!("could not write {}", SELFTEST_FILE)); }
+    let back = VFS.read_file_alloc(SELFTEST_FILE);
+    let deleted = VFS.delete_file(SELFTEST_FILE).is_ok();
+    match back {
+        Some(b) if b == data => {}
+        Some(b) => return Err(format!("read back {} bytes that differ from the {} written", b.len(), data.len())),
+        None => return Err(format!("could not read {} back", SELFTEST_FILE)),
+    }
+    if !deleted { return Err(format!("could not delete {}", SELFTEST_FILE)); }
+    Ok(())
+}
+
+static SELFTEST_CHILD_RAN: core::sync::atomic::AtomicBool = core::sync::atomic::AtomicBool::new(false);
+
+/// Spawns a kernel task that sets a flag and returns, and waits for it to exit.
+fn selftest_sched() -> Result<(), String> {
+    use core::sync::atomic::Ordering;
+    SELFTEST_CHILD_RAN.store(false, Ordering::SeqCst);
+    let pid = crate::scheduler::spawn_kernel_task("selftest-child", || SELFTEST_CHILD_RAN.store(true, Ordering::SeqCst))
+        .map_err(String::from)?;
+    let deadline = crate::time::monotonic_ms() + SELFTEST_TASK_TIMEOUT_MS;
+    loop {
+        if let Some(code) = crate::scheduler::exit_status(pid) {
+            if !SELFTEST_CHILD_RAN.load(Ordering::SeqCst) { return Err(format!("PID {} exited without running", pid)); }
+            return if code == 0 { Ok(()) } else { Err(format!("PID {} exited with {}", pid, code)) };
+        }
+        if crate::time::monotonic_ms() >= deadline {
+            return Err(format!("PID {} still running after {} ms", pid, SELFTEST_TASK_TIMEOUT_MS));
+        }
+        crate::time::sleep_ms(10);
+    }
+}
+
+/// Two user tasks overwriting their own files through SYS_FS_WRITE at the
+/// same time, so each is often blocked mid-syscall while the other runs.
+/// Sharing a kernel stack would corrupt one's saved registers or return
+/// value; each file must also read back as exactly what its task wrote.
+fn selftest_stacks() -> Result<(), String> {
+    let pattern = |task: usize| -> Vec<u8> { (0..SELFTEST_WRITER_BYTES).map(|i| (i * (task + 3) + task) as u8).collect() };
+    let parent = crate::scheduler::current_pid();
+    let mut pids = Vec::new();
+    for (task, path) in SELFTEST_WRITER_FILES.iter().enumerate() {
+        let mut image = alloc::vec![0u8; SELFTEST_WRITER_DATA_AT];
+        image[..SELFTEST_WRITER_CODE.len()].copy_from_slice(&SELFTEST_WRITER_CODE);
+        image[SELFTEST_WRITER_PATH_AT..SELFTEST_WRITER_PATH_AT + path.len()].copy_from_slice(path.as_bytes());
+        image.extend_from_slice(&pattern(task));
+
+        let mut child = crate::process::Process::spawn_flat(&image, "selftest-writer")?;
+        child.parent_pid = Some(parent);
+        pids.push(child.pid);
+        crate::percpu::current().scheduler.add(child);
+        crate::scheduler::LIVE_USER_TASKS.fetch_add(1, core::sync::atomic::Ordering::SeqCst);
+    }
+
+    let deadline = crate::time::monotonic_ms() + SELFTEST_WRITERS_TIMEOUT_MS;
+    let mut result = Ok(());
+    for &pid in &pids {
+        let code = loop {
+            if let Some(code) = crate::scheduler::exit_status(pid) { break Some(code); }
+            if crate::time::monotonic_ms() >= deadline { break None; }
+            crate::time::sleep_ms(10);
+        };
+        match code {
+            Some(0) => {}
+            Some(code) => { result = Err(format!("writer PID {} exited with {}", pid, code)); break; }
+            None => { result = Err(format!("writer PID {} still running after {} ms", pid, SELFTEST_WRITERS_TIMEOUT_MS)); break; }
+        }
+    }
+
+    for (task, path) in SELFTEST_WRITER_FILES.iter().enumerate() {
+        let back = VFS.read_file_alloc(path);
+        let _ = VFS.delete_file(path);
+        if result.is_ok() && back != Some(pattern(task)) {
+            result = Err(format!("{} doesn't hold what its writer wrote", path));
+        }
+    }
+    result
+}
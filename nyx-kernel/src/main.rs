@@ -11,9 +11,11 @@ extern crate alloc;
 
 pub mod vga_log;
 pub mod serial;
+pub mod errno;
 pub mod interrupts;
 pub mod gdt;
 pub mod memory;
+pub mod uaccess;
 pub mod allocator;
 pub mod acpi;
 pub mod apic;
@@ -21,22 +23,36 @@ pub mod ioapic;
 pub mod smp;
 pub mod percpu;
 pub mod time;
+pub mod rtc;
+pub mod sound;
 pub mod task;
 pub mod executor;
 pub mod scheduler;
+pub mod workqueue;
 pub mod pci;
 pub mod drivers;
 pub mod fs;
 pub mod vfs;
+pub mod elf;
 pub mod process;
 pub mod gui;
+pub mod display;
+pub mod glyph_cache;
 pub mod window;
 pub mod mouse;
 pub mod shell;
+pub mod input;
+pub mod kshell;
+pub mod serial_console;
+pub mod panic;
+pub mod watchdog;
 pub mod entity;
 pub mod c_stubs;
 pub mod usb;
+pub mod usb_keyboard;
+pub mod usb_storage;
 pub mod partitioner;
+pub use nyx_core::{gpt, mbr};
 pub mod thermal;
 pub mod laptop_fans;
 pub mod installer;
@@ -136,17 +152,17 @@ fn kernel_main(boot_info: &'static mut BootInfo) -> ! {
         
         unsafe { 
              crate::gui::SCREEN_PAINTER = Some(gui::VgaPainter { buffer: raw_buffer, info });
+             // Kernel text goes here; the timer presents it (see gui.rs)
+             crate::gui::BACK_BUFFER = Some(gui::BackBuffer::new(info));
              if let Some(phys) = crate::memory::virt_to_phys(fb_virt_ptr) { crate::gui::FRAMEBUFFER_PHYS_ADDR = phys; }
              else { crate::gui::FRAMEBUFFER_PHYS_ADDR = fb_virt_ptr; }
         }
+        crate::glyph_cache::init();
+        crate::gui::benchmark_clear();
         
         crate::window::WINDOW_MANAGER.lock().set_resolution(info.width, info.height);
         
-        {
-            let mut mouse_state = crate::mouse::MOUSE_STATE.lock();
-            mouse_state.screen_width = info.width;
-            mouse_state.screen_height = info.height;
-        }
+        crate::display::init(info.width, info.height);
         crate::vga_println!("[BOOT] Framebuffer Mapped: {}x{}", info.width, info.height);
     }
 
@@ -165,18 +181,24 @@ fn kernel_main(boot_info: &'static mut BootInfo) -> ! {
 
     x86_64::instructions::interrupts::enable();
 
-    if let Some(rsdp_addr) = boot_info.rsdp_addr.into_option() {
-        acpi::init(rsdp_addr);
-        acpi::init_intel_acpica();
-        acpi::scan_for_modern_inputs();
-        apic::init();
-        
+    match boot_info.rsdp_addr.into_option() {
+        Some(rsdp_addr) => {
+            acpi::init(rsdp_addr);
+            acpi::init_intel_acpica();
+            acpi::scan_for_modern_inputs();
+        }
+        None => crate::vga_println!("[BOOT] WARN: ACPI Tables missing! Attempting degraded boot."),
+    }
+
+    // Without a MADT there is no way to find the APICs; stay on the 8259 PIC and the PIT
+    if apic::init() {
         let apic_ids = crate::apic::get_cpu_apic_ids();
         percpu::init(&apic_ids);
         
         crate::memory::identity_map_low_memory();
         time::init();
         crate::time::calibrate_tsc();
+        crate::apic::calibrate_timer();
         ioapic::init();
         
         let bsp_apic_id = apic_ids[0] as u8;
@@ -189,13 +211,14 @@ fn kernel_main(boot_info: &'static mut BootInfo) -> ! {
         smp::init_aps(&apic_ids);
         pci::enumerate_pci();
     } else {
-        crate::vga_println!("[BOOT] WARN: ACPI Tables missing! Attempting degraded boot.");
+        crate::vga_println!("[BOOT] WARN: No MADT. Falling back to PIC mode on a single core.");
         let apic_ids = [0];
         percpu::init(&apic_ids);
         time::init();
         crate::time::calibrate_tsc();
         pci::enumerate_pci();
     }
+    rtc::init();
 
     // ==========================================
     // NVME HARDWARE DRIVER INITIALIZATION
@@ -210,21 +233,53 @@ fn kernel_main(boot_info: &'static mut BootInfo) -> ! {
     }
 
     // ==========================================
-    // PHYSICAL NVME VFS MOUNT POINT
+    // SATA (ROOT FALLBACK + LSDISK)
+    // ==========================================
+    unsafe {
+        if let Some(ahci) = crate::drivers::ahci::AhciDriver::init() {
+            for (port, disk) in ahci.disks.iter().enumerate() {
+                if let Some(d) = disk {
+                    crate::vga_println!("[BOOT] SATA port {}: {} ({} MiB)", port, d.model, d.sectors * d.sector_size as u64 / (1024 * 1024));
+                }
+            }
+            if let Some(port) = ahci.first_sata_port() {
+                if crate::fs::GLOBAL_NVME.is_none() {
+                    crate::vga_println!("[BOOT] No NVMe, using SATA disk on AHCI port {}", port);
+                }
+                crate::fs::GLOBAL_SATA = Some(crate::drivers::ahci::AhciDisk { driver: ahci, port });
+            }
+        }
+    }
+
+    // ==========================================
+    // USB MASS STORAGE (LAST ROOT FALLBACK)
+    // ==========================================
+    unsafe {
+        if crate::fs::GLOBAL_NVME.is_none() && crate::fs::GLOBAL_SATA.is_none() {
+            if let Some(disk) = crate::usb_storage::find_disk() {
+                crate::vga_println!("[BOOT] No NVMe or SATA, using USB disk: {} ({} MiB)", disk.model, disk.sectors * 512 / (1024 * 1024));
+                crate::fs::GLOBAL_USB_DISK = Some(disk);
+            }
+        }
+    }
+
+    // ==========================================
+    // PHYSICAL DISK VFS MOUNT POINT
     // ==========================================
     unsafe {
-        if crate::fs::GLOBAL_NVME.is_some() {
+        if crate::fs::GLOBAL_NVME.is_some() || crate::fs::GLOBAL_SATA.is_some() || crate::fs::GLOBAL_USB_DISK.is_some() {
             if let Some(ext4_fs) = crate::fs::NvmeLwExt4Fs::new() {
                 crate::vfs::VFS.mount("/mnt/nvme", Box::new(ext4_fs));
-                crate::vga_println!("[BOOT] Physical NVMe Hardware (lwext4 R/W) Mounted to /mnt/nvme");
+                crate::vga_println!("[BOOT] Physical Disk (lwext4 R/W) Mounted to /mnt/nvme");
                 
                 crate::installer::extract_tar_to_ext4(INITRD_TAR);
+                crate::gui::load_display_config();
                 
             } else {
-                panic!("FATAL: NVMe Drive Found but no ext4 partition detected.");
+                panic!("FATAL: Disk Found but no ext4 partition detected.");
             }
         } else {
-            panic!("FATAL: No NVMe Drive Detected! Cannot boot without a system drive.");
+            panic!("FATAL: No NVMe, SATA or USB Drive Detected! Cannot boot without a system drive.");
         }
     }
     // GPU TEST
@@ -243,72 +298,55 @@ fn kernel_main(boot_info: &'static mut BootInfo) -> ! {
     x86_64::instructions::interrupts::disable();
     let percpu = crate::percpu::current();
 
-    // 1. Thermal Governor
-    let mut thermal_task = crate::process::Process::new().unwrap();
-    thermal_task.name = *b"thermal-governor";
-    unsafe {
-        let iretq_ptr = thermal_task.kernel_stack_top - 40;
-        let iret_slice = core::slice::from_raw_parts_mut(iretq_ptr as *mut u64, 5);
-        iret_slice[0] = crate::thermal::nyx_task_manager_daemon as u64; 
-        iret_slice[1] = 0x08; iret_slice[2] = 0x202;             
-        iret_slice[3] = thermal_task.kernel_stack_top; iret_slice[4] = 0x10;              
-        let regs_ptr = iretq_ptr - 120;
-        core::ptr::write_bytes(regs_ptr as *mut u8, 0, 120); 
-        let fxsave_ptr = (regs_ptr - 512) & !0xF;
-        core::ptr::write_bytes(fxsave_ptr as *mut u8, 0, 512); 
-        *(fxsave_ptr as *mut u32).add(6) = 0x1F80; 
-        let final_rsp = fxsave_ptr - 16;
-        let bottom = core::slice::from_raw_parts_mut(final_rsp as *mut u64, 2);
-        bottom[0] = regs_ptr; bottom[1] = 0;
-        thermal_task.saved_rsp = final_rsp;
-    }
-
-    // 2. Idle Task
-    let mut idle_task = crate::process::Process::new().unwrap();
-    idle_task.name = *b"kernel-idle\0\0\0\0\0";
+    // 1. Idle Task
+    let mut idle_task = crate::process::Process::new_kernel_task("kernel-idle", || crate::process::nyx_idle_task())
+        .expect("Failed to create idle task");
     idle_task.is_idle = true; 
-    unsafe {
-        let iretq_ptr = idle_task.kernel_stack_top - 40;
-        let iret_slice = core::slice::from_raw_parts_mut(iretq_ptr as *mut u64, 5);
-        iret_slice[0] = crate::process::nyx_idle_task as u64; 
-        iret_slice[1] = 0x08; iret_slice[2] = 0x202;             
-        iret_slice[3] = idle_task.kernel_stack_top; iret_slice[4] = 0x10;              
-        let regs_ptr = iretq_ptr - 120;
-        core::ptr::write_bytes(regs_ptr as *mut u8, 0, 120);
-        let fxsave_ptr = (regs_ptr - 512) & !0xF;
-        core::ptr::write_bytes(fxsave_ptr as *mut u8, 0, 512);
-        *(fxsave_ptr as *mut u32).add(6) = 0x1F80;
-        let final_rsp = fxsave_ptr - 16;
-        let bottom = core::slice::from_raw_parts_mut(final_rsp as *mut u64, 2);
-        bottom[0] = regs_ptr; bottom[1] = 0;
-        idle_task.saved_rsp = final_rsp;
-    }
 
-    // 3. Init Process (PID 1)
+    // 2. Init Process (PID 1)
     crate::vga_println!("[BOOT] Loading Init.nyx into PID 1 directly from NVMe...");
     let mut init_process = crate::process::Process::new().expect("Failed to create init process");
+    // Private lower-half tables, so init's pages never land in the kernel tasks' tables
+    crate::memory::clone_kernel_mappings(x86_64::PhysAddr::new(unsafe { crate::memory::BOOTLOADER_CR3 }), init_process.cr3);
     init_process.state = crate::scheduler::TaskState::Running;
     init_process.name = *b"nyx-init\0\0\0\0\0\0\0\0";
     
     let init_cr3 = init_process.cr3.as_u64();
     let init_kernel_stack = init_process.kernel_stack_top;
     
-    percpu.scheduler.tasks.push(idle_task);    
-    percpu.scheduler.tasks.push(init_process); 
-    percpu.scheduler.tasks.push(thermal_task); 
+    percpu.scheduler.add(idle_task);
+    percpu.scheduler.add(init_process);
+    crate::scheduler::LIVE_USER_TASKS.fetch_add(1, core::sync::atomic::Ordering::SeqCst);
+
+    // 3. Thermal Governor
+    crate::scheduler::spawn_kernel_task("thermal-governor", || crate::thermal::nyx_task_manager_daemon())
+        .expect("Failed to create thermal governor");
+
+    // 4. Kernel work queue
+    crate::workqueue::init();
+
+    // 5. USB event task
+    crate::usb::start_event_task();
+
+    // 6. Shell on COM1
+    crate::serial_console::init();
     
     percpu.scheduler.core_task_idx[percpu.logical_id as usize % 32] = 1;
 
     unsafe {
         core::arch::asm!("mov cr3, {}", in(reg) init_cr3);
-        let percpu_base = percpu as *const _ as *mut u64;
-        *percpu_base = init_kernel_stack;
     }
+    percpu.set_kernel_stack(init_kernel_stack);
 
     let init_data = crate::vfs::VFS.read_file_alloc("/mnt/nvme/apps/Init.nyx/run.bin")
         .expect("VFS FATAL: Failed to load /mnt/nvme/apps/Init.nyx/run.bin from SSD!");
         
-    let entry_point = crate::process::load_elf(&init_data).expect("ELF Parse Fail");
+    // Older images were flat binaries; anything with ELF magic must load cleanly
+    let entry_point = if crate::elf::is_elf(&init_data) {
+        crate::elf::load(&init_data).unwrap_or_else(|e| panic!("Init.nyx rejected: {}", e))
+    } else {
+        crate::elf::load_flat(&init_data).unwrap_or_else(|e| panic!("Init.nyx (flat) failed to load: {}", e))
+    };
     
     let stack_base = 0x7FFF_0000_0000;
     let stack_pages = 32; 
@@ -325,30 +363,20 @@ fn kernel_main(boot_info: &'static mut BootInfo) -> ! {
     }
 
     // 🔥 ADDED HERE: Safe Hardware Timer Initialization
-    crate::apic::init_timer(0x40);
+    crate::apic::start_ticks(0x40);
 
+    percpu.scheduler.tasks[1].user = Some(crate::process::UserTask { entry: entry_point, stack_top });
     crate::vga_println!("[BOOT] Jumping to Ring 3 Natively (Entry: {:#x})...", entry_point);
     unsafe { process::enter_userspace(entry_point, stack_top); }
 }
 
 #[panic_handler]
 fn panic(info: &core::panic::PanicInfo) -> ! {
-    let msg = alloc::format!("{}", info);
-    trigger_rsod(&msg);
+    crate::panic::on_panic(info);
 }
 
 pub fn trigger_rsod(msg: &str) -> ! {
-    x86_64::instructions::interrupts::disable();
-    unsafe {
-        if let Some(painter) = &mut crate::gui::SCREEN_PAINTER {
-            let buf = painter.buffer.as_mut();
-            for i in (0..buf.len()).step_by(4) {
-                buf[i] = 0; buf[i+1] = 0; buf[i+2] = 255; buf[i+3] = 255;
-            }
-        }
-    }
-    crate::vga_println!("\n\n  [FATAL KERNEL PANIC]\n  -> {}", msg);
-    loop { x86_64::instructions::hlt(); }
+    crate::panic::show(msg);
 }
 
 #[alloc_error_handler]
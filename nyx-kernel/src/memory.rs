@@ -8,12 +8,17 @@ use x86_64::{
 use bootloader_api::info::MemoryRegionKind;
 use spin::Mutex;
 
+use alloc::collections::BTreeMap;
 use alloc::vec::Vec;
 
 pub struct ShmBlock {
     pub id: u64,
     pub frames: Vec<PhysAddr>,
     pub size: usize,
+    /// Address spaces that mapped the block through SYS_MAP_SHM and haven't unmapped it
+    pub mappings: u32,
+    /// Set by an unmap with release; the frames go back once `mappings` is 0
+    pub released: bool,
 }
 
 lazy_static::lazy_static! {
@@ -61,99 +66,132 @@ pub unsafe fn active_mapper() -> OffsetPageTable<'static> {
     OffsetPageTable::new(&mut *pml4_ptr, phys_offset)
 }
 
+// ==========================================
+// PHYSICAL FRAME ALLOCATOR
+// ==========================================
+// Two bitmaps with one bit per 4 KiB frame up to the highest usable address:
+// `usable` is fixed at boot from the memory map, `free` is set while a frame
+// can be handed out. Both live in the first usable region big enough to hold
+// them, and those frames start out allocated. A word cursor makes single
+// frames close to constant time; contiguous requests scan for a run.
+//
+// Freeing a frame twice, or one that was never usable RAM (an MMIO window),
+// is logged and ignored rather than letting it be handed out again.
+
+const FRAME: u64 = 4096;
+
 pub struct BootInfoFrameAllocator {
-    memory_map: &'static [bootloader_api::info::MemoryRegion],
-    current_region: usize,
-    current_offset: u64,
-    phys_offset: VirtAddr,
-    recycled_frames: Option<PhysFrame>,
+    free: &'static mut [u64],
+    usable: &'static mut [u64],
+    cursor: usize,
+    total_frames: u64,
+    free_frames: u64,
 }
 
 impl BootInfoFrameAllocator {
     pub unsafe fn init(memory_map: &'static [bootloader_api::info::MemoryRegion], phys_offset: VirtAddr) -> Self {
-        BootInfoFrameAllocator { 
-            memory_map, current_region: 0, current_offset: 0, phys_offset, recycled_frames: None 
+        let regions = || memory_map.iter()
+            .filter(|r| r.kind == MemoryRegionKind::Usable)
+            .map(|r| ((r.start + FRAME - 1) / FRAME, r.end / FRAME))
+            .filter(|(first, end)| end > first);
+
+        let frame_limit = regions().map(|(_, end)| end).max().unwrap_or(0);
+        let words = ((frame_limit + 63) / 64) as usize;
+        let bitmap_frames = (2 * words as u64 * 8 + FRAME - 1) / FRAME;
+        let (home, _) = regions().find(|(first, end)| end - first >= bitmap_frames)
+            .expect("no usable region can hold the frame bitmaps");
+
+        let base = (phys_offset + home * FRAME).as_mut_ptr::<u64>();
+        let mut allocator = BootInfoFrameAllocator {
+            free: core::slice::from_raw_parts_mut(base, words),
+            usable: core::slice::from_raw_parts_mut(base.add(words), words),
+            cursor: 0, total_frames: 0, free_frames: 0,
+        };
+        allocator.free.fill(0);
+        allocator.usable.fill(0);
+
+        for (first, end) in regions() {
+            for frame in first..end {
+                allocator.usable[frame as usize / 64] |= 1 << (frame % 64);
+                allocator.free[frame as usize / 64] |= 1 << (frame % 64);
+            }
+            allocator.total_frames += end - first;
         }
+        allocator.free_frames = allocator.total_frames;
+
+        // Physical 0 doubles as "no table" in the page walkers, so it is never handed out
+        allocator.take(0);
+        for frame in home..home + bitmap_frames { allocator.take(frame); }
+        allocator
     }
 
-    pub fn deallocate_frame(&mut self, frame: PhysFrame) {
-        let phys_addr = frame.start_address().as_u64();
-        let virt_addr = self.phys_offset + phys_addr;
-        let ptr = virt_addr.as_mut_ptr() as *mut u64;
+    /// Usable frames reported by the bootloader's memory map
+    pub fn total_frames(&self) -> u64 { self.total_frames }
+    pub fn used_frames(&self) -> u64 { self.total_frames - self.free_frames }
+    pub fn frames_free(&self) -> u64 { self.free_frames }
 
-        let next_ptr = match self.recycled_frames { Some(f) => f.start_address().as_u64(), None => 0, };
-        unsafe { *ptr = next_ptr; }
-        self.recycled_frames = Some(frame);
+    fn is_free(&self, frame: u64) -> bool { self.free[frame as usize / 64] & (1 << (frame % 64)) != 0 }
+    fn is_usable(&self, frame: u64) -> bool {
+        (frame as usize / 64) < self.usable.len() && self.usable[frame as usize / 64] & (1 << (frame % 64)) != 0
     }
 
-    pub fn allocate_contiguous_frames(&mut self, num_frames: usize, alignment: u64, below_4gb: bool) -> Option<PhysFrame> {
-        let size = (num_frames as u64) * 4096;
-        
-        // Save state in case we need to search and fail
-        let orig_region = self.current_region;
-        let orig_offset = self.current_offset;
-
-        while self.current_region < self.memory_map.len() {
-            let region = &self.memory_map[self.current_region];
-            if region.kind == MemoryRegionKind::Usable {
-                let mut target_addr = region.start + self.current_offset;
-                
-                // Align the target address
-                let remainder = target_addr % alignment;
-                if remainder != 0 {
-                    target_addr += alignment - remainder;
-                }
+    /// Marks a free frame allocated
+    fn take(&mut self, frame: u64) {
+        if self.is_usable(frame) && self.is_free(frame) {
+            self.free[frame as usize / 64] &= !(1 << (frame % 64));
+            self.free_frames -= 1;
+        }
+    }
 
-                if target_addr + size <= region.end {
-                    if below_4gb && (target_addr + size) > 0x1_0000_0000 {
-                        self.current_region += 1;
-                        self.current_offset = 0;
-                        continue;
-                    }
+    pub fn deallocate_frame(&mut self, frame: PhysFrame) {
+        let addr = frame.start_address().as_u64();
+        let n = addr / FRAME;
+        if !self.is_usable(n) {
+            crate::serial_println!("[MEM] Ignoring free of non-RAM frame {:#x}", addr);
+            return;
+        }
+        if self.is_free(n) {
+            crate::serial_println!("[MEM] Double free of frame {:#x} ignored", addr);
+            return;
+        }
+        self.free[n as usize / 64] |= 1 << (n % 64);
+        self.free_frames += 1;
+    }
 
-                    // Found a suitable block
-                    self.current_offset = (target_addr - region.start) + size;
-                    return Some(PhysFrame::containing_address(PhysAddr::new(target_addr)));
+    pub fn allocate_contiguous_frames(&mut self, num_frames: usize, alignment: u64, below_4gb: bool) -> Option<PhysFrame> {
+        let num = num_frames as u64;
+        let step = (alignment / FRAME).max(1);
+        let mut limit = self.free.len() as u64 * 64;
+        if below_4gb { limit = limit.min(0x1_0000_0000 / FRAME); }
+
+        let mut start = step;
+        while start + num <= limit {
+            // On a taken frame, restart at the next aligned frame past it
+            match (start..start + num).find(|&f| !self.is_free(f)) {
+                Some(taken) => start = (taken / step + 1) * step,
+                None => {
+                    for frame in start..start + num { self.take(frame); }
+                    return Some(PhysFrame::containing_address(PhysAddr::new(start * FRAME)));
                 }
             }
-            self.current_region += 1;
-            self.current_offset = 0;
         }
-        
-        // Restore state if failed
-        self.current_region = orig_region;
-        self.current_offset = orig_offset;
         None
     }
 }
 
 unsafe impl FrameAllocator<Size4KiB> for BootInfoFrameAllocator {
     fn allocate_frame(&mut self) -> Option<PhysFrame> {
-        if let Some(frame) = self.recycled_frames {
-            let phys_addr = frame.start_address().as_u64();
-            let virt_addr = self.phys_offset + phys_addr;
-            let ptr = virt_addr.as_ptr() as *const u64;
-            unsafe {
-                let next_addr = *ptr;
-                if next_addr == 0 { self.recycled_frames = None; } 
-                else { self.recycled_frames = Some(PhysFrame::containing_address(PhysAddr::new(next_addr))); }
-            }
-            return Some(frame);
-        }
-
-        while self.current_region < self.memory_map.len() {
-            let region = &self.memory_map[self.current_region];
-            if region.kind == MemoryRegionKind::Usable {
-                let target_addr = region.start + self.current_offset;
-                if target_addr + 4096 <= region.end {
-                    self.current_offset += 4096;
-                    return Some(PhysFrame::containing_address(PhysAddr::new(target_addr)));
-                }
-            }
-            self.current_region += 1;
-            self.current_offset = 0;
+        let words = self.free.len();
+        for i in 0..words {
+            let w = (self.cursor + i) % words;
+            if self.free[w] == 0 { continue; }
+
+            let frame = w as u64 * 64 + self.free[w].trailing_zeros() as u64;
+            self.take(frame);
+            self.cursor = w;
+            return Some(PhysFrame::containing_address(PhysAddr::new(frame * FRAME)));
         }
-        None 
+        None
     }
 }
 
@@ -206,6 +244,15 @@ pub fn allocate_user_pages_at(start_vaddr: u64, num_pages: usize) -> Result<u64,
             match active_mapper.map_to(page, frame, flags, &mut system.frame_allocator) {
                 Ok(mapper) => mapper.flush(),
                 Err(MapToError::PageAlreadyMapped(_)) => {
+                    // Only user pages may be replaced; a kernel page left in the
+                    // lower half stays where it is
+                    let user = matches!(active_mapper.translate(page.start_address()),
+                        x86_64::structures::paging::mapper::TranslateResult::Mapped { flags, .. }
+                            if flags.contains(PageTableFlags::USER_ACCESSIBLE));
+                    if !user {
+                        system.frame_allocator.deallocate_frame(frame);
+                        return Err("User page would cover a kernel mapping");
+                    }
                     let (_phys, flush) = active_mapper.unmap(page).unwrap();
                     flush.flush();
                     active_mapper.map_to(page, frame, flags, &mut system.frame_allocator).unwrap().flush();
@@ -222,6 +269,70 @@ pub fn allocate_user_pages_at(start_vaddr: u64, num_pages: usize) -> Result<u64,
     Ok(start_vaddr)
 }
 
+/// Backs the user page holding `vaddr` with a zeroed frame, unless something is
+/// already mapped there (another thread may have touched it first).
+pub fn map_lazy_user_page(vaddr: u64) -> Result<(), &'static str> {
+    let mut system_lock = MEMORY_MANAGER.lock();
+    let system = system_lock.as_mut().ok_or("Memory System not initialized")?;
+    let mut active_mapper = unsafe { active_mapper() };
+
+    let page: Page<Size4KiB> = Page::containing_address(VirtAddr::new(vaddr));
+    if active_mapper.translate_page(page).is_ok() { return Ok(()); }
+
+    let frame = system.frame_allocator.allocate_frame().ok_or("Out of physical memory!")?;
+    unsafe {
+        core::ptr::write_bytes((frame.start_address().as_u64() + PHYS_MEM_OFFSET) as *mut u8, 0, 4096);
+        let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::USER_ACCESSIBLE;
+        match active_mapper.map_to(page, frame, flags, &mut system.frame_allocator) {
+            Ok(mapper) => mapper.flush(),
+            Err(_) => {
+                system.frame_allocator.deallocate_frame(frame);
+                return Err("Failed to map user page");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Unmaps whatever is present in the range. Frames the task owns outright go back to
+/// the allocator, and so do frames from fork once nobody else maps them; MMIO (0x10)
+/// and shared memory (0x200) are only detached.
+pub fn release_user_pages(start_vaddr: u64, num_pages: usize) {
+    let mut system_lock = MEMORY_MANAGER.lock();
+    let system = match system_lock.as_mut() { Some(s) => s, None => return };
+    let mut active_mapper = unsafe { active_mapper() };
+    let start_page: Page<Size4KiB> = Page::containing_address(VirtAddr::new(start_vaddr));
+
+    for i in 0..num_pages {
+        let page = start_page + i as u64;
+        let flags = match active_mapper.translate(page.start_address()) {
+            x86_64::structures::paging::mapper::TranslateResult::Mapped { flags, .. } => flags,
+            _ => continue,
+        };
+        if let Ok((frame, flush)) = active_mapper.unmap(page) {
+            flush.flush();
+            let bits = flags.bits();
+            if bits & 0x210 == 0 && (bits & SHARED_FRAME == 0 || release_shared_frame(frame.start_address().as_u64())) {
+                system.frame_allocator.deallocate_frame(frame);
+            }
+        }
+    }
+}
+
+/// Drops the writable bit from already mapped user pages (ELF text and rodata).
+pub fn protect_user_pages_readonly(start_vaddr: u64, num_pages: usize) -> Result<(), &'static str> {
+    let mut active_mapper = unsafe { active_mapper() };
+    let start_page: Page<Size4KiB> = Page::containing_address(VirtAddr::new(start_vaddr));
+    let flags = PageTableFlags::PRESENT | PageTableFlags::USER_ACCESSIBLE;
+
+    for i in 0..num_pages {
+        unsafe {
+            active_mapper.update_flags(start_page + i as u64, flags).map_err(|_| "Failed to protect user page")?.flush();
+        }
+    }
+    Ok(())
+}
+
 pub fn map_user_framebuffer(phys_addr: u64, size: u64) -> Result<u64, &'static str> {
     let mut system_lock = MEMORY_MANAGER.lock();
     let system = system_lock.as_mut().ok_or("Memory System not initialized")?;
@@ -304,6 +415,29 @@ pub fn allocate_kernel_stack(pages: usize) -> u64 {
     top_of_stack & !0xF 
 }
 
+/// Unmaps a stack from allocate_kernel_stack and returns its frames. The virtual
+/// range isn't reused, so a stale TLB entry elsewhere can't reach the freed frames
+/// through a new stack. The task that ran on it must never run again.
+pub fn free_kernel_stack(top: u64, pages: usize) {
+    let mut lock = MEMORY_MANAGER.lock();
+    let system = match lock.as_mut() { Some(s) => s, None => return };
+    let mut active_mapper = unsafe { active_mapper() };
+
+    for i in 0..pages as u64 {
+        let page = Page::<Size4KiB>::containing_address(VirtAddr::new(top - (i + 1) * 4096));
+        if let Ok((frame, flush)) = active_mapper.unmap(page) {
+            flush.flush();
+            system.frame_allocator.deallocate_frame(frame);
+        }
+    }
+}
+
+pub fn free_frame(frame: PhysFrame) {
+    if let Some(system) = MEMORY_MANAGER.lock().as_mut() {
+        system.frame_allocator.deallocate_frame(frame);
+    }
+}
+
 pub fn identity_map_low_memory() {
     let mut lock = MEMORY_MANAGER.lock();
     if let Some(system) = lock.as_mut() {
@@ -330,6 +464,12 @@ pub fn allocate_contiguous(num_frames: usize, alignment: u64, below_4gb: bool) -
     lock.as_mut().and_then(|sys| sys.frame_allocator.allocate_contiguous_frames(num_frames, alignment, below_4gb))
 }
 
+/// (usable, in use) physical frames
+pub fn frame_stats() -> (u64, u64) {
+    let lock = MEMORY_MANAGER.lock();
+    lock.as_ref().map_or((0, 0), |sys| (sys.frame_allocator.total_frames(), sys.frame_allocator.used_frames()))
+}
+
 pub fn clone_kernel_page_table(new_pml4_phys: PhysAddr) {
     unsafe {
         let offset = PHYS_MEM_OFFSET;
@@ -341,7 +481,45 @@ pub fn clone_kernel_page_table(new_pml4_phys: PhysAddr) {
     }
 }
 
+// ==========================================
+// SHARED USER FRAMES
+// ==========================================
+// fork hands the child the parent's user frames instead of copying them:
+// read-only ELF pages for good, writable ones until either side writes
+// (CoW, bit 10). Both kinds carry SHARED_FRAME (bit 11), and FRAME_REFS
+// counts the address spaces mapping each such frame. Whoever unmaps it last,
+// at exit or by copying it on a write fault, frees it.
+
+pub const SHARED_FRAME: u64 = 0x800;
+static FRAME_REFS: Mutex<BTreeMap<u64, u32>> = Mutex::new(BTreeMap::new());
+
+/// One more address space maps the frame at `phys`.
+fn share_frame(phys: u64) {
+    *FRAME_REFS.lock().entry(phys).or_insert(1) += 1;
+}
+
+/// One address space stops mapping the SHARED_FRAME at `phys`. True when it
+/// was the last, and the frame is now the caller's to free.
+pub fn release_shared_frame(phys: u64) -> bool {
+    let mut refs = FRAME_REFS.lock();
+    match refs.get_mut(&phys) {
+        Some(n) if *n > 1 => { *n -= 1; false }
+        _ => { refs.remove(&phys); true }
+    }
+}
+
 pub fn clone_user_address_space(parent_cr3: PhysAddr, child_cr3: PhysAddr) {
+    clone_lower_half(parent_cr3, child_cr3, true);
+}
+
+/// Gives `child_cr3` private copies of the parent's lower-half tables holding only
+/// kernel mappings (identity-mapped MMIO, low memory), none of the parent's user pages.
+/// A freshly spawned program starts from this, so loading it can't disturb the parent.
+pub fn clone_kernel_mappings(parent_cr3: PhysAddr, child_cr3: PhysAddr) {
+    clone_lower_half(parent_cr3, child_cr3, false);
+}
+
+fn clone_lower_half(parent_cr3: PhysAddr, child_cr3: PhysAddr, copy_user: bool) {
     unsafe {
         let mut lock = crate::memory::MEMORY_MANAGER.lock();
         let system = lock.as_mut().expect("Memory System not initialized");
@@ -356,6 +534,12 @@ pub fn clone_user_address_space(parent_cr3: PhysAddr, child_cr3: PhysAddr) {
             let pml4_entry = *parent_pml4.add(i4);
             if pml4_entry & 1 == 0 { continue; }
 
+            // The kernel heap's tables are shared so that heap growth reaches every task
+            if i4 == crate::allocator::HEAP_PML4_SLOT {
+                *child_pml4.add(i4) = pml4_entry;
+                continue;
+            }
+
             let new_pml3 = system.frame_allocator.allocate_frame().expect("OOM: PML3");
             let child_pml3_ptr = (new_pml3.start_address().as_u64() + offset) as *mut u64;
             core::ptr::write_bytes(child_pml3_ptr as *mut u8, 0, 4096);
@@ -377,7 +561,7 @@ pub fn clone_user_address_space(parent_cr3: PhysAddr, child_cr3: PhysAddr) {
                     if pml2_entry & 1 == 0 { continue; }
 
                     if pml2_entry & (1 << 7) != 0 {
-                        *child_pml2_ptr.add(i2) = pml2_entry;
+                        if copy_user || pml2_entry & 0x4 == 0 { *child_pml2_ptr.add(i2) = pml2_entry; }
                         continue;
                     }
 
@@ -390,13 +574,18 @@ pub fn clone_user_address_space(parent_cr3: PhysAddr, child_cr3: PhysAddr) {
                     for i1 in 0..512 {
                         let pt_entry = *parent_pt_ptr.add(i1);
                         if pt_entry & 1 == 0 { continue; }
+                        if !copy_user && pt_entry & 0x4 != 0 { continue; }
+
+                        let is_plain_user = (pt_entry & 0x4) != 0 && (pt_entry & 0x10) == 0 && (pt_entry & 0x200) == 0;
 
                         // 🚨 COPY-ON-WRITE IMPLEMENTATION
-                        // If it is User Memory (0x4), AND it is NOT MMIO (0x10), AND NOT Shared (0x200)
-                        if (pt_entry & 0x4) != 0 && (pt_entry & 0x10) == 0 && (pt_entry & 0x200) == 0 {
+                        // If it is User Memory (0x4), AND it is NOT MMIO (0x10), AND NOT Shared (0x200),
+                        // AND the task may write it (directly or through an earlier CoW)
+                        if is_plain_user && (pt_entry & 0x402) != 0 {
                             
                             // 1. Strip the Writable flag (Bit 1), Add the CoW flag (Bit 10 = 0x400)
-                            let cow_entry = (pt_entry & !(1 << 1)) | 0x400;
+                            let cow_entry = (pt_entry & !(1 << 1)) | 0x400 | SHARED_FRAME;
+                            share_frame(pt_entry & phys_mask);
                             
                             // 2. Update Child to point to the SAME frame, but Read-Only (CoW)
                             *child_pt_ptr.add(i1) = cow_entry;
@@ -405,6 +594,12 @@ pub fn clone_user_address_space(parent_cr3: PhysAddr, child_cr3: PhysAddr) {
                             let mut_parent_pt = parent_pt_ptr as *mut u64;
                             *mut_parent_pt.add(i1) = cow_entry;
                             
+                        } else if is_plain_user {
+                            // Read-only ELF segments are shared outright, counted in FRAME_REFS
+                            let shared_entry = pt_entry | SHARED_FRAME;
+                            share_frame(pt_entry & phys_mask);
+                            *child_pt_ptr.add(i1) = shared_entry;
+                            *(parent_pt_ptr as *mut u64).add(i1) = shared_entry;
                         } else {
                             // It is Shared MMIO/Framebuffer! Keep it fully writable for both.
                             *child_pt_ptr.add(i1) = pt_entry;
@@ -426,7 +621,7 @@ pub fn clear_user_address_space(cr3_phys: PhysAddr) {
 
         for i4 in 0..256 {
             let pml4_entry = *pml4.add(i4);
-            if pml4_entry & 1 == 0 { continue; }
+            if pml4_entry & 1 == 0 || i4 == crate::allocator::HEAP_PML4_SLOT { continue; }
 
             let pml3_phys = pml4_entry & phys_mask;
             let pml3 = (pml3_phys + offset) as *mut u64;
@@ -463,10 +658,11 @@ pub fn clear_user_address_space(cr3_phys: PhysAddr) {
                         
                         if (pt_entry & 0x4) != 0 {
                             // 🚨 THE CoW LEAK FIX 🚨
-                            // Do NOT free MMIO (0x10), Shared Memory (0x200), OR CoW Pages (0x400)!
-                            // If it has the CoW bit, the Parent is still relying on this physical RAM!
-                            if (pt_entry & 0x10) == 0 && (pt_entry & 0x200) == 0 && (pt_entry & 0x400) == 0 {
-                                let page_phys = pt_entry & phys_mask;
+                            // Do NOT free MMIO (0x10) or Shared Memory (0x200), nor a frame from
+                            // fork (CoW or read-only) that another address space still maps!
+                            let page_phys = pt_entry & phys_mask;
+                            if (pt_entry & 0x10) == 0 && (pt_entry & 0x200) == 0
+                                && (pt_entry & SHARED_FRAME == 0 || release_shared_frame(page_phys)) {
                                 use x86_64::structures::paging::PhysFrame;
                                 system.frame_allocator.deallocate_frame(PhysFrame::containing_address(x86_64::PhysAddr::new(page_phys)));
                             }
@@ -525,13 +721,13 @@ pub fn create_shm_block(size: usize) -> Option<u64> {
     }
     
     let id = NEXT_SHM_ID.fetch_add(1, core::sync::atomic::Ordering::Relaxed);
-    SHM_REGISTRY.lock().push(ShmBlock { id, frames, size });
+    SHM_REGISTRY.lock().push(ShmBlock { id, frames, size, mappings: 0, released: false });
     Some(id)
 }
 
 pub fn map_shm_block(id: u64, target_vaddr: u64) -> Result<u64, &'static str> {
-    let registry = SHM_REGISTRY.lock();
-    let block = registry.iter().find(|b| b.id == id).ok_or("SHM not found")?;
+    let mut registry = SHM_REGISTRY.lock();
+    let block = registry.iter_mut().find(|b| b.id == id).ok_or("SHM not found")?;
     
     let mut system_lock = MEMORY_MANAGER.lock();
     let system = system_lock.as_mut().ok_or("Memory System not initialized")?;
@@ -551,5 +747,42 @@ pub fn map_shm_block(id: u64, target_vaddr: u64) -> Result<u64, &'static str> {
             }
         }
     }
+    block.mappings += 1;
     Ok(target_vaddr)
+}
+
+/// Detaches block `id` from the caller's address space at `vaddr`, where
+/// map_shm_block put it. `release` says the block won't be mapped again;
+/// its frames are freed once the last mapping is gone. Nothing is unmapped
+/// unless every page at `vaddr` really is this block.
+pub fn unmap_shm_block(id: u64, vaddr: u64, release: bool) -> Result<(), &'static str> {
+    let mut registry = SHM_REGISTRY.lock();
+    let idx = registry.iter().position(|b| b.id == id).ok_or("SHM not found")?;
+
+    let mut system_lock = MEMORY_MANAGER.lock();
+    let system = system_lock.as_mut().ok_or("Memory System not initialized")?;
+    let mut active_mapper = unsafe { active_mapper() };
+    let start_page: Page<Size4KiB> = Page::containing_address(VirtAddr::new(vaddr));
+
+    let block = &mut registry[idx];
+    for (i, &phys_addr) in block.frames.iter().enumerate() {
+        match active_mapper.translate((start_page + i as u64).start_address()) {
+            x86_64::structures::paging::mapper::TranslateResult::Mapped { frame, .. }
+                if frame.start_address() == phys_addr => {},
+            _ => return Err("SHM not mapped there"),
+        }
+    }
+    for i in 0..block.frames.len() {
+        if let Ok((_, flush)) = active_mapper.unmap(start_page + i as u64) { flush.flush(); }
+    }
+
+    block.mappings = block.mappings.saturating_sub(1);
+    block.released |= release;
+    if block.released && block.mappings == 0 {
+        let block = registry.remove(idx);
+        for phys_addr in block.frames {
+            system.frame_allocator.deallocate_frame(PhysFrame::containing_address(phys_addr));
+        }
+    }
+    Ok(())
 }
\ No newline at end of file
@@ -1,3 +1,4 @@
+use core::sync::atomic::{AtomicBool, Ordering};
 use spin::Mutex;
 use x86_64::instructions::port::Port;
 use lazy_static::lazy_static;
@@ -5,21 +6,33 @@ use lazy_static::lazy_static;
 const PS2_CMD_PORT: u16 = 0x64;
 const PS2_DATA_PORT: u16 = 0x60;
 
+// Setting these sample rates in a row switches an IntelliMouse to 4-byte
+// packets; it then reports device ID 3 instead of 0
+const WHEEL_MAGIC_RATES: [u8; 3] = [200, 100, 80];
+const INTELLIMOUSE_ID: u8 = 3;
+
+// Logical range of a tablet's X and Y
+const TABLET_MAX: usize = 0x7FFF;
+
+// Set by MouseDriver::init once the mouse accepted wheel mode
+static PS2_WHEEL: AtomicBool = AtomicBool::new(false);
+
 pub struct MouseState {
     pub x: usize,
     pub y: usize,
     pub left_click: bool,
     pub right_click: bool,
     pub middle_click: bool,
-    pub screen_width: usize,
-    pub screen_height: usize,
+    /// Wheel notches since the last read, positive away from the user
+    pub wheel: i32,
+    /// The last update came from an absolute pointer (tablet) rather than a mouse
+    pub absolute: bool,
 }
 
 lazy_static! {
     pub static ref MOUSE_STATE: Mutex<MouseState> = Mutex::new(MouseState {
         x: 512, y: 384, 
-        left_click: false, right_click: false, middle_click: false,
-        screen_width: 1024, screen_height: 768, 
+        left_click: false, right_click: false, middle_click: false, wheel: 0, absolute: false,
     });
 }
 
@@ -27,7 +40,7 @@ pub struct MouseDriver {
     command_port: Port<u8>,
     data_port: Port<u8>,
     cycle: u8,
-    packet: [u8; 3], 
+    packet: [u8; 4],
 }
 
 impl MouseDriver {
@@ -36,7 +49,7 @@ impl MouseDriver {
             command_port: Port::new(PS2_CMD_PORT),
             data_port: Port::new(PS2_DATA_PORT),
             cycle: 0,
-            packet: [0; 3],
+            packet: [0; 4],
         }
     }
 
@@ -62,6 +75,19 @@ impl MouseDriver {
             // This prevents the hardware from flooding the buffer with 3 bytes and breaking the packet cycle!
             self.write_mouse(0xF6);
             self.wait_for_read(); let _ = self.data_port.read();
+
+            // Mice that ignore the sequence keep ID 0 and the 3-byte packet
+            for rate in WHEEL_MAGIC_RATES {
+                self.write_mouse(0xF3);
+                self.wait_for_read(); let _ = self.data_port.read();
+                self.write_mouse(rate);
+                self.wait_for_read(); let _ = self.data_port.read();
+            }
+            self.write_mouse(0xF2);
+            self.wait_for_read(); let _ = self.data_port.read();
+            self.wait_for_read(); let id = self.data_port.read();
+            PS2_WHEEL.store(id == INTELLIMOUSE_ID, Ordering::SeqCst);
+            crate::serial_println!("[MOUSE] PS/2 device ID {}{}", id, if id == INTELLIMOUSE_ID { " (wheel)" } else { "" });
             
             self.write_mouse(0xF4);
             self.wait_for_read(); let _ = self.data_port.read();
@@ -74,15 +100,31 @@ impl MouseDriver {
     }
 }
 
-pub fn update_from_usb(dx: i8, dy: i8, buttons: u8) {
+pub fn update_from_usb(dx: i8, dy: i8, wheel: i8, buttons: u8) {
     let mut state = MOUSE_STATE.lock();
     let new_x = state.x as i64 + (dx as i64); 
     let new_y = state.y as i64 + (dy as i64); 
-    state.x = new_x.clamp(0, (state.screen_width - 1) as i64) as usize;
-    state.y = new_y.clamp(0, (state.screen_height - 1) as i64) as usize;
+    (state.x, state.y) = crate::display::clamp(new_x.max(0) as usize, new_y.max(0) as usize);
+    state.left_click = (buttons & 0x01) != 0;
+    state.right_click = (buttons & 0x02) != 0;
+    state.middle_click = (buttons & 0x04) != 0;
+    state.wheel += wheel as i32;
+    state.absolute = false;
+}
+
+/// Places the cursor from a tablet report: `x` and `y` span 0..=TABLET_MAX
+/// across the box around every display, whatever a relative mouse did before.
+pub fn update_absolute(x: u16, y: u16, wheel: i8, buttons: u8) {
+    let (width, height) = crate::display::extent();
+    let mut state = MOUSE_STATE.lock();
+    let x = (x as usize).min(TABLET_MAX) * width.saturating_sub(1) / TABLET_MAX;
+    let y = (y as usize).min(TABLET_MAX) * height.saturating_sub(1) / TABLET_MAX;
+    (state.x, state.y) = crate::display::clamp(x, y);
     state.left_click = (buttons & 0x01) != 0;
     state.right_click = (buttons & 0x02) != 0;
     state.middle_click = (buttons & 0x04) != 0;
+    state.wheel += wheel as i32;
+    state.absolute = true;
 }
 
 pub fn handle_interrupt(packet_byte: u8) {
@@ -94,8 +136,9 @@ pub fn handle_interrupt(packet_byte: u8) {
         match driver.cycle {
             0 => { if (packet_byte & 0x08) != 0 { driver.packet[0] = packet_byte; driver.cycle += 1; } }
             1 => { driver.packet[1] = packet_byte; driver.cycle += 1; }
-            2 => {
-                driver.packet[2] = packet_byte;
+            2 if PS2_WHEEL.load(Ordering::Relaxed) => { driver.packet[2] = packet_byte; driver.cycle += 1; }
+            2 | 3 => {
+                driver.packet[driver.cycle as usize] = packet_byte;
                 let flags = driver.packet[0];
                 let rel_x = if (flags & 0x10) != 0 { (driver.packet[1] as i16) - 256 } else { driver.packet[1] as i16 };
                 let rel_y = if (flags & 0x20) != 0 { (driver.packet[2] as i16) - 256 } else { driver.packet[2] as i16 };
@@ -105,10 +148,12 @@ pub fn handle_interrupt(packet_byte: u8) {
                 let new_x = state.x as i32 + (rel_x as i32 * multiplier);
                 let new_y = state.y as i32 - (rel_y as i32 * multiplier); 
 
-                state.x = new_x.clamp(0, state.screen_width as i32 - 1) as usize;
-                state.y = new_y.clamp(0, state.screen_height as i32 - 1) as usize;
+                (state.x, state.y) = crate::display::clamp(new_x.max(0) as usize, new_y.max(0) as usize);
                 state.left_click = (flags & 0x01) != 0;
                 state.right_click = (flags & 0x02) != 0;
+                state.absolute = false;
+                // The fourth byte counts notches toward the user
+                if driver.cycle == 3 { state.wheel -= driver.packet[3] as i8 as i32; }
                 driver.cycle = 0;
             }
             _ => driver.cycle = 0,
@@ -0,0 +1,244 @@
+use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicBool, Ordering};
+use x86_64::instructions::port::Port;
+use x86_64::structures::idt::InterruptStackFrame;
+use x86_64::structures::paging::Translate;
+use x86_64::VirtAddr;
+
+// ==========================================
+// PANIC SCREEN
+// ==========================================
+// Paints the screen red and shows what the kernel was doing: the message,
+// the task that was running, a few registers, a raw backtrace from the
+// frame-pointer chain (addresses only; look them up in the kernel's map
+// file) and the newest lines of the kernel log ring. Then it waits for a
+// key on the PS/2 controller: R reboots, anything else halts. USB keyboards
+// can't answer here, since the xHCI driver needs interrupts.
+//
+// Nothing on this path allocates (the heap may be what broke), and every
+// lock it needs is forced open, since the panicking code may have held it.
+// The first core in stops the others with an NMI; a second panic, on this
+// core or another, just halts rather than drawing over the first.
+
+const MAX_FRAMES: usize = 16;
+const LOG_TAIL_LINES: usize = 20;
+// Enough of the log ring to hold LOG_TAIL_LINES ordinary lines
+const LOG_TAIL_BYTES: usize = 4096;
+const MSG_BYTES: usize = 512;
+const SC_R: u8 = 0x13;
+
+static PANICKING: AtomicBool = AtomicBool::new(false);
+
+/// True once some core has started the panic screen.
+pub fn in_progress() -> bool { PANICKING.load(Ordering::SeqCst) }
+
+/// Formats into a fixed buffer, dropping whatever doesn't fit.
+pub struct StackBuf<const N: usize> {
+    buf: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> StackBuf<N> {
+    pub const fn new() -> Self { Self { buf: [0; N], len: 0 } }
+
+    pub fn as_str(&self) -> &str {
+        // Only whole chars are ever copied in
+        unsafe { core::str::from_utf8_unchecked(&self.buf[..self.len]) }
+    }
+}
+
+impl<const N: usize> Write for StackBuf<N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut take = s.len().min(N - self.len);
+        while !s.is_char_boundary(take) { take -= 1; }
+        self.buf[self.len..self.len + take].copy_from_slice(&s.as_bytes()[..take]);
+        self.len += take;
+        Ok(())
+    }
+}
+
+pub fn on_panic(info: &core::panic::PanicInfo) -> ! {
+    let mut msg = StackBuf::<MSG_BYTES>::new();
+    let _ = write!(msg, "{}", info);
+    show(msg.as_str());
+}
+
+/// A CPU exception the kernel can't recover from. Registers come from the
+/// exception frame, not from the handler that is reporting it.
+pub fn fault(name: &str, frame: &InterruptStackFrame, error_code: u64) -> ! {
+    let mut msg = StackBuf::<MSG_BYTES>::new();
+    let _ = write!(msg, "EXCEPTION: {} (error {:#x})\n     RIP {:#018x}  RSP {:#018x}  CS {:#x}",
+        name, error_code, frame.instruction_pointer.as_u64(), frame.stack_pointer.as_u64(), frame.code_segment);
+    show(msg.as_str());
+}
+
+pub fn show(msg: &str) -> ! {
+    x86_64::instructions::interrupts::disable();
+    if PANICKING.swap(true, Ordering::SeqCst) {
+        loop { x86_64::instructions::hlt(); }
+    }
+    crate::apic::send_nmi_to_others();
+    let (rsp, rbp): (u64, u64);
+    unsafe {
+        core::arch::asm!("mov {}, rsp", out(reg) rsp);
+        core::arch::asm!("mov {}, rbp", out(reg) rbp);
+        crate::vga_log::VGA_LOGGER.force_unlock();
+        crate::serial::SERIAL1.force_unlock();
+        crate::serial::BOOT_LOG.force_unlock();
+    }
+    crate::vga_log::enter_panic_mode();
+
+    unsafe {
+        if let Some(painter) = &mut crate::gui::SCREEN_PAINTER {
+            let buf = painter.buffer.as_mut();
+            for i in (0..buf.len()).step_by(4) {
+                buf[i] = 0; buf[i+1] = 0; buf[i+2] = 255; buf[i+3] = 255;
+            }
+        }
+    }
+    crate::vga_log::home_cursor();
+    crate::serial_println!("\n[PANIC] {}", msg);
+    crate::vga_println!("  [FATAL KERNEL PANIC]\n  -> {}\n", msg);
+
+    match current_task() {
+        Some((pid, cpu)) => crate::vga_println!("  Task: PID {} on CPU {}", pid, cpu),
+        None => crate::vga_println!("  Task: none (early boot)"),
+    }
+    let cr2 = x86_64::registers::control::Cr2::read_raw();
+    let cr3 = x86_64::registers::control::Cr3::read().0.start_address().as_u64();
+    crate::vga_println!("  RSP {:#018x}  RBP {:#018x}  CR2 {:#018x}  CR3 {:#x}", rsp, rbp, cr2, cr3);
+    crate::serial_println!("[PANIC] RSP {:#x} RBP {:#x} CR2 {:#x} CR3 {:#x}", rsp, rbp, cr2, cr3);
+
+    print_backtrace(rbp);
+    print_log_tail();
+
+    crate::vga_println!("\n  Press R to reboot, any other key to halt.");
+    // Under virtio-gpu the host only shows what it's told changed
+    crate::drivers::virtio_gpu::show_panic_screen();
+    crate::sound::panic_alarm();
+    if wait_for_key() == SC_R { perform_reboot(); }
+    crate::vga_println!("  Halted.");
+    crate::drivers::virtio_gpu::show_panic_screen();
+    loop { x86_64::instructions::hlt(); }
+}
+
+/// (pid, logical core) of whatever this core was running
+fn current_task() -> Option<(u64, u32)> {
+    if x86_64::registers::model_specific::GsBase::read().as_u64() == 0 { return None; }
+    let percpu = crate::percpu::current();
+    let idx = percpu.scheduler.core_task_idx[percpu.logical_id as usize % 32];
+    percpu.scheduler.tasks.get(idx).map(|t| (t.pid, percpu.logical_id as u32))
+}
+
+/// Whether reading 8 bytes at `addr` can't fault. Says no when the page
+/// tables are locked, rather than guessing.
+fn is_readable(addr: u64) -> bool {
+    if addr % 8 != 0 || VirtAddr::try_new(addr).is_err() { return false; }
+    match crate::memory::MEMORY_MANAGER.try_lock() {
+        Some(guard) => guard.as_ref().map_or(false, |mm| mm.mapper.translate_addr(VirtAddr::new(addr)).is_some()),
+        None => false,
+    }
+}
+
+/// Follows saved RBPs: [rbp] is the caller's RBP, [rbp + 8] the return address.
+fn print_backtrace(mut rbp: u64) {
+    crate::vga_println!("\n  Backtrace:");
+    crate::serial_println!("[PANIC] Backtrace:");
+    let mut line = StackBuf::<128>::new();
+    let mut frames = 0;
+    while frames < MAX_FRAMES && rbp != 0 && is_readable(rbp) && is_readable(rbp + 8) {
+        let (next, ret) = unsafe { (*(rbp as *const u64), *((rbp + 8) as *const u64)) };
+        if ret == 0 { break; }
+        crate::serial_println!("  #{} {:#x}", frames, ret);
+        let _ = write!(line, "  #{:<2} {:#018x}", frames, ret);
+        frames += 1;
+        if frames % 3 == 0 {
+            crate::vga_println!("{}", line.as_str());
+            line = StackBuf::new();
+        }
+        // The stack grows down, so callers' frames sit at higher addresses
+        if next <= rbp { break; }
+        rbp = next;
+    }
+    if frames % 3 != 0 { crate::vga_println!("{}", line.as_str()); }
+    if frames == 0 { crate::vga_println!("  (no frame pointers to follow)"); }
+}
+
+/// The newest lines of the log ring, as many as still fit on screen.
+fn print_log_tail() {
+    let mut tail = [0u8; LOG_TAIL_BYTES];
+    let (len, lost) = {
+        let log = crate::serial::BOOT_LOG.lock();
+        let (start, len) = log.read_at(log.end().saturating_sub(LOG_TAIL_BYTES as u64), &mut tail);
+        (len, start > 0)
+    };
+    let text = &tail[..len];
+
+    let fits = crate::vga_log::rows_left().saturating_sub(3);
+    let wanted = LOG_TAIL_LINES.min(fits);
+    if wanted == 0 { return; }
+    crate::vga_println!("\n  Recent kernel log:");
+
+    // Walk back from the end to the start of the wanted-th last line
+    let text = match text.last() { Some(b'\n') => &text[..len - 1], _ => text };
+    let mut start = text.len();
+    let mut lines = 0;
+    while start > 0 && lines < wanted {
+        start -= 1;
+        if text[start] == b'\n' { lines += 1; }
+    }
+    // Without a newline before it, the first line may be cut in half by the ring
+    if lines < wanted && lost { while start < text.len() && text[start] != b'\n' { start += 1; } }
+
+    let columns = crate::vga_log::columns().saturating_sub(4);
+    for line in text[start..].split(|&b| b == b'\n').filter(|l| !l.is_empty()) {
+        let mut row = StackBuf::<256>::new();
+        let _ = row.write_str("    ");
+        for &b in line.iter().take(columns) {
+            let _ = row.write_char(if (0x20..0x7F).contains(&b) { b as char } else { '.' });
+        }
+        crate::vga_println!("{}", row.as_str());
+    }
+}
+
+/// Blocks on the PS/2 controller until a key goes down and returns its
+/// set-1 make code.
+fn wait_for_key() -> u8 {
+    let mut status = Port::<u8>::new(0x64);
+    let mut data = Port::<u8>::new(0x60);
+    unsafe {
+        // Whatever was typed before the panic doesn't count
+        while status.read() & 0x01 != 0 { let _: u8 = data.read(); }
+        loop {
+            let s = status.read();
+            if s & 0x01 == 0 { core::hint::spin_loop(); continue; }
+            let code: u8 = data.read();
+            // Mouse bytes, extended-key prefixes and releases
+            if s & 0x20 != 0 || code == 0xE0 || code & 0x80 != 0 { continue; }
+            return code;
+        }
+    }
+}
+
+/// Resets the machine: the 8042 reset line, then the PCI reset register,
+/// then a triple fault.
+pub fn perform_reboot() -> ! {
+    crate::serial_println!("[PANIC] Rebooting...");
+    unsafe {
+        let mut status = Port::<u8>::new(0x64);
+        for _ in 0..100_000 {
+            if status.read() & 0x02 == 0 { break; }
+            core::hint::spin_loop();
+        }
+        status.write(0xFE);
+        for _ in 0..10_000_000 { core::hint::spin_loop(); }
+
+        Port::<u8>::new(0xCF9).write(0x06);
+        for _ in 0..10_000_000 { core::hint::spin_loop(); }
+
+        let empty = x86_64::structures::DescriptorTablePointer { limit: 0, base: VirtAddr::new(0) };
+        x86_64::instructions::tables::lidt(&empty);
+        core::arch::asm!("int3");
+    }
+    loop { x86_64::instructions::hlt(); }
+}
@@ -2,9 +2,7 @@ use alloc::vec::Vec;
 use core::cmp::Ordering;
 use crate::drivers::nvme::NvmeDriver;
 
-const FAT_SECTOR_SIZE: u64 = 512;
 const NYXOS_REQUIRED_BYTES: u64 = 2 * 1024 * 1024 * 1024; // 2GB
-const NYXOS_REQUIRED_SECTORS: u64 = NYXOS_REQUIRED_BYTES / FAT_SECTOR_SIZE;
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub struct GptEntry {
@@ -33,36 +31,40 @@ impl NyxPartitioner {
     pub fn find_free_space(driver: &mut NvmeDriver) -> Option<u64> {
         crate::serial_println!("[GPT] Initiating safe read-only drive mapping...");
         
-        let mut entry_block = alloc::vec![0u8; 4096];
+        let nsid = driver.default_nsid();
+        let block_size = driver.lba_size(nsid);
+        let mut entry_block = alloc::vec![0u8; block_size];
         let mut partitions = Vec::new();
 
-        // 1. Read LBA 2 (The start of the Partition Entry Array)
-        if !driver.read_block(2, &mut entry_block) {
-            crate::serial_println!("[GPT] ERR: Failed to read partition array.");
-            return None;
-        }
+        // 1. Read the Partition Entry Array (128 entries of 128 bytes from LBA 2)
+        for array_lba in 0..(16384 / block_size).max(1) as u64 {
+            if !driver.read_block(nsid, 2 + array_lba, &mut entry_block) {
+                crate::serial_println!("[GPT] ERR: Failed to read partition array.");
+                return None;
+            }
 
-        // 2. Parse all 32 potential partition slots
-        for i in 0..32 {
-            let offset = i * 128;
-            if offset + 128 > entry_block.len() { break; }
-            
-            let mut type_guid = [0u8; 16];
-            type_guid.copy_from_slice(&entry_block[offset..offset + 16]);
-            
-            // If the GUID is not all zeros, it is an active partition
-            if type_guid.iter().any(|&b| b != 0) {
-                let start_lba = u64::from_le_bytes(entry_block[offset + 32..offset + 40].try_into().unwrap());
-                let end_lba = u64::from_le_bytes(entry_block[offset + 40..offset + 48].try_into().unwrap());
+            // 2. Parse every slot in this block
+            for i in 0..block_size / 128 {
+                let offset = i * 128;
+                
+                let mut type_guid = [0u8; 16];
+                type_guid.copy_from_slice(&entry_block[offset..offset + 16]);
                 
-                partitions.push(GptEntry { type_guid, start_lba, end_lba });
+                // If the GUID is not all zeros, it is an active partition
+                if type_guid.iter().any(|&b| b != 0) {
+                    let start_lba = u64::from_le_bytes(entry_block[offset + 32..offset + 40].try_into().unwrap());
+                    let end_lba = u64::from_le_bytes(entry_block[offset + 40..offset + 48].try_into().unwrap());
+                    
+                    partitions.push(GptEntry { type_guid, start_lba, end_lba });
+                }
             }
         }
+        let required_lbas = NYXOS_REQUIRED_BYTES / block_size as u64;
 
         // 3. Sort partitions physically from front to back of the drive
         partitions.sort();
 
-        crate::serial_println!("[GPT] Found {} active partitions. Scanning for {} sectors of free space...", partitions.len(), NYXOS_REQUIRED_SECTORS);
+        crate::serial_println!("[GPT] Found {} active partitions. Scanning for {} sectors of free space...", partitions.len(), required_lbas);
 
         // 4. Find the gaps between partitions
         // We start looking after LBA 34 (End of GPT reserved area)
@@ -72,7 +74,7 @@ impl NyxPartitioner {
             // Calculate the gap between our search cursor and the start of the next partition
             let gap_sectors = part.start_lba.saturating_sub(current_search_lba);
             
-            if gap_sectors >= NYXOS_REQUIRED_SECTORS {
+            if gap_sectors >= required_lbas {
                 crate::serial_println!("[GPT] SUCCESS: Found safe gap of {} sectors starting at LBA {}", gap_sectors, current_search_lba);
                 return Some(current_search_lba);
             }
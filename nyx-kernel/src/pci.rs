@@ -66,8 +66,86 @@ impl PciDriver {
             Some(addr)
         } else { None }
     }
+
+    pub fn write_config(bus: u8, device: u8, func: u8, offset: u8, value: u32) {
+        let address = 0x80000000 | ((bus as u32) << 16) | ((device as u32) << 11) | ((func as u32) << 8) | (offset as u32 & 0xFC);
+        let mut port_addr: Port<u32> = Port::new(0xCF8);
+        let mut port_data: Port<u32> = Port::new(0xCFC);
+        unsafe {
+            port_addr.write(address);
+            port_data.write(value);
+        }
+    }
+
+    /// Config-space offset of capability `cap_id`, if the device has it.
+    pub fn find_capability(dev: &PciDevice, cap_id: u8) -> Option<u8> {
+        let status = Self::read_config(dev.bus, dev.device, dev.func, 0x04) >> 16;
+        if status & (1 << 4) == 0 { return None; } // No capability list
+
+        let mut cap_ptr = (Self::read_config(dev.bus, dev.device, dev.func, 0x34) & 0xFC) as u8;
+        // A broken list could loop forever; there are at most 48 capabilities in 256 bytes
+        for _ in 0..48 {
+            if cap_ptr == 0 { return None; }
+            let cap = Self::read_config(dev.bus, dev.device, dev.func, cap_ptr);
+            if (cap & 0xFF) as u8 == cap_id { return Some(cap_ptr); }
+            cap_ptr = ((cap >> 8) & 0xFC) as u8;
+        }
+        None
+    }
+
+    /// Sends the device's interrupts to `vector` on the BSP: through MSI if it has it,
+    /// else through the first MSI-X table entry. Legacy INTx is switched off either way.
+    /// False if the device supports neither, or the local APIC isn't running.
+    pub fn enable_msi(&self, dev: &PciDevice, vector: u8) -> bool {
+        if !crate::apic::APIC_MODE.load(core::sync::atomic::Ordering::Relaxed) { return false; }
+        let bsp_apic_id = unsafe { crate::percpu::PER_CPU.as_ref().map_or(0, |c| c[0].apic_id) } as u32;
+        let msg_addr = 0xFEE0_0000 | (bsp_apic_id << 12);
+        let msg_data = vector as u32; // Fixed delivery, edge triggered
+        let (bus, device, func) = (dev.bus, dev.device, dev.func);
+
+        if let Some(cap) = Self::find_capability(dev, CAP_MSI) {
+            let ctrl = Self::read_config(bus, device, func, cap);
+            let is_64bit = ctrl & (1 << 23) != 0;
+            Self::write_config(bus, device, func, cap + 4, msg_addr);
+            if is_64bit { Self::write_config(bus, device, func, cap + 8, 0); }
+            let data_reg = if is_64bit { cap + 12 } else { cap + 8 };
+            Self::write_config(bus, device, func, data_reg, msg_data);
+            // Enable, with a single message (Multiple Message Enable = 0)
+            Self::write_config(bus, device, func, cap, (ctrl & !(0x7 << 20)) | (1 << 16));
+        } else if let Some(cap) = Self::find_capability(dev, CAP_MSIX) {
+            let table = Self::read_config(bus, device, func, cap + 4);
+            let table_phys = match self.get_bar_address(dev, (table & 0x7) as u8) {
+                Some(bar) => bar + (table & !0x7) as u64,
+                None => return false,
+            };
+            if unsafe { crate::memory::map_mmio(table_phys, 16) }.is_err() { return false; }
+
+            let ctrl = Self::read_config(bus, device, func, cap);
+            // Function mask while the entry is programmed
+            Self::write_config(bus, device, func, cap, ctrl | (1 << 31) | (1 << 30));
+            unsafe {
+                let entry = table_phys as *mut u32;
+                core::ptr::write_volatile(entry, msg_addr);
+                core::ptr::write_volatile(entry.add(1), 0);
+                core::ptr::write_volatile(entry.add(2), msg_data);
+                core::ptr::write_volatile(entry.add(3), 0); // Unmasked
+            }
+            Self::write_config(bus, device, func, cap, (ctrl | (1 << 31)) & !(1 << 30));
+        } else {
+            return false;
+        }
+
+        let cmd = Self::read_config(bus, device, func, 0x04);
+        Self::write_config(bus, device, func, 0x04, (cmd & 0xFFFF) | (1 << 10) | (1 << 2)); // INTx off, bus master
+        crate::serial_println!("[PCI] {:02x}:{:02x}.{} interrupts -> vector {:#x} (MSI{})",
+            bus, device, func, vector, if Self::find_capability(dev, CAP_MSI).is_some() { "" } else { "-X" });
+        true
+    }
 }
 
+const CAP_MSI: u8 = 0x05;
+const CAP_MSIX: u8 = 0x11;
+
 // ==========================================
 // 2. MODERN PCIe (MCFG) STRUCTURES
 // ==========================================
@@ -188,21 +266,17 @@ fn enumerate_pci_legacy() {
                             cap_ptr = ((cap >> 8) & 0xFF) as u8;
                         }
 
-                        use smoltcp::iface::{Config, Interface};
-                        use smoltcp::wire::{EthernetAddress, HardwareAddress};
-                        let hw_addr = HardwareAddress::Ethernet(EthernetAddress::from_bytes(&eth_driver.mac_address));
-
-                        let mut config = Config::new();
-                        config.hardware_addr = Some(hw_addr);
-                        
-                        let iface = Interface::new(config, &mut eth_driver);
-
-                        *crate::drivers::net::NET_DRIVER.lock() = Some(eth_driver);
-                        *crate::drivers::net::NET_IFACE.lock() = Some(iface);
+                        crate::drivers::net::attach(crate::drivers::net::NicDriver::Rtl8168(eth_driver));
                     }
                 }
+
+                if dev.vendor_id == crate::drivers::net::e1000::VENDOR_ID && crate::drivers::net::e1000::DEVICE_IDS.contains(&dev.device_id) {
+                    crate::serial_println!("[PCI] Binding Intel E1000 Ethernet Driver...");
+                    crate::drivers::net::e1000::probe(&dev);
+                }
             },
             0x01 => crate::serial_println!("[PCI] Found Mass Storage: Vendor {:#06x}, Device {:#06x}", dev.vendor_id, dev.device_id),
+            0x04 if dev.subclass_id == 0x03 => crate::sound::found_hda(dev.vendor_id, dev.device_id),
             
             0x03 => {
                 crate::serial_println!("[PCI] *** FOUND GPU: Vendor {:#06x}, Device {:#06x} ***", dev.vendor_id, dev.device_id);
@@ -228,6 +302,12 @@ fn enumerate_pci_legacy() {
                             }
                         }
                     }
+                } else if dev.vendor_id == crate::drivers::virtio_gpu::VENDOR_ID && dev.device_id == crate::drivers::virtio_gpu::DEVICE_ID {
+                    crate::serial_println!("[PCI] virtio-gpu detected (Legacy)");
+                    crate::drivers::virtio_gpu::probe(&dev);
+                } else if dev.vendor_id == crate::drivers::gpu::bochs::VENDOR_ID && dev.device_id == crate::drivers::gpu::bochs::DEVICE_ID {
+                    crate::serial_println!("[PCI] Bochs/QEMU VGA detected (Legacy)");
+                    crate::drivers::gpu::bochs::probe(&dev);
                 }
             },
 
@@ -243,17 +323,7 @@ fn enumerate_pci_legacy() {
                         unsafe { Port::<u32>::new(0xCF8).write(address); Port::<u32>::new(0xCFC).write(cmd); }
                         if let Some(mmio_phys) = driver.get_bar_address(&dev, 0) {
                             if let Ok(mmio_virt) = unsafe { crate::memory::map_mmio(mmio_phys, 0x10000) } {
-                                unsafe {
-                                    match crate::usb::XhciController::new(mmio_virt) {
-                                        Ok(mut controller) => {
-                                            if controller.init().is_ok() {
-                                                controller.check_ports();
-                                                *crate::usb::USB_CONTROLLER.lock() = Some(controller);
-                                            }
-                                        },
-                                        Err(_) => {},
-                                    }
-                                }
+                                unsafe { crate::usb::bring_up(&dev, mmio_virt); }
                             }
                         }
                     }
@@ -368,20 +438,22 @@ fn scan_bus_range(base_addr: u64, start_bus: u8, end_bus: u8) {
                                         cap_ptr = ((cap >> 8) & 0xFF) as u8;
                                     }
 
-                                    use smoltcp::iface::{Config, Interface};
-                                    use smoltcp::wire::{EthernetAddress, HardwareAddress};
-                                    let hw_addr = HardwareAddress::Ethernet(EthernetAddress::from_bytes(&eth_driver.mac_address));
-
-                                    let mut config = Config::new();
-                                    config.hardware_addr = Some(hw_addr);
-                                    
-                                    let iface = Interface::new(config, &mut eth_driver);
+                                    crate::drivers::net::attach(crate::drivers::net::NicDriver::Rtl8168(eth_driver));
+                                }
 
-                                    *crate::drivers::net::NET_DRIVER.lock() = Some(eth_driver);
-                                    *crate::drivers::net::NET_IFACE.lock() = Some(iface);
+                                if vendor_id == crate::drivers::net::e1000::VENDOR_ID && crate::drivers::net::e1000::DEVICE_IDS.contains(&device_id) {
+                                    crate::serial_println!("[PCI] Binding Intel E1000 Ethernet Driver...");
+                                    let pci_dev = crate::pci::PciDevice {
+                                        bus, device, func,
+                                        vendor_id, device_id,
+                                        class_id: class_code,
+                                        subclass_id: subclass,
+                                    };
+                                    crate::drivers::net::e1000::probe(&pci_dev);
                                 }
                             },
                             0x01 => crate::serial_println!("[PCI] Found Mass Storage: Vendor {:#06x}, Device {:#06x}", vendor_id, device_id),
+                            0x04 if subclass == 0x03 => crate::sound::found_hda(vendor_id, device_id),
                             0x03 => {
                                 if vendor_id == 0x8086 {
                                     let gen = crate::drivers::gpu::intel::IntelGpuDriver::probe(device_id);
@@ -413,6 +485,24 @@ fn scan_bus_range(base_addr: u64, start_bus: u8, end_bus: u8) {
                                             }
                                         }
                                     }
+                                } else if vendor_id == crate::drivers::virtio_gpu::VENDOR_ID && device_id == crate::drivers::virtio_gpu::DEVICE_ID {
+                                    crate::serial_println!("[PCI] virtio-gpu detected (MCFG)");
+                                    let pci_dev = crate::pci::PciDevice {
+                                        bus, device, func,
+                                        vendor_id, device_id,
+                                        class_id: class_code,
+                                        subclass_id: subclass,
+                                    };
+                                    crate::drivers::virtio_gpu::probe(&pci_dev);
+                                } else if vendor_id == crate::drivers::gpu::bochs::VENDOR_ID && device_id == crate::drivers::gpu::bochs::DEVICE_ID {
+                                    crate::serial_println!("[PCI] Bochs/QEMU VGA detected (MCFG)");
+                                    let pci_dev = crate::pci::PciDevice {
+                                        bus, device, func,
+                                        vendor_id, device_id,
+                                        class_id: class_code,
+                                        subclass_id: subclass,
+                                    };
+                                    crate::drivers::gpu::bochs::probe(&pci_dev);
                                 }
                             },
                             0x0C => {
@@ -430,18 +520,14 @@ fn scan_bus_range(base_addr: u64, start_bus: u8, end_bus: u8) {
                                         let mut mmio_phys = (bar0 & 0xFFFFFFF0) as u64;
                                         if (bar0 & 0b100) != 0 { mmio_phys |= (bar1 as u64) << 32; }
                                         
+                                        let pci_dev = crate::pci::PciDevice {
+                                            bus, device, func,
+                                            vendor_id, device_id,
+                                            class_id: class_code,
+                                            subclass_id: subclass,
+                                        };
                                         if let Ok(mmio_virt) = unsafe { crate::memory::map_mmio(mmio_phys, 0x10000) } {
-                                            unsafe {
-                                                match crate::usb::XhciController::new(mmio_virt) {
-                                                    Ok(mut controller) => {
-                                                        if controller.init().is_ok() {
-                                                            controller.check_ports();
-                                                            *crate::usb::USB_CONTROLLER.lock() = Some(controller);
-                                                        }
-                                                    },
-                                                    Err(_) => {},
-                                                }
-                                            }
+                                            unsafe { crate::usb::bring_up(&pci_dev, mmio_virt); }
                                         }
                                     }
                                 } else if subclass == 0x05 {
@@ -59,6 +59,16 @@ pub fn init(apic_ids: &[u32]) {
     crate::serial_println!("[PERCPU] Core 0 initialized, GS loaded, and GDT/TSS active.");
 }
 
+impl PerCpu {
+    /// Makes `top` the kernel stack for everything that enters from ring 3
+    /// on this core: SYSCALL (gs:[0]) and interrupts (TSS RSP0). Every task
+    /// has its own, so the scheduler calls this on each switch.
+    pub fn set_kernel_stack(&mut self, top: u64) {
+        self.kernel_rsp = top;
+        self.gdt_state.set_rsp0(top);
+    }
+}
+
 pub fn current() -> &'static mut PerCpu {
     unsafe {
         let ptr: *mut PerCpu;
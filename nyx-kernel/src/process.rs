@@ -3,38 +3,9 @@ use alloc::vec::Vec;
 use alloc::collections::VecDeque;
 use crate::scheduler::{FileDescriptor, TaskState};
 use core::sync::atomic::{AtomicU64, Ordering};
+use alloc::sync::Arc;
+use spin::Mutex;
 
-#[repr(C)]
-#[derive(Debug, Clone, Copy)]
-pub struct Elf64_Ehdr {
-    pub e_ident: [u8; 16],
-    pub e_type: u16,
-    pub e_machine: u16,
-    pub e_version: u32,
-    pub e_entry: u64,
-    pub e_phoff: u64,
-    pub e_shoff: u64,
-    pub e_flags: u32,
-    pub e_ehsize: u16,
-    pub e_phentsize: u16,
-    pub e_phnum: u16,
-    pub e_shentsize: u16,
-    pub e_shnum: u16,
-    pub e_shstrndx: u16,
-}
-
-#[repr(C)]
-#[derive(Debug, Clone, Copy)]
-pub struct Elf64_Phdr {
-    pub p_type: u32,
-    pub p_flags: u32,
-    pub p_offset: u64,
-    pub p_vaddr: u64,
-    pub p_paddr: u64,
-    pub p_filesz: u64,
-    pub p_memsz: u64,
-    pub p_align: u64,
-}
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
 pub struct IpcMessage {
@@ -44,47 +15,21 @@ pub struct IpcMessage {
     pub data2: u64,
 }
 
-pub fn load_elf(file_data: &[u8]) -> Result<u64, &'static str> {
-    if file_data.len() < core::mem::size_of::<Elf64_Ehdr>() { return Err("File too small"); }
-    let header = unsafe { &*(file_data.as_ptr() as *const Elf64_Ehdr) };
-    if header.e_ident[0..4] != [0x7F, b'E', b'L', b'F'] { return Err("Invalid ELF Magic"); }
-
-    let ph_offset = header.e_phoff as usize;
-    let ph_size = header.e_phentsize as usize;
-    
-    for i in 0..header.e_phnum {
-        let offset = ph_offset + (i as usize * ph_size);
-        let phdr = unsafe { &*(file_data.as_ptr().add(offset) as *const Elf64_Phdr) };
-
-        if phdr.p_type == 1 { // PT_LOAD Segment
-            // PIE FIX: Allow modern GCC binaries to load at address 0x0.
-            // We only block them from loading into the Top Half (Kernel Space).
-            if phdr.p_vaddr >= 0x0000_7FFF_FFFF_FFFF { 
-                return Err("Security Violation: Cannot load into Kernel Space"); 
-            }
-            
-            let start_page = phdr.p_vaddr & !0xFFF; 
-            let end_page = (phdr.p_vaddr + phdr.p_memsz + 0xFFF) & !0xFFF; 
-            let num_pages = ((end_page - start_page) / 4096) as usize;
-
-            crate::memory::allocate_user_pages_at(start_page, num_pages)?;
-
-            unsafe {
-                let dest = phdr.p_vaddr as *mut u8;
-                let src = file_data.as_ptr().add(phdr.p_offset as usize);
-                core::ptr::copy_nonoverlapping(src, dest, phdr.p_filesz as usize);
-
-                if phdr.p_memsz > phdr.p_filesz {
-                    let bss_start = dest.add(phdr.p_filesz as usize);
-                    let bss_len = phdr.p_memsz - phdr.p_filesz;
-                    core::ptr::write_bytes(bss_start, 0, bss_len as usize);
-                }
-            }
-        }
-    }
-    Ok(header.e_entry)
+/// Where a user task first entered ring 3. The kernel half of its state (cr3,
+/// the kernel stack it re-enters on, the context saved at each switch) is
+/// in Process itself; this is what the fault path adds when it reports
+/// whose code went wrong (see scheduler::kill_current_user_task).
+#[derive(Debug, Clone, Copy)]
+pub struct UserTask {
+    pub entry: u64,
+    pub stack_top: u64,
 }
 
+/// Drops into ring 3 at `entry` on `stack`. Only init starts this way; later
+/// tasks start through prime_user_entry. Nothing ever comes back here: the
+/// boot stack this runs on is abandoned, the task enters the kernel on its
+/// own kernel stack (see PerCpu::set_kernel_stack), and when it exits or
+/// faults, scheduler::exit_current_user_task hands the core to the scheduler.
 pub unsafe fn enter_userspace(entry: u64, stack: u64) -> ! {
     core::arch::asm!(
         "cli",           
@@ -108,6 +53,52 @@ pub unsafe fn enter_userspace(entry: u64, stack: u64) -> ! {
 
 static NEXT_PID: AtomicU64 = AtomicU64::new(1);
 
+pub const KERNEL_STACK_PAGES: usize = 4;
+
+// Every program gets the same stack window; address spaces keep them apart
+pub const USER_STACK_BASE: u64 = 0x7FFF_0000_0000;
+pub const USER_STACK_PAGES: usize = 32;
+pub const USER_STACK_TOP: u64 = ((USER_STACK_BASE + USER_STACK_PAGES as u64 * 4096) & !0xF) - 8;
+
+// The brk heap grows up from here, well clear of the mmap bump region
+pub const USER_HEAP_BASE: u64 = 0x2000_0000_0000;
+pub const USER_HEAP_LIMIT: u64 = USER_HEAP_BASE + (1 << 30);
+
+/// An anonymous mapping reserved by mmap/alloc_pages but not yet backed.
+#[derive(Debug, Clone, Copy)]
+pub struct Vma {
+    pub start: u64,
+    pub end: u64,
+}
+
+/// Lazily backed memory of one address space, shared by all of its threads. Pages
+/// inside get a zeroed frame on first touch (see demand_map).
+#[derive(Clone)]
+pub struct UserRegions {
+    /// Program break: the heap is [USER_HEAP_BASE, brk)
+    pub brk: u64,
+    pub vmas: Vec<Vma>,
+}
+
+impl UserRegions {
+    pub fn new() -> Self { Self { brk: USER_HEAP_BASE, vmas: Vec::new() } }
+
+    pub fn contains(&self, addr: u64) -> bool {
+        (addr >= USER_HEAP_BASE && addr < self.brk) || self.vmas.iter().any(|v| addr >= v.start && addr < v.end)
+    }
+}
+
+/// Backs the page holding `addr` if it lies in a lazy region of the task running on
+/// this core. False means the address isn't ours to fill and the access is a real fault.
+pub fn demand_map(addr: u64) -> bool {
+    if x86_64::registers::model_specific::GsBase::read().as_u64() == 0 { return false; }
+    let percpu = crate::percpu::current();
+    let curr_idx = percpu.scheduler.core_task_idx[percpu.logical_id as usize % 32];
+    let task = match percpu.scheduler.tasks.get(curr_idx) { Some(t) => t, None => return false };
+    if task.is_idle || !task.regions.lock().contains(addr) { return false; }
+    crate::memory::map_lazy_user_page(addr).is_ok()
+}
+
 pub struct Process {
     pub pid: u64,
     pub parent_pid: Option<u64>,
@@ -123,13 +114,30 @@ pub struct Process {
     // --- NEW: WAKE TIMER FOR SYS_SLEEP ---
     pub wake_tsc: u64, 
     pub mailbox: VecDeque<IpcMessage>,
+    // Set by SYS_EXIT (or the fault handler) when the task becomes a zombie
+    pub exit_code: i64,
+    pub regions: Arc<Mutex<UserRegions>>,
+    // Lottery weight; see scheduler::DEFAULT_TICKETS
+    pub tickets: u64,
+    // UPTIME_MS when the task last got the CPU
+    pub last_run_ms: u64,
+    // Set by SYS_KILL; the task exits the next time it enters the kernel
+    pub kill_requested: bool,
+    // None for kernel tasks
+    pub user: Option<UserTask>,
+}
+
+/// Fresh fd table with 0, 1 and 2 bound to a new console.
+fn console_fd_table() -> [Option<FileDescriptor>; 32] {
+    let console = alloc::sync::Arc::new(crate::vfs::Console::new());
+    core::array::from_fn(|i| if i < 3 { Some(FileDescriptor::Console(console.clone())) } else { None })
 }
 
 impl Process {
     pub fn new() -> Result<Self, &'static str> {
         let pid = NEXT_PID.fetch_add(1, Ordering::Relaxed);
         
-        let kernel_stack = crate::memory::allocate_kernel_stack(4);
+        let kernel_stack = crate::memory::allocate_kernel_stack(KERNEL_STACK_PAGES);
         let pml4_frame = crate::memory::allocate_frame().ok_or("OOM: CR3 allocation failed")?;
         crate::memory::clone_kernel_page_table(pml4_frame.start_address());
 
@@ -140,19 +148,25 @@ impl Process {
             saved_rsp: kernel_stack, 
             kernel_stack_top: kernel_stack,
             mmap_bump: 0x4000_0000_0000, 
-            fd_table: core::array::from_fn(|_| None),
+            fd_table: console_fd_table(),
             state: TaskState::Ready,
             cpu_ticks: 0,
             name: [0; 16],
             is_idle: false, 
             wake_tsc: 0,
             mailbox: VecDeque::new(), // Default to empty mailbox
+            exit_code: 0,
+            regions: Arc::new(Mutex::new(UserRegions::new())),
+            tickets: crate::scheduler::DEFAULT_TICKETS,
+            last_run_ms: 0,
+            kill_requested: false,
+            user: None,
         })
     }
     
     pub fn new_thread(parent_cr3: PhysAddr) -> Result<Self, &'static str> {
         let pid = NEXT_PID.fetch_add(1, Ordering::Relaxed);
-        let kernel_stack = crate::memory::allocate_kernel_stack(4);
+        let kernel_stack = crate::memory::allocate_kernel_stack(KERNEL_STACK_PAGES);
 
         Ok(Process {
             pid,
@@ -168,8 +182,93 @@ impl Process {
             is_idle: false,
             wake_tsc: 0,
             mailbox: VecDeque::new(), // Default to empty mailbox
+            exit_code: 0,
+            regions: Arc::new(Mutex::new(UserRegions::new())),
+            tickets: crate::scheduler::DEFAULT_TICKETS,
+            last_run_ms: 0,
+            kill_requested: false,
+            user: None,
         })
     }
+
+    /// Starts a new program in its own address space: fresh user half, the ELF
+    /// `image` loaded into it and a user stack below USER_STACK_TOP. The caller
+    /// fills in the fd table and queues the task.
+    pub fn spawn(image: &[u8], name: &str) -> Result<Self, &'static str> {
+        Self::spawn_loaded(name, || crate::elf::load(image))
+    }
+
+    /// Like spawn, for a flat binary: raw code at FLAT_LOAD_ADDR, entered at
+    /// its first byte. Only the kernel's own test programs come this way.
+    pub fn spawn_flat(code: &[u8], name: &str) -> Result<Self, &'static str> {
+        Self::spawn_loaded(name, || crate::elf::load_flat(code))
+    }
+
+    fn spawn_loaded(name: &str, load: impl FnOnce() -> Result<u64, &'static str>) -> Result<Self, &'static str> {
+        let mut process = Process::new()?;
+        crate::memory::clone_kernel_mappings(x86_64::registers::control::Cr3::read().0.start_address(), process.cr3);
+
+        // The loader maps through the active tables, so borrow the child's for the duration
+        let (parent_frame, parent_flags) = x86_64::registers::control::Cr3::read();
+        let child_frame = x86_64::structures::paging::PhysFrame::containing_address(process.cr3);
+        unsafe { x86_64::registers::control::Cr3::write(child_frame, parent_flags); }
+        let loaded = load().and_then(|entry| {
+            crate::memory::allocate_user_pages_at(USER_STACK_BASE, USER_STACK_PAGES).map(|_| entry)
+        });
+        unsafe { x86_64::registers::control::Cr3::write(parent_frame, parent_flags); }
+        let entry = loaded?;
+
+        let len = name.len().min(16);
+        process.name[..len].copy_from_slice(&name.as_bytes()[..len]);
+        process.prime_user_entry(entry, USER_STACK_TOP);
+        Ok(process)
+    }
+
+    /// A ring 0 task running `entry` on the kernel's own page tables. When `entry`
+    /// returns the task exits through scheduler::task_exit and is reaped later.
+    pub fn new_kernel_task(name: &str, entry: fn()) -> Result<Self, &'static str> {
+        let mut task = Process::new_thread(PhysAddr::new(unsafe { crate::memory::BOOTLOADER_CR3 }))?;
+        let len = name.len().min(16);
+        task.name[..len].copy_from_slice(&name.as_bytes()[..len]);
+
+        // Enter kernel_task_entry as if called: rsp + 8 is 16-byte aligned, entry in rdi
+        let rsp = task.kernel_stack_top - 8;
+        task.prime_frame([kernel_task_entry as u64, 0x08, 0x202, rsp, 0x10], entry as u64);
+        Ok(task)
+    }
+
+    /// Lays out the kernel stack the way a context switch expects to find it, so
+    /// the task's first run irets to ring 3 at `rip` with zeroed registers.
+    pub fn prime_user_entry(&mut self, rip: u64, rsp: u64) {
+        self.user = Some(UserTask { entry: rip, stack_top: rsp });
+        self.prime_frame([rip, 0x33, 0x202, rsp, 0x2B], 0);
+    }
+
+    /// Writes the iret frame, saved registers (all zero but rdi) and FPU state that
+    /// a context switch restores on the task's first run.
+    fn prime_frame(&mut self, iret_frame: [u64; 5], rdi: u64) {
+        let iretq_ptr = self.kernel_stack_top - 40;
+        let regs_ptr = iretq_ptr - 120;
+        let fxsave_ptr = (regs_ptr - 512) & !0xF;
+        let final_rsp = fxsave_ptr - 16;
+        unsafe {
+            let iret = core::slice::from_raw_parts_mut(iretq_ptr as *mut u64, 5);
+            iret.copy_from_slice(&iret_frame);
+            core::ptr::write_bytes(regs_ptr as *mut u8, 0, 120);
+            *(regs_ptr as *mut u64).add(8) = rdi; // popped r15 first, so rdi is the ninth slot
+            core::ptr::write_bytes(fxsave_ptr as *mut u8, 0, 512);
+            *(fxsave_ptr as *mut u32).add(6) = 0x1F80; // MXCSR: all SSE exceptions masked
+            let bottom = core::slice::from_raw_parts_mut(final_rsp as *mut u64, 2);
+            bottom[0] = regs_ptr;
+            bottom[1] = 0;
+        }
+        self.saved_rsp = final_rsp;
+    }
+}
+
+extern "C" fn kernel_task_entry(entry: fn()) -> ! {
+    entry();
+    crate::scheduler::task_exit(0)
 }
 
 // ==========================================
@@ -177,6 +276,8 @@ impl Process {
 // ==========================================
 pub extern "C" fn nyx_idle_task() {
     loop {
+        // Finished tasks are torn down here, never from the interrupt that switched away from them
+        crate::scheduler::reap_finished();
         // This allows smoltcp to send the DHCP Discover and handle incoming ARP/TCP packets.
         crate::drivers::net::poll_network();
         // Ensure interrupts are ALWAYS enabled before halting, 
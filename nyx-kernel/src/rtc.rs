@@ -0,0 +1,146 @@
+use core::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use nyx_core::date::{DateTime, RtcRegisters};
+use spin::Mutex;
+use x86_64::instructions::port::Port;
+use crate::time::UPTIME_MS;
+
+// ==========================================
+// CMOS REAL-TIME CLOCK
+// ==========================================
+// Wall-clock time, as Unix seconds, for file timestamps. Reading the RTC is
+// slow (port I/O, and waiting out its once-a-second update), so a reading is
+// cached with the uptime it was taken at and advanced by the scheduler tick
+// from then on. It is read again every RESYNC_MS so tick drift never builds
+// up. A clock that reads as nonsense (flat battery, no RTC at all) gives 0,
+// which file timestamps treat as "unknown".
+//
+// set_time (what `netclock` calls after asking an SNTP server) doesn't touch
+// the chip: it keeps the difference from what the chip says and adds that
+// on from then on, until the next boot.
+
+const CMOS_ADDR: u16 = 0x70;
+const CMOS_DATA: u16 = 0x71;
+
+const REG_SECONDS: u8 = 0x00;
+const REG_MINUTES: u8 = 0x02;
+const REG_HOURS: u8 = 0x04;
+const REG_DAY: u8 = 0x07;
+const REG_MONTH: u8 = 0x08;
+const REG_YEAR: u8 = 0x09;
+const REG_STATUS_A: u8 = 0x0A;
+const REG_STATUS_B: u8 = 0x0B;
+/// Status register A: the chip is about to change the time registers
+const UPDATE_IN_PROGRESS: u8 = 0x80;
+
+const RESYNC_MS: u64 = 10 * 60 * 1000;
+// The update flag stays up for under 2 ms; this is several times that
+const UIP_SPINS: u32 = 20_000;
+const READ_ATTEMPTS: usize = 8;
+
+struct Reading {
+    unix: u64,
+    at_ms: u64,
+}
+
+static CACHE: Mutex<Option<Reading>> = Mutex::new(None);
+/// Seconds set_time put the clock ahead (or behind) the chip
+static ADJUST_SECS: AtomicI64 = AtomicI64::new(0);
+/// Set once set_time has been called, so an adjusted clock is never "unknown"
+static ADJUSTED: AtomicBool = AtomicBool::new(false);
+
+fn read_register(reg: u8) -> u8 {
+    let mut addr: Port<u8> = Port::new(CMOS_ADDR);
+    let mut data: Port<u8> = Port::new(CMOS_DATA);
+    // Bit 7 of the address port disables NMIs; leave it clear
+    unsafe {
+        addr.write(reg & 0x7F);
+        data.read()
+    }
+}
+
+fn update_in_progress() -> bool { read_register(REG_STATUS_A) & UPDATE_IN_PROGRESS != 0 }
+
+fn read_registers() -> RtcRegisters {
+    RtcRegisters {
+        second: read_register(REG_SECONDS),
+        minute: read_register(REG_MINUTES),
+        hour: read_register(REG_HOURS),
+        day: read_register(REG_DAY),
+        month: read_register(REG_MONTH),
+        year: read_register(REG_YEAR),
+    }
+}
+
+/// Reads the clock as Unix seconds, or None if it can't be trusted.
+fn read_rtc() -> Option<u64> {
+    // An update can still land between two register reads (the seconds
+    // rolling over into a new minute, or midnight into a new day or year)
+    // and mix old and new values, so read until two readings agree
+    let mut last = None;
+    for _ in 0..READ_ATTEMPTS {
+        let mut spins = 0;
+        while update_in_progress() && spins < UIP_SPINS {
+            core::hint::spin_loop();
+            spins += 1;
+        }
+        let regs = read_registers();
+        if last == Some(regs) {
+            return regs.decode(read_register(REG_STATUS_B)).to_unix();
+        }
+        last = Some(regs);
+    }
+    None
+}
+
+/// Current wall-clock time in Unix seconds (UTC), or 0 if the machine has
+/// no usable clock and it was never set.
+pub fn unix_time() -> u64 {
+    let adjust = ADJUST_SECS.load(Ordering::Relaxed);
+    match chip_time() {
+        0 if !ADJUSTED.load(Ordering::Relaxed) => 0,
+        // Without a chip the adjustment is counted from boot
+        0 => (UPTIME_MS.load(Ordering::Relaxed) as i64 / 1000 + adjust).max(0) as u64,
+        t => (t as i64 + adjust).max(0) as u64,
+    }
+}
+
+/// Makes unix_time read `unix` from now on.
+pub fn set_time(unix: u64) {
+    let base = match chip_time() {
+        0 => UPTIME_MS.load(Ordering::Relaxed) / 1000,
+        t => t,
+    };
+    ADJUST_SECS.store(unix as i64 - base as i64, Ordering::Relaxed);
+    ADJUSTED.store(true, Ordering::Relaxed);
+    crate::serial_println!("[RTC] Wall clock set to {} UTC ({:+} s from the chip)", DateTime::from_unix(unix), unix as i64 - base as i64);
+}
+
+/// What the chip says, from the cache; 0 if it can't be read.
+fn chip_time() -> u64 {
+    let now_ms = UPTIME_MS.load(Ordering::Relaxed);
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let mut cache = CACHE.lock();
+        let stale = match &*cache {
+            Some(r) => now_ms.saturating_sub(r.at_ms) >= RESYNC_MS,
+            None => true,
+        };
+        if stale {
+            match read_rtc() {
+                Some(unix) => *cache = Some(Reading { unix, at_ms: now_ms }),
+                // Keep counting from the last good reading, if there was one
+                None if cache.is_none() => return 0,
+                None => {}
+            }
+        }
+        let r = cache.as_ref().unwrap();
+        r.unix + now_ms.saturating_sub(r.at_ms) / 1000
+    })
+}
+
+/// Logs the boot-time reading and primes the cache.
+pub fn init() {
+    match unix_time() {
+        0 => crate::serial_println!("[BOOT] WARN: CMOS clock unreadable; file times will be unknown."),
+        t => crate::serial_println!("[BOOT] Wall clock: {} UTC", DateTime::from_unix(t)),
+    }
+}
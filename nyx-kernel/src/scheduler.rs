@@ -6,6 +6,221 @@ use crate::process::Process;
 
 // Keep track of context switches for sysinfo (Syscall 523)
 pub static CONTEXT_SWITCHES: AtomicU64 = AtomicU64::new(0);
+// Tasks that have exited or been killed since boot (Syscall 546)
+pub static TASKS_EXITED: AtomicU64 = AtomicU64::new(0);
+// User tasks (init, forks, threads) that haven't exited yet, across all cores
+pub static LIVE_USER_TASKS: AtomicU64 = AtomicU64::new(0);
+// Timer ticks across all cores, and how many of them landed on an idle task
+static TOTAL_TICKS: AtomicU64 = AtomicU64::new(0);
+static IDLE_TICKS: AtomicU64 = AtomicU64::new(0);
+// Busy share of the last full second in per mille (Syscall 549)
+pub static CPU_LOAD_PERMILLE: AtomicU64 = AtomicU64::new(0);
+
+/// Called on every timer tick with whether the interrupted task was the idle task.
+/// The BSP closes a one second window every TICK_HZ of its own ticks.
+pub fn account_tick(idle: bool, is_bsp: bool) {
+    static BSP_TICKS: AtomicU64 = AtomicU64::new(0);
+    static WINDOW_START: Mutex<(u64, u64)> = Mutex::new((0, 0));
+
+    TOTAL_TICKS.fetch_add(1, Ordering::Relaxed);
+    if idle { IDLE_TICKS.fetch_add(1, Ordering::Relaxed); }
+
+    let hz = crate::time::TICK_HZ;
+    if is_bsp && BSP_TICKS.fetch_add(1, Ordering::Relaxed) % hz == hz - 1 {
+        let total = TOTAL_TICKS.load(Ordering::Relaxed);
+        let idle = IDLE_TICKS.load(Ordering::Relaxed);
+        let mut start = WINDOW_START.lock();
+        let (dt, di) = (total - start.0, idle - start.1);
+        if dt > 0 { CPU_LOAD_PERMILLE.store(1000 - (di * 1000 / dt).min(1000), Ordering::Relaxed); }
+        *start = (total, idle);
+    }
+}
+
+/// Bookkeeping for a user task that just became a zombie. When the last one goes,
+/// nothing owns the screen anymore, so the kernel puts up a status page and a
+/// kernel shell to look around with.
+pub fn note_user_exit() {
+    TASKS_EXITED.fetch_add(1, Ordering::Relaxed);
+    if LIVE_USER_TASKS.fetch_sub(1, Ordering::SeqCst) == 1 {
+        crate::vga_log::show_status_screen("All user tasks have exited.");
+        crate::window::open_kernel_shell("All user tasks have exited.");
+    }
+}
+
+// ==========================================
+// TASK LIFETIME
+// ==========================================
+// A task that finishes becomes a Zombie: the scheduler never picks it again,
+// but its stack may still be the one the exit path is running on. Each core's
+// idle task later reaps its zombies outside interrupt context, freeing the
+// kernel stack and (when no thread shares it) the PML4, and records the exit
+// code so exit_status keeps answering after the task is gone.
+
+// Exit codes of reaped tasks, newest last
+const REAPED_LOG_LEN: usize = 64;
+static REAPED: Mutex<alloc::collections::VecDeque<(u64, i64)>> = Mutex::new(alloc::collections::VecDeque::new());
+
+/// Starts `entry` as a kernel task on this core and returns its pid.
+pub fn spawn_kernel_task(name: &str, entry: fn()) -> Result<u64, &'static str> {
+    let task = Process::new_kernel_task(name, entry)?;
+    let pid = task.pid;
+    crate::percpu::current().scheduler.add(task);
+    Ok(pid)
+}
+
+/// Ends the calling kernel task. Never returns.
+pub fn task_exit(code: i64) -> ! {
+    x86_64::instructions::interrupts::disable();
+    let percpu = crate::percpu::current();
+    let idx = percpu.scheduler.core_task_idx[percpu.logical_id as usize % 32];
+    let task = &mut percpu.scheduler.tasks[idx];
+    crate::serial_println!("[PID {}] Kernel task finished (Code: {})", task.pid, code);
+    task.exit_code = code;
+    task.state = TaskState::Zombie;
+    TASKS_EXITED.fetch_add(1, Ordering::Relaxed);
+
+    unsafe {
+        x86_64::instructions::interrupts::enable();
+        loop { core::arch::asm!("int 0x41"); }
+    }
+}
+
+/// Ends the calling user task: closes its descriptors, shreds its user
+/// address space and leaves it a zombie. Used by SYS_EXIT and by the fault
+/// handlers for ring-3 faults. Never returns.
+pub fn exit_current_user_task(code: i64) -> ! {
+    x86_64::instructions::interrupts::disable();
+    let percpu = crate::percpu::current();
+    let idx = percpu.scheduler.core_task_idx[percpu.logical_id as usize % 32];
+    let task = &mut percpu.scheduler.tasks[idx];
+    crate::serial_println!("[PID {}] Exited (Code: {})", task.pid, code);
+
+    // The last reference to a socket takes it out of the network stack
+    for i in 0..32 {
+        if let Some(FileDescriptor::Socket(sock_mtx)) = &task.fd_table[i] {
+            if Arc::strong_count(sock_mtx) == 1 {
+                let sock = sock_mtx.lock();
+                if let Some(sockets) = crate::drivers::net::GLOBAL_SOCKETS.lock().as_mut() {
+                    match sock.kind {
+                        SocketKind::Tcp(handle) => {
+                            let socket = sockets.get_mut::<smoltcp::socket::tcp::Socket>(handle);
+                            socket.abort(); // Send TCP RST
+                            sockets.remove(handle);
+                        },
+                        SocketKind::Udp(handle) => { sockets.remove(handle); }
+                    }
+                }
+            }
+        }
+        task.fd_table[i] = None;
+    }
+
+    // Only the user half goes. DO NOT swap CR3 to KERNEL_CR3, or the CPU will
+    // triple fault on the next stack access.
+    crate::memory::clear_user_address_space(task.cr3);
+
+    // Zombie at the very end, once all locks are released
+    task.exit_code = code;
+    task.state = TaskState::Zombie;
+    note_user_exit();
+
+    // The scheduler never picks a zombie, so this never returns
+    unsafe {
+        x86_64::instructions::interrupts::enable();
+        loop { core::arch::asm!("int 0x41"); }
+    }
+}
+
+/// Exit code of a user task killed by a fault, like SIGSEGV
+pub const FAULT_EXIT_CODE: i64 = -11;
+
+/// Ends the calling user task after a ring-3 fault, logging `what` with the
+/// task's UserTask so the report says whose code faulted. Never returns.
+pub fn kill_current_user_task(what: core::fmt::Arguments) -> ! {
+    let percpu = crate::percpu::current();
+    let idx = percpu.scheduler.core_task_idx[percpu.logical_id as usize % 32];
+    if let Some(task) = percpu.scheduler.tasks.get(idx) {
+        match task.user {
+            Some(user) => crate::serial_println!("\n[SEGFAULT] PID {} killed: {} (entered at {:#x}, stack {:#x})",
+                task.pid, what, user.entry, user.stack_top),
+            None => crate::serial_println!("\n[SEGFAULT] PID {} killed: {}", task.pid, what),
+        }
+    }
+    exit_current_user_task(FAULT_EXIT_CODE)
+}
+
+/// Exit code of `pid` once it has finished, None while it runs (or if no such task
+/// was reaped recently enough to be remembered).
+pub fn exit_status(pid: u64) -> Option<i64> {
+    let cores = unsafe { crate::percpu::PER_CPU.as_mut() }?;
+    for core in cores.iter_mut() {
+        let found = core.scheduler.with_tasks(|tasks| {
+            tasks.iter().find(|t| t.pid == pid).map(|t| if t.state == TaskState::Zombie { Some(t.exit_code) } else { None })
+        });
+        if let Some(status) = found { return status; }
+    }
+    REAPED.lock().iter().find(|(p, _)| *p == pid).map(|&(_, code)| code)
+}
+
+/// Sleeps until `pid` finishes and returns its exit code.
+pub fn join(pid: u64) -> i64 {
+    loop {
+        if let Some(code) = exit_status(pid) { return code; }
+        crate::time::sleep_ms(1);
+    }
+}
+
+/// Wakes `pid` if it is blocked, wherever it runs. Safe to call from interrupt handlers.
+pub fn wake_task(pid: u64) {
+    let cores = match unsafe { crate::percpu::PER_CPU.as_mut() } { Some(c) => c, None => return };
+    for core in cores.iter_mut() {
+        let found = core.scheduler.with_tasks(|tasks| match tasks.iter_mut().find(|t| t.pid == pid) {
+            Some(task) => {
+                if task.state == TaskState::Blocked {
+                    task.state = TaskState::Ready;
+                    task.wake_tsc = 0;
+                }
+                true
+            }
+            None => false,
+        });
+        if found { return; }
+    }
+}
+
+/// Blocks the calling task for up to `ms` or until wake_task. Call with interrupts
+/// off, right after seeing there is nothing to do, so a wakeup can't slip in
+/// between the check and the block. Returns with interrupts on.
+pub fn block_current(ms: u64) {
+    let percpu = crate::percpu::current();
+    let idx = percpu.scheduler.core_task_idx[percpu.logical_id as usize % 32];
+    let task = &mut percpu.scheduler.tasks[idx];
+    task.state = TaskState::Blocked;
+    task.wake_tsc = crate::time::UPTIME_MS.load(Ordering::Relaxed) + ms;
+    unsafe { core::arch::asm!("int 0x41"); }
+    x86_64::instructions::interrupts::enable();
+}
+
+/// Reaps this core's zombies. Called from the idle task, never from an interrupt.
+pub fn reap_finished() {
+    let percpu = crate::percpu::current();
+    if !percpu.scheduler.tasks.iter().any(|t| t.state == TaskState::Zombie) { return; }
+
+    let dead = x86_64::instructions::interrupts::without_interrupts(|| percpu.scheduler.reap());
+    for task in dead {
+        crate::memory::free_kernel_stack(task.kernel_stack_top, crate::process::KERNEL_STACK_PAGES);
+        // Threads share their parent's address space (and regions); the last one out frees the PML4.
+        // Lower-level tables of a forked space stay with it; only user pages were freed at exit.
+        let kernel_tables = task.cr3.as_u64() == unsafe { crate::memory::BOOTLOADER_CR3 };
+        if !kernel_tables && Arc::strong_count(&task.regions) == 1 {
+            crate::memory::free_frame(x86_64::structures::paging::PhysFrame::containing_address(task.cr3));
+        }
+
+        let mut log = REAPED.lock();
+        if log.len() == REAPED_LOG_LEN { log.pop_front(); }
+        log.push_back((task.pid, task.exit_code));
+    }
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TaskState {
@@ -35,6 +250,8 @@ pub enum FileDescriptor {
     Socket(alloc::sync::Arc<spin::Mutex<KernelSocket>>),
     PipeRead(alloc::sync::Arc<spin::Mutex<alloc::collections::VecDeque<u8>>>),
     PipeWrite(alloc::sync::Arc<spin::Mutex<alloc::collections::VecDeque<u8>>>),
+    Dir(alloc::sync::Arc<crate::vfs::DirStream>),
+    Console(alloc::sync::Arc<crate::vfs::Console>),
 }
 
 pub fn generate_pid() -> u64 {
@@ -42,17 +259,187 @@ pub fn generate_pid() -> u64 {
     NEXT_PID.fetch_add(1, Ordering::Relaxed)
 }
 
+// ==========================================
+// LOTTERY TICKETS
+// ==========================================
+// Every tick draws a winner among the runnable tasks, weighted by tickets, so
+// a task's share of its core follows its share of the tickets. The task that
+// owns the focused window (reported by the compositor, Syscall 552) holds
+// FOREGROUND_TICKETS until focus moves on, which keeps the UI responsive while
+// background work like the kworker keeps its own core busy. Only the screen
+// owner may move the boost. Syscall 561 refuses a task raising its own
+// tickets, but any other user task may raise them for it, so tickets are a
+// scheduling hint for the Task Manager, not a limit an app can't get around.
+
+pub const DEFAULT_TICKETS: u64 = 100;
+pub const FOREGROUND_TICKETS: u64 = 400;
+pub const MAX_TICKETS: u64 = 10_000;
+
+static FOREGROUND_PID: AtomicU64 = AtomicU64::new(0);
+
+/// Sets the tickets of `pid`, wherever it runs. False if there is no such task.
+pub fn set_tickets(pid: u64, tickets: u64) -> bool {
+    let cores = match unsafe { crate::percpu::PER_CPU.as_mut() } { Some(c) => c, None => return false };
+    cores.iter_mut().any(|core| core.scheduler.set_tickets(pid, tickets))
+}
+
+/// Pid of the task running on this core.
+pub fn current_pid() -> u64 {
+    let percpu = crate::percpu::current();
+    let idx = percpu.scheduler.core_task_idx[percpu.logical_id as usize % 32];
+    percpu.scheduler.tasks.get(idx).map_or(0, |t| t.pid)
+}
+
+/// Moves the foreground boost to `pid` (0 for none) and returns the previous
+/// holder to DEFAULT_TICKETS.
+pub fn set_foreground(pid: u64) {
+    let previous = FOREGROUND_PID.swap(pid, Ordering::SeqCst);
+    if previous == pid { return; }
+    if previous != 0 { set_tickets(previous, DEFAULT_TICKETS); }
+    if pid != 0 { set_tickets(pid, FOREGROUND_TICKETS); }
+}
+
+// ==========================================
+// TASK CONTROL (Syscalls 561, 562)
+// ==========================================
+// Apps may reweight and kill user tasks, but not the ones the system stands
+// on: the kernel's own tasks and init (the tasks nobody forked) and the
+// compositor (whoever holds the framebuffer). A kill only marks the task;
+// only a task can tear down its own address space, so it exits the next
+// time it enters the kernel (a syscall, or a timer tick if it's spinning in
+// its own code), woken first if it's blocked. The task may live on another
+// core, so its core's task list is locked while it's found and marked.
+
+pub const KILLED_EXIT_CODE: i64 = -9;
+
+fn is_protected(task: &Process) -> bool {
+    task.parent_pid.is_none() || task.pid == crate::gui::screen_owner()
+}
+
+/// True when the calling task is init or the compositor, the tasks that own
+/// machine-wide settings.
+pub fn current_is_protected() -> bool {
+    let percpu = crate::percpu::current();
+    let idx = percpu.scheduler.core_task_idx[percpu.logical_id as usize % 32];
+    percpu.scheduler.tasks.get(idx).is_some_and(is_protected)
+}
+
+/// Runs `f` on the live user task `pid`, with its core's task list locked.
+/// Returns 0, EPERM or ESRCH.
+fn control_task(pid: u64, f: impl FnOnce(&mut Process)) -> i64 {
+    let cores = match unsafe { crate::percpu::PER_CPU.as_mut() } { Some(c) => c, None => return crate::errno::ESRCH };
+    let mut f = Some(f);
+    for core in cores.iter_mut() {
+        let result = core.scheduler.with_tasks(|tasks| {
+            let task = tasks.iter_mut().find(|t| t.pid == pid && t.state != TaskState::Zombie)?;
+            if is_protected(task) { return Some(crate::errno::EPERM); }
+            if let Some(f) = f.take() { f(task); }
+            Some(0)
+        });
+        if let Some(result) = result { return result; }
+    }
+    crate::errno::ESRCH
+}
+
+/// Sets the tickets of user task `pid`, clamped like Scheduler::set_tickets.
+/// A task may lower its own tickets but not raise them (EPERM); another
+/// task may still raise them for it.
+pub fn set_user_tickets(pid: u64, tickets: u64) -> i64 {
+    let caller = current_pid();
+    let tickets = tickets.clamp(1, MAX_TICKETS);
+    let mut raised_self = false;
+    let result = control_task(pid, |task| {
+        if task.pid == caller && tickets > task.tickets { raised_self = true; } else { task.tickets = tickets; }
+    });
+    if raised_self { crate::errno::EPERM } else { result }
+}
+
+pub fn kill(pid: u64) -> i64 {
+    let result = control_task(pid, |task| task.kill_requested = true);
+    if result == 0 { wake_task(pid); }
+    result
+}
+
+/// Ends the calling task with KILLED_EXIT_CODE if it has been killed.
+/// Only called on user tasks' way through the kernel.
+pub fn exit_if_killed() {
+    let percpu = crate::percpu::current();
+    let idx = percpu.scheduler.core_task_idx[percpu.logical_id as usize % 32];
+    if percpu.scheduler.tasks.get(idx).map_or(false, |t| t.kill_requested) {
+        exit_current_user_task(KILLED_EXIT_CODE);
+    }
+}
+
 pub struct Scheduler {
     pub tasks: Vec<Process>,
+    // Held, with interrupts off, while tasks are added or removed and while
+    // another core walks `tasks`; this core's own reads go without it
+    tasks_lock: Mutex<()>,
     pub core_task_idx: [usize; 32],
+    // xorshift state for the ticket draw
+    rng: u64,
 }
 
 impl Scheduler {
     pub fn new() -> Self {
         Self {
             tasks: Vec::new(),
+            tasks_lock: Mutex::new(()),
             core_task_idx: [0; 32],
+            rng: unsafe { core::arch::x86_64::_rdtsc() } | 1,
+        }
+    }
+
+    /// Queues `task` on this scheduler, from its own core or any other.
+    pub fn add(&mut self, task: Process) {
+        x86_64::instructions::interrupts::without_interrupts(|| {
+            let _guard = self.tasks_lock.lock();
+            self.tasks.push(task);
+        });
+    }
+
+    /// Runs `f` on this scheduler's tasks with them locked against additions
+    /// and removals, for code that may be running on another core.
+    pub fn with_tasks<R>(&mut self, f: impl FnOnce(&mut [Process]) -> R) -> R {
+        x86_64::instructions::interrupts::without_interrupts(|| {
+            let _guard = self.tasks_lock.lock();
+            f(&mut self.tasks)
+        })
+    }
+
+    /// Sets the tickets of `pid` if it lives on this core. Clamped to 1..=MAX_TICKETS
+    /// so nobody can starve (or become) everyone else.
+    pub fn set_tickets(&mut self, pid: u64, tickets: u64) -> bool {
+        self.with_tasks(|tasks| match tasks.iter_mut().find(|t| t.pid == pid) {
+            Some(task) => { task.tickets = tickets.clamp(1, MAX_TICKETS); true }
+            None => false,
+        })
+    }
+
+    fn draw(&mut self, below: u64) -> u64 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        self.rng % below
+    }
+
+    /// Removes every zombie except the task running right now and returns them.
+    /// Must run with interrupts off: indices shift under core_task_idx.
+    pub fn reap(&mut self) -> Vec<Process> {
+        let logical_id = crate::percpu::current().logical_id as usize % 32;
+        let mut current = self.core_task_idx[logical_id];
+        let mut dead = Vec::new();
+        let _guard = self.tasks_lock.lock();
+
+        let mut i = self.tasks.len();
+        while i > 0 {
+            i -= 1;
+            if i == current || self.tasks[i].state != TaskState::Zombie { continue; }
+            dead.push(self.tasks.remove(i));
+            if i < current { current -= 1; }
         }
+        self.core_task_idx[logical_id] = current;
+        dead
     }
 
     /// Takes the stack pointer of the currently preempted process,
@@ -93,52 +480,38 @@ impl Scheduler {
             }
         }
 
-        // --- 3. SMART PRIORITY ROUND-ROBIN ---
-        let mut next_idx = (curr_idx + 1) % self.tasks.len();
-        let mut fallback_idle_idx = None;
-        let mut found = false;
-
-        for _ in 0..self.tasks.len() {
-            let state = self.tasks[next_idx].state;
-            
-            if state == TaskState::Ready || state == TaskState::Running {
-                // If it's the Idle Task, remember it, but keep looking for real work!
-                if self.tasks[next_idx].is_idle {
-                    fallback_idle_idx = Some(next_idx);
-                } else {
-                    // We found a REAL task! Stop searching.
-                    found = true;
-                    break; 
-                }
-            }
-            next_idx = (next_idx + 1) % self.tasks.len();
-        }
+        // --- 3. LOTTERY DRAW ---
+        let runnable = |t: &Process| !t.is_idle && (t.state == TaskState::Ready || t.state == TaskState::Running);
+        let pool: u64 = self.tasks.iter().filter(|t| runnable(t)).map(|t| t.tickets).sum();
 
-        if !found {
+        let next_idx = if pool > 0 {
+            let mut ticket = self.draw(pool);
+            let winner = self.tasks.iter().position(|t| {
+                if !runnable(t) { return false; }
+                if ticket < t.tickets { return true; }
+                ticket -= t.tickets;
+                false
+            });
+            winner.unwrap_or(curr_idx)
+        } else {
             // No normal user/kernel tasks are ready to run. Let the CPU sleep!
-            if let Some(idle_idx) = fallback_idle_idx {
-                next_idx = idle_idx;
-            } else {
-                return current_rsp; // Absolute worst-case fallback
+            match self.tasks.iter().position(|t| t.is_idle && (t.state == TaskState::Ready || t.state == TaskState::Running)) {
+                Some(idle_idx) => idle_idx,
+                None => return current_rsp, // Absolute worst-case fallback
             }
-        }
+        };
 
         // --- 4. UPDATE STATE ---
         self.core_task_idx[logical_id] = next_idx;
         let next_process = &mut self.tasks[next_idx];
         next_process.state = TaskState::Running;
+        next_process.last_run_ms = current_ms;
 
         // 🚨 5. THE HARDWARE BRAIN SWAP 🚨
         unsafe {
-            // A. Point the Syscall Gateway to this process's specific Kernel Stack.
-            // When `syscall` is called, the CPU looks at `gs:[0]`.
-            let percpu_base = crate::percpu::current() as *const _ as *mut u64;
-            *percpu_base = next_process.kernel_stack_top; 
-            
-            // B. Point the Hardware Interrupt Gateway to this process's Kernel Stack!
-            // When a hardware timer interrupts userspace, the CPU reads the TSS to find a secure Ring 0 stack.
-            let tss_ptr = crate::percpu::current().gdt_state.tss as *const _ as *mut x86_64::structures::tss::TaskStateSegment;
-            (*tss_ptr).privilege_stack_table[0] = x86_64::VirtAddr::new(next_process.kernel_stack_top);
+            // A/B. Point the Syscall Gateway (gs:[0]) and the Hardware Interrupt
+            // Gateway (TSS RSP0) at this process's own Kernel Stack.
+            crate::percpu::current().set_kernel_stack(next_process.kernel_stack_top);
 
             // C. Swap the Virtual Memory Space!
             let next_cr3 = next_process.cr3.as_u64();
@@ -48,6 +48,14 @@ impl SerialPort {
         self.wait_for_tx_empty();
         unsafe { self.data.write(b); }
     }
+
+    /// The next received byte, if one is waiting.
+    pub fn try_read_byte(&mut self) -> Option<u8> {
+        unsafe {
+            if self.line_sts.read() & 0x01 == 0 { return None; }
+            Some(self.data.read())
+        }
+    }
 }
 
 impl fmt::Write for SerialPort {
@@ -68,22 +76,60 @@ lazy_static! {
     };
 }
 
-// --- NEW: KERNEL BOOT LOG BUFFER ---
-pub const BOOT_LOG_SIZE: usize = 16384; // 16 KB of text
-pub static mut BOOT_LOG: [u8; BOOT_LOG_SIZE] = [0; BOOT_LOG_SIZE];
-pub static mut BOOT_LOG_IDX: usize = 0;
+// ==========================================
+// KERNEL LOG RING (Syscall 518)
+// ==========================================
+// Everything printed to serial is also kept here, from the very first
+// serial_println in kernel_main on. Once BOOT_LOG_SIZE bytes have been
+// written the oldest text is overwritten; `generation` counts those wraps,
+// so generation * BOOT_LOG_SIZE + idx is the absolute offset of the next
+// byte. Readers poll with the absolute offset they got to and learn from
+// the returned start whether anything they hadn't read yet was overwritten.
+pub const BOOT_LOG_SIZE: usize = 64 * 1024;
 
-struct BufWriter;
-impl core::fmt::Write for BufWriter {
-    fn write_str(&mut self, s: &str) -> core::fmt::Result {
-        unsafe {
-            for &b in s.as_bytes() {
-                if BOOT_LOG_IDX < BOOT_LOG_SIZE {
-                    BOOT_LOG[BOOT_LOG_IDX] = b;
-                    BOOT_LOG_IDX += 1;
-                }
+pub struct BootLog {
+    buf: [u8; BOOT_LOG_SIZE],
+    idx: usize,
+    pub generation: u64,
+}
+
+impl BootLog {
+    /// Absolute offset one past the newest byte
+    pub fn end(&self) -> u64 { self.generation * BOOT_LOG_SIZE as u64 + self.idx as u64 }
+
+    /// Absolute offset of the oldest byte still held
+    pub fn start(&self) -> u64 { self.end().saturating_sub(BOOT_LOG_SIZE as u64) }
+
+    fn push(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.buf[self.idx] = b;
+            self.idx += 1;
+            if self.idx == BOOT_LOG_SIZE {
+                self.idx = 0;
+                self.generation += 1;
             }
         }
+    }
+
+    /// Copies from absolute `offset` on into `out`. An offset that was
+    /// already overwritten reads from the oldest byte instead. Returns the
+    /// offset actually read from and how many bytes were copied.
+    pub fn read_at(&self, offset: u64, out: &mut [u8]) -> (u64, usize) {
+        let from = offset.clamp(self.start(), self.end());
+        let len = ((self.end() - from) as usize).min(out.len());
+        for (i, b) in out[..len].iter_mut().enumerate() {
+            *b = self.buf[((from + i as u64) % BOOT_LOG_SIZE as u64) as usize];
+        }
+        (from, len)
+    }
+}
+
+pub static BOOT_LOG: Mutex<BootLog> = Mutex::new(BootLog { buf: [0; BOOT_LOG_SIZE], idx: 0, generation: 0 });
+
+struct BufWriter<'a>(&'a mut BootLog);
+impl core::fmt::Write for BufWriter<'_> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        self.0.push(s.as_bytes());
         Ok(())
     }
 }
@@ -96,7 +142,8 @@ pub fn _print(args: ::core::fmt::Arguments) {
         SERIAL1.lock().write_fmt(args).expect("Printing to serial failed");
         
         // Save a copy in RAM for the userspace UI
-        let mut bw = BufWriter;
+        let mut log = BOOT_LOG.lock();
+        let mut bw = BufWriter(&mut log);
         let _ = core::fmt::Write::write_fmt(&mut bw, args);
     });
 }
@@ -0,0 +1,66 @@
+use alloc::string::String;
+
+// ==========================================
+// SERIAL CONSOLE
+// ==========================================
+// A line-based front end to the kernel shell on COM1, with no window
+// manager, keyboard or userspace involved. That makes it what a headless
+// QEMU run (the runner's --test mode) types into. The UART raises no
+// interrupt here, so a kernel task polls the receive register every
+// POLL_MS and runs each finished line through kshell::run, printing the
+// output back. Bytes are echoed as they arrive; output also lands in the
+// log ring like any other serial text.
+
+pub const PROMPT: &str = "nyx> ";
+/// Printed once the task is taking input; the runner's test script waits for it
+pub const READY_MARKER: &str = "[SERIAL] Console ready";
+
+const POLL_MS: u64 = 20;
+const MAX_LINE: usize = 256;
+
+/// Starts the console task on this core. Call once during boot.
+pub fn init() {
+    crate::scheduler::spawn_kernel_task("serial-console", console_loop).expect("Failed to create serial console");
+}
+
+fn read_byte() -> Option<u8> {
+    x86_64::instructions::interrupts::without_interrupts(|| crate::serial::SERIAL1.lock().try_read_byte())
+}
+
+fn console_loop() {
+    crate::serial_println!("{}", READY_MARKER);
+    crate::serial_print!("{}", PROMPT);
+    let mut line = String::new();
+    // Terminals send CR, CR LF or LF for Enter; LF straight after CR is the same Enter
+    let mut after_cr = false;
+    loop {
+        let byte = match read_byte() {
+            Some(b) => b,
+            None => {
+                x86_64::instructions::interrupts::disable();
+                crate::scheduler::block_current(POLL_MS);
+                continue;
+            }
+        };
+        let lf_after_cr = after_cr && byte == b'\n';
+        after_cr = byte == b'\r';
+        match byte {
+            b'\n' if lf_after_cr => {}
+            b'\r' | b'\n' => {
+                crate::serial_println!();
+                for out in crate::kshell::run(&line) { crate::serial_println!("{}", out); }
+                line.clear();
+                crate::serial_print!("{}", PROMPT);
+            }
+            // Backspace and DEL both rub out the last character
+            0x08 | 0x7F => {
+                if line.pop().is_some() { crate::serial_print!("\x08 \x08"); }
+            }
+            0x20..=0x7E if line.len() < MAX_LINE => {
+                line.push(byte as char);
+                crate::serial_print!("{}", byte as char);
+            }
+            _ => {}
+        }
+    }
+}
@@ -1,33 +1,61 @@
-use pc_keyboard::{layouts, DecodedKey, HandleControl, Keyboard, ScancodeSet1};
 use spin::Mutex;
 use alloc::collections::vec_deque::VecDeque;
 use lazy_static::lazy_static;
 
+// Navigation keys have no Unicode equivalent, so they travel through the
+// key queue as Private Use Area codepoints (mirrored in nyx_api).
+pub const KEY_UP: char = '\u{E000}';
+pub const KEY_DOWN: char = '\u{E001}';
+pub const KEY_LEFT: char = '\u{E002}';
+pub const KEY_RIGHT: char = '\u{E003}';
+pub const KEY_PAGE_UP: char = '\u{E004}';
+pub const KEY_PAGE_DOWN: char = '\u{E005}';
+pub const KEY_HOME: char = '\u{E006}';
+pub const KEY_END: char = '\u{E007}';
+
+// ─────────────────────────────────────────────────────────────────────────
+// PACKED KEY EVENT LAYOUT (Syscall 538)
+//   bits  0..31  translated char (0 if the key has none)
+//   bits 32..39  set-1 scancode (make code, release bit stripped)
+//   bits 40..47  modifier bits (MOD_*)
+//   bit  48      1 = pressed, 0 = released
+//   bit  49      1 = extended (0xE0-prefixed) key
+// ─────────────────────────────────────────────────────────────────────────
+pub const MOD_SHIFT: u8 = 1 << 0;
+pub const MOD_CTRL: u8 = 1 << 1;
+pub const MOD_ALT: u8 = 1 << 2;
+pub const MOD_CAPS: u8 = 1 << 3;
+
+pub const KEY_EVENT_PRESSED: u64 = 1 << 48;
+pub const KEY_EVENT_EXTENDED: u64 = 1 << 49;
+
+// Drop the oldest events if nobody is draining the queue
+const KEY_QUEUE_CAP: usize = 256;
+
 lazy_static! {
-    // Queue for keys waiting to be read by User Space
-    pub static ref KEY_QUEUE: Mutex<VecDeque<char>> = Mutex::new(VecDeque::new());
+    // Queue for key events waiting to be read by User Space
+    pub static ref KEY_QUEUE: Mutex<VecDeque<u64>> = Mutex::new(VecDeque::new());
 }
 
-pub fn handle_key(scancode: u8) {
-    lazy_static! {
-        static ref KEYBOARD: Mutex<Keyboard<layouts::Us104Key, ScancodeSet1>> =
-            Mutex::new(Keyboard::new(ScancodeSet1::new(), layouts::Us104Key, HandleControl::Ignore));
-    }
+/// Queues one decoded key event for userspace (Syscalls 506 and 538).
+pub fn push_key_event(packed: u64) {
+    let mut queue = KEY_QUEUE.lock();
+    if queue.len() >= KEY_QUEUE_CAP { queue.pop_front(); }
+    queue.push_back(packed);
+}
 
-    let mut keyboard = KEYBOARD.lock();
-    if let Ok(Some(key_event)) = keyboard.add_byte(scancode) {
-        if let Some(key) = keyboard.process_keyevent(key_event) {
-            match key {
-                DecodedKey::Unicode(character) => {
-                    // Push to queue for Syscalls
-                    KEY_QUEUE.lock().push_back(character);
-                },
-                DecodedKey::RawKey(_) => {},
-            }
+/// Legacy path for Syscall 506: returns the next pressed key that has a character.
+pub fn pop_key() -> Option<char> {
+    let mut queue = KEY_QUEUE.lock();
+    while let Some(packed) = queue.pop_front() {
+        let ch = (packed & 0xFFFF_FFFF) as u32;
+        if packed & KEY_EVENT_PRESSED != 0 && ch != 0 {
+            return core::char::from_u32(ch);
         }
     }
+    None
 }
 
-pub fn pop_key() -> Option<char> {
+pub fn pop_key_event() -> Option<u64> {
     KEY_QUEUE.lock().pop_front()
-}
\ No newline at end of file
+}
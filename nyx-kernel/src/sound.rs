@@ -0,0 +1,146 @@
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use spin::Mutex;
+use x86_64::instructions::port::Port;
+use crate::time::{PIT_HZ, UPTIME_MS};
+
+// ==========================================
+// SOUND
+// ==========================================
+// The PC speaker, for now the only thing that makes a noise. PIT channel 2
+// runs as a square wave at the tone's frequency, and bits 0 (gate) and 1
+// (speaker data) of port 0x61 connect it to the speaker. play_tone returns
+// at once: the tone is queued, and the BSP's timer tick (check) starts the
+// next one or silences the speaker when its time is up, so a beep never
+// holds up the caller or any core.
+//
+// Intel HD Audio controllers are only noticed on the PCI scan so far and
+// reported through SYS_GET_SYSTEM_INFO; nothing streams to them yet.
+
+const PIT_CHANNEL2: u16 = 0x42;
+const PIT_COMMAND: u16 = 0x43;
+const SPEAKER_PORT: u16 = 0x61;
+// Channel 2, lobyte/hibyte, mode 3 (square wave)
+const PIT_SQUARE_WAVE: u8 = 0b1011_0110;
+const SPEAKER_ON: u8 = 0b11;
+
+pub const MIN_FREQ_HZ: u32 = 20;
+pub const MAX_FREQ_HZ: u32 = 20_000;
+pub const MAX_TONE_MS: u32 = 5000;
+// Tones waiting behind the one playing; more than this are dropped
+const QUEUE_LEN: usize = 8;
+
+/// SYS_GET_SYSTEM_INFO's `audio` bits
+pub const AUDIO_PC_SPEAKER: u32 = 1;
+pub const AUDIO_HDA: u32 = 2;
+
+#[derive(Clone, Copy)]
+struct Tone {
+    freq_hz: u32,
+    ms: u32,
+}
+
+struct Queue {
+    tones: [Tone; QUEUE_LEN],
+    head: usize,
+    len: usize,
+}
+
+static QUEUE: Mutex<Queue> = Mutex::new(Queue { tones: [Tone { freq_hz: 0, ms: 0 }; QUEUE_LEN], head: 0, len: 0 });
+// Uptime at which the tone playing ends; 0 while the speaker is quiet
+static STOP_AT_MS: AtomicU64 = AtomicU64::new(0);
+static HDA_PRESENT: AtomicBool = AtomicBool::new(false);
+
+fn speaker_on(freq_hz: u32) {
+    let divisor = (PIT_HZ / freq_hz as u64).clamp(1, u16::MAX as u64) as u16;
+    let mut command: Port<u8> = Port::new(PIT_COMMAND);
+    let mut channel2: Port<u8> = Port::new(PIT_CHANNEL2);
+    let mut speaker: Port<u8> = Port::new(SPEAKER_PORT);
+    unsafe {
+        command.write(PIT_SQUARE_WAVE);
+        channel2.write((divisor & 0xFF) as u8);
+        channel2.write((divisor >> 8) as u8);
+        let value = speaker.read();
+        speaker.write(value | SPEAKER_ON);
+    }
+}
+
+fn speaker_off() {
+    let mut speaker: Port<u8> = Port::new(SPEAKER_PORT);
+    unsafe {
+        let value = speaker.read();
+        speaker.write(value & !SPEAKER_ON);
+    }
+}
+
+/// Queues a `freq_hz` tone for `ms` milliseconds behind whatever is playing.
+/// False if the arguments are out of range or the queue is full.
+pub fn play_tone(freq_hz: u32, ms: u32) -> bool {
+    if !(MIN_FREQ_HZ..=MAX_FREQ_HZ).contains(&freq_hz) || ms == 0 || ms > MAX_TONE_MS { return false; }
+    let now = UPTIME_MS.load(Ordering::Relaxed);
+    // The tick takes this lock too, so it mustn't fire on this core meanwhile
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let mut queue = QUEUE.lock();
+        if STOP_AT_MS.load(Ordering::Relaxed) == 0 {
+            speaker_on(freq_hz);
+            STOP_AT_MS.store(now + ms as u64, Ordering::Relaxed);
+            return true;
+        }
+        if queue.len == QUEUE_LEN { return false; }
+        let slot = (queue.head + queue.len) % QUEUE_LEN;
+        queue.tones[slot] = Tone { freq_hz, ms };
+        queue.len += 1;
+        true
+    })
+}
+
+/// Silences the speaker and drops anything queued.
+pub fn stop() {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let mut queue = QUEUE.lock();
+        queue.len = 0;
+        STOP_AT_MS.store(0, Ordering::Relaxed);
+        speaker_off();
+    });
+}
+
+/// Called from the BSP's timer tick: ends the current tone when its time is
+/// up and starts the next.
+pub fn check(now_ms: u64) {
+    let stop_at = STOP_AT_MS.load(Ordering::Relaxed);
+    if stop_at == 0 || now_ms < stop_at { return; }
+    // Whoever holds the lock is changing the queue; look again next tick
+    let Some(mut queue) = QUEUE.try_lock() else { return };
+    if queue.len == 0 {
+        speaker_off();
+        STOP_AT_MS.store(0, Ordering::Relaxed);
+        return;
+    }
+    let tone = queue.tones[queue.head];
+    queue.head = (queue.head + 1) % QUEUE_LEN;
+    queue.len -= 1;
+    speaker_on(tone.freq_hz);
+    STOP_AT_MS.store(now_ms + tone.ms as u64, Ordering::Relaxed);
+}
+
+/// The panic screen's alarm: high, low, high, low. Interrupts are off by
+/// then, so this times itself on the TSC and takes no locks.
+pub fn panic_alarm() {
+    for &(freq_hz, ms) in &[(880, 150), (440, 150), (880, 150), (440, 300)] {
+        speaker_on(freq_hz);
+        let end = crate::time::monotonic_ms() + ms;
+        while crate::time::monotonic_ms() < end { core::hint::spin_loop(); }
+    }
+    speaker_off();
+}
+
+/// The PCI scan found an HD Audio controller (class 0x04, subclass 0x03).
+pub fn found_hda(vendor_id: u16, device_id: u16) {
+    crate::serial_println!("[PCI] Found HD Audio controller: Vendor {:#06x}, Device {:#06x} (no driver yet)", vendor_id, device_id);
+    HDA_PRESENT.store(true, Ordering::Relaxed);
+}
+
+/// AUDIO_* bits for the audio hardware found at boot.
+pub fn devices() -> u32 {
+    let hda = if HDA_PRESENT.load(Ordering::Relaxed) { AUDIO_HDA } else { 0 };
+    AUDIO_PC_SPEAKER | hda
+}
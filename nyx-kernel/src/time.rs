@@ -4,6 +4,15 @@ use x86_64::instructions::port::Port;
 // The unbendable wall clock.
 pub static UPTIME_MS: AtomicU64 = AtomicU64::new(0);
 
+/// Scheduler tick rate. Every tick advances UPTIME_MS by MS_PER_TICK, so this
+/// must divide 1000 evenly (1000, 500, 250, 200, 100 ...).
+pub const TICK_HZ: u64 = 1000;
+pub const MS_PER_TICK: u64 = 1000 / TICK_HZ;
+const _: () = assert!(1000 % TICK_HZ == 0, "TICK_HZ must divide 1000");
+
+// PIT input clock
+pub const PIT_HZ: u64 = 1_193_182;
+
 // Default to 2 GHz, but will be dynamically calibrated on boot!
 pub static TSC_MHZ: AtomicU64 = AtomicU64::new(2000);
 
@@ -11,14 +20,15 @@ pub fn init() {
     crate::serial_println!("[BOOT] Initializing Global Uptime Clock...");
 }
 
-/// Uses the legacy Programmable Interval Timer (PIT) Channel 2 (PC Speaker) 
-/// to dynamically calculate the CPU's true clock speed!
-pub fn calibrate_tsc() {
+/// Uses the legacy Programmable Interval Timer (PIT) Channel 2 (PC Speaker)
+/// as a one-shot 10 ms gate: `start` runs when the countdown begins and
+/// `stop` when it ends. None if the PIT never signalled (no legacy timer).
+pub fn pit_gate_10ms<T>(start: impl FnOnce(), stop: impl FnOnce() -> T) -> Option<T> {
     let mut port_61: Port<u8> = Port::new(0x61); // PC Speaker Port
     let mut port_43: Port<u8> = Port::new(0x43); // PIT Command Port
     let mut port_42: Port<u8> = Port::new(0x42); // PIT Channel 2 Data Port
 
-    let ticks: u16 = 11931;
+    let ticks: u16 = (PIT_HZ / 100) as u16;
 
     unsafe {
         port_43.write(0b10110000);
@@ -27,10 +37,7 @@ pub fn calibrate_tsc() {
 
         let port_61_val = port_61.read();
         port_61.write((port_61_val & 0xFD) | 1);
-
-        let mut lo: u32; let mut hi: u32;
-        core::arch::asm!("rdtsc", out("eax") lo, out("edx") hi);
-        let start_tsc = ((hi as u64) << 32) | (lo as u64);
+        start();
 
         let mut timeout = 0;
         while (port_61.read() & 0x20) == 0 {
@@ -41,27 +48,63 @@ pub fn calibrate_tsc() {
             if timeout > 50_000 { break; } 
         }
 
-        core::arch::asm!("rdtsc", out("eax") lo, out("edx") hi);
-        let end_tsc = ((hi as u64) << 32) | (lo as u64);
-
+        let result = stop();
         port_61.write(port_61_val);
+        if timeout <= 50_000 { Some(result) } else { None }
+    }
+}
 
-        if timeout <= 50_000 {
-            let tsc_hz = (end_tsc - start_tsc) * 100;
-            let mut tsc_mhz = tsc_hz / 1_000_000;
-            
-            if tsc_mhz < 100 || tsc_mhz > 10_000 { tsc_mhz = 2000; }
-            
-            TSC_MHZ.store(tsc_mhz, Ordering::SeqCst);
-            crate::serial_println!("[TIME] CPU TSC Calibrated successfully to {} MHz!", tsc_mhz);
-        } else {
-            crate::serial_println!("[TIME] Hardware PIT missing. Defaulting to 2000 MHz.");
-        }
+/// Runs PIT channel 0 as a periodic TICK_HZ interrupt on IRQ0. Only used when
+/// there is no local APIC timer to drive the scheduler (see apic::start_ticks).
+pub fn start_pit_ticks() {
+    let divisor = (PIT_HZ / TICK_HZ) as u16;
+    let mut port_43: Port<u8> = Port::new(0x43);
+    let mut port_40: Port<u8> = Port::new(0x40);
+    unsafe {
+        port_43.write(0b00110100); // Channel 0, lo/hi byte, rate generator
+        port_40.write((divisor & 0xFF) as u8);
+        port_40.write((divisor >> 8) as u8);
+    }
+}
+
+pub fn rdtsc() -> u64 {
+    unsafe { core::arch::x86_64::_rdtsc() }
+}
+
+/// Dynamically calculates the CPU's true clock speed against the PIT.
+pub fn calibrate_tsc() {
+    let mut start_tsc = 0;
+    let end_tsc = pit_gate_10ms(|| start_tsc = rdtsc(), rdtsc);
+
+    if let Some(end_tsc) = end_tsc {
+        let tsc_hz = (end_tsc - start_tsc) * 100;
+        let mut tsc_mhz = tsc_hz / 1_000_000;
+        
+        if tsc_mhz < 100 || tsc_mhz > 10_000 { tsc_mhz = 2000; }
+        
+        TSC_MHZ.store(tsc_mhz, Ordering::SeqCst);
+        crate::serial_println!("[TIME] CPU TSC Calibrated successfully to {} MHz!", tsc_mhz);
+    } else {
+        crate::serial_println!("[TIME] Hardware PIT missing. Defaulting to 2000 MHz.");
     }
 }
 
-/// Early-boot hardware delay used exclusively by SMP initialization.
+/// Milliseconds since the TSC started counting. Unlike UPTIME_MS this runs
+/// before the scheduler tick is started, so boot-time driver timeouts use it.
+pub fn monotonic_ms() -> u64 {
+    rdtsc() / (TSC_MHZ.load(Ordering::Relaxed) * 1000)
+}
+
+/// Waits at least `ms` milliseconds. Once the uptime clock is ticking and
+/// interrupts are on, the core halts between ticks instead of burning cycles;
+/// before that (driver init, AP bring-up) it spins on the calibrated TSC.
 pub fn sleep_ms(ms: u64) {
+    if UPTIME_MS.load(Ordering::Relaxed) > 0 && x86_64::instructions::interrupts::are_enabled() {
+        let target = UPTIME_MS.load(Ordering::Relaxed) + ms;
+        while UPTIME_MS.load(Ordering::Relaxed) < target { x86_64::instructions::hlt(); }
+        return;
+    }
+
     let mut lo: u32; let mut hi: u32;
     unsafe { core::arch::asm!("rdtsc", out("eax") lo, out("edx") hi) };
     let start = ((hi as u64) << 32) | (lo as u64);
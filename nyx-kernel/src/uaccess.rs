@@ -0,0 +1,116 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use crate::errno::{EFAULT, EINVAL, ENAMETOOLONG};
+
+// ==========================================
+// USER MEMORY ACCESS
+// ==========================================
+// Syscalls never dereference a user pointer on trust. Each page of the range
+// is looked up in the *current* page tables and must be present and user
+// accessible; destinations must also be writable (or copy-on-write, which
+// the page fault handler resolves for ring 0 too). That only admits memory
+// the task really has: its image, stacks, heap pages, shared memory and the
+// framebuffer/MMIO windows mapped into it. Pages of a lazy region the task
+// hasn't touched yet are backed on the spot, as a user access would be.
+//
+// Copies run one page at a time, so oversized lengths fail cleanly instead
+// of faulting the kernel halfway through.
+
+/// End of the lower canonical half; nothing at or above this belongs to a task.
+pub const USER_TOP: u64 = 0x0000_8000_0000_0000;
+/// Largest single range a syscall may hand us (a 4K framebuffer fits comfortably)
+pub const MAX_USER_RANGE: usize = 64 * 1024 * 1024;
+/// Longest string argument (paths, host names) read_user_str accepts
+pub const MAX_USER_STR: usize = 4096;
+
+const PTE_PRESENT: u64 = 1 << 0;
+const PTE_WRITABLE: u64 = 1 << 1;
+const PTE_USER: u64 = 1 << 2;
+const PTE_HUGE: u64 = 1 << 7;
+const PTE_COW: u64 = 1 << 10;
+const PHYS_MASK: u64 = 0x000F_FFFF_FFFF_F000;
+
+/// Looks up `vaddr` in the active address space. Returns (user, writable, page size),
+/// where user/writable only hold if every level of the walk allows them. None if unmapped.
+fn lookup(vaddr: u64) -> Option<(bool, bool, u64)> {
+    let offset = unsafe { crate::memory::PHYS_MEM_OFFSET };
+    let mut table = x86_64::registers::control::Cr3::read().0.start_address().as_u64();
+    let mut user = true;
+    let mut writable = true;
+
+    for (level, shift) in [39u64, 30, 21, 12].into_iter().enumerate() {
+        let entry = unsafe { *((table + offset) as *const u64).add(((vaddr >> shift) & 0x1FF) as usize) };
+        if entry & PTE_PRESENT == 0 { return None; }
+        if entry & PTE_USER == 0 { user = false; }
+        // CoW pages are read-only until the fault handler gives the task its own copy
+        if entry & PTE_WRITABLE == 0 && entry & PTE_COW == 0 { writable = false; }
+
+        // 1 GiB and 2 MiB pages end the walk early
+        if level == 3 || (level > 0 && entry & PTE_HUGE != 0) {
+            return Some((user, writable, 1u64 << shift));
+        }
+        table = entry & PHYS_MASK;
+    }
+    None
+}
+
+/// True if `[ptr, ptr + len)` is mapped for the current task, and writable if `write`.
+pub fn range_ok(ptr: u64, len: usize, write: bool) -> bool {
+    if ptr == 0 { return false; }
+    if len == 0 { return ptr < USER_TOP; }
+    if len > MAX_USER_RANGE { return false; }
+    let end = match ptr.checked_add(len as u64) { Some(e) if e <= USER_TOP => e, _ => return false };
+
+    let mut addr = ptr;
+    while addr < end {
+        // Reserved-but-untouched heap pages are backed now rather than refused
+        let found = lookup(addr).or_else(|| if crate::process::demand_map(addr) { lookup(addr) } else { None });
+        let (user, writable, page_size) = match found { Some(f) => f, None => return false };
+        if !user || (write && !writable) { return false; }
+        addr = (addr & !(page_size - 1)) + page_size;
+    }
+    true
+}
+
+/// Copies `dst.len()` bytes from user address `src` into kernel memory.
+pub fn copy_from_user(dst: &mut [u8], src: u64) -> Result<(), i64> {
+    if !range_ok(src, dst.len(), false) { return Err(EFAULT); }
+    let mut done = 0;
+    while done < dst.len() {
+        let addr = src + done as u64;
+        let n = (4096 - (addr & 0xFFF) as usize).min(dst.len() - done);
+        unsafe { core::ptr::copy_nonoverlapping(addr as *const u8, dst[done..].as_mut_ptr(), n); }
+        done += n;
+    }
+    Ok(())
+}
+
+/// Copies `src` out to user address `dst`.
+pub fn copy_to_user(dst: u64, src: &[u8]) -> Result<(), i64> {
+    if !range_ok(dst, src.len(), true) { return Err(EFAULT); }
+    let mut done = 0;
+    while done < src.len() {
+        let addr = dst + done as u64;
+        let n = (4096 - (addr & 0xFFF) as usize).min(src.len() - done);
+        unsafe { core::ptr::copy_nonoverlapping(src[done..].as_ptr(), addr as *mut u8, n); }
+        done += n;
+    }
+    Ok(())
+}
+
+/// Copies `len` bytes at user address `ptr` into a new buffer. The range is
+/// checked before anything is allocated for it; an empty read never faults.
+pub fn read_user_bytes(ptr: u64, len: usize) -> Result<Vec<u8>, i64> {
+    if len == 0 { return Ok(Vec::new()); }
+    if !range_ok(ptr, len, false) { return Err(EFAULT); }
+    let mut bytes = alloc::vec![0u8; len];
+    copy_from_user(&mut bytes, ptr)?;
+    Ok(bytes)
+}
+
+/// Copies a (ptr, len) string argument into the kernel. ENAMETOOLONG past
+/// MAX_USER_STR, EFAULT for bad memory, EINVAL for anything that isn't UTF-8.
+pub fn read_user_str(ptr: u64, len: usize) -> Result<String, i64> {
+    if len > MAX_USER_STR { return Err(ENAMETOOLONG); }
+    String::from_utf8(read_user_bytes(ptr, len)?).map_err(|_| EINVAL)
+}
@@ -1,7 +1,7 @@
 use core::ptr::{read_volatile, write_volatile};
 use alloc::alloc::{alloc, Layout};
 use alloc::vec::Vec;
-use core::sync::atomic::{fence, Ordering};
+use core::sync::atomic::{fence, AtomicBool, AtomicU64, Ordering};
 use spin::Mutex;
 use lazy_static::lazy_static;
 
@@ -9,6 +9,290 @@ lazy_static! {
     pub static ref USB_CONTROLLER: Mutex<Option<XhciController>> = Mutex::new(None);
 }
 
+// ==========================================
+// EVENT DISPATCH
+// ==========================================
+// Interrupter 0's event ring is drained in exactly one place, EventRing::drain,
+// which runs from the interrupt handler (MSI vector XHCI_VECTOR) and from
+// anyone waiting on an event, so controllers without MSI still make progress.
+// Events are sorted into two queues: command completions, which the command
+// helpers wait on, and transfer events, split between control transfers on
+// EP0 (waited on by the request helpers) and HID reports (consumed by the
+// event task through poll_hid_devices). Port status changes are queued for
+// the event task too, which attaches and detaches devices after boot. Waits
+// are bounded in milliseconds, not spin counts. The queues are fixed arrays
+// so the interrupt path never touches the heap.
+
+/// IDT vector of the controller's interrupter 0 (0x30 RTL8168, 0x31 NVMe)
+pub const XHCI_VECTOR: u8 = 0x32;
+const CMD_TIMEOUT_MS: u64 = 500;
+const XFER_TIMEOUT_MS: u64 = 500;
+// Register handshakes during bring-up: BIOS handoff, halt, reset, port reset
+const HANDOFF_TIMEOUT_MS: u64 = 1000;
+const HALT_TIMEOUT_MS: u64 = 100;
+const RESET_TIMEOUT_MS: u64 = 1000;
+const PORT_RESET_TIMEOUT_MS: u64 = 500;
+// Reset recovery (TRSTRCY) before a freshly reset device must answer
+const PORT_RECOVERY_MS: u64 = 20;
+// Without MSI the event task polls at roughly a HID report interval
+const EVENT_POLL_MS: u64 = 8;
+
+const TRB_TRANSFER_EVENT: u32 = 32;
+const TRB_COMMAND_COMPLETION: u32 = 33;
+const TRB_PORT_STATUS_CHANGE: u32 = 34;
+const CC_SUCCESS: u8 = 1;
+const CC_SHORT_PACKET: u8 = 13;
+const EP0_DCI: u8 = 1;
+
+#[derive(Clone, Copy, Debug)]
+pub struct CommandEvent { pub slot: u8, pub code: u8 }
+
+#[derive(Clone, Copy, Debug)]
+pub struct TransferEvent { pub slot: u8, pub endpoint: u8, pub code: u8, pub residual: u32 }
+
+/// Fixed-capacity FIFO; when full, the oldest entry is dropped.
+struct EventQueue<T: Copy, const N: usize> { items: [Option<T>; N], len: usize }
+
+impl<T: Copy, const N: usize> EventQueue<T, N> {
+    fn new() -> Self { Self { items: [None; N], len: 0 } }
+    fn clear(&mut self) { self.len = 0; }
+    fn push(&mut self, item: T) {
+        if self.len == N { self.remove(0); }
+        self.items[self.len] = Some(item);
+        self.len += 1;
+    }
+    fn remove(&mut self, idx: usize) -> Option<T> {
+        let item = self.items[idx].take();
+        self.items.copy_within(idx + 1..self.len, idx);
+        self.len -= 1;
+        item
+    }
+    fn pop(&mut self) -> Option<T> { self.take(|_| true) }
+    /// Removes and returns the oldest entry matching `pred`.
+    fn take(&mut self, pred: impl Fn(&T) -> bool) -> Option<T> {
+        let idx = (0..self.len).find(|&i| self.items[i].as_ref().map_or(false, &pred))?;
+        self.remove(idx)
+    }
+    fn any(&self, pred: impl Fn(&T) -> bool) -> bool {
+        self.items[..self.len].iter().flatten().any(pred)
+    }
+}
+
+struct EventRing {
+    ring: *mut Trb,
+    phys: u64,
+    index: usize,
+    cycle: bool,
+    ir: *mut InterrupterRegisters,
+    usbsts: *mut u32,
+    commands: EventQueue<CommandEvent, 16>,
+    transfers: EventQueue<TransferEvent, 64>,
+    // Root port numbers (1-based) with a status change to look at
+    port_changes: EventQueue<u8, 32>,
+    // DCI of each slot's HID interrupt endpoint (0 = none). Only those events
+    // belong to the event task; bulk transfers are waited on by their caller.
+    hid_dci: [u8; 256],
+}
+
+unsafe impl Send for EventRing {}
+
+impl EventRing {
+    /// Moves everything the controller has posted into the queues and hands
+    /// the consumed TRBs back. Returns true if anything arrived.
+    unsafe fn drain(&mut self) -> bool {
+        let mut any = false;
+        loop {
+            let trb_ptr = self.ring.add(self.index);
+            XhciController::clflush(trb_ptr as *const u8);
+            let trb = read_volatile(trb_ptr);
+            if ((trb.control & 1) != 0) != self.cycle { break; }
+            self.index = (self.index + 1) % 256;
+            if self.index == 0 { self.cycle = !self.cycle; }
+            any = true;
+
+            let code = ((trb.status >> 24) & 0xFF) as u8;
+            let slot = ((trb.control >> 24) & 0xFF) as u8;
+            match (trb.control >> 10) & 0x3F {
+                TRB_TRANSFER_EVENT => self.transfers.push(TransferEvent {
+                    slot, code, endpoint: ((trb.control >> 16) & 0x1F) as u8, residual: trb.status & 0xFF_FFFF,
+                }),
+                TRB_COMMAND_COMPLETION => self.commands.push(CommandEvent { slot, code }),
+                TRB_PORT_STATUS_CHANGE => {
+                    let port = ((trb.parameter >> 24) & 0xFF) as u8;
+                    if !self.port_changes.any(|&p| p == port) { self.port_changes.push(port); }
+                }
+                _ => {}
+            }
+        }
+        if any {
+            let phys = self.phys + (self.index as u64 * 16);
+            write_volatile(&mut (*self.ir).erdp, phys | 8); // EHB: event handler busy cleared
+        }
+        any
+    }
+
+    /// Clears the interrupt pending bits so the next event raises a new interrupt.
+    unsafe fn acknowledge(&mut self) {
+        write_volatile(self.usbsts, 1 << 3); // EINT, write-1-to-clear
+        write_volatile(&mut (*self.ir).iman, 3); // IP (write-1-to-clear) | IE
+    }
+
+    fn is_hid_report(hid_dci: &[u8; 256], t: &TransferEvent) -> bool {
+        t.endpoint != EP0_DCI && hid_dci[t.slot as usize] == t.endpoint
+    }
+
+    /// True if the event task has something to do
+    fn has_work(&self) -> bool {
+        self.transfers.any(|t| Self::is_hid_report(&self.hid_dci, t)) || self.port_changes.len > 0
+    }
+}
+
+static EVENTS: Mutex<Option<EventRing>> = Mutex::new(None);
+// True once the controller's interrupts reach XHCI_VECTOR
+static IRQ_ROUTED: AtomicBool = AtomicBool::new(false);
+static EVENT_TASK_PID: AtomicU64 = AtomicU64::new(0);
+
+/// Called from the xHCI interrupt handler.
+pub fn handle_interrupt() {
+    // Waiters hold EVENTS with interrupts off, so on this core it is always free;
+    // if another core has it, that core is draining right now anyway.
+    let mut guard = match EVENTS.try_lock() { Some(g) => g, None => return };
+    let events = match guard.as_mut() { Some(e) => e, None => return };
+    let wake = unsafe {
+        events.acknowledge();
+        events.drain() && events.has_work()
+    };
+    drop(guard);
+    if wake { crate::scheduler::wake_task(EVENT_TASK_PID.load(Ordering::Relaxed)); }
+}
+
+/// Drains the event ring until `take` finds what the caller is waiting for, or
+/// `timeout_ms` pass. Halts between events once interrupts and the scheduler tick
+/// are running; before that (boot-time enumeration) it polls.
+fn wait_event<T>(timeout_ms: u64, take: impl Fn(&mut EventRing) -> Option<T>) -> Option<T> {
+    crate::watch!("XHCI", "event ring");
+    let deadline = crate::time::monotonic_ms() + timeout_ms;
+    loop {
+        let found = x86_64::instructions::interrupts::without_interrupts(|| {
+            let mut guard = EVENTS.lock();
+            let events = guard.as_mut()?;
+            unsafe { events.drain(); }
+            take(events)
+        });
+        if found.is_some() || crate::time::monotonic_ms() >= deadline { return found; }
+
+        let can_sleep = IRQ_ROUTED.load(Ordering::Relaxed)
+            && x86_64::instructions::interrupts::are_enabled()
+            && crate::time::UPTIME_MS.load(Ordering::Relaxed) > 0;
+        if can_sleep { x86_64::instructions::hlt(); } else { core::hint::spin_loop(); }
+    }
+}
+
+/// Waits for the next transfer event on endpoint `dci` of `slot_id`.
+fn wait_transfer(slot_id: u8, dci: u8, timeout_ms: u64) -> Option<TransferEvent> {
+    wait_event(timeout_ms, |ev| ev.transfers.take(|t| t.slot == slot_id && t.endpoint == dci))
+}
+
+/// Drops events a timed-out transfer on `dci` of `slot_id` may have left behind.
+fn discard_transfers(slot_id: u8, dci: u8) {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        if let Some(ev) = EVENTS.lock().as_mut() {
+            while ev.transfers.take(|t| t.slot == slot_id && (dci == 0 || t.endpoint == dci)).is_some() {}
+        }
+    });
+}
+
+/// Tells the event task which endpoint of `slot_id` carries HID reports (0 = none).
+fn set_hid_endpoint(slot_id: u8, dci: u8) {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        if let Some(ev) = EVENTS.lock().as_mut() { ev.hid_dci[slot_id as usize] = dci; }
+    });
+}
+
+/// Waits for the transfer event that ends a control transfer on `slot_id`'s EP0.
+fn wait_control(slot_id: u8) -> Result<TransferEvent, &'static str> {
+    match wait_transfer(slot_id, EP0_DCI, XFER_TIMEOUT_MS) {
+        Some(t) if t.code == CC_SUCCESS || t.code == CC_SHORT_PACKET => Ok(t),
+        Some(t) => {
+            crate::serial_println!("[USB] Control transfer on Slot {} failed (Code {})", slot_id, t.code);
+            Err("Transfer Error")
+        }
+        None => Err("Transfer Timeout"),
+    }
+}
+
+/// Starts the xHCI controller `dev` whose registers are mapped at `mmio_virt`,
+/// routes its interrupts to XHCI_VECTOR and enumerates the root ports.
+pub unsafe fn bring_up(dev: &crate::pci::PciDevice, mmio_virt: u64) {
+    let mut controller = match XhciController::new(mmio_virt) { Ok(c) => c, Err(_) => return };
+    if controller.init().is_err() { return; }
+    let routed = crate::pci::PciDriver::new().enable_msi(dev, XHCI_VECTOR);
+    if !routed { crate::serial_println!("[USB] No MSI; events will be polled."); }
+    IRQ_ROUTED.store(routed, Ordering::SeqCst);
+    controller.check_ports();
+    *USB_CONTROLLER.lock() = Some(controller);
+}
+
+/// Starts the kernel task that feeds HID reports to the input drivers and
+/// handles hotplug, if a controller came up. Call once after PCI enumeration.
+pub fn start_event_task() {
+    if USB_CONTROLLER.lock().is_none() { return; }
+    match crate::scheduler::spawn_kernel_task("usb-events", event_task) {
+        Ok(pid) => EVENT_TASK_PID.store(pid, Ordering::SeqCst),
+        Err(e) => crate::serial_println!("[USB] Could not start the event task: {}", e),
+    }
+}
+
+/// Takes USB_CONTROLLER. The event task holds it with interrupts on, so it can
+/// be preempted with the lock held; a caller with interrupts off (USB storage
+/// from a syscall) lets them in while it waits, or that task could never run
+/// on this core again to release it.
+pub fn lock_controller() -> spin::MutexGuard<'static, Option<XhciController>> {
+    loop {
+        if let Some(guard) = USB_CONTROLLER.try_lock() { return guard; }
+        if x86_64::instructions::interrupts::are_enabled() {
+            core::hint::spin_loop();
+        } else {
+            x86_64::instructions::interrupts::enable_and_hlt();
+            x86_64::instructions::interrupts::disable();
+        }
+    }
+}
+
+/// The next root port the controller flagged as changed, if any.
+fn next_port_change() -> Option<u8> {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let mut guard = EVENTS.lock();
+        let events = guard.as_mut()?;
+        unsafe { events.drain(); }
+        events.port_changes.pop()
+    })
+}
+
+fn event_task() {
+    loop {
+        // Interrupts stay on: the controller is locked only while it's being
+        // worked on, and the settle and reset recovery sleeps happen without it
+        while let Some(port) = next_port_change() {
+            if !lock_controller().as_mut().is_some_and(|c| c.port_changed(port)) { continue; }
+            // Let the connection settle before the reset, as check_ports does
+            crate::time::sleep_ms(100);
+            if !lock_controller().as_mut().is_some_and(|c| unsafe { c.reset_port(port) }) { continue; }
+            crate::time::sleep_ms(PORT_RECOVERY_MS);
+            if let Some(controller) = lock_controller().as_mut() { unsafe { controller.attach_port(port); } }
+        }
+        if let Some(controller) = lock_controller().as_mut() { controller.poll_hid_devices(); }
+
+        x86_64::instructions::interrupts::disable();
+        let pending = EVENTS.lock().as_ref().map_or(false, |ev| ev.has_work());
+        if pending {
+            x86_64::instructions::interrupts::enable();
+        } else {
+            crate::scheduler::block_current(EVENT_POLL_MS);
+        }
+    }
+}
+
 const CMD_RUN: u32 = 0x00000001;
 const CMD_HCRST: u32 = 0x00000002;
 const CMD_INTE: u32 = 0x00000004;
@@ -93,6 +377,10 @@ impl Trb {
     pub const TYPE_STATUS: u32 = 4 << 10;
     pub const TYPE_LINK: u32 = 6 << 10;
     pub const TYPE_ENABLE_SLOT: u32 = 9 << 10;
+    pub const TYPE_DISABLE_SLOT: u32 = 10 << 10;
+    pub const TYPE_RESET_ENDPOINT: u32 = 14 << 10;
+    pub const TYPE_STOP_ENDPOINT: u32 = 15 << 10;
+    pub const TYPE_SET_TR_DEQUEUE: u32 = 16 << 10;
     pub const TYPE_ADDRESS_DEVICE: u32 = 11 << 10;
     pub const TYPE_CONFIGURE_ENDPOINT: u32 = 12 << 10;
     pub const TYPE_NOOP: u32 = 23 << 10;
@@ -110,6 +398,152 @@ impl Trb {
 #[derive(Clone, Copy)]
 pub struct ErstEntry { pub base_addr: u64, pub size: u16, pub rsvd: u16, pub rsvd2: u32 }
 
+// PORTSC bits that survive a write unchanged (PP, PIC, wake enables) and the
+// write-1-to-clear change bits (CSC, PEC, WRC, OCC, PRC, PLC, CEC)
+const PORTSC_PRESERVE: u32 = (1 << 9) | (3 << 14) | (7 << 25);
+const PORTSC_CHANGE: u32 = 0x7F << 17;
+const PORTSC_CSC: u32 = 1 << 17;
+
+// Endpoint context types
+const EP_TYPE_BULK_OUT: u32 = 2;
+const EP_TYPE_BULK_IN: u32 = 6;
+const EP_TYPE_INTERRUPT_IN: u32 = 7;
+
+const CC_STALL: u8 = 6;
+const BULK_TIMEOUT_MS: u64 = 5000;
+
+/// Producer side of one endpoint's transfer ring
+struct TransferRing { ring: *mut Trb, phys: u64, index: usize, cycle: bool }
+
+impl TransferRing {
+    unsafe fn new() -> Result<Self, &'static str> {
+        let ring = XhciController::alloc_aligned(4096, 64)? as *mut Trb;
+        let phys = crate::memory::virt_to_phys(ring as u64).unwrap();
+        let link = &mut *ring.add(255);
+        link.parameter = phys;
+        link.control = Trb::TYPE_LINK | Trb::ENT_BIT;
+        XhciController::clflush_range(ring as u64, 4096);
+        Ok(Self { ring, phys, index: 0, cycle: true })
+    }
+
+    unsafe fn push(&mut self, trb: Trb) {
+        XhciController::push_ring_trb(self.ring, self.phys, &mut self.index, &mut self.cycle, trb);
+    }
+
+    /// Enqueue pointer with the cycle state, as Set TR Dequeue Pointer wants it
+    fn dequeue_ptr(&self) -> u64 {
+        (self.phys + self.index as u64 * 16) | (self.cycle as u64)
+    }
+
+    unsafe fn free(&self) { XhciController::free_aligned(self.ring as *mut u8, 4096, 64); }
+}
+
+/// Bulk pipes of a mass-storage interface
+struct BulkPipes { interface: u8, bulk_in: TransferRing, in_dci: u8, in_addr: u8, bulk_out: TransferRing, out_dci: u8, out_addr: u8 }
+
+#[derive(Clone, Copy, Debug)]
+struct EndpointDesc { address: u8, max_packet: u16 }
+
+impl EndpointDesc {
+    fn dci(&self) -> u8 { (self.address & 0x0F) * 2 + (self.address >> 7) }
+}
+
+/// A Bulk-Only Transport SCSI interface (class 0x08, subclass 0x06, protocol 0x50)
+#[derive(Clone, Copy, Debug)]
+struct MassStorageInterface { number: u8, bulk_in: EndpointDesc, bulk_out: EndpointDesc }
+
+impl MassStorageInterface {
+    fn find(cfg: &[u8]) -> Option<Self> {
+        let total = ((cfg[2] as usize) | ((cfg[3] as usize) << 8)).min(cfg.len());
+        let mut found: Option<(u8, Option<EndpointDesc>, Option<EndpointDesc>)> = None;
+        let mut in_msc = false;
+        let mut idx = 0;
+        while idx + 1 < total {
+            let len = cfg[idx] as usize;
+            if len == 0 || idx + len > total { break; }
+            match cfg[idx + 1] {
+                4 if len >= 9 => {
+                    if let Some((number, Some(bulk_in), Some(bulk_out))) = found { return Some(Self { number, bulk_in, bulk_out }); }
+                    in_msc = cfg[idx + 5] == 0x08 && cfg[idx + 6] == 0x06 && cfg[idx + 7] == 0x50;
+                    found = if in_msc { Some((cfg[idx + 2], None, None)) } else { None };
+                }
+                5 if len >= 7 && in_msc && cfg[idx + 3] & 3 == 2 => {
+                    let ep = EndpointDesc { address: cfg[idx + 2], max_packet: ((cfg[idx + 4] as u16) | ((cfg[idx + 5] as u16) << 8)) & 0x07FF };
+                    if let Some((_, bulk_in, bulk_out)) = found.as_mut() {
+                        if ep.address & 0x80 != 0 { bulk_in.get_or_insert(ep); } else { bulk_out.get_or_insert(ep); }
+                    }
+                }
+                _ => {}
+            }
+            idx += len;
+        }
+        match found { Some((number, Some(bulk_in), Some(bulk_out))) => Some(Self { number, bulk_in, bulk_out }), _ => None }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BulkError {
+    /// The device stalled the endpoint; the ring has been reset and the halt cleared
+    Stall,
+    Timeout,
+    Failed(u8),
+    NoDevice,
+}
+
+// DMA buffer each HID slot's interrupt transfers land in
+const REPORT_BUF_LEN: usize = 64;
+
+/// What the interrupt endpoint of a slot delivers
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum HidDevice { None, Mouse, Keyboard, Tablet }
+
+impl HidDevice {
+    /// HID interface protocol: 1 = boot keyboard, 2 = boot mouse. Anything
+    /// else with an interrupt IN endpoint is still read as a mouse.
+    fn from_protocol(protocol: u8) -> Self {
+        if protocol == 1 { HidDevice::Keyboard } else { HidDevice::Mouse }
+    }
+
+    fn transfer_len(self) -> u32 {
+        if self == HidDevice::Keyboard { crate::usb_keyboard::REPORT_LEN as u32 } else { REPORT_BUF_LEN as u32 }
+    }
+}
+
+/// Scans a HID report descriptor for the Input item carrying the X usage and
+/// says whether it is absolute. Only short items are walked; this is enough to
+/// tell a tablet from a mouse, not a general report parser.
+fn pointer_is_absolute(desc: &[u8]) -> bool {
+    const ITEM_USAGE: u8 = 0x08;
+    const ITEM_INPUT: u8 = 0x80;
+    const USAGE_X: u32 = 0x30;
+    // Input flag bit 2: 0 = absolute, 1 = relative
+    const INPUT_RELATIVE: u32 = 0x04;
+
+    let mut saw_x = false;
+    let mut i = 0;
+    while i < desc.len() {
+        let prefix = desc[i];
+        if prefix == 0xFE {
+            // Long item: data size, tag, data
+            i += 3 + desc.get(i + 1).copied().unwrap_or(0) as usize;
+            continue;
+        }
+        let size = match prefix & 0x03 { 3 => 4, n => n as usize };
+        if i + 1 + size > desc.len() { break; }
+        let data = desc[i + 1..i + 1 + size].iter().rev().fold(0u32, |acc, &b| (acc << 8) | b as u32);
+        match prefix & 0xFC {
+            // Extended usages carry the Generic Desktop page in the high half
+            ITEM_USAGE if data & 0xFFFF == USAGE_X && (size < 4 || data >> 16 == 1) => saw_x = true,
+            ITEM_INPUT if saw_x => return data & INPUT_RELATIVE == 0,
+            // Local usages end with every main item
+            p if p & 0x0C == 0 => saw_x = false,
+            _ => {}
+        }
+        i += 1 + size;
+    }
+    false
+}
+
 #[repr(C)] struct SlotContext { info1: u32, info2: u32, ttd: u32, state: u32, rsvd: [u32; 4] }
 #[repr(C)] struct EndpointContext { info1: u32, info2: u32, tr_dequeue: u64, avg_trb_len: u32, rsvd: [u32; 3] }
 
@@ -140,12 +574,17 @@ pub struct XhciController {
     ep1_dci: Vec<u8>, 
     
     ep1_halted: Vec<bool>,
-    mouse_pending: Vec<bool>, 
+    report_pending: Vec<bool>, 
     
-    mouse_buf_virt: Vec<*mut u8>,
-    mouse_buf_phys: Vec<u64>,
-
-    cmd_index: usize, cmd_cycle: bool, event_index: usize, event_cycle: bool, ctx_size: usize,
+    report_buf_virt: Vec<*mut u8>,
+    report_buf_phys: Vec<u64>,
+    hid_kind: Vec<HidDevice>,
+    keyboards: Vec<crate::usb_keyboard::BootKeyboard>,
+    // Root port each enabled slot sits on (0 = slot unused)
+    slot_port: Vec<u8>,
+    storage: Vec<Option<BulkPipes>>,
+
+    cmd_index: usize, cmd_cycle: bool, ctx_size: usize,
 }
 
 unsafe impl Send for XhciController {}
@@ -194,9 +633,9 @@ impl XhciController {
         let mut ep1_configured = Vec::with_capacity(max_slots);
         let mut ep1_dci = Vec::with_capacity(max_slots);
         let mut ep1_halted = Vec::with_capacity(max_slots);
-        let mut mouse_pending = Vec::with_capacity(max_slots);
-        let mut mouse_buf_virt = Vec::with_capacity(max_slots);
-        let mut mouse_buf_phys = Vec::with_capacity(max_slots);
+        let mut report_pending = Vec::with_capacity(max_slots);
+        let mut report_buf_virt = Vec::with_capacity(max_slots);
+        let mut report_buf_phys = Vec::with_capacity(max_slots);
 
         for _ in 0..max_slots { 
             ep0_rings.push(core::ptr::null_mut()); 
@@ -211,12 +650,9 @@ impl XhciController {
             ep1_configured.push(false);
             ep1_dci.push(0);
             ep1_halted.push(false);
-            mouse_pending.push(false);
-            
-            let virt = Self::alloc_aligned(128, 64)?;
-            let phys = crate::memory::virt_to_phys(virt as u64).unwrap();
-            mouse_buf_virt.push(virt);
-            mouse_buf_phys.push(phys);
+            report_pending.push(false);
+            report_buf_virt.push(core::ptr::null_mut());
+            report_buf_phys.push(0);
         }
 
         Ok(Self {
@@ -225,8 +661,12 @@ impl XhciController {
             scratchpad_array: core::ptr::null_mut(), scratchpad_pages: Vec::new(),
             ep0_rings, ep0_rings_phys, ep0_cycles, ep0_indices, 
             ep1_rings, ep1_rings_phys, ep1_cycles, ep1_indices, ep1_configured, ep1_dci,
-            ep1_halted, mouse_pending, mouse_buf_virt, mouse_buf_phys,
-            cmd_index: 0, cmd_cycle: true, event_index: 0, event_cycle: true,
+            ep1_halted, report_pending, report_buf_virt, report_buf_phys,
+            hid_kind: alloc::vec![HidDevice::None; max_slots],
+            keyboards: alloc::vec![Default::default(); max_slots],
+            slot_port: alloc::vec![0; max_slots],
+            storage: (0..max_slots).map(|_| None).collect(),
+            cmd_index: 0, cmd_cycle: true,
             ctx_size: caps.context_size(),
         })
     }
@@ -252,6 +692,41 @@ impl XhciController {
         }
     }
 
+    /// Queues `trb` on the command ring and rings the host controller's doorbell.
+    unsafe fn push_command(&mut self, cmd: Trb) {
+        let trb = &mut *self.cmd_ring.add(self.cmd_index);
+        trb.parameter = cmd.parameter; trb.status = cmd.status;
+        fence(Ordering::SeqCst);
+        trb.control = cmd.control | (if self.cmd_cycle { Trb::CYCLE_BIT } else { 0 });
+        fence(Ordering::SeqCst);
+        Self::clflush(trb as *const _ as *const u8);
+        self.cmd_index = (self.cmd_index + 1) % 256;
+        if self.cmd_index == 0 {
+            let link = &mut *self.cmd_ring.add(255);
+            link.control = Trb::TYPE_LINK | Trb::ENT_BIT | (if self.cmd_cycle { Trb::CYCLE_BIT } else { 0 });
+            Self::clflush(link as *const _ as *const u8);
+            self.cmd_cycle = !self.cmd_cycle;
+        }
+        self.doorbell.ring(0, 0);
+    }
+
+    /// Runs one command and waits for its completion. Returns the slot the completion names.
+    unsafe fn run_command(&mut self, cmd: Trb) -> Result<u8, &'static str> {
+        // A completion left over from an earlier timed-out command must not answer this one
+        x86_64::instructions::interrupts::without_interrupts(|| {
+            if let Some(ev) = EVENTS.lock().as_mut() { ev.commands.clear(); }
+        });
+        self.push_command(cmd);
+        match wait_event(CMD_TIMEOUT_MS, |ev| ev.commands.pop()) {
+            Some(c) if c.code == CC_SUCCESS => Ok(c.slot),
+            Some(c) => {
+                crate::serial_println!("[USB] EVENT ERROR: Code {} Slot {}", c.code, c.slot);
+                Err("Cmd Error")
+            }
+            None => Err("Cmd Timeout"),
+        }
+    }
+
     unsafe fn push_ep0_trb(&mut self, slot_id: usize, trb: Trb) {
         Self::push_ring_trb(self.ep0_rings[slot_id], self.ep0_rings_phys[slot_id], &mut self.ep0_indices[slot_id], &mut self.ep0_cycles[slot_id], trb);
     }
@@ -271,16 +746,8 @@ impl XhciController {
                 crate::serial_println!("[USB] Requesting BIOS Handoff...");
                 if (cap_val & (1 << 16)) != 0 {
                     write_volatile(cap_ptr, cap_val | (1 << 24));
-                    let mut t = 0;
-                    while t < 1000000 {
-                         if (read_volatile(cap_ptr) & (1 << 16)) == 0 { break; } 
-                         core::hint::spin_loop(); t += 1;
-                    }
-                    t = 0;
-                    while (read_volatile(cap_ptr) & (1 << 24)) == 0 { 
-                        if t > 5000000 { break; }
-                        core::hint::spin_loop(); t += 1;
-                    }
+                    crate::watchdog::spin_until("XHCI", "BIOS release", HANDOFF_TIMEOUT_MS, || (read_volatile(cap_ptr) & (1 << 16)) == 0);
+                    crate::watchdog::spin_until("XHCI", "OS ownership", HANDOFF_TIMEOUT_MS, || (read_volatile(cap_ptr) & (1 << 24)) != 0);
                     crate::serial_println!("[USB] BIOS Released xHCI controller.");
                 } else { write_volatile(cap_ptr, cap_val | (1 << 24)); }
                 break;
@@ -317,14 +784,14 @@ impl XhciController {
             let mut cmd = self.op.read_usbcmd();
             cmd &= !CMD_RUN; 
             self.op.write_usbcmd(cmd);
-            for _ in 0..100000 { if (self.op.read_usbsts() & STS_HALT) != 0 { break; } core::hint::spin_loop(); }
+            crate::watchdog::spin_until("XHCI", "halt", HALT_TIMEOUT_MS, || (self.op.read_usbsts() & STS_HALT) != 0);
 
             // 🚨 2. SECURE RESET: Wipe all hardware states
             let mut cmd = self.op.read_usbcmd();
             cmd |= CMD_HCRST;
             self.op.write_usbcmd(cmd);
-            for _ in 0..100000 { if (self.op.read_usbcmd() & CMD_HCRST) == 0 { break; } core::hint::spin_loop(); }
-            for _ in 0..100000 { if (self.op.read_usbsts() & STS_CNR) == 0 { break; } core::hint::spin_loop(); }
+            crate::watchdog::spin_until("XHCI", "reset", RESET_TIMEOUT_MS, || (self.op.read_usbcmd() & CMD_HCRST) == 0);
+            crate::watchdog::spin_until("XHCI", "controller ready", RESET_TIMEOUT_MS, || (self.op.read_usbsts() & STS_CNR) == 0);
             
             self.init_scratchpads()?;
             
@@ -342,6 +809,15 @@ impl XhciController {
             let ir0 = &mut self.runtime.ir[0];
             ir0.erstba = crate::memory::virt_to_phys(self.erst as u64).unwrap();
             ir0.erstsz = 1; ir0.erdp = self.event_ring_phys | 8; ir0.iman = 2; ir0.imod = 4000;
+            let ir0 = ir0 as *mut InterrupterRegisters;
+            x86_64::instructions::interrupts::without_interrupts(|| {
+                *EVENTS.lock() = Some(EventRing {
+                    ring: self.event_ring, phys: self.event_ring_phys, index: 0, cycle: true,
+                    ir: ir0, usbsts: &mut self.op.usbsts as *mut u32,
+                    commands: EventQueue::new(), transfers: EventQueue::new(), port_changes: EventQueue::new(),
+                    hid_dci: [0; 256],
+                });
+            });
 
             let phys_dcbaa = crate::memory::virt_to_phys(self.dcbaa as u64).unwrap();
             self.op.write_dcbaap(phys_dcbaa);
@@ -355,106 +831,148 @@ impl XhciController {
             run |= CMD_RUN | CMD_INTE;
             self.op.write_usbcmd(run);
             
-            let mut started = false;
-            for _ in 0..100_000_000 {
-                if (self.op.read_usbsts() & STS_HALT) == 0 { started = true; break; }
-                core::hint::spin_loop();
-            }
+            let started = crate::watchdog::spin_until("XHCI", "run", HALT_TIMEOUT_MS, || (self.op.read_usbsts() & STS_HALT) == 0);
             if !started { return Err("Ctlr Halted"); }
 
-            let trb = &mut *self.cmd_ring.add(self.cmd_index);
-            trb.parameter = 0; trb.status = 0;
-            fence(Ordering::SeqCst);
-            trb.control = Trb::TYPE_NOOP | Trb::IOC_BIT | (if self.cmd_cycle { Trb::CYCLE_BIT } else { 0 });
-            fence(Ordering::SeqCst);
-            Self::clflush(trb as *const _ as *const u8);
-            self.cmd_index = (self.cmd_index + 1) % 256;
-            if self.cmd_index == 0 {
-                let link = &mut *self.cmd_ring.add(255);
-                link.control = Trb::TYPE_LINK | Trb::ENT_BIT | (if self.cmd_cycle { Trb::CYCLE_BIT } else { 0 });
-                Self::clflush(link as *const _ as *const u8);
-                self.cmd_cycle = !self.cmd_cycle;
-            }
-            self.doorbell.ring(0, 0);
-            
-            let mut noop_ok = false;
-            for _ in 0..200_000 { if let Some(_) = self.check_event_sync() { noop_ok = true; break; } core::hint::spin_loop(); }
-            if noop_ok { crate::serial_println!("[USB] NoOp Command Successful."); } 
+            let mut noop = Trb::new();
+            noop.control = Trb::TYPE_NOOP | Trb::IOC_BIT;
+            if self.run_command(noop).is_ok() { crate::serial_println!("[USB] NoOp Command Successful."); } 
             else { crate::serial_println!("[USB] WARNING: NoOp Command Failed."); }
         }
         Ok(())
     }
 
     pub fn enable_slot(&mut self) -> Result<u8, &'static str> {
+        let mut trb = Trb::new();
+        trb.control = Trb::TYPE_ENABLE_SLOT;
+        unsafe { self.run_command(trb) }
+    }
+
+    pub fn disable_slot(&mut self, slot_id: u8) -> Result<(), &'static str> {
+        let mut trb = Trb::new();
+        trb.control = Trb::TYPE_DISABLE_SLOT | (slot_id as u32) << 24;
+        unsafe { self.run_command(trb).map(|_| ()) }
+    }
+
+    unsafe fn free_aligned(ptr: *mut u8, size: usize, align_val: usize) {
+        if ptr.is_null() { return; }
+        alloc::alloc::dealloc(ptr, Layout::from_size_align_unchecked(size, align_val));
+    }
+
+    /// Acknowledges a change the controller flagged on root port `port` and
+    /// tears down the slot of a device that went away. True if a new device is
+    /// connected there; the caller lets it settle, then runs reset_port and
+    /// attach_port.
+    pub fn port_changed(&mut self, port: u8) -> bool {
+        let idx = (port as usize).wrapping_sub(1) * 4;
+        if port == 0 || idx >= self.op.portregs.len() { return false; }
+
         unsafe {
-            let trb = &mut *self.cmd_ring.add(self.cmd_index);
-            trb.parameter = 0; trb.status = 0;
-            fence(Ordering::SeqCst);
-            trb.control = Trb::TYPE_ENABLE_SLOT | (if self.cmd_cycle { Trb::CYCLE_BIT } else { 0 });
-            fence(Ordering::SeqCst);
-            Self::clflush(trb as *const _ as *const u8);
-            self.cmd_index = (self.cmd_index + 1) % 256;
-            if self.cmd_index == 0 {
-                let link = &mut *self.cmd_ring.add(255);
-                link.control = Trb::TYPE_LINK | Trb::ENT_BIT | (if self.cmd_cycle { Trb::CYCLE_BIT } else { 0 });
-                Self::clflush(link as *const _ as *const u8);
-                self.cmd_cycle = !self.cmd_cycle;
+            // Acknowledge the change bits, leaving PED (write-1-disables) and PR alone
+            let portsc = read_volatile(&self.op.portregs[idx]);
+            write_volatile(&mut self.op.portregs[idx], (portsc & PORTSC_PRESERVE) | (portsc & PORTSC_CHANGE));
+
+            let mut attached = self.slot_port.iter().position(|&p| p == port);
+            let connected = portsc & 1 != 0;
+            // Unplugged and replugged before we looked: the old slot is gone too
+            if let (true, Some(slot)) = (connected, attached) {
+                if portsc & PORTSC_CSC != 0 { self.detach_slot(slot as u8); attached = None; }
+            }
+            match (connected, attached) {
+                (true, None) => true,
+                (false, Some(slot)) => { self.detach_slot(slot as u8); false }
+                _ => false,
             }
-            self.doorbell.ring(0, 0); 
-            for _ in 0..500_000 { if let Some(slot) = self.check_event_sync() { return Ok(slot); } core::hint::spin_loop(); }
         }
-        Err("Cmd Timeout")
     }
 
-    // 🚨 FIX: Strict Synchronous Event Checker that ignores background polling noise
-    unsafe fn check_event_sync(&mut self) -> Option<u8> {
-        for _ in 0..16 { 
-            let trb_ptr = self.event_ring.add(self.event_index);
-            Self::clflush(trb_ptr as *const u8); 
-            let trb = read_volatile(trb_ptr);
-            if ((trb.control & 1) != 0) != self.event_cycle { return None; }
-            self.event_index = (self.event_index + 1) % 256;
-            if self.event_index == 0 { self.event_cycle = !self.event_cycle; }
-            
-            let ir0 = &mut self.runtime.ir[0];
-            let phys = self.event_ring_phys + (self.event_index as u64 * 16);
-            ir0.erdp = phys | 8; ir0.iman = 3;
-            
-            let type_ = (trb.control >> 10) & 0x3F;
-            let code = (trb.status >> 24) & 0xFF;
-            let slot = ((trb.control >> 24) & 0xFF) as u8;
-
-            if type_ == 33 { // Command Completion Event
-                if code == 1 || code == 0 { return Some(slot); }
-                else { 
-                    crate::serial_println!("[USB] EVENT ERROR: Code {} Slot {}", code, slot);
-                    return None; 
-                }
-            } else if type_ == 32 {
-                // Background transfer event arrived during init. Drop it safely.
-                continue;
+    /// Stops using `slot_id` after its device went away and gives its rings,
+    /// contexts and report buffer back.
+    unsafe fn detach_slot(&mut self, slot_id: u8) {
+        let s = slot_id as usize;
+        crate::serial_println!("[USB] --- DEVICE ON PORT {} REMOVED (Slot {}) ---", self.slot_port[s], slot_id);
+
+        // Nothing more may be queued on the dead rings
+        self.ep1_configured[s] = false;
+        self.report_pending[s] = false;
+        self.ep1_halted[s] = false;
+        match self.hid_kind[s] {
+            HidDevice::Keyboard => self.keyboards[s].release_all(),
+            HidDevice::Mouse | HidDevice::Tablet => crate::mouse::update_from_usb(0, 0, 0, 0),
+            HidDevice::None => {}
+        }
+        self.hid_kind[s] = HidDevice::None;
+        self.slot_port[s] = 0;
+        set_hid_endpoint(slot_id, 0);
+        // The block layer sees NoDevice from here on
+        let storage = self.storage[s].take();
+
+        if let Err(e) = self.disable_slot(slot_id) {
+            crate::serial_println!("[USB] Disable Slot {} failed: {}", slot_id, e);
+        }
+        discard_transfers(slot_id, 0);
+        if let Some(pipes) = storage { pipes.bulk_in.free(); pipes.bulk_out.free(); }
+
+        Self::free_aligned(self.ep0_rings[s] as *mut u8, 4096, 64);
+        Self::free_aligned(self.ep1_rings[s] as *mut u8, 4096, 64);
+        Self::free_aligned(self.report_buf_virt[s], REPORT_BUF_LEN, 64);
+        self.ep0_rings[s] = core::ptr::null_mut();
+        self.ep1_rings[s] = core::ptr::null_mut();
+        self.report_buf_virt[s] = core::ptr::null_mut();
+        self.report_buf_phys[s] = 0;
+
+        let out_ctx_phys = *self.dcbaa.add(s);
+        if out_ctx_phys != 0 {
+            if let Some(out_ctx_virt) = crate::memory::phys_to_virt(out_ctx_phys) {
+                Self::free_aligned(out_ctx_virt as *mut u8, self.ctx_size * 33, 64);
             }
+            *self.dcbaa.add(s) = 0;
+            Self::clflush(self.dcbaa.add(s) as *const u8);
         }
-        None
     }
 
-    pub fn poll_all_mice(&mut self) {
+    /// Hands finished interrupt-endpoint transfers to the mouse and keyboard
+    /// drivers and re-arms every idle HID endpoint. EP0 events are left for
+    /// wait_control.
+    pub fn poll_hid_devices(&mut self) {
+        let now = crate::time::monotonic_ms();
         unsafe {
-            for _ in 0..32 { 
-                if let Some((event_slot, code)) = self.check_event_async() {
-                    if event_slot == 255 { continue; } 
+            loop {
+                let event = x86_64::instructions::interrupts::without_interrupts(|| {
+                    let mut guard = EVENTS.lock();
+                    let events = guard.as_mut()?;
+                    events.drain();
+                    let hid_dci = &events.hid_dci;
+                    events.transfers.take(|t| EventRing::is_hid_report(hid_dci, t))
+                });
+                if let Some(TransferEvent { slot: event_slot, endpoint, code, .. }) = event {
                     let s = event_slot as usize;
-                    if s < self.mouse_pending.len() {
-                        self.mouse_pending[s] = false;
+                    // Only the slot's own HID endpoint reports into its buffer
+                    if s < self.report_pending.len() && endpoint == self.ep1_dci[s] && self.hid_kind[s] != HidDevice::None {
+                        self.report_pending[s] = false;
                         
-                        if code != 1 && code != 13 && code != 0 {
+                        if code != CC_SUCCESS && code != CC_SHORT_PACKET {
                             if !self.ep1_halted[s] {
                                 crate::serial_println!("[USB] Endpoint Halted on Slot {} (Code {}). Halting Polling Ring.", s, code);
                                 self.ep1_halted[s] = true;
                             }
                         } else {
-                            let buffer = self.mouse_buf_virt[s];
-                            Self::clflush(buffer);
+                            let buffer = self.report_buf_virt[s];
+                            Self::clflush_range(buffer as u64, REPORT_BUF_LEN);
+
+                            if self.hid_kind[s] == HidDevice::Keyboard {
+                                let report = core::slice::from_raw_parts(buffer, crate::usb_keyboard::REPORT_LEN);
+                                self.keyboards[s].handle_report(report, now);
+                                continue;
+                            }
+                            if self.hid_kind[s] == HidDevice::Tablet {
+                                // Buttons, X and Y as 0..=32767 little-endian, wheel
+                                let r = core::slice::from_raw_parts(buffer, 6);
+                                let x = u16::from_le_bytes([r[1], r[2]]);
+                                let y = u16::from_le_bytes([r[3], r[4]]);
+                                crate::mouse::update_absolute(x, y, r[5] as i8, r[0]);
+                                continue;
+                            }
                             
                             let b0 = *buffer.add(0);
                             let b1 = *buffer.add(1);
@@ -463,14 +981,16 @@ impl XhciController {
                             let b4 = *buffer.add(4);
                             let b5 = *buffer.add(5);
                             
-                            if b0 != 0 || b1 != 0 || b2 != 0 || b3 != 0 {
+                            if b0 != 0 || b1 != 0 || b2 != 0 || b3 != 0 || b4 != 0 {
                                 crate::serial_println!("[USB] HID [{}]: {:02x} {:02x} {:02x} {:02x} {:02x} {:02x}", s, b0, b1, b2, b3, b4, b5);
                                 
                                 let buttons = b1;
                                 let dx = b2 as i8; 
                                 let dy = b3 as i8;
+                                // Wheel notches follow Y; mice without a wheel send 0
+                                let wheel = b4 as i8;
                                 
-                                crate::mouse::update_from_usb(dx, dy, buttons);
+                                crate::mouse::update_from_usb(dx, dy, wheel, buttons);
                             }
                         }
                     }
@@ -479,15 +999,17 @@ impl XhciController {
                 }
             }
 
+            for keyboard in self.keyboards.iter_mut() { keyboard.tick(now); }
+
             for s_id in 1..self.ep1_configured.len() {
-                if self.ep1_configured[s_id] && !self.mouse_pending[s_id] && !self.ep1_halted[s_id] {
+                if self.ep1_configured[s_id] && !self.report_pending[s_id] && !self.ep1_halted[s_id] {
                     let ring = self.ep1_rings[s_id];
                     if ring.is_null() { continue; } 
 
-                    let phys_buf = self.mouse_buf_phys[s_id];
+                    let phys_buf = self.report_buf_phys[s_id];
                     let mut trb = Trb::new();
                     trb.parameter = phys_buf;
-                    trb.status = 128; 
+                    trb.status = self.hid_kind[s_id].transfer_len();
                     trb.control = Trb::TYPE_NORMAL | Trb::IOC_BIT | Trb::ISP_BIT; 
                     
                     self.push_ep1_trb(s_id, trb);
@@ -495,45 +1017,34 @@ impl XhciController {
                     let dci = self.ep1_dci[s_id] as u32;
                     self.doorbell.ring(s_id, dci); 
                     
-                    self.mouse_pending[s_id] = true;
+                    self.report_pending[s_id] = true;
                 }
             }
         }
     }
 
-    unsafe fn check_event_async(&mut self) -> Option<(u8, u8)> {
-        let trb_ptr = self.event_ring.add(self.event_index);
-        let trb = read_volatile(trb_ptr);
-
-        if ((trb.control & 1) != 0) != self.event_cycle { return None; }
-
-        self.event_index = (self.event_index + 1) % 256;
-        if self.event_index == 0 { self.event_cycle = !self.event_cycle; }
-
-        let ir0 = &mut self.runtime.ir[0];
-        let phys = self.event_ring_phys + (self.event_index as u64 * 16);
-        ir0.erdp = phys | 8; ir0.iman = 3;
+    pub fn get_descriptor(&mut self, slot_id: u8, desc_type: u8, desc_index: u8, read_len: u16) -> Result<[u8; 128], &'static str> {
+        let param_low = 0x0680 | ((desc_index as u64) << 16) | ((desc_type as u64) << 24);
+        self.control_in(slot_id, param_low, read_len)
+    }
 
-        let type_ = (trb.control >> 10) & 0x3F;
-        if type_ == 32 || type_ == 33 { 
-            let code = (trb.status >> 24) & 0xFF;
-            let slot = ((trb.control >> 24) & 0xFF) as u8;
-            return Some((slot, code as u8));
-        }
-        
-        Some((255, 255))
+    /// HID class descriptor (0x22 = report descriptor) of `interface`; these
+    /// are addressed to the interface rather than the device.
+    pub fn get_hid_descriptor(&mut self, slot_id: u8, interface: u8, desc_type: u8, read_len: u16) -> Result<[u8; 128], &'static str> {
+        let param_low = 0x0681 | ((desc_type as u64) << 24) | ((interface as u64) << 32);
+        self.control_in(slot_id, param_low, read_len)
     }
 
-    pub fn get_descriptor(&mut self, slot_id: u8, desc_type: u8, desc_index: u8, read_len: u16) -> Result<[u8; 128], &'static str> {
+    /// Runs a control read; `setup_packet` is the setup packet without wLength.
+    fn control_in(&mut self, slot_id: u8, setup_packet: u64, read_len: u16) -> Result<[u8; 128], &'static str> {
         unsafe {
             let s_id = slot_id as usize;
             let buffer = Self::alloc_aligned(read_len as usize, 64)? as *mut u8;
             let phys_buf = crate::memory::virt_to_phys(buffer as u64).unwrap();
             
             let mut setup = Trb::new(); 
-            let param_low = 0x0680 | ((desc_index as u64) << 16) | ((desc_type as u64) << 24);
             let param_high = (read_len as u64) << 48;
-            setup.parameter = param_high | param_low; 
+            setup.parameter = param_high | setup_packet; 
             setup.status = 8; 
             setup.control = Trb::TYPE_SETUP | Trb::IDT_BIT;
             self.push_ep0_trb(s_id, setup);
@@ -551,59 +1062,39 @@ impl XhciController {
             self.push_ep0_trb(s_id, status);
             
             self.doorbell.ring(s_id, 1); 
-            
-            for _ in 0..2_000_000 {
-                if let Some(id) = self.check_event_sync() {
-                    if id == 0 { return Err("Desc Fail"); }
-                    
-                    let mut result = [0u8; 128];
-                    let copy_len = core::cmp::min(read_len as usize, 128);
-                    for i in 0..copy_len { result[i] = *buffer.add(i); }
-                    return Ok(result);
-                }
-                core::hint::spin_loop();
-            }
+            wait_control(slot_id)?;
+
+            let mut result = [0u8; 128];
+            let copy_len = core::cmp::min(read_len as usize, 128);
+            Self::clflush_range(buffer as u64, copy_len);
+            for i in 0..copy_len { result[i] = *buffer.add(i); }
+            Ok(result)
         }
-        Err("Desc Timeout")
     }
 
-    pub fn set_configuration(&mut self, slot_id: u8) -> Result<(), &'static str> {
+    /// Runs a control request without a data stage; `setup_packet` is the 8-byte setup packet.
+    fn control_no_data(&mut self, slot_id: u8, setup_packet: u64) -> Result<(), &'static str> {
         unsafe {
             let s_id = slot_id as usize;
-            let mut setup = Trb::new(); setup.parameter = 0x0000_0000_0001_0900; setup.status = 8; setup.control = Trb::TYPE_SETUP | Trb::IDT_BIT;
+            let mut setup = Trb::new(); setup.parameter = setup_packet; setup.status = 8; setup.control = Trb::TYPE_SETUP | Trb::IDT_BIT;
             self.push_ep0_trb(s_id, setup);
             let mut status = Trb::new(); status.parameter = 0; status.status = 0; status.control = Trb::TYPE_STATUS | Trb::IOC_BIT | (1 << 16);
             self.push_ep0_trb(s_id, status);
             self.doorbell.ring(s_id, 1);
-            for _ in 0..2_000_000 { if let Some(id) = self.check_event_sync() { if id == slot_id { return Ok(()); } } core::hint::spin_loop(); }
+            wait_control(slot_id).map(|_| ())
         }
-        Err("Cfg Timeout")
     }
 
-    pub fn set_idle(&mut self, slot_id: u8) -> Result<(), &'static str> {
-        unsafe {
-            let s_id = slot_id as usize;
-            let mut setup = Trb::new(); setup.parameter = 0x0000_0000_0000_0A21; setup.status = 8; setup.control = Trb::TYPE_SETUP | Trb::IDT_BIT;
-            self.push_ep0_trb(s_id, setup);
-            let mut status = Trb::new(); status.parameter = 0; status.status = 0; status.control = Trb::TYPE_STATUS | Trb::IOC_BIT | (1 << 16);
-            self.push_ep0_trb(s_id, status);
-            self.doorbell.ring(s_id, 1);
-            for _ in 0..2_000_000 { if let Some(id) = self.check_event_sync() { if id == slot_id { return Ok(()); } } core::hint::spin_loop(); }
-        }
-        Err("Idle Timeout")
+    pub fn set_configuration(&mut self, slot_id: u8) -> Result<(), &'static str> {
+        self.control_no_data(slot_id, 0x0000_0000_0001_0900)
     }
 
-    pub fn set_boot_protocol(&mut self, slot_id: u8) -> Result<(), &'static str> {
-        unsafe {
-            let s_id = slot_id as usize;
-            let mut setup = Trb::new(); setup.parameter = 0x0000_0000_0000_0B21; setup.status = 8; setup.control = Trb::TYPE_SETUP | Trb::IDT_BIT;
-            self.push_ep0_trb(s_id, setup);
-            let mut status = Trb::new(); status.parameter = 0; status.status = 0; status.control = Trb::TYPE_STATUS | Trb::IOC_BIT | (1 << 16);
-            self.push_ep0_trb(s_id, status);
-            self.doorbell.ring(s_id, 1);
-            for _ in 0..2_000_000 { if let Some(id) = self.check_event_sync() { if id == slot_id { return Ok(()); } } core::hint::spin_loop(); }
-        }
-        Err("Proto Timeout")
+    pub fn set_idle(&mut self, slot_id: u8, interface: u8) -> Result<(), &'static str> {
+        self.control_no_data(slot_id, 0x0000_0000_0000_0A21 | ((interface as u64) << 32))
+    }
+
+    pub fn set_boot_protocol(&mut self, slot_id: u8, interface: u8) -> Result<(), &'static str> {
+        self.control_no_data(slot_id, 0x0000_0000_0000_0B21 | ((interface as u64) << 32))
     }
 
     pub fn configure_interrupt_endpoint(&mut self, slot_id: u8, max_packet: u16, interval: u8, dci: u8) -> Result<(), &'static str> {
@@ -622,67 +1113,166 @@ impl XhciController {
             self.ep1_rings_phys[s_id] = ep1_ring_phys; 
             self.ep1_cycles[s_id] = true;
             self.ep1_indices[s_id] = 0;
-            self.ep1_dci[s_id] = dci; 
-
-            let in_ctx_mem = Self::alloc_aligned(self.ctx_size * 34, 64)?;
-            let phys_in = crate::memory::virt_to_phys(in_ctx_mem as u64).unwrap();
-            
-            let icc_ptr = in_ctx_mem as *mut u32; 
-            *icc_ptr.add(0) = 0; 
-            *icc_ptr.add(1) = (1 << dci) | (1 << 0); 
-
-            // Safely retrieve the existing Slot Context so we don't corrupt the Speed/Route maps!
-            let out_ctx_phys = *self.dcbaa.add(s_id);
-            let out_ctx_virt = crate::memory::phys_to_virt(out_ctx_phys).unwrap() as *const u8;
-            let out_slot_ptr = out_ctx_virt.add(self.ctx_size) as *const SlotContext;
-
-            let in_slot_ptr = in_ctx_mem.add(self.ctx_size) as *mut SlotContext;
-            (*in_slot_ptr).info1 = (*out_slot_ptr).info1;
-            (*in_slot_ptr).info2 = (*out_slot_ptr).info2;
-            (*in_slot_ptr).ttd   = (*out_slot_ptr).ttd;
-
-            let current_entries = ((*in_slot_ptr).info1 >> 27) & 0x1F;
-            let new_entries = if (dci as u32) > current_entries { dci as u32 } else { current_entries };
-            (*in_slot_ptr).info1 = ((*in_slot_ptr).info1 & !(0x1F << 27)) | (new_entries << 27);
+            self.ep1_dci[s_id] = dci;
+
+            // Each slot reports into its own buffer, so two HID devices never overwrite each other
+            if self.report_buf_virt[s_id].is_null() {
+                let buf = Self::alloc_aligned(REPORT_BUF_LEN, 64)?;
+                self.report_buf_virt[s_id] = buf;
+                self.report_buf_phys[s_id] = crate::memory::virt_to_phys(buf as u64).unwrap();
+            } 
+
+            self.add_endpoints(slot_id, &[(dci, EP_TYPE_INTERRUPT_IN, max_packet, interval, ep1_ring_phys)])?;
+            crate::serial_println!("[USB] EP Configured on Slot {}: DCI={} MaxPacket={} Interval={}", slot_id, dci, max_packet, interval);
+            Ok(())
+        }
+    }
 
+    /// Adds endpoints to `slot_id` with one Configure Endpoint command. Each entry
+    /// is (dci, endpoint type, max packet size, interval, transfer ring phys).
+    unsafe fn add_endpoints(&mut self, slot_id: u8, eps: &[(u8, u32, u16, u8, u64)]) -> Result<(), &'static str> {
+        let s_id = slot_id as usize;
+        let in_ctx_mem = Self::alloc_aligned(self.ctx_size * 34, 64)?;
+        let phys_in = crate::memory::virt_to_phys(in_ctx_mem as u64).unwrap();
+        
+        let icc_ptr = in_ctx_mem as *mut u32; 
+        *icc_ptr.add(0) = 0; 
+        *icc_ptr.add(1) = eps.iter().fold(1 << 0, |flags, ep| flags | (1 << ep.0)); 
+
+        // Safely retrieve the existing Slot Context so we don't corrupt the Speed/Route maps!
+        let out_ctx_phys = *self.dcbaa.add(s_id);
+        let out_ctx_virt = crate::memory::phys_to_virt(out_ctx_phys).unwrap() as *const u8;
+        let out_slot_ptr = out_ctx_virt.add(self.ctx_size) as *const SlotContext;
+
+        let in_slot_ptr = in_ctx_mem.add(self.ctx_size) as *mut SlotContext;
+        (*in_slot_ptr).info1 = (*out_slot_ptr).info1;
+        (*in_slot_ptr).info2 = (*out_slot_ptr).info2;
+        (*in_slot_ptr).ttd   = (*out_slot_ptr).ttd;
+
+        let current_entries = ((*in_slot_ptr).info1 >> 27) & 0x1F;
+        let highest_dci = eps.iter().map(|ep| ep.0 as u32).max().unwrap_or(0);
+        let new_entries = if highest_dci > current_entries { highest_dci } else { current_entries };
+        (*in_slot_ptr).info1 = ((*in_slot_ptr).info1 & !(0x1F << 27)) | (new_entries << 27);
+
+        for &(dci, ep_type, max_packet, interval, ring_phys) in eps {
             let ep_idx = dci as usize + 1;
             let ep_ptr = in_ctx_mem.add(self.ctx_size * ep_idx) as *mut EndpointContext; 
             
             (*ep_ptr).info1 = (interval as u32) << 16; 
-            (*ep_ptr).info2 = ((max_packet as u32) << 16) | (7 << 3) | (3 << 1); 
-            (*ep_ptr).tr_dequeue = ep1_ring_phys | 1; 
+            (*ep_ptr).info2 = ((max_packet as u32) << 16) | (ep_type << 3) | (3 << 1); 
+            (*ep_ptr).tr_dequeue = ring_phys | 1; 
             (*ep_ptr).avg_trb_len = max_packet as u32;
+        }
 
-            Self::clflush_range(in_ctx_mem as u64, self.ctx_size * 34);
+        Self::clflush_range(in_ctx_mem as u64, self.ctx_size * 34);
 
-            let trb = &mut *self.cmd_ring.add(self.cmd_index);
-            trb.parameter = phys_in;
-            trb.status = 0;
-            fence(Ordering::SeqCst);
-            trb.control = Trb::TYPE_CONFIGURE_ENDPOINT | (slot_id as u32) << 24 | (if self.cmd_cycle { Trb::CYCLE_BIT } else { 0 });
-            fence(Ordering::SeqCst);
-            Self::clflush(trb as *const _ as *const u8);
-
-            self.cmd_index = (self.cmd_index + 1) % 256;
-            if self.cmd_index == 0 {
-                let link = &mut *self.cmd_ring.add(255);
-                link.control = Trb::TYPE_LINK | Trb::ENT_BIT | (if self.cmd_cycle { Trb::CYCLE_BIT } else { 0 });
-                Self::clflush(link as *const _ as *const u8);
-                self.cmd_cycle = !self.cmd_cycle;
-            }
-            self.doorbell.ring(0, 0);
+        let mut trb = Trb::new();
+        trb.parameter = phys_in;
+        trb.control = Trb::TYPE_CONFIGURE_ENDPOINT | (slot_id as u32) << 24;
+        let result = self.run_command(trb).map(|_| ()).map_err(|_| "EP Fail");
+        Self::free_aligned(in_ctx_mem, self.ctx_size * 34, 64);
+        result
+    }
 
-            for _ in 0..2_000_000 {
-                if let Some(id) = self.check_event_sync() {
-                    if id == slot_id { 
-                        crate::serial_println!("[USB] EP Configured on Slot {}: DCI={} MaxPacket={} Interval={}", slot_id, dci, max_packet, interval);
-                        return Ok(()); 
-                    }
-                }
-                core::hint::spin_loop();
+    /// Sets up the bulk pipes of a mass-storage interface on `slot_id`. The
+    /// BOT/SCSI layer on top lives in usb_storage.
+    unsafe fn configure_mass_storage(&mut self, slot_id: u8, msc: MassStorageInterface) {
+        let (bulk_in, bulk_out) = match (TransferRing::new(), TransferRing::new()) {
+            (Ok(i), Ok(o)) => (i, o),
+            _ => return,
+        };
+        let eps = [
+            (msc.bulk_in.dci(), EP_TYPE_BULK_IN, msc.bulk_in.max_packet, 0, bulk_in.phys),
+            (msc.bulk_out.dci(), EP_TYPE_BULK_OUT, msc.bulk_out.max_packet, 0, bulk_out.phys),
+        ];
+        if self.add_endpoints(slot_id, &eps).is_err() || self.set_configuration(slot_id).is_err() {
+            crate::serial_println!("[USB] Mass storage on Slot {} could not be configured.", slot_id);
+            bulk_in.free(); bulk_out.free();
+            return;
+        }
+        crate::serial_println!("[USB] Slot {} is a mass storage device (interface {}, IN DCI {}, OUT DCI {})",
+            slot_id, msc.number, msc.bulk_in.dci(), msc.bulk_out.dci());
+        self.storage[slot_id as usize] = Some(BulkPipes {
+            interface: msc.number,
+            bulk_in, in_dci: msc.bulk_in.dci(), in_addr: msc.bulk_in.address,
+            bulk_out, out_dci: msc.bulk_out.dci(), out_addr: msc.bulk_out.address,
+        });
+    }
+
+    /// Slots with a configured mass-storage interface, with its interface number
+    pub fn storage_slots(&self) -> Vec<(u8, u8)> {
+        self.storage.iter().enumerate().filter_map(|(s, p)| p.as_ref().map(|p| (s as u8, p.interface))).collect()
+    }
+
+    /// Runs one bulk transfer of `len` bytes at `phys` (which must not cross a
+    /// page) on the storage pipe of `slot_id`, IN if `dir_in`. Returns the
+    /// bytes moved. A stalled pipe is reset and its halt cleared before
+    /// BulkError::Stall comes back, so the caller can carry on with the protocol.
+    pub fn bulk_transfer(&mut self, slot_id: u8, dir_in: bool, phys: u64, len: usize) -> Result<usize, BulkError> {
+        let s = slot_id as usize;
+        let pipes = match self.storage.get_mut(s).and_then(|p| p.as_mut()) { Some(p) => p, None => return Err(BulkError::NoDevice) };
+        let dci = if dir_in { pipes.in_dci } else { pipes.out_dci };
+        discard_transfers(slot_id, dci);
+
+        let mut trb = Trb::new();
+        trb.parameter = phys;
+        trb.status = len as u32;
+        trb.control = Trb::TYPE_NORMAL | Trb::IOC_BIT;
+        unsafe {
+            if dir_in { pipes.bulk_in.push(trb); } else { pipes.bulk_out.push(trb); }
+            self.doorbell.ring(s, dci as u32);
+        }
+
+        match wait_transfer(slot_id, dci, BULK_TIMEOUT_MS) {
+            Some(t) if t.code == CC_SUCCESS || t.code == CC_SHORT_PACKET => Ok(len.saturating_sub(t.residual as usize)),
+            Some(t) if t.code == CC_STALL => {
+                self.recover_pipe(slot_id, dir_in, true);
+                Err(BulkError::Stall)
+            }
+            Some(t) => {
+                self.recover_pipe(slot_id, dir_in, true);
+                Err(BulkError::Failed(t.code))
+            }
+            None => {
+                self.recover_pipe(slot_id, dir_in, false);
+                Err(BulkError::Timeout)
             }
         }
-        Err("EP Fail")
+    }
+
+    /// Gets a storage pipe moving again after a failed transfer: resets (if
+    /// halted) or stops the endpoint, skips the ring past the abandoned TD and
+    /// clears the device's halt feature.
+    pub fn recover_pipe(&mut self, slot_id: u8, dir_in: bool, halted: bool) {
+        let s = slot_id as usize;
+        let (dci, addr, dequeue) = match self.storage.get(s).and_then(|p| p.as_ref()) {
+            Some(p) if dir_in => (p.in_dci, p.in_addr, p.bulk_in.dequeue_ptr()),
+            Some(p) => (p.out_dci, p.out_addr, p.bulk_out.dequeue_ptr()),
+            None => return,
+        };
+        let target = (dci as u32) << 16 | (slot_id as u32) << 24;
+        unsafe {
+            let mut stop = Trb::new();
+            stop.control = if halted { Trb::TYPE_RESET_ENDPOINT } else { Trb::TYPE_STOP_ENDPOINT } | target;
+            let _ = self.run_command(stop);
+
+            let mut set_deq = Trb::new();
+            set_deq.parameter = dequeue;
+            set_deq.control = Trb::TYPE_SET_TR_DEQUEUE | target;
+            let _ = self.run_command(set_deq);
+        }
+        discard_transfers(slot_id, dci);
+        // CLEAR_FEATURE(ENDPOINT_HALT) to the endpoint
+        let _ = self.control_no_data(slot_id, 0x0102 | ((addr as u64) << 32));
+    }
+
+    /// Bulk-Only Mass Storage Reset followed by recovery of both pipes.
+    pub fn mass_storage_reset(&mut self, slot_id: u8) -> Result<(), &'static str> {
+        let interface = match self.storage.get(slot_id as usize).and_then(|p| p.as_ref()) { Some(p) => p.interface, None => return Err("No device") };
+        self.control_no_data(slot_id, 0xFF21 | ((interface as u64) << 32))?;
+        self.recover_pipe(slot_id, true, true);
+        self.recover_pipe(slot_id, false, true);
+        Ok(())
     }
 
     pub fn address_device(&mut self, slot_id: u8, port_id: u8, speed: u8, bsr: bool, packet_size_override: Option<u32>) -> Result<(), &'static str> {
@@ -721,24 +1311,12 @@ impl XhciController {
             (*ep0_ptr).tr_dequeue = ep_ring_phys | 1; 
             Self::clflush_range(in_ctx_mem as u64, self.ctx_size * 34);
 
-            let trb = &mut *self.cmd_ring.add(self.cmd_index);
-            trb.parameter = phys_in; trb.status = 0;
-            fence(Ordering::SeqCst);
+            let mut trb = Trb::new();
+            trb.parameter = phys_in;
             let bsr_flag = if bsr { Trb::BSR_BIT } else { 0 };
-            trb.control = Trb::TYPE_ADDRESS_DEVICE | bsr_flag | (slot_id as u32) << 24 | (if self.cmd_cycle { Trb::CYCLE_BIT } else { 0 });
-            fence(Ordering::SeqCst);
-            Self::clflush(trb as *const _ as *const u8);
-            self.cmd_index = (self.cmd_index + 1) % 256;
-            if self.cmd_index == 0 {
-                let link = &mut *self.cmd_ring.add(255);
-                link.control = Trb::TYPE_LINK | Trb::ENT_BIT | (if self.cmd_cycle { Trb::CYCLE_BIT } else { 0 });
-                Self::clflush(link as *const _ as *const u8);
-                self.cmd_cycle = !self.cmd_cycle;
-            }
-            self.doorbell.ring(0, 0); 
-            for _ in 0..5_000_000 { if let Some(s_id) = self.check_event_sync() { if s_id == slot_id { return Ok(()); } } core::hint::spin_loop(); }
+            trb.control = Trb::TYPE_ADDRESS_DEVICE | bsr_flag | (slot_id as u32) << 24;
+            self.run_command(trb).map(|_| ())
         }
-        Err("Addr Timeout")
     }
 
     pub fn check_ports(&mut self) {
@@ -758,92 +1336,137 @@ impl XhciController {
                 }
             }
 
-            for _ in 0..100_000_000 { core::hint::spin_loop(); }
+            // Give powered ports time to report what is attached
+            crate::time::sleep_ms(100);
 
-            for i in 1..=limit {
-                let idx = (i - 1) as usize * 4;
-                if idx >= self.op.portregs.len() { break; }
-                let portsc = read_volatile(&self.op.portregs[idx]);
-                
-                if (portsc & 1) != 0 { 
-                    crate::serial_println!("[USB] --- DEVICE DETECTED ON PORT {} ---", i);
-                    
-                    // 🚨 THE FIX: Mask out RW1C and PR bits before clearing so we don't accidentally enable a broken port!
-                    let mut clean_sc = portsc & !((1 << 1) | (1 << 4));
-                    write_volatile(&mut self.op.portregs[idx], clean_sc | (1 << 24) | (1 << 20) | (1 << 17)); 
-                    
-                    // 🚨 Force a true Hardware Reset no matter what the BIOS did
-                    let mut reset_sc = read_volatile(&self.op.portregs[idx]);
-                    reset_sc &= !((1 << 1) | (1 << 24) | (1 << 20) | (1 << 17));
-                    write_volatile(&mut self.op.portregs[idx], reset_sc | (1 << 4)); 
-                    
-                    for _ in 0..20_000_000 { if (read_volatile(&self.op.portregs[idx]) & (1<<4)) == 0 { break; } core::hint::spin_loop(); }
-                    for _ in 0..20_000_000 { core::hint::spin_loop(); }
-                    
-                    if (read_volatile(&self.op.portregs[idx]) & (1<<1)) != 0 {
-                        let speed = (read_volatile(&self.op.portregs[idx]) >> 10) & 0xF; 
+            for port in 1..=limit {
+                if self.reset_port(port) {
+                    crate::time::sleep_ms(PORT_RECOVERY_MS);
+                    self.attach_port(port);
+                }
+            }
+        }
+    }
+
+    /// Resets whatever is connected to root port `port` (1-based). True if
+    /// something is; give it PORT_RECOVERY_MS before attach_port.
+    unsafe fn reset_port(&mut self, port: u8) -> bool {
+        let idx = (port as usize).wrapping_sub(1) * 4;
+        if port == 0 || idx >= self.op.portregs.len() { return false; }
+        let portsc = read_volatile(&self.op.portregs[idx]);
+        if (portsc & 1) == 0 { return false; }
+
+        crate::serial_println!("[USB] --- DEVICE DETECTED ON PORT {} ---", port);
+
+        // 🚨 THE FIX: Mask out RW1C and PR bits before clearing so we don't accidentally enable a broken port!
+        let clean_sc = portsc & !((1 << 1) | (1 << 4));
+        write_volatile(&mut self.op.portregs[idx], clean_sc | (1 << 24) | (1 << 20) | (1 << 17));
+
+        // 🚨 Force a true Hardware Reset no matter what the BIOS did
+        let mut reset_sc = read_volatile(&self.op.portregs[idx]);
+        reset_sc &= !((1 << 1) | (1 << 24) | (1 << 20) | (1 << 17));
+        write_volatile(&mut self.op.portregs[idx], reset_sc | (1 << 4));
+
+        crate::watchdog::spin_until("XHCI", "port reset", PORT_RESET_TIMEOUT_MS, || (read_volatile(&self.op.portregs[idx]) & (1<<4)) == 0);
+        true
+    }
+
+    /// Brings up the device on root port `port` after reset_port and the
+    /// recovery time: as mass storage, or as a HID device.
+    unsafe fn attach_port(&mut self, port: u8) {
+        let idx = (port as usize).wrapping_sub(1) * 4;
+        if port == 0 || idx >= self.op.portregs.len() { return; }
+        if (read_volatile(&self.op.portregs[idx]) & (1<<1)) != 0 {
+            let speed = (read_volatile(&self.op.portregs[idx]) >> 10) & 0xF; 
+            
+            if let Ok(id) = self.enable_slot() {
+                if id > 0 { 
+                    self.slot_port[id as usize] = port;
+                    if let Ok(dev_desc) = self.get_descriptor(id, 1, 0, 18) {
+                        let real_mp = dev_desc[7] as u32;
+                        let vid = (dev_desc[8] as u16) | ((dev_desc[9] as u16) << 8);
+                        let pid = (dev_desc[10] as u16) | ((dev_desc[11] as u16) << 8);
                         
-                        if let Ok(id) = self.enable_slot() {
-                            if id > 0 { 
-                                if let Ok(dev_desc) = self.get_descriptor(id, 1, 0, 18) {
-                                    let real_mp = dev_desc[7] as u32;
-                                    let vid = (dev_desc[8] as u16) | ((dev_desc[9] as u16) << 8);
-                                    let pid = (dev_desc[10] as u16) | ((dev_desc[11] as u16) << 8);
-                                    
-                                    crate::serial_println!("[USB] Port {} (Slot {}) -> Vendor {:04x} : Product {:04x}", i, id, vid, pid);
+                        crate::serial_println!("[USB] Port {} (Slot {}) -> Vendor {:04x} : Product {:04x}", port, id, vid, pid);
+                        
+                        if self.address_device(id, port, speed as u8, false, Some(real_mp)).is_ok() {
+                            
+                            if vid == 0x0c45 || vid == 0x8087 {
+                                crate::serial_println!("[USB] Skipping incompatible hardware on Slot {}.", id);
+                            } else {
+                                let mut ep_max_packet: u16 = 64;
+                                let mut ep_interval: u8 = 10;
+                                let mut ep_dci: u8 = 3; 
+                                let mut kind = HidDevice::Mouse;
+                                let mut ep_interface: u8 = 0;
+
+                                let cfg_desc = self.get_descriptor(id, 2, 0, 128).ok();
+                                if let Some(msc) = cfg_desc.as_ref().and_then(|c| MassStorageInterface::find(c)) {
+                                    self.configure_mass_storage(id, msc);
+                                    return;
+                                }
+                                
+                                if let Some(cfg_desc) = cfg_desc {
+                                    let total_len = (cfg_desc[2] as u16) | ((cfg_desc[3] as u16) << 8);
+                                    let scan_len = core::cmp::min(total_len as usize, 128);
                                     
-                                    if self.address_device(id, i as u8, speed as u8, false, Some(real_mp)).is_ok() {
+                                    // Endpoint descriptors follow the interface they belong to
+                                    let mut iface_num: u8 = 0;
+                                    let mut iface_protocol: u8 = 0;
+                                    let mut scan_idx = 0;
+                                    while scan_idx + 1 < scan_len {
+                                        let desc_len = cfg_desc[scan_idx] as usize;
+                                        if desc_len == 0 || scan_idx + desc_len > scan_len { break; }
+                                        let desc_type = cfg_desc[scan_idx + 1];
+
+                                        if desc_type == 4 && desc_len >= 9 {
+                                            iface_num = cfg_desc[scan_idx + 2];
+                                            // bInterfaceClass 3 = HID
+                                            iface_protocol = if cfg_desc[scan_idx + 5] == 3 { cfg_desc[scan_idx + 7] } else { 0 };
+                                        }
                                         
-                                        if vid == 0x0c45 || vid == 0x8087 {
-                                            crate::serial_println!("[USB] Skipping incompatible hardware on Slot {}.", id);
-                                        } else {
-                                            let mut ep_max_packet: u16 = 64;
-                                            let mut ep_interval: u8 = 10;
-                                            let mut ep_dci: u8 = 3; 
+                                        if desc_type == 5 { 
+                                            let ep_addr = cfg_desc[scan_idx + 2];
+                                            let attr = cfg_desc[scan_idx + 3];
                                             
-                                            if let Ok(cfg_desc) = self.get_descriptor(id, 2, 0, 128) {
-                                                let total_len = (cfg_desc[2] as u16) | ((cfg_desc[3] as u16) << 8);
-                                                let scan_len = core::cmp::min(total_len as usize, 128);
+                                            if (ep_addr & 0x80) != 0 && (attr & 3) == 3 {
+                                                ep_max_packet = (cfg_desc[scan_idx + 4] as u16) | ((cfg_desc[scan_idx + 5] as u16) << 8);
+                                                ep_max_packet &= 0x07FF; 
+                                                ep_interval = cfg_desc[scan_idx + 6];
                                                 
-                                                let mut scan_idx = 0;
-                                                while scan_idx < scan_len {
-                                                    let desc_len = cfg_desc[scan_idx] as usize;
-                                                    if desc_len == 0 { break; }
-                                                    let desc_type = cfg_desc[scan_idx + 1];
-                                                    
-                                                    if desc_type == 5 { 
-                                                        let ep_addr = cfg_desc[scan_idx + 2];
-                                                        let attr = cfg_desc[scan_idx + 3];
-                                                        
-                                                        if (ep_addr & 0x80) != 0 && (attr & 3) == 3 {
-                                                            ep_max_packet = (cfg_desc[scan_idx + 4] as u16) | ((cfg_desc[scan_idx + 5] as u16) << 8);
-                                                            ep_max_packet &= 0x07FF; 
-                                                            ep_interval = cfg_desc[scan_idx + 6];
-                                                            
-                                                            let ep_num = ep_addr & 0x0F;
-                                                            ep_dci = (ep_num * 2) + 1; 
-                                                            break; 
-                                                        }
-                                                    }
-                                                    scan_idx += desc_len;
-                                                }
+                                                let ep_num = ep_addr & 0x0F;
+                                                ep_dci = (ep_num * 2) + 1; 
+                                                kind = HidDevice::from_protocol(iface_protocol);
+                                                ep_interface = iface_num;
+                                                break; 
                                             }
-                                            
-                                            if self.configure_interrupt_endpoint(id, ep_max_packet, ep_interval, ep_dci).is_ok() {
-                                                for _ in 0..5_000_000 { core::hint::spin_loop(); }
-                                                
-                                                if self.set_configuration(id).is_ok() {
-                                                    for _ in 0..5_000_000 { core::hint::spin_loop(); }
-                                                    
-                                                    if self.set_boot_protocol(id).is_err() {
-                                                        crate::serial_println!("[USB] Note: Trackpad on Slot {} rejected Legacy Protocol.", id);
-                                                    }
-                                                    let _ = self.set_idle(id);
-                                                    
-                                                    self.ep1_configured[id as usize] = true;
-                                                }
+                                        }
+                                        scan_idx += desc_len;
+                                    }
+                                }
+                                
+                                if self.configure_interrupt_endpoint(id, ep_max_packet, ep_interval, ep_dci).is_ok() {
+                                    crate::time::sleep_ms(5);
+                                    
+                                    if self.set_configuration(id).is_ok() {
+                                        crate::time::sleep_ms(5);
+                                        
+                                        // Tablets (QEMU usb-tablet) report absolute X/Y and have no boot protocol
+                                        if kind == HidDevice::Mouse {
+                                            if let Ok(report_desc) = self.get_hid_descriptor(id, ep_interface, 0x22, 128) {
+                                                if pointer_is_absolute(&report_desc) { kind = HidDevice::Tablet; }
                                             }
                                         }
+                                        if kind != HidDevice::Tablet && self.set_boot_protocol(id, ep_interface).is_err() {
+                                            crate::serial_println!("[USB] Note: Device on Slot {} rejected Legacy Protocol.", id);
+                                        }
+                                        let _ = self.set_idle(id, ep_interface);
+                                        
+                                        crate::serial_println!("[USB] Slot {} is a {:?}", id, kind);
+                                        self.hid_kind[id as usize] = kind;
+                                        self.keyboards[id as usize] = Default::default();
+                                        self.ep1_configured[id as usize] = true;
+                                        set_hid_endpoint(id, ep_dci);
                                     }
                                 }
                             }
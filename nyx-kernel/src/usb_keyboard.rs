@@ -0,0 +1,127 @@
+// ==========================================
+// USB BOOT KEYBOARD
+// ==========================================
+// Boot-protocol reports (modifier byte, reserved byte, up to six key usages)
+// are diffed against the previous report and replayed as set-1 make/break
+// codes through input::handle_scancode, so a USB keyboard feeds exactly the
+// same decoder as the PS/2 path: same layout, modifiers and packed events.
+// USB keyboards don't repeat on their own, so the newest held key repeats
+// like PS/2 typematic would.
+
+/// Boot-protocol report size
+pub const REPORT_LEN: usize = 8;
+
+const REPEAT_DELAY_MS: u64 = 500;
+const REPEAT_INTERVAL_MS: u64 = 33;
+
+// Set-1 codes with this bit are sent behind an 0xE0 prefix
+const EXT: u16 = 0xE000;
+// Every key slot reads 0x01 when more keys are down than the report can hold
+const ERR_ROLLOVER: u8 = 0x01;
+const FIRST_KEY_USAGE: u8 = 0x04;
+
+// Lock keys toggle on every make code, so they never repeat
+const USAGE_CAPS_LOCK: u8 = 0x39;
+const USAGE_SCROLL_LOCK: u8 = 0x47;
+const USAGE_NUM_LOCK: u8 = 0x53;
+
+/// Set-1 make code for HID usage IDs 0x00..=0x65 (0 = no equivalent).
+static USAGE_TO_SET1: [u16; 0x66] = [
+    // 0x00: no event, rollover, POST fail, undefined
+    0, 0, 0, 0,
+    // 0x04: a..z
+    0x1E, 0x30, 0x2E, 0x20, 0x12, 0x21, 0x22, 0x23, 0x17, 0x24, 0x25, 0x26, 0x32,
+    0x31, 0x18, 0x19, 0x10, 0x13, 0x1F, 0x14, 0x16, 0x2F, 0x11, 0x2D, 0x15, 0x2C,
+    // 0x1E: 1..9, 0
+    0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0A, 0x0B,
+    // 0x28: Enter, Esc, Backspace, Tab, Space, - = [ ] \ (non-US #) ; ' ` , . /
+    0x1C, 0x01, 0x0E, 0x0F, 0x39, 0x0C, 0x0D, 0x1A, 0x1B, 0x2B, 0x2B, 0x27, 0x28, 0x29, 0x33, 0x34, 0x35,
+    // 0x39: Caps Lock, F1..F12
+    0x3A, 0x3B, 0x3C, 0x3D, 0x3E, 0x3F, 0x40, 0x41, 0x42, 0x43, 0x44, 0x57, 0x58,
+    // 0x46: Print Screen, Scroll Lock, Pause (multi-byte sequences, dropped except Scroll Lock)
+    0, 0x46, 0,
+    // 0x49: Insert, Home, Page Up, Delete, End, Page Down
+    EXT | 0x52, EXT | 0x47, EXT | 0x49, EXT | 0x53, EXT | 0x4F, EXT | 0x51,
+    // 0x4F: Right, Left, Down, Up
+    EXT | 0x4D, EXT | 0x4B, EXT | 0x50, EXT | 0x48,
+    // 0x53: Num Lock, keypad / * - + Enter
+    0x45, EXT | 0x35, 0x37, 0x4A, 0x4E, EXT | 0x1C,
+    // 0x59: keypad 1..9, 0, .
+    0x4F, 0x50, 0x51, 0x4B, 0x4C, 0x4D, 0x47, 0x48, 0x49, 0x52, 0x53,
+    // 0x64: non-US \, Application
+    0x56, EXT | 0x5D,
+];
+
+/// Set-1 make code for each bit of the modifier byte: LCtrl, LShift, LAlt,
+/// LGUI, RCtrl, RShift, RAlt, RGUI.
+const MODIFIER_SET1: [u16; 8] = [0x1D, 0x2A, 0x38, EXT | 0x5B, EXT | 0x1D, 0x36, EXT | 0x38, EXT | 0x5C];
+
+fn set1_code(usage: u8) -> Option<u16> {
+    USAGE_TO_SET1.get(usage as usize).copied().filter(|&c| c != 0)
+}
+
+fn emit(code: u16, pressed: bool) {
+    let make = (code & 0xFF) as u8;
+    // The PS/2 interrupt handler takes the same keyboard lock
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        if code & EXT != 0 { crate::input::handle_scancode(0xE0); }
+        crate::input::handle_scancode(if pressed { make } else { make | 0x80 });
+    });
+}
+
+/// Key state of one boot keyboard, kept between reports.
+#[derive(Clone, Copy, Default)]
+pub struct BootKeyboard {
+    modifiers: u8,
+    keys: [u8; 6],
+    // (set-1 code, when it next repeats)
+    repeat: Option<(u16, u64)>,
+}
+
+impl BootKeyboard {
+    pub fn handle_report(&mut self, report: &[u8], now_ms: u64) {
+        if report.len() < REPORT_LEN { return; }
+        let keys = &report[2..REPORT_LEN];
+        // A rollover report says nothing about which keys changed
+        if keys.iter().all(|&k| k == ERR_ROLLOVER) { return; }
+
+        let modifiers = report[0];
+        for (bit, &code) in MODIFIER_SET1.iter().enumerate() {
+            let mask = 1 << bit;
+            if (modifiers ^ self.modifiers) & mask != 0 { emit(code, modifiers & mask != 0); }
+        }
+        self.modifiers = modifiers;
+
+        for &old in self.keys.iter().filter(|&&k| k >= FIRST_KEY_USAGE && !keys.contains(&k)) {
+            if let Some(code) = set1_code(old) {
+                emit(code, false);
+                if self.repeat.map_or(false, |(c, _)| c == code) { self.repeat = None; }
+            }
+        }
+        for &new in keys.iter().filter(|&&k| k >= FIRST_KEY_USAGE && !self.keys.contains(&k)) {
+            if let Some(code) = set1_code(new) {
+                emit(code, true);
+                let is_lock = matches!(new, USAGE_CAPS_LOCK | USAGE_SCROLL_LOCK | USAGE_NUM_LOCK);
+                if !is_lock { self.repeat = Some((code, now_ms + REPEAT_DELAY_MS)); }
+            }
+        }
+        self.keys.copy_from_slice(keys);
+    }
+
+    /// Releases everything still held, for a keyboard that was unplugged
+    /// with keys down, so no modifier stays stuck.
+    pub fn release_all(&mut self) {
+        self.handle_report(&[0; REPORT_LEN], 0);
+        self.repeat = None;
+    }
+
+    /// Sends the typematic repeat of the held key once it is due.
+    pub fn tick(&mut self, now_ms: u64) {
+        if let Some((code, due)) = self.repeat {
+            if now_ms >= due {
+                emit(code, true);
+                self.repeat = Some((code, now_ms + REPEAT_INTERVAL_MS));
+            }
+        }
+    }
+}
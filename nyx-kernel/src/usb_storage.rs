@@ -0,0 +1,247 @@
+use alloc::alloc::{alloc_zeroed, dealloc, Layout};
+use alloc::string::String;
+use crate::usb::{lock_controller, BulkError, XhciController};
+
+// ==========================================
+// USB MASS STORAGE (BULK-ONLY TRANSPORT)
+// ==========================================
+// SCSI commands travel in a 31-byte Command Block Wrapper on bulk OUT, the
+// data stage goes whichever way the command says, and a 13-byte Command
+// Status Wrapper comes back on bulk IN. A stalled data stage still ends with
+// a CSW; a stall on the CBW, a garbled or missing CSW, or a phase error gets
+// the full reset recovery (Bulk-Only Mass Storage Reset, then both halts
+// cleared). Failed commands are retried up to MAX_ATTEMPTS times before the
+// block layer sees an error. Data moves through one bounce page, so each
+// command covers at most MAX_SECTORS_PER_CMD sectors, like AHCI.
+
+pub const SECTOR_SIZE: usize = 512;
+/// Sectors per command: one bounce page
+pub const MAX_SECTORS_PER_CMD: usize = 4096 / SECTOR_SIZE;
+
+const CBW_SIGNATURE: u32 = 0x4342_5355; // "USBC"
+const CSW_SIGNATURE: u32 = 0x5342_5355; // "USBS"
+const CBW_LEN: usize = 31;
+const CSW_LEN: usize = 13;
+// The CSW lands after the CBW in the same small buffer
+const CSW_OFFSET: usize = 64;
+
+const CSW_PASSED: u8 = 0;
+const CSW_PHASE_ERROR: u8 = 2;
+const MAX_ATTEMPTS: usize = 3;
+// Sticks may answer TEST UNIT READY with "not ready" for a while after reset
+const READY_ATTEMPTS: usize = 10;
+
+const SCSI_TEST_UNIT_READY: u8 = 0x00;
+const SCSI_INQUIRY: u8 = 0x12;
+const SCSI_READ_CAPACITY_10: u8 = 0x25;
+const SCSI_READ_10: u8 = 0x28;
+const SCSI_WRITE_10: u8 = 0x2A;
+
+enum Data<'a> {
+    None,
+    In(&'a mut [u8]),
+    Out(&'a [u8]),
+}
+
+impl Data<'_> {
+    fn len(&self) -> usize {
+        match self { Data::None => 0, Data::In(b) => b.len(), Data::Out(b) => b.len() }
+    }
+}
+
+pub struct UsbDisk {
+    pub slot: u8,
+    pub sectors: u64,
+    pub model: String,
+    tag: u32,
+    // Bounce page for the data stage, and a small buffer for CBW + CSW
+    page: *mut u8,
+    page_phys: u64,
+    wrapper: *mut u8,
+    wrapper_phys: u64,
+}
+
+unsafe impl Send for UsbDisk {}
+
+/// Brings up the first mass-storage device the xHCI controller found.
+pub fn find_disk() -> Option<UsbDisk> {
+    let mut guard = lock_controller();
+    let ctrl = guard.as_mut()?;
+    let slots = ctrl.storage_slots();
+    slots.into_iter().find_map(|(slot, _)| UsbDisk::probe(ctrl, slot))
+}
+
+impl UsbDisk {
+    fn probe(ctrl: &mut XhciController, slot: u8) -> Option<Self> {
+        let (page, page_phys) = dma_alloc(4096)?;
+        let (wrapper, wrapper_phys) = match dma_alloc(128) {
+            Some(w) => w,
+            None => { dma_free(page, 4096); return None; }
+        };
+        // From here on, giving up drops the disk and its Drop frees both buffers
+        let mut disk = Self { slot, sectors: 0, model: String::new(), tag: 0, page, page_phys, wrapper, wrapper_phys };
+
+        let mut inquiry = [0u8; 36];
+        disk.command(ctrl, &[SCSI_INQUIRY, 0, 0, 0, 36, 0], Data::In(&mut inquiry)).ok()?;
+        // Vendor (8..16) and product (16..32), space padded
+        let text: String = inquiry[8..32].iter().map(|&b| if b.is_ascii_graphic() { b as char } else { ' ' }).collect();
+        disk.model = text.split_whitespace().collect::<alloc::vec::Vec<_>>().join(" ");
+
+        for _ in 0..READY_ATTEMPTS {
+            if disk.command(ctrl, &[SCSI_TEST_UNIT_READY, 0, 0, 0, 0, 0], Data::None).is_ok() { break; }
+            crate::time::sleep_ms(100);
+        }
+
+        let mut capacity = [0u8; 8];
+        disk.command(ctrl, &[SCSI_READ_CAPACITY_10, 0, 0, 0, 0, 0, 0, 0, 0, 0], Data::In(&mut capacity)).ok()?;
+        let last_lba = u32::from_be_bytes([capacity[0], capacity[1], capacity[2], capacity[3]]);
+        let block_len = u32::from_be_bytes([capacity[4], capacity[5], capacity[6], capacity[7]]);
+        if block_len as usize != SECTOR_SIZE {
+            crate::serial_println!("[USB] Storage on Slot {} uses {}-byte blocks; only 512 is supported.", slot, block_len);
+            return None;
+        }
+        disk.sectors = last_lba as u64 + 1;
+        crate::serial_println!("[USB] Storage on Slot {}: {} ({} MiB)", slot, disk.model, disk.sectors * SECTOR_SIZE as u64 / (1024 * 1024));
+        Some(disk)
+    }
+
+    /// Runs `cdb`, retrying after failures and resetting the device when the
+    /// transport itself got confused.
+    fn command(&mut self, ctrl: &mut XhciController, cdb: &[u8], mut data: Data) -> Result<(), BulkError> {
+        let mut last = BulkError::Failed(0);
+        for _ in 0..MAX_ATTEMPTS {
+            match self.transport(ctrl, cdb, &mut data) {
+                Ok(CSW_PASSED) => return Ok(()),
+                // The command failed but the transport is fine; just try again
+                Ok(status) if status != CSW_PHASE_ERROR => last = BulkError::Failed(status),
+                Ok(status) => {
+                    last = BulkError::Failed(status);
+                    let _ = ctrl.mass_storage_reset(self.slot);
+                }
+                Err(BulkError::NoDevice) => return Err(BulkError::NoDevice),
+                Err(e) => {
+                    last = e;
+                    let _ = ctrl.mass_storage_reset(self.slot);
+                }
+            }
+        }
+        Err(last)
+    }
+
+    /// One CBW / data / CSW exchange. Returns the CSW status byte.
+    fn transport(&mut self, ctrl: &mut XhciController, cdb: &[u8], data: &mut Data) -> Result<u8, BulkError> {
+        let len = data.len();
+        self.tag = self.tag.wrapping_add(1);
+
+        let mut cbw = [0u8; CBW_LEN];
+        cbw[0..4].copy_from_slice(&CBW_SIGNATURE.to_le_bytes());
+        cbw[4..8].copy_from_slice(&self.tag.to_le_bytes());
+        cbw[8..12].copy_from_slice(&(len as u32).to_le_bytes());
+        cbw[12] = if matches!(data, Data::In(_)) { 0x80 } else { 0 };
+        cbw[13] = 0; // LUN
+        cbw[14] = cdb.len() as u8;
+        cbw[15..15 + cdb.len()].copy_from_slice(cdb);
+        unsafe { core::ptr::copy_nonoverlapping(cbw.as_ptr(), self.wrapper, CBW_LEN); }
+        ctrl.bulk_transfer(self.slot, false, self.wrapper_phys, CBW_LEN)?;
+
+        if len > 0 {
+            if let Data::Out(src) = data {
+                unsafe { core::ptr::copy_nonoverlapping(src.as_ptr(), self.page, len); }
+            }
+            let dir_in = matches!(data, Data::In(_));
+            match ctrl.bulk_transfer(self.slot, dir_in, self.page_phys, len) {
+                // A stalled data stage still ends with a CSW
+                Ok(_) | Err(BulkError::Stall) => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        let csw_phys = self.wrapper_phys + CSW_OFFSET as u64;
+        let got = match ctrl.bulk_transfer(self.slot, true, csw_phys, CSW_LEN) {
+            // A stall here is allowed once; the CSW follows on the cleared pipe
+            Err(BulkError::Stall) => ctrl.bulk_transfer(self.slot, true, csw_phys, CSW_LEN)?,
+            other => other?,
+        };
+        let mut csw = [0u8; CSW_LEN];
+        unsafe { core::ptr::copy_nonoverlapping(self.wrapper.add(CSW_OFFSET), csw.as_mut_ptr(), CSW_LEN); }
+        let valid = got == CSW_LEN
+            && u32::from_le_bytes([csw[0], csw[1], csw[2], csw[3]]) == CSW_SIGNATURE
+            && u32::from_le_bytes([csw[4], csw[5], csw[6], csw[7]]) == self.tag;
+        if !valid { return Err(BulkError::Failed(0)); }
+
+        if csw[12] == CSW_PASSED {
+            if let Data::In(dst) = data {
+                unsafe { core::ptr::copy_nonoverlapping(self.page, dst.as_mut_ptr(), len); }
+            }
+        }
+        Ok(csw[12])
+    }
+
+    fn rw_cdb(opcode: u8, lba: u64, count: usize) -> [u8; 10] {
+        let lba = (lba as u32).to_be_bytes();
+        let count = (count as u16).to_be_bytes();
+        [opcode, 0, lba[0], lba[1], lba[2], lba[3], 0, count[0], count[1], 0]
+    }
+
+    /// Reads `count` sectors starting at `sector` into `buf`.
+    pub fn read(&mut self, sector: u64, count: usize, buf: &mut [u8]) -> bool {
+        let mut guard = lock_controller();
+        let ctrl = match guard.as_mut() { Some(c) => c, None => return false };
+        let mut done = 0;
+        while done < count {
+            let n = (count - done).min(MAX_SECTORS_PER_CMD);
+            let cdb = Self::rw_cdb(SCSI_READ_10, sector + done as u64, n);
+            let dst = &mut buf[done * SECTOR_SIZE..(done + n) * SECTOR_SIZE];
+            if self.command(ctrl, &cdb, Data::In(dst)).is_err() { return false; }
+            done += n;
+        }
+        true
+    }
+
+    /// Writes `count` sectors from `buf` starting at `sector`.
+    pub fn write(&mut self, sector: u64, count: usize, buf: &[u8]) -> bool {
+        let mut guard = lock_controller();
+        let ctrl = match guard.as_mut() { Some(c) => c, None => return false };
+        let mut done = 0;
+        while done < count {
+            let n = (count - done).min(MAX_SECTORS_PER_CMD);
+            let cdb = Self::rw_cdb(SCSI_WRITE_10, sector + done as u64, n);
+            let src = &buf[done * SECTOR_SIZE..(done + n) * SECTOR_SIZE];
+            if self.command(ctrl, &cdb, Data::Out(src)).is_err() { return false; }
+            done += n;
+        }
+        true
+    }
+}
+
+impl Drop for UsbDisk {
+    fn drop(&mut self) {
+        dma_free(self.page, 4096);
+        dma_free(self.wrapper, 128);
+    }
+}
+
+impl crate::drivers::block::BlockDevice for UsbDisk {
+    fn read_sector(&mut self, sector: u64, buf: &mut [u8]) -> bool {
+        self.read(sector, 1, buf)
+    }
+
+    fn write_sector(&mut self, sector: u64, buf: &[u8]) -> bool {
+        self.write(sector, 1, buf)
+    }
+}
+
+/// Zeroed, page-aligned DMA memory (never crosses a page for `size` <= 4096)
+fn dma_alloc(size: usize) -> Option<(*mut u8, u64)> {
+    let ptr = unsafe { alloc_zeroed(Layout::from_size_align(size, 4096).ok()?) };
+    if ptr.is_null() { return None; }
+    match crate::memory::virt_to_phys(ptr as u64) {
+        Some(phys) => Some((ptr, phys)),
+        None => { dma_free(ptr, size); None }
+    }
+}
+
+/// Gives back a dma_alloc(size) buffer.
+fn dma_free(ptr: *mut u8, size: usize) {
+    if let Ok(layout) = Layout::from_size_align(size, 4096) { unsafe { dealloc(ptr, layout); } }
+}
@@ -8,6 +8,19 @@ lazy_static::lazy_static! {
     pub static ref VFS: VirtualFileSystem = VirtualFileSystem::new();
 }
 
+// Bumped by every create, write, delete and rename that succeeds, on any
+// mount. Listings compare it (SYS_FS_GENERATION) instead of rescanning
+// their directory to find out whether anything changed.
+static GENERATION: core::sync::atomic::AtomicU64 = core::sync::atomic::AtomicU64::new(0);
+
+pub fn generation() -> u64 { GENERATION.load(core::sync::atomic::Ordering::Acquire) }
+
+/// Passes a mutating operation's result through, counting it if it succeeded.
+fn changed<T>(res: Result<T, FsError>) -> Result<T, FsError> {
+    if res.is_ok() { GENERATION.fetch_add(1, core::sync::atomic::Ordering::Release); }
+    res
+}
+
 // ==========================================
 // 1. THE HARDWARE DRIVER ABSTRACTION
 // ==========================================
@@ -21,6 +34,25 @@ pub enum FsError {
     OutOfSpace,
     Unsupported,
     PermissionDenied,
+    AlreadyExists,
+    /// A name to create holds a reserved character (nyx_core::path::RESERVED_NAME_CHARS)
+    InvalidName,
+}
+
+pub const FS_TYPE_EXT4: u32 = 1;
+
+/// Size and free space of a mounted volume. Also the layout
+/// SYS_FS_VOLUME_INFO copies out, so it's repr(C).
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+pub struct VolumeInfo {
+    pub total_blocks: u64,
+    pub free_blocks: u64,
+    pub block_size: u32,
+    /// One of the FS_TYPE_ constants
+    pub fs_type: u32,
+    /// NUL-padded
+    pub label: [u8; 16],
 }
 
 /// Any storage driver (NVMe, AHCI, TAR RAMFS) must implement this trait.
@@ -42,8 +74,20 @@ pub trait FileSystem: Send + Sync {
     // 🔥 MILESTONE 1.3: Delete File Added
     fn delete_file(&mut self, _path: &str) -> Result<(), FsError> { Err(FsError::Unsupported) }
     
+    /// Renames a file or directory. Must fail with AlreadyExists instead of clobbering the target.
+    fn rename_file(&mut self, _old_path: &str, _new_path: &str) -> Result<(), FsError> { Err(FsError::Unsupported) }
+    
     // 🔥 MILESTONE 1.7: Sync/Flush to commit Journal to physical disk
     fn sync(&mut self) -> Result<(), FsError> { Ok(()) }
+
+    /// DIRENT_FLAG_READONLY / DIRENT_FLAG_SYSTEM bits of a file or directory.
+    fn attributes(&self, _path: &str) -> u16 { 0 }
+
+    /// Last-modified time in Unix seconds; 0 if it was never stamped.
+    fn modified_time(&self, _path: &str) -> Result<u64, FsError> { Err(FsError::Unsupported) }
+
+    /// How big the volume is and how much of it is free.
+    fn volume_info(&self) -> Result<VolumeInfo, FsError> { Err(FsError::Unsupported) }
     
     // --- WAL (Write-Ahead Logging) Hooks ---
     fn begin_transaction(&mut self) -> u64 { 0 }
@@ -146,24 +190,55 @@ impl VirtualFileSystem {
         None
     }
 
+    /// Runs `f` against the driver mounted over `path` with the mount-relative path.
+    /// Every read-only router goes through here, so path and fd syscalls agree.
+    fn with_driver<R>(&self, path: &str, f: impl FnOnce(&dyn FileSystem, &str) -> Result<R, FsError>) -> Result<R, FsError> {
+        let (mount_point, relative_path) = self.resolve_mount(path).ok_or(FsError::NotFound)?;
+        let mounts = self.mounts.lock();
+        let driver = mounts.get(&mount_point).ok_or(FsError::NotFound)?;
+        f(driver.as_ref(), &relative_path)
+    }
+
+    fn with_driver_mut<R>(&self, path: &str, f: impl FnOnce(&mut dyn FileSystem, &str) -> Result<R, FsError>) -> Result<R, FsError> {
+        let (mount_point, relative_path) = self.resolve_mount(path).ok_or(FsError::NotFound)?;
+        let mut mounts = self.mounts.lock();
+        let driver = mounts.get_mut(&mount_point).ok_or(FsError::NotFound)?;
+        f(driver.as_mut(), &relative_path)
+    }
+
     // ==========================================
     // GLOBAL VFS SYSTEM CALL ROUTERS
     // ==========================================
     
     pub fn read_file_alloc(&self, path: &str) -> Option<Vec<u8>> {
-        let (mount_point, relative_path) = self.resolve_mount(path)?;
-        let mounts = self.mounts.lock();
-        let driver = mounts.get(&mount_point)?;
+        self.with_driver(path, |driver, rel| {
+            let size = driver.get_file_size(rel)?;
+            let mut buf = alloc::vec![0u8; size];
+            if driver.read_file(rel, 0, &mut buf)? == size { Ok(buf) } else { Err(FsError::IoError) }
+        }).ok()
+    }
+    
+    /// Reads up to buf.len() bytes starting at `offset`. Callers loop over offsets for large files.
+    pub fn read_file_at(&self, path: &str, offset: usize, buf: &mut [u8]) -> Result<usize, FsError> {
+        self.with_driver(path, |driver, rel| driver.read_file(rel, offset, buf))
+    }
 
-        let size = driver.get_file_size(&relative_path).ok()?;
-        let mut buf = alloc::vec![0u8; size];
-        
-        let bytes_read = driver.read_file(&relative_path, 0, &mut buf).ok()?;
-        if bytes_read == size {
-            Some(buf)
-        } else {
-            None
-        }
+    pub fn file_size(&self, path: &str) -> Result<usize, FsError> {
+        self.with_driver(path, |driver, rel| driver.get_file_size(rel))
+    }
+
+    /// DIRENT_FLAG_READONLY / DIRENT_FLAG_SYSTEM bits; 0 where nothing is mounted.
+    pub fn attributes(&self, path: &str) -> u16 {
+        self.with_driver(path, |driver, rel| Ok(driver.attributes(rel))).unwrap_or(0)
+    }
+
+    pub fn modified_time(&self, path: &str) -> Result<u64, FsError> {
+        self.with_driver(path, |driver, rel| driver.modified_time(rel))
+    }
+
+    /// True if some mount covers `path`. Says nothing about whether the file exists.
+    pub fn is_mounted(&self, path: &str) -> bool {
+        self.resolve_mount(path).is_some()
     }
     
     pub fn list_dir(&self, path: &str) -> Vec<String> {
@@ -198,58 +273,81 @@ impl VirtualFileSystem {
         results
     }
     
-    pub fn open_path(&self, path: &str) -> Option<String> {
-        if self.resolve_mount(path).is_some() {
-            Some(String::from(path))
-        } else {
-            None
-        }
+    /// Opens an existing file for fd-based I/O. The handle only remembers the path, so
+    /// every read and write goes back through the mount table like the path syscalls do.
+    pub fn open_path(&self, path: &str) -> Result<OpenFile, FsError> {
+        self.file_size(path)?;
+        Ok(OpenFile::new(String::from(path)))
     }
     
-    pub fn create_dir(&self, path: &str) -> bool {
-        if let Some((mount_point, rel_path)) = self.resolve_mount(path) {
-            let mut mounts = self.mounts.lock();
-            if let Some(driver) = mounts.get_mut(&mount_point) {
-                return driver.create_dir(&rel_path).is_ok();
-            }
-        }
-        false
+    pub fn create_dir(&self, path: &str) -> Result<(), FsError> {
+        changed(self.with_driver_mut(path, |driver, rel| driver.create_dir(rel)))
     }
 
-    pub fn create_file(&self, path: &str) -> bool {
-        if let Some((mount_point, rel_path)) = self.resolve_mount(path) {
-            let mut mounts = self.mounts.lock();
-            if let Some(driver) = mounts.get_mut(&mount_point) {
-                return driver.create_file(&rel_path).is_ok();
+    /// Creates every missing directory along `path`, like `mkdir -p`. Fails
+    /// with IoError if nothing along it is on a mount.
+    pub fn create_dir_all(&self, path: &str) -> Result<(), FsError> {
+        let mut prefix = String::new();
+        let mut created = false;
+        
+        for part in path.split('/').filter(|p| !p.is_empty()) {
+            prefix.push('/');
+            prefix.push_str(part);
+            
+            match self.resolve_mount(&prefix) {
+                // The mount point itself (e.g. "/mnt/nvme") always exists
+                Some((_, rel_path)) if rel_path == "/" => continue,
+                Some(_) => {
+                    self.create_dir(&prefix)?;
+                    created = true;
+                },
+                None => continue,
             }
         }
-        false
+        if created { Ok(()) } else { Err(FsError::IoError) }
+    }
+
+    /// The volume `path` is on.
+    pub fn volume_info(&self, path: &str) -> Result<VolumeInfo, FsError> {
+        self.with_driver(path, |driver, _| driver.volume_info())
+    }
+
+    pub fn create_file(&self, path: &str) -> Result<(), FsError> {
+        changed(self.with_driver_mut(path, |driver, rel| driver.create_file(rel)))
     }
 
     pub fn write_file(&self, path: &str, buf: &[u8]) -> bool {
-        if let Some((mount_point, rel_path)) = self.resolve_mount(path) {
-            let mut mounts = self.mounts.lock();
-            if let Some(driver) = mounts.get_mut(&mount_point) {
-                return driver.write_file(&rel_path, 0, buf).is_ok();
-            }
-        }
-        false
+        self.write_file_at(path, 0, buf).is_ok()
     }
     
-    pub fn delete_file(&self, path: &str) -> bool {
-        if let Some((mount_point, rel_path)) = self.resolve_mount(path) {
-            let mut mounts = self.mounts.lock();
-            if let Some(driver) = mounts.get_mut(&mount_point) {
-                return driver.delete_file(&rel_path).is_ok();
-            }
-        }
-        false
+    pub fn write_file_at(&self, path: &str, offset: usize, buf: &[u8]) -> Result<usize, FsError> {
+        changed(self.with_driver_mut(path, |driver, rel| driver.write_file(rel, offset, buf)))
+    }
+    
+    pub fn delete_file(&self, path: &str) -> Result<(), FsError> {
+        changed(self.with_driver_mut(path, |driver, rel| driver.delete_file(rel)))
+    }
+
+    pub fn rename_file(&self, old_path: &str, new_path: &str) -> Result<(), FsError> {
+        let (old_mount, old_rel) = self.resolve_mount(old_path).ok_or(FsError::NotFound)?;
+        let (new_mount, new_rel) = self.resolve_mount(new_path).ok_or(FsError::InvalidPath)?;
+        
+        // A rename can't move data between two different drivers
+        if old_mount != new_mount { return Err(FsError::Unsupported); }
+        
+        let mut mounts = self.mounts.lock();
+        let driver = mounts.get_mut(&old_mount).ok_or(FsError::NotFound)?;
+        changed(driver.rename_file(&old_rel, &new_rel))
     }
 }
 
 // ==========================================
 // 4. LEGACY FILE DESCRIPTOR BRIDGES
 // ==========================================
+pub const SEEK_SET: usize = 0;
+pub const SEEK_CUR: usize = 1;
+pub const SEEK_END: usize = 2;
+
 pub struct OpenFile {
     pub path: String,
     pub offset: spin::Mutex<usize>,
@@ -260,22 +358,35 @@ impl OpenFile {
         Self { path, offset: spin::Mutex::new(0) } 
     }
 
-    pub fn read(&self, buf: &mut [u8]) -> usize {
+    /// Reads at the current offset and advances it by the bytes returned.
+    pub fn read(&self, buf: &mut [u8]) -> Result<usize, FsError> {
         let mut off = self.offset.lock();
-        
-        if let Some((mount_point, rel_path)) = VFS.resolve_mount(&self.path) {
-            let mounts = VFS.mounts.lock();
-            if let Some(driver) = mounts.get(&mount_point) {
-                if let Ok(bytes_read) = driver.read_file(&rel_path, *off, buf) {
-                    *off += bytes_read;
-                    return bytes_read;
-                }
-            }
-        }
-        0 // EOF or Error
+        let bytes_read = VFS.read_file_at(&self.path, *off, buf)?;
+        *off += bytes_read;
+        Ok(bytes_read)
     }
 
-    pub fn write(&self, _buf: &[u8]) -> usize { 0 }
+    /// Writes at the current offset and advances it by the bytes written.
+    pub fn write(&self, buf: &[u8]) -> Result<usize, FsError> {
+        let mut off = self.offset.lock();
+        let written = VFS.write_file_at(&self.path, *off, buf)?;
+        *off += written;
+        Ok(written)
+    }
+
+    /// Moves the offset like lseek. Seeking past the end is allowed; reads there return 0.
+    pub fn seek(&self, pos: i64, whence: usize) -> Result<usize, i64> {
+        let mut off = self.offset.lock();
+        let base = match whence {
+            SEEK_SET => 0,
+            SEEK_CUR => *off as i64,
+            SEEK_END => VFS.file_size(&self.path).map_err(|_| -5i64)? as i64, // EIO
+            _ => return Err(-22), // EINVAL
+        };
+        let target = base.checked_add(pos).filter(|&t| t >= 0).ok_or(-22i64)?; // EINVAL
+        *off = target as usize;
+        Ok(*off)
+    }
 
     pub fn mmap(&self, _offset: usize, _size: usize) -> Result<u64, i64> {
         Err(-12) // ENOMEM
@@ -284,4 +395,117 @@ impl OpenFile {
     pub fn ioctl(&self, _cmd: usize, _arg: usize) -> Result<usize, i64> {
         Err(-25) // ENOTTY (Not a terminal)
     }
+}
+
+// Unread console output is capped; a task nobody listens to loses its oldest bytes
+const CONSOLE_CAP: usize = 16 * 1024;
+
+/// The stdin/stdout/stderr of a task. Writes land in a ring buffer that the owning
+/// Terminal drains with Syscall 545, and are mirrored to serial so headless boots
+/// still see them. Forked children inherit the same console, like a shared tty.
+pub struct Console {
+    output: spin::Mutex<alloc::collections::VecDeque<u8>>,
+}
+
+impl Console {
+    pub fn new() -> Self {
+        Self { output: spin::Mutex::new(alloc::collections::VecDeque::new()) }
+    }
+
+    pub fn write(&self, buf: &[u8]) -> usize {
+        if let Ok(s) = core::str::from_utf8(buf) { crate::serial_print!("{}", s); }
+
+        let mut out = self.output.lock();
+        out.extend(buf.iter().copied());
+        let excess = out.len().saturating_sub(CONSOLE_CAP);
+        out.drain(..excess);
+        buf.len()
+    }
+
+    /// Moves up to buf.len() bytes of pending output into `buf`.
+    pub fn drain(&self, buf: &mut [u8]) -> usize {
+        let mut out = self.output.lock();
+        let n = out.len().min(buf.len());
+        for (dst, src) in buf.iter_mut().zip(out.drain(..n)) { *dst = src; }
+        n
+    }
+}
+
+// Packed readdir record (Syscall 542), little endian:
+//   u16 name length, u16 flags (DIRENT_FLAG_*), u64 size,
+//   u64 modified time (Unix seconds, 0 if unknown), then the name bytes
+pub const DIRENT_HEADER_LEN: usize = 20;
+pub const DIRENT_FLAG_DIR: u16 = 1;
+/// Dot files, which listings leave out unless asked
+pub const DIRENT_FLAG_HIDDEN: u16 = 2;
+/// Kept by the file system itself (ext4's lost+found); hidden like dot files
+pub const DIRENT_FLAG_SYSTEM: u16 = 4;
+/// Writes, truncation, renames and deletes fail with PermissionDenied
+pub const DIRENT_FLAG_READONLY: u16 = 8;
+
+/// Snapshot of a directory taken at open time, handed out in batches.
+/// The directory is scanned once, not once per entry like syscall 511.
+pub struct DirStream {
+    entries: Vec<DirStreamEntry>,
+    pos: spin::Mutex<usize>,
+    // generation() just before the scan; anything newer may be missing
+    generation: u64,
+}
+
+struct DirStreamEntry {
+    name: String,
+    flags: u16,
+    size: u64,
+    mtime: u64,
+}
+
+impl DirStream {
+    pub fn open(path: &str) -> Self {
+        let generation = generation();
+        let base = path.trim_end_matches('/');
+        let entries = VFS.list_dir(path).into_iter().map(|name| {
+            let is_dir = name.ends_with('/');
+            let name = String::from(name.trim_end_matches('/'));
+            let full = alloc::format!("{}/{}", base, name);
+            let mtime = VFS.modified_time(&full).unwrap_or(0);
+            let mut flags = VFS.attributes(&full);
+            if name.starts_with('.') { flags |= DIRENT_FLAG_HIDDEN; }
+            // Mount points show up without the trailing slash but have no size either
+            match (is_dir, VFS.file_size(&full)) {
+                (false, Ok(size)) => DirStreamEntry { name, flags, size: size as u64, mtime },
+                _ => DirStreamEntry { name, flags: flags | DIRENT_FLAG_DIR, size: 0, mtime },
+            }
+        }).collect();
+        Self { entries, pos: spin::Mutex::new(0), generation }
+    }
+
+    /// The generation the snapshot was taken at. A listing is stale once
+    /// generation() has moved past it.
+    pub fn generation(&self) -> u64 { self.generation }
+
+    /// Packs as many of the remaining entries as fit into `buf`. Returns how many were
+    /// written (0 once the stream is exhausted), or None if the next one doesn't fit at all.
+    pub fn fill(&self, buf: &mut [u8]) -> Option<usize> {
+        let mut pos = self.pos.lock();
+        let mut off = 0;
+        let mut count = 0;
+
+        while let Some(entry) = self.entries.get(*pos) {
+            let name = entry.name.as_bytes();
+            let rec_len = DIRENT_HEADER_LEN + name.len();
+            if off + rec_len > buf.len() { break; }
+
+            buf[off..off + 2].copy_from_slice(&(name.len() as u16).to_le_bytes());
+            buf[off + 2..off + 4].copy_from_slice(&entry.flags.to_le_bytes());
+            buf[off + 4..off + 12].copy_from_slice(&entry.size.to_le_bytes());
+            buf[off + 12..off + 20].copy_from_slice(&entry.mtime.to_le_bytes());
+            buf[off + 20..off + rec_len].copy_from_slice(name);
+
+            off += rec_len;
+            count += 1;
+            *pos += 1;
+        }
+
+        if count == 0 && *pos < self.entries.len() { None } else { Some(count) }
+    }
 }
\ No newline at end of file
@@ -1,5 +1,6 @@
 use core::fmt;
 use core::fmt::Write;
+use core::sync::atomic::{AtomicBool, Ordering};
 use spin::Mutex;
 use crate::gui::Color;
 use crate::gui::Painter;
@@ -27,10 +28,52 @@ pub struct VgaLogger {
 // Start drawing text at Y=70 so we don't overwrite your kernel boot header
 pub static VGA_LOGGER: Mutex<VgaLogger> = Mutex::new(VgaLogger { x: MARGIN_LEFT, y: MARGIN_TOP });
 
+// Set by the panic handler: the timer can't present any more, so draw straight to VRAM
+static DIRECT_TO_SCREEN: AtomicBool = AtomicBool::new(false);
+
+/// Switches the logger to drawing on the screen itself. Panic path only.
+pub fn enter_panic_mode() { DIRECT_TO_SCREEN.store(true, Ordering::SeqCst); }
+
+pub fn in_panic_mode() -> bool { DIRECT_TO_SCREEN.load(Ordering::SeqCst) }
+
+/// Moves the cursor back to the top-left text position.
+pub fn home_cursor() {
+    let mut logger = VGA_LOGGER.lock();
+    logger.x = MARGIN_LEFT;
+    logger.y = MARGIN_TOP;
+}
+
+fn screen_size() -> (usize, usize) {
+    unsafe { target().map_or((0, 0), |p| (p.width(), p.height())) }
+}
+
+/// Lines that still fit below the cursor before the logger wraps to the top.
+pub fn rows_left() -> usize {
+    let y = VGA_LOGGER.lock().y;
+    screen_size().1.saturating_sub(20 + y) / LINE_ADVANCE
+}
+
+/// Characters per line.
+pub fn columns() -> usize {
+    screen_size().0.saturating_sub(2 * MARGIN_LEFT) / CHAR_ADVANCE
+}
+
+/// The BackBuffer, or the screen while panicking or before the BackBuffer exists.
+unsafe fn target() -> Option<&'static mut dyn Painter> {
+    if !DIRECT_TO_SCREEN.load(Ordering::SeqCst) {
+        if let Some(back) = &mut crate::BACK_BUFFER { return Some(back); }
+    }
+    match &mut crate::SCREEN_PAINTER {
+        Some(screen) => Some(screen),
+        None => None,
+    }
+}
+
 impl fmt::Write for VgaLogger {
     fn write_str(&mut self, s: &str) -> fmt::Result {
         unsafe {
-            if let Some(painter) = &mut crate::SCREEN_PAINTER {
+            if let Some(painter) = target() {
+                let (width, height) = (painter.width(), painter.height());
                 for c in s.chars() {
                     
                     if c == '\n' {
@@ -38,7 +81,7 @@ impl fmt::Write for VgaLogger {
                         self.y += LINE_ADVANCE;
                     } else {
                         // 🚨 THE FIX: Check boundaries BEFORE drawing to prevent edge-clipping
-                        if self.x + CHAR_ADVANCE >= painter.info.width - MARGIN_LEFT {
+                        if self.x + CHAR_ADVANCE >= width - MARGIN_LEFT {
                             self.x = MARGIN_LEFT;
                             self.y += LINE_ADVANCE;
                         }
@@ -47,34 +90,82 @@ impl fmt::Write for VgaLogger {
                         let char_str = c.encode_utf8(&mut buf);
                         
                         // Using YELLOW to make debug logs pop on the physical screen
-                        painter.draw_string(self.x, self.y, char_str, Color::YELLOW);
+                        painter.draw_string(self.x, self.y, char_str, Color::YELLOW, None);
                         
                         // Move cursor forward with our new spacing math
                         self.x += CHAR_ADVANCE; 
                     }
                     
                     // Screen wrap vertically (loop back to top)
-                    if self.y + LINE_ADVANCE >= painter.info.height - 20 {
+                    if self.y + LINE_ADVANCE >= height - 20 {
                         self.y = MARGIN_TOP;
                         
                         // Optional: clear a block here if the text turns into a smeared mess
                         // painter.clear(Color::BLACK); 
                     }
                 }
+                crate::gui::mark_dirty();
             }
         }
         Ok(())
     }
 }
 
+/// Clears the screen and shows a short kernel status page. Used once userspace is
+/// gone and nobody else will draw.
+pub fn show_status_screen(reason: &str) {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        crate::gui::reclaim_screen();
+        unsafe {
+            if let Some(painter) = target() { painter.clear(Color::BLACK); }
+        }
+        let mut logger = VGA_LOGGER.lock();
+        logger.x = MARGIN_LEFT;
+        logger.y = MARGIN_TOP;
+        let _ = write!(logger, "NyxOS kernel\n\n{}\n", reason);
+        let _ = write!(logger, "Tasks exited: {}  Context switches: {}\n",
+            crate::scheduler::TASKS_EXITED.load(core::sync::atomic::Ordering::Relaxed),
+            crate::scheduler::CONTEXT_SWITCHES.load(core::sync::atomic::Ordering::Relaxed));
+        let _ = write!(logger, "The system is idle. It is now safe to power off.\n");
+        drop(logger);
+        crate::gui::present_now();
+    });
+}
+
+/// Logs one line to the boot screen and the kernel DebugLog window.
+pub fn klog_line(line: &str) {
+    _vga_print(format_args!("{}\n", line));
+}
+
 #[doc(hidden)]
 pub fn _vga_print(args: fmt::Arguments) {
     // Disable interrupts so a context switch doesn't split a log message in half
     x86_64::instructions::interrupts::without_interrupts(|| {
-        VGA_LOGGER.lock().write_fmt(args).unwrap();
+        let mut logger = VGA_LOGGER.lock();
+        logger.write_fmt(args).unwrap();
+        drop(logger);
+        // The panic screen draws straight to VRAM and mustn't allocate
+        if in_panic_mode() { return; }
+        // Also keep it in the DebugLog window's scrollback, unless the window
+        // manager itself is what's logging
+        if let Some(mut wm) = crate::window::WINDOW_MANAGER.try_lock() {
+            let mut sink = LogWindowSink(&mut wm);
+            let _ = sink.write_fmt(args);
+        }
+        // Before the timer ticks nobody presents, and early boot text must still show
+        if crate::time::UPTIME_MS.load(Ordering::Relaxed) == 0 { crate::gui::present_now(); }
     });
 }
 
+struct LogWindowSink<'a>(&'a mut crate::window::WindowManager);
+
+impl fmt::Write for LogWindowSink<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() { self.0.console_print(c); }
+        Ok(())
+    }
+}
+
 #[macro_export]
 macro_rules! vga_print {
     ($($arg:tt)*) => ($crate::vga_log::_vga_print(format_args!($($arg)*)));
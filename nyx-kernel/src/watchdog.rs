@@ -0,0 +1,129 @@
+use core::fmt::Write;
+use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use spin::Mutex;
+
+// ==========================================
+// DRIVER WATCHDOG
+// ==========================================
+// Drivers wait on hardware through spin_until, which gives up at a deadline
+// on the TSC clock rather than after a number of iterations, so a timeout
+// lasts as long on a fast CPU as on a slow one. While a wait runs it holds a
+// slot in WAITS (watch! covers loops that can't use spin_until), and the
+// BSP's timer tick warns, once per wait, about any slot older than STUCK_MS.
+// A spin_until that reaches its deadline says so itself, since most
+// deadlines are shorter than STUCK_MS. Either way a controller that never
+// answers turns into a line naming the driver and the operation, on serial
+// and in the DebugLog window, instead of a silently frozen machine. The tick
+// only runs once the scheduler is up and interrupts are on; timeouts are
+// reported from the start.
+
+/// How long a wait may run before the watchdog reports it. Only waits with
+/// longer deadlines (bulk transfers, disk commands) and watch! loops get
+/// this far; the rest have timed out first.
+pub const STUCK_MS: u64 = 2000;
+const SLOTS: usize = 16;
+
+#[derive(Clone, Copy)]
+struct Wait {
+    subsystem: &'static str,
+    operation: &'static str,
+    start_ms: u64,
+    reported: bool,
+}
+
+static WAITS: Mutex<[Option<Wait>; SLOTS]> = Mutex::new([None; SLOTS]);
+// Slots in use, so the tick doesn't touch the lock while nothing waits
+static ACTIVE: AtomicUsize = AtomicUsize::new(0);
+// One bit per core with a line going out, so a driver the logging itself
+// waits on (a GPU presenting the boot screen) can't report its timeout
+// recursively
+static REPORTING: AtomicU32 = AtomicU32::new(0);
+
+fn core_bit() -> u32 {
+    // Before the per-CPU area exists only the BSP runs
+    if x86_64::registers::model_specific::GsBase::read().as_u64() == 0 { return 1; }
+    1 << (crate::percpu::current().logical_id % 32) as u32
+}
+
+/// Frees its slot when the wait it covers ends.
+pub struct WaitGuard {
+    slot: Option<usize>,
+}
+
+impl Drop for WaitGuard {
+    fn drop(&mut self) {
+        if let Some(slot) = self.slot {
+            x86_64::instructions::interrupts::without_interrupts(|| WAITS.lock()[slot] = None);
+            ACTIVE.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Records a wait until the returned guard is dropped. With every slot taken
+/// the wait simply goes unwatched.
+pub fn enter(subsystem: &'static str, operation: &'static str) -> WaitGuard {
+    let start_ms = crate::time::monotonic_ms();
+    let slot = x86_64::instructions::interrupts::without_interrupts(|| {
+        let mut waits = WAITS.lock();
+        let slot = waits.iter().position(|w| w.is_none())?;
+        waits[slot] = Some(Wait { subsystem, operation, start_ms, reported: false });
+        Some(slot)
+    });
+    if slot.is_some() { ACTIVE.fetch_add(1, Ordering::Relaxed); }
+    WaitGuard { slot }
+}
+
+/// Watches the rest of the enclosing scope as one wait.
+#[macro_export]
+macro_rules! watch {
+    ($subsystem:expr, $operation:expr) => {
+        let _watch = $crate::watchdog::enter($subsystem, $operation);
+    };
+}
+
+/// Spins until `done` returns true or `timeout_ms` have passed, and says
+/// whether it came true.
+pub fn spin_until(subsystem: &'static str, operation: &'static str, timeout_ms: u64, mut done: impl FnMut() -> bool) -> bool {
+    crate::watch!(subsystem, operation);
+    let deadline = crate::time::monotonic_ms() + timeout_ms;
+    loop {
+        if done() { return true; }
+        // One last look, in case the deadline passed while this core was elsewhere
+        if crate::time::monotonic_ms() >= deadline {
+            if done() { return true; }
+            report(format_args!("[WATCHDOG] {}: {} timed out after {} ms", subsystem, operation, timeout_ms));
+            return false;
+        }
+        core::hint::spin_loop();
+    }
+}
+
+/// Sends one line to serial and the DebugLog window.
+fn report(args: core::fmt::Arguments) {
+    let bit = core_bit();
+    if REPORTING.fetch_or(bit, Ordering::Acquire) & bit != 0 { return; }
+    let mut line = crate::panic::StackBuf::<128>::new();
+    let _ = line.write_fmt(args);
+    crate::serial_println!("{}", line.as_str());
+    crate::vga_log::klog_line(line.as_str());
+    REPORTING.fetch_and(!bit, Ordering::Release);
+}
+
+/// Reports waits that have run past STUCK_MS. Called from the BSP's timer tick.
+pub fn check() {
+    if ACTIVE.load(Ordering::Relaxed) == 0 { return; }
+    // Reporting takes these locks; if the interrupted code holds one, the
+    // next tick tries again
+    if crate::serial::SERIAL1.is_locked() || crate::serial::BOOT_LOG.is_locked()
+        || crate::vga_log::VGA_LOGGER.is_locked() || crate::allocator::is_busy()
+        || REPORTING.load(Ordering::Relaxed) & core_bit() != 0 {
+        return;
+    }
+    let mut waits = match WAITS.try_lock() { Some(w) => w, None => return };
+    let now = crate::time::monotonic_ms();
+    for wait in waits.iter_mut().flatten() {
+        if wait.reported || now.saturating_sub(wait.start_ms) < STUCK_MS { continue; }
+        wait.reported = true;
+        report(format_args!("[WATCHDOG] {}: {} still waiting after {} ms", wait.subsystem, wait.operation, now - wait.start_ms));
+    }
+}
@@ -6,6 +6,9 @@ use crate::gui::{Painter, Rect, Color, turbo_copy};
 use crate::mouse::MouseState;
 use core::fmt::Write; 
 use bootloader_api::info::PixelFormat;
+use nyx_core::rect::{intersects, union, DirtyRegion};
+use nyx_core::wrap::{wrap_line, wrapped_rows};
+pub use nyx_core::rect::Area;
 
 lazy_static! {
     pub static ref WINDOW_MANAGER: Mutex<WindowManager> = Mutex::new(WindowManager::new());
@@ -16,6 +19,18 @@ const TITLE_BAR_HEIGHT: usize = 28;
 const SAFE_PADDING: usize = 40;
 const LINE_HEIGHT: usize = 36; 
 const CHAR_WIDTH: usize = 16; 
+// Log lines a DebugLog window keeps before dropping the oldest
+pub const LOG_LINES_CAP: usize = 1000;
+// Rows scrolled per wheel notch
+const WHEEL_ROWS: usize = 3;
+// Smallest a window can be resized to
+const MIN_WIN_W: usize = 300;
+const MIN_WIN_H: usize = 200;
+// Side of the resize grip in a window's bottom-right corner
+const GRIP_SIZE: usize = 16;
+// Taskbar buttons for closed DebugLog windows
+const TASK_BTN_W: usize = 200;
+const TASK_BTN_GAP: usize = 8;
 
 #[derive(Clone, PartialEq)]
 pub enum WindowType { Terminal, SystemMonitor, DebugLog }
@@ -27,7 +42,13 @@ pub struct Window {
     pub is_dragging: bool,
     pub drag_offset_x: usize, pub drag_offset_y: usize,
     pub content_color: Color,
+    // Unwrapped lines; wrapping happens at draw time from the current size
     pub buffer: Vec<String>,
+    pub max_lines: usize,
+    // Wrapped rows scrolled back from the bottom, 0 = following new output
+    pub scroll: usize,
+    // Terminal windows: the command line being typed (see kshell.rs)
+    pub input: String,
 }
 
 impl Window {
@@ -40,31 +61,101 @@ impl Window {
         Self {
             x, y, w, h, title: String::from(title), window_type: w_type,
             is_dragging: false, drag_offset_x: 0, drag_offset_y: 0,
-            content_color: color, buffer: Vec::new(),
+            content_color: color, buffer: Vec::new(), max_lines: LOG_LINES_CAP, scroll: 0,
+            input: String::new(),
         }
     }
 
+    fn text_cols(&self) -> usize { (self.w.saturating_sub(16) / CHAR_WIDTH).max(1) }
+    fn text_rows(&self) -> usize {
+        let rows = self.h.saturating_sub(TITLE_BAR_HEIGHT + 10) / LINE_HEIGHT;
+        // A Terminal's last row is its prompt
+        if self.window_type == WindowType::Terminal { rows.saturating_sub(1) } else { rows }
+    }
+
+    fn total_rows(&self, cols: usize) -> usize {
+        self.buffer.iter().map(|l| wrapped_rows(l, cols)).sum()
+    }
+
     pub fn append_char(&mut self, c: char) {
         if self.buffer.is_empty() { self.buffer.push(String::new()); }
-        
-        let max_chars = (self.w.saturating_sub(16)) / CHAR_WIDTH;
-        let available_height = self.h.saturating_sub(TITLE_BAR_HEIGHT + 10);
-        let max_lines = available_height / LINE_HEIGHT;
+        let cols = self.text_cols();
+        let rows_before = self.buffer.last().map_or(0, |l| wrapped_rows(l, cols));
 
         match c {
             '\n' => self.buffer.push(String::new()),
             '\x08' => { if let Some(line) = self.buffer.last_mut() { line.pop(); } },
-            _ => { 
-                if let Some(line) = self.buffer.last_mut() {
-                    if line.len() >= max_chars {
-                        self.buffer.push(String::from(c));
-                    } else {
-                        line.push(c); 
-                    }
-                } 
-            }
+            _ => { if let Some(line) = self.buffer.last_mut() { line.push(c); } }
+        }
+
+        // Keep a scrolled-back view on the same rows while output arrives
+        if self.scroll > 0 {
+            let rows_after: usize = self.buffer.iter().rev().take(if c == '\n' { 2 } else { 1 }).map(|l| wrapped_rows(l, cols)).sum();
+            self.scroll = (self.scroll + rows_after).saturating_sub(rows_before);
+        }
+        if self.buffer.len() > self.max_lines {
+            let excess = self.buffer.len() - self.max_lines;
+            self.buffer.drain(..excess);
+            self.scroll = self.scroll.min(self.total_rows(cols).saturating_sub(self.text_rows()));
         }
-        while self.buffer.len() > max_lines { self.buffer.remove(0); }
+    }
+
+    /// Scrolls the text by `rows` wrapped rows, positive = back towards older lines.
+    pub fn scroll_by(&mut self, rows: isize) {
+        let max_scroll = self.total_rows(self.text_cols()).saturating_sub(self.text_rows());
+        self.scroll = if rows >= 0 {
+            (self.scroll + rows as usize).min(max_scroll)
+        } else {
+            self.scroll.saturating_sub(rows.unsigned_abs()).min(max_scroll)
+        };
+    }
+
+    /// Appends a finished line, dropping the oldest past `max_lines`.
+    pub fn push_line(&mut self, line: String) {
+        self.buffer.push(line);
+        if self.buffer.len() > self.max_lines {
+            let excess = self.buffer.len() - self.max_lines;
+            self.buffer.drain(..excess);
+        }
+    }
+
+    /// PageUp / PageDown scroll a page; Terminal windows also take typing.
+    /// Returns whether the key was used.
+    pub fn handle_key(&mut self, c: char) -> bool {
+        let page = self.text_rows().saturating_sub(1).max(1) as isize;
+        match c {
+            crate::shell::KEY_PAGE_UP => self.scroll_by(page),
+            crate::shell::KEY_PAGE_DOWN => self.scroll_by(-page),
+            _ if self.window_type != WindowType::Terminal => return false,
+            '\n' => self.submit(),
+            '\x08' => { self.input.pop(); },
+            c if !c.is_control() && !('\u{E000}'..='\u{F8FF}').contains(&c) => self.input.push(c),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Echoes the typed command and hands it to the kworker; keys arrive in
+    /// interrupt context, which is no place for disk reads.
+    fn submit(&mut self) {
+        let line = core::mem::take(&mut self.input);
+        self.push_line(alloc::format!("> {}", line));
+        self.scroll = 0;
+        crate::workqueue::submit_job(move || {
+            let output = crate::kshell::run(&line);
+            x86_64::instructions::interrupts::without_interrupts(|| WINDOW_MANAGER.lock().command_output(output));
+        });
+    }
+
+    /// Output longer than the window starts out scrolled to the command
+    /// line, and PageDown pages through the rest.
+    fn show_output(&mut self, output: Vec<String>) {
+        let added = output.len() + 1;
+        for out in output { self.push_line(out); }
+
+        let cols = self.text_cols();
+        let new_rows: usize = self.buffer.iter().rev().take(added).map(|l| wrapped_rows(l, cols)).sum();
+        self.scroll = new_rows.saturating_sub(self.text_rows());
     }
 
     pub fn draw(&self, painter: &mut impl Painter, is_active: bool) {
@@ -84,19 +175,48 @@ impl Window {
         } else { Color::new(45, 45, 48) };
 
         painter.draw_rect(Rect::new(self.x, self.y, self.w, TITLE_BAR_HEIGHT), header_color);
-        painter.draw_string(self.x + 8, self.y + 6, &self.title, Color::WHITE);
+        painter.draw_string(self.x + 8, self.y + 6, &self.title, Color::WHITE, None);
 
         painter.draw_rect(Rect::new(self.x + self.w - 24, self.y + 4, 20, 20), Color::new(200, 60, 60));
-        painter.draw_string(self.x + self.w - 17, self.y + 4, "X", Color::WHITE);
+        painter.draw_string(self.x + self.w - 17, self.y + 4, "X", Color::WHITE, None);
 
         let start_y = self.y + TITLE_BAR_HEIGHT + 4;
-        let available_height = self.h.saturating_sub(TITLE_BAR_HEIGHT + 10);
-        let max_draw_lines = available_height / LINE_HEIGHT;
+        let cols = self.text_cols();
+        let max_draw_lines = self.text_rows();
+        if max_draw_lines == 0 { return; }
+
+        // Wrap from the newest line backwards until the scrolled-back view is covered
+        let mut rows: Vec<&str> = Vec::new();
+        let wanted = self.scroll + max_draw_lines;
+        for line in self.buffer.iter().rev() {
+            let mut chunks = wrap_line(line, cols);
+            while let Some(chunk) = chunks.pop() { rows.push(chunk); }
+            if rows.len() >= wanted { break; }
+        }
+        let skip = self.scroll.min(rows.len().saturating_sub(max_draw_lines));
+        let visible = rows.iter().skip(skip).take(max_draw_lines).collect::<Vec<_>>();
+        for (i, row) in visible.iter().rev().enumerate() {
+            painter.draw_string(self.x + 8, start_y + (i * LINE_HEIGHT), row, Color::WHITE, Some(self.content_color));
+        }
 
-        for (i, line) in self.buffer.iter().enumerate() {
-            if i >= max_draw_lines { break; }
-            painter.draw_string(self.x + 8, start_y + (i * LINE_HEIGHT), line, Color::WHITE);
+        if self.window_type == WindowType::Terminal {
+            // Keep the end of a long command line in view
+            let prompt = alloc::format!("> {}_", self.input);
+            let overflow = prompt.chars().count().saturating_sub(cols);
+            let shown: String = prompt.chars().skip(overflow).collect();
+            painter.draw_string(self.x + 8, start_y + max_draw_lines * LINE_HEIGHT, &shown, Color::new(0, 200, 255), Some(self.content_color));
         }
+
+        // Scrolled back: mark the bottom edge so it's clear newer output is hidden
+        if skip > 0 {
+            painter.draw_rect(Rect::new(self.x, self.y + self.h - 3, self.w, 3), header_color);
+        }
+
+        // Resize grip: two steps up to the corner
+        let (gx, gy) = (self.x + self.w - GRIP_SIZE, self.y + self.h - GRIP_SIZE);
+        let grip_color = Color::new(120, 120, 120);
+        painter.draw_rect(Rect::new(gx + 10, gy + 4, 3, 10), grip_color);
+        painter.draw_rect(Rect::new(gx + 4, gy + 10, 9, 3), grip_color);
     }
 
     pub fn is_close_hit(&self, mx: usize, my: usize) -> bool {
@@ -108,37 +228,82 @@ impl Window {
     pub fn is_body_hit(&self, mx: usize, my: usize) -> bool {
         mx >= self.x && mx <= self.x + self.w && my >= self.y && my <= self.y + self.h
     }
+    pub fn is_grip_hit(&self, mx: usize, my: usize) -> bool {
+        mx >= self.x + self.w - GRIP_SIZE && mx <= self.x + self.w && my >= self.y + self.h - GRIP_SIZE && my <= self.y + self.h
+    }
+
+    /// Everything draw() touches, border and shadow included, as (x0, y0, x1, y1).
+    pub fn bounds(&self) -> Area {
+        (self.x.saturating_sub(2), self.y.saturating_sub(2), self.x + self.w + 6, self.y + self.h + 6)
+    }
 }
 
+/// What a held left button is doing to the topmost window.
+#[derive(Clone, Copy, PartialEq)]
+enum Grab { Move, Resize }
+
 pub struct WindowManager {
     windows: Vec<Window>,
+    // Closed DebugLog windows, still collecting the log, each with a taskbar
+    // button that brings it back
+    hidden: Vec<Window>,
+    grab: Option<Grab>,
     prev_left: bool, prev_right: bool,
+    // The framebuffer the kernel paints into, which is the primary display
     pub screen_width: usize, pub screen_height: usize,
     pub desktop_buffer: Vec<u32>, 
+    // Screen area that changed since the last draw; nothing else is repainted
+    damage: DirtyRegion,
 }
 
 impl WindowManager {
     pub fn new() -> Self { 
         Self { 
-            windows: Vec::new(), prev_left: false, prev_right: false, 
+            windows: Vec::new(), hidden: Vec::new(), grab: None, prev_left: false, prev_right: false, 
             screen_width: 1024, screen_height: 768,
-            desktop_buffer: Vec::new(), 
+            desktop_buffer: Vec::new(), damage: DirtyRegion::new(),
         }
     }
 
+    /// Marks an area for the next draw.
+    pub fn damage(&mut self, area: Area) {
+        self.damage.mark_area(area, self.screen_width, self.screen_height);
+    }
+
+    pub fn damage_all(&mut self) {
+        self.damage.mark_all(self.screen_width, self.screen_height);
+    }
+
     pub fn set_resolution(&mut self, w: usize, h: usize) { 
         self.screen_width = w; 
         self.screen_height = h; 
         self.desktop_buffer.resize(w * h, 0x00000030); 
+        self.damage_all();
     }
 
-    pub fn add(&mut self, window: Window) { self.windows.push(window); }
+    /// (x, y, w, h) for a window `percent` of the size of `display`, centred
+    /// in the part of it above the taskbar (which only the primary has). The
+    /// kernel draws into the framebuffer 1:1, so displays it can't show, the
+    /// virtual ones, get the window on the primary instead.
+    pub fn place(&self, display: usize, percent: usize) -> (usize, usize, usize, usize) {
+        let d = crate::display::get(display).filter(|d| !d.is_virtual)
+            .unwrap_or(crate::display::Display { x: 0, y: 0, w: self.screen_width, h: self.screen_height, is_virtual: false });
+        let usable_h = if (d.x, d.y) == (0, 0) { d.h.saturating_sub(TASKBAR_HEIGHT) } else { d.h };
+        let (w, h) = (d.w * percent / 100, usable_h * percent / 100);
+        (d.x + (d.w - w) / 2, d.y + (usable_h - h) / 2, w, h)
+    }
+
+    pub fn add(&mut self, window: Window) {
+        self.damage(window.bounds());
+        self.windows.push(window);
+    }
     
     pub fn put_desktop_pixel(&mut self, x: usize, y: usize, color: u32) {
         if x < self.screen_width && y < self.screen_height {
             let idx = y * self.screen_width + x;
             if idx < self.desktop_buffer.len() {
                 self.desktop_buffer[idx] = color;
+                self.damage((x, y, x + 1, y + 1));
             }
         }
     }
@@ -147,17 +312,180 @@ impl WindowManager {
         for win in self.windows.iter_mut().rev() {
             if win.window_type == WindowType::DebugLog {
                 win.append_char(c);
+                let area = win.bounds();
+                self.damage(area);
                 return;
             }
         }
+        // A closed log keeps its lines for when it's reopened
+        if let Some(win) = self.hidden.iter_mut().rev().find(|w| w.window_type == WindowType::DebugLog) {
+            win.append_char(c);
+        }
+    }
+
+    /// The strip along the bottom of the screen holding the hidden windows' buttons.
+    fn taskbar_area(&self) -> Area {
+        (0, self.screen_height.saturating_sub(TASKBAR_HEIGHT), self.screen_width, self.screen_height)
     }
 
+    fn task_button(&self, i: usize) -> Rect {
+        let (_, y0, _, _) = self.taskbar_area();
+        Rect::new(TASK_BTN_GAP + i * (TASK_BTN_W + TASK_BTN_GAP), y0 + 6, TASK_BTN_W, TASKBAR_HEIGHT - 12)
+    }
+
+    /// Moves the window at `i` to the top, where it takes the keys.
+    fn raise(&mut self, i: usize) {
+        if i + 1 == self.windows.len() { return; }
+        // Both the old and the new top window change their border and header
+        let below = self.windows.last().map(|w| w.bounds());
+        let win = self.windows.remove(i);
+        self.damage(win.bounds());
+        self.windows.push(win);
+        if let Some(area) = below { self.damage(area); }
+    }
+
+    /// Takes the window at `i` off the screen; a DebugLog is only hidden.
+    fn close(&mut self, i: usize) {
+        let win = self.windows.remove(i);
+        self.damage(win.bounds());
+        if i == self.windows.len() {
+            if let Some(area) = self.windows.last().map(|w| w.bounds()) { self.damage(area); }
+        }
+        if win.window_type == WindowType::DebugLog {
+            self.hidden.push(win);
+            let area = self.taskbar_area();
+            self.damage(area);
+        }
+    }
+
+    fn reopen(&mut self, i: usize) {
+        let win = self.hidden.remove(i);
+        let below = self.windows.last().map(|w| w.bounds());
+        self.add(win);
+        if let Some(area) = below { self.damage(area); }
+        let area = self.taskbar_area();
+        self.damage(area);
+    }
+
+    /// Starts whatever a left click at (mx, my) does: a taskbar button
+    /// reopens its window, and on a window the grip resizes, the X closes and
+    /// the title bar moves it. Whichever window is hit comes to the top.
+    fn press(&mut self, mx: usize, my: usize) {
+        if !self.hidden.is_empty() {
+            let hit = (0..self.hidden.len()).find(|&i| {
+                let b = self.task_button(i);
+                mx >= b.x && mx < b.x + b.w && my >= b.y && my < b.y + b.h
+            });
+            if let Some(i) = hit { self.reopen(i); return; }
+        }
+
+        let i = match self.windows.iter().rposition(|w| w.is_body_hit(mx, my)) { Some(i) => i, None => return };
+        let win = &self.windows[i];
+        let grab = if win.is_grip_hit(mx, my) {
+            Some(Grab::Resize)
+        } else if win.is_close_hit(mx, my) {
+            self.close(i);
+            return;
+        } else if win.is_header_hit(mx, my) {
+            Some(Grab::Move)
+        } else { None };
+
+        self.raise(i);
+        let win = self.windows.last_mut().unwrap();
+        // Move keeps the pointer's spot on the title bar; resize its spot
+        // relative to the bottom-right corner
+        (win.drag_offset_x, win.drag_offset_y) = match grab {
+            Some(Grab::Resize) => ((win.x + win.w).saturating_sub(mx), (win.y + win.h).saturating_sub(my)),
+            _ => (mx.saturating_sub(win.x), my.saturating_sub(win.y)),
+        };
+        win.is_dragging = grab.is_some();
+        self.grab = grab;
+    }
+
+    /// Follows the pointer with the grabbed (topmost) window, kept within the
+    /// screen above the taskbar.
+    fn drag(&mut self, grab: Grab, mx: usize, my: usize) {
+        let (screen_w, usable_h) = (self.screen_width, self.screen_height.saturating_sub(TASKBAR_HEIGHT));
+        let win = match self.windows.last_mut() { Some(w) => w, None => return };
+        let before = win.bounds();
+        match grab {
+            Grab::Move => {
+                win.x = mx.saturating_sub(win.drag_offset_x).min(screen_w.saturating_sub(win.w));
+                win.y = my.saturating_sub(win.drag_offset_y).min(usable_h.saturating_sub(win.h));
+            },
+            Grab::Resize => {
+                // The minimum wins over the screen edge
+                let w = (mx + win.drag_offset_x).saturating_sub(win.x);
+                let h = (my + win.drag_offset_y).saturating_sub(win.y);
+                win.w = w.min(screen_w.saturating_sub(win.x)).max(MIN_WIN_W);
+                win.h = h.min(usable_h.saturating_sub(win.y)).max(MIN_WIN_H);
+                // Rows rewrap to the new width; keep the scroll in range
+                win.scroll_by(0);
+            },
+        }
+        let after = win.bounds();
+        if after != before {
+            self.damage(before);
+            self.damage(after);
+        }
+    }
+
+    /// `mouse.wheel` is taken as the notches since the last update.
     pub fn update(&mut self, mouse: &MouseState) {
         let click_l = mouse.left_click && !self.prev_left;
         self.prev_left = mouse.left_click; self.prev_right = mouse.right_click;
+        let (mx, my) = (mouse.x, mouse.y);
+
+        if !mouse.left_click {
+            if self.grab.take().is_some() {
+                if let Some(win) = self.windows.last_mut() { win.is_dragging = false; }
+            }
+        } else if click_l {
+            self.press(mx, my);
+        } else if let Some(grab) = self.grab {
+            self.drag(grab, mx, my);
+        }
+
+        if mouse.wheel != 0 {
+            if let Some(win) = self.windows.iter_mut().rev().find(|w| w.is_body_hit(mx, my)) {
+                win.scroll_by(mouse.wheel as isize * WHEEL_ROWS as isize);
+                let area = win.bounds();
+                self.damage(area);
+            }
+        }
+    }
+
+    /// Delivers a finished kernel shell command to the Terminal that ran it.
+    pub fn command_output(&mut self, output: Vec<String>) {
+        let win = match self.windows.iter_mut().rev().find(|w| w.window_type == WindowType::Terminal) {
+            Some(w) => w,
+            None => return,
+        };
+        win.show_output(output);
+        let area = win.bounds();
+        self.damage(area);
+    }
+
+    /// Kernel windows only take input while the kernel is drawing the screen.
+    pub fn has_focus(&self) -> bool {
+        crate::gui::kernel_owns_screen() && !self.windows.is_empty()
+    }
+
+    /// Keys go to the focused (topmost) window; returns whether it used the key.
+    pub fn handle_key(&mut self, c: char) -> bool {
+        let win = match self.windows.last_mut() { Some(w) => w, None => return false };
+        if !win.handle_key(c) { return false; }
+        let area = win.bounds();
+        self.damage(area);
+        true
     }
 
-    pub fn draw(&self, painter: &mut crate::gui::BackBuffer) {
+    /// Repaints only what was damaged since the last call.
+    pub fn draw(&mut self, painter: &mut crate::gui::BackBuffer) {
+        let area = match self.damage.take() { Some(a) => a, None => return };
+        let (x0, y0, x1, y1) = area;
+        painter.mark_region(x0, y0, x1 - x0, y1 - y0);
+
         if self.desktop_buffer.len() == self.screen_width * self.screen_height {
             let stride = painter.info.stride;
             let width = self.screen_width;
@@ -167,25 +495,25 @@ impl WindowManager {
 
             match bpp {
                 4 => {
-                    for y in 0..height {
-                        let src_idx = y * width;
-                        let dest_offset = (y * stride) * 4;
+                    for y in y0..y1.min(height) {
+                        let src_idx = y * width + x0;
+                        let dest_offset = (y * stride + x0) * 4;
                         
-                        if src_idx < self.desktop_buffer.len() && dest_offset < painter.buffer.len() {
+                        if src_idx < self.desktop_buffer.len() && dest_offset + (x1 - x0) * 4 <= painter.buffer.len() {
                             unsafe {
                                 let src_ptr = self.desktop_buffer.as_ptr().add(src_idx) as *const u8;
                                 let dest_ptr = painter.buffer.as_mut_ptr().add(dest_offset);
-                                turbo_copy(dest_ptr, src_ptr, width * 4);
+                                turbo_copy(dest_ptr, src_ptr, (x1 - x0) * 4);
                             }
                         }
                     }
                 },
                 3 => {
-                    for y in 0..height {
+                    for y in y0..y1.min(height) {
                         let src_start = y * width;
                         let dest_start = (y * stride) * 3;
                         
-                        for x in 0..width {
+                        for x in x0..x1.min(width) {
                             let color = self.desktop_buffer[src_start + x];
                             let dest_idx = dest_start + (x * 3);
                             
@@ -213,11 +541,65 @@ impl WindowManager {
                 _ => {}
             }
         } else {
-            painter.clear(Color::new(0, 0, 30));
+            painter.draw_rect(Rect::new(x0, y0, x1 - x0, y1 - y0), Color::new(0, 0, 30));
         }
 
+        // A window drawn in full may cover more than the damage, so whatever
+        // sits above it has to be redrawn too
+        let mut redrawn = area;
         for (i, w) in self.windows.iter().enumerate() { 
-             w.draw(painter, i == self.windows.len()-1); 
+            if !intersects(redrawn, w.bounds()) { continue; }
+            w.draw(painter, i == self.windows.len()-1); 
+            redrawn = union(redrawn, w.bounds());
+        }
+
+        if !self.hidden.is_empty() && intersects(redrawn, self.taskbar_area()) {
+            let (x0, y0, x1, y1) = self.taskbar_area();
+            painter.draw_rect(Rect::new(x0, y0, x1 - x0, y1 - y0), Color::new(30, 30, 35));
+            let max_chars = (TASK_BTN_W - 16) / CHAR_WIDTH;
+            for (i, win) in self.hidden.iter().enumerate() {
+                let b = self.task_button(i);
+                if b.x + b.w > x1 { break; }
+                painter.draw_rect(Rect::new(b.x, b.y, b.w, b.h), Color::new(60, 60, 70));
+                let label: String = win.title.chars().take(max_chars).collect();
+                painter.draw_string(b.x + 8, b.y + 4, &label, Color::WHITE, None);
+            }
         }
     }
-}
\ No newline at end of file
+}
+
+/// Feeds the pointer to the kernel windows and paints whatever changed
+/// into the back buffer. Runs from the present tick, so it passes on a tick
+/// where the manager, the mouse or the heap is held by the code it
+/// interrupted.
+pub fn redraw() {
+    if crate::allocator::is_busy() { return; }
+    let mut wm = match WINDOW_MANAGER.try_lock() { Some(wm) => wm, None => return };
+    if wm.windows.is_empty() && wm.hidden.is_empty() { return; }
+    if let Some(mut m) = crate::mouse::MOUSE_STATE.try_lock() {
+        // Nobody in userspace reads the wheel while the kernel has the screen
+        let mouse = MouseState { wheel: core::mem::take(&mut m.wheel), ..*m };
+        drop(m);
+        wm.update(&mouse);
+    }
+    if wm.damage.is_clean() { return; }
+    unsafe {
+        if let Some(back) = &mut crate::gui::BACK_BUFFER { wm.draw(back); }
+    }
+    crate::gui::mark_dirty();
+}
+
+/// Opens a kernel Terminal in the middle of the screen, so the machine
+/// stays usable (see kshell.rs) once userspace is gone.
+pub fn open_kernel_shell(reason: &str) {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let mut wm = WINDOW_MANAGER.lock();
+        let (x, y, w, h) = wm.place(0, 75);
+        let mut shell = Window::new(x, y, w, h, "Kernel Shell", WindowType::Terminal);
+        shell.push_line(String::from(reason));
+        shell.push_line(String::from("Type 'help' for the commands."));
+        wm.add(shell);
+        // The whole screen, since what was there before belongs to nobody now
+        wm.damage_all();
+    });
+}
@@ -0,0 +1,77 @@
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use core::sync::atomic::{AtomicU64, Ordering};
+use spin::Mutex;
+
+// ==========================================
+// KERNEL WORK QUEUE
+// ==========================================
+// Deferred work (write-behind file writes and the like) queued by syscalls
+// and drivers and run by one kernel worker task, in submission order. Jobs
+// never run in interrupt context. The worker runs a bounded batch per slice
+// and yields between batches, so a burst of slow jobs can't hog its core;
+// with nothing queued it sleeps until submit_job wakes it.
+
+type Job = Box<dyn FnOnce() + Send>;
+
+// Jobs run back to back before the worker yields
+const JOBS_PER_SLICE: usize = 8;
+// Idle re-check interval, in case a wakeup is lost to a race with the scheduler
+const IDLE_SLEEP_MS: u64 = 50;
+
+static QUEUE: Mutex<VecDeque<Job>> = Mutex::new(VecDeque::new());
+static WORKER_PID: AtomicU64 = AtomicU64::new(0);
+// Queued but not yet finished, and finished since boot (Syscall 524)
+pub static PENDING_JOBS: AtomicU64 = AtomicU64::new(0);
+pub static COMPLETED_JOBS: AtomicU64 = AtomicU64::new(0);
+
+/// Starts the worker on this core. Call once during boot.
+pub fn init() {
+    let pid = crate::scheduler::spawn_kernel_task("kworker", worker_loop).expect("Failed to create kworker");
+    WORKER_PID.store(pid, Ordering::SeqCst);
+}
+
+/// Queues `job` for the worker task and wakes it.
+pub fn submit_job<F: FnOnce() + Send + 'static>(job: F) {
+    PENDING_JOBS.fetch_add(1, Ordering::Relaxed);
+    x86_64::instructions::interrupts::without_interrupts(|| QUEUE.lock().push_back(Box::new(job)));
+    wake_worker();
+}
+
+fn wake_worker() {
+    crate::scheduler::wake_task(WORKER_PID.load(Ordering::Relaxed));
+}
+
+fn pop_job() -> Option<Job> {
+    x86_64::instructions::interrupts::without_interrupts(|| QUEUE.lock().pop_front())
+}
+
+/// Blocks the worker until `IDLE_SLEEP_MS` pass or a job arrives.
+fn sleep_until_work() {
+    x86_64::instructions::interrupts::disable();
+    if !QUEUE.lock().is_empty() {
+        x86_64::instructions::interrupts::enable();
+        return;
+    }
+    crate::scheduler::block_current(IDLE_SLEEP_MS);
+}
+
+fn worker_loop() {
+    loop {
+        let mut ran = 0;
+        while ran < JOBS_PER_SLICE {
+            let job = match pop_job() { Some(j) => j, None => break };
+            job();
+            PENDING_JOBS.fetch_sub(1, Ordering::Relaxed);
+            COMPLETED_JOBS.fetch_add(1, Ordering::Relaxed);
+            ran += 1;
+        }
+
+        if ran == JOBS_PER_SLICE {
+            // More may be waiting; let everything else run first
+            unsafe { core::arch::asm!("int 0x41"); }
+        } else {
+            sleep_until_work();
+        }
+    }
+}
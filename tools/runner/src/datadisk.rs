@@ -0,0 +1,146 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::process::Command;
+use nyx_core::gpt::{self, GptPartition};
+
+// ==========================================
+// DATA DISK (--disk)
+// ==========================================
+// A disk that outlives the run, so whatever the kernel writes to it is still
+// there next boot. One that doesn't exist yet is made here: a raw file with
+// a GPT (built by nyx-core, the same code the kernel reads tables with) and
+// a single FAT32 partition. A .qcow2 name gets the raw image converted by
+// qemu-img afterwards. Existing disks are attached as they are.
+
+const SECTOR: u64 = 512;
+// Partitions start on a 1 MiB boundary, as every partitioning tool does
+const ALIGN_SECTORS: u64 = 2048;
+const LABEL: &str = "NYX-DATA";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Format { Raw, Qcow2 }
+
+impl Format {
+    pub fn of(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("qcow2") => Format::Qcow2,
+            _ => Format::Raw,
+        }
+    }
+
+    pub fn qemu_name(self) -> &'static str {
+        match self { Format::Raw => "raw", Format::Qcow2 => "qcow2" }
+    }
+}
+
+/// Creates `path` as a `size_mib` MiB disk with one FAT32 partition, unless
+/// it already exists. Says whether it made one.
+pub fn ensure(path: &Path, size_mib: u64) -> io::Result<bool> {
+    if path.exists() { return Ok(false); }
+    let format = Format::of(path);
+    let raw_path = match format {
+        Format::Raw => path.to_path_buf(),
+        Format::Qcow2 => path.with_extension("raw.tmp"),
+    };
+
+    qemu_img(&["create", "-f", "raw", &raw_path.to_string_lossy(), &format!("{}M", size_mib)])?;
+    let made = partition_and_format(&raw_path, size_mib * 1024 * 1024 / SECTOR);
+    if made.is_ok() && format == Format::Qcow2 {
+        let converted = qemu_img(&["convert", "-f", "raw", "-O", "qcow2", &raw_path.to_string_lossy(), &path.to_string_lossy()]);
+        let _ = fs::remove_file(&raw_path);
+        converted?;
+    } else if made.is_err() {
+        let _ = fs::remove_file(&raw_path);
+    }
+    made.map(|_| true)
+}
+
+fn qemu_img(args: &[&str]) -> io::Result<()> {
+    let status = Command::new("qemu-img").args(args).status()
+        .map_err(|e| io::Error::new(e.kind(), format!("could not run qemu-img: {}", e)))?;
+    if status.success() { Ok(()) } else { Err(io::Error::other(format!("qemu-img {} failed: {}", args[0], status))) }
+}
+
+fn partition_and_format(path: &Path, disk_sectors: u64) -> io::Result<()> {
+    let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+    let first = ALIGN_SECTORS.max(gpt::first_usable_lba(SECTOR as usize));
+    let last = gpt::last_usable_lba(disk_sectors, SECTOR as usize);
+    if last <= first { return Err(io::Error::other("disk is too small for a partition")); }
+
+    let partition = GptPartition { type_guid: gpt::BASIC_DATA_GUID, first_lba: first, last_lba: last, name: LABEL.into() };
+    for (lba, bytes) in gpt::build_table(disk_sectors, SECTOR as usize, disk_guid(), &[partition]) {
+        file.seek(SeekFrom::Start(lba * SECTOR))?;
+        file.write_all(&bytes)?;
+    }
+
+    let len = (last - first + 1) * SECTOR;
+    let mut label = [b' '; 11];
+    label[..LABEL.len()].copy_from_slice(LABEL.as_bytes());
+    let options = fatfs::FormatVolumeOptions::new()
+        .fat_type(fatfs::FatType::Fat32)
+        .total_sectors((len / SECTOR) as u32)
+        .volume_label(label);
+    fatfs::format_volume(Slice::new(file, first * SECTOR, len)?, options)
+}
+
+/// Random enough for telling disks apart: the time and the process id, with
+/// the version 4 bits set.
+fn disk_guid() -> [u8; 16] {
+    let nanos = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_nanos());
+    let mut guid = ((nanos << 32) ^ std::process::id() as u128).to_le_bytes();
+    guid[7] = (guid[7] & 0x0F) | 0x40;
+    guid[8] = (guid[8] & 0x3F) | 0x80;
+    guid
+}
+
+/// The part of a file a partition covers, as a stream of its own.
+struct Slice {
+    file: File,
+    start: u64,
+    len: u64,
+    pos: u64,
+}
+
+impl Slice {
+    fn new(mut file: File, start: u64, len: u64) -> io::Result<Self> {
+        file.seek(SeekFrom::Start(start))?;
+        Ok(Slice { file, start, len, pos: 0 })
+    }
+}
+
+impl Read for Slice {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let take = buf.len().min(self.len.saturating_sub(self.pos) as usize);
+        let n = self.file.read(&mut buf[..take])?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Write for Slice {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let left = self.len.saturating_sub(self.pos) as usize;
+        if left == 0 && !buf.is_empty() { return Err(io::Error::new(io::ErrorKind::WriteZero, "write past the partition")); }
+        let n = self.file.write(&buf[..buf.len().min(left)])?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> { self.file.flush() }
+}
+
+impl Seek for Slice {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(p) => Some(p),
+            SeekFrom::End(d) => self.len.checked_add_signed(d),
+            SeekFrom::Current(d) => self.pos.checked_add_signed(d),
+        };
+        let target = target.filter(|&t| t <= self.len)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "seek outside the partition"))?;
+        self.file.seek(SeekFrom::Start(self.start + target))?;
+        self.pos = target;
+        Ok(target)
+    }
+}
@@ -0,0 +1,121 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// ==========================================
+// HEADLESS TEST MODE (--test)
+// ==========================================
+// Boots the image with no display, the kernel's COM1 on QEMU's stdio and an
+// isa-debug-exit device, then plays SCRIPT against the serial console:
+// wait for a line containing some text, type a command, and so on. The
+// last command is `selftest --exit`, which ends QEMU with the result, so the
+// run passes when every expected line showed up in time and QEMU exited
+// with the pass status. Everything the kernel prints is copied to stdout,
+// and to the --serial-log file if there is one, so a failed run can be read
+// afterwards.
+
+/// QEMU's exit status for a value v written to isa-debug-exit is (v << 1) | 1,
+/// so these mirror kshell's QEMU_EXIT_PASS (0x10) and QEMU_EXIT_FAIL (0x11)
+const EXIT_PASS: i32 = 0x21;
+const EXIT_FAIL: i32 = 0x23;
+/// The whole run, boot included, gets this long
+pub const TIMEOUT: Duration = Duration::from_secs(120);
+/// Printed by the panic screen; nothing after it is worth waiting for
+const PANIC_MARKER: &str = "[PANIC]";
+
+pub enum Step {
+    /// Wait for a line containing this text
+    Expect(&'static str),
+    /// Type this line into the serial console
+    Send(&'static str),
+}
+
+pub const SCRIPT: &[Step] = &[
+    Step::Expect("[BOOT] NyxOS Kernel Starting..."),
+    Step::Expect("[FS] Mounted ext4"),
+    Step::Expect("[SERIAL] Console ready"),
+    Step::Send("mountinfo"),
+    Step::Expect("/ on "),
+    Step::Send("selftest --exit"),
+    Step::Expect("SELFTEST: PASS"),
+];
+
+/// The QEMU arguments test mode adds to the usual ones.
+pub fn add_test_args(cmd: &mut Command) {
+    cmd.arg("-display").arg("none");
+    cmd.arg("-device").arg("isa-debug-exit,iobase=0xf4,iosize=0x04");
+    // A triple fault would otherwise reboot into the same failure until the timeout
+    cmd.arg("-no-reboot");
+}
+
+/// Runs `qemu` through `script` and says what went wrong, if anything.
+pub fn run(mut qemu: Command, script: &[Step], timeout: Duration, log: Option<&Path>) -> Result<(), String> {
+    let deadline = Instant::now() + timeout;
+    let mut log = match log {
+        Some(path) => Some(File::create(path).map_err(|e| format!("could not create {}: {}", path.display(), e))?),
+        None => None,
+    };
+    let mut child = qemu.stdin(Stdio::piped()).stdout(Stdio::piped()).spawn()
+        .map_err(|e| format!("could not start QEMU: {}", e))?;
+    let mut stdin = child.stdin.take().unwrap();
+    let stdout = child.stdout.take().unwrap();
+
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        // The kernel ends lines with CR LF; bytes that aren't UTF-8 are kept as U+FFFD
+        for line in BufReader::new(stdout).split(b'\n').map_while(Result::ok) {
+            let line = String::from_utf8_lossy(&line).trim_end_matches('\r').to_string();
+            println!("{}", line);
+            if let Some(log) = log.as_mut() { let _ = writeln!(log, "{}", line); }
+            if tx.send(line).is_err() { break; }
+        }
+    });
+
+    let result = (|| {
+        for step in script {
+            match step {
+                Step::Send(command) => {
+                    writeln!(stdin, "{}", command).and_then(|_| stdin.flush())
+                        .map_err(|e| format!("could not type '{}': {}", command, e))?;
+                }
+                Step::Expect(text) => loop {
+                    let left = deadline.saturating_duration_since(Instant::now());
+                    match rx.recv_timeout(left) {
+                        Ok(line) if line.contains(text) => break,
+                        Ok(line) if line.contains(PANIC_MARKER) => return Err(format!("kernel panicked waiting for '{}'", text)),
+                        Ok(_) => {}
+                        Err(mpsc::RecvTimeoutError::Timeout) => return Err(format!("timed out waiting for '{}'", text)),
+                        Err(mpsc::RecvTimeoutError::Disconnected) => return Err(format!("QEMU exited before '{}'", text)),
+                    }
+                },
+            }
+        }
+        Ok(())
+    })();
+    if let Err(e) = result {
+        let _ = child.kill();
+        let _ = child.wait();
+        return Err(e);
+    }
+
+    // The script is done; QEMU should be on its way out
+    loop {
+        if let Some(status) = child.try_wait().map_err(|e| e.to_string())? {
+            return match status.code() {
+                Some(EXIT_PASS) => Ok(()),
+                Some(EXIT_FAIL) => Err(String::from("selftest reported a failure")),
+                other => Err(format!("QEMU exited with {:?} instead of the selftest status", other)),
+            };
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(String::from("QEMU was still running when the time ran out"));
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+}
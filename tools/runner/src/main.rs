@@ -1,33 +1,179 @@
 use std::{env, process::Command, path::PathBuf};
-use bootloader::UefiBoot;
+use bootloader::{BiosBoot, UefiBoot};
 
-fn main() {
+mod datadisk;
+mod harness;
+
+const USAGE: &str = "\
+usage: runner <kernel> [options] [-- <qemu args>...]
+
+Builds a boot image around the kernel and runs it in QEMU.
+
+  --uefi               boot through OVMF (default)
+  --bios               boot through the legacy BIOS loader
+  --ovmf <file>        OVMF firmware to use [/usr/share/OVMF/OVMF_CODE.fd]
+  --disk <file>        attach a disk that persists between runs; one that
+                       doesn't exist is created with a FAT32 partition
+                       (.qcow2 names are created as qcow2, anything else raw)
+  --disk-if <if>       nvme (default) or ahci
+  --disk-size <MiB>    size of a newly created disk [256]
+  --kvm                use KVM acceleration
+  --serial-log <file>  write COM1 to a file instead of the terminal
+  --usb                add an xHCI controller with a USB keyboard and mouse
+  --test               boot headless and run the serial self-test script
+  --help               show this text
+
+Anything after -- is passed to QEMU as it is.";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum DiskInterface { Nvme, Ahci }
+
+struct Options {
+    kernel: PathBuf,
+    bios: bool,
+    ovmf: PathBuf,
+    disk: Option<PathBuf>,
+    disk_if: DiskInterface,
+    disk_size_mib: u64,
+    kvm: bool,
+    serial_log: Option<PathBuf>,
+    usb: bool,
+    test: bool,
+    qemu_args: Vec<String>,
+}
+
+fn parse_args() -> Result<Options, String> {
     let mut args = env::args().skip(1);
-    let kernel_binary = args.next().expect("Kernel binary path not received");
-    let kernel_path = PathBuf::from(&kernel_binary);
+    let mut opts = Options {
+        kernel: PathBuf::new(),
+        bios: false,
+        ovmf: PathBuf::from("/usr/share/OVMF/OVMF_CODE.fd"),
+        disk: None,
+        disk_if: DiskInterface::Nvme,
+        disk_size_mib: 256,
+        kvm: false,
+        serial_log: None,
+        usb: false,
+        test: false,
+        qemu_args: Vec::new(),
+    };
+    let mut kernel = None;
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or_else(|| format!("{} needs a value", name));
+        match arg.as_str() {
+            "--uefi" => opts.bios = false,
+            "--bios" => opts.bios = true,
+            "--ovmf" => opts.ovmf = PathBuf::from(value("--ovmf")?),
+            "--disk" => opts.disk = Some(PathBuf::from(value("--disk")?)),
+            "--disk-if" => opts.disk_if = match value("--disk-if")?.as_str() {
+                "nvme" => DiskInterface::Nvme,
+                "ahci" => DiskInterface::Ahci,
+                other => return Err(format!("--disk-if: '{}' is not nvme or ahci", other)),
+            },
+            "--disk-size" => opts.disk_size_mib = value("--disk-size")?.parse().map_err(|_| String::from("--disk-size takes a number of MiB"))?,
+            "--kvm" => opts.kvm = true,
+            "--serial-log" => opts.serial_log = Some(PathBuf::from(value("--serial-log")?)),
+            "--usb" => opts.usb = true,
+            "--test" => opts.test = true,
+            "--help" | "-h" => { println!("{}", USAGE); std::process::exit(0); }
+            "--" => { opts.qemu_args.extend(args.by_ref()); }
+            _ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+            _ if kernel.is_none() => kernel = Some(PathBuf::from(arg)),
+            _ => return Err(format!("unexpected argument {}", arg)),
+        }
+    }
+    opts.kernel = kernel.ok_or("Kernel binary path not received")?;
+    Ok(opts)
+}
+
+fn main() {
+    let opts = parse_args().unwrap_or_else(|e| {
+        eprintln!("runner: {}\n\n{}", e, USAGE);
+        std::process::exit(2);
+    });
+    let kernel_path = &opts.kernel;
 
-    // 1. Create UEFI Image (Required for Dell G3 GPT)
-    let image_path = kernel_path.with_extension("efi.img");
-    let boot = UefiBoot::new(&kernel_path);
-    boot.create_disk_image(&image_path).expect("Failed to create UEFI image");
+    // 1. Create the boot image (UEFI is required for Dell G3 GPT)
+    let image_path = if opts.bios {
+        let image_path = kernel_path.with_extension("bios.img");
+        BiosBoot::new(kernel_path).create_disk_image(&image_path).expect("Failed to create BIOS image");
+        image_path
+    } else {
+        let image_path = kernel_path.with_extension("efi.img");
+        UefiBoot::new(kernel_path).create_disk_image(&image_path).expect("Failed to create UEFI image");
+        image_path
+    };
 
     println!("--------------------------------------------------");
-    println!("UEFI IMAGE CREATED: {}", image_path.display());
+    println!("{} IMAGE CREATED: {}", if opts.bios { "BIOS" } else { "UEFI" }, image_path.display());
     println!("--------------------------------------------------");
 
     // Prevent QEMU from launching in GitHub Actions to avoid hangs/crashes
-    if env::var("CI").is_ok() {
+    if env::var("CI").is_ok() && !opts.test {
         println!("CI environment detected. Skipping QEMU execution.");
         return;
     }
 
-    // 2. Launch QEMU with UEFI Support
+    // 2. Launch QEMU
     let mut cmd = Command::new("qemu-system-x86_64");
-    cmd.arg("-bios").arg("/usr/share/OVMF/OVMF_CODE.fd"); // Required for UEFI images
+    if !opts.bios {
+        cmd.arg("-bios").arg(&opts.ovmf); // Required for UEFI images
+    }
     cmd.arg("-drive").arg(format!("format=raw,file={}", image_path.display()));
-    cmd.arg("-serial").arg("stdio");
 
-    println!("Launching QEMU... If it fails, check for ovmf_code.fd in the root.");
+    if let Some(disk) = &opts.disk {
+        match datadisk::ensure(disk, opts.disk_size_mib) {
+            Ok(true) => println!("Created {} MiB data disk {}", opts.disk_size_mib, disk.display()),
+            Ok(false) => {}
+            Err(e) => {
+                eprintln!("runner: could not create {}: {}", disk.display(), e);
+                std::process::exit(1);
+            }
+        }
+        let format = datadisk::Format::of(disk).qemu_name();
+        cmd.arg("-drive").arg(format!("if=none,id=nyxdisk,format={},file={}", format, disk.display()));
+        match opts.disk_if {
+            DiskInterface::Nvme => { cmd.arg("-device").arg("nvme,serial=nyxdisk,drive=nyxdisk"); }
+            DiskInterface::Ahci => {
+                cmd.arg("-device").arg("ahci,id=ahci");
+                cmd.arg("-device").arg("ide-hd,drive=nyxdisk,bus=ahci.0");
+            }
+        }
+    }
+
+    if opts.kvm {
+        cmd.arg("-enable-kvm").arg("-cpu").arg("host");
+    }
+    if opts.usb {
+        // The xHCI driver binds to qemu-xhci and takes boot-protocol HID devices
+        cmd.arg("-device").arg("qemu-xhci,id=xhci");
+        cmd.arg("-device").arg("usb-kbd,bus=xhci.0");
+        cmd.arg("-device").arg("usb-mouse,bus=xhci.0");
+    }
+
+    if opts.test {
+        // The script talks to COM1 over stdio; the log gets a copy
+        cmd.arg("-serial").arg("stdio");
+        harness::add_test_args(&mut cmd);
+        cmd.args(&opts.qemu_args);
+        println!("Booting headless for the serial test script...");
+        match harness::run(cmd, harness::SCRIPT, harness::TIMEOUT, opts.serial_log.as_deref()) {
+            Ok(()) => println!("TEST PASSED"),
+            Err(e) => {
+                eprintln!("TEST FAILED: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    match &opts.serial_log {
+        Some(log) => { cmd.arg("-serial").arg(format!("file:{}", log.display())); }
+        None => { cmd.arg("-serial").arg("stdio"); }
+    }
+    cmd.args(&opts.qemu_args);
+
+    println!("Launching QEMU... If it fails, check the OVMF path (--ovmf).");
     let mut child = cmd.spawn().expect("Failed to start QEMU");
     child.wait().unwrap();
 }
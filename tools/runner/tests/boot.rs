@@ -0,0 +1,23 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+// Boots a kernel that's already been built (NYX_KERNEL, or the release
+// build's usual path) in QEMU through the runner's --test mode. Needs
+// qemu-system-x86_64, OVMF and an ext4 system disk in NYX_DISK (passed on
+// as --disk), so it only runs when asked for: cargo test -p runner -- --ignored
+
+#[test]
+#[ignore = "boots the kernel in QEMU"]
+fn boots_and_passes_selftest() {
+    let kernel = std::env::var("NYX_KERNEL").map(PathBuf::from).unwrap_or_else(|_| {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../../target/x86_64-unknown-none/release/nyx-kernel")
+    });
+    assert!(kernel.exists(), "no kernel at {}; build it or set NYX_KERNEL", kernel.display());
+
+    let mut runner = Command::new(env!("CARGO_BIN_EXE_runner"));
+    runner.arg(&kernel).arg("--test");
+    if let Ok(disk) = std::env::var("NYX_DISK") { runner.arg("--disk").arg(disk); }
+    let status = runner.status()
+        .expect("could not start the runner");
+    assert!(status.success(), "runner --test failed: {}", status);
+}